@@ -212,7 +212,7 @@ fn create_userdata(c: &mut Criterion) {
 fn call_userdata_index(c: &mut Criterion) {
     struct UserData(i64);
     impl LuaUserData for UserData {
-        fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
             methods.add_meta_method(LuaMetaMethod::Index, move |_, _, index: String| Ok(index));
         }
     }
@@ -239,7 +239,7 @@ fn call_userdata_index(c: &mut Criterion) {
 fn call_userdata_method(c: &mut Criterion) {
     struct UserData(i64);
     impl LuaUserData for UserData {
-        fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
             methods.add_method("method", |_, this, ()| Ok(this.0));
         }
     }
@@ -263,11 +263,42 @@ fn call_userdata_method(c: &mut Criterion) {
     });
 }
 
+fn call_userdata_method_with_fields(c: &mut Criterion) {
+    struct UserData(i64);
+    impl LuaUserData for UserData {
+        fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("field", |_, this| Ok(this.0));
+        }
+
+        fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("method", |_, this, ()| Ok(this.0));
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("userdata", UserData(10)).unwrap();
+
+    c.bench_function("call [userdata method with fields] 10", |b| {
+        b.iter_batched_ref(
+            || {
+                collect_gc_twice(&lua);
+                lua.load("function() for i = 1,10 do userdata:method() end end")
+                    .eval::<LuaFunction>()
+                    .unwrap()
+            },
+            |function| {
+                function.call::<_, ()>(()).unwrap();
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
 fn call_async_userdata_method(c: &mut Criterion) {
     #[derive(Clone, Copy)]
     struct UserData(i64);
     impl LuaUserData for UserData {
-        fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
             methods.add_async_method("method", |_, this, ()| async move { Ok(this.0) });
         }
     }
@@ -312,6 +343,7 @@ criterion_group! {
         create_userdata,
         call_userdata_index,
         call_userdata_method,
+        call_userdata_method_with_fields,
         call_async_userdata_method,
 }
 
@@ -1,8 +1,21 @@
 #![allow(dead_code)]
 
+use std::env;
 use std::path::PathBuf;
 
+// Lua handles errors using `setjmp`/`longjmp`, on WASI they are implemented using the WebAssembly
+// exception handling. `lua-src` enables it in its C compiler configuration and links `libsetjmp`
+// of wasi-sdk. Emscripten supports them out of the box.
+fn check_wasm_target() {
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    if cfg!(feature = "luajit") && target_arch.starts_with("wasm") {
+        panic!("LuaJIT does not support WebAssembly targets");
+    }
+}
+
 pub fn probe_lua() -> Option<PathBuf> {
+    check_wasm_target();
+
     #[cfg(feature = "lua54")]
     let artifacts = lua_src::Build::new().build(lua_src::Lua54);
     #[cfg(feature = "lua53")]
@@ -20,7 +33,7 @@ pub fn probe_lua() -> Option<PathBuf> {
         builder.build()
     };
     #[cfg(feature = "luau")]
-    let artifacts = luau0_src::Build::new().build();
+    let artifacts = luau0_src::Build::new().use_longjmp(true).build();
 
     artifacts.print_cargo_metadata();
 
@@ -108,6 +108,13 @@ fn main() {
     #[cfg(all(feature = "luau", feature = "module"))]
     compile_error!("Luau does not support module mode");
 
+    // Vendored Lua and Luau are always compiled as C (or with `longjmp` error handling)
+    #[cfg(all(
+        feature = "cxx-exceptions",
+        any(feature = "luau", all(feature = "vendored", not(feature = "luajit")))
+    ))]
+    compile_error!("`cxx-exceptions` feature requires Lua (not vendored) compiled as C++");
+
     #[cfg(any(not(feature = "module"), target_os = "windows"))]
     find::probe_lua();
 
@@ -0,0 +1,943 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::string::String as StdString;
+
+use crate::chunk::{Chunk, ChunkMode};
+use crate::error::{Error, Result};
+use crate::table::Table;
+use crate::value::Value;
+
+/// Kind of a [`Diagnostic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// Access to a global variable which is neither defined in the environment of the chunk nor
+    /// assigned by the chunk.
+    UndefinedGlobal,
+    /// A local variable which is never accessed.
+    UnusedVariable,
+}
+
+/// A warning about Lua code, returned by [`Chunk::analyze`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// Kind of the warning.
+    pub kind: DiagnosticKind,
+    /// Name of the variable.
+    pub name: StdString,
+    /// Line of the access (or the declaration for unused variables).
+    pub line: usize,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            DiagnosticKind::UndefinedGlobal => {
+                write!(f, "line {}: undefined global '{}'", self.line, self.name)
+            }
+            DiagnosticKind::UnusedVariable => {
+                write!(f, "line {}: unused variable '{}'", self.line, self.name)
+            }
+        }
+    }
+}
+
+impl Chunk<'_> {
+    /// Analyzes the chunk without executing it, returning warnings about undefined globals and
+    /// unused local variables.
+    ///
+    /// A global is undefined if it is not set in the environment of the chunk (the globals table
+    /// by default) at the time of analysis, and the chunk does not assign it. Local variables
+    /// (including loop variables) are reported if they are never read; function parameters and
+    /// variables with names starting with `_` are not reported.
+    ///
+    /// Returns a syntax error if the chunk cannot be compiled. Binary chunks cannot be analyzed.
+    /// Luau type annotations are not supported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{DiagnosticKind, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let diagnostics = lua
+    ///     .load(
+    ///         r#"
+    ///         local count = 0
+    ///         local unused = 1
+    ///         for i = 1, 10 do
+    ///             count = count + i
+    ///         end
+    ///         prnit(count)
+    ///     "#,
+    ///     )
+    ///     .analyze()?;
+    ///
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert_eq!(diagnostics[0].to_string(), "line 3: unused variable 'unused'");
+    /// assert_eq!(diagnostics[1].kind, DiagnosticKind::UndefinedGlobal);
+    /// assert_eq!(diagnostics[1].name, "prnit");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn analyze(&mut self) -> Result<Vec<Diagnostic>> {
+        if let Some(mut reader) = self.reader.take() {
+            let mut source = Vec::new();
+            self.source = reader.read_to_end(&mut source).map(|_| Cow::Owned(source));
+        }
+        if self.detect_mode() == ChunkMode::Binary {
+            return Err(Error::RuntimeError(
+                "cannot analyze binary chunk".to_string(),
+            ));
+        }
+        let source = match self.source {
+            Ok(ref source) => source.clone(),
+            Err(ref err) => return Err(Error::RuntimeError(err.to_string())),
+        };
+        let env = match self.env {
+            Ok(Value::Nil) => self.lua.globals_storage()?,
+            Ok(Value::Table(ref env)) => env.clone(),
+            Ok(_) => return Err(Error::RuntimeError("invalid environment".to_string())),
+            Err(ref err) => return Err(err.clone()),
+        };
+
+        // Report syntax errors as Lua does
+        let name = Self::convert_name(self.name.clone())?;
+        (self.lua).load_chunk(Some(&name), Value::Nil, Some(ChunkMode::Text), &source)?;
+
+        let tokens = Lexer::new(&source).tokenize()?;
+        let mut analyzer = Analyzer::new(&tokens);
+        analyzer.chunk()?;
+        analyzer.into_diagnostics(&env)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(StdString),
+    String,
+    Number,
+    Op(&'static str),
+    Eof,
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+// Longer operators go first
+const OPERATORS: &[&str] = &[
+    "...", "..=", "//=", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "+=", "-=", "*=",
+    "/=", "%=", "^=", "->", "+", "-", "*", "/", "%", "^", "#", "&", "~", "|", "<", ">", "=", "(",
+    ")", "{", "}", "[", "]", ";", ":", ",", ".", "?",
+];
+
+const BINARY_OPERATORS: &[&str] = &[
+    "+", "-", "*", "/", "//", "%", "^", "..", "==", "~=", "<", "<=", ">", ">=", "&", "|", "~",
+    "<<", ">>",
+];
+
+const COMPOUND_OPERATORS: &[&str] = &["+=", "-=", "*=", "/=", "//=", "%=", "^=", "..="];
+
+fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+fn is_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+    tokens: Vec<(Token, usize)>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a [u8]) -> Self {
+        let mut lexer = Lexer {
+            src,
+            pos: 0,
+            line: 1,
+            tokens: Vec::new(),
+        };
+        // Skip shebang
+        if src.starts_with(b"#") {
+            lexer.skip_line();
+        }
+        lexer
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize)>> {
+        self.lex(false)?;
+        self.tokens.push((Token::Eof, self.line));
+        Ok(self.tokens)
+    }
+
+    fn peek(&self, offset: usize) -> u8 {
+        self.src.get(self.pos + offset).copied().unwrap_or(0)
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::RuntimeError(format!("cannot analyze chunk: {msg} at line {}", self.line))
+    }
+
+    fn skip_line(&mut self) {
+        while self.pos < self.src.len() && self.src[self.pos] != b'\n' {
+            self.pos += 1;
+        }
+    }
+
+    // Lexes tokens until the end of the source or, in an interpolated string, the closing brace
+    fn lex(&mut self, interpolation: bool) -> Result<()> {
+        let mut depth = 0;
+        while self.pos < self.src.len() {
+            let c = self.src[self.pos];
+            match c {
+                b'\n' => {
+                    self.line += 1;
+                    self.pos += 1;
+                }
+                c if c.is_ascii_whitespace() => self.pos += 1,
+                b'-' if self.peek(1) == b'-' => {
+                    self.pos += 2;
+                    match self.long_bracket_level() {
+                        Some(level) => self.skip_long_bracket(level)?,
+                        None => self.skip_line(),
+                    }
+                }
+                b'[' if self.long_bracket_level().is_some() => {
+                    let line = self.line;
+                    self.skip_long_bracket(self.long_bracket_level().unwrap_or(0))?;
+                    self.tokens.push((Token::String, line));
+                }
+                b'"' | b'\'' => {
+                    let line = self.line;
+                    self.skip_string(c)?;
+                    self.tokens.push((Token::String, line));
+                }
+                b'`' => self.lex_interpolated_string()?,
+                c if c.is_ascii_digit() || (c == b'.' && self.peek(1).is_ascii_digit()) => {
+                    self.skip_number();
+                    self.tokens.push((Token::Number, self.line));
+                }
+                c if is_name_char(c) => {
+                    let start = self.pos;
+                    while is_name_char(self.peek(0)) {
+                        self.pos += 1;
+                    }
+                    let name = StdString::from_utf8_lossy(&self.src[start..self.pos]);
+                    self.tokens
+                        .push((Token::Name(name.into_owned()), self.line));
+                }
+                _ => {
+                    let rest = &self.src[self.pos..];
+                    let op = (OPERATORS.iter())
+                        .find(|op| rest.starts_with(op.as_bytes()))
+                        .ok_or_else(|| self.error("unexpected symbol"))?;
+                    if interpolation {
+                        match *op {
+                            "{" => depth += 1,
+                            "}" if depth == 0 => {
+                                self.pos += 1;
+                                return Ok(());
+                            }
+                            "}" => depth -= 1,
+                            _ => {}
+                        }
+                    }
+                    self.pos += op.len();
+                    self.tokens.push((Token::Op(op), self.line));
+                }
+            }
+        }
+        if interpolation {
+            return Err(self.error("unfinished string"));
+        }
+        Ok(())
+    }
+
+    // Returns the level of the long bracket at the current position
+    fn long_bracket_level(&self) -> Option<usize> {
+        if self.peek(0) != b'[' {
+            return None;
+        }
+        let mut level = 0;
+        while self.peek(level + 1) == b'=' {
+            level += 1;
+        }
+        (self.peek(level + 1) == b'[').then_some(level)
+    }
+
+    fn skip_long_bracket(&mut self, level: usize) -> Result<()> {
+        self.pos += level + 2;
+        let mut close = vec![b'='; level + 2];
+        (close[0], close[level + 1]) = (b']', b']');
+        while self.pos < self.src.len() {
+            if self.src[self.pos..].starts_with(&close) {
+                self.pos += close.len();
+                return Ok(());
+            }
+            if self.src[self.pos] == b'\n' {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+        Err(self.error("unfinished long string or comment"))
+    }
+
+    fn skip_string(&mut self, quote: u8) -> Result<()> {
+        self.pos += 1;
+        while self.pos < self.src.len() {
+            match self.src[self.pos] {
+                b'\\' => {
+                    if self.peek(1) == b'\n' {
+                        self.line += 1;
+                    }
+                    self.pos += 2;
+                    continue;
+                }
+                b'\n' => self.line += 1,
+                c if c == quote => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err(self.error("unfinished string"))
+    }
+
+    // Luau interpolated strings are lexed as a table constructor of the interpolated expressions
+    fn lex_interpolated_string(&mut self) -> Result<()> {
+        self.tokens.push((Token::Op("{"), self.line));
+        self.pos += 1;
+        while self.pos < self.src.len() {
+            match self.src[self.pos] {
+                b'\\' => {
+                    if self.peek(1) == b'\n' {
+                        self.line += 1;
+                    }
+                    self.pos += 2;
+                    continue;
+                }
+                b'\n' => self.line += 1,
+                b'{' => {
+                    self.pos += 1;
+                    self.lex(true)?;
+                    self.tokens.push((Token::Op(","), self.line));
+                    continue;
+                }
+                b'`' => {
+                    self.pos += 1;
+                    self.tokens.push((Token::Op("}"), self.line));
+                    return Ok(());
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err(self.error("unfinished string"))
+    }
+
+    fn skip_number(&mut self) {
+        while self.pos < self.src.len() {
+            let c = self.src[self.pos];
+            let exponent = matches!(c, b'e' | b'E' | b'p' | b'P');
+            if c.is_ascii_alphanumeric() || c == b'.' || c == b'_' {
+                self.pos += 1;
+                // Hex numbers use `e` as a digit, but cannot have signed `e` exponents
+                if exponent && matches!(self.peek(0), b'+' | b'-') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+struct Local {
+    name: StdString,
+    line: usize,
+    used: bool,
+    // Parameters are not reported when unused
+    report: bool,
+}
+
+// Walks the tokens of a chunk, resolving variable accesses in scopes
+struct Analyzer<'a> {
+    tokens: &'a [(Token, usize)],
+    pos: usize,
+    scopes: Vec<Vec<Local>>,
+    global_reads: Vec<(StdString, usize)>,
+    global_writes: HashSet<StdString>,
+    unused: Vec<(StdString, usize)>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(tokens: &'a [(Token, usize)]) -> Self {
+        Analyzer {
+            tokens,
+            pos: 0,
+            scopes: Vec::new(),
+            global_reads: Vec::new(),
+            global_writes: HashSet::new(),
+            unused: Vec::new(),
+        }
+    }
+
+    fn into_diagnostics(self, env: &Table) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        let mut defined = HashMap::new();
+        for (name, line) in self.global_reads {
+            if self.global_writes.contains(&name) || name == "_ENV" {
+                continue;
+            }
+            let is_defined = match defined.get(&name) {
+                Some(&is_defined) => is_defined,
+                None => {
+                    let is_defined = env.get::<_, Value>(name.as_str())? != Value::Nil;
+                    defined.insert(name.clone(), is_defined);
+                    is_defined
+                }
+            };
+            if !is_defined {
+                let kind = DiagnosticKind::UndefinedGlobal;
+                diagnostics.push(Diagnostic { kind, name, line });
+            }
+        }
+        for (name, line) in self.unused {
+            let kind = DiagnosticKind::UnusedVariable;
+            diagnostics.push(Diagnostic { kind, name, line });
+        }
+        diagnostics.sort_by_key(|diag| diag.line);
+        Ok(diagnostics)
+    }
+
+    fn peek(&self) -> &Token {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let last = self.tokens.len() - 1;
+        &self.tokens[(self.pos + offset).min(last)].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos.min(self.tokens.len() - 1)].1
+    }
+
+    fn next(&mut self) {
+        self.pos += 1;
+    }
+
+    fn error(&self) -> Error {
+        let token = match self.peek() {
+            Token::Name(name) => format!("'{name}'"),
+            Token::String => "string".to_string(),
+            Token::Number => "number".to_string(),
+            Token::Op(op) => format!("'{op}'"),
+            Token::Eof => "end of chunk".to_string(),
+        };
+        let line = self.line();
+        Error::RuntimeError(format!(
+            "cannot analyze chunk: unexpected {token} at line {line}"
+        ))
+    }
+
+    fn check_op(&self, op: &str) -> bool {
+        matches!(self.peek(), Token::Op(o) if *o == op)
+    }
+
+    fn check_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Name(name) if name == keyword)
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<()> {
+        if !self.check_op(op) {
+            return Err(self.error());
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.check_keyword(keyword) {
+            return Err(self.error());
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn expect_name(&mut self) -> Result<(StdString, usize)> {
+        match self.peek() {
+            Token::Name(name) if !is_keyword(name) => {
+                let name = (name.clone(), self.line());
+                self.next();
+                Ok(name)
+            }
+            _ => Err(self.error()),
+        }
+    }
+
+    fn open_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn close_scope(&mut self) {
+        for local in self.scopes.pop().unwrap_or_default() {
+            if local.report && !local.used && !local.name.starts_with('_') {
+                self.unused.push((local.name, local.line));
+            }
+        }
+    }
+
+    fn declare(&mut self, name: StdString, line: usize, report: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let used = false;
+            scope.push(Local {
+                name,
+                line,
+                used,
+                report,
+            });
+        }
+    }
+
+    fn resolve(&mut self, name: &str) -> Option<&mut Local> {
+        (self.scopes.iter_mut().rev())
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|local| local.name == name)
+    }
+
+    fn read(&mut self, name: StdString, line: usize) {
+        match self.resolve(&name) {
+            Some(local) => local.used = true,
+            None => self.global_reads.push((name, line)),
+        }
+    }
+
+    fn assign(&mut self, name: StdString) {
+        if self.resolve(&name).is_none() {
+            self.global_writes.insert(name);
+        }
+    }
+
+    fn chunk(&mut self) -> Result<()> {
+        self.open_scope();
+        self.block()?;
+        if *self.peek() != Token::Eof {
+            return Err(self.error());
+        }
+        self.close_scope();
+        Ok(())
+    }
+
+    fn block_end(&self) -> bool {
+        match self.peek() {
+            Token::Eof => true,
+            Token::Name(name) => matches!(name.as_str(), "end" | "else" | "elseif" | "until"),
+            _ => false,
+        }
+    }
+
+    fn block(&mut self) -> Result<()> {
+        while !self.block_end() {
+            self.statement()?;
+        }
+        Ok(())
+    }
+
+    fn scoped_block(&mut self) -> Result<()> {
+        self.open_scope();
+        self.block()?;
+        self.close_scope();
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<()> {
+        let keyword = match self.peek() {
+            Token::Op(";") => {
+                self.next();
+                return Ok(());
+            }
+            Token::Op("::") => {
+                self.next();
+                self.expect_name()?;
+                return self.expect_op("::");
+            }
+            Token::Name(name) => name.clone(),
+            _ => return self.expr_statement(),
+        };
+        match keyword.as_str() {
+            "break" => self.next(),
+            // `goto` and `continue` (Luau) are not reserved in all Lua versions
+            "goto" if matches!(self.peek_at(1), Token::Name(_)) => {
+                self.next();
+                self.expect_name()?;
+            }
+            "continue" if !self.is_suffix(self.peek_at(1)) => self.next(),
+            "do" => {
+                self.next();
+                self.scoped_block()?;
+                self.expect_keyword("end")?;
+            }
+            "while" => {
+                self.next();
+                self.expr()?;
+                self.expect_keyword("do")?;
+                self.scoped_block()?;
+                self.expect_keyword("end")?;
+            }
+            "repeat" => {
+                self.next();
+                // The condition can access locals of the block
+                self.open_scope();
+                self.block()?;
+                self.expect_keyword("until")?;
+                self.expr()?;
+                self.close_scope();
+            }
+            "if" => {
+                self.next();
+                self.expr()?;
+                self.expect_keyword("then")?;
+                self.scoped_block()?;
+                loop {
+                    if self.check_keyword("elseif") {
+                        self.next();
+                        self.expr()?;
+                        self.expect_keyword("then")?;
+                        self.scoped_block()?;
+                    } else if self.check_keyword("else") {
+                        self.next();
+                        self.scoped_block()?;
+                    } else {
+                        break;
+                    }
+                }
+                self.expect_keyword("end")?;
+            }
+            "for" => self.for_statement()?,
+            "function" => {
+                self.next();
+                let (name, line) = self.expect_name()?;
+                let (mut plain, mut method) = (true, false);
+                while self.check_op(".") || self.check_op(":") {
+                    method = self.check_op(":");
+                    plain = false;
+                    self.next();
+                    self.expect_name()?;
+                    if method {
+                        break;
+                    }
+                }
+                match plain {
+                    true => self.assign(name),
+                    false => self.read(name, line),
+                }
+                self.function_body(method)?;
+            }
+            "local" => {
+                self.next();
+                if self.check_keyword("function") {
+                    self.next();
+                    let (name, line) = self.expect_name()?;
+                    self.declare(name, line, true);
+                    return self.function_body(false);
+                }
+                let mut names = vec![self.local_name()?];
+                while self.check_op(",") {
+                    self.next();
+                    names.push(self.local_name()?);
+                }
+                if self.check_op("=") {
+                    self.next();
+                    self.expr_list()?;
+                }
+                for (name, line) in names {
+                    self.declare(name, line, true);
+                }
+            }
+            "return" => {
+                self.next();
+                if !self.block_end() && !self.check_op(";") {
+                    self.expr_list()?;
+                }
+                if self.check_op(";") {
+                    self.next();
+                }
+            }
+            _ => self.expr_statement()?,
+        }
+        Ok(())
+    }
+
+    // Returns `true` if the token continues an expression statement
+    fn is_suffix(&self, token: &Token) -> bool {
+        match token {
+            Token::String => true,
+            Token::Op(op) => {
+                matches!(*op, "(" | "{" | "." | "[" | ":" | "=" | ",")
+                    || COMPOUND_OPERATORS.contains(op)
+            }
+            _ => false,
+        }
+    }
+
+    fn local_name(&mut self) -> Result<(StdString, usize)> {
+        let name = self.expect_name()?;
+        // Attribute (`<const>` or `<close>`)
+        if self.check_op("<") {
+            self.next();
+            self.expect_name()?;
+            self.expect_op(">")?;
+        }
+        Ok(name)
+    }
+
+    fn for_statement(&mut self) -> Result<()> {
+        self.next();
+        let mut names = vec![self.expect_name()?];
+        if self.check_op("=") {
+            self.next();
+            self.expr()?;
+            self.expect_op(",")?;
+            self.expr()?;
+            if self.check_op(",") {
+                self.next();
+                self.expr()?;
+            }
+        } else {
+            while self.check_op(",") {
+                self.next();
+                names.push(self.expect_name()?);
+            }
+            self.expect_keyword("in")?;
+            self.expr_list()?;
+        }
+        self.expect_keyword("do")?;
+        self.open_scope();
+        for (name, line) in names {
+            self.declare(name, line, true);
+        }
+        self.block()?;
+        self.close_scope();
+        self.expect_keyword("end")
+    }
+
+    fn expr_statement(&mut self) -> Result<()> {
+        let target = self.suffixed_expr()?;
+        if self.check_op("=") || self.check_op(",") {
+            let mut targets = vec![target];
+            while self.check_op(",") {
+                self.next();
+                targets.push(self.suffixed_expr()?);
+            }
+            self.expect_op("=")?;
+            self.expr_list()?;
+            for (name, _) in targets.into_iter().flatten() {
+                self.assign(name);
+            }
+            return Ok(());
+        }
+        let compound = matches!(self.peek(), Token::Op(op) if COMPOUND_OPERATORS.contains(op));
+        if let Some((name, line)) = target {
+            self.read(name.clone(), line);
+            if compound {
+                self.assign(name);
+            }
+        }
+        if compound {
+            self.next();
+            self.expr()?;
+        }
+        Ok(())
+    }
+
+    fn expr_list(&mut self) -> Result<()> {
+        self.expr()?;
+        while self.check_op(",") {
+            self.next();
+            self.expr()?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<()> {
+        loop {
+            while self.check_op("-") || self.check_op("#") || self.check_op("~") {
+                self.next();
+            }
+            if self.check_keyword("not") {
+                self.next();
+                continue;
+            }
+            self.simple_expr()?;
+            let binary = match self.peek() {
+                Token::Op(op) => BINARY_OPERATORS.contains(op),
+                Token::Name(name) => name == "and" || name == "or",
+                _ => false,
+            };
+            if !binary {
+                return Ok(());
+            }
+            self.next();
+        }
+    }
+
+    fn simple_expr(&mut self) -> Result<()> {
+        match self.peek() {
+            Token::Number | Token::String | Token::Op("...") => self.next(),
+            Token::Op("{") => self.table()?,
+            Token::Name(name) => match name.as_str() {
+                "nil" | "true" | "false" => self.next(),
+                "function" => {
+                    self.next();
+                    self.function_body(false)?;
+                }
+                // Luau if-then-else expression
+                "if" => {
+                    self.next();
+                    self.expr()?;
+                    self.expect_keyword("then")?;
+                    self.expr()?;
+                    while self.check_keyword("elseif") {
+                        self.next();
+                        self.expr()?;
+                        self.expect_keyword("then")?;
+                        self.expr()?;
+                    }
+                    self.expect_keyword("else")?;
+                    self.expr()?;
+                }
+                _ => {
+                    if let Some((name, line)) = self.suffixed_expr()? {
+                        self.read(name, line);
+                    }
+                }
+            },
+            _ => {
+                if let Some((name, line)) = self.suffixed_expr()? {
+                    self.read(name, line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Parses a prefix expression with suffixes, returns the variable name if it is a plain name
+    fn suffixed_expr(&mut self) -> Result<Option<(StdString, usize)>> {
+        let mut plain = None;
+        if self.check_op("(") {
+            self.next();
+            self.expr()?;
+            self.expect_op(")")?;
+        } else {
+            plain = Some(self.expect_name()?);
+        }
+        loop {
+            match self.peek() {
+                Token::Op(".") => {
+                    self.flush(&mut plain);
+                    self.next();
+                    self.expect_name()?;
+                }
+                Token::Op("[") => {
+                    self.flush(&mut plain);
+                    self.next();
+                    self.expr()?;
+                    self.expect_op("]")?;
+                }
+                Token::Op(":") => {
+                    self.flush(&mut plain);
+                    self.next();
+                    self.expect_name()?;
+                    self.args()?;
+                }
+                Token::Op("(") | Token::Op("{") | Token::String => {
+                    self.flush(&mut plain);
+                    self.args()?;
+                }
+                _ => return Ok(plain),
+            }
+        }
+    }
+
+    fn flush(&mut self, plain: &mut Option<(StdString, usize)>) {
+        if let Some((name, line)) = plain.take() {
+            self.read(name, line);
+        }
+    }
+
+    fn args(&mut self) -> Result<()> {
+        match self.peek() {
+            Token::String => self.next(),
+            Token::Op("{") => self.table()?,
+            _ => {
+                self.expect_op("(")?;
+                if !self.check_op(")") {
+                    self.expr_list()?;
+                }
+                self.expect_op(")")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn table(&mut self) -> Result<()> {
+        self.expect_op("{")?;
+        while !self.check_op("}") {
+            if self.check_op("[") {
+                self.next();
+                self.expr()?;
+                self.expect_op("]")?;
+                self.expect_op("=")?;
+            } else if matches!(self.peek(), Token::Name(name) if !is_keyword(name))
+                && *self.peek_at(1) == Token::Op("=")
+            {
+                self.next();
+                self.next();
+            }
+            self.expr()?;
+            if self.check_op(",") || self.check_op(";") {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_op("}")
+    }
+
+    fn function_body(&mut self, method: bool) -> Result<()> {
+        self.expect_op("(")?;
+        self.open_scope();
+        if method {
+            self.declare("self".to_string(), self.line(), false);
+        }
+        while !self.check_op(")") {
+            if self.check_op("...") {
+                self.next();
+            } else {
+                let (name, line) = self.expect_name()?;
+                self.declare(name, line, false);
+            }
+            if !self.check_op(",") {
+                break;
+            }
+            self.next();
+        }
+        self.expect_op(")")?;
+        self.block()?;
+        self.expect_keyword("end")?;
+        self.close_scope();
+        Ok(())
+    }
+}
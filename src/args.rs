@@ -0,0 +1,131 @@
+use std::ops::Deref;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::value::{FromLua, IntoLua, Value};
+
+/// A number argument that must be within the `MIN..=MAX` range.
+///
+/// Converting a value outside of the range fails, so a Rust function receiving a `Ranged`
+/// argument raises a `bad argument` error without additional checks.
+///
+/// Implemented for all primitive integer and float types.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Ranged, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let percent = lua.create_function(|_, value: Ranged<u8, 0, 100>| Ok(format!("{}%", *value)))?;
+/// assert_eq!(percent.call::<_, String>(42)?, "42%");
+///
+/// let err = percent.call::<_, String>(200).unwrap_err();
+/// assert!(err.to_string().contains("bad argument #1"));
+/// assert!(err.to_string().contains("out of range 0..=100"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ranged<T, const MIN: i64, const MAX: i64>(pub T);
+
+impl<T, const MIN: i64, const MAX: i64> Ranged<T, MIN, MAX> {
+    /// Returns the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> Deref for Ranged<T, MIN, MAX> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: IntoLua, const MIN: i64, const MAX: i64> IntoLua for Ranged<T, MIN, MAX> {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        self.0.into_lua(lua)
+    }
+}
+
+macro_rules! ranged_from_lua {
+    ($x:ty, $cmp:ty) => {
+        impl<const MIN: i64, const MAX: i64> FromLua for Ranged<$x, MIN, MAX> {
+            fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+                let ty = value.type_name();
+                let x = <$x>::from_lua(value, lua)?;
+                // NaN is never within the range
+                if !((MIN as $cmp)..=(MAX as $cmp)).contains(&(x as $cmp)) {
+                    return Err(Error::FromLuaConversionError {
+                        from: ty,
+                        to: stringify!($x),
+                        message: Some(format!("value {x} out of range {MIN}..={MAX}")),
+                    });
+                }
+                Ok(Ranged(x))
+            }
+        }
+    };
+}
+
+ranged_from_lua!(i8, i128);
+ranged_from_lua!(u8, i128);
+ranged_from_lua!(i16, i128);
+ranged_from_lua!(u16, i128);
+ranged_from_lua!(i32, i128);
+ranged_from_lua!(u32, i128);
+ranged_from_lua!(i64, i128);
+ranged_from_lua!(u64, i128);
+ranged_from_lua!(i128, i128);
+ranged_from_lua!(isize, i128);
+ranged_from_lua!(usize, i128);
+ranged_from_lua!(f32, f64);
+ranged_from_lua!(f64, f64);
+
+/// A string argument that must not be empty.
+///
+/// Similar to [`Ranged`], converting an empty string fails with a conversion error.
+/// Numbers are coerced to strings as usual.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NonEmptyString(pub StdString);
+
+impl NonEmptyString {
+    /// Returns the inner string.
+    pub fn into_inner(self) -> StdString {
+        self.0
+    }
+}
+
+impl Deref for NonEmptyString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl IntoLua for NonEmptyString {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        self.0.into_lua(lua)
+    }
+}
+
+impl FromLua for NonEmptyString {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        let ty = value.type_name();
+        let s = StdString::from_lua(value, lua)?;
+        if s.is_empty() {
+            return Err(Error::FromLuaConversionError {
+                from: ty,
+                to: "NonEmptyString",
+                message: Some("expected non-empty string".to_string()),
+            });
+        }
+        Ok(NonEmptyString(s))
+    }
+}
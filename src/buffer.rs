@@ -0,0 +1,185 @@
+use std::ops::Range;
+use std::os::raw::c_void;
+use std::{fmt, mem, slice};
+
+#[cfg(feature = "serialize")]
+use {
+    serde::ser::{Serialize, Serializer},
+    std::result::Result as StdResult,
+};
+
+use crate::error::{Error, Result};
+use crate::ffi;
+use crate::types::LuaRef;
+
+/// Handle to a Luau buffer.
+///
+/// A buffer is a fixed-size block of mutable memory, managed by Luau and manipulated from scripts
+/// using the `buffer` library. Buffers are never resized or moved, so their contents can be
+/// accessed from Rust without copying, see [`LuauBuffer::as_slice`].
+///
+/// Typed accessors use little-endian byte order, like the `buffer` library.
+///
+/// Requires `feature = "luau"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let mut buf = lua.create_buffer([0; 8])?;
+/// buf.write_u32(0, 0xdeadbeef)?;
+/// lua.globals().set("buf", buf.clone())?;
+/// lua.load("buffer.writef32(buf, 4, 1.5)").exec()?;
+/// assert_eq!(buf.read_f32(4)?, 1.5);
+/// assert_eq!(&buf.as_slice()[..4], &[0xef, 0xbe, 0xad, 0xde]);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+#[derive(Clone, PartialEq)]
+pub struct LuauBuffer(pub(crate) LuaRef);
+
+macro_rules! buffer_accessors {
+    ($($ty:ty, $read:ident, $write:ident;)*) => {
+        $(
+            #[doc = concat!("Reads a `", stringify!($ty), "` at the given byte offset.")]
+            ///
+            /// Returns an error if the value does not fit into the buffer.
+            pub fn $read(&self, offset: usize) -> Result<$ty> {
+                let bytes = self.read_bytes::<{ mem::size_of::<$ty>() }>(offset)?;
+                Ok(<$ty>::from_le_bytes(bytes))
+            }
+
+            #[doc = concat!("Writes a `", stringify!($ty), "` at the given byte offset.")]
+            ///
+            /// Returns an error if the value does not fit into the buffer.
+            pub fn $write(&mut self, offset: usize, value: $ty) -> Result<()> {
+                self.write_bytes(offset, &value.to_le_bytes())
+            }
+        )*
+    };
+}
+
+impl LuauBuffer {
+    /// Returns the size of the buffer in bytes.
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Returns `true` if the buffer has a size of zero bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the contents of the buffer without copying.
+    ///
+    /// Luau cannot resize buffers, so the slice stays valid for the lifetime of the handle.
+    /// Writes made by Lua code or by other handles to the same buffer are visible through the
+    /// slice, so it should not be held across calls into Lua.
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe {
+            let (data, len) = self.data();
+            slice::from_raw_parts(data as *const u8, len)
+        }
+    }
+
+    /// Returns the contents of the buffer as a mutable slice without copying.
+    ///
+    /// See [`LuauBuffer::as_slice`] for the guarantees of the returned slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe {
+            let (data, len) = self.data();
+            slice::from_raw_parts_mut(data as *mut u8, len)
+        }
+    }
+
+    /// Copies the contents of the buffer into a new `Vec`.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.as_slice().to_vec()
+    }
+
+    /// Reads `N` bytes at the given byte offset.
+    ///
+    /// Returns an error if the range does not fit into the buffer.
+    pub fn read_bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        let bytes = self.range(offset, N)?;
+        let mut data = [0; N];
+        data.copy_from_slice(&self.as_slice()[bytes]);
+        Ok(data)
+    }
+
+    /// Writes the given bytes at the given byte offset.
+    ///
+    /// Returns an error if the bytes do not fit into the buffer.
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let range = self.range(offset, bytes.len())?;
+        self.as_mut_slice()[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    buffer_accessors! {
+        i8, read_i8, write_i8;
+        u8, read_u8, write_u8;
+        i16, read_i16, write_i16;
+        u16, read_u16, write_u16;
+        i32, read_i32, write_i32;
+        u32, read_u32, write_u32;
+        f32, read_f32, write_f32;
+        f64, read_f64, write_f64;
+    }
+
+    /// Converts the buffer to a generic C pointer.
+    ///
+    /// There is no way to convert the pointer back to its original value.
+    ///
+    /// Typically this function is used only for hashing and debug information.
+    #[inline]
+    pub fn to_pointer(&self) -> *const c_void {
+        let ref_thread = self.0.lua.ref_thread();
+        unsafe { ffi::lua_topointer(ref_thread, self.0.index()) }
+    }
+
+    unsafe fn data(&self) -> (*mut c_void, usize) {
+        let ref_thread = self.0.lua.ref_thread();
+        mlua_debug_assert!(
+            ffi::lua_type(ref_thread, self.0.index()) == ffi::LUA_TBUFFER,
+            "buffer ref is not buffer type"
+        );
+        let mut len = 0;
+        let data = ffi::lua_tobuffer(ref_thread, self.0.index(), &mut len);
+        (data, len)
+    }
+
+    fn range(&self, offset: usize, len: usize) -> Result<Range<usize>> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len() => Ok(offset..end),
+            _ => Err(Error::RuntimeError(
+                "buffer access out of bounds".to_string(),
+            )),
+        }
+    }
+}
+
+impl fmt::Debug for LuauBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LuauBuffer({:?})", self.as_slice())
+    }
+}
+
+impl AsRef<[u8]> for LuauBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl Serialize for LuauBuffer {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.as_slice())
+    }
+}
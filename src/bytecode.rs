@@ -0,0 +1,658 @@
+use std::fmt;
+
+use crate::function::Function;
+
+/// A bytecode instruction of a Lua function, returned by [`Function::disassemble`].
+///
+/// Opcodes and operands follow the representation of the Lua backend (eg. `GETTABUP 0 0 1` for
+/// Lua 5.4 or `GGET 0 0` for LuaJIT), as printed by `luac -l` or `luajit -bl`. Operands are
+/// listed in the following order, depending on the format of the instruction:
+/// - `A B C` (and the `k` flag as the 4th operand for Lua 5.4)
+/// - `A Bx` or `A sBx` (signed) for Lua, `A D` for LuaJIT (signed for jumps)
+/// - `Ax` and `sJ` (signed) for Lua
+///
+/// The [`Display`] implementation formats instructions as `pc [line] OPCODE operands`.
+///
+/// [`Display`]: fmt::Display
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Instruction {
+    /// Position of the instruction (starting at 1).
+    pub pc: usize,
+    /// Name of the opcode.
+    pub opcode: &'static str,
+    /// Decoded operands.
+    pub operands: Vec<i64>,
+    /// Source line, if the function has debug information.
+    pub line: Option<usize>,
+    /// Encoded instruction.
+    pub raw: u32,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<4} ", self.pc)?;
+        match self.line {
+            Some(line) => write!(f, "[{line}]\t")?,
+            None => write!(f, "[-]\t")?,
+        }
+        write!(f, "{:<9}", self.opcode)?;
+        for operand in &self.operands {
+            write!(f, " {operand}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Function {
+    /// Disassembles the bytecode of the function.
+    ///
+    /// Returns the instructions of the function itself, nested functions can be disassembled
+    /// separately. Returns an empty vector for C functions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let func: Function = lua.load("return function(t) return t.x + 1 end").eval()?;
+    /// for ins in func.disassemble() {
+    ///     println!("{ins}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn disassemble(&self) -> Vec<Instruction> {
+        let dump = self.dump(false);
+        let proto = match dump.get(..4) {
+            Some(b"\x1bLua") => Reader::new(&dump).and_then(|mut r| r.function()),
+            Some(b"\x1bLJ\x02") => luajit_proto(&dump),
+            _ => None,
+        };
+        let Some(proto) = proto else {
+            return Vec::new();
+        };
+        (proto.code.iter().enumerate())
+            .map(|(i, &raw)| {
+                let (opcode, operands) = proto.decode(raw);
+                Instruction {
+                    pc: i + 1,
+                    opcode,
+                    operands,
+                    line: proto.lines.get(i).copied(),
+                    raw,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Lua51,
+    Lua52,
+    Lua53,
+    Lua54,
+    LuaJit,
+}
+
+// Instructions and their lines of a function prototype
+struct Proto {
+    format: Format,
+    code: Vec<u32>,
+    lines: Vec<usize>,
+}
+
+const LUA51_OPCODES: &[&str] = &[
+    "MOVE",
+    "LOADK",
+    "LOADBOOL",
+    "LOADNIL",
+    "GETUPVAL",
+    "GETGLOBAL",
+    "GETTABLE",
+    "SETGLOBAL",
+    "SETUPVAL",
+    "SETTABLE",
+    "NEWTABLE",
+    "SELF",
+    "ADD",
+    "SUB",
+    "MUL",
+    "DIV",
+    "MOD",
+    "POW",
+    "UNM",
+    "NOT",
+    "LEN",
+    "CONCAT",
+    "JMP",
+    "EQ",
+    "LT",
+    "LE",
+    "TEST",
+    "TESTSET",
+    "CALL",
+    "TAILCALL",
+    "RETURN",
+    "FORLOOP",
+    "FORPREP",
+    "TFORLOOP",
+    "SETLIST",
+    "CLOSE",
+    "CLOSURE",
+    "VARARG",
+];
+
+const LUA52_OPCODES: &[&str] = &[
+    "MOVE", "LOADK", "LOADKX", "LOADBOOL", "LOADNIL", "GETUPVAL", "GETTABUP", "GETTABLE",
+    "SETTABUP", "SETUPVAL", "SETTABLE", "NEWTABLE", "SELF", "ADD", "SUB", "MUL", "DIV", "MOD",
+    "POW", "UNM", "NOT", "LEN", "CONCAT", "JMP", "EQ", "LT", "LE", "TEST", "TESTSET", "CALL",
+    "TAILCALL", "RETURN", "FORLOOP", "FORPREP", "TFORCALL", "TFORLOOP", "SETLIST", "CLOSURE",
+    "VARARG", "EXTRAARG",
+];
+
+const LUA53_OPCODES: &[&str] = &[
+    "MOVE", "LOADK", "LOADKX", "LOADBOOL", "LOADNIL", "GETUPVAL", "GETTABUP", "GETTABLE",
+    "SETTABUP", "SETUPVAL", "SETTABLE", "NEWTABLE", "SELF", "ADD", "SUB", "MUL", "MOD", "POW",
+    "DIV", "IDIV", "BAND", "BOR", "BXOR", "SHL", "SHR", "UNM", "BNOT", "NOT", "LEN", "CONCAT",
+    "JMP", "EQ", "LT", "LE", "TEST", "TESTSET", "CALL", "TAILCALL", "RETURN", "FORLOOP", "FORPREP",
+    "TFORCALL", "TFORLOOP", "SETLIST", "CLOSURE", "VARARG", "EXTRAARG",
+];
+
+const LUA54_OPCODES: &[&str] = &[
+    "MOVE",
+    "LOADI",
+    "LOADF",
+    "LOADK",
+    "LOADKX",
+    "LOADFALSE",
+    "LFALSESKIP",
+    "LOADTRUE",
+    "LOADNIL",
+    "GETUPVAL",
+    "SETUPVAL",
+    "GETTABUP",
+    "GETTABLE",
+    "GETI",
+    "GETFIELD",
+    "SETTABUP",
+    "SETTABLE",
+    "SETI",
+    "SETFIELD",
+    "NEWTABLE",
+    "SELF",
+    "ADDI",
+    "ADDK",
+    "SUBK",
+    "MULK",
+    "MODK",
+    "POWK",
+    "DIVK",
+    "IDIVK",
+    "BANDK",
+    "BORK",
+    "BXORK",
+    "SHRI",
+    "SHLI",
+    "ADD",
+    "SUB",
+    "MUL",
+    "MOD",
+    "POW",
+    "DIV",
+    "IDIV",
+    "BAND",
+    "BOR",
+    "BXOR",
+    "SHL",
+    "SHR",
+    "MMBIN",
+    "MMBINI",
+    "MMBINK",
+    "UNM",
+    "BNOT",
+    "NOT",
+    "LEN",
+    "CONCAT",
+    "CLOSE",
+    "TBC",
+    "JMP",
+    "EQ",
+    "LT",
+    "LE",
+    "EQK",
+    "EQI",
+    "LTI",
+    "LEI",
+    "GTI",
+    "GEI",
+    "TEST",
+    "TESTSET",
+    "CALL",
+    "TAILCALL",
+    "RETURN",
+    "RETURN0",
+    "RETURN1",
+    "FORLOOP",
+    "FORPREP",
+    "TFORPREP",
+    "TFORCALL",
+    "TFORLOOP",
+    "SETLIST",
+    "CLOSURE",
+    "VARARG",
+    "VARARGPREP",
+    "EXTRAARG",
+];
+
+const LUAJIT_OPCODES: &[&str] = &[
+    "ISLT", "ISGE", "ISLE", "ISGT", "ISEQV", "ISNEV", "ISEQS", "ISNES", "ISEQN", "ISNEN", "ISEQP",
+    "ISNEP", "ISTC", "ISFC", "IST", "ISF", "ISTYPE", "ISNUM", "MOV", "NOT", "UNM", "LEN", "ADDVN",
+    "SUBVN", "MULVN", "DIVVN", "MODVN", "ADDNV", "SUBNV", "MULNV", "DIVNV", "MODNV", "ADDVV",
+    "SUBVV", "MULVV", "DIVVV", "MODVV", "POW", "CAT", "KSTR", "KCDATA", "KSHORT", "KNUM", "KPRI",
+    "KNIL", "UGET", "USETV", "USETS", "USETN", "USETP", "UCLO", "FNEW", "TNEW", "TDUP", "GGET",
+    "GSET", "TGETV", "TGETS", "TGETB", "TGETR", "TSETV", "TSETS", "TSETB", "TSETM", "TSETR",
+    "CALLM", "CALL", "CALLMT", "CALLT", "ITERC", "ITERN", "VARG", "ISNEXT", "RETM", "RET", "RET0",
+    "RET1", "FORI", "JFORI", "FORL", "IFORL", "JFORL", "ITERL", "IITERL", "JITERL", "LOOP",
+    "ILOOP", "JLOOP", "JMP", "FUNCF", "IFUNCF", "JFUNCF", "FUNCV", "IFUNCV", "JFUNCV", "FUNCC",
+    "FUNCCW",
+];
+
+// LuaJIT opcodes with the `A B C` format (others have the `A D` format)
+const LUAJIT_ABC_OPCODES: &[&str] = &[
+    "ADDVN", "SUBVN", "MULVN", "DIVVN", "MODVN", "ADDNV", "SUBNV", "MULNV", "DIVNV", "MODNV",
+    "ADDVV", "SUBVV", "MULVV", "DIVVV", "MODVV", "POW", "CAT", "TGETV", "TGETS", "TGETB", "TGETR",
+    "TSETV", "TSETS", "TSETB", "TSETR", "CALLM", "CALL", "ITERC", "ITERN", "VARG",
+];
+
+// LuaJIT opcodes with a jump offset in `D`
+const LUAJIT_JUMP_OPCODES: &[&str] = &[
+    "UCLO", "ISNEXT", "FORI", "JFORI", "FORL", "IFORL", "ITERL", "IITERL", "LOOP", "ILOOP", "JMP",
+];
+
+enum Mode {
+    Abc,
+    Abx,
+    AsBx,
+    Ax,
+    SJ,
+}
+
+impl Proto {
+    fn decode(&self, ins: u32) -> (&'static str, Vec<i64>) {
+        let bits = |pos: u32, size: u32| ((ins >> pos) & ((1 << size) - 1)) as i64;
+        if self.format == Format::LuaJit {
+            let opcode = LUAJIT_OPCODES.get(bits(0, 8) as usize).unwrap_or(&"?");
+            let a = bits(8, 8);
+            let operands = if LUAJIT_ABC_OPCODES.contains(opcode) {
+                vec![a, bits(24, 8), bits(16, 8)]
+            } else if LUAJIT_JUMP_OPCODES.contains(opcode) {
+                vec![a, bits(16, 16) - 0x8000]
+            } else {
+                vec![a, bits(16, 16)]
+            };
+            return (opcode, operands);
+        }
+
+        if self.format == Format::Lua54 {
+            let opcode = LUA54_OPCODES.get(bits(0, 7) as usize).unwrap_or(&"?");
+            let a = bits(7, 8);
+            let operands = match self.mode(opcode) {
+                Mode::Abc => vec![a, bits(16, 8), bits(24, 8), bits(15, 1)],
+                Mode::Abx => vec![a, bits(15, 17)],
+                Mode::AsBx => vec![a, bits(15, 17) - 65535],
+                Mode::Ax => vec![bits(7, 25)],
+                Mode::SJ => vec![bits(7, 25) - 16777215],
+            };
+            return (opcode, operands);
+        }
+
+        let opcodes = match self.format {
+            Format::Lua51 => LUA51_OPCODES,
+            Format::Lua52 => LUA52_OPCODES,
+            _ => LUA53_OPCODES,
+        };
+        let opcode = opcodes.get(bits(0, 6) as usize).unwrap_or(&"?");
+        let a = bits(6, 8);
+        let operands = match self.mode(opcode) {
+            Mode::Abc => vec![a, bits(23, 9), bits(14, 9)],
+            Mode::Abx => vec![a, bits(14, 18)],
+            Mode::AsBx => vec![a, bits(14, 18) - 131071],
+            Mode::Ax | Mode::SJ => vec![bits(6, 26)],
+        };
+        (opcode, operands)
+    }
+
+    fn mode(&self, opcode: &str) -> Mode {
+        match (self.format, opcode) {
+            (_, "LOADK" | "LOADKX" | "GETGLOBAL" | "SETGLOBAL" | "CLOSURE") => Mode::Abx,
+            (_, "EXTRAARG") => Mode::Ax,
+            (Format::Lua54, "LOADI" | "LOADF") => Mode::AsBx,
+            (Format::Lua54, "JMP") => Mode::SJ,
+            (Format::Lua54, "FORLOOP" | "FORPREP" | "TFORPREP" | "TFORLOOP") => Mode::Abx,
+            (_, "JMP" | "FORLOOP" | "FORPREP") => Mode::AsBx,
+            (Format::Lua52 | Format::Lua53, "TFORLOOP") => Mode::AsBx,
+            _ => Mode::Abc,
+        }
+    }
+}
+
+// Reader of functions dumped by PUC Lua
+struct Reader<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+    int_size: usize,
+    size_t_size: usize,
+    integer_size: usize,
+    number_size: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let mut reader = Reader {
+            format: Format::Lua51,
+            data,
+            pos: 0,
+            int_size: 4,
+            size_t_size: 8,
+            integer_size: 8,
+            number_size: 8,
+        };
+        let header = reader.bytes(5)?;
+        reader.format = match header[4] {
+            0x51 => Format::Lua51,
+            0x52 => Format::Lua52,
+            0x53 => Format::Lua53,
+            0x54 => Format::Lua54,
+            _ => return None,
+        };
+        match reader.format {
+            Format::Lua51 | Format::Lua52 => {
+                let sizes = reader.bytes(7)?;
+                (reader.int_size, reader.size_t_size) = (sizes[2] as usize, sizes[3] as usize);
+                reader.number_size = sizes[5] as usize;
+                if reader.format == Format::Lua52 {
+                    reader.bytes(6)?;
+                }
+            }
+            Format::Lua53 => {
+                let sizes = reader.bytes(12)?;
+                (reader.int_size, reader.size_t_size) = (sizes[7] as usize, sizes[8] as usize);
+                reader.integer_size = sizes[10] as usize;
+                reader.number_size = sizes[11] as usize;
+                reader.bytes(reader.integer_size + reader.number_size + 1)?;
+            }
+            _ => {
+                let sizes = reader.bytes(10)?;
+                reader.integer_size = sizes[8] as usize;
+                reader.number_size = sizes[9] as usize;
+                reader.bytes(reader.integer_size + reader.number_size + 1)?;
+            }
+        }
+        Some(reader)
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn uint(&mut self, size: usize) -> Option<u64> {
+        let bytes = self.bytes(size)?;
+        let mut buf = [0; 8];
+        if cfg!(target_endian = "little") {
+            buf[..size.min(8)].copy_from_slice(&bytes[..size.min(8)]);
+            Some(u64::from_le_bytes(buf))
+        } else {
+            buf[8 - size.min(8)..].copy_from_slice(&bytes[size - size.min(8)..]);
+            Some(u64::from_be_bytes(buf))
+        }
+    }
+
+    // Reads a variable-length integer of Lua 5.4
+    fn varint(&mut self) -> Option<usize> {
+        let mut value = 0usize;
+        loop {
+            let b = self.byte()?;
+            value = value.checked_mul(128)? | (b & 0x7f) as usize;
+            if b & 0x80 != 0 {
+                return Some(value);
+            }
+        }
+    }
+
+    fn int(&mut self) -> Option<usize> {
+        match self.format {
+            Format::Lua54 => self.varint(),
+            _ => Some(self.uint(self.int_size)? as usize),
+        }
+    }
+
+    fn skip_string(&mut self) -> Option<()> {
+        let len = match self.format {
+            Format::Lua51 | Format::Lua52 => self.uint(self.size_t_size)? as usize,
+            Format::Lua53 => match self.byte()? {
+                0xff => self.uint(self.size_t_size)? as usize,
+                size => size as usize,
+            }
+            .saturating_sub(1),
+            _ => self.varint()?.saturating_sub(1),
+        };
+        self.bytes(len)?;
+        Some(())
+    }
+
+    fn skip_constants(&mut self) -> Option<()> {
+        for _ in 0..self.int()? {
+            match (self.format, self.byte()?) {
+                (Format::Lua54, 0x01 | 0x11) | (_, 0x00) => {}
+                (Format::Lua54, 0x03) | (Format::Lua53, 0x13) => {
+                    self.bytes(self.integer_size)?;
+                }
+                (Format::Lua54, 0x13) | (_, 0x03) => {
+                    self.bytes(self.number_size)?;
+                }
+                (_, 0x01) => {
+                    self.byte()?;
+                }
+                (_, 0x04 | 0x14) => self.skip_string()?,
+                _ => return None,
+            }
+        }
+        Some(())
+    }
+
+    fn skip_upvalues(&mut self) -> Option<()> {
+        let n = self.int()?;
+        let size = if self.format == Format::Lua54 { 3 } else { 2 };
+        self.bytes(n.checked_mul(size)?)?;
+        Some(())
+    }
+
+    fn skip_protos(&mut self) -> Option<()> {
+        for _ in 0..self.int()? {
+            self.function()?;
+        }
+        Some(())
+    }
+
+    fn skip_debug_names(&mut self) -> Option<()> {
+        // Local variables
+        for _ in 0..self.int()? {
+            self.skip_string()?;
+            self.int()?;
+            self.int()?;
+        }
+        // Upvalue names
+        for _ in 0..self.int()? {
+            self.skip_string()?;
+        }
+        Some(())
+    }
+
+    fn function(&mut self) -> Option<Proto> {
+        if matches!(self.format, Format::Lua51 | Format::Lua53 | Format::Lua54) {
+            self.skip_string()?;
+        }
+        let line_defined = self.int()?;
+        self.int()?;
+        let n = if self.format == Format::Lua51 { 4 } else { 3 };
+        self.bytes(n)?;
+
+        let n = self.int()?;
+        let code = (0..n)
+            .map(|_| Some(self.uint(4)? as u32))
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut lines = Vec::new();
+        match self.format {
+            Format::Lua51 => {
+                self.skip_constants()?;
+                self.skip_protos()?;
+                for _ in 0..self.int()? {
+                    lines.push(self.int()?);
+                }
+            }
+            Format::Lua52 => {
+                self.skip_constants()?;
+                self.skip_protos()?;
+                self.skip_upvalues()?;
+                self.skip_string()?;
+                for _ in 0..self.int()? {
+                    lines.push(self.int()?);
+                }
+            }
+            Format::Lua53 => {
+                self.skip_constants()?;
+                self.skip_upvalues()?;
+                self.skip_protos()?;
+                for _ in 0..self.int()? {
+                    lines.push(self.int()?);
+                }
+            }
+            _ => {
+                self.skip_constants()?;
+                self.skip_upvalues()?;
+                self.skip_protos()?;
+                let deltas = self.int()?;
+                let deltas = self.bytes(deltas)?;
+                let mut abs_lines = Vec::new();
+                for _ in 0..self.int()? {
+                    abs_lines.push((self.varint()?, self.varint()?));
+                }
+                let mut line = line_defined;
+                for (pc, &delta) in deltas.iter().enumerate() {
+                    line = match delta as i8 {
+                        -128 => (abs_lines.iter())
+                            .find(|&&(abs_pc, _)| abs_pc == pc)
+                            .map(|&(_, line)| line)?,
+                        delta => line.checked_add_signed(delta as isize)?,
+                    };
+                    lines.push(line);
+                }
+            }
+        }
+        self.skip_debug_names()?;
+
+        Some(Proto {
+            format: self.format,
+            code,
+            lines,
+        })
+    }
+}
+
+// Reads a ULEB128 number of a LuaJIT dump
+fn read_uleb(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((b & 0x7f) as u64).checked_shl(shift)?;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+// Parses the main function prototype of a LuaJIT dump.
+// Prototypes are dumped in the order of their definition, with the main function being last.
+fn luajit_proto(data: &[u8]) -> Option<Proto> {
+    const FLAG_STRIP: u64 = 0x02;
+
+    let mut pos = 4;
+    let flags = read_uleb(data, &mut pos)?;
+    if flags & FLAG_STRIP == 0 {
+        let len = read_uleb(data, &mut pos)? as usize;
+        pos = pos.checked_add(len)?;
+    }
+
+    let mut last = None;
+    loop {
+        let len = read_uleb(data, &mut pos)? as usize;
+        if len == 0 {
+            break;
+        }
+        let end = pos.checked_add(len)?;
+        last = Some(data.get(pos..end)?);
+        pos = end;
+    }
+    let proto = last?;
+
+    // Skip flags, number of params, frame size and number of upvalues
+    let mut pos = 4;
+    read_uleb(proto, &mut pos)?;
+    read_uleb(proto, &mut pos)?;
+    let sizebc = read_uleb(proto, &mut pos)? as usize;
+    let (mut first_line, mut num_line, mut sizedbg) = (0, 0, 0);
+    if flags & FLAG_STRIP == 0 {
+        sizedbg = read_uleb(proto, &mut pos)?;
+        if sizedbg != 0 {
+            first_line = read_uleb(proto, &mut pos)? as usize;
+            num_line = read_uleb(proto, &mut pos)?;
+        }
+    }
+
+    let code_len = sizebc.checked_mul(4)?;
+    let code = (proto.get(pos..pos.checked_add(code_len)?)?)
+        .chunks_exact(4)
+        .map(|ins| u32::from_ne_bytes([ins[0], ins[1], ins[2], ins[3]]))
+        .collect::<Vec<_>>();
+
+    let mut lines = Vec::new();
+    if sizedbg != 0 {
+        // Line info is stored at the beginning of the debug info, at the end of the prototype
+        let entry_size = match num_line {
+            0..=0xff => 1,
+            0x100..=0xffff => 2,
+            _ => 4,
+        };
+        let start = proto.len().checked_sub(sizedbg as usize)?;
+        let info = proto.get(start..start.checked_add(sizebc.checked_mul(entry_size)?)?)?;
+        for entry in info.chunks_exact(entry_size) {
+            let delta = match entry_size {
+                1 => entry[0] as usize,
+                2 => u16::from_ne_bytes([entry[0], entry[1]]) as usize,
+                _ => u32::from_ne_bytes([entry[0], entry[1], entry[2], entry[3]]) as usize,
+            };
+            lines.push(first_line + delta);
+        }
+    }
+
+    Some(Proto {
+        format: Format::LuaJit,
+        code,
+        lines,
+    })
+}
@@ -0,0 +1,386 @@
+#[cfg(not(feature = "send"))]
+use std::cell::{RefCell, RefMut};
+use std::collections::VecDeque;
+use std::mem;
+#[cfg(not(feature = "send"))]
+use std::rc::Rc;
+#[cfg(feature = "send")]
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use futures_util::future;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::userdata::{UserData, UserDataMethods};
+use crate::value::{MultiValue, Value};
+
+/// The sending half of a channel created by [`Lua::create_channel`].
+///
+/// Senders can be cloned and passed to Lua, where they provide the following methods:
+/// - `sender:send(value)`: sends a value, waiting (asynchronously) while the channel is full
+/// - `sender:try_send(value)`: sends a value if the channel is not full, returns `false` otherwise
+/// - `sender:close()`: closes the channel
+/// - `sender:is_closed()`: returns `true` if the channel is closed
+///
+/// Requires `feature = "async"`
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct ChannelSender(SharedChannel);
+
+/// The receiving half of a channel created by [`Lua::create_channel`].
+///
+/// Receivers can be cloned and passed to Lua, where they provide the following methods:
+/// - `receiver:recv()`: receives a value, waiting (asynchronously) while the channel is empty.
+///   Returns `nil` if the channel is empty and closed.
+/// - `receiver:try_recv()`: receives a value if the channel is not empty, returns `nil` otherwise
+/// - `receiver:len()`: returns the number of values in the channel
+///
+/// Requires `feature = "async"`
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct ChannelReceiver(SharedChannel);
+
+// Channels are shared across threads only with `feature = "send"`
+#[cfg(feature = "send")]
+type SharedChannel = Arc<Mutex<Channel>>;
+#[cfg(not(feature = "send"))]
+type SharedChannel = Rc<RefCell<Channel>>;
+
+struct Channel {
+    queue: VecDeque<QueuedValue>,
+    capacity: Option<usize>,
+    senders: usize,
+    receivers: usize,
+    closed: bool,
+    // Tasks waiting for a value
+    recv_wakers: Vec<Waker>,
+    // Tasks waiting for free capacity
+    send_wakers: Vec<Waker>,
+}
+
+// A value waiting in the channel.
+//
+// With `feature = "send"` every `Value` variant is `Send` except `LightUserData`, which holds an
+// opaque pointer that is never dereferenced by mlua, so moving it across threads is sound.
+struct QueuedValue(Value);
+
+#[cfg(feature = "send")]
+unsafe impl Send for QueuedValue {}
+
+impl Lua {
+    /// Creates a channel for passing Lua values between coroutines and Rust async code.
+    ///
+    /// If `capacity` is `Some`, the channel is bounded and senders wait while it holds
+    /// `capacity` values (at least 1), otherwise the channel is unbounded.
+    ///
+    /// The channel is closed when [`ChannelSender::close`] is called or when all senders (or all
+    /// receivers) are dropped. Receivers can still receive the values sent before closing.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mlua::{FromLua, Function, Lua, Result};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let (tx, rx) = lua.create_channel(Some(1));
+    ///
+    ///     let producer: Function = lua
+    ///         .load("function(tx) for i = 1, 3 do tx:send(i * 10) end tx:close() end")
+    ///         .eval()?;
+    ///     let producer = producer.call_async::<_, ()>(tx);
+    ///     let consumer = async {
+    ///         let mut values = Vec::new();
+    ///         while let Some(value) = rx.recv().await {
+    ///             values.push(i64::from_lua(value, &lua)?);
+    ///         }
+    ///         Ok::<_, mlua::Error>(values)
+    ///     };
+    ///     let (result, values) = futures_util::future::join(producer, consumer).await;
+    ///     result?;
+    ///     assert_eq!(values?, [10, 20, 30]);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_channel(&self, capacity: Option<usize>) -> (ChannelSender, ChannelReceiver) {
+        let channel = Channel {
+            queue: VecDeque::new(),
+            capacity: capacity.map(|cap| cap.max(1)),
+            senders: 1,
+            receivers: 1,
+            closed: false,
+            recv_wakers: Vec::new(),
+            send_wakers: Vec::new(),
+        };
+        #[cfg(feature = "send")]
+        let channel = Arc::new(Mutex::new(channel));
+        #[cfg(not(feature = "send"))]
+        let channel = Rc::new(RefCell::new(channel));
+        (ChannelSender(channel.clone()), ChannelReceiver(channel))
+    }
+
+    /// Loads the `channel` module, which creates channels from Lua.
+    ///
+    /// The module is registered in `package.loaded` and returned, it is available to Lua
+    /// scripts using `require("channel")`. It has the following functions:
+    /// - `channel.bounded(capacity)`: creates a bounded channel, returns a sender and a receiver
+    /// - `channel.unbounded()`: creates an unbounded channel, returns a sender and a receiver
+    ///
+    /// Refer to [`Lua::create_channel`] for more details.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mlua::{Lua, Result};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     lua.load_channel_module()?;
+    ///
+    ///     let sum: i64 = lua
+    ///         .load(
+    ///             r#"
+    ///             local channel = require("channel")
+    ///             local tx, rx = channel.unbounded()
+    ///             local producer = coroutine.wrap(function()
+    ///                 for i = 1, 3 do tx:send(i) end
+    ///                 tx:close()
+    ///             end)
+    ///             producer()
+    ///             local sum = 0
+    ///             for value in function() return rx:recv() end do
+    ///                 sum = sum + value
+    ///             end
+    ///             return sum
+    ///         "#,
+    ///         )
+    ///         .eval_async()
+    ///         .await?;
+    ///     assert_eq!(sum, 6);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn load_channel_module(&self) -> Result<Table> {
+        let loader = self.create_function(|lua, _: MultiValue| {
+            let module = lua.create_table_with_capacity(0, 2)?;
+            let bounded = |lua: &Lua, capacity: usize| Ok(lua.create_channel(Some(capacity)));
+            module.raw_set("bounded", lua.create_function(bounded)?)?;
+            let unbounded = |lua: &Lua, ()| Ok(lua.create_channel(None));
+            module.raw_set("unbounded", lua.create_function(unbounded)?)?;
+            Ok(module)
+        })?;
+        self.load_from_function("channel", loader)
+    }
+}
+
+impl Channel {
+    // Locks the channel, ignoring poisoning as the channel is always left in a consistent state
+    #[cfg(feature = "send")]
+    fn lock(channel: &Mutex<Channel>) -> MutexGuard<'_, Channel> {
+        channel.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // The borrow is always released before waking tasks or dropping values
+    #[cfg(not(feature = "send"))]
+    fn lock(channel: &RefCell<Channel>) -> RefMut<'_, Channel> {
+        channel.borrow_mut()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed || self.senders == 0 || self.receivers == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|cap| self.queue.len() >= cap)
+    }
+}
+
+// Wakes the tasks after the channel lock is released
+fn wake_all(wakers: Vec<Waker>) {
+    wakers.into_iter().for_each(Waker::wake);
+}
+
+fn closed_error() -> Error {
+    Error::RuntimeError("channel is closed".to_string())
+}
+
+impl ChannelSender {
+    /// Sends a value, waiting while the channel is full.
+    ///
+    /// Returns an error if the channel is closed.
+    pub async fn send(&self, value: Value) -> Result<()> {
+        let mut value = Some(value);
+        future::poll_fn(|cx| self.poll_send(cx, &mut value)).await
+    }
+
+    /// Sends a value if the channel is not full.
+    ///
+    /// Returns `false` (dropping the value) if the channel is full, or an error if the channel
+    /// is closed.
+    pub fn try_send(&self, value: Value) -> Result<bool> {
+        let mut channel = Channel::lock(&self.0);
+        if channel.is_closed() {
+            return Err(closed_error());
+        }
+        if channel.is_full() {
+            return Ok(false);
+        }
+        channel.queue.push_back(QueuedValue(value));
+        let wakers = mem::take(&mut channel.recv_wakers);
+        drop(channel);
+        wake_all(wakers);
+        Ok(true)
+    }
+
+    /// Closes the channel.
+    ///
+    /// Pending and future sends fail, receivers get the values sent before closing.
+    pub fn close(&self) {
+        let mut channel = Channel::lock(&self.0);
+        channel.closed = true;
+        let mut wakers = mem::take(&mut channel.recv_wakers);
+        wakers.append(&mut channel.send_wakers);
+        drop(channel);
+        wake_all(wakers);
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        Channel::lock(&self.0).is_closed()
+    }
+
+    fn poll_send(&self, cx: &mut Context, value: &mut Option<Value>) -> Poll<Result<()>> {
+        let mut channel = Channel::lock(&self.0);
+        if channel.is_closed() {
+            return Poll::Ready(Err(closed_error()));
+        }
+        if channel.is_full() {
+            channel.send_wakers.push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        if let Some(value) = value.take() {
+            channel.queue.push_back(QueuedValue(value));
+        }
+        let wakers = mem::take(&mut channel.recv_wakers);
+        drop(channel);
+        wake_all(wakers);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl ChannelReceiver {
+    /// Receives a value, waiting while the channel is empty.
+    ///
+    /// Returns `None` if the channel is empty and closed.
+    pub async fn recv(&self) -> Option<Value> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Receives a value if the channel is not empty.
+    pub fn try_recv(&self) -> Option<Value> {
+        let mut channel = Channel::lock(&self.0);
+        let QueuedValue(value) = channel.queue.pop_front()?;
+        let wakers = mem::take(&mut channel.send_wakers);
+        drop(channel);
+        wake_all(wakers);
+        Some(value)
+    }
+
+    /// Returns the number of values in the channel.
+    pub fn len(&self) -> usize {
+        Channel::lock(&self.0).queue.len()
+    }
+
+    /// Returns `true` if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        Channel::lock(&self.0).queue.is_empty()
+    }
+
+    fn poll_recv(&self, cx: &mut Context) -> Poll<Option<Value>> {
+        let mut channel = Channel::lock(&self.0);
+        match channel.queue.pop_front() {
+            Some(QueuedValue(value)) => {
+                let wakers = mem::take(&mut channel.send_wakers);
+                drop(channel);
+                wake_all(wakers);
+                Poll::Ready(Some(value))
+            }
+            None if channel.closed || channel.senders == 0 => Poll::Ready(None),
+            None => {
+                channel.recv_wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Clone for ChannelSender {
+    fn clone(&self) -> Self {
+        Channel::lock(&self.0).senders += 1;
+        ChannelSender(self.0.clone())
+    }
+}
+
+impl Clone for ChannelReceiver {
+    fn clone(&self) -> Self {
+        Channel::lock(&self.0).receivers += 1;
+        ChannelReceiver(self.0.clone())
+    }
+}
+
+impl Drop for ChannelSender {
+    fn drop(&mut self) {
+        let mut channel = Channel::lock(&self.0);
+        channel.senders -= 1;
+        if channel.senders == 0 {
+            let wakers = mem::take(&mut channel.recv_wakers);
+            drop(channel);
+            wake_all(wakers);
+        }
+    }
+}
+
+impl Drop for ChannelReceiver {
+    fn drop(&mut self) {
+        let mut channel = Channel::lock(&self.0);
+        channel.receivers -= 1;
+        if channel.receivers == 0 {
+            // Values are dropped outside of the lock
+            let queue = mem::take(&mut channel.queue);
+            let wakers = mem::take(&mut channel.send_wakers);
+            drop(channel);
+            drop(queue);
+            wake_all(wakers);
+        }
+    }
+}
+
+impl UserData for ChannelSender {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("send", |_, this, value: Value| async move {
+            this.send(value).await
+        });
+        methods.add_method("try_send", |_, this, value: Value| this.try_send(value));
+        methods.add_method("close", |_, this, ()| {
+            this.close();
+            Ok(())
+        });
+        methods.add_method("is_closed", |_, this, ()| Ok(this.is_closed()));
+    }
+}
+
+impl UserData for ChannelReceiver {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("recv", |_, this, ()| async move { Ok(this.recv().await) });
+        methods.add_method("try_recv", |_, this, ()| Ok(this.try_recv()));
+        methods.add_method("len", |_, this, ()| Ok(this.len()));
+    }
+}
@@ -1,7 +1,8 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ffi::CString;
-use std::io::Result as IoResult;
+use std::io::{Read, Result as IoResult};
+use std::panic::Location;
 use std::path::{Path, PathBuf};
 use std::string::String as StdString;
 
@@ -9,6 +10,7 @@ use crate::error::{Error, Result};
 use crate::ffi;
 use crate::function::Function;
 use crate::lua::Lua;
+use crate::types::MaybeSend;
 use crate::value::{FromLuaMulti, IntoLua, IntoLuaMulti, Value};
 
 #[cfg(feature = "async")]
@@ -97,6 +99,30 @@ impl AsChunk<'static> for PathBuf {
     }
 }
 
+/// Maps lines of a generated Lua chunk back to the original source.
+///
+/// Used by [`Chunk::set_source_map`] to rewrite locations in error messages and tracebacks when
+/// Lua code is generated or transpiled from another language.
+///
+/// The trait is implemented for closures `Fn(usize) -> Option<(String, usize)>`.
+///
+/// [`Chunk::set_source_map`]: crate::Chunk::set_source_map
+pub trait SourceMap: MaybeSend + 'static {
+    /// Returns the original source name and line number for the given line of the chunk.
+    ///
+    /// Returning `None` leaves the location unchanged.
+    fn map_line(&self, line: usize) -> Option<(StdString, usize)>;
+}
+
+impl<F> SourceMap for F
+where
+    F: Fn(usize) -> Option<(StdString, usize)> + MaybeSend + 'static,
+{
+    fn map_line(&self, line: usize) -> Option<(StdString, usize)> {
+        self(line)
+    }
+}
+
 /// Returned from [`Lua::load`] and is used to finalize loading and executing Lua main chunks.
 ///
 /// [`Lua::load`]: crate::Lua::load
@@ -107,8 +133,10 @@ pub struct Chunk<'a> {
     pub(crate) env: Result<Value>,
     pub(crate) mode: Option<ChunkMode>,
     pub(crate) source: IoResult<Cow<'a, [u8]>>,
+    pub(crate) reader: Option<Box<dyn Read + 'a>>,
     #[cfg(feature = "luau")]
     pub(crate) compiler: Option<Compiler>,
+    pub(crate) source_map: Option<Box<dyn SourceMap>>,
 }
 
 /// Represents chunk mode (text or binary).
@@ -238,10 +266,13 @@ impl Compiler {
             let options = ffi::lua_CompileOptions {
                 optimizationLevel: self.optimization_level as c_int,
                 debugLevel: self.debug_level as c_int,
+                typeInfoLevel: 0,
                 coverageLevel: self.coverage_level as c_int,
                 vectorLib: vector_lib.map_or(ptr::null(), |s| s.as_ptr()),
                 vectorCtor: vector_ctor.map_or(ptr::null(), |s| s.as_ptr()),
+                vectorType: ptr::null(),
                 mutableGlobals: mutable_globals_ptr,
+                userdataTypes: ptr::null(),
             };
             ffi::luau_compile(source.as_ref(), options)
         }
@@ -255,6 +286,35 @@ impl<'a> Chunk<'a> {
         self
     }
 
+    /// Sets the name of this chunk to the location of the Rust caller.
+    ///
+    /// Unlike the default name assigned by [`Lua::load`] (which is shown by Lua as
+    /// `[string "src/main.rs:10:5"]`), the location is used as a file name, so errors and
+    /// tracebacks look like `src/main.rs:10:5:3: message` where the last number is the line
+    /// within the chunk.
+    ///
+    /// This is useful when the chunk is loaded through a helper function, as the location of
+    /// this method call is used rather than the location of [`Lua::load`] call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let err = lua.load("error('boom')").set_name_from_caller().exec().unwrap_err();
+    /// assert!(err.to_string().contains(&format!("{}:", file!())));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::load`]: crate::Lua::load
+    #[track_caller]
+    pub fn set_name_from_caller(mut self) -> Self {
+        self.name = format!("={}", Location::caller());
+        self
+    }
+
     /// Sets the first upvalue (`_ENV`) of the loaded chunk to the given value.
     ///
     /// Lua main chunks always have exactly one upvalue, and this upvalue is used as the `_ENV`
@@ -292,6 +352,38 @@ impl<'a> Chunk<'a> {
         self
     }
 
+    /// Sets a source map used to translate locations of this chunk in errors.
+    ///
+    /// Runtime and syntax error messages, as well as tracebacks, which refer to lines of this
+    /// chunk are rewritten to the original source name and line returned by the `map`.
+    ///
+    /// The source map is registered in the Lua state when the chunk is loaded and applies to all
+    /// chunks sharing the same name. It stays registered until it is replaced by loading another
+    /// chunk with the same name and a source map, or removed using [`Lua::remove_source_map`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let err = lua
+    ///     .load("local x = 1\nerror('boom')")
+    ///     .set_name("=generated")
+    ///     .set_source_map(|line| Some(("template.txt".to_string(), line * 10)))
+    ///     .exec()
+    ///     .unwrap_err();
+    /// assert!(err.to_string().contains("template.txt:20: boom"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::remove_source_map`]: crate::Lua::remove_source_map
+    pub fn set_source_map(mut self, map: impl SourceMap) -> Self {
+        self.source_map = Some(Box::new(map));
+        self
+    }
+
     /// Execute this chunk of code.
     ///
     /// This is equivalent to calling the chunk function with no arguments and no return values.
@@ -309,7 +401,7 @@ impl<'a> Chunk<'a> {
     /// [`exec`]: #method.exec
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
-    pub fn exec_async(self) -> LocalBoxFuture<'lua, Result<()>> {
+    pub fn exec_async(self) -> LocalBoxFuture<'static, Result<()>> {
         self.call_async(())
     }
 
@@ -318,15 +410,17 @@ impl<'a> Chunk<'a> {
     /// If the chunk can be parsed as an expression, this loads and executes the chunk and returns
     /// the value that it evaluates to. Otherwise, the chunk is interpreted as a block as normal,
     /// and this is equivalent to calling `exec`.
-    pub fn eval<R: FromLuaMulti>(self) -> Result<R> {
+    pub fn eval<R: FromLuaMulti>(mut self) -> Result<R> {
+        self.register_source_map();
+
         // Bytecode is always interpreted as a statement.
         // For source code, first try interpreting the lua as an expression by adding
         // "return", then as a statement. This is the same thing the
         // actual lua repl does.
-        if self.detect_mode() == ChunkMode::Binary {
+        if self.detect_mode() == ChunkMode::Binary || self.reader.is_some() {
             self.call(())
         } else if let Ok(function) = self.to_expression() {
-            function.call(())
+            self.lua.measure_chunk(&self.name, || function.call(()))
         } else {
             self.call(())
         }
@@ -341,12 +435,13 @@ impl<'a> Chunk<'a> {
     /// [`eval`]: #method.eval
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
-    pub fn eval_async<'fut, R>(self) -> LocalBoxFuture<'fut, Result<R>>
+    pub fn eval_async<'fut, R>(mut self) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         R: FromLuaMulti + 'fut,
     {
-        if self.detect_mode() == ChunkMode::Binary {
+        self.register_source_map();
+
+        if self.detect_mode() == ChunkMode::Binary || self.reader.is_some() {
             self.call_async(())
         } else if let Ok(function) = self.to_expression() {
             function.call_async(())
@@ -359,7 +454,9 @@ impl<'a> Chunk<'a> {
     ///
     /// This is equivalent to `into_function` and calling the resulting function.
     pub fn call<A: IntoLuaMulti, R: FromLuaMulti>(self, args: A) -> Result<R> {
-        self.into_function()?.call(args)
+        let (lua, name) = (self.lua.clone(), self.name.clone());
+        let func = self.into_function()?;
+        lua.measure_chunk(&name, || func.call(args))
     }
 
     /// Load the chunk function and asynchronously call it with the given arguments.
@@ -373,7 +470,6 @@ impl<'a> Chunk<'a> {
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub fn call_async<'fut, A, R>(self, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
@@ -386,8 +482,28 @@ impl<'a> Chunk<'a> {
     /// Load this chunk into a regular `Function`.
     ///
     /// This simply compiles the chunk without actually executing it.
-    #[cfg_attr(not(feature = "luau"), allow(unused_mut))]
     pub fn into_function(mut self) -> Result<Function> {
+        self.register_source_map();
+
+        if let Some(mut reader) = self.reader.take() {
+            #[cfg(not(feature = "luau"))]
+            {
+                let name = Self::convert_name(self.name)?;
+                return (self.lua).load_chunk_reader(
+                    Some(&name),
+                    self.env?,
+                    self.mode,
+                    &mut reader,
+                );
+            }
+            // Luau compiles the whole source at once
+            #[cfg(feature = "luau")]
+            {
+                let mut source = Vec::new();
+                self.source = reader.read_to_end(&mut source).map(|_| Cow::Owned(source));
+            }
+        }
+
         #[cfg(feature = "luau")]
         if self.compiler.is_some() {
             // We don't need to compile source if no compiler set
@@ -467,6 +583,12 @@ impl<'a> Chunk<'a> {
         self
     }
 
+    fn register_source_map(&mut self) {
+        if let Some(map) = self.source_map.take() {
+            self.lua.set_source_map(short_source(&self.name), map);
+        }
+    }
+
     fn to_expression(&self) -> Result<Function> {
         // We assume that mode is Text
         let source = self.source.as_ref();
@@ -485,7 +607,7 @@ impl<'a> Chunk<'a> {
             .load_chunk(Some(&name), self.env.clone()?, None, &source)
     }
 
-    fn detect_mode(&self) -> ChunkMode {
+    pub(crate) fn detect_mode(&self) -> ChunkMode {
         match (self.mode, &self.source) {
             (Some(mode), _) => mode,
             (None, Ok(source)) => {
@@ -503,7 +625,7 @@ impl<'a> Chunk<'a> {
         }
     }
 
-    fn convert_name(name: String) -> Result<CString> {
+    pub(crate) fn convert_name(name: String) -> Result<CString> {
         CString::new(name).map_err(|err| Error::RuntimeError(format!("invalid name: {err}")))
     }
 
@@ -514,3 +636,65 @@ impl<'a> Chunk<'a> {
         buf
     }
 }
+
+// Returns chunk name in the form used by Lua in error messages (see `luaO_chunkid`)
+pub(crate) fn short_source(name: &str) -> StdString {
+    #[cfg(not(feature = "luau"))]
+    const LUA_IDSIZE: usize = 60;
+    #[cfg(feature = "luau")]
+    const LUA_IDSIZE: usize = 256;
+
+    if let Some(name) = name.strip_prefix('=').or_else(|| name.strip_prefix('@')) {
+        return name.chars().take(LUA_IDSIZE - 1).collect();
+    }
+
+    // Available length for the source text in `[string "..."]`
+    let max_len = LUA_IDSIZE - r#"[string "..."]"#.len() - 1;
+    match name.find('\n') {
+        None if name.len() < max_len => format!(r#"[string "{name}"]"#),
+        nl => {
+            let mut len = nl.unwrap_or(name.len()).min(max_len);
+            while !name.is_char_boundary(len) {
+                len -= 1;
+            }
+            format!(r#"[string "{}..."]"#, &name[..len])
+        }
+    }
+}
+
+// Replaces `short_src:line:` locations (and `<short_src:line>` in tracebacks) in the message with
+// the original ones
+pub(crate) fn rewrite_source_locations(
+    message: &str,
+    short_src: &str,
+    map: &dyn SourceMap,
+) -> Option<StdString> {
+    let mut result = StdString::with_capacity(message.len());
+    let mut last = 0;
+    for (pos, _) in message.match_indices(short_src) {
+        // The location must be a separate token, eg. `data.lua:1:` does not match `a.lua`
+        let prev = message[..pos].chars().next_back();
+        if prev.is_some_and(|c| !c.is_whitespace() && c != '<') {
+            continue;
+        }
+        let line_str = match message[pos + short_src.len()..].strip_prefix(':') {
+            Some(s) => &s[..s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len())],
+            None => continue,
+        };
+        let end = pos + short_src.len() + 1 + line_str.len();
+        let terminator = if prev == Some('<') { '>' } else { ':' };
+        if !message[end..].starts_with(terminator) {
+            continue;
+        }
+        if let Some((source, line)) = line_str.parse().ok().and_then(|line| map.map_line(line)) {
+            result.push_str(&message[last..pos]);
+            result.push_str(&format!("{source}:{line}"));
+            last = end;
+        }
+    }
+    if last == 0 {
+        return None;
+    }
+    result.push_str(&message[last..]);
+    Some(result)
+}
@@ -0,0 +1,526 @@
+use std::os::raw::{c_int, c_void};
+use std::{slice, str};
+
+use rustc_hash::FxHashSet;
+
+use crate::error::{Error, Result};
+use crate::ffi;
+use crate::lua::Lua;
+use crate::types::{Integer, Number};
+use crate::util::{check_stack, StackGuard};
+use crate::value::{Nil, Value};
+
+/// Binary format used by [`Lua::encode`] and [`Lua::decode`].
+///
+/// Requires `feature = "codec"`
+#[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// [MessagePack](https://msgpack.org) format.
+    MessagePack,
+    /// [CBOR](https://cbor.io) format (RFC 8949).
+    Cbor,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::MessagePack => "MessagePack",
+            Encoding::Cbor => "CBOR",
+        }
+    }
+}
+
+// Maximum nesting level of tables
+const MAX_DEPTH: usize = 128;
+
+impl Lua {
+    /// Encodes a Lua value into a binary format.
+    ///
+    /// Supported values are `nil`, booleans, numbers, strings and tables consisting of them.
+    /// Tables with keys `1..n` are encoded as arrays, other tables as maps. Strings that are not
+    /// valid UTF-8 are encoded as binary data.
+    ///
+    /// Values are read directly from the Lua stack without intermediate conversion.
+    ///
+    /// Requires `feature = "codec"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Encoding, Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let value = lua.load("{name = 'mlua', tags = {'a', 'b'}}").eval()?;
+    /// let bytes = lua.encode(&value, Encoding::MessagePack)?;
+    /// let table: Table = lua.unpack(lua.decode(&bytes, Encoding::MessagePack)?)?;
+    /// assert_eq!(table.get::<_, String>("name")?, "mlua");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn encode(&self, value: &Value, encoding: Encoding) -> Result<Vec<u8>> {
+        let state = self.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            self.push_value(value.clone())?;
+            let mut encoder = Encoder {
+                buf: Vec::new(),
+                encoding,
+                visited: FxHashSet::default(),
+            };
+            encoder.encode_value(state, ffi::lua_absindex(state, -1), 0)?;
+            Ok(encoder.buf)
+        }
+    }
+
+    /// Decodes a Lua value from a binary format.
+    ///
+    /// Arrays and maps are decoded to tables, binary data to strings. Unsigned integers
+    /// that do not fit into [`Integer`] are decoded to [`Number`].
+    ///
+    /// Requires `feature = "codec"`
+    ///
+    /// [`Integer`]: crate::Integer
+    /// [`Number`]: crate::Number
+    #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+    pub fn decode(&self, bytes: &[u8], encoding: Encoding) -> Result<Value> {
+        let mut decoder = Decoder {
+            lua: self,
+            input: bytes,
+            pos: 0,
+            encoding,
+        };
+        let value = decoder.decode_value(0)?;
+        if decoder.pos != bytes.len() {
+            return Err(decoder.error("trailing bytes after value"));
+        }
+        Ok(value)
+    }
+}
+
+struct Encoder {
+    buf: Vec<u8>,
+    encoding: Encoding,
+    visited: FxHashSet<*const c_void>,
+}
+
+impl Encoder {
+    // Encodes value at the absolute stack index `idx`
+    unsafe fn encode_value(
+        &mut self,
+        state: *mut ffi::lua_State,
+        idx: c_int,
+        depth: usize,
+    ) -> Result<()> {
+        match ffi::lua_type(state, idx) {
+            ffi::LUA_TNIL => self.write_nil(),
+            ffi::LUA_TBOOLEAN => self.write_bool(ffi::lua_toboolean(state, idx) != 0),
+            ffi::LUA_TNUMBER =>
+            {
+                #[allow(clippy::useless_conversion)]
+                if ffi::lua_isinteger(state, idx) != 0 {
+                    self.write_integer(ffi::lua_tointeger(state, idx).into());
+                } else {
+                    self.write_float(ffi::lua_tonumber(state, idx));
+                }
+            }
+            ffi::LUA_TSTRING => {
+                let mut size = 0;
+                let data = ffi::lua_tolstring(state, idx, &mut size);
+                let bytes = slice::from_raw_parts(data as *const u8, size);
+                match str::from_utf8(bytes) {
+                    Ok(_) => self.write_header(Kind::Str, bytes.len())?,
+                    Err(_) => self.write_header(Kind::Bin, bytes.len())?,
+                }
+                self.buf.extend_from_slice(bytes);
+            }
+            ffi::LUA_TTABLE => self.encode_table(state, idx, depth)?,
+            // `null` marker used by serde integration
+            ffi::LUA_TLIGHTUSERDATA if ffi::lua_touserdata(state, idx).is_null() => {
+                self.write_nil()
+            }
+            t => {
+                return Err(Error::FromLuaConversionError {
+                    from: type_name(t),
+                    to: self.encoding.name(),
+                    message: Some("unsupported value type".to_string()),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn encode_table(
+        &mut self,
+        state: *mut ffi::lua_State,
+        idx: c_int,
+        depth: usize,
+    ) -> Result<()> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error("table nesting is too deep"));
+        }
+        let ptr = ffi::lua_topointer(state, idx);
+        if !self.visited.insert(ptr) {
+            return Err(self.error("recursive table detected"));
+        }
+        check_stack(state, 3)?;
+
+        // Count entries and check if the table is a sequence `1..len`.
+        // `lua_next` cannot fail here as keys are produced by the traversal itself.
+        let len = ffi::lua_rawlen(state, idx);
+        let mut count = 0;
+        let mut is_array = len > 0;
+        ffi::lua_pushnil(state);
+        while ffi::lua_next(state, idx) != 0 {
+            count += 1;
+            if is_array {
+                is_array = ffi::lua_type(state, -2) == ffi::LUA_TNUMBER
+                    && ffi::lua_isinteger(state, -2) != 0
+                    && matches!(ffi::lua_tointeger(state, -2), i if i >= 1 && i as usize <= len);
+            }
+            ffi::lua_pop(state, 1);
+        }
+
+        if is_array && count == len {
+            self.write_header(Kind::Array, len)?;
+            for i in 1..=len {
+                ffi::lua_rawgeti(state, idx, i as Integer);
+                self.encode_value(state, ffi::lua_absindex(state, -1), depth + 1)?;
+                ffi::lua_pop(state, 1);
+            }
+        } else {
+            self.write_header(Kind::Map, count)?;
+            ffi::lua_pushnil(state);
+            while ffi::lua_next(state, idx) != 0 {
+                let top = ffi::lua_gettop(state);
+                self.encode_value(state, top - 1, depth + 1)?;
+                self.encode_value(state, top, depth + 1)?;
+                ffi::lua_pop(state, 1);
+            }
+        }
+
+        self.visited.remove(&ptr);
+        Ok(())
+    }
+
+    fn write_nil(&mut self) {
+        match self.encoding {
+            Encoding::MessagePack => self.buf.push(0xc0),
+            Encoding::Cbor => self.buf.push(0xf6),
+        }
+    }
+
+    fn write_bool(&mut self, b: bool) {
+        match self.encoding {
+            Encoding::MessagePack => self.buf.push(if b { 0xc3 } else { 0xc2 }),
+            Encoding::Cbor => self.buf.push(if b { 0xf5 } else { 0xf4 }),
+        }
+    }
+
+    fn write_integer(&mut self, i: i64) {
+        match self.encoding {
+            Encoding::MessagePack => match i {
+                0..=0x7f => self.buf.push(i as u8),
+                -32..=-1 => self.buf.push(i as u8),
+                0x80..=0xff => self.buf.extend_from_slice(&[0xcc, i as u8]),
+                0x100..=0xffff => self.write_tagged(0xcd, &(i as u16).to_be_bytes()),
+                0x10000..=0xffff_ffff => self.write_tagged(0xce, &(i as u32).to_be_bytes()),
+                0x1_0000_0000.. => self.write_tagged(0xcf, &(i as u64).to_be_bytes()),
+                -0x80..=-33 => self.buf.extend_from_slice(&[0xd0, i as u8]),
+                -0x8000..=-0x81 => self.write_tagged(0xd1, &(i as i16).to_be_bytes()),
+                -0x8000_0000..=-0x8001 => self.write_tagged(0xd2, &(i as i32).to_be_bytes()),
+                _ => self.write_tagged(0xd3, &i.to_be_bytes()),
+            },
+            Encoding::Cbor if i >= 0 => self.write_cbor_head(0, i as u64),
+            Encoding::Cbor => self.write_cbor_head(1, !i as u64),
+        }
+    }
+
+    fn write_float(&mut self, n: f64) {
+        match self.encoding {
+            Encoding::MessagePack => self.write_tagged(0xcb, &n.to_be_bytes()),
+            Encoding::Cbor => self.write_tagged(0xfb, &n.to_be_bytes()),
+        }
+    }
+
+    fn write_header(&mut self, kind: Kind, len: usize) -> Result<()> {
+        match self.encoding {
+            Encoding::MessagePack => {
+                let (fix, fix_max, tags) = match kind {
+                    Kind::Str => (0xa0, 31, [0xd9, 0xda, 0xdb]),
+                    Kind::Bin => (0, 0, [0xc4, 0xc5, 0xc6]),
+                    Kind::Array => (0x90, 15, [0, 0xdc, 0xdd]),
+                    Kind::Map => (0x80, 15, [0, 0xde, 0xdf]),
+                };
+                match len {
+                    _ if fix != 0 && len <= fix_max => self.buf.push(fix | len as u8),
+                    _ if tags[0] != 0 && len <= 0xff => {
+                        self.buf.extend_from_slice(&[tags[0], len as u8])
+                    }
+                    0..=0xffff => self.write_tagged(tags[1], &(len as u16).to_be_bytes()),
+                    _ if len <= 0xffff_ffff => {
+                        self.write_tagged(tags[2], &(len as u32).to_be_bytes())
+                    }
+                    _ => return Err(self.error("value is too large")),
+                }
+            }
+            Encoding::Cbor => {
+                let major = match kind {
+                    Kind::Bin => 2,
+                    Kind::Str => 3,
+                    Kind::Array => 4,
+                    Kind::Map => 5,
+                };
+                self.write_cbor_head(major, len as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_cbor_head(&mut self, major: u8, n: u64) {
+        let major = major << 5;
+        match n {
+            0..=23 => self.buf.push(major | n as u8),
+            24..=0xff => self.buf.extend_from_slice(&[major | 24, n as u8]),
+            0x100..=0xffff => self.write_tagged(major | 25, &(n as u16).to_be_bytes()),
+            0x10000..=0xffff_ffff => self.write_tagged(major | 26, &(n as u32).to_be_bytes()),
+            _ => self.write_tagged(major | 27, &n.to_be_bytes()),
+        }
+    }
+
+    fn write_tagged(&mut self, tag: u8, bytes: &[u8]) {
+        self.buf.push(tag);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::FromLuaConversionError {
+            from: "table",
+            to: self.encoding.name(),
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Str,
+    Bin,
+    Array,
+    Map,
+}
+
+struct Decoder<'a> {
+    lua: &'a Lua,
+    input: &'a [u8],
+    pos: usize,
+    encoding: Encoding,
+}
+
+impl<'a> Decoder<'a> {
+    fn decode_value(&mut self, depth: usize) -> Result<Value> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error("nesting is too deep"));
+        }
+        match self.encoding {
+            Encoding::MessagePack => self.decode_msgpack(depth),
+            Encoding::Cbor => self.decode_cbor(depth),
+        }
+    }
+
+    fn decode_msgpack(&mut self, depth: usize) -> Result<Value> {
+        let b = self.read_u8()?;
+        let value = match b {
+            0x00..=0x7f => Value::Integer(b.into()),
+            0x80..=0x8f => self.decode_map((b & 0x0f) as usize, depth)?,
+            0x90..=0x9f => self.decode_array((b & 0x0f) as usize, depth)?,
+            0xa0..=0xbf => self.decode_string((b & 0x1f) as usize)?,
+            0xc0 => Nil,
+            0xc2 => Value::Boolean(false),
+            0xc3 => Value::Boolean(true),
+            0xc4 | 0xd9 => {
+                let len = self.read_u8()? as usize;
+                self.decode_string(len)?
+            }
+            0xc5 | 0xda => {
+                let len = self.read_uint(2)? as usize;
+                self.decode_string(len)?
+            }
+            0xc6 | 0xdb => {
+                let len = self.read_uint(4)? as usize;
+                self.decode_string(len)?
+            }
+            0xca => Value::Number(f32::from_bits(self.read_uint(4)? as u32).into()),
+            0xcb => Value::Number(f64::from_bits(self.read_uint(8)?)),
+            0xcc..=0xcf => unsigned_value(self.read_uint(1 << (b - 0xcc))?),
+            0xd0 => integer_value(self.read_uint(1)? as u8 as i8 as i64),
+            0xd1 => integer_value(self.read_uint(2)? as u16 as i16 as i64),
+            0xd2 => integer_value(self.read_uint(4)? as u32 as i32 as i64),
+            0xd3 => integer_value(self.read_uint(8)? as i64),
+            0xdc => {
+                let len = self.read_uint(2)? as usize;
+                self.decode_array(len, depth)?
+            }
+            0xdd => {
+                let len = self.read_uint(4)? as usize;
+                self.decode_array(len, depth)?
+            }
+            0xde => {
+                let len = self.read_uint(2)? as usize;
+                self.decode_map(len, depth)?
+            }
+            0xdf => {
+                let len = self.read_uint(4)? as usize;
+                self.decode_map(len, depth)?
+            }
+            0xe0..=0xff => Value::Integer((b as i8).into()),
+            _ => return Err(self.error(&format!("unsupported type 0x{b:02x}"))),
+        };
+        Ok(value)
+    }
+
+    fn decode_cbor(&mut self, depth: usize) -> Result<Value> {
+        let b = self.read_u8()?;
+        let (major, info) = (b >> 5, b & 0x1f);
+        if major == 7 {
+            return match info {
+                20 => Ok(Value::Boolean(false)),
+                21 => Ok(Value::Boolean(true)),
+                22 | 23 => Ok(Nil),
+                25 => Ok(Value::Number(f16_to_f64(self.read_uint(2)? as u16))),
+                26 => Ok(Value::Number(
+                    f32::from_bits(self.read_uint(4)? as u32).into(),
+                )),
+                27 => Ok(Value::Number(f64::from_bits(self.read_uint(8)?))),
+                _ => Err(self.error(&format!("unsupported simple value {info}"))),
+            };
+        }
+
+        let n = match info {
+            0..=23 => info as u64,
+            24 => self.read_uint(1)?,
+            25 => self.read_uint(2)?,
+            26 => self.read_uint(4)?,
+            27 => self.read_uint(8)?,
+            31 => return Err(self.error("indefinite length items are not supported")),
+            _ => return Err(self.error(&format!("invalid additional info {info}"))),
+        };
+        match major {
+            0 => Ok(unsigned_value(n)),
+            1 if n <= i64::MAX as u64 => Ok(integer_value(!(n as i64))),
+            1 => Ok(Value::Number(-1.0 - n as Number)),
+            2 | 3 => self.decode_string(self.to_len(n)?),
+            4 => self.decode_array(self.to_len(n)?, depth),
+            5 => self.decode_map(self.to_len(n)?, depth),
+            // Tags are ignored, the tagged item is decoded as is
+            _ => self.decode_value(depth + 1),
+        }
+    }
+
+    fn decode_string(&mut self, len: usize) -> Result<Value> {
+        let bytes = self.read_bytes(len)?;
+        Ok(Value::String(self.lua.create_string(bytes)?))
+    }
+
+    fn decode_array(&mut self, len: usize, depth: usize) -> Result<Value> {
+        // Each item takes at least one byte, do not trust the declared length blindly
+        let capacity = len.min(self.input.len() - self.pos);
+        let table = self.lua.create_table_with_capacity(capacity as c_int, 0)?;
+        for i in 1..=len {
+            let value = self.decode_value(depth + 1)?;
+            table.raw_set(i as Integer, value)?;
+        }
+        Ok(Value::Table(table))
+    }
+
+    fn decode_map(&mut self, len: usize, depth: usize) -> Result<Value> {
+        let capacity = len.min((self.input.len() - self.pos) / 2);
+        let table = self.lua.create_table_with_capacity(0, capacity as c_int)?;
+        for _ in 0..len {
+            let key = self.decode_value(depth + 1)?;
+            let value = self.decode_value(depth + 1)?;
+            table.raw_set(key, value)?;
+        }
+        Ok(Value::Table(table))
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_uint(&mut self, size: usize) -> Result<u64> {
+        let bytes = self.read_bytes(size)?;
+        Ok(bytes.iter().fold(0, |n, &b| (n << 8) | b as u64))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        match self.pos.checked_add(len) {
+            Some(end) if end <= self.input.len() => {
+                let input = self.input;
+                let bytes = &input[self.pos..end];
+                self.pos = end;
+                Ok(bytes)
+            }
+            _ => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn to_len(&self, n: u64) -> Result<usize> {
+        usize::try_from(n).map_err(|_| self.error("length is too large"))
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::ToLuaConversionError {
+            from: self.encoding.name(),
+            to: "value",
+            message: Some(format!("{message} (at byte {})", self.pos)),
+        }
+    }
+}
+
+// Integers that do not fit into `Integer` are converted to `Number`
+fn integer_value(i: i64) -> Value {
+    match Integer::try_from(i) {
+        Ok(i) => Value::Integer(i),
+        Err(_) => Value::Number(i as Number),
+    }
+}
+
+fn unsigned_value(n: u64) -> Value {
+    match Integer::try_from(n) {
+        Ok(i) => Value::Integer(i),
+        Err(_) => Value::Number(n as Number),
+    }
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = (half & 0x3ff) as f64;
+    let value = match exp {
+        0 => mant * 2f64.powi(-24),
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mant + 1024.0) * 2f64.powi(exp as i32 - 25),
+    };
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+fn type_name(t: c_int) -> &'static str {
+    match t {
+        ffi::LUA_TLIGHTUSERDATA => "lightuserdata",
+        ffi::LUA_TFUNCTION => "function",
+        ffi::LUA_TUSERDATA => "userdata",
+        ffi::LUA_TTHREAD => "thread",
+        #[cfg(feature = "luau")]
+        ffi::LUA_TBUFFER => "buffer",
+        _ => "unknown",
+    }
+}
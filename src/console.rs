@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::repl::{ReplOutput, ReplState};
+use crate::table::Table;
+
+/// A remote console for inspecting a live Lua state over a TCP or Unix socket.
+///
+/// Created by [`Lua::serve_debug_console`]. Clients (eg. `nc` or `telnet`) send lines of Lua
+/// code, which are evaluated in REPL mode (see [`Lua::repl_eval`]) and the results are written
+/// back followed by a prompt (`> `, or `>> ` for incomplete input). Errors are written as
+/// `error: <message>`.
+///
+/// Connections are accepted in background, the input is evaluated on the thread calling
+/// [`DebugConsole::poll`] (eg. once per frame in a game loop). The server stops when the console
+/// is dropped.
+///
+/// Requires `feature = "console"`
+///
+/// # Examples
+///
+/// ```no_run
+/// # use mlua::{Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let mut console = lua
+///     .serve_debug_console("127.0.0.1:7000")?
+///     .with_auth(|token| token == "secret");
+///
+/// loop {
+///     console.poll();
+///     lua.load("game.update()").exec()?;
+/// #   break;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "console")))]
+pub struct DebugConsole {
+    lua: Lua,
+    env: Option<Table>,
+    auth: Option<AuthCallback>,
+    requests: Receiver<Request>,
+    sessions: HashMap<u64, Session>,
+    local_addr: Option<SocketAddr>,
+    shutdown: Arc<AtomicBool>,
+    #[cfg(unix)]
+    socket_path: Option<PathBuf>,
+}
+
+type AuthCallback = Box<dyn FnMut(&str) -> bool>;
+
+struct Request {
+    session: u64,
+    kind: RequestKind,
+    reply: Sender<Reply>,
+}
+
+enum RequestKind {
+    Connect,
+    Line(StdString),
+    Disconnect,
+}
+
+struct Reply {
+    text: StdString,
+    close: bool,
+}
+
+#[derive(Default)]
+struct Session {
+    repl: ReplState,
+    authenticated: bool,
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+// How often the background thread checks for new connections and shutdown
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(20);
+
+impl Lua {
+    /// Starts a [`DebugConsole`] server listening on `addr`.
+    ///
+    /// `addr` is either a TCP socket address (eg. `"127.0.0.1:7000"`) or, on Unix, a path of a
+    /// Unix socket prefixed with `unix:` (eg. `"unix:/tmp/game.sock"`).
+    ///
+    /// The console is not authenticated by default, anyone who can connect can run arbitrary
+    /// code. Use [`DebugConsole::with_auth`] to require a token.
+    ///
+    /// Requires `feature = "console"`
+    #[cfg_attr(docsrs, doc(cfg(feature = "console")))]
+    pub fn serve_debug_console(&self, addr: &str) -> Result<DebugConsole> {
+        let (listener, local_addr) = match addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => (Listener::Unix(UnixListener::bind(path)?), None),
+            #[cfg(not(unix))]
+            Some(_) => {
+                return Err(Error::RuntimeError(
+                    "unix sockets are not supported on this platform".to_string(),
+                ))
+            }
+            None => {
+                let listener = TcpListener::bind(addr)?;
+                let local_addr = listener.local_addr()?;
+                (Listener::Tcp(listener), Some(local_addr))
+            }
+        };
+
+        let (tx, requests) = mpsc::channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let shutdown2 = shutdown.clone();
+        thread::Builder::new()
+            .name("mlua-console".to_string())
+            .spawn(move || accept_connections(listener, tx, shutdown2))
+            .map_err(Error::external)?;
+
+        Ok(DebugConsole {
+            lua: self.clone(),
+            env: None,
+            auth: None,
+            requests,
+            sessions: HashMap::new(),
+            local_addr,
+            shutdown,
+            #[cfg(unix)]
+            socket_path: addr.strip_prefix("unix:").map(PathBuf::from),
+        })
+    }
+}
+
+impl DebugConsole {
+    /// Sets the environment table in which the input is evaluated (globals by default).
+    #[must_use]
+    pub fn with_environment(mut self, env: Table) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Sets a callback checking the token sent by clients as the first line.
+    ///
+    /// Clients are disconnected if the callback returns `false`.
+    #[must_use]
+    pub fn with_auth<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str) -> bool + 'static,
+    {
+        self.auth = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the address the server is listening on, or `None` for Unix sockets.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Returns the number of connected clients.
+    pub fn clients(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Evaluates the input received since the last call and returns the number of evaluated
+    /// lines.
+    ///
+    /// Does not block.
+    pub fn poll(&mut self) -> usize {
+        let mut evaluated = 0;
+        while let Ok(request) = self.requests.try_recv() {
+            let session = self.sessions.get(&request.session);
+            if matches!(request.kind, RequestKind::Line(_))
+                && session.is_some_and(|s| s.authenticated)
+            {
+                evaluated += 1;
+            }
+            if let Some(reply) = self.handle(request.session, request.kind) {
+                if reply.close {
+                    self.sessions.remove(&request.session);
+                }
+                let _ = request.reply.send(reply);
+            }
+        }
+        evaluated
+    }
+
+    fn handle(&mut self, session: u64, kind: RequestKind) -> Option<Reply> {
+        match kind {
+            RequestKind::Connect => {
+                let session = self.sessions.entry(session).or_default();
+                session.authenticated = self.auth.is_none();
+                let prompt = if session.authenticated {
+                    "> "
+                } else {
+                    "token: "
+                };
+                Some(Reply::new(prompt, false))
+            }
+            RequestKind::Line(line) => {
+                let session = self.sessions.entry(session).or_default();
+                if !session.authenticated {
+                    let auth = self.auth.as_mut().is_none_or(|auth| auth(line.trim()));
+                    session.authenticated = auth;
+                    return Some(match auth {
+                        true => Reply::new("> ", false),
+                        false => Reply::new("error: unauthorized\n", true),
+                    });
+                }
+
+                let env = self.env.as_ref();
+                let result = (self.lua).repl_eval_in(&line, &mut session.repl, env, "=console");
+                let mut text = match result {
+                    Ok(ReplOutput::Complete { output, .. }) if !output.is_empty() => output + "\n",
+                    Ok(_) => StdString::new(),
+                    Err(err) => format!("error: {err}\n"),
+                };
+                text.push_str(if session.repl.is_continuation() {
+                    ">> "
+                } else {
+                    "> "
+                });
+                Some(Reply::new(text, false))
+            }
+            RequestKind::Disconnect => {
+                self.sessions.remove(&session);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for DebugConsole {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        #[cfg(unix)]
+        if let Some(path) = &self.socket_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl Reply {
+    fn new(text: impl Into<StdString>, close: bool) -> Self {
+        let text = text.into();
+        Reply { text, close }
+    }
+}
+
+fn accept_connections(listener: Listener, requests: Sender<Request>, shutdown: Arc<AtomicBool>) {
+    let nonblocking = match &listener {
+        Listener::Tcp(listener) => listener.set_nonblocking(true),
+        #[cfg(unix)]
+        Listener::Unix(listener) => listener.set_nonblocking(true),
+    };
+    if nonblocking.is_err() {
+        return;
+    }
+
+    let mut next_session = 0;
+    while !shutdown.load(Ordering::Relaxed) {
+        let session = next_session;
+        let requests = requests.clone();
+        let result = match &listener {
+            Listener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                let reader = stream.try_clone()?;
+                spawn_session(session, reader, stream, requests)
+            }),
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                let reader = stream.try_clone()?;
+                spawn_session(session, reader, stream, requests)
+            }),
+        };
+        match result {
+            Ok(()) => next_session += 1,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+            Err(_) => {}
+        }
+    }
+}
+
+fn spawn_session<R, W>(
+    session: u64,
+    reader: R,
+    writer: W,
+    requests: Sender<Request>,
+) -> io::Result<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    thread::Builder::new()
+        .name("mlua-console-session".to_string())
+        .spawn(move || {
+            let _ = serve_session(session, BufReader::new(reader), writer, &requests);
+            let (reply, _) = mpsc::channel();
+            let kind = RequestKind::Disconnect;
+            let _ = requests.send(Request {
+                session,
+                kind,
+                reply,
+            });
+        })?;
+    Ok(())
+}
+
+// Forwards lines from the client to the console and writes back the replies
+fn serve_session(
+    session: u64,
+    reader: impl BufRead,
+    mut writer: impl Write,
+    requests: &Sender<Request>,
+) -> io::Result<()> {
+    let (reply_tx, replies) = mpsc::channel();
+    let mut send = |kind| -> io::Result<bool> {
+        let reply = reply_tx.clone();
+        let disconnected = || io::Error::from(io::ErrorKind::BrokenPipe);
+        (requests.send(Request {
+            session,
+            kind,
+            reply,
+        }))
+        .map_err(|_| disconnected())?;
+        let reply: Reply = replies.recv().map_err(|_| disconnected())?;
+        writer.write_all(reply.text.as_bytes())?;
+        writer.flush()?;
+        Ok(!reply.close)
+    };
+
+    if !send(RequestKind::Connect)? {
+        return Ok(());
+    }
+    for line in reader.lines() {
+        if !send(RequestKind::Line(line?))? {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -1,29 +1,32 @@
+use std::any::{type_name, TypeId};
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 use std::ffi::{CStr, CString};
 use std::hash::{BuildHasher, Hash};
+use std::os::raw::c_void;
 use std::string::String as StdString;
 
 use bstr::{BStr, BString};
 use num_traits::cast;
 
+#[cfg(feature = "indexmap")]
+use indexmap::IndexMap;
+
+#[cfg(feature = "luau")]
+use crate::buffer::LuauBuffer;
 use crate::error::{Error, Result};
-use crate::function::Function;
+use crate::function::{Function, OwnedFunction};
 use crate::lua::Lua;
-use crate::string::String;
-use crate::table::Table;
-use crate::thread::Thread;
-use crate::types::{LightUserData, MaybeSend};
-use crate::userdata::{AnyUserData, UserData, UserDataRef, UserDataRefMut};
+use crate::string::{OwnedString, String};
+use crate::table::{OwnedTable, Table};
+use crate::thread::{OwnedThread, Thread};
+use crate::types::{LightUserData, MaybeSend, Tagged};
+use crate::userdata::{AnyUserData, OwnedAnyUserData, UserData, UserDataRef, UserDataRefMut};
 use crate::value::{FromLua, IntoLua, Nil, Value};
 
 #[cfg(feature = "unstable")]
-use crate::{
-    function::{OwnedFunction, WrappedFunction},
-    table::OwnedTable,
-    userdata::OwnedAnyUserData,
-};
+use crate::function::WrappedFunction;
 
 #[cfg(all(feature = "async", feature = "unstable"))]
 use crate::function::WrappedAsyncFunction;
@@ -62,6 +65,20 @@ impl FromLua for String {
     }
 }
 
+impl IntoLua for OwnedString {
+    #[inline]
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::String(self.0))
+    }
+}
+
+impl FromLua for OwnedString {
+    #[inline]
+    fn from_lua(value: Value, lua: &Lua) -> Result<OwnedString> {
+        String::from_lua(value, lua).map(|s| s.into_owned())
+    }
+}
+
 impl IntoLua for Table {
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
@@ -83,15 +100,13 @@ impl FromLua for Table {
     }
 }
 
-#[cfg(feature = "unstable")]
 impl IntoLua for OwnedTable {
     #[inline]
-    fn into_lua(self, lua: &Lua) -> Result<Value> {
-        Ok(Value::Table(Table(lua.adopt_owned_ref(self.0))))
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::Table(self.0))
     }
 }
 
-#[cfg(feature = "unstable")]
 impl FromLua for OwnedTable {
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<OwnedTable> {
@@ -99,6 +114,29 @@ impl FromLua for OwnedTable {
     }
 }
 
+#[cfg(feature = "luau")]
+impl IntoLua for LuauBuffer {
+    #[inline]
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::Buffer(self))
+    }
+}
+
+#[cfg(feature = "luau")]
+impl FromLua for LuauBuffer {
+    #[inline]
+    fn from_lua(value: Value, _: &Lua) -> Result<LuauBuffer> {
+        match value {
+            Value::Buffer(buf) => Ok(buf),
+            _ => Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "buffer",
+                message: None,
+            }),
+        }
+    }
+}
+
 impl IntoLua for Function {
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
@@ -120,15 +158,13 @@ impl FromLua for Function {
     }
 }
 
-#[cfg(feature = "unstable")]
 impl IntoLua for OwnedFunction {
     #[inline]
-    fn into_lua(self, lua: &Lua) -> Result<Value> {
-        Ok(Value::Function(Function(lua.adopt_owned_ref(self.0))))
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::Function(self.0))
     }
 }
 
-#[cfg(feature = "unstable")]
 impl FromLua for OwnedFunction {
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<OwnedFunction> {
@@ -173,6 +209,20 @@ impl FromLua for Thread {
     }
 }
 
+impl IntoLua for OwnedThread {
+    #[inline]
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::Thread(self.0))
+    }
+}
+
+impl FromLua for OwnedThread {
+    #[inline]
+    fn from_lua(value: Value, lua: &Lua) -> Result<OwnedThread> {
+        Thread::from_lua(value, lua).map(|s| s.into_owned())
+    }
+}
+
 impl IntoLua for AnyUserData {
     #[inline]
     fn into_lua(self, _: &Lua) -> Result<Value> {
@@ -194,15 +244,13 @@ impl FromLua for AnyUserData {
     }
 }
 
-#[cfg(feature = "unstable")]
 impl IntoLua for OwnedAnyUserData {
     #[inline]
-    fn into_lua(self, lua: &Lua) -> Result<Value> {
-        Ok(Value::UserData(AnyUserData(lua.adopt_owned_ref(self.0))))
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::UserData(self.0))
     }
 }
 
-#[cfg(feature = "unstable")]
 impl FromLua for OwnedAnyUserData {
     #[inline]
     fn from_lua(value: Value, lua: &Lua) -> Result<OwnedAnyUserData> {
@@ -291,6 +339,45 @@ impl FromLua for LightUserData {
     }
 }
 
+impl<T: 'static> IntoLua for Tagged<T> {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        let ptr = self.0 as *mut c_void;
+        match lua.light_userdata_tag(ptr) {
+            Some(tag) if tag != TypeId::of::<T>() => {
+                return Err(Error::ToLuaConversionError {
+                    from: "Tagged",
+                    to: "light userdata",
+                    message: Some("pointer is tagged as another type".to_string()),
+                })
+            }
+            Some(_) => {}
+            None => lua.set_light_userdata_tag(ptr, TypeId::of::<T>())?,
+        }
+        Ok(Value::LightUserData(LightUserData(ptr)))
+    }
+}
+
+impl<T: 'static> FromLua for Tagged<T> {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        match value {
+            Value::LightUserData(ud) if lua.light_userdata_tag(ud.0) == Some(TypeId::of::<T>()) => {
+                Ok(Tagged(ud.0 as *mut T))
+            }
+            Value::LightUserData(_) => Err(Error::FromLuaConversionError {
+                from: "light userdata",
+                to: "Tagged",
+                message: Some(format!("pointer is not tagged as `{}`", type_name::<T>())),
+            }),
+            _ => Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "light userdata",
+                message: None,
+            }),
+        }
+    }
+}
+
 impl IntoLua for StdString {
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
@@ -653,6 +740,30 @@ impl<'lua, K: Eq + Hash + FromLua, V: FromLua, S: BuildHasher + Default> FromLua
     }
 }
 
+#[cfg(feature = "indexmap")]
+impl<K: Eq + Hash + IntoLua, V: IntoLua, S: BuildHasher> IntoLua for IndexMap<K, V, S> {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        Ok(Value::Table(lua.create_table_from(self)?))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K: Eq + Hash + FromLua, V: FromLua, S: BuildHasher + Default> FromLua for IndexMap<K, V, S> {
+    #[inline]
+    fn from_lua(value: Value, _: &Lua) -> Result<Self> {
+        if let Value::Table(table) = value {
+            table.pairs().collect()
+        } else {
+            Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "IndexMap",
+                message: Some("expected table".to_string()),
+            })
+        }
+    }
+}
+
 impl<'lua, K: Ord + IntoLua, V: IntoLua> IntoLua for BTreeMap<K, V> {
     #[inline]
     fn into_lua(self, lua: &Lua) -> Result<Value> {
@@ -0,0 +1,440 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_int;
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::{Error, Result, TracebackFrame};
+use crate::ffi;
+use crate::hook::{Debug, DebugEvent, HookTriggers};
+use crate::lua::Lua;
+use crate::util::{check_stack, StackGuard};
+use crate::value::Value;
+
+/// A controller for debugging Lua code from another thread.
+///
+/// Created by [`Lua::attach_debugger`]. The debugger stops execution of Lua code on breakpoints,
+/// on [`pause`] requests and after stepping, blocking the thread running the code. While it is
+/// stopped, the call stack and local variables can be inspected and execution is resumed
+/// using [`resume`].
+///
+/// The handle is cheap to clone and can be sent to other threads. When all handles are dropped,
+/// the debugger stops pausing execution (but keeps the hook until [`Lua::remove_hook`] is called).
+///
+/// These are the building blocks for implementing a [Debug Adapter Protocol] server on top of mlua.
+///
+/// Requires `feature = "lua54/lua53/lua52/lua51/luajit"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result, StepAction};
+/// # use std::time::Duration;
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let debugger = lua.attach_debugger()?;
+/// debugger.set_breakpoint("main", 3);
+///
+/// let thread = std::thread::spawn(move || {
+///     let chunk = "local x = 1\nx = x + 1\nreturn x * 10";
+///     lua.load(chunk).set_name("@main").eval::<i32>()
+/// });
+///
+/// let stop = debugger.wait(Some(Duration::from_secs(5))).unwrap();
+/// assert_eq!((stop.chunk.as_str(), stop.line), ("main", 3));
+/// assert_eq!(debugger.locals(0)?[0].value, "2");
+/// debugger.resume(StepAction::Continue)?;
+///
+/// assert_eq!(thread.join().unwrap()?, 20);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`pause`]: Debugger::pause
+/// [`resume`]: Debugger::resume
+/// [Debug Adapter Protocol]: https://microsoft.github.io/debug-adapter-protocol/
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Clone)]
+pub struct Debugger {
+    shared: Arc<Shared>,
+    commands: Sender<Command>,
+    stops: Arc<Mutex<Receiver<DebugStop>>>,
+}
+
+/// Action to take when resuming execution stopped by the [`Debugger`].
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepAction {
+    /// Continue execution until the next breakpoint or pause request.
+    Continue,
+    /// Stop on the next line, entering called functions.
+    StepInto,
+    /// Stop on the next line of the current function (or its callers).
+    StepOver,
+    /// Stop on the next line after returning from the current function.
+    StepOut,
+}
+
+/// Reason why execution was stopped by the [`Debugger`].
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StopReason {
+    /// A breakpoint was hit.
+    Breakpoint,
+    /// A step requested by [`Debugger::resume`] was completed.
+    Step,
+    /// Execution was paused by [`Debugger::pause`].
+    Pause,
+}
+
+/// Location where execution was stopped by the [`Debugger`].
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DebugStop {
+    /// Reason why execution was stopped.
+    pub reason: StopReason,
+    /// Name of the chunk, without the leading `@` or `=`.
+    pub chunk: StdString,
+    /// Line about to be executed.
+    pub line: usize,
+}
+
+/// A snapshot of a local variable captured by [`Debugger::locals`].
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DebugVariable {
+    /// Name of the variable.
+    pub name: StdString,
+    /// Lua type of the value.
+    pub type_name: &'static str,
+    /// Short representation of the value: strings are quoted, and reference types are shown
+    /// as type name and address (eg. `table: 0x55c1d1c0`).
+    pub value: StdString,
+}
+
+// State shared between the debugger handles and the hook
+struct Shared {
+    breakpoints: Mutex<HashMap<StdString, BTreeSet<usize>>>,
+    pause: AtomicBool,
+    stopped: AtomicBool,
+}
+
+enum Command {
+    Resume(StepAction),
+    Locals(usize, Sender<Vec<DebugVariable>>),
+    Stack(Sender<Vec<TracebackFrame>>),
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Run,
+    StepInto,
+    // Stop when the stack depth is less or equal to
+    StepOver(usize),
+    // Stop when the stack depth is less than
+    StepOut(usize),
+}
+
+// State owned by the hook, on the thread running Lua code
+struct HookState {
+    mode: Mode,
+    commands: Receiver<Command>,
+    stops: Sender<DebugStop>,
+}
+
+impl Lua {
+    /// Attaches a [`Debugger`] to this Lua instance.
+    ///
+    /// The debugger is implemented using a hook triggered on every line, so it replaces any hook
+    /// set by [`Lua::set_hook`]. Use [`Lua::remove_hook`] to detach it.
+    ///
+    /// Requires `feature = "lua54/lua53/lua52/lua51/luajit"`
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn attach_debugger(&self) -> Result<Debugger> {
+        let shared = Arc::new(Shared {
+            breakpoints: Mutex::new(HashMap::new()),
+            pause: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+        let (commands, commands_rx) = mpsc::channel();
+        let (stops_tx, stops) = mpsc::channel();
+        let state = Mutex::new(HookState {
+            mode: Mode::Run,
+            commands: commands_rx,
+            stops: stops_tx,
+        });
+
+        let hook_shared = shared.clone();
+        self.set_hook(HookTriggers::every_line(), move |lua, debug| {
+            if debug.event() != DebugEvent::Line {
+                return Ok(());
+            }
+            let mut state = mlua_expect!(state.lock(), "debugger state poisoned");
+            state.on_line(lua, &debug, &hook_shared);
+            Ok(())
+        })?;
+
+        Ok(Debugger {
+            shared,
+            commands,
+            stops: Arc::new(Mutex::new(stops)),
+        })
+    }
+}
+
+impl Debugger {
+    /// Sets a breakpoint on the `line` of the `chunk`.
+    ///
+    /// The chunk name is matched without the leading `@` or `=` (see [`Chunk::set_name`]).
+    ///
+    /// [`Chunk::set_name`]: crate::Chunk::set_name
+    pub fn set_breakpoint(&self, chunk: &str, line: usize) {
+        let mut breakpoints = self.breakpoints();
+        breakpoints
+            .entry(chunk.to_string())
+            .or_default()
+            .insert(line);
+    }
+
+    /// Removes a breakpoint. Returns `true` if the breakpoint was set.
+    pub fn remove_breakpoint(&self, chunk: &str, line: usize) -> bool {
+        let mut breakpoints = self.breakpoints();
+        let removed = (breakpoints.get_mut(chunk)).is_some_and(|lines| lines.remove(&line));
+        if breakpoints.get(chunk).is_some_and(|lines| lines.is_empty()) {
+            breakpoints.remove(chunk);
+        }
+        removed
+    }
+
+    /// Removes all breakpoints of the `chunk`.
+    pub fn clear_breakpoints(&self, chunk: &str) {
+        self.breakpoints().remove(chunk);
+    }
+
+    /// Returns lines of breakpoints set in the `chunk`, in ascending order.
+    pub fn breakpoints_in(&self, chunk: &str) -> Vec<usize> {
+        let breakpoints = self.breakpoints();
+        (breakpoints.get(chunk)).map_or_else(Vec::new, |lines| lines.iter().copied().collect())
+    }
+
+    /// Requests to stop execution on the next executed line.
+    pub fn pause(&self) {
+        self.shared.pause.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if execution is currently stopped by the debugger.
+    pub fn is_stopped(&self) -> bool {
+        self.shared.stopped.load(Ordering::Acquire)
+    }
+
+    /// Waits until execution is stopped, for at most `timeout` if provided.
+    ///
+    /// Returns `None` on timeout or if the debugger was detached.
+    pub fn wait(&self, timeout: Option<Duration>) -> Option<DebugStop> {
+        let stops = mlua_expect!(self.stops.lock(), "debugger stops poisoned");
+        match timeout {
+            Some(timeout) => stops.recv_timeout(timeout).ok(),
+            None => stops.recv().ok(),
+        }
+    }
+
+    /// Resumes stopped execution.
+    ///
+    /// Returns an error if execution is not stopped.
+    pub fn resume(&self, action: StepAction) -> Result<()> {
+        self.check_stopped()?;
+        self.shared.stopped.store(false, Ordering::Release);
+        (self.commands.send(Command::Resume(action))).map_err(|_| detached_error())
+    }
+
+    /// Returns local variables of the function at the stack `level` of stopped execution.
+    ///
+    /// Level 0 is the function being executed, level 1 is the function that called it, and so
+    /// on. Returns an empty list if there is no function at the level.
+    pub fn locals(&self, level: usize) -> Result<Vec<DebugVariable>> {
+        self.check_stopped()?;
+        let (reply, response) = mpsc::channel();
+        (self.commands.send(Command::Locals(level, reply))).map_err(|_| detached_error())?;
+        response.recv().map_err(|_| detached_error())
+    }
+
+    /// Returns the call stack of stopped execution, starting from the function being executed.
+    pub fn stack(&self) -> Result<Vec<TracebackFrame>> {
+        self.check_stopped()?;
+        let (reply, response) = mpsc::channel();
+        (self.commands.send(Command::Stack(reply))).map_err(|_| detached_error())?;
+        response.recv().map_err(|_| detached_error())
+    }
+
+    fn breakpoints(&self) -> std::sync::MutexGuard<'_, HashMap<StdString, BTreeSet<usize>>> {
+        mlua_expect!(
+            self.shared.breakpoints.lock(),
+            "debugger breakpoints poisoned"
+        )
+    }
+
+    fn check_stopped(&self) -> Result<()> {
+        if !self.is_stopped() {
+            return Err(Error::RuntimeError("execution is not stopped".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl HookState {
+    fn on_line(&mut self, lua: &Lua, debug: &Debug, shared: &Shared) {
+        let line = debug.curr_line().max(0) as usize;
+        let source = debug.source();
+        let chunk = chunk_name(source.source.unwrap_or_default());
+
+        let reason = if shared.pause.swap(false, Ordering::Relaxed) {
+            Some(StopReason::Pause)
+        } else if self.is_breakpoint(shared, &chunk, line) {
+            Some(StopReason::Breakpoint)
+        } else {
+            match self.mode {
+                Mode::Run => None,
+                Mode::StepInto => Some(StopReason::Step),
+                Mode::StepOver(depth) => (stack_depth(lua) <= depth).then_some(StopReason::Step),
+                Mode::StepOut(depth) => (stack_depth(lua) < depth).then_some(StopReason::Step),
+            }
+        };
+        if let Some(reason) = reason {
+            self.stop(
+                lua,
+                shared,
+                DebugStop {
+                    reason,
+                    chunk,
+                    line,
+                },
+            );
+        }
+    }
+
+    fn is_breakpoint(&self, shared: &Shared, chunk: &str, line: usize) -> bool {
+        let breakpoints = mlua_expect!(shared.breakpoints.lock(), "debugger breakpoints poisoned");
+        (breakpoints.get(chunk)).is_some_and(|lines| lines.contains(&line))
+    }
+
+    // Blocks until execution is resumed, serving inspection requests
+    fn stop(&mut self, lua: &Lua, shared: &Shared, stop: DebugStop) {
+        self.mode = Mode::Run;
+        shared.stopped.store(true, Ordering::Release);
+        if self.stops.send(stop).is_err() {
+            // All debugger handles are dropped
+            shared.stopped.store(false, Ordering::Release);
+            return;
+        }
+        loop {
+            match self.commands.recv() {
+                Ok(Command::Resume(action)) => {
+                    self.mode = match action {
+                        StepAction::Continue => Mode::Run,
+                        StepAction::StepInto => Mode::StepInto,
+                        StepAction::StepOver => Mode::StepOver(stack_depth(lua)),
+                        StepAction::StepOut => Mode::StepOut(stack_depth(lua)),
+                    };
+                    return;
+                }
+                Ok(Command::Locals(level, reply)) => {
+                    let _ = reply.send(unsafe { locals(lua, level) });
+                }
+                Ok(Command::Stack(reply)) => {
+                    let _ = reply.send(stack(lua));
+                }
+                Err(_) => {
+                    shared.stopped.store(false, Ordering::Release);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn chunk_name(source: &[u8]) -> StdString {
+    let source = match source.first() {
+        Some(b'@' | b'=') => &source[1..],
+        _ => source,
+    };
+    StdString::from_utf8_lossy(source).into_owned()
+}
+
+fn stack_depth(lua: &Lua) -> usize {
+    let mut depth = 0;
+    while lua.inspect_stack(depth).is_some() {
+        depth += 1;
+    }
+    depth
+}
+
+fn stack(lua: &Lua) -> Vec<TracebackFrame> {
+    let mut frames = Vec::new();
+    while let Some(debug) = lua.inspect_stack(frames.len()) {
+        let source = debug.source();
+        let names = debug.names();
+        let to_string = |s: &[u8]| StdString::from_utf8_lossy(s).into_owned();
+        let line = debug.curr_line();
+        frames.push(TracebackFrame {
+            source: source.short_src.map(to_string).unwrap_or_default(),
+            line: (line > 0).then_some(line as usize),
+            name: names.name.map(to_string),
+            is_c: source.what == Some(b"C"),
+        });
+    }
+    frames
+}
+
+unsafe fn locals(lua: &Lua, level: usize) -> Vec<DebugVariable> {
+    let state = lua.state();
+    let mut ar: ffi::lua_Debug = mem::zeroed();
+    if ffi::lua_getstack(state, level as c_int, &mut ar) == 0 {
+        return Vec::new();
+    }
+
+    let _sg = StackGuard::new(state);
+    let mut variables = Vec::new();
+    for n in 1.. {
+        if check_stack(state, 2).is_err() {
+            break;
+        }
+        let name = ffi::lua_getlocal(state, &ar, n);
+        if name.is_null() {
+            break;
+        }
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+        let value = lua.pop_value();
+        // Skip internal variables, eg. `(for state)` or `(temporary)`
+        if name.starts_with('(') {
+            continue;
+        }
+        variables.push(DebugVariable {
+            name,
+            type_name: value.type_name(),
+            value: describe(&value),
+        });
+    }
+    variables
+}
+
+fn describe(value: &Value) -> StdString {
+    match value {
+        Value::Nil => "nil".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        value => format!("{}: {:?}", value.type_name(), value.to_pointer()),
+    }
+}
+
+fn detached_error() -> Error {
+    Error::RuntimeError("debugger is detached".to_string())
+}
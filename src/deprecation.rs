@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::table::Table;
+use crate::value::Value;
+
+// Wraps a function to call `warn_once` first
+const WRAPPER: &str = r#"
+local f, warn_once = ...
+return function(...)
+    warn_once()
+    return f(...)
+end
+"#;
+
+// Wraps the function found at the time of the call in a table
+const ALIAS_WRAPPER: &str = r#"
+local t, name, warn_once = ...
+return function(...)
+    warn_once()
+    return t[name](...)
+end
+"#;
+
+impl Table {
+    /// Adds `old_name` as a deprecated alias of the function `new_name` in this table.
+    ///
+    /// Calling the alias calls the function currently stored under `new_name` (so replacing
+    /// it later does not require updating the alias), emitting a Lua warning
+    /// `'old_name' is deprecated, use 'new_name' instead` on the first call.
+    /// See [`Lua::set_warning_function`] for receiving warnings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let api = lua.create_table()?;
+    /// api.set("spawn_entity", lua.create_function(|_, name: String| Ok(name))?)?;
+    /// api.alias("spawn", "spawn_entity")?;
+    /// lua.globals().set("api", api)?;
+    ///
+    /// assert_eq!(lua.load("api.spawn('npc')").eval::<String>()?, "npc");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_warning_function`]: crate::Lua::set_warning_function
+    pub fn alias(&self, old_name: &str, new_name: &str) -> Result<()> {
+        let lua = &self.0.lua;
+        let warn_once = warn_once_function(
+            self,
+            format!("'{old_name}' is deprecated, use '{new_name}' instead"),
+        )?;
+        let alias: Function = lua.load(ALIAS_WRAPPER).set_name("=__mlua_alias").call((
+            self.clone(),
+            new_name,
+            warn_once,
+        ))?;
+        self.raw_set(old_name, alias)
+    }
+
+    /// Marks the function `name` in this table as deprecated.
+    ///
+    /// The function is replaced by a wrapper that emits a Lua warning
+    /// `'name' is deprecated since <since>: <note>` on the first call.
+    /// See [`Lua::set_warning_function`] for receiving warnings.
+    ///
+    /// Returns an error if `name` is not a function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let warnings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    /// let warnings2 = warnings.clone();
+    /// lua.set_warning_function(move |_, msg, _| {
+    ///     warnings2.lock().unwrap().push(msg.to_string_lossy().into_owned());
+    ///     Ok(())
+    /// });
+    ///
+    /// lua.load("function legacy() return 1 end").exec()?;
+    /// lua.globals().deprecate("legacy", "2.0", "use `modern` instead")?;
+    /// lua.load("legacy() legacy()").exec()?;
+    /// assert_eq!(
+    ///     *warnings.lock().unwrap(),
+    ///     ["'legacy' is deprecated since 2.0: use `modern` instead"]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lua::set_warning_function`]: crate::Lua::set_warning_function
+    pub fn deprecate(&self, name: &str, since: &str, note: &str) -> Result<()> {
+        let lua = &self.0.lua;
+        let func = match self.raw_get::<_, Value>(name)? {
+            Value::Function(func) => func,
+            value => {
+                return Err(Error::RuntimeError(format!(
+                    "'{name}' is not a function (a {} value)",
+                    value.type_name()
+                )))
+            }
+        };
+        let mut msg = format!("'{name}' is deprecated since {since}");
+        if !note.is_empty() {
+            msg = format!("{msg}: {note}");
+        }
+        let warn_once = warn_once_function(self, msg)?;
+        let wrapper: Function = lua
+            .load(WRAPPER)
+            .set_name("=__mlua_deprecated")
+            .call((func, warn_once))?;
+        self.raw_set(name, wrapper)
+    }
+}
+
+// Creates a function emitting the warning `msg` on the first call only
+fn warn_once_function(table: &Table, msg: String) -> Result<Function> {
+    let warned = AtomicBool::new(false);
+    table.0.lua.create_function(move |lua, ()| {
+        if !warned.swap(true, Ordering::Relaxed) {
+            lua.warning(msg.as_str(), false)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,342 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::types::{Integer, MaybeSend};
+use crate::value::{MultiValue, Nil, Value};
+
+// Options to make execution of Lua code reproducible, see `LuaOptions::deterministic`
+
+// Registry keys of the original `os.time` and `os.date` functions and of the time source
+const OS_TIME_KEY: &str = "__mlua_os_time";
+const OS_DATE_KEY: &str = "__mlua_os_date";
+const TIME_SOURCE_KEY: &str = "__mlua_time_source";
+
+// Stable `pairs`, iterating keys in the order they were inserted.
+//
+// The keys of every iterated table are kept in a shadow index, a sequence of keys in the insertion
+// order. New keys are appended to it by the `__newindex` metamethod of the `tracker` metatable,
+// which is set on tables without a metatable when they are iterated the first time. Keys stored
+// before that (or without `__newindex`) are appended in a stable order on the next iteration.
+// The tracker is hidden from `getmetatable`.
+const STABLE_PAIRS: &str = r#"
+local pairs, getmetatable, sort_keys, raw_metatable = ...
+local next, rawget, rawset, setmetatable, type = next, rawget, rawset, setmetatable, type
+local isfrozen = table and table.isfrozen
+
+local shadows = setmetatable({}, { __mode = "k" })
+-- Marks the old position of a key that was removed and then inserted again
+local moved = {}
+
+local tracker = {}
+function tracker.__newindex(t, k, v)
+    rawset(t, k, v)
+    local shadow = shadows[t]
+    if v ~= nil and shadow ~= nil then
+        local n = shadow.n + 1
+        if shadow.index[k] ~= nil then
+            shadow.keys[shadow.index[k]] = moved
+        end
+        shadow.keys[n], shadow.index[k], shadow.n = k, n, n
+    end
+end
+
+-- Drops removed keys from the shadow index and appends keys stored without tracking
+local function sync(t)
+    local shadow = shadows[t]
+    if shadow == nil then
+        shadow = { keys = {}, index = {}, n = 0 }
+        shadows[t] = shadow
+        if raw_metatable(t) == nil and not (isfrozen and isfrozen(t)) then
+            setmetatable(t, tracker)
+        end
+    end
+    local keys, index, n = {}, {}, 0
+    for i = 1, shadow.n do
+        local k = shadow.keys[i]
+        if k ~= moved and rawget(t, k) ~= nil then
+            n = n + 1
+            keys[n], index[k] = k, n
+        end
+    end
+    local untracked = {}
+    for k in next, t do
+        if index[k] == nil then
+            untracked[#untracked + 1] = k
+        end
+    end
+    sort_keys(untracked)
+    for i = 1, #untracked do
+        n = n + 1
+        keys[n], index[untracked[i]] = untracked[i], n
+    end
+    shadow.keys, shadow.index, shadow.n = keys, index, n
+    return keys, n
+end
+
+local function stable_pairs(t)
+    if type(t) ~= "table" then
+        return pairs(t)
+    end
+    -- Respect `__pairs` metamethod
+    local mt = raw_metatable(t)
+    if mt ~= nil and rawget(mt, "__pairs") ~= nil then
+        return rawget(mt, "__pairs")(t)
+    end
+    local keys, n = sync(t)
+    local i = 0
+    return function(t)
+        while i < n do
+            i = i + 1
+            -- Skip keys removed during traversal
+            local k = keys[i]
+            local v = rawget(t, k)
+            if v ~= nil then
+                return k, v
+            end
+        end
+    end, t, nil
+end
+
+local function stable_getmetatable(v)
+    local mt = getmetatable(v)
+    if mt == tracker then
+        return nil
+    end
+    return mt
+end
+
+return stable_pairs, stable_getmetatable
+"#;
+
+impl Lua {
+    /// Sets the source of the current time used by `os.time` and `os.date`.
+    ///
+    /// `os.time()` returns the time provided by `source` (in whole seconds since the Unix epoch)
+    /// and `os.date(format)` formats it, instead of reading the system clock. Calls with an
+    /// explicit table or time argument behave as usual.
+    ///
+    /// Together with [`Lua::set_clock_source`], this allows deterministic tests and replay
+    /// systems to control the time seen by scripts without removing the `os` library.
+    ///
+    /// The functions are replaced in the currently loaded `os` library. If the library is not
+    /// loaded, this method does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_time_source(|| UNIX_EPOCH + Duration::from_secs(86400))?;
+    ///
+    /// let (time, date): (i64, String) = lua.load(r#"os.time(), os.date("!%Y-%m-%d")"#).eval()?;
+    /// assert_eq!(time, 86400);
+    /// assert_eq!(date, "1970-01-02");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_time_source<F>(&self, source: F) -> Result<()>
+    where
+        F: 'static + MaybeSend + Fn() -> SystemTime,
+    {
+        let os = match self.globals_storage()?.raw_get::<_, Value>("os")? {
+            Value::Table(os) => os,
+            _ => return Ok(()),
+        };
+
+        // Keep the original functions to handle explicit arguments
+        for (name, key) in [("time", OS_TIME_KEY), ("date", OS_DATE_KEY)] {
+            if self
+                .named_registry_value::<Option<Function>>(key)?
+                .is_none()
+            {
+                let func = os.raw_get::<_, Function>(name)?;
+                self.set_named_registry_value(key, func)?;
+            }
+        }
+
+        let now = self.create_function(move |_, ()| Ok(unix_time(source()) as Integer))?;
+        self.set_named_registry_value(TIME_SOURCE_KEY, now)?;
+
+        let time = self.create_function(|lua, args: MultiValue| {
+            if !args.is_empty() && args[0] != Nil {
+                let time = lua.named_registry_value::<Function>(OS_TIME_KEY)?;
+                return time.call::<_, Value>(args);
+            }
+            let now = lua.named_registry_value::<Function>(TIME_SOURCE_KEY)?;
+            now.call::<_, Value>(())
+        })?;
+        let date = self.create_function(|lua, (format, time): (Value, Value)| {
+            let date = lua.named_registry_value::<Function>(OS_DATE_KEY)?;
+            match time {
+                Nil => {
+                    let now = lua.named_registry_value::<Function>(TIME_SOURCE_KEY)?;
+                    date.call::<_, Value>((format, now.call::<_, Value>(())?))
+                }
+                time => date.call::<_, Value>((format, time)),
+            }
+        })?;
+
+        os.raw_set("time", time)?;
+        os.raw_set("date", date)?;
+        Ok(())
+    }
+
+    /// Sets the source of the processor time returned by `os.clock`.
+    ///
+    /// `os.clock()` returns the duration provided by `source` in seconds. A virtual clock can
+    /// be implemented by sharing a counter between the `source` and the application.
+    ///
+    /// The function is replaced in the currently loaded `os` library. If the library is not
+    /// loaded, this method does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let millis = Arc::new(AtomicU64::new(0));
+    /// let millis2 = millis.clone();
+    /// lua.set_clock_source(move || Duration::from_millis(millis2.load(Ordering::Relaxed)))?;
+    ///
+    /// assert_eq!(lua.load("os.clock()").eval::<f64>()?, 0.0);
+    /// millis.fetch_add(1500, Ordering::Relaxed);
+    /// assert_eq!(lua.load("os.clock()").eval::<f64>()?, 1.5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_clock_source<F>(&self, source: F) -> Result<()>
+    where
+        F: 'static + MaybeSend + Fn() -> Duration,
+    {
+        let os = match self.globals_storage()?.raw_get::<_, Value>("os")? {
+            Value::Table(os) => os,
+            _ => return Ok(()),
+        };
+        let clock = self.create_function(move |_, ()| Ok(source().as_secs_f64()))?;
+        os.raw_set("clock", clock)
+    }
+
+    // Replaces `math.random` and `math.randomseed` with functions backed by a seeded PRNG
+    pub(crate) fn install_deterministic_random(&self, seed: u64) -> Result<()> {
+        let math = match self.globals_storage()?.raw_get::<_, Value>("math")? {
+            Value::Table(math) => math,
+            _ => return Ok(()),
+        };
+
+        let state = Arc::new(AtomicU64::new(seed));
+        let state2 = state.clone();
+        let random =
+            self.create_function(move |_, (m, n): (Option<Integer>, Option<Integer>)| {
+                let x = splitmix64(&state);
+                let (low, high) = match (m, n) {
+                    (None, _) => {
+                        // Float in the range [0, 1)
+                        let value = (x >> 11) as f64 * (1.0 / (1u64 << 53) as f64);
+                        return Ok(Value::Number(value));
+                    }
+                    // Integer with all bits random (Lua 5.4)
+                    (Some(0), None) => return Ok(Value::Integer(x as Integer)),
+                    (Some(m), None) => (1, m),
+                    (Some(m), Some(n)) => (m, n),
+                };
+                if low > high {
+                    return Err(Error::RuntimeError(
+                        "bad argument to 'random' (interval is empty)".to_string(),
+                    ));
+                }
+                let range = (high as u64).wrapping_sub(low as u64);
+                let value = match range.checked_add(1) {
+                    Some(range) => (low as u64).wrapping_add(x % range),
+                    None => x,
+                };
+                Ok(Value::Integer(value as Integer))
+            })?;
+        let randomseed = self.create_function(move |_, seed: Option<Integer>| {
+            state2.store(seed.unwrap_or_default() as u64, Ordering::Relaxed);
+            Ok(())
+        })?;
+
+        math.raw_set("random", random)?;
+        math.raw_set("randomseed", randomseed)?;
+        Ok(())
+    }
+
+    // Replaces `pairs` with a function iterating keys in the insertion order
+    pub(crate) fn install_stable_pairs(&self) -> Result<()> {
+        let globals = self.globals_storage()?;
+        let sort_keys = self.create_function(|_, keys: Table| {
+            let mut sorted =
+                (keys.clone().sequence_values::<Value>()).collect::<Result<Vec<_>>>()?;
+            sorted.sort_by(compare_keys);
+            for (i, key) in sorted.into_iter().enumerate() {
+                keys.raw_set(i + 1, key)?;
+            }
+            Ok(())
+        })?;
+        let raw_metatable = self.create_function(|_, table: Table| Ok(table.get_metatable()))?;
+        let (pairs, getmetatable): (Function, Function) = self
+            .load(STABLE_PAIRS)
+            .set_name("=__mlua_stable_pairs")
+            .call((
+                globals.raw_get::<_, Value>("pairs")?,
+                globals.raw_get::<_, Value>("getmetatable")?,
+                sort_keys,
+                raw_metatable,
+            ))?;
+        globals.raw_set("pairs", pairs)?;
+        globals.raw_set("getmetatable", getmetatable)
+    }
+}
+
+// Returns the number of whole seconds since the Unix epoch (negative for earlier times)
+fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(err) => {
+            let d = err.duration();
+            -(d.as_secs() as i64) - (d.subsec_nanos() > 0) as i64
+        }
+    }
+}
+
+fn splitmix64(state: &AtomicU64) -> u64 {
+    let mut z =
+        (state.fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed)).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Orders keys as booleans, numbers, strings and then other values (in the traversal order)
+fn compare_keys(a: &Value, b: &Value) -> CmpOrdering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Boolean(_) => 0,
+            Value::Integer(_) | Value::Number(_) => 1,
+            Value::String(_) => 2,
+            _ => 3,
+        }
+    }
+
+    match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Number(b)) => (*a as f64).total_cmp(b),
+        (Value::Number(a), Value::Integer(b)) => a.total_cmp(&(*b as f64)),
+        (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+        (Value::String(a), Value::String(b)) => a.as_bytes().cmp(b.as_bytes()),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
@@ -0,0 +1,163 @@
+use std::any::type_name;
+use std::ops::Deref;
+use std::os::raw::c_int;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::{FromLua, IntoLua, Value};
+
+/// Trait for fieldless Rust enums that can be exported to Lua as enum tables.
+///
+/// See [`Lua::create_enum`] for an example of implementing it, and [`EnumValue`] for converting
+/// variants to and from Lua.
+pub trait LuaEnum: Copy + 'static {
+    /// Names and variants of the enum.
+    const VARIANTS: &'static [(&'static str, Self)];
+
+    /// Returns the integer value of the variant, as seen by Lua.
+    fn value(self) -> Integer;
+}
+
+/// Wraps a [`LuaEnum`] variant to convert it to and from Lua.
+///
+/// The variant is converted to Lua as its integer value, and can be converted from either the
+/// value or the name of the variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EnumValue<T>(pub T);
+
+impl<T> EnumValue<T> {
+    /// Returns the wrapped variant.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for EnumValue<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: LuaEnum> IntoLua for EnumValue<T> {
+    #[inline]
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::Integer(self.0.value()))
+    }
+}
+
+impl<T: LuaEnum> FromLua for EnumValue<T> {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        let ty = value.type_name();
+        let (variant, repr) = match value {
+            Value::String(ref s) => {
+                let name = s.as_bytes();
+                let variant = (T::VARIANTS.iter()).find(|(n, _)| n.as_bytes() == name);
+                (variant, format!("'{}'", s.to_string_lossy()))
+            }
+            Value::Integer(_) | Value::Number(_) => {
+                let value = Integer::from_lua(value, lua)?;
+                let variant = (T::VARIANTS.iter()).find(|(_, v)| v.value() == value);
+                (variant, value.to_string())
+            }
+            _ => {
+                return Err(Error::FromLuaConversionError {
+                    from: ty,
+                    to: type_name::<T>(),
+                    message: Some("expected variant name or value".to_string()),
+                })
+            }
+        };
+        match variant {
+            Some(&(_, variant)) => Ok(EnumValue(variant)),
+            None => Err(Error::FromLuaConversionError {
+                from: ty,
+                to: type_name::<T>(),
+                message: Some(format!("invalid variant {repr}")),
+            }),
+        }
+    }
+}
+
+impl Lua {
+    /// Creates a read-only table describing the enum `T`.
+    ///
+    /// The table maps names of the variants to their values (eg. `{ Red = 1, Green = 2 }`) and
+    /// values back to the names (eg. `Color[1] == "Red"`).
+    ///
+    /// On Luau the table itself is made read-only. On other Lua versions a proxy table is
+    /// returned that raises an error on modification; `rawget` on the proxy returns `nil`, and
+    /// iterating it using `pairs` works on Lua 5.2+ only.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{EnumValue, Integer, Lua, LuaEnum, Result};
+    /// # fn main() -> Result<()> {
+    /// #[derive(Clone, Copy, Debug, PartialEq)]
+    /// enum Color {
+    ///     Red = 1,
+    ///     Green = 2,
+    /// }
+    ///
+    /// impl LuaEnum for Color {
+    ///     const VARIANTS: &'static [(&'static str, Self)] =
+    ///         &[("Red", Color::Red), ("Green", Color::Green)];
+    ///
+    ///     fn value(self) -> Integer {
+    ///         self as Integer
+    ///     }
+    /// }
+    ///
+    /// let lua = Lua::new();
+    /// lua.globals().set("Color", lua.create_enum::<Color>()?)?;
+    /// let is_red = lua.create_function(|_, c: EnumValue<Color>| Ok(*c == Color::Red))?;
+    /// lua.globals().set("is_red", is_red)?;
+    ///
+    /// assert!(lua.load("is_red(Color.Red) and is_red('Red')").eval::<bool>()?);
+    /// assert_eq!(lua.load("Color[Color.Green]").eval::<String>()?, "Green");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_enum<T: LuaEnum>(&self) -> Result<Table> {
+        let n = T::VARIANTS.len() as c_int;
+        let data = self.create_table_with_capacity(n, n)?;
+        for &(name, variant) in T::VARIANTS {
+            data.raw_set(name, variant.value())?;
+            data.raw_set(variant.value(), name)?;
+        }
+
+        #[cfg(feature = "luau")]
+        {
+            data.set_readonly(true);
+            Ok(data)
+        }
+
+        #[cfg(not(feature = "luau"))]
+        {
+            let metatable = self.create_table_with_capacity(0, 4)?;
+            let newindex = self.create_function(|_, _: Value| -> Result<()> {
+                Err(Error::RuntimeError(
+                    "attempt to modify a read-only enum table".to_string(),
+                ))
+            })?;
+            metatable.raw_set("__newindex", newindex)?;
+            if let Value::Function(next) = self.globals_storage()?.get::<_, Value>("next")? {
+                let pairs = self
+                    .load("local next, data = ... return function() return next, data, nil end")
+                    .set_name("=__mlua_enum_pairs")
+                    .call::<_, Value>((next, data.clone()))?;
+                metatable.raw_set("__pairs", pairs)?;
+            }
+            metatable.raw_set("__index", data)?;
+            metatable.raw_set("__metatable", false)?;
+
+            let proxy = self.create_table()?;
+            proxy.set_metatable(Some(metatable));
+            Ok(proxy)
+        }
+    }
+}
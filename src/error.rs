@@ -1,3 +1,5 @@
+use std::any::Any;
+use std::collections::VecDeque;
 use std::error::Error as StdError;
 use std::fmt;
 use std::io::Error as IoError;
@@ -5,9 +7,12 @@ use std::net::AddrParseError;
 use std::result::Result as StdResult;
 use std::str::Utf8Error;
 use std::string::String as StdString;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
 
 use crate::private::Sealed;
+use crate::value::Value;
 
 /// Error type returned by `mlua` methods.
 #[derive(Debug, Clone)]
@@ -117,6 +122,18 @@ pub enum Error {
     /// [`Thread::resume`]: crate::Thread::resume
     /// [`Thread::status`]: crate::Thread::status
     CoroutineInactive,
+    /// Async execution of Lua code has exceeded the time budget.
+    ///
+    /// The budget is set using [`Lua::set_async_timeout`].
+    ///
+    /// [`Lua::set_async_timeout`]: crate::Lua::set_async_timeout
+    Timeout,
+    /// An async method call of userdata was aborted.
+    ///
+    /// Calls are aborted using [`AnyUserData::abort_pending`] or when the userdata is destroyed.
+    ///
+    /// [`AnyUserData::abort_pending`]: crate::AnyUserData::abort_pending
+    Aborted,
     /// An [`AnyUserData`] is not the expected type in a borrow.
     ///
     /// This error can only happen when manually using [`AnyUserData`], or when implementing
@@ -179,6 +196,17 @@ pub enum Error {
     /// This error can occur only when a Rust panic resumed previously was recovered
     /// and returned again.
     PreviouslyResumedPanic,
+    /// A Rust panic in a callback converted to an error.
+    ///
+    /// Returned only if [`PanicPolicy::ConvertToError`] is set.
+    ///
+    /// [`PanicPolicy::ConvertToError`]: crate::PanicPolicy::ConvertToError
+    CallbackPanic {
+        /// The panic message (if the panic payload is a string).
+        message: StdString,
+        /// The original panic payload, can be taken using [`Error::take_panic_payload`].
+        payload: Arc<Mutex<Option<Box<dyn Any + Send>>>>,
+    },
     /// Serialization error.
     #[cfg(feature = "serialize")]
     #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
@@ -205,6 +233,73 @@ pub enum Error {
 /// A specialized `Result` type used by `mlua`'s API.
 pub type Result<T> = StdResult<T, Error>;
 
+/// A single frame of a Lua stack traceback.
+///
+/// Returned by [`Error::traceback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TracebackFrame {
+    /// Short source name of the chunk where the function was defined (`[C]` for C functions).
+    pub source: StdString,
+    /// Current line of the function, if available.
+    pub line: Option<usize>,
+    /// Name of the function, if known.
+    pub name: Option<StdString>,
+    /// `true` if the function is a C (or Rust) function.
+    pub is_c: bool,
+}
+
+/// Location and details of a syntax error.
+///
+/// Returned by [`Error::syntax_error_info`]. Lua reports only the line of a syntax error, so
+/// the column is not available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SyntaxErrorInfo {
+    /// The chunk name as it appears in the error message (eg. `[string "..."]`).
+    pub chunk_name: Option<StdString>,
+    /// The line where the error occurred.
+    pub line: Option<usize>,
+    /// The token near which the error occurred without quotes (eg. `end` or `<eof>`).
+    pub near: Option<StdString>,
+    /// `true` if the error can likely be fixed by appending more input to the source code.
+    pub incomplete_input: bool,
+}
+
+// Maximum number of errors for which gathered traceback frames are kept
+const TRACEBACK_FRAMES_CAPACITY: usize = 128;
+
+type TracebackFramesStore = VecDeque<(StdString, Arc<[TracebackFrame]>)>;
+
+// Traceback frames gathered at error time, keyed by the traceback string (or the runtime error
+// message) they belong to. Error variants carry only the string form, so the frames are kept aside
+// for the most recent errors and looked up by `Error::traceback`.
+static TRACEBACK_FRAMES: Lazy<Mutex<TracebackFramesStore>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(TRACEBACK_FRAMES_CAPACITY)));
+
+// Remembers frames of the traceback (or runtime error message) `key`
+pub(crate) fn store_traceback_frames(key: &str, frames: Vec<TracebackFrame>) {
+    let mut store = TRACEBACK_FRAMES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if let Some(pos) = store.iter().position(|(k, _)| k == key) {
+        store.remove(pos);
+    }
+    if store.len() == TRACEBACK_FRAMES_CAPACITY {
+        store.pop_front();
+    }
+    store.push_back((key.to_string(), frames.into()));
+}
+
+pub(crate) fn traceback_frames(key: &str) -> Option<Arc<[TracebackFrame]>> {
+    let store = TRACEBACK_FRAMES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    (store.iter().rev())
+        .find(|(k, _)| k == key)
+        .map(|(_, frames)| frames.clone())
+}
+
 #[cfg(not(tarpaulin_include))]
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -267,6 +362,8 @@ impl fmt::Display for Error {
                 }
             }
             Error::CoroutineInactive => write!(fmt, "cannot resume inactive coroutine"),
+            Error::Timeout => write!(fmt, "execution timed out"),
+            Error::Aborted => write!(fmt, "async call aborted"),
             Error::UserDataTypeMismatch => write!(fmt, "userdata is not expected type"),
             Error::UserDataDestructed => write!(fmt, "userdata has been destructed"),
             Error::UserDataBorrowError => write!(fmt, "userdata already mutably borrowed"),
@@ -308,6 +405,9 @@ impl fmt::Display for Error {
             Error::PreviouslyResumedPanic => {
                 write!(fmt, "previously resumed panic returned again")
             }
+            Error::CallbackPanic { ref message, .. } => {
+                write!(fmt, "panic in callback: {message}")
+            }
             #[cfg(feature = "serialize")]
             Error::SerializeError(ref err) => {
                 write!(fmt, "serialize error: {err}")
@@ -345,17 +445,166 @@ impl Error {
         Error::ExternalError(err.into().into())
     }
 
+    /// Creates an [`Error::BadArgument`] for an argument of unexpected type.
+    ///
+    /// The error is displayed in the Lua style, eg. `bad argument #2: number expected, got
+    /// string`. Useful for validating arguments received as [`Value`]s.
+    ///
+    /// [`Value`]: crate::Value
+    pub fn bad_argument_type(pos: usize, expected: &str, got: &Value) -> Self {
+        Error::BadArgument {
+            to: None,
+            pos,
+            name: None,
+            cause: Arc::new(Error::external(format!(
+                "{expected} expected, got {}",
+                got.type_name()
+            ))),
+        }
+    }
+
     /// Attempts to downcast the external error object to a concrete type by reference.
+    ///
+    /// Looks through [`Error::CallbackError`], [`Error::WithContext`] and [`Error::BadArgument`]
+    /// wrappers, so it's possible to recover an error returned from a Rust callback after it
+    /// passed through Lua code.
     pub fn downcast_ref<T>(&self) -> Option<&T>
     where
         T: StdError + 'static,
     {
+        self.external_error()?.downcast_ref()
+    }
+
+    /// Attempts to downcast the external error object to a concrete type.
+    ///
+    /// Similar to [`downcast_ref`], but returns a shared reference counted pointer to the error
+    /// object. Returns the original error if the downcast fails.
+    ///
+    /// [`downcast_ref`]: #method.downcast_ref
+    pub fn downcast<T>(self) -> StdResult<Arc<T>, Self>
+    where
+        T: StdError + Send + Sync + 'static,
+    {
+        match self.external_error() {
+            Some(err) if err.is::<T>() => {
+                let err = Arc::into_raw(Arc::clone(err));
+                // Safety: we checked that the error object has type `T`
+                Ok(unsafe { Arc::from_raw(err as *const T) })
+            }
+            _ => Err(self),
+        }
+    }
+
+    /// Takes the original panic payload out of [`Error::CallbackPanic`].
+    ///
+    /// Looks through error wrappers (see [`Error::chain`]). The payload can be taken only once,
+    /// subsequent calls (including calls on clones of the error) return `None`.
+    pub fn take_panic_payload(&self) -> Option<Box<dyn Any + Send>> {
+        self.chain().find_map(|err| match err {
+            Error::CallbackPanic { payload, .. } => payload.lock().ok()?.take(),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = if let Some(msg) = payload.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = payload.downcast_ref::<StdString>() {
+            msg.clone()
+        } else {
+            "<panic>".to_string()
+        };
+        Error::CallbackPanic {
+            message,
+            payload: Arc::new(Mutex::new(Some(payload))),
+        }
+    }
+
+    /// Returns an iterator over this error and its causes.
+    ///
+    /// Causes of [`Error::WithContext`], [`Error::CallbackError`] and [`Error::BadArgument`]
+    /// are followed, the first item is always the error itself.
+    pub fn chain(&self) -> impl Iterator<Item = &Error> {
+        let mut next = Some(self);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = match current {
+                Error::WithContext { cause, .. }
+                | Error::CallbackError { cause, .. }
+                | Error::BadArgument { cause, .. } => Some(cause.as_ref()),
+                _ => None,
+            };
+            Some(current)
+        })
+    }
+
+    // Returns the innermost external error object (if any)
+    fn external_error(&self) -> Option<&Arc<dyn StdError + Send + Sync>> {
         match self {
-            Error::ExternalError(err) => err.downcast_ref(),
+            Error::ExternalError(err) => Some(err),
+            Error::CallbackError { cause, .. }
+            | Error::WithContext { cause, .. }
+            | Error::BadArgument { cause, .. } => cause.external_error(),
             _ => None,
         }
     }
 
+    /// Returns the Lua stack traceback attached to this error as a list of frames.
+    ///
+    /// The traceback is available for [`Error::CallbackError`] and for [`Error::RuntimeError`]
+    /// raised during a protected call. Frames are gathered from the Lua stack at the moment of
+    /// error and kept for a limited number of most recent errors.
+    /// Errors wrapped by [`Error::WithContext`] are inspected as well.
+    ///
+    /// Returns `None` if the error does not have a traceback.
+    pub fn traceback(&self) -> Option<Vec<TracebackFrame>> {
+        let key = match self {
+            Error::CallbackError { traceback, .. } => traceback,
+            Error::RuntimeError(msg) => msg,
+            Error::WithContext { cause, .. } => return cause.traceback(),
+            _ => return None,
+        };
+        traceback_frames(key).map(|frames| frames.to_vec())
+    }
+
+    /// Returns the location and details of a syntax error.
+    ///
+    /// The details are extracted from the message of [`Error::SyntaxError`]. Errors wrapped by
+    /// [`Error::WithContext`] are inspected as well.
+    ///
+    /// Returns `None` if the error is not a syntax error.
+    pub fn syntax_error_info(&self) -> Option<SyntaxErrorInfo> {
+        match self {
+            Error::SyntaxError {
+                message,
+                incomplete_input,
+            } => {
+                let (chunk_name, line) = match parse_location(message) {
+                    Some((chunk_name, line)) => (Some(chunk_name.to_string()), Some(line)),
+                    None => (None, None),
+                };
+                Some(SyntaxErrorInfo {
+                    chunk_name,
+                    line,
+                    near: parse_near_token(message).map(|s| s.to_string()),
+                    incomplete_input: *incomplete_input,
+                })
+            }
+            Error::WithContext { cause, .. } => cause.syntax_error_info(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn syntax_error(message: StdString) -> Self {
+        // This seems terrible, but as far as I can tell, this is exactly what the
+        // stock Lua REPL does.
+        let incomplete_input = message.ends_with("<eof>") || message.ends_with("'<eof>'");
+        Error::SyntaxError {
+            message,
+            incomplete_input,
+        }
+    }
+
     pub(crate) fn bad_self_argument(to: &str, cause: Error) -> Self {
         Error::BadArgument {
             to: Some(to.to_string()),
@@ -378,6 +627,31 @@ impl Error {
     }
 }
 
+// Parses `chunk_name:line:` prefix of a Lua error message
+fn parse_location(message: &str) -> Option<(&str, usize)> {
+    // Chunk name can contain colons, `[string "..."]` form needs special handling
+    let start = match message.starts_with("[string \"") {
+        true => message.find("\"]:")? + 2,
+        false => 0,
+    };
+    // Find the first `:<digits>:` sequence
+    message[start..].match_indices(':').find_map(|(pos, _)| {
+        let name_end = start + pos;
+        let rest = &message[name_end + 1..];
+        let line_end = rest.find(':')?;
+        let line = rest[..line_end].parse().ok()?;
+        Some((&message[..name_end], line))
+    })
+}
+
+// Parses token from the trailing `near 'token'` (Lua) or `got 'token'` (Luau) part of the message
+fn parse_near_token(message: &str) -> Option<&str> {
+    let pos = message.rfind(" near ").map(|p| p + 6);
+    let pos = pos.or_else(|| message.rfind(" got ").map(|p| p + 5))?;
+    let token = message[pos..].lines().next()?;
+    Some((token.strip_prefix('\'').and_then(|t| t.strip_suffix('\''))).unwrap_or(token))
+}
+
 pub trait ExternalError {
     fn into_lua_err(self) -> Error;
 }
@@ -402,8 +676,32 @@ where
 }
 
 /// Provides the `context` method for [`Error`] and `Result<T, Error>`.
+///
+/// The error is wrapped into [`Error::WithContext`], which is preserved when the error passes
+/// through Lua code (e.g. returned from a Rust callback), and the context is displayed before
+/// the original error message.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Error, ErrorContext, Lua, Result};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let err = lua
+///     .load("error('bad config')")
+///     .exec()
+///     .context("while loading config")
+///     .unwrap_err();
+/// assert!(err.to_string().starts_with("while loading config"));
+/// assert!(matches!(err.chain().last(), Some(Error::RuntimeError(_))));
+/// # Ok(())
+/// # }
+/// ```
 pub trait ErrorContext: Sealed {
+    /// Wraps the error value with additional context.
     fn context<C: fmt::Display>(self, context: C) -> Self;
+
+    /// Wraps the error value with additional context that is evaluated lazily.
     fn with_context<C: fmt::Display>(self, f: impl FnOnce(&Error) -> C) -> Self;
 }
 
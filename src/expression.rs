@@ -0,0 +1,142 @@
+use std::ffi::CString;
+
+use crate::chunk::ChunkMode;
+use crate::error::Result;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::value::{FromLuaMulti, IntoLuaMulti, Nil, Value};
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+use crate::value::IntoLua;
+#[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+use {
+    crate::ffi,
+    crate::util::{check_stack, StackGuard},
+};
+
+// Registry key of the table caching compiled expressions by their source
+const EXPRESSION_CACHE_KEY: &str = "__mlua_expression_cache";
+// Maximum number of functions in a chunk cache, a full cache is cleared on the next miss
+const CHUNK_CACHE_SIZE: usize = 256;
+
+impl Lua {
+    /// Evaluates the expression `expr` using `env` as the environment (or the globals table if
+    /// `None`).
+    ///
+    /// Unlike [`Chunk::eval`], the expression is compiled only once: compiled functions are
+    /// cached by the expression source, so evaluating the same expression repeatedly (eg. in
+    /// spreadsheets or rules engines) is cheap. The expression can return multiple values
+    /// (eg. `"x, y"`); statements are not allowed.
+    ///
+    /// Up to 256 expressions are cached, the cache is cleared when it's full. Use
+    /// [`Lua::clear_expression_cache`] to release it earlier.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let row = lua.create_table()?;
+    /// for (price, qty) in [(2.5, 4), (10.0, 2)] {
+    ///     row.set("price", price)?;
+    ///     row.set("qty", qty)?;
+    ///     let total: f64 = lua.eval("price * qty", Some(&row))?;
+    ///     assert_eq!(total, price * qty as f64);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Chunk::eval`]: crate::Chunk::eval
+    pub fn eval<R: FromLuaMulti>(&self, expr: &str, env: Option<&Table>) -> Result<R> {
+        let func = self.cached_chunk(EXPRESSION_CACHE_KEY, expr, || {
+            self.compile_with_env("=expression", &format!("return {expr}"))
+        })?;
+        let env = env.cloned().unwrap_or_else(|| self.globals());
+        self.call_with_env(&func, env, ())
+    }
+
+    /// Removes all compiled expressions cached by [`Lua::eval`].
+    pub fn clear_expression_cache(&self) -> Result<()> {
+        self.unset_named_registry_value(EXPRESSION_CACHE_KEY)
+    }
+
+    // Returns the function cached under `key` in the registry table named `cache_key`,
+    // calling `compile` to create it on a cache miss
+    pub(crate) fn cached_chunk(
+        &self,
+        cache_key: &str,
+        key: &str,
+        compile: impl FnOnce() -> Result<Function>,
+    ) -> Result<Function> {
+        let cache = self.named_registry_value::<Option<Table>>(cache_key)?;
+        if let Some(func) = cache
+            .as_ref()
+            .map(|c| c.raw_get(key))
+            .transpose()?
+            .flatten()
+        {
+            return Ok(func);
+        }
+
+        let func = compile()?;
+        // Counting entries is cheap compared to compiling the source on a miss
+        let cache = match cache {
+            Some(cache) if cache.clone().pairs::<Value, Value>().count() < CHUNK_CACHE_SIZE => {
+                cache
+            }
+            _ => {
+                let cache = self.create_table()?;
+                self.set_named_registry_value(cache_key, cache.clone())?;
+                cache
+            }
+        };
+        cache.raw_set(key, func.clone())?;
+        Ok(func)
+    }
+
+    // Compiles `body` to a function called by `call_with_env`.
+    // Where `_ENV` is supported, the environment is passed as the first argument. Otherwise the
+    // compiled function returns a new closure for every call, whose environment is set instead,
+    // so nested calls with another environment cannot change it.
+    pub(crate) fn compile_with_env(&self, name: &str, body: &str) -> Result<Function> {
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        let source = format!("local _ENV = ... {body}");
+        #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+        let source = format!("return function(...) {body} end");
+        let name = mlua_expect!(CString::new(name), "invalid chunk name");
+        let mode = Some(ChunkMode::Text);
+        self.load_chunk(Some(&name), Nil, mode, source.as_bytes())
+    }
+
+    // Calls a function compiled by `compile_with_env` using `env` as the environment
+    pub(crate) fn call_with_env<R: FromLuaMulti>(
+        &self,
+        func: &Function,
+        env: Table,
+        args: impl IntoLuaMulti,
+    ) -> Result<R> {
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        {
+            let mut args = args.into_lua_multi(self)?;
+            args.push_front(env.into_lua(self)?);
+            func.call(args)
+        }
+
+        #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+        {
+            let func: Function = func.call(())?;
+            unsafe {
+                let state = self.state();
+                let _sg = StackGuard::new(state);
+                check_stack(state, 2)?;
+                self.push_ref(&func.0);
+                self.push_ref(&env.0);
+                ffi::lua_setfenv(state, -2);
+            }
+            func.call(args)
+        }
+    }
+}
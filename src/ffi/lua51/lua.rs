@@ -73,23 +73,30 @@ pub type lua_Integer = i32;
 pub type lua_Integer = i64;
 
 /// Type for native C functions that can be passed to Lua.
-pub type lua_CFunction = unsafe extern "C" fn(L: *mut lua_State) -> c_int;
+pub type lua_CFunction = unsafe extern "C-unwind" fn(L: *mut lua_State) -> c_int;
 
 // Type for functions that read/write blocks when loading/dumping Lua chunks
-pub type lua_Reader =
-    unsafe extern "C" fn(L: *mut lua_State, ud: *mut c_void, sz: *mut usize) -> *const c_char;
-pub type lua_Writer =
-    unsafe extern "C" fn(L: *mut lua_State, p: *const c_void, sz: usize, ud: *mut c_void) -> c_int;
+pub type lua_Reader = unsafe extern "C-unwind" fn(
+    L: *mut lua_State,
+    ud: *mut c_void,
+    sz: *mut usize,
+) -> *const c_char;
+pub type lua_Writer = unsafe extern "C-unwind" fn(
+    L: *mut lua_State,
+    p: *const c_void,
+    sz: usize,
+    ud: *mut c_void,
+) -> c_int;
 
 /// Type for memory-allocation functions
-pub type lua_Alloc = unsafe extern "C" fn(
+pub type lua_Alloc = unsafe extern "C-unwind" fn(
     ud: *mut c_void,
     ptr: *mut c_void,
     osize: usize,
     nsize: usize,
 ) -> *mut c_void;
 
-extern "C" {
+extern "C-unwind" {
     //
     // State manipulation
     //
@@ -221,14 +228,14 @@ pub const LUA_GCSTEP: c_int = 5;
 pub const LUA_GCSETPAUSE: c_int = 6;
 pub const LUA_GCSETSTEPMUL: c_int = 7;
 
-extern "C" {
+extern "C-unwind" {
     pub fn lua_gc(L: *mut lua_State, what: c_int, data: c_int) -> c_int;
 }
 
 //
 // Miscellaneous functions
 //
-extern "C" {
+extern "C-unwind" {
     pub fn lua_error(L: *mut lua_State) -> !;
     pub fn lua_next(L: *mut lua_State, idx: c_int) -> c_int;
     pub fn lua_concat(L: *mut lua_State, n: c_int);
@@ -351,9 +358,9 @@ pub const LUA_MASKLINE: c_int = 1 << (LUA_HOOKLINE as usize);
 pub const LUA_MASKCOUNT: c_int = 1 << (LUA_HOOKCOUNT as usize);
 
 /// Type for functions to be called on debug events.
-pub type lua_Hook = unsafe extern "C" fn(L: *mut lua_State, ar: *mut lua_Debug);
+pub type lua_Hook = unsafe extern "C-unwind" fn(L: *mut lua_State, ar: *mut lua_Debug);
 
-extern "C" {
+extern "C-unwind" {
     pub fn lua_getstack(L: *mut lua_State, level: c_int, ar: *mut lua_Debug) -> c_int;
     pub fn lua_getinfo(L: *mut lua_State, what: *const c_char, ar: *mut lua_Debug) -> c_int;
     pub fn lua_getlocal(L: *mut lua_State, ar: *const lua_Debug, n: c_int) -> *const c_char;
@@ -387,3 +394,38 @@ pub struct lua_Debug {
     // lua.h mentions this is for private use
     i_ci: c_int,
 }
+
+//
+// LuaJIT specific (`luajit.h`)
+//
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_MASK: c_int = 0x00ff;
+
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_ENGINE: c_int = 0; // Set mode for whole JIT engine
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_DEBUG: c_int = 1; // Set debug mode (idx = level)
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_FUNC: c_int = 2; // Change mode for a function
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_ALLFUNC: c_int = 3; // Recurse into subroutine protos
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_ALLSUBFUNC: c_int = 4; // Change only the subroutines
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_TRACE: c_int = 5; // Flush a compiled trace
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_WRAPCFUNC: c_int = 0x10; // Set wrapper mode for C function calls
+
+// Flags or'ed in to the mode
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_OFF: c_int = 0x0000; // Turn feature off
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_ON: c_int = 0x0100; // Turn feature on
+#[cfg(feature = "luajit")]
+pub const LUAJIT_MODE_FLUSH: c_int = 0x0200; // Flush JIT-compiled code
+
+#[cfg(feature = "luajit")]
+extern "C-unwind" {
+    // Control the JIT engine
+    pub fn luaJIT_setmode(L: *mut lua_State, idx: c_int, mode: c_int) -> c_int;
+}
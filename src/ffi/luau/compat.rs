@@ -337,7 +337,7 @@ pub unsafe fn luaL_loadbufferx(
     name: *const c_char,
     mode: *const c_char,
 ) -> c_int {
-    extern "C" {
+    extern "C-unwind" {
         fn free(p: *mut c_void);
     }
 
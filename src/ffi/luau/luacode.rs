@@ -7,13 +7,16 @@ use std::slice;
 pub struct lua_CompileOptions {
     pub optimizationLevel: c_int,
     pub debugLevel: c_int,
+    pub typeInfoLevel: c_int,
     pub coverageLevel: c_int,
     pub vectorLib: *const c_char,
     pub vectorCtor: *const c_char,
+    pub vectorType: *const c_char,
     pub mutableGlobals: *mut *const c_char,
+    pub userdataTypes: *const *const c_char,
 }
 
-extern "C" {
+extern "C-unwind" {
     #[link_name = "luau_compile"]
     pub fn luau_compile_(
         source: *const c_char,
@@ -0,0 +1,103 @@
+//! Low-level helpers for working with the Lua C API.
+//!
+//! This module exposes a small, supported subset of the utilities mlua uses internally, so crates
+//! that implement their own C API level features (eg. bindings to C libraries) can check and
+//! restore the stack, call fallible Lua API functions in protected mode and convert errors
+//! between Rust and Lua in the same way as mlua does.
+//!
+//! All functions must be called with a `lua_State` belonging to a Lua instance managed by mlua
+//! (see [`Lua::exec_raw_multi`] or [`Lua::create_c_function`]).
+//!
+//! [`Lua::exec_raw_multi`]: crate::Lua::exec_raw_multi
+//! [`Lua::create_c_function`]: crate::Lua::create_c_function
+
+use std::os::raw::c_int;
+
+use crate::error::{Error, Result};
+use crate::ffi::lua_State;
+use crate::util;
+
+/// Restores the Lua stack to its original size when dropped.
+///
+/// Any values pushed after creating the guard are popped on drop. Popping more values than
+/// were on the stack when the guard was created is considered a logic error and panics.
+pub struct StackGuard {
+    _guard: util::StackGuard,
+}
+
+impl StackGuard {
+    /// Creates a new guard recording the current size of the stack.
+    ///
+    /// # Safety
+    /// The `state` must be a valid Lua state, which outlives the guard.
+    pub unsafe fn new(state: *mut lua_State) -> StackGuard {
+        StackGuard {
+            _guard: util::StackGuard::new(state),
+        }
+    }
+}
+
+/// Ensures that the stack has at least `amount` free slots.
+///
+/// Returns [`Error::StackError`] if the stack cannot grow.
+///
+/// # Safety
+/// The `state` must be a valid Lua state.
+pub unsafe fn check_stack(state: *mut lua_State, amount: c_int) -> Result<()> {
+    util::check_stack(state, amount)
+}
+
+/// Calls `f` in protected mode, returning Lua errors raised by it as [`Error`].
+///
+/// The `nargs` values on top of the stack are passed to `f`, which runs on a fresh stack frame
+/// as a Lua C function. After `f` returns, its top `nresults` values (or all values if
+/// `nresults` is `LUA_MULTRET`) are moved to the caller stack in place of the arguments.
+///
+/// Uses 3 extra stack slots and does not check the stack size.
+///
+/// # Safety
+/// The `state` must be a valid Lua state with `nargs` values on the stack. Lua errors are
+/// implemented using `longjmp`, so `f` must not panic or hold values that implement `Drop`.
+pub unsafe fn protect_lua_closure<F, R>(
+    state: *mut lua_State,
+    nargs: c_int,
+    nresults: c_int,
+    f: F,
+) -> Result<R>
+where
+    F: Fn(*mut lua_State) -> R,
+    R: Copy,
+{
+    util::protect_lua_closure(state, nargs, nresults, f)
+}
+
+/// Pops an error object off the stack and converts it to [`Error`].
+///
+/// `err_code` is the status returned by `lua_pcall` (or similar functions). Errors raised by
+/// Rust code are returned unchanged, and Rust panics are resumed.
+///
+/// # Safety
+/// The `state` must be a valid Lua state with the error object on top of the stack.
+pub unsafe fn pop_error(state: *mut lua_State, err_code: c_int) -> Error {
+    util::pop_error(state, err_code)
+}
+
+/// Calls `f` from a Lua C function, converting returned errors and panics to Lua errors.
+///
+/// `f` receives the number of arguments passed to the C function, and must return the number of
+/// results, as usual. Errors are raised as [`Error::CallbackError`], and panics are propagated
+/// to the Rust code calling Lua.
+///
+/// One extra stack slot is inserted below the arguments, so they are available at indices
+/// `2..=nargs + 1` (or using negative indices).
+///
+/// # Safety
+/// Must be called only at the start of a Lua C function, when the only values on the stack are
+/// its arguments. Raising an error uses `longjmp`, so no values implementing `Drop` should be
+/// alive in the calling frame.
+pub unsafe fn callback_error<F, R>(state: *mut lua_State, f: F) -> R
+where
+    F: FnOnce(c_int) -> Result<R>,
+{
+    util::callback_error(state, f)
+}
@@ -0,0 +1,138 @@
+use std::any::type_name;
+use std::ops::Deref;
+
+use bitflags::{Bits, Flags as BitFlags};
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::value::{FromLua, IntoLua, Value};
+
+/// Wraps a [bitflags] type to convert it to and from Lua.
+///
+/// The flags are converted to Lua as an integer. They can be converted from an integer (all
+/// bits must correspond to defined flags), a flag name or a string of names separated by `|`
+/// (eg. `"READ | WRITE"`), or an array of names and integers.
+///
+/// Requires `feature = "bitflags"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Flags, Lua, Result};
+/// # fn main() -> Result<()> {
+/// bitflags::bitflags! {
+///     #[derive(Debug, Clone, Copy, PartialEq)]
+///     struct Access: u8 {
+///         const READ = 1;
+///         const WRITE = 2;
+///     }
+/// }
+///
+/// let lua = Lua::new();
+/// lua.globals().set("Access", lua.create_flags::<Access>()?)?;
+/// let open = lua.create_function(|_, access: Flags<Access>| Ok(access.contains(Access::READ)))?;
+/// lua.globals().set("open", open)?;
+///
+/// assert!(lua.load("open(Access.READ + Access.WRITE)").eval::<bool>()?);
+/// assert!(lua.load("open('READ | WRITE') and open({'READ'})").eval::<bool>()?);
+/// assert!(!lua.load("open('WRITE')").eval::<bool>()?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [bitflags]: https://docs.rs/bitflags
+#[cfg_attr(docsrs, doc(cfg(feature = "bitflags")))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Flags<T>(pub T);
+
+impl<T> Flags<T> {
+    /// Returns the wrapped flags.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Flags<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: BitFlags> IntoLua for Flags<T>
+where
+    T::Bits: IntoLua,
+{
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        self.0.bits().into_lua(lua)
+    }
+}
+
+impl<T: BitFlags> FromLua for Flags<T>
+where
+    T::Bits: FromLua,
+{
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        let ty = value.type_name();
+        let conversion_error = |message: String| Error::FromLuaConversionError {
+            from: ty,
+            to: type_name::<T>(),
+            message: Some(message),
+        };
+        match value {
+            Value::Integer(_) | Value::Number(_) => {
+                let bits = T::Bits::from_lua(value, lua)?;
+                T::from_bits(bits)
+                    .map(Flags)
+                    .ok_or_else(|| conversion_error("value contains unknown bits".to_string()))
+            }
+            Value::String(s) => {
+                let s = s.to_str()?;
+                let mut bits = T::Bits::EMPTY;
+                for name in s.split('|').map(str::trim).filter(|name| !name.is_empty()) {
+                    match T::from_name(name) {
+                        Some(flag) => bits = bits | flag.bits(),
+                        None => return Err(conversion_error(format!("unknown flag '{name}'"))),
+                    }
+                }
+                Ok(Flags(T::from_bits_retain(bits)))
+            }
+            Value::Table(table) => {
+                let mut bits = T::Bits::EMPTY;
+                for value in table.sequence_values::<Value>() {
+                    let Flags(flags) = Flags::<T>::from_lua(value?, lua)?;
+                    bits = bits | flags.bits();
+                }
+                Ok(Flags(T::from_bits_retain(bits)))
+            }
+            _ => Err(conversion_error(
+                "expected integer, flag names or array of flags".to_string(),
+            )),
+        }
+    }
+}
+
+impl Lua {
+    /// Creates a table mapping names of the flags of the [bitflags] type `T` to their values,
+    /// eg. `{ READ = 1, WRITE = 2 }`.
+    ///
+    /// See [`Flags`] for an example.
+    ///
+    /// Requires `feature = "bitflags"`
+    ///
+    /// [bitflags]: https://docs.rs/bitflags
+    #[cfg_attr(docsrs, doc(cfg(feature = "bitflags")))]
+    pub fn create_flags<T: BitFlags>(&self) -> Result<Table>
+    where
+        T::Bits: IntoLua,
+    {
+        let table = self.create_table()?;
+        for flag in T::FLAGS.iter().filter(|flag| flag.is_named()) {
+            table.raw_set(flag.name(), flag.value().bits())?;
+        }
+        Ok(table)
+    }
+}
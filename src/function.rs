@@ -1,15 +1,17 @@
 use std::mem;
+use std::ops::Deref;
 use std::os::raw::{c_int, c_void};
 use std::ptr;
 use std::slice;
 
 use crate::error::{Error, Result};
 use crate::ffi;
+use crate::memory::MemoryInfo;
 use crate::types::LuaRef;
 use crate::util::{
     assert_stack, check_stack, error_traceback, pop_error, ptr_to_cstr_bytes, StackGuard,
 };
-use crate::value::{FromLuaMulti, IntoLuaMulti};
+use crate::value::{FromLuaMulti, IntoLuaMulti, Value};
 
 #[cfg(feature = "unstable")]
 use {
@@ -30,16 +32,33 @@ use {crate::types::AsyncCallback, futures_core::Future, futures_util::TryFutureE
 pub struct Function(pub(crate) LuaRef);
 
 /// Owned handle to an internal Lua function.
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-#[derive(Clone, Debug)]
-pub struct OwnedFunction(pub(crate) crate::types::LuaOwnedRef);
+///
+/// See [`OwnedTable`] for the semantics of owned handles.
+///
+/// [`OwnedTable`]: crate::OwnedTable
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedFunction(pub(crate) Function);
 
-#[cfg(feature = "unstable")]
 impl OwnedFunction {
-    /// Get borrowed handle to the underlying Lua function.
-    pub const fn as_ref(&self) -> Function {
-        Function(self.0.as_ref())
+    /// Returns a handle to the underlying Lua function.
+    #[inline]
+    pub fn to_ref(&self) -> Function {
+        self.0.clone()
+    }
+
+    /// Converts this owned handle into a regular handle to the Lua function.
+    #[inline]
+    pub fn into_ref(self) -> Function {
+        self.0
+    }
+}
+
+impl Deref for OwnedFunction {
+    type Target = Function;
+
+    #[inline]
+    fn deref(&self) -> &Function {
+        &self.0
     }
 }
 
@@ -118,7 +137,9 @@ impl Function {
             let _sg = StackGuard::new(state);
             check_stack(state, nargs + 3)?;
 
-            ffi::lua_pushcfunction(state, error_traceback);
+            MemoryInfo::relax_limit_with(state, || {
+                ffi::lua_pushcfunction(state, error_traceback);
+            });
             let stack_start = ffi::lua_gettop(state);
             lua.push_ref(&self.0);
             for arg in args.drain_all() {
@@ -173,11 +194,10 @@ impl Function {
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub fn call_async<'fut, A, R>(&self, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
-        let lua = self.0.lua;
+        let lua = &self.0.lua;
         match lua.create_recycled_thread(self) {
             Ok(t) => {
                 let mut t = t.into_async(args);
@@ -216,7 +236,7 @@ impl Function {
     /// # }
     /// ```
     pub fn bind<A: IntoLuaMulti>(&self, args: A) -> Result<Function> {
-        unsafe extern "C" fn args_wrapper_impl(state: *mut ffi::lua_State) -> c_int {
+        unsafe extern "C-unwind" fn args_wrapper_impl(state: *mut ffi::lua_State) -> c_int {
             let nargs = ffi::lua_gettop(state);
             let nbinds = ffi::lua_tointeger(state, ffi::lua_upvalueindex(1)) as c_int;
             ffi::luaL_checkstack(state, nbinds, ptr::null());
@@ -323,7 +343,7 @@ impl Function {
     #[cfg(not(feature = "luau"))]
     #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
     pub fn dump(&self, strip: bool) -> Vec<u8> {
-        unsafe extern "C" fn writer(
+        unsafe extern "C-unwind" fn writer(
             _state: *mut ffi::lua_State,
             buf: *const c_void,
             buf_len: usize,
@@ -351,6 +371,43 @@ impl Function {
         data
     }
 
+    // Returns names and values of the function upvalues
+    pub(crate) fn upvalues(&self) -> Vec<(Vec<u8>, Value)> {
+        let lua = &self.0.lua;
+        let state = lua.state();
+        let mut upvalues = Vec::new();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 2);
+
+            lua.push_ref(&self.0);
+            loop {
+                let name = ffi::lua_getupvalue(state, -1, upvalues.len() as c_int + 1);
+                if name.is_null() {
+                    break;
+                }
+                let name = ptr_to_cstr_bytes(name).unwrap_or_default().to_vec();
+                upvalues.push((name, lua.pop_value()));
+            }
+        }
+        upvalues
+    }
+
+    // Sets value of the `n`-th upvalue (starting from 1), returns `false` if there is no such upvalue
+    pub(crate) fn set_upvalue(&self, n: usize, value: Value) -> Result<bool> {
+        let lua = &self.0.lua;
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 2)?;
+
+            lua.push_ref(&self.0);
+            lua.push_value(value)?;
+            let n = c_int::try_from(n).unwrap_or(c_int::MAX);
+            Ok(!ffi::lua_setupvalue(state, -2, n).is_null())
+        }
+    }
+
     /// Retrieves recorded coverage information about this Lua function including inner calls.
     ///
     /// This function takes a callback as an argument and calls it providing [`CoverageInfo`] snapshot
@@ -370,7 +427,7 @@ impl Function {
         use std::ffi::CStr;
         use std::os::raw::c_char;
 
-        unsafe extern "C" fn callback<F: FnMut(CoverageInfo)>(
+        unsafe extern "C-unwind" fn callback<F: FnMut(CoverageInfo)>(
             data: *mut c_void,
             function: *const c_char,
             line_defined: c_int,
@@ -392,7 +449,7 @@ impl Function {
             });
         }
 
-        let lua = self.0.lua;
+        let lua = &self.0.lua;
         let state = lua.state();
         unsafe {
             let _sg = StackGuard::new(state);
@@ -404,12 +461,12 @@ impl Function {
         }
     }
 
-    /// Convert this handle to owned version.
-    #[cfg(feature = "unstable")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
+    /// Converts this handle into an owned version.
+    ///
+    /// See [`OwnedFunction`] for details.
     #[inline]
     pub fn into_owned(self) -> OwnedFunction {
-        OwnedFunction(self.0.into_owned())
+        OwnedFunction(self)
     }
 }
 
@@ -483,6 +540,5 @@ mod assertions {
 
     static_assertions::assert_not_impl_any!(Function: Send);
 
-    #[cfg(feature = "unstable")]
-    static_assertions::assert_not_impl_any!(OwnedFunction: Send);
+    static_assertions::assert_impl_all!(OwnedFunction: Send, Sync);
 }
@@ -0,0 +1,298 @@
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::value::{FromLua, IntoLua, Nil, Value};
+
+// Registry key of the weak table caching intermediate tables of global paths
+const GLOBAL_PATHS_KEY: &str = "__mlua_global_paths";
+
+// Registry key of the table holding global variables once the globals interceptor is installed
+const GLOBALS_BACKING_KEY: &str = "__mlua_globals_backing";
+
+/// Kind of a global variable access passed to the interceptor set by
+/// [`Lua::set_globals_interceptor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalAccess {
+    /// Reading a global variable.
+    Read,
+    /// Assigning a global variable.
+    Write,
+}
+
+/// Decision returned by the interceptor set by [`Lua::set_globals_interceptor`].
+#[derive(Debug, Clone)]
+pub enum GlobalPolicy {
+    /// Proceed with the access as usual.
+    Allow,
+    /// Raise an error instead of accessing the global variable.
+    Deny,
+    /// Store the given value as the global variable.
+    ///
+    /// For reads the value is returned to the script, which allows to create globals lazily on
+    /// the first access. For writes the value is stored instead of the assigned one.
+    Replace(Value),
+}
+
+impl Lua {
+    /// Sets a function which is called on every access of a global variable by name.
+    ///
+    /// The interceptor receives the kind of access and the variable name, and decides whether
+    /// to allow the access, deny it (raising a Lua error), or replace the value. It can be used
+    /// to audit which globals untrusted scripts touch, to forbid some of them, or to create
+    /// globals lazily.
+    ///
+    /// Interception is implemented by moving all globals to a hidden table and installing
+    /// `__index`/`__newindex` metamethods on the global table, which replace an existing
+    /// metatable (if any). As a consequence, iterating the global table with `pairs` does not
+    /// yield any values, and raw accesses to the global table (eg. [`Table::raw_set`]) do not
+    /// see the global variables. Functionality of `mlua` that manages global variables (eg.
+    /// [`Lua::filter_std_lib`]) keeps working and is not intercepted. Accesses using non-string
+    /// keys are always allowed.
+    ///
+    /// On Luau, the global table must not be read-only (see [`Lua::sandbox`]); the "safe env"
+    /// optimization of the global table is disabled so that every access is intercepted.
+    ///
+    /// Calling this method again replaces the interceptor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{GlobalAccess, GlobalPolicy, Lua, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_globals_interceptor(|_, access, name| match (access, name) {
+    ///     (GlobalAccess::Read, "os") => Ok(GlobalPolicy::Deny),
+    ///     (GlobalAccess::Read, "answer") => Ok(GlobalPolicy::Replace(Value::Integer(42))),
+    ///     _ => Ok(GlobalPolicy::Allow),
+    /// })?;
+    ///
+    /// assert!(lua.load("return os.time()").exec().is_err());
+    /// assert_eq!(lua.load("return answer").eval::<i32>()?, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Table::raw_set`]: crate::Table::raw_set
+    /// [`Lua::filter_std_lib`]: #method.filter_std_lib
+    /// [`Lua::sandbox`]: #method.sandbox
+    pub fn set_globals_interceptor<F>(&self, callback: F) -> Result<()>
+    where
+        F: Fn(&Lua, GlobalAccess, &str) -> Result<GlobalPolicy> + MaybeSend + 'static,
+    {
+        if !self.globals_interceptor_installed() {
+            self.install_globals_proxy()?;
+        }
+        self.set_globals_interceptor_callback(Some(Arc::new(callback)));
+        Ok(())
+    }
+
+    /// Removes the interceptor previously set by [`Lua::set_globals_interceptor`].
+    ///
+    /// All accesses to the global variables are allowed afterwards.
+    pub fn remove_globals_interceptor(&self) {
+        self.set_globals_interceptor_callback(None);
+    }
+
+    /// Gets the value at a dot separated path of global variables, eg. `app.config.width`.
+    ///
+    /// Each name is looked up (invoking metamethods) in the table returned by the previous one,
+    /// starting from the global table. Returns `nil` (converted to `T`) if any of the
+    /// intermediate values is `nil`, and an error if it's not a table.
+    ///
+    /// Intermediate tables are cached, so repeated accesses to the same nested table do not
+    /// invoke metamethods again. A cached table is used only while it's still (raw) stored under
+    /// its name in the parent table, so tables replaced by scripts are picked up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.load("app = { window = { width = 800 } }").exec()?;
+    /// assert_eq!(lua.get_global_path::<i32>("app.window.width")?, 800);
+    /// assert_eq!(lua.get_global_path::<Option<i32>>("app.menu.width")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_global_path<T: FromLua>(&self, path: &str) -> Result<T> {
+        check_global_path(path)?;
+        let (table, name) = match path.rsplit_once('.') {
+            Some((parent, name)) => (self.global_path_table(parent, false)?, name),
+            None => (Some(self.globals()), path),
+        };
+        match table {
+            Some(table) => table.get(name),
+            None => T::from_lua(Nil, self),
+        }
+    }
+
+    /// Sets the value at a dot separated path of global variables, eg. `app.config.width`.
+    ///
+    /// Missing intermediate tables are created, an error is returned if any of the
+    /// intermediate values is not a table. See [`Lua::get_global_path`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_global_path("app.hooks.on_start", lua.create_function(|_, ()| Ok("started"))?)?;
+    /// assert_eq!(lua.load("app.hooks.on_start()").eval::<String>()?, "started");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_global_path<V: IntoLua>(&self, path: &str, value: V) -> Result<()> {
+        check_global_path(path)?;
+        let value = value.into_lua(self)?;
+        let (table, name) = match path.rsplit_once('.') {
+            Some((parent, name)) => (self.global_path_table(parent, true)?, name),
+            None => (Some(self.globals()), path),
+        };
+        let table = table.expect("missing tables are created");
+        table.set(name, value)
+    }
+
+    fn install_globals_proxy(&self) -> Result<()> {
+        let globals = self.globals();
+        #[cfg(feature = "luau")]
+        if globals.is_readonly() {
+            return Err(Error::RuntimeError(
+                "cannot intercept access to read-only globals table".to_string(),
+            ));
+        }
+
+        // Move all globals to the backing table
+        let backing = self.create_table()?;
+        for pair in globals.clone().pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            backing.raw_set(key, value)?;
+        }
+        globals.clear()?;
+        self.set_named_registry_value(GLOBALS_BACKING_KEY, backing.clone())?;
+        let backing = Arc::new(self.create_registry_value(backing)?);
+
+        let metatable = self.create_table()?;
+        let backing2 = backing.clone();
+        let index = self.create_function(move |lua, (_, key): (Value, Value)| {
+            let backing: Table = lua.registry_value(&backing2)?;
+            let name = match key {
+                Value::String(ref name) => name.to_string_lossy().into_owned(),
+                _ => return backing.raw_get::<_, Value>(key),
+            };
+            match intercept(lua, GlobalAccess::Read, &name)? {
+                GlobalPolicy::Allow => backing.raw_get(key),
+                GlobalPolicy::Deny => Err(access_denied(&name)),
+                GlobalPolicy::Replace(value) => {
+                    backing.raw_set(key, value.clone())?;
+                    Ok(value)
+                }
+            }
+        })?;
+        let newindex =
+            self.create_function(move |lua, (_, key, value): (Value, Value, Value)| {
+                let backing: Table = lua.registry_value(&backing)?;
+                let name = match key {
+                    Value::String(ref name) => name.to_string_lossy().into_owned(),
+                    _ => return backing.raw_set(key, value),
+                };
+                match intercept(lua, GlobalAccess::Write, &name)? {
+                    GlobalPolicy::Allow => backing.raw_set(key, value),
+                    GlobalPolicy::Deny => Err(access_denied(&name)),
+                    GlobalPolicy::Replace(value) => backing.raw_set(key, value),
+                }
+            })?;
+        metatable.raw_set("__index", index)?;
+        metatable.raw_set("__newindex", newindex)?;
+        globals.set_metatable(Some(metatable));
+
+        #[cfg(feature = "luau")]
+        unsafe {
+            self.disable_globals_safeenv();
+        }
+
+        self.set_globals_interceptor_installed();
+        Ok(())
+    }
+
+    // Returns the table holding global variables, bypassing the globals interceptor.
+    // It's the global table itself unless the interceptor moved the globals to a backing table.
+    pub(crate) fn globals_storage(&self) -> Result<Table> {
+        if self.globals_interceptor_installed() {
+            return self.named_registry_value(GLOBALS_BACKING_KEY);
+        }
+        Ok(self.globals())
+    }
+
+    // Returns the table at `path`, creating missing tables if `create` is true.
+    // Cached tables are checked against their parent table, one `rawget` per path segment.
+    fn global_path_table(&self, path: &str, create: bool) -> Result<Option<Table>> {
+        let cache = match self.named_registry_value::<Option<Table>>(GLOBAL_PATHS_KEY)? {
+            Some(cache) => cache,
+            None => {
+                let cache = self.create_table()?;
+                let metatable = self.create_table_from([("__mode", "v")])?;
+                cache.set_metatable(Some(metatable));
+                self.set_named_registry_value(GLOBAL_PATHS_KEY, cache.clone())?;
+                cache
+            }
+        };
+
+        let (parent, name) = match path.rsplit_once('.') {
+            Some((parent, name)) => match self.global_path_table(parent, create)? {
+                Some(parent) => (parent, name),
+                None => return Ok(None),
+            },
+            None => (self.globals(), path),
+        };
+        if let Some(table) = cache.raw_get::<_, Option<Table>>(path)? {
+            let storage = match path.contains('.') {
+                true => parent.clone(),
+                false => self.globals_storage()?,
+            };
+            if matches!(storage.raw_get(name)?, Value::Table(t) if t == table) {
+                return Ok(Some(table));
+            }
+        }
+
+        let table = match parent.get::<_, Value>(name)? {
+            Value::Table(table) => table,
+            Value::Nil if create => {
+                let table = self.create_table()?;
+                parent.set(name, table.clone())?;
+                table
+            }
+            Value::Nil => return Ok(None),
+            value => {
+                return Err(Error::RuntimeError(format!(
+                    "global '{path}' is not a table (a {} value)",
+                    value.type_name()
+                )))
+            }
+        };
+        cache.raw_set(path, table.clone())?;
+        Ok(Some(table))
+    }
+}
+
+fn check_global_path(path: &str) -> Result<()> {
+    if path.split('.').any(|name| name.is_empty()) {
+        return Err(Error::RuntimeError(format!("invalid global path '{path}'")));
+    }
+    Ok(())
+}
+
+fn intercept(lua: &Lua, access: GlobalAccess, name: &str) -> Result<GlobalPolicy> {
+    match lua.globals_interceptor_callback() {
+        Some(callback) => callback(lua, access, name),
+        None => Ok(GlobalPolicy::Allow),
+    }
+}
+
+fn access_denied(name: &str) -> Error {
+    Error::RuntimeError(format!("access to global '{name}' is denied"))
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe, Location};
+use std::pin::Pin;
+use std::string::String as StdString;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::chunk::AsChunk;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
+
+type Command = Box<dyn FnOnce(&Lua) + Send>;
+
+/// A thread-safe handle to a Lua instance running on a dedicated thread.
+///
+/// The Lua instance is owned by the thread and never leaves it. The handle sends commands to
+/// the thread, which executes them one by one in order of sending, and returns a
+/// [`HandleResponse`] future resolved with the command result. This makes it possible to use
+/// Lua from multi-threaded (or async) applications without enabling the `send` feature.
+///
+/// The handle is cheap to clone, the thread stops when all handles are dropped.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{LuaHandle, Result};
+/// # fn main() -> Result<()> {
+/// let handle = LuaHandle::new()?;
+///
+/// let handle2 = handle.clone();
+/// std::thread::spawn(move || {
+///     let f = handle2.load::<()>("function sum(a, b) return a + b end");
+///     f.wait().unwrap();
+/// })
+/// .join()
+/// .unwrap();
+///
+/// // Responses are futures, they can be awaited or waited for synchronously
+/// assert_eq!(handle.call::<_, i32>("sum", (3, 4)).wait()?, 7);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct LuaHandle {
+    commands: Sender<Command>,
+}
+
+/// A pending result of a command sent to the [`LuaHandle`] thread.
+///
+/// Can be awaited as a [`Future`] or waited for synchronously using [`HandleResponse::wait`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct HandleResponse<R> {
+    slot: Arc<Slot<R>>,
+}
+
+struct Slot<R> {
+    state: Mutex<SlotState<R>>,
+    ready: Condvar,
+}
+
+struct SlotState<R> {
+    result: Option<Result<R>>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+// Sending half of the response, marks the response as closed when dropped
+struct Responder<R>(Arc<Slot<R>>);
+
+impl LuaHandle {
+    /// Starts a new thread with a Lua instance created by [`Lua::new`].
+    pub fn new() -> Result<Self> {
+        Self::with(|| Ok(Lua::new()))
+    }
+
+    /// Starts a new thread with a Lua instance created by `init`.
+    ///
+    /// `init` is called on the new thread, so the instance can be configured there (eg. by
+    /// loading libraries or registering functions). Returns an error if `init` fails.
+    pub fn with<F>(init: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<Lua> + Send + 'static,
+    {
+        let (commands, receiver) = mpsc::channel::<Command>();
+        let (init_tx, init_rx) = mpsc::sync_channel(1);
+        thread::Builder::new()
+            .name("mlua".to_string())
+            .spawn(move || {
+                let lua = catch_unwind(AssertUnwindSafe(init));
+                let lua = match lua.unwrap_or_else(|p| Err(Error::from_panic(p))) {
+                    Ok(lua) => lua,
+                    Err(err) => {
+                        let _ = init_tx.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = init_tx.send(Ok(()));
+                for command in receiver {
+                    command(&lua);
+                }
+            })
+            .map_err(Error::external)?;
+        init_rx.recv().unwrap_or_else(|_| Err(stopped_error()))?;
+        Ok(LuaHandle { commands })
+    }
+
+    /// Executes `f` with the Lua instance on the handle thread.
+    ///
+    /// The result must not contain Lua values (eg. [`Table`] or [`Function`]), they are bound to
+    /// the Lua instance and must stay on the handle thread.
+    ///
+    /// Rust panics in `f` are returned as [`Error::CallbackPanic`] and do not stop the thread.
+    ///
+    /// [`Table`]: crate::Table
+    pub fn exec<R, F>(&self, f: F) -> HandleResponse<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Lua) -> Result<R> + Send + 'static,
+    {
+        let slot = Arc::new(Slot {
+            state: Mutex::new(SlotState {
+                result: None,
+                closed: false,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        });
+        let responder = Responder(slot.clone());
+        // If the thread has stopped, the responder is dropped and the response is closed
+        let _ = self.commands.send(Box::new(move |lua| {
+            let result = catch_unwind(AssertUnwindSafe(|| f(lua)));
+            responder.send(result.unwrap_or_else(|p| Err(Error::from_panic(p))));
+        }));
+        HandleResponse { slot }
+    }
+
+    /// Calls the global function `name` with the provided arguments.
+    pub fn call<A, R>(&self, name: impl Into<StdString>, args: A) -> HandleResponse<R>
+    where
+        A: IntoLuaMulti + Send + 'static,
+        R: FromLuaMulti + Send + 'static,
+    {
+        let name = name.into();
+        self.exec(move |lua| lua.globals().get::<_, Function>(name)?.call(args))
+    }
+
+    /// Loads and evaluates a chunk (see [`Chunk::eval`]), returning the result.
+    ///
+    /// [`Chunk::eval`]: crate::Chunk::eval
+    #[track_caller]
+    pub fn load<R>(&self, chunk: impl AsChunk<'static> + Send + 'static) -> HandleResponse<R>
+    where
+        R: FromLuaMulti + Send + 'static,
+    {
+        let name = chunk
+            .name()
+            .unwrap_or_else(|| Location::caller().to_string());
+        self.exec(move |lua| lua.load(chunk).set_name(name).eval())
+    }
+
+    /// Returns the value of the global variable `name`.
+    pub fn get_global<R>(&self, name: impl Into<StdString>) -> HandleResponse<R>
+    where
+        R: FromLua + Send + 'static,
+    {
+        let name = name.into();
+        self.exec(move |lua| lua.globals().get(name))
+    }
+
+    /// Sets the global variable `name` to `value`.
+    pub fn set_global<V>(&self, name: impl Into<StdString>, value: V) -> HandleResponse<()>
+    where
+        V: IntoLua + Send + 'static,
+    {
+        let name = name.into();
+        self.exec(move |lua| lua.globals().set(name, value))
+    }
+}
+
+impl fmt::Debug for LuaHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LuaHandle").finish_non_exhaustive()
+    }
+}
+
+impl<R> HandleResponse<R> {
+    /// Blocks the current thread until the result is ready.
+    pub fn wait(self) -> Result<R> {
+        let mut state = mlua_expect!(self.slot.state.lock(), "response slot poisoned");
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            if state.closed {
+                return Err(stopped_error());
+            }
+            state = mlua_expect!(self.slot.ready.wait(state), "response slot poisoned");
+        }
+    }
+}
+
+impl<R> Future for HandleResponse<R> {
+    type Output = Result<R>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = mlua_expect!(self.slot.state.lock(), "response slot poisoned");
+        if let Some(result) = state.result.take() {
+            return Poll::Ready(result);
+        }
+        if state.closed {
+            return Poll::Ready(Err(stopped_error()));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<R> fmt::Debug for HandleResponse<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HandleResponse").finish_non_exhaustive()
+    }
+}
+
+impl<R> Responder<R> {
+    fn send(self, result: Result<R>) {
+        let mut state = mlua_expect!(self.0.state.lock(), "response slot poisoned");
+        state.result = Some(result);
+    }
+}
+
+impl<R> Drop for Responder<R> {
+    fn drop(&mut self) {
+        let mut state = mlua_expect!(self.0.state.lock(), "response slot poisoned");
+        state.closed = true;
+        self.0.ready.notify_all();
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn stopped_error() -> Error {
+    Error::RuntimeError("Lua handle thread has stopped".to_string())
+}
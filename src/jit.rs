@@ -0,0 +1,208 @@
+use std::fmt;
+use std::os::raw::c_int;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::ffi;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::multi::Variadic;
+use crate::table::Table;
+use crate::util::{check_stack, StackGuard};
+use crate::value::{Nil, Value};
+
+// Registry key of the `jit` module loaded by the controller
+const JIT_MODULE_KEY: &str = "__mlua_jit";
+
+/// Controller of the LuaJIT compiler.
+///
+/// Provides the functionality of the [`jit`] library without loading it into the Lua state,
+/// so the JIT compiler can be tuned or disabled for problematic code paths by the host
+/// application only.
+///
+/// Returned by [`Lua::jit`].
+///
+/// Requires `feature = "luajit"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Function, Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let jit = lua.jit();
+///
+/// // Do not compile the function and all functions defined in it
+/// let f: Function = lua.load("return function() for i = 1, 100 do end end").eval()?;
+/// jit.off_function(&f, true)?;
+///
+/// // Tune optimization parameters (same as `jit.opt.start("hotloop=10")`)
+/// jit.set_opt(&["hotloop=10"])?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`jit`]: https://luajit.org/ext_jit.html
+#[cfg_attr(docsrs, doc(cfg(feature = "luajit")))]
+#[derive(Clone)]
+pub struct Jit {
+    lua: Lua,
+}
+
+impl Lua {
+    /// Returns a controller of the LuaJIT compiler.
+    ///
+    /// Note that loading the `jit` library (eg. using [`Lua::load_from_std_lib`]) resets the
+    /// JIT compiler state to defaults.
+    ///
+    /// Requires `feature = "luajit"`
+    #[cfg_attr(docsrs, doc(cfg(feature = "luajit")))]
+    pub fn jit(&self) -> Jit {
+        Jit { lua: self.clone() }
+    }
+}
+
+impl Jit {
+    /// Turns the whole JIT compiler on (the default).
+    ///
+    /// Equivalent to `jit.on()`.
+    pub fn on(&self) -> Result<()> {
+        self.set_engine_mode(ffi::LUAJIT_MODE_ON)
+    }
+
+    /// Turns the whole JIT compiler off.
+    ///
+    /// Already compiled code is not flushed. Equivalent to `jit.off()`.
+    pub fn off(&self) -> Result<()> {
+        self.set_engine_mode(ffi::LUAJIT_MODE_OFF)
+    }
+
+    /// Flushes the whole cache of compiled code.
+    ///
+    /// Equivalent to `jit.flush()`.
+    pub fn flush(&self) -> Result<()> {
+        self.set_engine_mode(ffi::LUAJIT_MODE_FLUSH)
+    }
+
+    /// Enables JIT compilation of the Lua function.
+    ///
+    /// If `recursive` is `true`, the mode is also set for all functions defined (recursively)
+    /// inside it. Equivalent to `jit.on(func, recursive)`.
+    pub fn on_function(&self, func: &Function, recursive: bool) -> Result<()> {
+        self.set_function_mode(func, recursive, ffi::LUAJIT_MODE_ON)
+    }
+
+    /// Disables JIT compilation of the Lua function and flushes already compiled code.
+    ///
+    /// If `recursive` is `true`, the mode is also set for all functions defined (recursively)
+    /// inside it. Equivalent to `jit.off(func, recursive)`.
+    pub fn off_function(&self, func: &Function, recursive: bool) -> Result<()> {
+        self.set_function_mode(func, recursive, ffi::LUAJIT_MODE_OFF)
+    }
+
+    /// Flushes the code compiled for the Lua function.
+    ///
+    /// If `recursive` is `true`, the code of all functions defined (recursively) inside it is
+    /// flushed too. Equivalent to `jit.flush(func, recursive)`.
+    pub fn flush_function(&self, func: &Function, recursive: bool) -> Result<()> {
+        self.set_function_mode(func, recursive, ffi::LUAJIT_MODE_FLUSH)
+    }
+
+    /// Returns `true` if the JIT compiler is turned on, and the list of enabled CPU-specific
+    /// features and optimizations.
+    ///
+    /// Equivalent to `jit.status()`.
+    pub fn status(&self) -> Result<(bool, Vec<StdString>)> {
+        let status: Function = self.module()?.raw_get("status")?;
+        let (enabled, flags): (bool, Variadic<StdString>) = status.call(())?;
+        Ok((enabled, flags.into_iter().collect()))
+    }
+
+    /// Sets optimization level, flags and parameters of the JIT compiler.
+    ///
+    /// Each argument is either an optimization level (eg. `"3"`), a flag (eg. `"-fold"`) or a
+    /// parameter (eg. `"hotloop=10"`). Equivalent to `jit.opt.start(...)`.
+    ///
+    /// See the [`jit.opt`] documentation for supported values.
+    ///
+    /// [`jit.opt`]: https://luajit.org/running.html#opt_O
+    pub fn set_opt(&self, params: &[&str]) -> Result<()> {
+        let opt: Option<Table> = self.module()?.raw_get("opt")?;
+        let opt = opt.ok_or_else(|| Error::RuntimeError("JIT compiler disabled".to_string()))?;
+        let start: Function = opt.raw_get("start")?;
+        start.call(Variadic::from_iter(params.iter().copied()))
+    }
+
+    fn set_engine_mode(&self, mode: c_int) -> Result<()> {
+        // Loading the module resets the engine state, so it must be loaded first
+        self.module()?;
+
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            let mode = ffi::LUAJIT_MODE_ENGINE | mode;
+            if protect_lua!(state, 0, 0, |state| ffi::luaJIT_setmode(state, 0, mode))? == 0 {
+                return Err(Error::RuntimeError("JIT compiler disabled".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    fn set_function_mode(&self, func: &Function, recursive: bool, mode: c_int) -> Result<()> {
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            let mode = match recursive {
+                true => ffi::LUAJIT_MODE_ALLFUNC | mode,
+                false => ffi::LUAJIT_MODE_FUNC | mode,
+            };
+            self.lua.push_ref(&func.0);
+            if protect_lua!(state, 1, 0, |state| ffi::luaJIT_setmode(state, -1, mode))? == 0 {
+                return Err(Error::RuntimeError(
+                    "cannot change JIT mode of a C function".to_string(),
+                ));
+            }
+            Ok(())
+        }
+    }
+
+    // Returns the `jit` module table.
+    //
+    // If the module is not loaded into the Lua state, it's loaded privately (without setting
+    // the `jit` global variable) and stored in the registry.
+    fn module(&self) -> Result<Table> {
+        let lua = &self.lua;
+        if let Some(module) = lua.named_registry_value::<Option<Table>>(JIT_MODULE_KEY)? {
+            return Ok(module);
+        }
+        let loaded: Table = lua.named_registry_value("_LOADED")?;
+        if let Some(module) = loaded.raw_get::<_, Option<Table>>(ffi::LUA_JITLIBNAME)? {
+            return Ok(module);
+        }
+
+        let globals = lua.globals_storage()?;
+        let global = globals.raw_get::<_, Value>(ffi::LUA_JITLIBNAME)?;
+        let open = unsafe { lua.create_c_function(ffi::luaopen_jit)? };
+        let res = open.call::<_, ()>(ffi::LUA_JITLIBNAME);
+        // The module registers itself in the `_LOADED` table
+        let module = loaded.raw_get::<_, Table>(ffi::LUA_JITLIBNAME);
+        globals.raw_set(ffi::LUA_JITLIBNAME, global)?;
+        loaded.raw_set(ffi::LUA_JITLIBNAME, Nil)?;
+        loaded.raw_set(format!("{}.opt", ffi::LUA_JITLIBNAME), Nil)?;
+
+        res?;
+        let module = module?;
+        lua.set_named_registry_value(JIT_MODULE_KEY, module.clone())?;
+        Ok(module)
+    }
+}
+
+impl fmt::Debug for Jit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Jit").finish_non_exhaustive()
+    }
+}
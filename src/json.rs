@@ -0,0 +1,74 @@
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::serde::LuaSerdeExt;
+use crate::string::String;
+use crate::table::Table;
+use crate::value::Value;
+
+impl Lua {
+    /// Installs the `json` module implemented on top of the serde support.
+    ///
+    /// Similar to the standard libraries, the module is set as the global `json` variable and
+    /// registered in `package.loaded`, so `require("json")` works as well.
+    ///
+    /// The module has the following members:
+    /// - `json.encode(value [, pretty])`: encodes a Lua value to a JSON string
+    /// - `json.decode(s)`: decodes a JSON string to a Lua value
+    /// - `json.null`: the [`null`] value, representing JSON `null`
+    ///
+    /// Encoding follows the rules of the [`Value`] serializer: tables with the
+    /// [`array_metatable`] or with a sequence of values are encoded as arrays, other tables as
+    /// objects. Decoded arrays have the [`array_metatable`] attached, so they are encoded back
+    /// as arrays even when empty.
+    ///
+    /// Requires `feature = "json"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.enable_json_module()?;
+    ///
+    /// let s: String = lua
+    ///     .load(r#"
+    ///         local t = json.decode('{"list": [], "value": null}')
+    ///         assert(t.value == json.null)
+    ///         return json.encode(t.list)
+    ///     "#)
+    ///     .eval()?;
+    /// assert_eq!(s, "[]");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`null`]: crate::LuaSerdeExt::null
+    /// [`array_metatable`]: crate::LuaSerdeExt::array_metatable
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn enable_json_module(&self) -> Result<()> {
+        let module = self.create_table_with_capacity(0, 3)?;
+
+        let encode = |lua: &Lua, (value, pretty): (Value, Option<bool>)| {
+            let json = match pretty.unwrap_or_default() {
+                true => serde_json::to_vec_pretty(&value),
+                false => serde_json::to_vec(&value),
+            };
+            lua.create_string(json.map_err(|err| Error::SerializeError(err.to_string()))?)
+        };
+        module.raw_set("encode", self.create_function(encode)?)?;
+
+        let decode = |lua: &Lua, s: String| {
+            let json = serde_json::from_slice::<serde_json::Value>(s.as_bytes())
+                .map_err(|err| Error::DeserializeError(err.to_string()))?;
+            lua.to_value(&json)
+        };
+        module.raw_set("decode", self.create_function(decode)?)?;
+        module.raw_set("null", self.null())?;
+
+        if let Some(loaded) = self.named_registry_value::<Option<Table>>("_LOADED")? {
+            loaded.raw_set("json", module.clone())?;
+        }
+        self.globals_storage()?.raw_set("json", module)
+    }
+}
@@ -81,68 +81,164 @@
 #[macro_use]
 mod macros;
 
+mod analyze;
+mod args;
+#[cfg(feature = "luau")]
+mod buffer;
+#[cfg(not(feature = "luau"))]
+mod bytecode;
+#[cfg(feature = "async")]
+mod channel;
 mod chunk;
+#[cfg(feature = "codec")]
+mod codec;
+#[cfg(feature = "console")]
+mod console;
 mod conversion;
+#[cfg(not(feature = "luau"))]
+mod debugger;
+mod deprecation;
+mod deterministic;
+mod enums;
 mod error;
+mod expression;
 mod ffi;
+#[cfg(feature = "bitflags")]
+mod flags;
 mod function;
+mod globals;
+mod handle;
 mod hook;
+#[cfg(feature = "luajit")]
+mod jit;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "log")]
+mod logger;
 mod lua;
 #[cfg(feature = "luau")]
 mod luau;
+mod math;
+mod memory;
+mod metrics;
+#[cfg(feature = "serialize")]
+mod mirror;
 mod multi;
+mod overrides;
+#[cfg(feature = "persist")]
+mod persist;
+#[cfg(feature = "regex")]
+mod regexp;
+mod reload;
+mod repl;
+mod scheduler;
 mod scope;
+mod signal;
 mod stdlib;
 mod string;
 mod table;
+mod template;
 mod thread;
+mod type_registry;
+#[cfg(feature = "typegen")]
+mod typegen;
 mod types;
 mod userdata;
 mod userdata_ext;
 mod userdata_impl;
+mod utf8;
 mod util;
 mod value;
+mod vfs;
+#[cfg(feature = "watch")]
+mod watcher;
 
+pub mod ffi_util;
 pub mod prelude;
 
 pub use crate::{ffi::lua_CFunction, ffi::lua_State};
 
-pub use crate::chunk::{AsChunk, Chunk, ChunkMode};
-pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result};
-pub use crate::function::{Function, FunctionInfo};
+pub use crate::analyze::{Diagnostic, DiagnosticKind};
+pub use crate::args::{NonEmptyString, Ranged};
+pub use crate::chunk::{AsChunk, Chunk, ChunkMode, SourceMap};
+#[cfg(feature = "codec")]
+pub use crate::codec::Encoding;
+#[cfg(feature = "console")]
+pub use crate::console::DebugConsole;
+pub use crate::enums::{EnumValue, LuaEnum};
+pub use crate::error::{
+    Error, ErrorContext, ExternalError, ExternalResult, Result, SyntaxErrorInfo, TracebackFrame,
+};
+#[cfg(feature = "bitflags")]
+pub use crate::flags::Flags;
+pub use crate::function::{Function, FunctionInfo, OwnedFunction};
+pub use crate::globals::{GlobalAccess, GlobalPolicy};
+pub use crate::handle::{HandleResponse, LuaHandle};
 pub use crate::hook::{Debug, DebugEvent, DebugNames, DebugSource, DebugStack};
-pub use crate::lua::{GCMode, Lua, LuaOptions};
-pub use crate::multi::Variadic;
-pub use crate::scope::Scope;
-pub use crate::stdlib::StdLib;
-pub use crate::string::String;
-pub use crate::table::{Table, TableExt, TablePairs, TableSequence};
-pub use crate::thread::{Thread, ThreadStatus};
-pub use crate::types::{Integer, LightUserData, Number, RegistryKey};
+pub use crate::lua::{
+    Backend, Capability, ErrorPropagation, GCMode, Lua, LuaOptions, PanicPolicy, RefThreadStats,
+};
+pub use crate::memory::{MemoryEvent, MemoryEventKind, MemoryTriggers};
+pub use crate::metrics::{CallbackMetrics, MetricsKind};
+pub use crate::multi::{AtLeast, Multi, Variadic};
+pub use crate::repl::{ReplOutput, ReplState};
+pub use crate::scope::{Scope, ScopeLeak, UserDataTag};
+pub use crate::signal::Signal;
+pub use crate::stdlib::{StdLib, StdLibFilter};
+pub use crate::string::{OwnedString, String, StringChars, StringMatches, Utf8Policy};
+pub use crate::table::{OwnedTable, Table, TableExt, TablePairs, TableSequence};
+pub use crate::thread::{OwnedThread, Thread, ThreadStatus};
+pub use crate::type_registry::TypeRegistry;
+pub use crate::types::{Integer, LightUserData, Number, RegistryKey, Tagged};
 pub use crate::userdata::{
-    AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMetatable, UserDataMethods,
-    UserDataRef, UserDataRefMut,
+    AnyUserData, MetaMethod, OwnedAnyUserData, UserData, UserDataFields, UserDataMetatable,
+    UserDataMethods, UserDataRef, UserDataRefMut,
 };
 pub use crate::userdata_ext::AnyUserDataExt;
 pub use crate::userdata_impl::UserDataRegistrar;
 pub use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
+pub use crate::vfs::{MemoryVfs, Vfs};
+#[cfg(feature = "watch")]
+pub use crate::watcher::ScriptWatcher;
 
+#[cfg(not(feature = "luau"))]
+pub use crate::bytecode::Instruction;
+#[cfg(not(feature = "luau"))]
+pub use crate::debugger::{DebugStop, DebugVariable, Debugger, StepAction, StopReason};
 #[cfg(not(feature = "luau"))]
 pub use crate::hook::HookTriggers;
 
+#[cfg(any(not(feature = "module"), docsrs))]
+pub use crate::lua::StateOwnership;
+
+#[cfg(feature = "luajit")]
+#[cfg_attr(docsrs, doc(cfg(feature = "luajit")))]
+pub use crate::jit::Jit;
+
 #[cfg(any(feature = "luau", doc))]
 #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
 pub use crate::{chunk::Compiler, function::CoverageInfo, types::VmState};
 
+#[cfg(feature = "luau")]
+#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+pub use crate::{buffer::LuauBuffer, luau::Require};
+
 #[cfg(feature = "async")]
-pub use crate::thread::AsyncThread;
+pub use crate::{
+    channel::{ChannelReceiver, ChannelSender},
+    thread::{AsyncThread, PollPending},
+};
 
 #[cfg(feature = "serialize")]
 #[doc(inline)]
 pub use crate::serde::{
-    de::Options as DeserializeOptions, ser::Options as SerializeOptions, LuaSerdeExt,
+    de::Options as DeserializeOptions, de::StringPool, ser::EnumRepr,
+    ser::Options as SerializeOptions, LuaSerdeExt,
 };
 
+#[cfg(feature = "serialize")]
+pub use crate::{mirror::Mirror, userdata::UserDataSerialize};
+
 #[cfg(feature = "serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
 pub mod serde;
@@ -152,10 +248,6 @@ pub mod serde;
 #[macro_use]
 extern crate mlua_derive;
 
-// Unstable features
-#[cfg(all(feature = "unstable", not(feature = "send")))]
-pub use crate::{function::OwnedFunction, table::OwnedTable, userdata::OwnedAnyUserData};
-
 /// Create a type that implements [`AsChunk`] and can capture Rust variables.
 ///
 /// This macro allows to write Lua code directly in Rust code.
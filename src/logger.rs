@@ -0,0 +1,124 @@
+use std::cell::RefCell;
+use std::string::String as StdString;
+
+use crate::error::Result;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::multi::Variadic;
+use crate::value::Value;
+
+impl Lua {
+    /// Routes Lua logging to the [`log`] crate using `target` as the log target.
+    ///
+    /// Replaces the global `print` function with one that emits records at the `Info` level,
+    /// installs a warning function (see [`Lua::set_warning_function`]) that emits records at
+    /// the `Warn` level, and adds a global `log` table with `error`, `warn`, `info`, `debug` and
+    /// `trace` functions.
+    ///
+    /// Each record has the chunk name and current line of the calling Lua function as its
+    /// `file` and `line` fields. Applications that use [`tracing`] can receive these records
+    /// through the `tracing-log` adapter.
+    ///
+    /// Requires `feature = "log"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.attach_logger("my_app::lua")?;
+    /// lua.load(r#"
+    ///     print("hello", 123)
+    ///     log.debug("debug message")
+    /// "#).exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`log`]: https://docs.rs/log
+    /// [`tracing`]: https://docs.rs/tracing
+    #[cfg_attr(docsrs, doc(cfg(feature = "log")))]
+    pub fn attach_logger(&self, target: impl Into<StdString>) -> Result<()> {
+        let target: StdString = target.into();
+        let globals = self.globals_storage()?;
+
+        let print = {
+            let target = target.clone();
+            self.create_function(move |lua, args: Variadic<Value>| {
+                let tostring: Function = lua.globals_storage()?.raw_get("tostring")?;
+                let mut msg = StdString::new();
+                for (i, arg) in args.into_iter().enumerate() {
+                    if i > 0 {
+                        msg.push('\t');
+                    }
+                    let s: crate::String = tostring.call(arg)?;
+                    msg.push_str(&s.to_string_lossy());
+                }
+                log_record(lua, &target, log::Level::Info, &msg);
+                Ok(())
+            })?
+        };
+        globals.raw_set("print", print)?;
+
+        let log_table = self.create_table()?;
+        for (name, level) in [
+            ("error", log::Level::Error),
+            ("warn", log::Level::Warn),
+            ("info", log::Level::Info),
+            ("debug", log::Level::Debug),
+            ("trace", log::Level::Trace),
+        ] {
+            let target = target.clone();
+            let func = self.create_function(move |lua, msg: crate::String| {
+                log_record(lua, &target, level, &msg.to_string_lossy());
+                Ok(())
+            })?;
+            log_table.raw_set(name, func)?;
+        }
+        globals.raw_set("log", log_table)?;
+
+        // Warnings can be split into several pieces, accumulate them until the last one
+        let buffer = RefCell::new(StdString::new());
+        self.set_warning_function(move |lua, msg, tocont| {
+            let mut buffer = buffer.borrow_mut();
+            buffer.push_str(&msg.to_string_lossy());
+            if !tocont {
+                log_record(lua, &target, log::Level::Warn, &buffer);
+                buffer.clear();
+            }
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+fn log_record(lua: &Lua, target: &str, level: log::Level, msg: &str) {
+    if level > log::max_level() {
+        return;
+    }
+
+    // Level 0 is the Rust callback itself, level 1 is the Lua caller
+    let (file, line) = match lua.inspect_stack(1) {
+        Some(debug) => {
+            let file = debug
+                .source()
+                .short_src
+                .map(|s| StdString::from_utf8_lossy(s).into_owned());
+            let line = debug.curr_line();
+            (file, if line > 0 { Some(line as u32) } else { None })
+        }
+        None => (None, None),
+    };
+
+    log::logger().log(
+        &log::Record::builder()
+            .args(format_args!("{msg}"))
+            .level(level)
+            .target(target)
+            .file(file.as_deref())
+            .line(line)
+            .build(),
+    );
+}
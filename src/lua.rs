@@ -1,34 +1,43 @@
 use std::any::{Any, TypeId};
-use std::cell::{Ref, RefCell, RefMut, UnsafeCell};
+use std::borrow::Cow;
+use std::cell::{Cell, Ref, RefCell, RefMut, UnsafeCell};
 use std::ffi::{CStr, CString};
 use std::fmt;
+use std::io;
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
 use std::os::raw::{c_char, c_int, c_void};
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe, Location};
 use std::ptr::NonNull;
+use std::string::String as StdString;
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::sync::{Arc, Mutex};
 use std::{mem, ptr, str};
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use crate::chunk::{AsChunk, Chunk, ChunkMode};
-use crate::error::{Error, Result};
+use crate::chunk::{rewrite_source_locations, short_source, AsChunk, Chunk, ChunkMode, SourceMap};
+use crate::error::{self, Error, Result};
 use crate::ffi;
 use crate::function::Function;
 use crate::hook::Debug;
+use crate::memory::{allocator, MemoryEvent, MemoryInfo, MemoryTriggers, MemoryWatch};
+use crate::metrics::MetricsRegistry;
 use crate::scope::Scope;
-use crate::stdlib::StdLib;
+use crate::stdlib::{StdLib, StdLibFilter};
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
+#[cfg(not(feature = "module"))]
+use crate::types::CloseCallback;
 use crate::types::{
-    Callback, CallbackUpvalue, DestructedUserdata, Integer, LightUserData, LuaRef, MaybeSend,
-    Number, RegistryKey,
+    Callback, CallbackUpvalue, DestructedUserdata, GlobalsInterceptor, Integer, LightUserData,
+    LuaRef, MaybeSend, MemoryObserverCallback, Number, RegistryKey, WarnCallback,
 };
 use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataCell};
-use crate::userdata_impl::{UserDataProxy, UserDataRegistrar};
+use crate::userdata_impl::{
+    get_function_name, with_userdata_ref, UserDataProxy, UserDataRegistrar,
+};
 use crate::util::{
     self, assert_stack, callback_error, check_stack, get_destructed_userdata_metatable,
     get_gc_metatable, get_gc_userdata, get_main_state, get_userdata, init_error_registry,
@@ -38,31 +47,42 @@ use crate::util::{
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
 
 #[cfg(not(feature = "lua54"))]
-use crate::util::push_userdata;
+use crate::{multi::Variadic, util::push_userdata};
 #[cfg(feature = "lua54")]
-use crate::{types::WarnCallback, userdata::USER_VALUE_MAXSLOT, util::push_userdata_uv};
+use crate::{userdata::USER_VALUE_MAXSLOT, util::push_userdata_uv};
 
 #[cfg(not(feature = "luau"))]
 use crate::{hook::HookTriggers, types::HookCallback};
 
 #[cfg(feature = "luau")]
-use crate::types::InterruptCallback;
+use crate::{buffer::LuauBuffer, types::InterruptCallback};
 #[cfg(any(feature = "luau", doc))]
 use crate::{chunk::Compiler, types::VmState};
 
 #[cfg(feature = "async")]
 use {
-    crate::types::{AsyncCallback, AsyncCallbackUpvalue, AsyncPollUpvalue},
+    crate::thread::PollPending,
+    crate::types::{AsyncCallback, AsyncCallbackUpvalue, AsyncPollUpvalue, YieldAsyncHook},
+    crate::userdata_impl::{PendingTask, TrackedFuture},
     futures_task::noop_waker_ref,
-    futures_util::future::{self, TryFutureExt},
+    futures_util::future::{self, LocalBoxFuture},
+    futures_util::io::AsyncRead,
     std::{
         future::Future,
-        task::{Context, Poll, Waker},
+        pin::Pin,
+        rc::{Rc, Weak},
+        task::{Context, Poll, Wake, Waker},
+        thread,
+        time::Duration,
     },
 };
 
 #[cfg(feature = "serialize")]
-use serde::Serialize;
+use {
+    crate::types::UserDataSerializer,
+    crate::userdata::{UserDataSerialize, UserDataSerializeWrapper},
+    serde::Serialize,
+};
 
 /// Top level Lua struct which represents an instance of Lua VM.
 #[derive(Clone)]
@@ -120,7 +140,12 @@ pub(crate) struct ExtraData {
     inner: Option<ManuallyDrop<Arc<LuaInner>>>,
 
     registered_userdata: FxHashMap<TypeId, c_int>,
+    // Types registered using `Lua::register_userdata_type`
+    registered_userdata_types: FxHashSet<TypeId>,
+    // Metatables created by `Lua::create_any_userdata_with`, keyed by data and closure types
+    registered_userdata_with: FxHashMap<(TypeId, TypeId), c_int>,
     registered_userdata_mt: FxHashMap<*const c_void, Option<TypeId>>,
+    registered_nonstatic_userdata: FxHashMap<TypeId, c_int>,
     last_checked_userdata_mt: (*const c_void, Option<TypeId>),
 
     // When Lua instance dropped, setting `None` would prevent collecting `RegistryKey`s
@@ -136,15 +161,22 @@ pub(crate) struct ExtraData {
     mem_info: Option<NonNull<MemoryInfo>>,
 
     ref_thread: *mut ffi::lua_State,
+    // Registry reference to the ref thread
+    ref_thread_ref: c_int,
     ref_stack_size: c_int,
     ref_stack_top: c_int,
+    // Highest `ref_stack_top` reached
+    ref_stack_max: c_int,
     ref_free: Vec<c_int>,
+    // Stack slots of the ref thread values, indexed by handle (0 for free handles)
+    ref_slots: Vec<c_int>,
+    ref_free_handles: Vec<c_int>,
 
-    // Pool of `WrappedFailure` enums in the ref thread (as userdata)
+    // Pool of `WrappedFailure` enums in the ref thread (as userdata), stored as handles
     wrapped_failure_pool: Vec<c_int>,
     // Pool of `MultiValue` containers
     multivalue_pool: Vec<MultiValue>,
-    // Pool of `Thread`s (coroutines) for async execution
+    // Pool of `Thread`s (coroutines) for async execution, stored as handles
     #[cfg(feature = "async")]
     thread_pool: Vec<c_int>,
 
@@ -154,10 +186,26 @@ pub(crate) struct ExtraData {
     // Waker for polling futures
     #[cfg(feature = "async")]
     waker: NonNull<Waker>,
+    // Time budget of async execution
+    #[cfg(feature = "async")]
+    async_timeout: Option<Duration>,
+    // Size (in kbytes) of the GC step performed after every resume of async threads
+    #[cfg(feature = "async")]
+    gc_budget: Option<c_int>,
+    // Hook called when an async thread returns `Poll::Pending`
+    #[cfg(feature = "async")]
+    yield_async_hook: Option<YieldAsyncHook>,
+    // Pending async method calls by userdata pointer
+    #[cfg(feature = "async")]
+    userdata_tasks: FxHashMap<*const c_void, Vec<Weak<PendingTask>>>,
+    // Async thread periodically suspended to check its time budget
+    #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+    preempted_thread: *mut ffi::lua_State,
+    #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+    thread_preempted: bool,
 
     #[cfg(not(feature = "luau"))]
     hook_callback: Option<HookCallback>,
-    #[cfg(feature = "lua54")]
     warn_callback: Option<WarnCallback>,
     #[cfg(feature = "luau")]
     interrupt_callback: Option<InterruptCallback>,
@@ -166,12 +214,63 @@ pub(crate) struct ExtraData {
     sandboxed: bool,
     #[cfg(feature = "luau")]
     compiler: Option<Compiler>,
+
+    // Source maps of loaded chunks, keyed by the chunk short source name
+    source_maps: FxHashMap<StdString, Box<dyn SourceMap>>,
+
+    panic_policy: PanicPolicy,
+
+    // Callback and chunk metrics, collected if enabled
+    metrics: Option<Arc<MetricsRegistry>>,
+
+    memory_observer: Option<MemoryObserverCallback>,
+
+    #[cfg(not(feature = "module"))]
+    ownership: StateOwnership,
+    #[cfg(not(feature = "module"))]
+    close_callback: Option<CloseCallback>,
+    // Callbacks userdata and atom function set by the host application before attaching
+    #[cfg(feature = "luau")]
+    host_callbacks: (
+        *mut c_void,
+        Option<unsafe extern "C-unwind" fn(*const c_char, usize) -> i16>,
+    ),
+
+    // Interceptor of global variables access
+    globals_interceptor: Option<GlobalsInterceptor>,
+    globals_proxy_installed: bool,
+
+    // Types of tagged light userdata pointers
+    light_userdata_tags: FxHashMap<*const c_void, TypeId>,
+
+    // Serializers of userdata types registered using `Lua::register_userdata_serializer`
+    #[cfg(feature = "serialize")]
+    userdata_serializers: FxHashMap<TypeId, UserDataSerializer>,
+
+    // Shapes of registered userdata types (in order of registration) for type declarations
+    #[cfg(feature = "typegen")]
+    userdata_types: Vec<(TypeId, crate::typegen::UserDataTypeInfo)>,
 }
 
-#[derive(Default)]
-struct MemoryInfo {
-    used_memory: isize,
-    memory_limit: isize,
+/// Usage statistics of the auxiliary stack storing handles of Lua values.
+///
+/// Every handle ([`Table`], [`Function`], etc.) occupies a slot of the stack. Slots of dropped
+/// handles become holes, which are reused by new handles.
+///
+/// Returned by [`Lua::ref_thread_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RefThreadStats {
+    /// Number of slots the stack can hold without growing.
+    pub capacity: usize,
+    /// Number of slots in use, including holes.
+    pub used: usize,
+    /// Number of free slots (holes) below the top of the stack.
+    pub holes: usize,
+    /// Number of live handles, including handles kept internally by mlua.
+    pub live: usize,
+    /// Highest number of slots in use since the creation of the Lua instance.
+    pub high_water_mark: usize,
 }
 
 /// Mode of the Lua garbage collector (GC).
@@ -191,6 +290,120 @@ pub enum GCMode {
     Generational,
 }
 
+/// Optional features of a Lua instance, see [`Lua::supports`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Memory limits ([`Lua::set_memory_limit`]) and memory observers
+    /// ([`Lua::set_memory_observer`]).
+    ///
+    /// Requires the Lua state to use the Rust allocator, which is not the case in module mode or
+    /// for non-vendored LuaJIT.
+    MemoryLimit,
+    /// Debug hooks ([`Lua::set_hook`]).
+    ///
+    /// Not supported by Luau, and requires the main thread in Lua 5.1/LuaJIT module mode.
+    Hooks,
+    /// Integer subtype of numbers, see [`Value::Integer`].
+    ///
+    /// Supported by Lua 5.3 and 5.4. Other versions store integers as floats and convert them
+    /// to [`Integer`] (of `Integer::BITS` width) only at the API boundary.
+    Integers,
+    /// Native bitwise operators (`&`, `|`, `~`, `<<`, `>>`).
+    ///
+    /// Supported by Lua 5.3 and 5.4.
+    BitwiseOperators,
+    /// To-be-closed variables (`local x <close> = ...`).
+    ///
+    /// Supported by Lua 5.4.
+    ToBeClosed,
+    /// Native vector type, see `Value::Vector`.
+    ///
+    /// Supported by Luau.
+    Vectors,
+    /// Sandboxing Lua code using `Lua::sandbox`.
+    ///
+    /// Supported by Luau.
+    Sandbox,
+    /// Yielding across `pcall` and other C calls.
+    ///
+    /// Supported by all versions except Lua 5.1.
+    YieldAcrossC,
+}
+
+/// Lua implementation (backend) mlua is built with, see [`Lua::backend`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Lua 5.1
+    Lua51,
+    /// Lua 5.2
+    Lua52,
+    /// Lua 5.3
+    Lua53,
+    /// Lua 5.4
+    Lua54,
+    /// LuaJIT
+    LuaJIT,
+    /// Luau
+    Luau,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Lua51 => write!(f, "Lua 5.1"),
+            Backend::Lua52 => write!(f, "Lua 5.2"),
+            Backend::Lua53 => write!(f, "Lua 5.3"),
+            Backend::Lua54 => write!(f, "Lua 5.4"),
+            Backend::LuaJIT => write!(f, "LuaJIT"),
+            Backend::Luau => write!(f, "Luau"),
+        }
+    }
+}
+
+/// Mechanism used by the Lua implementation to raise errors, see [`Lua::error_propagation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorPropagation {
+    /// Errors are raised using `longjmp` (Lua and Luau compiled as C).
+    LongJmp,
+    /// Errors are raised by unwinding the stack using C++ exceptions (LuaJIT, or Lua compiled as
+    /// C++ when `feature = "cxx-exceptions"` is enabled).
+    Unwind,
+}
+
+/// Defines how Rust panics in callbacks are handled.
+///
+/// See [`Lua::set_panic_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Carry the panic through Lua code and resume it when control returns to Rust (default).
+    #[default]
+    Propagate,
+    /// Convert the panic to [`Error::CallbackPanic`] which can be handled like any other error.
+    ///
+    /// [`Error::CallbackPanic`]: crate::Error::CallbackPanic
+    ConvertToError,
+    /// Abort the process.
+    Abort,
+}
+
+/// Defines who owns a Lua state adopted using [`Lua::from_existing_state`].
+#[cfg(any(not(feature = "module"), docsrs))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "module"))))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StateOwnership {
+    /// The state is closed when the last [`Lua`] handle is dropped (default).
+    #[default]
+    Owned,
+    /// The state is owned by the host application and is never closed by mlua.
+    ///
+    /// When the last [`Lua`] handle is dropped, mlua releases its internal data stored in the
+    /// state and leaves the state usable for the host.
+    Borrowed,
+}
+
 /// Controls Lua interpreter behavior such as Rust panics handling.
 #[derive(Clone, Debug)]
 #[non_exhaustive]
@@ -220,6 +433,32 @@ pub struct LuaOptions {
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub thread_pool_size: usize,
+
+    /// Seed of the pseudo-random number generator used by `math.random`.
+    ///
+    /// If set, `math.random` and `math.randomseed` are replaced with functions backed by
+    /// a deterministic generator (SplitMix64), so scripts produce the same sequence of random
+    /// numbers on every run and on every platform. Useful for replays and lockstep simulations.
+    ///
+    /// Has effect only if the `math` library is loaded when the Lua state is created.
+    ///
+    /// Default: **None**
+    pub deterministic_seed: Option<u64>,
+
+    /// Make `pairs` iterate tables in the key insertion order.
+    ///
+    /// If enabled, the keys of every table iterated by `pairs` are kept in a shadow index.
+    /// Tables without a metatable get a hidden one (not returned by `getmetatable`), whose
+    /// `__newindex` metamethod appends new keys to the index, so keys inserted after the first
+    /// iteration are traversed in the insertion order. Keys that were stored before that, using
+    /// `rawset` or in tables having their own metatable are traversed after the tracked keys:
+    /// booleans first, then numbers and strings in ascending order, followed by other values in
+    /// the traversal order. This has a performance cost proportional to the table size.
+    ///
+    /// Tables with the `__pairs` metamethod and generalized iteration in Luau are not affected.
+    ///
+    /// Default: **false**
+    pub stable_pairs: bool,
 }
 
 impl Default for LuaOptions {
@@ -235,6 +474,8 @@ impl LuaOptions {
             catch_rust_panics: true,
             #[cfg(feature = "async")]
             thread_pool_size: 0,
+            deterministic_seed: None,
+            stable_pairs: false,
         }
     }
 
@@ -257,14 +498,32 @@ impl LuaOptions {
         self.thread_pool_size = size;
         self
     }
+
+    /// Sets [`deterministic_seed`] option.
+    ///
+    /// [`deterministic_seed`]: #structfield.deterministic_seed
+    #[must_use]
+    pub const fn deterministic(mut self, seed: u64) -> Self {
+        self.deterministic_seed = Some(seed);
+        self
+    }
+
+    /// Sets [`stable_pairs`] option.
+    ///
+    /// [`stable_pairs`]: #structfield.stable_pairs
+    #[must_use]
+    pub const fn stable_pairs(mut self, enabled: bool) -> Self {
+        self.stable_pairs = enabled;
+        self
+    }
 }
 
-#[cfg(feature = "async")]
-pub(crate) static ASYNC_POLL_PENDING: u8 = 0;
 pub(crate) static EXTRA_REGISTRY_KEY: u8 = 0;
 
 const WRAPPED_FAILURE_POOL_SIZE: usize = 64;
 const MULTIVALUE_POOL_SIZE: usize = 64;
+// Maximum number of tagged light userdata pointers
+const LIGHT_USERDATA_TAGS_LIMIT: usize = 1 << 16;
 
 /// Requires `feature = "send"`
 #[cfg(feature = "send")]
@@ -276,25 +535,70 @@ impl Drop for LuaInner {
     fn drop(&mut self) {
         unsafe {
             let extra = &mut *self.extra.get();
-            let drain_iter = extra.wrapped_failure_pool.drain(..);
-            #[cfg(feature = "async")]
-            let drain_iter = drain_iter.chain(extra.thread_pool.drain(..));
-            for index in drain_iter {
-                ffi::lua_pushnil(extra.ref_thread);
-                ffi::lua_replace(extra.ref_thread, index);
-                extra.ref_free.push(index);
+            if let Some(callback) = extra.close_callback.take() {
+                callback(self.main_state);
             }
-            #[cfg(feature = "luau")]
-            {
-                (*ffi::lua_callbacks(self.state())).userdata = ptr::null_mut();
+            let handles = mem::take(&mut extra.wrapped_failure_pool).into_iter();
+            #[cfg(feature = "async")]
+            let handles = handles.chain(mem::take(&mut extra.thread_pool));
+            for handle in handles {
+                ref_stack_free(extra, handle);
             }
             mlua_debug_assert!(
                 ffi::lua_gettop(extra.ref_thread) == extra.ref_stack_top
-                    && extra.ref_stack_top as usize == extra.ref_free.len(),
+                    && extra.ref_stack_top as usize == extra.ref_free.len()
+                    && extra.ref_slots.len() == extra.ref_free_handles.len(),
                 "reference leak detected"
             );
-            ffi::lua_close(self.main_state);
+            match extra.ownership {
+                StateOwnership::Owned => {
+                    #[cfg(feature = "luau")]
+                    {
+                        (*ffi::lua_callbacks(self.main_state)).userdata = ptr::null_mut();
+                    }
+                    ffi::lua_close(self.main_state);
+                }
+                StateOwnership::Borrowed => self.release(extra),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "module"))]
+impl LuaInner {
+    // Unlinks mlua internal data from the state which stays alive
+    unsafe fn release(&self, extra: &mut ExtraData) {
+        let state = self.main_state;
+
+        // Functions created by mlua may still be reachable from the state
+        extra.inner = None;
+        // The state still uses the allocator data, so it's leaked
+        extra.mem_info = None;
+
+        #[cfg(not(feature = "luau"))]
+        if extra.hook_callback.take().is_some() {
+            if let Some(main_state) = get_main_state(state) {
+                ffi::lua_sethook(main_state, None, 0, 0);
+            }
+        }
+        #[cfg(feature = "lua54")]
+        if extra.warn_callback.take().is_some() {
+            ffi::lua_setwarnf(state, None, ptr::null_mut());
+        }
+        #[cfg(feature = "luau")]
+        {
+            let callbacks = ffi::lua_callbacks(state);
+            (*callbacks).userdata = extra.host_callbacks.0;
+            (*callbacks).useratom = extra.host_callbacks.1;
+            if extra.interrupt_callback.take().is_some() {
+                (*callbacks).interrupt = None;
+            }
         }
+
+        ffi::luaL_unref(state, ffi::LUA_REGISTRYINDEX, extra.ref_thread_ref);
+        let extra_key = &EXTRA_REGISTRY_KEY as *const u8 as *const c_void;
+        ffi::lua_pushnil(state);
+        ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, extra_key);
     }
 }
 
@@ -396,64 +700,6 @@ impl Lua {
 
     /// Creates a new Lua state with required `libs` and `options`
     unsafe fn inner_new(libs: StdLib, options: LuaOptions) -> Lua {
-        unsafe extern "C" fn allocator(
-            extra_data: *mut c_void,
-            ptr: *mut c_void,
-            osize: usize,
-            nsize: usize,
-        ) -> *mut c_void {
-            use std::alloc::{self, Layout};
-
-            let mem_info = &mut *(extra_data as *mut MemoryInfo);
-
-            if nsize == 0 {
-                // Free memory
-                if !ptr.is_null() {
-                    let layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
-                    alloc::dealloc(ptr as *mut u8, layout);
-                    mem_info.used_memory -= osize as isize;
-                }
-                return ptr::null_mut();
-            }
-
-            // Do not allocate more than isize::MAX
-            if nsize > isize::MAX as usize {
-                return ptr::null_mut();
-            }
-
-            // Are we fit to the memory limits?
-            let mut mem_diff = nsize as isize;
-            if !ptr.is_null() {
-                mem_diff -= osize as isize;
-            }
-            let new_used_memory = mem_info.used_memory + mem_diff;
-            if mem_info.memory_limit > 0 && new_used_memory > mem_info.memory_limit {
-                return ptr::null_mut();
-            }
-            mem_info.used_memory += mem_diff;
-
-            if ptr.is_null() {
-                // Allocate new memory
-                let new_layout = match Layout::from_size_align(nsize, ffi::SYS_MIN_ALIGN) {
-                    Ok(layout) => layout,
-                    Err(_) => return ptr::null_mut(),
-                };
-                let new_ptr = alloc::alloc(new_layout) as *mut c_void;
-                if new_ptr.is_null() {
-                    alloc::handle_alloc_error(new_layout);
-                }
-                return new_ptr;
-            }
-
-            // Reallocate memory
-            let old_layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
-            let new_ptr = alloc::realloc(ptr as *mut u8, old_layout, nsize) as *mut c_void;
-            if new_ptr.is_null() {
-                alloc::handle_alloc_error(old_layout);
-            }
-            new_ptr
-        }
-
         // Skip Rust allocator for non-vendored LuaJIT (see https://github.com/khvzak/mlua/issues/176)
         let use_rust_allocator = !(cfg!(feature = "luajit") && cfg!(not(feature = "vendored")));
 
@@ -512,6 +758,19 @@ impl Lua {
             (*extra).thread_pool.reserve_exact(options.thread_pool_size);
         }
 
+        if let Some(seed) = options.deterministic_seed {
+            mlua_expect!(
+                lua.install_deterministic_random(seed),
+                "Error during applying option `deterministic_seed`"
+            );
+        }
+        if options.stable_pairs {
+            mlua_expect!(
+                lua.install_stable_pairs(),
+                "Error during applying option `stable_pairs`"
+            );
+        }
+
         #[cfg(feature = "luau")]
         mlua_expect!(lua.prepare_luau_state(), "Error preparing Luau state");
 
@@ -525,8 +784,10 @@ impl Lua {
     #[allow(clippy::missing_safety_doc)]
     pub unsafe fn init_from_ptr(state: *mut ffi::lua_State) -> Lua {
         assert!(!state.is_null(), "Lua state is NULL");
-        if let Some(lua) = Lua::try_from_ptr(state) {
-            return lua;
+        // Look up the registry, in Luau the callbacks userdata may belong to the host application
+        let extra = registry_extra_data(state);
+        if let Some(lua) = extra.as_ref().and_then(|extra| extra.inner.as_ref()) {
+            return Lua(Arc::clone(lua));
         }
 
         let main_state = get_main_state(state).unwrap_or(state);
@@ -561,11 +822,10 @@ impl Lua {
 
         // Create ref stack thread and place it in the registry to prevent it from being garbage
         // collected.
-        let ref_thread = mlua_expect!(
+        let (ref_thread, ref_thread_ref) = mlua_expect!(
             protect_lua!(main_state, 0, 0, |state| {
                 let thread = ffi::lua_newthread(state);
-                ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX);
-                thread
+                (thread, ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX))
             }),
             "Error while creating ref thread",
         );
@@ -581,7 +841,10 @@ impl Lua {
         let extra = Arc::new(UnsafeCell::new(ExtraData {
             inner: None,
             registered_userdata: FxHashMap::default(),
+            registered_userdata_types: FxHashSet::default(),
+            registered_userdata_with: FxHashMap::default(),
             registered_userdata_mt: FxHashMap::default(),
+            registered_nonstatic_userdata: FxHashMap::default(),
             last_checked_userdata_mt: (ptr::null(), None),
             registry_unref_list: Arc::new(Mutex::new(Some(Vec::new()))),
             app_data: RefCell::new(FxHashMap::default()),
@@ -589,10 +852,14 @@ impl Lua {
             libs: StdLib::NONE,
             mem_info: None,
             ref_thread,
+            ref_thread_ref,
             // We need 1 extra stack space to move values in and out of the ref stack.
             ref_stack_size: ffi::LUA_MINSTACK - 1,
             ref_stack_top: ffi::lua_gettop(ref_thread),
+            ref_stack_max: ffi::lua_gettop(ref_thread),
             ref_free: Vec::new(),
+            ref_slots: Vec::new(),
+            ref_free_handles: Vec::new(),
             wrapped_failure_pool: Vec::with_capacity(WRAPPED_FAILURE_POOL_SIZE),
             multivalue_pool: Vec::with_capacity(MULTIVALUE_POOL_SIZE),
             #[cfg(feature = "async")]
@@ -600,9 +867,20 @@ impl Lua {
             wrapped_failure_mt_ptr,
             #[cfg(feature = "async")]
             waker: NonNull::from(noop_waker_ref()),
+            #[cfg(feature = "async")]
+            async_timeout: None,
+            #[cfg(feature = "async")]
+            gc_budget: None,
+            #[cfg(feature = "async")]
+            yield_async_hook: None,
+            #[cfg(feature = "async")]
+            userdata_tasks: FxHashMap::default(),
+            #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+            preempted_thread: ptr::null_mut(),
+            #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+            thread_preempted: false,
             #[cfg(not(feature = "luau"))]
             hook_callback: None,
-            #[cfg(feature = "lua54")]
             warn_callback: None,
             #[cfg(feature = "luau")]
             interrupt_callback: None,
@@ -610,6 +888,26 @@ impl Lua {
             sandboxed: false,
             #[cfg(feature = "luau")]
             compiler: None,
+            source_maps: FxHashMap::default(),
+            light_userdata_tags: FxHashMap::default(),
+            panic_policy: PanicPolicy::default(),
+            metrics: None,
+            memory_observer: None,
+            #[cfg(not(feature = "module"))]
+            ownership: StateOwnership::Owned,
+            #[cfg(not(feature = "module"))]
+            close_callback: None,
+            #[cfg(feature = "luau")]
+            host_callbacks: {
+                let callbacks = ffi::lua_callbacks(main_state);
+                ((*callbacks).userdata, (*callbacks).useratom)
+            },
+            globals_interceptor: None,
+            globals_proxy_installed: false,
+            #[cfg(feature = "serialize")]
+            userdata_serializers: FxHashMap::default(),
+            #[cfg(feature = "typegen")]
+            userdata_types: Vec::new(),
         }));
 
         // Store it in the registry
@@ -644,6 +942,7 @@ impl Lua {
         #[cfg(feature = "luau")]
         {
             (*ffi::lua_callbacks(main_state)).userdata = extra.get() as *mut c_void;
+            (*ffi::lua_callbacks(main_state)).useratom = Some(util::method_useratom);
         }
 
         let inner = Arc::new(LuaInner {
@@ -659,6 +958,64 @@ impl Lua {
         Lua(inner)
     }
 
+    /// Constructs a new Lua instance from an existing raw state owned by the host application.
+    ///
+    /// With [`StateOwnership::Borrowed`] the state is never closed by mlua: when the last `Lua`
+    /// handle is dropped, mlua only releases its internal data (registry entries, hooks, warning
+    /// function) and the state can be used by the host or attached again later. Functions
+    /// created by mlua that are still reachable from Lua return an error when called after that.
+    ///
+    /// The extra space of the state (`lua_getextraspace`) is not used by mlua and is left to the
+    /// host. On Luau, mlua uses the callbacks userdata (`lua_callbacks(L)->userdata`) while
+    /// attached, and the value set by the host is restored on release.
+    ///
+    /// If mlua is already attached to the state, the existing instance is returned with the
+    /// ownership changed to `ownership`.
+    ///
+    /// # Safety
+    /// The state must be valid and must not be closed by the host while any `Lua` handle is alive.
+    ///
+    /// Requires `feature = "module"` to be disabled
+    #[cfg(any(not(feature = "module"), docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "module"))))]
+    pub unsafe fn from_existing_state(
+        state: *mut ffi::lua_State,
+        ownership: StateOwnership,
+    ) -> Lua {
+        let lua = Lua::init_from_ptr(state);
+        (*lua.0.extra.get()).ownership = ownership;
+        lua
+    }
+
+    /// Detaches mlua from the Lua state without closing it.
+    ///
+    /// The state becomes [`StateOwnership::Borrowed`] and is released (but not closed) when the
+    /// last `Lua` handle is dropped. Returns the main Lua state pointer, the caller is
+    /// responsible for closing it with `lua_close`.
+    ///
+    /// Requires `feature = "module"` to be disabled
+    #[cfg(any(not(feature = "module"), docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "module"))))]
+    pub fn detach(self) -> *mut ffi::lua_State {
+        unsafe { (*self.0.extra.get()).ownership = StateOwnership::Borrowed };
+        self.0.main_state
+    }
+
+    /// Sets a callback to be called when the last `Lua` handle is dropped.
+    ///
+    /// The callback receives the main Lua state pointer and is called before the state is closed
+    /// (or released if it's [`StateOwnership::Borrowed`]), so the state is still valid.
+    ///
+    /// Requires `feature = "module"` to be disabled
+    #[cfg(any(not(feature = "module"), docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "module"))))]
+    pub fn set_close_callback<F>(&self, callback: F)
+    where
+        F: 'static + MaybeSend + FnOnce(*mut ffi::lua_State),
+    {
+        unsafe { (*self.0.extra.get()).close_callback = Some(Box::new(callback)) };
+    }
+
     /// Loads the specified subset of the standard libraries into an existing Lua state.
     ///
     /// Use the [`StdLib`] flags to specify the libraries you want to load.
@@ -696,6 +1053,89 @@ impl Lua {
         res
     }
 
+    /// Unloads the specified subset of the standard libraries from an existing Lua state.
+    ///
+    /// Removes the library tables from the global environment and from `package.loaded`, so
+    /// they can be loaded again later by [`Lua::load_from_std_lib`] (restoring all functions
+    /// removed by [`Lua::filter_std_lib`]).
+    ///
+    /// Note that values (functions) of the libraries already obtained by scripts remain valid.
+    pub fn unload_std_lib(&self, libs: StdLib) -> Result<()> {
+        let globals = self.globals_storage()?;
+        let loaded: Option<Table> = self.named_registry_value("_LOADED")?;
+        for name in libs.names() {
+            globals.raw_set(name, Nil)?;
+            if let Some(loaded) = &loaded {
+                loaded.raw_set(name, Nil)?;
+            }
+        }
+        unsafe { (*self.0.extra.get()).libs &= StdLib::ALL ^ libs };
+        Ok(())
+    }
+
+    /// Removes individual functions of the standard libraries according to the `filter`.
+    ///
+    /// The filter can be applied multiple times to tighten restrictions. To relax them, unload
+    /// the library using [`Lua::unload_std_lib`], load it again and apply a new filter.
+    ///
+    /// Returns an error without changing anything if the filter allows a plain global name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, StdLibFilter};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let filter = StdLibFilter::new()
+    ///     .deny("string.dump")
+    ///     .allow("os.time")
+    ///     .allow("os.clock")
+    ///     .deny("dofile");
+    /// lua.filter_std_lib(&filter)?;
+    ///
+    /// let (dump, time, execute, dofile): (bool, bool, bool, bool) = lua
+    ///     .load("return string.dump ~= nil, os.time ~= nil, os.execute ~= nil, dofile ~= nil")
+    ///     .eval()?;
+    /// assert_eq!((dump, time, execute, dofile), (false, true, false, false));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn filter_std_lib(&self, filter: &StdLibFilter) -> Result<()> {
+        if let Some(path) = filter.invalid.iter().next() {
+            return Err(Error::RuntimeError(format!(
+                "cannot allow '{path}': only library functions (eg. 'os.time') can be allowed"
+            )));
+        }
+        let globals = self.globals_storage()?;
+        for (lib, allowed) in &filter.allowed {
+            if let Value::Table(lib) = globals.raw_get(lib.as_str())? {
+                let keys = (lib.clone().pairs::<Value, Value>())
+                    .map(|pair| pair.map(|(key, _)| key))
+                    .collect::<Result<Vec<_>>>()?;
+                for key in keys {
+                    let is_allowed = match &key {
+                        Value::String(key) => allowed.contains(&*key.to_string_lossy()),
+                        _ => false,
+                    };
+                    if !is_allowed {
+                        lib.raw_set(key, Nil)?;
+                    }
+                }
+            }
+        }
+        for (lib, name) in &filter.denied {
+            match name {
+                Some(name) => {
+                    if let Value::Table(lib) = globals.raw_get(lib.as_str())? {
+                        lib.raw_set(name.as_str(), Nil)?;
+                    }
+                }
+                None => globals.raw_set(lib.as_str(), Nil)?,
+            }
+        }
+        Ok(())
+    }
+
     /// Loads module `modname` into an existing Lua state using the specified entrypoint
     /// function.
     ///
@@ -896,6 +1336,86 @@ impl Lua {
         }
     }
 
+    /// Creates a new environment table for running a script in sandbox mode.
+    ///
+    /// The environment performs writes locally and proxies reads to the (read-only when
+    /// sandboxed) global environment, the same way as [`Lua::sandbox`] does for the main
+    /// environment. It can be used to isolate scripts from each other by loading every script
+    /// with its own environment using [`Chunk::set_environment`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.sandbox(true)?;
+    ///
+    /// let env1 = lua.create_sandbox_environment()?;
+    /// let env2 = lua.create_sandbox_environment()?;
+    /// lua.load("var = 1").set_environment(env1.clone()).exec()?;
+    /// lua.load("var = 2").set_environment(env2.clone()).exec()?;
+    /// assert_eq!(env1.get::<_, u32>("var")?, 1);
+    /// assert_eq!(env2.get::<_, u32>("var")?, 2);
+    /// assert_eq!(lua.globals().get::<_, Option<u32>>("var")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Requires `feature = "luau"`
+    ///
+    /// [`Chunk::set_environment`]: crate::Chunk::set_environment
+    #[cfg(any(feature = "luau", docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn create_sandbox_environment(&self) -> Result<Table> {
+        let state = self.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 4)?;
+
+            push_table(state, 0, 0, true)?;
+            push_table(state, 0, 1, true)?;
+            // Original `LUA_GLOBALSINDEX` is kept in the ref thread
+            ffi::lua_xpush(self.ref_thread(), state, ffi::LUA_GLOBALSINDEX);
+            rawset_field(state, -2, "__index")?;
+            ffi::lua_setreadonly(state, -1, 1);
+            ffi::lua_setmetatable(state, -2);
+            ffi::lua_setsafeenv(state, -1, 1);
+            Ok(Table(self.pop_ref()))
+        }
+    }
+
+    /// Sets a global variable shared by all environments, bypassing the read-only protection
+    /// of the global environment in sandbox mode.
+    ///
+    /// Scripts see the value unless they have shadowed it in their local environment.
+    /// Luau resolves imports of builtin globals when a chunk is loaded, so chunks loaded before
+    /// this call may keep seeing the previous value of an existing global.
+    ///
+    /// Requires `feature = "luau"`
+    #[cfg(any(feature = "luau", docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn set_shared_global<K: IntoLua, V: IntoLua>(&self, key: K, value: V) -> Result<()> {
+        let state = self.state();
+        let key = key.into_lua(self)?;
+        let value = value.into_lua(self)?;
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 6)?;
+
+            ffi::lua_xpush(self.ref_thread(), state, ffi::LUA_GLOBALSINDEX);
+            let globals = ffi::lua_gettop(state);
+            let readonly = ffi::lua_getreadonly(state, globals);
+            ffi::lua_setreadonly(state, globals, 0);
+            ffi::lua_pushvalue(state, globals);
+            let res = (self.push_value(key))
+                .and_then(|_| self.push_value(value))
+                .and_then(|_| protect_lua!(state, 3, 0, fn(state) ffi::lua_rawset(state, -3)));
+            ffi::lua_setreadonly(state, globals, readonly);
+            res
+        }
+    }
+
     /// Sets a 'hook' function that will periodically be called as Lua code executes.
     ///
     /// When exactly the hook function is called depends on the contents of the `triggers`
@@ -935,13 +1455,16 @@ impl Lua {
     where
         F: 'static + MaybeSend + Fn(&Lua, Debug) -> Result<()>,
     {
-        unsafe extern "C" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+        unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
             let lua = match Lua::try_from_ptr(state) {
                 Some(lua) => lua,
                 None => return,
             };
             let extra = lua.0.extra.get();
             callback_error_ext(state, extra, move |_| {
+                // Hooks can be called from coroutines
+                let _guard = StateGuard::new(&lua.0, state);
+                lua.report_memory_events(state, 0)?;
                 let debug = Debug::new(&lua, ar);
                 let hook_cb = (*extra).hook_callback.clone();
                 let hook_cb = mlua_expect!(hook_cb, "no hook callback set in hook_proc");
@@ -1025,7 +1548,7 @@ impl Lua {
     where
         F: 'static + MaybeSend + Fn() -> Result<VmState>,
     {
-        unsafe extern "C" fn interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
+        unsafe extern "C-unwind" fn interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
             if gc >= 0 {
                 // We don't support GC interrupts since they cannot survive Lua exceptions
                 return;
@@ -1071,14 +1594,15 @@ impl Lua {
 
     /// Sets the warning function to be used by Lua to emit warnings.
     ///
-    /// Requires `feature = "lua54"`
-    #[cfg(feature = "lua54")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "lua54")))]
+    /// On Lua 5.4 this uses the native warning system (`lua_setwarnf`). On other Lua versions
+    /// the behavior is emulated by installing a global `warn` function that passes messages
+    /// to the callback (the function is not installed if globals are read-only).
     pub fn set_warning_function<F>(&self, callback: F)
     where
         F: 'static + MaybeSend + Fn(&Lua, &CStr, bool) -> Result<()>,
     {
-        unsafe extern "C" fn warn_proc(ud: *mut c_void, msg: *const c_char, tocont: c_int) {
+        #[cfg(feature = "lua54")]
+        unsafe extern "C-unwind" fn warn_proc(ud: *mut c_void, msg: *const c_char, tocont: c_int) {
             let extra = ud as *mut ExtraData;
             let lua: &Lua = mem::transmute((*extra).inner.as_ref().unwrap());
             callback_error_ext(lua.state(), extra, |_| {
@@ -1091,23 +1615,39 @@ impl Lua {
             });
         }
 
-        let state = self.0.main_state;
+        unsafe { (*self.0.extra.get()).warn_callback = Some(Box::new(callback)) };
+
+        #[cfg(feature = "lua54")]
         unsafe {
-            (*self.0.extra.get()).warn_callback = Some(Box::new(callback));
+            let state = self.0.main_state;
             ffi::lua_setwarnf(state, Some(warn_proc), self.0.extra.get() as *mut c_void);
         }
+
+        #[cfg(not(feature = "lua54"))]
+        {
+            // Emulates `warn` function from Lua 5.4
+            let warn = self.create_function(|lua, args: Variadic<String>| {
+                if args.is_empty() {
+                    return Err(Error::RuntimeError(
+                        "bad argument #1 to 'warn' (string expected, got no value)".to_string(),
+                    ));
+                }
+                for (i, msg) in args.iter().enumerate() {
+                    lua.warning(msg.as_bytes(), i + 1 < args.len())?;
+                }
+                Ok(())
+            });
+            let _ = warn.and_then(|warn| self.globals_storage()?.raw_set("warn", warn));
+        }
     }
 
     /// Removes warning function previously set by `set_warning_function`.
     ///
     /// This function has no effect if a warning function was not previously set.
-    ///
-    /// Requires `feature = "lua54"`
-    #[cfg(feature = "lua54")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "lua54")))]
     pub fn remove_warning_function(&self) {
         unsafe {
             (*self.0.extra.get()).warn_callback = None;
+            #[cfg(feature = "lua54")]
             ffi::lua_setwarnf(self.0.main_state, None, ptr::null_mut());
         }
     }
@@ -1116,15 +1656,34 @@ impl Lua {
     ///
     /// A message in a call with `tocont` set to `true` should be continued in another call to this function.
     ///
-    /// Requires `feature = "lua54"`
-    #[cfg(feature = "lua54")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "lua54")))]
+    /// On Lua versions other than 5.4 the message is passed directly to the warning function
+    /// (if set), see [`Lua::set_warning_function`].
     pub fn warning<S: Into<Vec<u8>>>(&self, msg: S, tocont: bool) -> Result<()> {
         let msg = CString::new(msg).map_err(|err| Error::RuntimeError(err.to_string()))?;
-        unsafe { ffi::lua_warning(self.state(), msg.as_ptr(), tocont as c_int) };
+        #[cfg(feature = "lua54")]
+        unsafe {
+            ffi::lua_warning(self.state(), msg.as_ptr(), tocont as c_int)
+        };
+        #[cfg(not(feature = "lua54"))]
+        if let Some(cb) = unsafe { (*self.0.extra.get()).warn_callback.as_ref() } {
+            cb(self, &msg, tocont)?;
+        }
         Ok(())
     }
 
+    /// Sets the policy for handling Rust panics in callbacks.
+    ///
+    /// By default ([`PanicPolicy::Propagate`]) a panic is carried through Lua code and resumed
+    /// when control returns to Rust. With [`PanicPolicy::ConvertToError`] the panic becomes
+    /// [`Error::CallbackPanic`] that can be caught by Lua code, the original payload is available
+    /// using [`Error::take_panic_payload`].
+    ///
+    /// [`Error::CallbackPanic`]: crate::Error::CallbackPanic
+    /// [`Error::take_panic_payload`]: crate::Error::take_panic_payload
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        unsafe { (*self.0.extra.get()).panic_policy = policy };
+    }
+
     /// Gets information about the interpreter runtime stack.
     ///
     /// This function returns [`Debug`] structure that can be used to get information about the function
@@ -1169,10 +1728,9 @@ impl Lua {
     /// a `Error::MemoryError` is generated instead.
     /// Returns previous limit (zero means no limit).
     ///
-    /// Does not work on module mode where Lua state is managed externally.
-    ///
-    /// Requires `feature = "lua54/lua53/lua52"`
-    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    /// Does not work on module mode where Lua state is managed externally, or with non-vendored
+    /// LuaJIT which uses its own allocator. Use [`Lua::supports`] with
+    /// [`Capability::MemoryLimit`] to check availability.
     pub fn set_memory_limit(&self, memory_limit: usize) -> Result<usize> {
         unsafe {
             match (*self.0.extra.get()).mem_info.map(|mut x| x.as_mut()) {
@@ -1186,26 +1744,198 @@ impl Lua {
         }
     }
 
-    /// Returns true if the garbage collector is currently running automatically.
+    /// Sets an observer of memory allocations, called when a trigger condition is met.
     ///
-    /// Requires `feature = "lua54/lua53/lua52/luau"`
-    #[cfg(any(
-        feature = "lua54",
-        feature = "lua53",
-        feature = "lua52",
-        feature = "luau"
-    ))]
-    pub fn gc_is_running(&self) -> bool {
-        unsafe { ffi::lua_gc(self.0.main_state, ffi::LUA_GCISRUNNING, 0) != 0 }
-    }
-
-    /// Stop the Lua GC from running
-    pub fn gc_stop(&self) {
-        unsafe { ffi::lua_gc(self.0.main_state, ffi::LUA_GCSTOP, 0) };
-    }
-
-    /// Restarts the Lua GC if it is not running
-    pub fn gc_restart(&self) {
+    /// The observer is notified of single allocations larger than
+    /// [`MemoryTriggers::large_allocation`] and memory usage exceeding any of
+    /// [`MemoryTriggers::thresholds`]. This helps to catch scripts creating huge strings or tables
+    /// before they reach the memory limit.
+    ///
+    /// The allocator cannot run any code, so events are recorded and reported to the observer
+    /// at the next safe point: when Lua calls a Rust function or the hook set by
+    /// [`Lua::set_hook`] is triggered. Each event includes a stack traceback of the Lua code at
+    /// that point. The observer can return an error to interrupt the running code.
+    ///
+    /// Does not work on module mode where Lua state is managed externally.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, MemoryTriggers, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_memory_observer(MemoryTriggers::large_allocation(1 << 20), |_, event| {
+    ///     println!("{:?} at\n{}", event.kind, event.traceback);
+    ///     Ok(())
+    /// })?;
+    /// lua.globals().set("check", lua.create_function(|_, ()| Ok(()))?)?;
+    /// lua.load("local s = string.rep('x', 2 << 20); check()").exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_memory_observer<F>(&self, triggers: MemoryTriggers, callback: F) -> Result<()>
+    where
+        F: 'static + MaybeSend + Fn(&Lua, &MemoryEvent) -> Result<()>,
+    {
+        unsafe {
+            let extra = self.0.extra.get();
+            match (*extra).mem_info.map(|mut x| x.as_mut()) {
+                Some(mem_info) => {
+                    let used_memory = mem_info.used_memory as usize;
+                    mem_info.watch = Some(MemoryWatch::new(triggers, used_memory));
+                    (*extra).memory_observer = Some(Arc::new(callback));
+                    Ok(())
+                }
+                None => Err(Error::MemoryLimitNotAvailable),
+            }
+        }
+    }
+
+    /// Removes the memory observer previously set by [`Lua::set_memory_observer`].
+    ///
+    /// Events recorded but not yet reported are discarded.
+    pub fn remove_memory_observer(&self) {
+        unsafe {
+            let extra = self.0.extra.get();
+            if let Some(mem_info) = (*extra).mem_info.map(|mut x| x.as_mut()) {
+                mem_info.watch = None;
+            }
+            (*extra).memory_observer = None;
+        }
+    }
+
+    // Reports recorded memory events to the memory observer.
+    // Must be called from a Rust function (or hook) called by Lua running in `state`, `level` is
+    // the first stack level of the traceback.
+    #[inline]
+    pub(crate) unsafe fn report_memory_events(
+        &self,
+        state: *mut ffi::lua_State,
+        level: c_int,
+    ) -> Result<()> {
+        let extra = self.0.extra.get();
+        let mem_info = match (*extra).mem_info.map(|mut x| x.as_mut()) {
+            Some(mem_info) => mem_info,
+            None => return Ok(()),
+        };
+        let events = match &mut mem_info.watch {
+            Some(watch) if watch.has_pending() => watch.take_pending(),
+            _ => return Ok(()),
+        };
+        let observer = match (*extra).memory_observer.clone() {
+            Some(observer) => observer,
+            None => return Ok(()),
+        };
+
+        let traceback = {
+            let _sg = StackGuard::new(state);
+            check_stack(state, ffi::LUA_TRACEBACK_STACK)?;
+            protect_lua!(state, 0, 1, |state| {
+                ffi::luaL_traceback(state, state, ptr::null(), level)
+            })?;
+            util::to_string(state, -1)
+        };
+        for (kind, used_memory) in events {
+            let event = MemoryEvent {
+                kind,
+                used_memory,
+                traceback: traceback.clone(),
+            };
+            observer(self, &event)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the Lua instance supports the `capability`.
+    ///
+    /// Some features depend on the Lua version or on how the Lua state was created (eg. in module
+    /// mode the state is managed externally). This function allows to discover them at runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Capability, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// if lua.supports(Capability::MemoryLimit) {
+    ///     lua.set_memory_limit(64 * 1024 * 1024)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn supports(&self, capability: Capability) -> bool {
+        let backend = Lua::backend();
+        match capability {
+            Capability::MemoryLimit => unsafe { (*self.0.extra.get()).mem_info.is_some() },
+            #[cfg(not(feature = "luau"))]
+            Capability::Hooks => unsafe { get_main_state(self.0.main_state).is_some() },
+            #[cfg(feature = "luau")]
+            Capability::Hooks => false,
+            Capability::Integers | Capability::BitwiseOperators => {
+                matches!(backend, Backend::Lua53 | Backend::Lua54)
+            }
+            Capability::ToBeClosed => backend == Backend::Lua54,
+            Capability::Vectors | Capability::Sandbox => backend == Backend::Luau,
+            Capability::YieldAcrossC => backend != Backend::Lua51,
+        }
+    }
+
+    /// Returns the Lua implementation mlua is built with.
+    ///
+    /// Together with [`Lua::supports`] it allows libraries built on mlua to branch on Lua
+    /// features without using `cfg` attributes.
+    pub const fn backend() -> Backend {
+        if cfg!(feature = "lua54") {
+            Backend::Lua54
+        } else if cfg!(feature = "lua53") {
+            Backend::Lua53
+        } else if cfg!(feature = "lua52") {
+            Backend::Lua52
+        } else if cfg!(feature = "luajit") {
+            Backend::LuaJIT
+        } else if cfg!(feature = "luau") {
+            Backend::Luau
+        } else {
+            Backend::Lua51
+        }
+    }
+
+    /// Returns the mechanism used by the Lua implementation to raise errors.
+    ///
+    /// Lua compiled as C raises errors using `longjmp`, while LuaJIT and Lua compiled as C++
+    /// (eg. embedded in a C++ engine) throw C++ exceptions. When linking to Lua compiled as C++,
+    /// enable `feature = "cxx-exceptions"` to select the unwinding mode. Errors and panics are
+    /// converted at the boundaries between Rust and Lua in both modes, but in the unwinding mode
+    /// Rust panics in raw C API code passed to [`Lua::with_raw_state`] cannot be caught and abort
+    /// the process.
+    pub const fn error_propagation() -> ErrorPropagation {
+        if cfg!(any(feature = "luajit", feature = "cxx-exceptions")) {
+            ErrorPropagation::Unwind
+        } else {
+            ErrorPropagation::LongJmp
+        }
+    }
+
+    /// Returns true if the garbage collector is currently running automatically.
+    ///
+    /// Requires `feature = "lua54/lua53/lua52/luau"`
+    #[cfg(any(
+        feature = "lua54",
+        feature = "lua53",
+        feature = "lua52",
+        feature = "luau"
+    ))]
+    pub fn gc_is_running(&self) -> bool {
+        unsafe { ffi::lua_gc(self.0.main_state, ffi::LUA_GCISRUNNING, 0) != 0 }
+    }
+
+    /// Stop the Lua GC from running
+    pub fn gc_stop(&self) {
+        unsafe { ffi::lua_gc(self.0.main_state, ffi::LUA_GCSTOP, 0) };
+    }
+
+    /// Restarts the Lua GC if it is not running
+    pub fn gc_restart(&self) {
         unsafe { ffi::lua_gc(self.0.main_state, ffi::LUA_GCRESTART, 0) };
     }
 
@@ -1357,7 +2087,11 @@ impl Lua {
     /// similar on the returned builder. Code is not even parsed until one of these methods is
     /// called.
     ///
+    /// Unless the chunk provides its own name (eg. a file path), the chunk is named after the
+    /// location of the Rust caller, see also [`Chunk::set_name_from_caller`].
+    ///
     /// [`Chunk::exec`]: crate::Chunk::exec
+    /// [`Chunk::set_name_from_caller`]: crate::Chunk::set_name_from_caller
     #[track_caller]
     pub fn load<'a>(&self, chunk: impl AsChunk<'a>) -> Chunk<'a> {
         let caller = Location::caller();
@@ -1367,11 +2101,111 @@ impl Lua {
             env: chunk.env(self),
             mode: chunk.mode(),
             source: chunk.source(),
+            reader: None,
+            #[cfg(feature = "luau")]
+            compiler: unsafe { (*self.0.extra.get()).compiler.clone() },
+            source_map: None,
+        }
+    }
+
+    /// Returns a `Chunk` builder which streams Lua source code (or bytecode) from the `reader`.
+    ///
+    /// The data is read in blocks while Lua parses the chunk, so the whole source is never
+    /// buffered in memory. Chunks loaded from a reader are always interpreted as a block by
+    /// [`Chunk::eval`].
+    ///
+    /// Luau compiles the whole source at once, so the data is read into memory first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let source = std::io::Cursor::new("return 1 + 2");
+    /// assert_eq!(lua.load_reader(source).eval::<i32>()?, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Chunk::eval`]: crate::Chunk::eval
+    #[track_caller]
+    pub fn load_reader<'a>(&self, reader: impl io::Read + 'a) -> Chunk<'a> {
+        let caller = Location::caller();
+        Chunk {
+            lua: self.clone(),
+            name: caller.to_string(),
+            env: Ok(Value::Nil),
+            mode: None,
+            source: Ok(Cow::Borrowed(&[])),
+            reader: Some(Box::new(reader)),
             #[cfg(feature = "luau")]
             compiler: unsafe { (*self.0.extra.get()).compiler.clone() },
+            source_map: None,
         }
     }
 
+    /// Returns a `Chunk` builder which streams Lua source code (or bytecode) from the
+    /// asynchronous `reader`.
+    ///
+    /// Lua parser cannot be suspended, so the reader is polled on the current thread while Lua
+    /// parses the chunk, blocking the thread until data is available. The reader must be able to
+    /// make progress without the current thread (eg. in-memory or file readers, or readers driven
+    /// by a multi-threaded runtime).
+    ///
+    /// See [`Lua::load_reader`] for details.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    #[track_caller]
+    pub fn load_async_reader<'a>(&self, reader: impl AsyncRead + Unpin + 'a) -> Chunk<'a> {
+        self.load_reader(BlockingReader(reader))
+    }
+
+    // Registers a source map for chunks with the given short source name
+    pub(crate) fn set_source_map(&self, short_src: StdString, map: Box<dyn SourceMap>) {
+        unsafe { (*self.0.extra.get()).source_maps.insert(short_src, map) };
+    }
+
+    /// Removes the source map registered for chunks with the given name
+    /// (see [`Chunk::set_source_map`]).
+    ///
+    /// Returns `true` if the source map was registered.
+    ///
+    /// [`Chunk::set_source_map`]: crate::Chunk::set_source_map
+    pub fn remove_source_map(&self, name: &str) -> bool {
+        let extra = unsafe { &mut *self.0.extra.get() };
+        extra.source_maps.remove(&short_source(name)).is_some()
+    }
+
+    #[inline]
+    pub(crate) fn globals_interceptor_callback(&self) -> Option<GlobalsInterceptor> {
+        unsafe { (*self.0.extra.get()).globals_interceptor.clone() }
+    }
+
+    #[inline]
+    pub(crate) fn set_globals_interceptor_callback(&self, callback: Option<GlobalsInterceptor>) {
+        unsafe { (*self.0.extra.get()).globals_interceptor = callback };
+    }
+
+    #[inline]
+    pub(crate) fn globals_interceptor_installed(&self) -> bool {
+        unsafe { (*self.0.extra.get()).globals_proxy_installed }
+    }
+
+    #[inline]
+    pub(crate) fn set_globals_interceptor_installed(&self) {
+        unsafe { (*self.0.extra.get()).globals_proxy_installed = true };
+    }
+
+    // Disables "safe env" optimization of the globals table, so every access goes through
+    // metamethods
+    #[cfg(feature = "luau")]
+    pub(crate) unsafe fn disable_globals_safeenv(&self) {
+        ffi::lua_setsafeenv(self.0.main_state, ffi::LUA_GLOBALSINDEX, 0);
+    }
+
     pub(crate) fn load_chunk(
         &self,
         name: Option<&CStr>,
@@ -1412,6 +2246,117 @@ impl Lua {
         }
     }
 
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn load_chunk_reader(
+        &self,
+        name: Option<&CStr>,
+        env: Value,
+        mode: Option<ChunkMode>,
+        reader: &mut dyn io::Read,
+    ) -> Result<Function> {
+        struct ReaderState<'a> {
+            reader: &'a mut dyn io::Read,
+            buffer: Box<[u8]>,
+            mode: Option<ChunkMode>,
+            first_block: bool,
+            error: Option<Error>,
+            panic: Option<Box<dyn Any + Send>>,
+        }
+
+        unsafe extern "C-unwind" fn reader_proc(
+            _state: *mut ffi::lua_State,
+            ud: *mut c_void,
+            size: *mut usize,
+        ) -> *const c_char {
+            let rs = &mut *(ud as *mut ReaderState);
+            *size = 0;
+            if rs.error.is_some() || rs.panic.is_some() {
+                return ptr::null();
+            }
+            let result = catch_unwind(AssertUnwindSafe(|| loop {
+                match rs.reader.read(&mut rs.buffer) {
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    result => return result,
+                }
+            }));
+            match result {
+                Ok(Ok(n)) => {
+                    if mem::take(&mut rs.first_block) && n > 0 {
+                        // Check loading mode here as Lua 5.1 does not support it
+                        let is_binary = rs.buffer[0] == ffi::LUA_SIGNATURE[0];
+                        match rs.mode {
+                            Some(ChunkMode::Text) if is_binary => {
+                                let msg = "attempt to load a binary chunk (mode is 't')";
+                                rs.error = Some(Error::syntax_error(msg.to_string()));
+                                return ptr::null();
+                            }
+                            Some(ChunkMode::Binary) if !is_binary => {
+                                let msg = "attempt to load a text chunk (mode is 'b')";
+                                rs.error = Some(Error::syntax_error(msg.to_string()));
+                                return ptr::null();
+                            }
+                            _ => {}
+                        }
+                    }
+                    *size = n;
+                    rs.buffer.as_ptr() as *const c_char
+                }
+                Ok(Err(err)) => {
+                    rs.error = Some(Error::from(err));
+                    ptr::null()
+                }
+                Err(panic) => {
+                    rs.panic = Some(panic);
+                    ptr::null()
+                }
+            }
+        }
+
+        let state = self.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            let mut rs = ReaderState {
+                reader,
+                buffer: vec![0; 8192].into_boxed_slice(),
+                mode,
+                first_block: true,
+                error: None,
+                panic: None,
+            };
+            let rs_ptr = &mut rs as *mut ReaderState as *mut c_void;
+            let name = name.map(|n| n.as_ptr()).unwrap_or_else(ptr::null);
+
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            let status = ffi::lua_load(state, reader_proc, rs_ptr, name, ptr::null());
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            let status = ffi::lua_load(state, reader_proc, rs_ptr, name);
+
+            // Errors from the reader take precedence over the Lua errors
+            if let Some(panic) = rs.panic {
+                resume_unwind(panic);
+            }
+            if let Some(err) = rs.error {
+                return Err(err);
+            }
+
+            match status {
+                ffi::LUA_OK => {
+                    if env != Value::Nil {
+                        self.push_value(env)?;
+                        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+                        ffi::lua_setupvalue(state, -2, 1);
+                        #[cfg(any(feature = "lua51", feature = "luajit"))]
+                        ffi::lua_setfenv(state, -2);
+                    }
+                    Ok(Function(self.pop_ref()))
+                }
+                err => Err(pop_error(state, err)),
+            }
+        }
+    }
+
     /// Create and return an interned Lua string. Lua strings can be arbitrary [u8] data including
     /// embedded nulls, so in addition to `&str` and `&String`, you can also pass plain `&[u8]`
     /// here.
@@ -1430,6 +2375,27 @@ impl Lua {
         }
     }
 
+    /// Creates and returns a new Luau buffer initialized with a copy of the given bytes.
+    ///
+    /// Requires `feature = "luau"`
+    #[cfg(feature = "luau")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn create_buffer(&self, data: impl AsRef<[u8]>) -> Result<LuauBuffer> {
+        let state = self.state();
+        let data = data.as_ref();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+            let buf = if self.unlikely_memory_error() {
+                ffi::lua_newbuffer(state, data.len())
+            } else {
+                protect_lua!(state, 0, 1, |state| ffi::lua_newbuffer(state, data.len()))?
+            };
+            ptr::copy_nonoverlapping(data.as_ptr(), buf as *mut u8, data.len());
+            Ok(LuauBuffer(self.pop_ref()))
+        }
+    }
+
     /// Creates and returns a new empty table.
     pub fn create_table(&self) -> Result<Table> {
         self.create_table_with_capacity(0, 0)
@@ -1559,15 +2525,18 @@ impl Lua {
     ///
     /// [`IntoLua`]: crate::IntoLua
     /// [`IntoLuaMulti`]: crate::IntoLuaMulti
+    #[track_caller]
     pub fn create_function<A, R, F>(&self, func: F) -> Result<Function>
     where
         A: FromLuaMulti,
         R: IntoLuaMulti,
         F: 'static + MaybeSend + Fn(&Lua, A) -> Result<R>,
     {
-        self.create_callback(Box::new(move |lua, args| {
+        let caller = Location::caller();
+        let func: Callback = Box::new(move |lua, args| {
             func(&lua, A::from_lua_multi_args(args, 1, None, &lua)?)?.into_lua_multi(&lua)
-        }))
+        });
+        self.create_callback(self.instrument_callback(|| caller.to_string(), func))
     }
 
     /// Wraps a Rust mutable closure, creating a callable Lua function handle to it.
@@ -1576,6 +2545,7 @@ impl Lua {
     /// [`create_function`] for more information about the implementation.
     ///
     /// [`create_function`]: #method.create_function
+    #[track_caller]
     pub fn create_function_mut<A, R, F>(&self, func: F) -> Result<Function>
     where
         A: FromLuaMulti,
@@ -1597,68 +2567,510 @@ impl Lua {
     pub unsafe fn create_c_function(&self, func: ffi::lua_CFunction) -> Result<Function> {
         let state = self.state();
         check_stack(state, 1)?;
-        ffi::lua_pushcfunction(state, func);
+        MemoryInfo::relax_limit_with(state, || ffi::lua_pushcfunction(state, func));
         Ok(Function(self.pop_ref()))
     }
 
-    /// Wraps a Rust async function or closure, creating a callable Lua function handle to it.
-    ///
-    /// While executing the function Rust will poll Future and if the result is not ready, call
-    /// `yield()` passing internal representation of a `Poll::Pending` value.
+    /// Runs custom Lua C API code against the underlying `lua_State`.
     ///
-    /// The function must be called inside Lua coroutine ([`Thread`]) to be able to suspend its execution.
-    /// An executor should be used to poll [`AsyncThread`] and mlua will take a provided Waker
-    /// in that case. Otherwise noop waker will be used if try to call the function outside of Rust
-    /// executors.
+    /// The `args` are pushed onto the stack before calling `f`, and all values left on the stack
+    /// after `f` returns are converted to the result `R`, so Lua values produced by the C code
+    /// (eg. by another C library) can be obtained as `Function` or `Table` handles.
     ///
-    /// The family of `call_async()` functions takes care about creating [`Thread`].
+    /// The function is called in protected mode, as a C function with at least `LUA_MINSTACK`
+    /// free stack slots. Lua errors raised by `f` are returned as [`Error`], and Rust panics are
+    /// propagated to the caller after restoring the Lua stack (except when Lua errors are C++
+    /// exceptions, see [`Lua::error_propagation`], where a panic in `f` aborts the process).
     ///
-    /// Requires `feature = "async"`
+    /// # Safety
+    /// The C code must respect the Lua stack discipline. Lua errors are implemented using
+    /// `longjmp`, so `f` must not hold any values that implement `Drop` while calling functions
+    /// that can raise an error.
     ///
     /// # Examples
     ///
-    /// Non blocking sleep:
+    /// ```
+    /// # use std::os::raw::c_int;
+    /// # use mlua::{lua_State, Lua, Result};
+    /// extern "C-unwind" {
+    ///     fn lua_pushboolean(state: *mut lua_State, b: c_int);
+    /// }
     ///
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let (s, b): (String, bool) =
+    ///     unsafe { lua.with_raw_state("hello", |state| lua_pushboolean(state, 1))? };
+    /// assert_eq!((s.as_str(), b), ("hello", true));
+    /// # Ok(())
+    /// # }
     /// ```
-    /// use std::time::Duration;
-    /// use futures_timer::Delay;
-    /// use mlua::{Lua, Result};
+    pub unsafe fn with_raw_state<R: FromLuaMulti>(
+        &self,
+        args: impl IntoLuaMulti,
+        f: impl FnOnce(*mut ffi::lua_State),
+    ) -> Result<R> {
+        let results = self.exec_raw_multi(args, |state| {
+            f(state);
+            ffi::LUA_MULTRET
+        })?;
+        R::from_lua_multi(results, self)
+    }
+
+    /// Runs custom Lua C API code against the underlying `lua_State`, returning `N` results.
     ///
-    /// async fn sleep(_lua: &Lua, n: u64) -> Result<&'static str> {
-    ///     Delay::new(Duration::from_millis(n)).await;
-    ///     Ok("done")
-    /// }
+    /// Works like a Lua C function: the `args` are pushed onto the stack before calling `f`, and
+    /// `f` returns the number of results left on top of the stack (or `LUA_MULTRET` (-1) to
+    /// return all values on the stack). The results are returned as [`MultiValue`] and the stack
+    /// is restored afterwards. This is the building block of [`Lua::with_raw_state`] and is
+    /// intended for crates wrapping C libraries on top of mlua.
+    ///
+    /// The function is called in protected mode, with the same guarantees about errors and
+    /// panics as [`Lua::with_raw_state`]. Returns an error if `f` reports more results than there
+    /// are values on the stack.
+    ///
+    /// # Safety
+    /// The C code must respect the Lua stack discipline. Lua errors are implemented using
+    /// `longjmp`, so `f` must not hold any values that implement `Drop` while calling functions
+    /// that can raise an error.
+    ///
+    /// # Examples
     ///
-    /// #[tokio::main]
-    /// async fn main() -> Result<()> {
-    ///     let lua = Lua::new();
-    ///     lua.globals().set("sleep", lua.create_async_function(sleep)?)?;
-    ///     let res: String = lua.load("return sleep(...)").call_async(100).await?; // Sleep 100ms
-    ///     assert_eq!(res, "done");
-    ///     Ok(())
-    /// }
     /// ```
+    /// # use std::os::raw::c_int;
+    /// # use mlua::{lua_State, Lua, Result};
+    /// extern "C-unwind" {
+    ///     fn lua_pushboolean(state: *mut lua_State, b: c_int);
+    /// }
     ///
-    /// [`Thread`]: crate::Thread
-    /// [`AsyncThread`]: crate::AsyncThread
-    #[cfg(feature = "async")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let results = unsafe {
+    ///     lua.exec_raw_multi("ignored", |state| {
+    ///         lua_pushboolean(state, 1);
+    ///         lua_pushboolean(state, 0);
+    ///         2
+    ///     })?
+    /// };
+    /// assert_eq!(lua.unpack_multi::<(bool, bool)>(results)?, (true, false));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub unsafe fn exec_raw_multi(
+        &self,
+        args: impl IntoLuaMulti,
+        f: impl FnOnce(*mut ffi::lua_State) -> c_int,
+    ) -> Result<MultiValue> {
+        let state = self.state();
+        let mut args = args.into_lua_multi(self)?;
+        let nargs = args.len() as c_int;
+
+        let _sg = StackGuard::new(state);
+        check_stack(state, nargs + 3)?;
+
+        let stack_start = ffi::lua_gettop(state);
+        for arg in args.drain_all() {
+            self.push_value(arg)?;
+        }
+        let (f, nresults, panic) = (Cell::new(Some(f)), Cell::new(0), Cell::new(None));
+        let res = protect_lua!(state, nargs, ffi::LUA_MULTRET, |state| {
+            // Errors raised using C++ exceptions cannot be caught by Rust
+            #[cfg(any(feature = "luajit", feature = "cxx-exceptions"))]
+            if let Some(f) = f.take() {
+                nresults.set(f(state));
+            }
+            #[cfg(not(any(feature = "luajit", feature = "cxx-exceptions")))]
+            if let Some(f) = f.take() {
+                match catch_unwind(AssertUnwindSafe(|| f(state))) {
+                    Ok(n) => nresults.set(n),
+                    Err(err) => panic.set(Some(err)),
+                }
+            }
+        });
+        if let Some(panic) = panic.take() {
+            drop(_sg);
+            resume_unwind(panic);
+        }
+        res?;
+
+        let available = ffi::lua_gettop(state) - stack_start;
+        let nresults = match nresults.get() {
+            ffi::LUA_MULTRET => available,
+            n if (0..=available).contains(&n) => n,
+            n => {
+                return Err(Error::RuntimeError(format!(
+                    "cannot return {n} results, only {available} values are on the stack"
+                )))
+            }
+        };
+        let mut results = args; // Reuse MultiValue container
+        check_stack(state, 2)?;
+        for _ in 0..nresults {
+            results.push_front(self.pop_value());
+        }
+        Ok(results)
+    }
+
+    /// Returns a value at the given index of the raw Lua stack, converted to `T`.
+    ///
+    /// The value is not removed from the stack. This can be used to get handles to values
+    /// passed to C functions (eg. created by [`Lua::create_c_function`]).
+    ///
+    /// # Safety
+    /// The `state` must be the main state or a thread (coroutine) of this Lua instance, and the
+    /// `index` must be a valid stack index.
+    pub unsafe fn stack_value<T: FromLua>(
+        &self,
+        state: *mut ffi::lua_State,
+        index: c_int,
+    ) -> Result<T> {
+        let current = self.state();
+        let _sg = StackGuard::new(current);
+        check_stack(current, 3)?;
+
+        if state == current {
+            ffi::lua_pushvalue(state, index);
+        } else {
+            check_stack(state, 1)?;
+            ffi::lua_pushvalue(state, index);
+            ffi::lua_xmove(state, current, 1);
+        }
+        T::from_lua(self.pop_value(), self)
+    }
+
+    /// Pushes a value onto the raw Lua stack.
+    ///
+    /// # Safety
+    /// The `state` must be the main state or a thread (coroutine) of this Lua instance.
+    pub unsafe fn push_stack_value(
+        &self,
+        state: *mut ffi::lua_State,
+        value: impl IntoLua,
+    ) -> Result<()> {
+        let value = value.into_lua(self)?;
+        let current = self.state();
+        check_stack(current, 3)?;
+
+        self.push_value(value)?;
+        if state != current {
+            if let Err(err) = check_stack(state, 1) {
+                ffi::lua_pop(current, 1);
+                return Err(err);
+            }
+            ffi::lua_xmove(current, state, 1);
+        }
+        Ok(())
+    }
+
+    /// Wraps a Rust async function or closure, creating a callable Lua function handle to it.
+    ///
+    /// While executing the function Rust will poll Future and if the result is not ready, call
+    /// `yield()` passing internal representation of a `Poll::Pending` value.
+    ///
+    /// The function must be called inside Lua coroutine ([`Thread`]) to be able to suspend its execution.
+    /// An executor should be used to poll [`AsyncThread`] and mlua will take a provided Waker
+    /// in that case. Otherwise noop waker will be used if try to call the function outside of Rust
+    /// executors.
+    ///
+    /// The family of `call_async()` functions takes care about creating [`Thread`].
+    ///
+    /// The function receives an owned [`Lua`] handle that can be moved into the returned future,
+    /// which must not borrow anything else (be `'static`).
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// Non blocking sleep:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use futures_timer::Delay;
+    /// use mlua::{Lua, Result};
+    ///
+    /// async fn sleep(_lua: Lua, n: u64) -> Result<&'static str> {
+    ///     Delay::new(Duration::from_millis(n)).await;
+    ///     Ok("done")
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     lua.globals().set("sleep", lua.create_async_function(sleep)?)?;
+    ///     let res: String = lua.load("return sleep(...)").call_async(100).await?; // Sleep 100ms
+    ///     assert_eq!(res, "done");
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`Thread`]: crate::Thread
+    /// [`AsyncThread`]: crate::AsyncThread
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub fn create_async_function<A, R, F, FR>(&self, func: F) -> Result<Function>
     where
         A: FromLuaMulti,
         R: IntoLuaMulti,
-        F: 'static + MaybeSend + Fn(&Lua, A) -> FR,
-        FR: 'lua + Future<Output = Result<R>>,
+        F: 'static + MaybeSend + Fn(Lua, A) -> FR,
+        FR: 'static + Future<Output = Result<R>>,
     {
         self.create_async_callback(Box::new(move |lua, args| {
-            let args = match A::from_lua_multi_args(args, 1, None, lua) {
+            let args = match A::from_lua_multi_args(args, 1, None, &lua) {
                 Ok(args) => args,
                 Err(e) => return Box::pin(future::err(e)),
             };
-            Box::pin(func(lua, args).and_then(move |ret| future::ready(ret.into_lua_multi(lua))))
+            let fut = func(lua.clone(), args);
+            Box::pin(async move { fut.await?.into_lua_multi(&lua) })
         }))
     }
 
+    /// Wraps a Rust async function or closure returning a `!Send` future that owns its captures.
+    ///
+    /// Unlike [`Lua::create_async_function`], the function is not required to be `Send`, so it
+    /// can capture `Rc`/`RefCell` data and hold it across `.await` points. Such functions are
+    /// meant for single-threaded executors (eg. Tokio `LocalSet`) and are not available when the
+    /// `send` feature is enabled.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use mlua::{Lua, Result};
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let log = Rc::new(RefCell::new(Vec::new()));
+    ///
+    ///     let log2 = log.clone();
+    ///     let push = lua.create_local_async_function(move |_, msg: String| {
+    ///         let log = log2.clone();
+    ///         async move {
+    ///             log.borrow_mut().push(msg);
+    ///             Ok(log.borrow().len())
+    ///         }
+    ///     })?;
+    ///     assert_eq!(push.call_async::<_, usize>("hello").await?, 1);
+    ///     assert_eq!(*log.borrow(), ["hello"]);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(all(feature = "async", not(feature = "send")))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "async", not(feature = "send")))))]
+    pub fn create_local_async_function<A, R, F, FR>(&self, func: F) -> Result<Function>
+    where
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+        F: 'static + Fn(Lua, A) -> FR,
+        FR: 'static + Future<Output = Result<R>>,
+    {
+        self.create_async_callback(Box::new(move |lua, args| {
+            let args = match A::from_lua_multi_args(args, 1, None, &lua) {
+                Ok(args) => args,
+                Err(e) => return Box::pin(future::err(e)),
+            };
+            let fut = func(lua.clone(), args);
+            Box::pin(async move { fut.await?.into_lua_multi(&lua) })
+        }))
+    }
+
+    /// Sets a wall-clock time budget for executing Lua code asynchronously.
+    ///
+    /// The budget applies to every [`AsyncThread`] created after this call, including the ones
+    /// created by the `call_async` family of functions. It starts when the thread is polled for
+    /// the first time, once it is exceeded the future (or stream) resolves with [`Error::Timeout`].
+    ///
+    /// In Lua 5.4 and 5.3 a running coroutine is suspended every few thousands of VM
+    /// instructions to check the budget and let the executor run other tasks, so busy Lua code
+    /// cannot block the executor. The instruction count hook set by [`Lua::set_hook`] is
+    /// replaced in the coroutine while it is polled.
+    /// In other Lua versions the budget is checked only when the coroutine yields.
+    ///
+    /// The budget is not checked while awaiting a Rust future, until the future wakes the task.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use mlua::{Error, Lua, Result};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     lua.set_async_timeout(Duration::from_millis(100));
+    ///     let res = lua.load("while true do end").exec_async().await;
+    ///     assert!(matches!(res, Err(Error::Timeout)));
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn set_async_timeout(&self, timeout: Duration) {
+        unsafe { (*self.0.extra.get()).async_timeout = Some(timeout) };
+    }
+
+    /// Removes the time budget previously set by [`Lua::set_async_timeout`].
+    ///
+    /// Threads created before this call keep their budget.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn remove_async_timeout(&self) {
+        unsafe { (*self.0.extra.get()).async_timeout = None };
+    }
+
+    /// Sets the amount of garbage collection work done between awaits of async calls.
+    ///
+    /// When set, every resume of an [`AsyncThread`] (eg. a poll of [`Function::call_async`]) is
+    /// followed by a GC step of `kbytes` (see [`Lua::gc_step_kbytes`]), so that collection work
+    /// is amortized across polls by the executor rather than done in a single long step when
+    /// the allocation debt is large. Combine it with a larger GC pause (or stopping the
+    /// collector) to move most of the work to these steps.
+    ///
+    /// Pass `None` to disable the steps.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn gc_budget_per_tick(&self, kbytes: Option<c_int>) {
+        unsafe { (*self.0.extra.get()).gc_budget = kbytes };
+    }
+
+    /// Sets a hook called every time an async Lua call yields to the async executor.
+    ///
+    /// The hook is called when an [`AsyncThread`] (eg. a future returned by
+    /// [`Function::call_async`]) returns `Poll::Pending`, because Lua code awaits a Rust future
+    /// or was suspended to check the time budget. This allows cooperating with event loops not
+    /// driven by the executor, eg. to give control back to the browser when running on
+    /// WebAssembly. An error returned by the hook is returned by the future.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use mlua::{Lua, Result};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let yields = Arc::new(AtomicUsize::new(0));
+    ///     let yields2 = yields.clone();
+    ///     lua.set_yield_async_hook(move |_| {
+    ///         yields2.fetch_add(1, Ordering::Relaxed);
+    ///         Ok(())
+    ///     });
+    ///
+    ///     let sleep = lua.create_async_function(|_, ()| async {
+    ///         tokio::task::yield_now().await;
+    ///         Ok(())
+    ///     })?;
+    ///     lua.globals().set("sleep", sleep)?;
+    ///     lua.load("sleep()").exec_async().await?;
+    ///     assert!(yields.load(Ordering::Relaxed) > 0);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn set_yield_async_hook<F>(&self, callback: F)
+    where
+        F: 'static + MaybeSend + Fn(&Lua) -> Result<()>,
+    {
+        unsafe { (*self.0.extra.get()).yield_async_hook = Some(Arc::new(callback)) };
+    }
+
+    /// Removes the hook previously set by [`Lua::set_yield_async_hook`].
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn remove_yield_async_hook(&self) {
+        unsafe { (*self.0.extra.get()).yield_async_hook = None };
+    }
+
+    /// Performs a full garbage-collection cycle incrementally, yielding to the async executor
+    /// between GC steps.
+    ///
+    /// Every step does the work of the budget set by [`Lua::gc_budget_per_tick`] (or a single
+    /// basic step if not set), so other tasks of the executor can run while the cycle is in
+    /// progress. Completes when the current collection cycle is finished.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mlua::{Lua, Result};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     lua.gc_budget_per_tick(Some(64));
+    ///     lua.load("for i = 1, 1000 do local t = {} end").exec()?;
+    ///     lua.gc_collect_async().await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn gc_collect_async(&self) -> Result<()> {
+        let kbytes = self.gc_budget().unwrap_or(0);
+        while !self.gc_step_kbytes(kbytes)? {
+            // Yield to the executor
+            let mut yielded = false;
+            future::poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+            .await;
+        }
+        Ok(())
+    }
+
+    // Registers the future of an async method call of the userdata `ud_ptr`, so that it can be
+    // aborted when the userdata is destroyed
+    #[cfg(feature = "async")]
+    pub(crate) fn track_userdata_task<'a>(
+        &self,
+        ud_ptr: *const c_void,
+        fut: LocalBoxFuture<'a, Result<MultiValue>>,
+    ) -> LocalBoxFuture<'a, Result<MultiValue>> {
+        if ud_ptr.is_null() {
+            return fut;
+        }
+        let task = Rc::new(PendingTask::default());
+        let extra = unsafe { &mut *self.0.extra.get() };
+        let tasks = extra.userdata_tasks.entry(ud_ptr).or_default();
+        // Forget finished calls
+        tasks.retain(|task| task.strong_count() > 0);
+        tasks.push(Rc::downgrade(&task));
+        Box::pin(TrackedFuture::new(fut, task))
+    }
+
+    // Aborts pending async method calls of the userdata `ud_ptr`, returns the number of calls
+    #[cfg(feature = "async")]
+    pub(crate) fn abort_userdata_tasks(&self, ud_ptr: *const c_void) -> usize {
+        unsafe { abort_userdata_tasks_extra(self.0.extra.get(), ud_ptr) }
+    }
+
     /// Wraps a Lua function into a new thread (or coroutine).
     ///
     /// Equivalent to `coroutine.create`.
@@ -1680,6 +3092,77 @@ impl Lua {
         }
     }
 
+    /// Wraps a Rust async closure into a new thread (or coroutine).
+    ///
+    /// This is the reverse of [`Lua::create_async_function`]: the future returned by `func`
+    /// runs as a Lua coroutine, so Lua scripts can drive Rust async work using the standard
+    /// `coroutine` functions. The closure is called with the arguments of the first resume.
+    /// Every resume polls the future: while it is not ready, the coroutine yields an internal
+    /// `Poll::Pending` value, once it is ready, the coroutine finishes returning its results.
+    ///
+    /// The future is polled with the waker of the executor when the thread (or the Lua code
+    /// resuming it) runs as an [`AsyncThread`], otherwise a noop waker is used.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use futures_timer::Delay;
+    /// use mlua::{Lua, Result};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let download = lua.create_thread_from_closure(|_, ms: u64| async move {
+    ///         Delay::new(Duration::from_millis(ms)).await;
+    ///         Ok("done")
+    ///     })?;
+    ///     lua.globals().set("download", download)?;
+    ///
+    ///     let result: String = lua
+    ///         .load(
+    ///             r#"
+    ///             local ok, result = coroutine.resume(download, 10)
+    ///             while coroutine.status(download) ~= "dead" do
+    ///                 ok, result = coroutine.resume(download)
+    ///             end
+    ///             return result
+    ///         "#,
+    ///         )
+    ///         .eval()?;
+    ///     assert_eq!(result, "done");
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_thread_from_closure<A, R, F, FR>(&self, func: F) -> Result<Thread>
+    where
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+        F: 'static + MaybeSend + FnOnce(Lua, A) -> FR,
+        FR: 'static + Future<Output = Result<R>>,
+    {
+        // The thread function is called only once
+        let func = Cell::new(Some(func));
+        let function = self.create_async_callback(Box::new(move |lua, args| {
+            let func = match func.take() {
+                Some(func) => func,
+                None => return Box::pin(future::err(Error::CallbackDestructed)),
+            };
+            let args = match A::from_lua_multi_args(args, 1, None, &lua) {
+                Ok(args) => args,
+                Err(e) => return Box::pin(future::err(e)),
+            };
+            let fut = func(lua.clone(), args);
+            Box::pin(async move { fut.await?.into_lua_multi(&lua) })
+        }))?;
+        self.create_thread(function)
+    }
+
     /// Wraps a Lua function into a new or recycled thread (coroutine).
     #[cfg(feature = "async")]
     pub(crate) fn create_recycled_thread(&self, func: &Function) -> Result<Thread> {
@@ -1693,8 +3176,8 @@ impl Lua {
             let _sg = StackGuard::new(state);
             check_stack(state, 1)?;
 
-            if let Some(index) = (*self.0.extra.get()).thread_pool.pop() {
-                let thread_state = ffi::lua_tothread(self.ref_thread(), index);
+            if let Some(handle) = (*self.0.extra.get()).thread_pool.pop() {
+                let thread_state = ffi::lua_tothread(self.ref_thread(), self.ref_index(handle));
                 self.push_ref(&func.0);
                 ffi::lua_xmove(state, thread_state, 1);
 
@@ -1705,7 +3188,7 @@ impl Lua {
                     ffi::lua_replace(thread_state, ffi::LUA_GLOBALSINDEX);
                 }
 
-                return Ok(Thread(LuaRef::new(self, index)));
+                return Ok(Thread(LuaRef::new(self.clone(), handle)));
             }
         };
         self.create_thread(func.clone())
@@ -1721,7 +3204,7 @@ impl Lua {
     pub(crate) unsafe fn recycle_thread(&self, thread: &mut Thread) -> bool {
         let extra = &mut *self.0.extra.get();
         if extra.thread_pool.len() < extra.thread_pool.capacity() {
-            let thread_state = ffi::lua_tothread(extra.ref_thread, thread.0.index);
+            let thread_state = ffi::lua_tothread(extra.ref_thread, thread.0.index());
             #[cfg(feature = "lua54")]
             let status = ffi::lua_resetthread(thread_state);
             #[cfg(feature = "lua54")]
@@ -1733,7 +3216,7 @@ impl Lua {
             ffi::lua_resetthread(self.state(), thread_state);
             #[cfg(feature = "luau")]
             ffi::lua_resetthread(thread_state);
-            extra.thread_pool.push(thread.0.index);
+            extra.thread_pool.push(thread.0.handle);
             thread.0.drop = false;
             return true;
         }
@@ -1764,6 +3247,129 @@ impl Lua {
         unsafe { self.make_userdata(UserDataCell::new_ser(data)) }
     }
 
+    /// Registers a serializer for userdata objects of type `T`.
+    ///
+    /// Once registered, userdata objects of type `T` can be serialized (eg. using
+    /// [`LuaSerdeExt::to_value`] or as part of a [`Table`]) using the [`UserDataSerialize`]
+    /// implementation, instead of failing with "cannot serialize <userdata>" error.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, LuaSerdeExt, UserData, UserDataSerialize, Value};
+    /// # use serde::Serializer;
+    /// struct Handle(u32);
+    ///
+    /// impl UserData for Handle {}
+    ///
+    /// impl UserDataSerialize for Handle {
+    ///     fn serialize_userdata<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    ///         serializer.serialize_u32(self.0)
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> mlua::Result<()> {
+    /// let lua = Lua::new();
+    /// lua.register_userdata_serializer::<Handle>();
+    /// let ud = lua.create_userdata(Handle(42))?;
+    /// let value = lua.to_value(&ud)?;
+    /// assert_eq!(value, Value::Integer(42));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`LuaSerdeExt::to_value`]: crate::LuaSerdeExt::to_value
+    /// [`UserDataSerialize`]: crate::UserDataSerialize
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    pub fn register_userdata_serializer<T: UserDataSerialize>(&self) {
+        fn serialize<T: UserDataSerialize>(ud: &AnyUserData) -> Result<serde_value::Value> {
+            let data = ud.borrow::<T>()?;
+            serde_value::to_value(UserDataSerializeWrapper(&*data))
+                .map_err(|err| Error::SerializeError(err.to_string()))
+        }
+
+        let extra = unsafe { &mut *self.0.extra.get() };
+        extra
+            .userdata_serializers
+            .insert(TypeId::of::<T>(), serialize::<T>);
+    }
+
+    #[cfg(feature = "serialize")]
+    pub(crate) fn userdata_serializer(&self, type_id: TypeId) -> Option<UserDataSerializer> {
+        unsafe {
+            (*self.0.extra.get())
+                .userdata_serializers
+                .get(&type_id)
+                .copied()
+        }
+    }
+
+    /// Tags a light userdata pointer with the Rust type `T` it points to.
+    ///
+    /// Tagged pointers can be extracted from Lua values as [`Tagged<T>`], which checks the tag.
+    /// Converting a [`Tagged<T>`] to a Lua value tags untagged pointers automatically, this
+    /// function is useful for pointers passed to Lua by C code.
+    ///
+    /// A pointer has only one tag, tagging it again replaces the previous tag (eg. when the memory
+    /// of a freed value is reused by a value of another type).
+    ///
+    /// Tags are kept until removed using [`Lua::untag_light_userdata`], so pointers must be
+    /// untagged when the value they point to is freed. Returns an error if too many pointers
+    /// (65536) are tagged.
+    ///
+    /// [`Tagged<T>`]: crate::Tagged
+    pub fn tag_light_userdata<T: 'static>(&self, ptr: *mut T) -> Result<()> {
+        self.set_light_userdata_tag(ptr as *const c_void, TypeId::of::<T>())
+    }
+
+    /// Removes the tag of a light userdata pointer.
+    ///
+    /// Returns `true` if the pointer was tagged with `T`, otherwise the tag is kept.
+    pub fn untag_light_userdata<T: 'static>(&self, ptr: *mut T) -> bool {
+        let extra = unsafe { &mut *self.0.extra.get() };
+        let ptr = ptr as *const c_void;
+        if extra.light_userdata_tags.get(&ptr) != Some(&TypeId::of::<T>()) {
+            return false;
+        }
+        extra.light_userdata_tags.remove(&ptr);
+        true
+    }
+
+    pub(crate) fn set_light_userdata_tag(&self, ptr: *const c_void, tag: TypeId) -> Result<()> {
+        let extra = unsafe { &mut *self.0.extra.get() };
+        let tags = &mut extra.light_userdata_tags;
+        if tags.len() >= LIGHT_USERDATA_TAGS_LIMIT && !tags.contains_key(&ptr) {
+            return Err(Error::RuntimeError(format!(
+                "too many tagged light userdata pointers (limit is {LIGHT_USERDATA_TAGS_LIMIT})"
+            )));
+        }
+        tags.insert(ptr, tag);
+        Ok(())
+    }
+
+    pub(crate) fn light_userdata_tag(&self, ptr: *mut c_void) -> Option<TypeId> {
+        let extra = unsafe { &*self.0.extra.get() };
+        (extra.light_userdata_tags)
+            .get(&(ptr as *const c_void))
+            .copied()
+    }
+
+    pub(crate) fn metrics_registry(&self) -> Option<Arc<MetricsRegistry>> {
+        unsafe { (*self.0.extra.get()).metrics.clone() }
+    }
+
+    pub(crate) fn set_metrics_registry(&self, registry: Option<Arc<MetricsRegistry>>) {
+        unsafe { (*self.0.extra.get()).metrics = registry };
+    }
+
+    #[cfg(feature = "typegen")]
+    pub(crate) fn userdata_type_infos(&self) -> Vec<(TypeId, crate::typegen::UserDataTypeInfo)> {
+        unsafe { (*self.0.extra.get()).userdata_types.clone() }
+    }
+
     /// Creates a Lua userdata object from a custom Rust type.
     ///
     /// You can register the type using [`Lua::register_userdata_type()`] to add fields or methods
@@ -1782,6 +3388,32 @@ impl Lua {
     /// Registers a custom Rust type in Lua to use in userdata objects.
     ///
     /// This methods provides a way to add fields or methods to userdata objects of a type `T`.
+    /// It's mostly useful for external types, which cannot implement [`UserData`] trait
+    /// because of the orphan rule.
+    ///
+    /// Calling this method again for the same type `T` replaces the previous registration.
+    /// Already created userdata objects keep their metatable, new objects use the new one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::net::Ipv4Addr;
+    /// # use mlua::{Lua, Result, UserDataFields, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// lua.register_userdata_type::<Ipv4Addr>(|reg| {
+    ///     reg.add_field_method_get("is_loopback", |_, this| Ok(this.is_loopback()));
+    ///     reg.add_method("octets", |_, this, ()| Ok(this.octets().to_vec()));
+    /// })?;
+    ///
+    /// let addr = lua.create_any_userdata(Ipv4Addr::LOCALHOST)?;
+    /// lua.globals().set("addr", addr)?;
+    /// lua.load("assert(addr.is_loopback and addr:octets()[1] == 127)").exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`UserData`]: crate::UserData
     pub fn register_userdata_type<T: 'static>(
         &self,
         f: impl FnOnce(&mut UserDataRegistrar<T>),
@@ -1797,11 +3429,76 @@ impl Lua {
             }
 
             // Register the type
-            self.register_userdata_metatable(registry)?;
+            let table_id = self.register_userdata_metatable(registry)?;
+            let extra = &mut *self.0.extra.get();
+            extra.registered_userdata.insert(type_id, table_id as c_int);
+            extra.registered_userdata_types.insert(type_id);
             Ok(())
         }
     }
 
+    /// Returns `true` if the type `T` was registered using [`Lua::register_userdata_type()`].
+    ///
+    /// Creating userdata of an unregistered type using [`Lua::create_any_userdata()`] does not
+    /// register it.
+    pub fn is_userdata_type_registered<T: 'static>(&self) -> bool {
+        let extra = unsafe { &*self.0.extra.get() };
+        extra.registered_userdata_types.contains(&TypeId::of::<T>())
+    }
+
+    /// Creates a Lua userdata object from a custom Rust type, registering methods and fields
+    /// for the type using the provided closure.
+    ///
+    /// This is useful for types that cannot implement [`UserData`] (eg. because of the orphan rule).
+    ///
+    /// The closure `f` is called only once per combination of `T` and the closure type, the
+    /// resulting metatable is reused for all subsequent calls with the same closure. It does not
+    /// affect the metatable
+    /// registered using [`Lua::register_userdata_type()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// fn wrap_path(lua: &Lua, path: std::path::PathBuf) -> Result<mlua::AnyUserData> {
+    ///     lua.create_any_userdata_with(path, |reg| {
+    ///         reg.add_method("exists", |_, this, ()| Ok(this.exists()));
+    ///     })
+    /// }
+    ///
+    /// lua.globals().set("path", wrap_path(&lua, "/".into())?)?;
+    /// lua.load("assert(path:exists())").exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`UserData`]: crate::UserData
+    pub fn create_any_userdata_with<T, F>(&self, data: T, f: F) -> Result<AnyUserData>
+    where
+        T: MaybeSend + 'static,
+        F: FnOnce(&mut UserDataRegistrar<T>) + 'static,
+    {
+        unsafe {
+            self.make_userdata_with_metatable(UserDataCell::new(data), || {
+                // Check if the metatable is already created for this closure
+                let key = (TypeId::of::<T>(), TypeId::of::<F>());
+                if let Some(&table_id) = (*self.0.extra.get()).registered_userdata_with.get(&key) {
+                    return Ok(table_id as Integer);
+                }
+
+                let mut registry = UserDataRegistrar::new();
+                f(&mut registry);
+                let table_id = self.register_userdata_metatable(registry)?;
+                (*self.0.extra.get())
+                    .registered_userdata_with
+                    .insert(key, table_id as c_int);
+                Ok(table_id)
+            })
+        }
+    }
+
     /// Create a Lua userdata "proxy" object from a custom userdata type.
     ///
     /// Proxy object is an empty userdata object that has `T` metatable attached.
@@ -1852,19 +3549,128 @@ impl Lua {
             ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, ffi::LUA_RIDX_GLOBALS);
             #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
             ffi::lua_pushvalue(state, ffi::LUA_GLOBALSINDEX);
+            // Threads created before enabling sandbox mode still have the read-only globals,
+            // return the sandboxed main environment instead
+            #[cfg(feature = "luau")]
+            if (*self.0.extra.get()).sandboxed && ffi::lua_getreadonly(state, -1) != 0 {
+                ffi::lua_pop(state, 1);
+                ffi::lua_xpush(self.0.main_state, state, ffi::LUA_GLOBALSINDEX);
+            }
             Table(self.pop_ref())
         }
     }
 
-    /// Returns a handle to the active `Thread`. For calls to `Lua` this will be the main Lua thread,
-    /// for parameters given to a callback, this will be whatever Lua thread called the callback.
-    pub fn current_thread(&self) -> Thread {
-        let state = self.state();
+    /// Returns a handle to the active `Thread`. For calls to `Lua` this will be the main Lua thread,
+    /// for parameters given to a callback, this will be whatever Lua thread called the callback.
+    pub fn current_thread(&self) -> Thread {
+        let state = self.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 1);
+            ffi::lua_pushthread(state);
+            Thread(self.pop_ref())
+        }
+    }
+
+    /// Calls the given function in arena mode, releasing the resources used by the values created
+    /// inside it en masse on completion.
+    ///
+    /// Handles of Lua values are stored in an auxiliary stack, which grows as needed and is
+    /// not shrunk when the handles are dropped. Likewise, registry values of dropped
+    /// [`RegistryKey`]s are kept until [`Lua::expire_registry_values`] is called. In
+    /// request-per-iteration patterns (eg. in servers) this makes the memory usage slowly grow.
+    ///
+    /// When the function returns, the registry values of dropped keys are removed and the
+    /// auxiliary stack is shrunk back, releasing the slots of handles created inside the arena
+    /// that were dropped. Handles that are still alive (eg. returned from the function) stay valid
+    /// and keep their slots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let handler = lua.create_function(|lua, n: usize| {
+    ///     let tables = (0..n).map(|_| lua.create_table()).collect::<Result<Vec<_>>>()?;
+    ///     Ok(tables.len())
+    /// })?;
+    ///
+    /// for _ in 0..10 {
+    ///     let result = lua.with_arena(|lua| {
+    ///         let count: usize = handler.call(100)?;
+    ///         lua.create_table_from([("count", count)])
+    ///     })?;
+    ///     assert_eq!(result.get::<_, usize>("count")?, 100);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_arena<R>(&self, f: impl FnOnce(&Lua) -> Result<R>) -> Result<R> {
+        let top = unsafe { (*self.0.extra.get()).ref_stack_top };
+        let ret = f(self);
+        self.expire_registry_values();
+        unsafe { ref_stack_trim(&mut *self.0.extra.get(), top) };
+        ret
+    }
+
+    /// Returns usage statistics of the auxiliary stack storing handles of Lua values.
+    ///
+    /// The number of live handles can be used to detect leaked handles, eg. in tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let live = lua.ref_thread_stats().live;
+    /// let table = lua.create_table()?;
+    /// assert_eq!(lua.ref_thread_stats().live, live + 1);
+    /// drop(table);
+    /// assert_eq!(lua.ref_thread_stats().live, live);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ref_thread_stats(&self) -> RefThreadStats {
+        let extra = unsafe { &*self.0.extra.get() };
+        RefThreadStats {
+            capacity: extra.ref_stack_size as usize,
+            used: extra.ref_stack_top as usize,
+            holes: extra.ref_free.len(),
+            live: extra.ref_stack_top as usize - extra.ref_free.len(),
+            high_water_mark: extra.ref_stack_max as usize,
+        }
+    }
+
+    /// Compacts the auxiliary stack storing handles of Lua values.
+    ///
+    /// Live values are relocated to the holes left by dropped handles, so the stack shrinks to
+    /// the number of live handles. Existing handles remain valid. The capacity of the stack is
+    /// lowered as well, so Lua can shrink the stack memory during garbage collection.
+    /// Returns the number of released slots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let mut tables = (0..100).map(|_| lua.create_table()).collect::<Result<Vec<_>>>()?;
+    /// let last = tables.pop().unwrap();
+    /// tables.clear();
+    /// assert!(lua.compact_refs() >= 99);
+    /// assert_eq!(lua.ref_thread_stats().holes, 0);
+    /// last.set("key", "value")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compact_refs(&self) -> usize {
         unsafe {
-            let _sg = StackGuard::new(state);
-            assert_stack(state, 1);
-            ffi::lua_pushthread(state);
-            Thread(self.pop_ref())
+            let extra = &mut *self.0.extra.get();
+            let n = ref_stack_compact(extra);
+            extra.ref_stack_size = extra.ref_stack_top.max(ffi::LUA_MINSTACK - 1);
+            n
         }
     }
 
@@ -1889,7 +3695,7 @@ impl Lua {
     /// would be impossible to prevent handles to scoped values from escaping anyway, since you
     /// would always be able to smuggle them through Lua state.
     pub fn scope<'scope, R>(&self, f: impl FnOnce(&Scope<'scope>) -> Result<R>) -> Result<R> {
-        f(&Scope::new(self))
+        Scope::with(self, |scope| f(&scope))
     }
 
     /// Attempts to coerce a Lua value into a String in a manner consistent with Lua's internal
@@ -1973,6 +3779,80 @@ impl Lua {
         })
     }
 
+    /// Converts a number to a string in the same way as Lua does (eg. in `tostring`).
+    ///
+    /// The format depends on the Lua version (see `LUAI_NUMFFORMAT`), for example `1.0` is
+    /// converted to `"1.0"` in Lua 5.3+ and to `"1"` in older versions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let s = lua.number_to_string(0.1)?;
+    /// assert_eq!(s, lua.load("tostring(0.1)").eval::<String>()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn number_to_string(&self, n: Number) -> Result<StdString> {
+        let s = self.coerce_string(Value::Number(n))?;
+        let s = mlua_expect!(s, "number must be convertible to string");
+        Ok(s.to_string_lossy().into_owned())
+    }
+
+    /// Converts a string to a number following the Lua coercion rules (eg. of `tonumber`).
+    ///
+    /// Returns [`Value::Integer`] or [`Value::Number`] if the string is a valid numeral, or `None`
+    /// otherwise. Leading and trailing spaces are allowed, supported formats depend on the Lua
+    /// version (eg. Lua 5.1 does not support hexadecimal floats like `"0x1p4"`). In Lua 5.3+ a
+    /// numeral without a decimal point or exponent (like
+    /// `"10"`) is converted to an integer, other versions convert all numbers to floats, which
+    /// are returned as [`Value::Integer`] if they have an exact integer representation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// assert_eq!(lua.string_to_number(" 0x10 ")?, Some(Value::Integer(16)));
+    /// assert_eq!(lua.string_to_number("1.5e1")?, Some(Value::Number(15.0)));
+    /// assert_eq!(lua.string_to_number("abc")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn string_to_number(&self, s: impl AsRef<[u8]>) -> Result<Option<Value>> {
+        let state = self.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            #[cfg(any(feature = "lua54", feature = "lua53"))]
+            {
+                // Strings with embedded zeros are never valid numerals
+                let s = match CString::new(s.as_ref()) {
+                    Ok(s) => s,
+                    Err(_) => return Ok(None),
+                };
+                if ffi::lua_stringtonumber(state, s.as_ptr()) == 0 {
+                    return Ok(None);
+                }
+            }
+            #[cfg(not(any(feature = "lua54", feature = "lua53")))]
+            {
+                push_string(state, s.as_ref(), !self.unlikely_memory_error())?;
+                let mut isnum = 0;
+                let n = ffi::lua_tonumberx(state, -1, &mut isnum);
+                if isnum == 0 {
+                    return Ok(None);
+                }
+                ffi::lua_pushnumber(state, n);
+            }
+            Ok(Some(self.pop_value()))
+        }
+    }
+
     /// Converts a value that implements `IntoLua` into a `Value` instance.
     pub fn pack<T: IntoLua>(&self, t: T) -> Result<Value> {
         t.into_lua(self)
@@ -2351,6 +4231,11 @@ impl Lua {
                 self.push_ref(&ud.0);
             }
 
+            #[cfg(feature = "luau")]
+            Value::Buffer(buf) => {
+                self.push_ref(&buf.0);
+            }
+
             Value::Error(err) => {
                 let protect = !self.unlikely_memory_error();
                 push_gc_userdata(state, WrappedFailure::Error(err), protect)?;
@@ -2448,6 +4333,9 @@ impl Lua {
 
             ffi::LUA_TTHREAD => Value::Thread(Thread(self.pop_ref())),
 
+            #[cfg(feature = "luau")]
+            ffi::LUA_TBUFFER => Value::Buffer(LuauBuffer(self.pop_ref())),
+
             #[cfg(feature = "luajit")]
             ffi::LUA_TCDATA => {
                 ffi::lua_pop(state, 1);
@@ -2465,7 +4353,7 @@ impl Lua {
             Arc::ptr_eq(&lref.lua.0, &self.0),
             "Lua instance passed Value created from a different main Lua state"
         );
-        ffi::lua_xpush(self.ref_thread(), self.state(), lref.index);
+        ffi::lua_xpush(self.ref_thread(), self.state(), lref.index());
     }
 
     // Pops the topmost element of the stack and stores a reference to it. This pins the object,
@@ -2479,61 +4367,79 @@ impl Lua {
     // references.
     pub(crate) unsafe fn pop_ref(&self) -> LuaRef {
         ffi::lua_xmove(self.state(), self.ref_thread(), 1);
-        let index = ref_stack_pop(&mut *self.0.extra.get());
-        LuaRef::new(self.clone(), index)
+        let handle = ref_stack_pop(&mut *self.0.extra.get());
+        LuaRef::new(self.clone(), handle)
     }
 
     // Same as `pop_ref` but assumes the value is already on the reference thread
     pub(crate) unsafe fn pop_ref_thread(&self) -> LuaRef {
-        let index = ref_stack_pop(&mut *self.0.extra.get());
-        LuaRef::new(self.clone(), index)
+        let handle = ref_stack_pop(&mut *self.0.extra.get());
+        LuaRef::new(self.clone(), handle)
     }
 
     pub(crate) fn clone_ref(&self, lref: &LuaRef) -> LuaRef {
         unsafe {
-            ffi::lua_pushvalue(self.ref_thread(), lref.index);
-            let index = ref_stack_pop(&mut *self.0.extra.get());
-            LuaRef::new(self.clone(), index)
+            ffi::lua_pushvalue(self.ref_thread(), lref.index());
+            let handle = ref_stack_pop(&mut *self.0.extra.get());
+            LuaRef::new(self.clone(), handle)
         }
     }
 
-    pub(crate) fn drop_ref_index(&self, index: c_int) {
-        unsafe {
-            let ref_thread = self.ref_thread();
-            ffi::lua_pushnil(ref_thread);
-            ffi::lua_replace(ref_thread, index);
-            (*self.0.extra.get()).ref_free.push(index);
-        }
+    pub(crate) fn drop_ref(&self, handle: c_int) {
+        unsafe { ref_stack_free(&mut *self.0.extra.get(), handle) }
     }
 
-    #[cfg(feature = "unstable")]
-    pub(crate) fn adopt_owned_ref(&self, loref: crate::types::LuaOwnedRef) -> LuaRef {
-        assert!(
-            Arc::ptr_eq(&loref.lua.0, &self.0),
-            "Lua instance passed Value created from a different main Lua state"
-        );
-        let index = loref.index;
-        unsafe {
-            ptr::read(&loref.lua);
-            mem::forget(loref);
-        }
-        LuaRef::new(self, index)
+    // Returns the stack index of the reference thread value with the given handle
+    #[inline]
+    pub(crate) fn ref_index(&self, handle: c_int) -> c_int {
+        unsafe { ref_stack_index(&*self.0.extra.get(), handle) }
     }
 
     unsafe fn register_userdata_metatable<T: 'static>(
         &self,
-        registry: UserDataRegistrar<T>,
+        mut registry: UserDataRegistrar<T>,
     ) -> Result<Integer> {
+        registry.apply_change_hooks();
+
         let state = self.state();
         let _sg = StackGuard::new(state);
         check_stack(state, 13)?;
 
+        // Methods dispatched directly by `__namecall` (without `__index` lookup).
+        // Methods are called from C there and cannot yield, so by default types with async
+        // methods or custom `__index` are not dispatched. Methods shadowed by field getters are
+        // skipped too.
+        #[cfg(feature = "luau")]
+        let namecall_methods = {
+            let is_custom = |k: &StdString| k == "__index" || k == "__namecall";
+            let dispatch = !registry.meta_methods.iter().any(|(k, _)| is_custom(k))
+                && !registry.meta_fields.iter().any(|(k, _)| is_custom(k));
+            #[cfg(feature = "async")]
+            let dispatch = dispatch && registry.async_methods.is_empty();
+            let is_field = |k: &StdString| {
+                registry.field_getters.iter().any(|(f, _)| f == k)
+                    || registry.field_accessors.iter().any(|(f, _)| f == k)
+            };
+            let enabled = |k: &StdString| match registry.namecall.iter().rfind(|(n, _)| n == k) {
+                Some(&(_, enabled)) => enabled,
+                None => dispatch && !is_field(k),
+            };
+            (registry.methods.iter())
+                .filter(|(k, _)| enabled(k))
+                .map(|(k, _)| k.clone())
+                .collect::<Vec<_>>()
+        };
+
+        #[cfg(feature = "typegen")]
+        let mut type_info = crate::typegen::UserDataTypeInfo::new(&registry);
+
         // Prepare metatable, add meta methods first and then meta fields
         let metatable_nrec = registry.meta_methods.len() + registry.meta_fields.len();
         #[cfg(feature = "async")]
         let metatable_nrec = metatable_nrec + registry.async_meta_methods.len();
         push_table(state, 0, metatable_nrec as c_int, true)?;
         for (k, m) in registry.meta_methods {
+            let m = self.instrument_callback(|| get_function_name::<T>(&k), m);
             self.push_value(Value::Function(self.create_callback(m)?))?;
             rawset_field(state, -2, MetaMethod::validate(&k)?)?;
         }
@@ -2543,7 +4449,15 @@ impl Lua {
             rawset_field(state, -2, MetaMethod::validate(&k)?)?;
         }
         for (k, f) in registry.meta_fields {
-            self.push_value(f(self)?)?;
+            let value = f(self)?;
+            #[cfg(feature = "typegen")]
+            if let Some(info) = type_info
+                .as_mut()
+                .filter(|_| k == "__type" || k == "__name")
+            {
+                info.set_name(&value);
+            }
+            self.push_value(value)?;
             rawset_field(state, -2, MetaMethod::validate(&k)?)?;
         }
         let metatable_index = ffi::lua_absindex(state, -1);
@@ -2551,13 +4465,27 @@ impl Lua {
         let mut extra_tables_count = 0;
 
         let mut field_getters_index = None;
-        let field_getters_nrec = registry.field_getters.len();
+        let field_getters_nrec = registry.field_getters.len() + registry.field_accessors.len();
+        #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+        let field_getters_nrec = field_getters_nrec + registry.async_field_getters.len();
         if field_getters_nrec > 0 {
             push_table(state, 0, field_getters_nrec as c_int, true)?;
             for (k, m) in registry.field_getters {
+                let m = self.instrument_callback(|| get_function_name::<T>(&k), m);
                 self.push_value(Value::Function(self.create_callback(m)?))?;
                 rawset_field(state, -2, &k)?;
             }
+            #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+            for (k, m) in registry.async_field_getters {
+                self.push_value(Value::Function(self.create_async_callback(m)?))?;
+                rawset_field(state, -2, &k)?;
+            }
+            for (k, (func, ptr)) in registry.field_accessors {
+                ffi::lua_pushlightuserdata(state, ptr as *mut c_void);
+                push_string(state, k.as_bytes(), true)?;
+                protect_lua!(state, 2, 1, |state| ffi::lua_pushcclosure(state, func, 2))?;
+                rawset_field(state, -2, &k)?;
+            }
             field_getters_index = Some(ffi::lua_absindex(state, -1));
             extra_tables_count += 1;
         }
@@ -2567,6 +4495,7 @@ impl Lua {
         if field_setters_nrec > 0 {
             push_table(state, 0, field_setters_nrec as c_int, true)?;
             for (k, m) in registry.field_setters {
+                let m = self.instrument_callback(|| get_function_name::<T>(&k), m);
                 self.push_value(Value::Function(self.create_callback(m)?))?;
                 rawset_field(state, -2, &k)?;
             }
@@ -2575,12 +4504,24 @@ impl Lua {
         }
 
         let mut methods_index = None;
-        let methods_nrec = registry.methods.len();
+        let methods_nrec = registry.methods.len() + registry.fields.len();
         #[cfg(feature = "async")]
         let methods_nrec = methods_nrec + registry.async_methods.len();
         if methods_nrec > 0 {
+            // Static fields are stored alongside methods
             push_table(state, 0, methods_nrec as c_int, true)?;
+            for (k, f) in registry.fields {
+                let value = f(self)?;
+                #[cfg(feature = "typegen")]
+                if let Some(info) = type_info.as_mut() {
+                    let ty = crate::typegen::value_type(self, &value, &mut Default::default(), 0)?;
+                    info.set_field_type(&k, ty);
+                }
+                self.push_value(value)?;
+                rawset_field(state, -2, &k)?;
+            }
             for (k, m) in registry.methods {
+                let m = self.instrument_callback(|| get_function_name::<T>(&k), m);
                 self.push_value(Value::Function(self.create_callback(m)?))?;
                 rawset_field(state, -2, &k)?;
             }
@@ -2593,6 +4534,28 @@ impl Lua {
             extra_tables_count += 1;
         }
 
+        #[cfg(feature = "luau")]
+        if let (Some(methods_index), false) = (methods_index, namecall_methods.is_empty()) {
+            // Methods are stored by their names and atoms of the (interned) names
+            let nmethods = namecall_methods.len() as c_int;
+            push_table(state, nmethods, nmethods, true)?;
+            for k in &namecall_methods {
+                push_string(state, k.as_bytes(), true)?;
+                ffi::lua_rawget(state, methods_index);
+                if let Some(atom) = util::method_atom(k.as_bytes()) {
+                    ffi::lua_pushvalue(state, -2);
+                    ffi::lua_pushvalue(state, -2);
+                    let n = atom as Integer + 1;
+                    protect_lua!(state, 2, 0, |state| ffi::lua_rawseti(state, -2, n))?;
+                }
+                rawset_field(state, -2, k)?;
+            }
+            protect_lua!(state, 1, 1, fn(state) {
+                ffi::lua_pushcclosure(state, util::userdata_namecall, 1);
+            })?;
+            rawset_field(state, metatable_index, "__namecall")?;
+        }
+
         init_userdata_metatable::<UserDataCell<T>>(
             state,
             metatable_index,
@@ -2609,13 +4572,16 @@ impl Lua {
             ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX)
         })?;
 
-        let type_id = TypeId::of::<T>();
-        (*self.0.extra.get())
-            .registered_userdata
-            .insert(type_id, id);
         (*self.0.extra.get())
             .registered_userdata_mt
-            .insert(mt_ptr, Some(type_id));
+            .insert(mt_ptr, Some(TypeId::of::<T>()));
+
+        #[cfg(feature = "typegen")]
+        if let Some(info) = type_info {
+            let userdata_types = &mut (*self.0.extra.get()).userdata_types;
+            userdata_types.retain(|(type_id, _)| *type_id != TypeId::of::<T>());
+            userdata_types.push((TypeId::of::<T>(), info));
+        }
 
         Ok(id as Integer)
     }
@@ -2639,6 +4605,22 @@ impl Lua {
         }
     }
 
+    // Returns registry id of a cached metatable for non-'static userdata
+    #[inline]
+    pub(crate) unsafe fn nonstatic_userdata_metatable(&self, tag: TypeId) -> Option<c_int> {
+        (*self.0.extra.get())
+            .registered_nonstatic_userdata
+            .get(&tag)
+            .copied()
+    }
+
+    #[inline]
+    pub(crate) unsafe fn register_nonstatic_userdata_metatable(&self, tag: TypeId, mt_id: c_int) {
+        (*self.0.extra.get())
+            .registered_nonstatic_userdata
+            .insert(tag, mt_id);
+    }
+
     // Pushes a LuaRef value onto the stack, checking that it's a registered
     // and not destructed UserData.
     // Uses 2 stack spaces, does not call checkstack.
@@ -2679,7 +4661,7 @@ impl Lua {
     // So we instead use a caller provided lifetime, which without the 'static requirement would be
     // unsafe.
     pub(crate) fn create_callback(&self, func: Callback<'static>) -> Result<Function> {
-        unsafe extern "C" fn call_callback(state: *mut ffi::lua_State) -> c_int {
+        unsafe extern "C-unwind" fn call_callback(state: *mut ffi::lua_State) -> c_int {
             let extra = match ffi::lua_type(state, ffi::lua_upvalueindex(1)) {
                 ffi::LUA_TUSERDATA => {
                     let upvalue = get_userdata::<CallbackUpvalue>(state, ffi::lua_upvalueindex(1));
@@ -2687,6 +4669,11 @@ impl Lua {
                 }
                 _ => ptr::null_mut(),
             };
+            // The Lua instance is gone if the state was released by mlua
+            #[cfg(not(feature = "module"))]
+            if !extra.is_null() && (*extra).inner.is_none() {
+                return callback_error(state, |_| Err(Error::CallbackDestructed));
+            }
             callback_error_ext(state, extra, |nargs| {
                 let upvalue_idx = ffi::lua_upvalueindex(1);
                 if ffi::lua_type(state, upvalue_idx) == ffi::LUA_TNIL {
@@ -2700,6 +4687,7 @@ impl Lua {
 
                 let lua: &Lua = mem::transmute((*extra).inner.as_ref().unwrap());
                 let _guard = StateGuard::new(&lua.0, state);
+                lua.report_memory_events(state, 1)?;
 
                 let mut args = MultiValue::new_or_pooled(lua);
                 args.reserve(nargs as usize);
@@ -2757,7 +4745,7 @@ impl Lua {
             }
         }
 
-        unsafe extern "C" fn call_callback(state: *mut ffi::lua_State) -> c_int {
+        unsafe extern "C-unwind" fn call_callback(state: *mut ffi::lua_State) -> c_int {
             let extra = match ffi::lua_type(state, ffi::lua_upvalueindex(1)) {
                 ffi::LUA_TUSERDATA => {
                     let upvalue =
@@ -2787,7 +4775,7 @@ impl Lua {
                 }
 
                 let func = &*(*upvalue).data;
-                let fut = func(lua, args);
+                let fut = func(lua.clone(), args);
                 let extra = Arc::clone(&(*upvalue).extra);
                 let protect = !lua.unlikely_memory_error();
                 push_gc_userdata(state, AsyncPollUpvalue { data: fut, extra }, protect)?;
@@ -2803,7 +4791,7 @@ impl Lua {
             })
         }
 
-        unsafe extern "C" fn poll_future(state: *mut ffi::lua_State) -> c_int {
+        unsafe extern "C-unwind" fn poll_future(state: *mut ffi::lua_State) -> c_int {
             let extra = match ffi::lua_type(state, ffi::lua_upvalueindex(1)) {
                 ffi::LUA_TUSERDATA => {
                     let upvalue = get_userdata::<AsyncPollUpvalue>(state, ffi::lua_upvalueindex(1));
@@ -2834,14 +4822,15 @@ impl Lua {
                         Ok(1)
                     }
                     Poll::Ready(results) => {
-                        let results = results?;
-                        let nresults = results.len() as Integer;
-                        let results = lua.create_sequence_from(results)?;
-                        check_stack(state, 3)?;
+                        let mut results = results?;
+                        let nresults = results.len() as c_int;
+                        check_stack(state, nresults + 1)?;
                         ffi::lua_pushboolean(state, 1);
-                        lua.push_value(Value::Table(results))?;
-                        lua.push_value(Value::Integer(nresults))?;
-                        Ok(3)
+                        for r in results.drain_all() {
+                            lua.push_value(r)?;
+                        }
+                        MultiValue::return_to_pool(results, lua);
+                        Ok(nresults + 1)
                     }
                 }
             })
@@ -2853,7 +4842,7 @@ impl Lua {
             check_stack(state, 4)?;
 
             let func = mem::transmute(func);
-            let extra = Arc::clone(&self.extra);
+            let extra = Arc::clone(&self.0.extra);
             let protect = !self.unlikely_memory_error();
             let upvalue = AsyncCallbackUpvalue { data: func, extra };
             push_gc_userdata(state, upvalue, protect)?;
@@ -2868,39 +4857,28 @@ impl Lua {
             Function(self.pop_ref())
         };
 
-        unsafe extern "C" fn unpack(state: *mut ffi::lua_State) -> c_int {
-            let len = ffi::lua_tointeger(state, 2);
-            ffi::luaL_checkstack(state, len as c_int, ptr::null());
-            for i in 1..=len {
-                ffi::lua_rawgeti(state, 1, i);
-            }
-            len as c_int
-        }
-
-        let coroutine = self.globals().get::<_, Table>("coroutine")?;
+        let coroutine = self.globals_storage()?.get::<_, Table>("coroutine")?;
 
         let env = self.create_table_with_capacity(0, 4)?;
         env.set("get_poll", get_poll)?;
+        env.set(
+            "new_pending",
+            self.create_function(|_, poll| Ok(PollPending::new(poll)))?,
+        )?;
         env.set("yield", coroutine.get::<_, Function>("yield")?)?;
-        unsafe {
-            env.set("unpack", self.create_c_function(unpack)?)?;
-        }
-        env.set("pending", {
-            LightUserData(&ASYNC_POLL_PENDING as *const u8 as *mut c_void)
-        })?;
 
         // We set `poll` variable in the env table to be able to destroy upvalues
         self.load(
             r#"
-            poll = get_poll(...)
-            local poll, pending, yield, unpack = poll, pending, yield, unpack
-            while true do
-                local ready, res, nres = poll()
-                if ready then
-                    return unpack(res, nres)
-                end
+            poll = new_pending(get_poll(...))
+            local pending, yield = poll, yield
+            local function results(_ready, ...)
+                return ...
+            end
+            while not pending:poll() do
                 yield(pending)
             end
+            return results(pending:poll())
             "#,
         )
         .try_cache()
@@ -2921,24 +4899,82 @@ impl Lua {
         mem::replace(&mut (*self.0.extra.get()).waker, waker)
     }
 
+    #[cfg(feature = "async")]
+    #[inline]
+    pub(crate) fn async_timeout(&self) -> Option<Duration> {
+        unsafe { (*self.0.extra.get()).async_timeout }
+    }
+
+    #[cfg(feature = "async")]
+    #[inline]
+    pub(crate) fn gc_budget(&self) -> Option<c_int> {
+        unsafe { (*self.0.extra.get()).gc_budget }
+    }
+
+    // Calls the hook set by `Lua::set_yield_async_hook`
+    #[cfg(feature = "async")]
+    pub(crate) fn call_yield_async_hook(&self) -> Result<()> {
+        // The hook is cloned as it can replace itself
+        match unsafe { (*self.0.extra.get()).yield_async_hook.clone() } {
+            Some(hook) => hook(self),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+    #[inline]
+    pub(crate) unsafe fn set_preempted_thread(
+        &self,
+        state: *mut ffi::lua_State,
+    ) -> *mut ffi::lua_State {
+        mem::replace(&mut (*self.0.extra.get()).preempted_thread, state)
+    }
+
+    // Marks the thread as preempted if it is the preempted thread and can yield
+    #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+    pub(crate) unsafe fn preempt_thread(&self, state: *mut ffi::lua_State) -> bool {
+        let extra = self.0.extra.get();
+        if (*extra).preempted_thread != state || ffi::lua_isyieldable(state) == 0 {
+            return false;
+        }
+        (*extra).thread_preempted = true;
+        true
+    }
+
+    #[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+    #[inline]
+    pub(crate) unsafe fn take_thread_preempted(&self) -> bool {
+        mem::take(&mut (*self.0.extra.get()).thread_preempted)
+    }
+
     pub(crate) unsafe fn make_userdata<T>(&self, data: UserDataCell<T>) -> Result<AnyUserData>
     where
         T: UserData + 'static,
     {
-        self.make_userdata_with_metatable(data, || {
-            // Check if userdata/metatable is already registered
-            let type_id = TypeId::of::<T>();
-            if let Some(&table_id) = (*self.0.extra.get()).registered_userdata.get(&type_id) {
-                return Ok(table_id as Integer);
-            }
+        self.make_userdata_with_metatable(data, || self.userdata_metatable_id::<T>())
+    }
 
-            // Create new metatable from UserData definition
-            let mut registry = UserDataRegistrar::new();
-            T::add_fields(&mut registry);
-            T::add_methods(&mut registry);
+    // Returns registry id of the `UserData` type metatable, registering the type if needed
+    pub(crate) unsafe fn userdata_metatable_id<T>(&self) -> Result<Integer>
+    where
+        T: UserData + 'static,
+    {
+        // Check if userdata/metatable is already registered
+        let type_id = TypeId::of::<T>();
+        if let Some(&table_id) = (*self.0.extra.get()).registered_userdata.get(&type_id) {
+            return Ok(table_id as Integer);
+        }
 
-            self.register_userdata_metatable(registry)
-        })
+        // Create new metatable from UserData definition
+        let mut registry = UserDataRegistrar::new();
+        T::add_fields(&mut registry);
+        T::add_methods(&mut registry);
+
+        let table_id = self.register_userdata_metatable(registry)?;
+        (*self.0.extra.get())
+            .registered_userdata
+            .insert(type_id, table_id as c_int);
+        Ok(table_id)
     }
 
     pub(crate) unsafe fn make_any_userdata<T>(&self, data: UserDataCell<T>) -> Result<AnyUserData>
@@ -2954,7 +4990,11 @@ impl Lua {
 
             // Create empty metatable
             let registry = UserDataRegistrar::new();
-            self.register_userdata_metatable::<T>(registry)
+            let table_id = self.register_userdata_metatable::<T>(registry)?;
+            (*self.0.extra.get())
+                .registered_userdata
+                .insert(type_id, table_id as c_int);
+            Ok(table_id)
         })
     }
 
@@ -2995,7 +5035,7 @@ impl Lua {
 
     #[cfg(not(feature = "luau"))]
     fn disable_c_modules(&self) -> Result<()> {
-        let package: Table = self.globals().get("package")?;
+        let package: Table = self.globals_storage()?.get("package")?;
 
         package.set(
             "loadlib",
@@ -3020,6 +5060,7 @@ impl Lua {
         Ok(())
     }
 
+    #[cfg(not(feature = "luau"))]
     pub(crate) unsafe fn try_from_ptr(state: *mut ffi::lua_State) -> Option<Self> {
         let extra = extra_data(state);
         if extra.is_null() {
@@ -3036,12 +5077,6 @@ impl Lua {
             .map(|x| x.as_ref().memory_limit == 0)
             .unwrap_or_default()
     }
-
-    #[cfg(feature = "unstable")]
-    #[inline]
-    pub(crate) fn clone(&self) -> Self {
-        Lua(Arc::clone(&self.0))
-    }
 }
 
 struct StateGuard<'a>(&'a LuaInner, *mut ffi::lua_State);
@@ -3059,6 +5094,51 @@ impl<'a> Drop for StateGuard<'a> {
     }
 }
 
+// Lua C function which implements field getters added by `UserDataFields::add_field_from`.
+// The accessor function pointer is stored in the first upvalue and the field name in the second.
+pub(crate) unsafe extern "C-unwind" fn userdata_field_accessor<T, R>(
+    state: *mut ffi::lua_State,
+) -> c_int
+where
+    T: 'static,
+    R: IntoLua + Clone + 'static,
+{
+    let extra = extra_data(state);
+    callback_error_ext(state, extra, |nargs| {
+        let lua: &Lua = mem::transmute((*extra).inner.as_ref().unwrap());
+        let _guard = StateGuard::new(&lua.0, state);
+
+        let accessor = ffi::lua_touserdata(state, ffi::lua_upvalueindex(1)) as *const ();
+        let accessor = mem::transmute::<*const (), fn(&T) -> &R>(accessor);
+        let bad_self_argument = |err| {
+            let name = util::to_string(state, ffi::lua_upvalueindex(2));
+            Error::bad_self_argument(&get_function_name::<T>(&name), err)
+        };
+
+        if nargs == 0 {
+            let err = Error::from_lua_conversion("missing argument", "userdata", None);
+            return Err(bad_self_argument(err));
+        }
+        // Only `self` argument is used
+        ffi::lua_pop(state, nargs - 1);
+        let userdata = AnyUserData::from_lua(lua.pop_value(), lua).map_err(bad_self_argument)?;
+
+        let value = {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 2)?;
+            let type_id = lua
+                .push_userdata_ref(&userdata.0)
+                .map_err(bad_self_argument)?;
+            with_userdata_ref(state, type_id, |data: &T| accessor(data).clone())
+                .map_err(bad_self_argument)?
+        };
+
+        check_stack(state, 1)?;
+        lua.push_value(value.into_lua(lua)?)?;
+        Ok(1)
+    })
+}
+
 #[cfg(feature = "luau")]
 unsafe fn extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
     (*ffi::lua_callbacks(state)).userdata as *mut ExtraData
@@ -3066,6 +5146,41 @@ unsafe fn extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
 
 #[cfg(not(feature = "luau"))]
 unsafe fn extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
+    registry_extra_data(state)
+}
+
+// Aborts pending async method calls of the userdata `ud_ptr` (on destruction).
+// Uses 1 stack space, does not call checkstack.
+#[cfg(all(feature = "async", not(feature = "luau")))]
+pub(crate) unsafe fn abort_userdata_tasks(state: *mut ffi::lua_State, ud_ptr: *const c_void) {
+    let extra = extra_data(state);
+    if !extra.is_null() {
+        abort_userdata_tasks_extra(extra, ud_ptr);
+    }
+}
+
+#[cfg(feature = "async")]
+unsafe fn abort_userdata_tasks_extra(extra: *mut ExtraData, ud_ptr: *const c_void) -> usize {
+    let tasks = (*extra).userdata_tasks.remove(&ud_ptr).unwrap_or_default();
+    // Wakers can run arbitrary code, so abort the tasks after releasing the registry
+    let tasks = tasks.iter().filter_map(Weak::upgrade).collect::<Vec<_>>();
+    tasks.iter().for_each(|task| task.abort());
+    tasks.len()
+}
+
+#[cfg(feature = "luau")]
+pub(crate) unsafe fn memory_info(state: *mut ffi::lua_State) -> *mut MemoryInfo {
+    let extra = extra_data(state);
+    if extra.is_null() {
+        return ptr::null_mut();
+    }
+    (*extra)
+        .mem_info
+        .map(|x| x.as_ptr())
+        .unwrap_or(ptr::null_mut())
+}
+
+unsafe fn registry_extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
     let extra_key = &EXTRA_REGISTRY_KEY as *const u8 as *const c_void;
     if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, extra_key) != ffi::LUA_TUSERDATA {
         ffi::lua_pop(state, 1);
@@ -3076,6 +5191,74 @@ unsafe fn extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
     (*extra_ptr).get()
 }
 
+// Rewrites chunk locations in the error message (or traceback) using registered source maps.
+// Uses 1 stack space, does not call checkstack.
+pub(crate) unsafe fn apply_source_maps(
+    state: *mut ffi::lua_State,
+    message: StdString,
+) -> StdString {
+    let extra = extra_data(state);
+    if extra.is_null() {
+        return message;
+    }
+    apply_source_maps_extra(extra, message)
+}
+
+// Traceback frames gathered for the original message are rewritten and kept for the new one.
+unsafe fn apply_source_maps_extra(extra: *mut ExtraData, message: StdString) -> StdString {
+    let source_maps = &(*extra).source_maps;
+    if source_maps.is_empty() {
+        return message;
+    }
+    let mut new_message = None;
+    for (short_src, map) in source_maps {
+        let current = new_message.as_deref().unwrap_or(message.as_str());
+        if let Some(rewritten) = rewrite_source_locations(current, short_src, map.as_ref()) {
+            new_message = Some(rewritten);
+        }
+    }
+    let new_message = new_message.unwrap_or_else(|| message.clone());
+
+    if let Some(frames) = error::traceback_frames(&message) {
+        let mut frames = frames.to_vec();
+        for frame in &mut frames {
+            let map = source_maps.get(&frame.source);
+            if let Some((source, line)) = frame.line.and_then(|line| map?.map_line(line)) {
+                frame.source = source;
+                frame.line = Some(line);
+            }
+        }
+        error::store_traceback_frames(&new_message, frames);
+    }
+    new_message
+}
+
+// Adapts `AsyncRead` to `io::Read`, blocking the current thread until data is available
+#[cfg(feature = "async")]
+struct BlockingReader<R>(R);
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> io::Read for BlockingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        struct ThreadWaker(thread::Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::new(&mut self.0).poll_read(&mut cx, buf) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+}
+
 // Creates required entries in the metatable cache (see `util::METATABLE_CACHE`)
 pub(crate) fn init_metatable_cache(cache: &mut FxHashMap<TypeId, u8>) {
     cache.insert(TypeId::of::<Arc<UnsafeCell<ExtraData>>>(), 0);
@@ -3124,7 +5307,7 @@ where
     // We cannot shadow Rust errors with Lua ones, so we need to obtain pre-allocated memory
     // to store a wrapped failure (error or panic) *before* we proceed.
     let prealloc_failure = match (*extra).wrapped_failure_pool.pop() {
-        Some(index) => PreallocatedFailure::Existing(index),
+        Some(handle) => PreallocatedFailure::Existing(handle),
         None => {
             let ud = WrappedFailure::new_userdata(state);
             ffi::lua_rotate(state, 1, 1);
@@ -3137,20 +5320,27 @@ where
             ffi::lua_settop(state, 1);
             ud
         }
-        PreallocatedFailure::Existing(index) => {
+        PreallocatedFailure::Existing(handle) => {
             ffi::lua_settop(state, 0);
             #[cfg(feature = "luau")]
             assert_stack(state, 2);
-            ffi::lua_pushvalue(ref_thread, index);
+            ffi::lua_pushvalue(ref_thread, ref_stack_index(&*extra, handle));
             ffi::lua_xmove(ref_thread, state, 1);
-            ffi::lua_pushnil(ref_thread);
-            ffi::lua_replace(ref_thread, index);
-            (*extra).ref_free.push(index);
+            ref_stack_free(&mut *extra, handle);
             ffi::lua_touserdata(state, -1) as *mut WrappedFailure
         }
     };
 
-    match catch_unwind(AssertUnwindSafe(|| f(nargs))) {
+    let result = match catch_unwind(AssertUnwindSafe(|| f(nargs))) {
+        Err(p) => match (*extra).panic_policy {
+            PanicPolicy::Propagate => Err(p),
+            PanicPolicy::ConvertToError => Ok(Err(Error::from_panic(p))),
+            PanicPolicy::Abort => std::process::abort(),
+        },
+        result => result,
+    };
+
+    match result {
         Ok(Ok(r)) => {
             // Return unused `WrappedFailure` to the pool
             match prealloc_failure {
@@ -3158,19 +5348,17 @@ where
                     if (*extra).wrapped_failure_pool.len() < WRAPPED_FAILURE_POOL_SIZE {
                         ffi::lua_rotate(state, 1, -1);
                         ffi::lua_xmove(state, ref_thread, 1);
-                        let index = ref_stack_pop(&mut *extra);
-                        (*extra).wrapped_failure_pool.push(index);
+                        let handle = ref_stack_pop(&mut *extra);
+                        (*extra).wrapped_failure_pool.push(handle);
                     } else {
                         ffi::lua_remove(state, 1);
                     }
                 }
-                PreallocatedFailure::Existing(index) => {
+                PreallocatedFailure::Existing(handle) => {
                     if (*extra).wrapped_failure_pool.len() < WRAPPED_FAILURE_POOL_SIZE {
-                        (*extra).wrapped_failure_pool.push(index);
+                        (*extra).wrapped_failure_pool.push(handle);
                     } else {
-                        ffi::lua_pushnil(ref_thread);
-                        ffi::lua_replace(ref_thread, index);
-                        (*extra).ref_free.push(index);
+                        ref_stack_free(&mut *extra, handle);
                     }
                 }
             }
@@ -3184,7 +5372,8 @@ where
                 ffi::luaL_traceback(state, state, ptr::null(), 0);
                 let traceback = util::to_string(state, -1);
                 ffi::lua_pop(state, 1);
-                traceback
+                error::store_traceback_frames(&traceback, util::traceback_frames(state, 0));
+                apply_source_maps_extra(extra, traceback)
             } else {
                 "<not enough stack space for traceback>".to_string()
             };
@@ -3304,6 +5493,12 @@ unsafe fn load_from_std_lib(state: *mut ffi::lua_State, libs: StdLib) -> Result<
         }
     }
 
+    #[cfg(feature = "luau")]
+    if libs.contains(StdLib::BUFFER) {
+        requiref(state, ffi::LUA_BUFFERLIBNAME, ffi::luaopen_buffer, 1)?;
+        ffi::lua_pop(state, 1);
+    }
+
     if libs.contains(StdLib::MATH) {
         requiref(state, ffi::LUA_MATHLIBNAME, ffi::luaopen_math, 1)?;
         ffi::lua_pop(state, 1);
@@ -3336,7 +5531,38 @@ unsafe fn load_from_std_lib(state: *mut ffi::lua_State, libs: StdLib) -> Result<
     Ok(())
 }
 
+// Moves the value on top of the reference thread stack into a free slot and returns its handle
 unsafe fn ref_stack_pop(extra: &mut ExtraData) -> c_int {
+    let index = ref_stack_pop_slot(extra);
+    match extra.ref_free_handles.pop() {
+        Some(handle) => {
+            extra.ref_slots[handle as usize] = index;
+            handle
+        }
+        None => {
+            extra.ref_slots.push(index);
+            (extra.ref_slots.len() - 1) as c_int
+        }
+    }
+}
+
+#[inline]
+unsafe fn ref_stack_index(extra: &ExtraData, handle: c_int) -> c_int {
+    let index = extra.ref_slots[handle as usize];
+    mlua_debug_assert!(index != 0, "use of a released reference");
+    index
+}
+
+// Releases the value of the handle, making its slot and the handle available for reuse
+unsafe fn ref_stack_free(extra: &mut ExtraData, handle: c_int) {
+    let index = mem::replace(&mut extra.ref_slots[handle as usize], 0);
+    ffi::lua_pushnil(extra.ref_thread);
+    ffi::lua_replace(extra.ref_thread, index);
+    extra.ref_free.push(index);
+    extra.ref_free_handles.push(handle);
+}
+
+unsafe fn ref_stack_pop_slot(extra: &mut ExtraData) -> c_int {
     if let Some(free) = extra.ref_free.pop() {
         ffi::lua_replace(extra.ref_thread, free);
         return free;
@@ -3362,9 +5588,57 @@ unsafe fn ref_stack_pop(extra: &mut ExtraData) -> c_int {
         extra.ref_stack_size += inc;
     }
     extra.ref_stack_top += 1;
+    extra.ref_stack_max = extra.ref_stack_max.max(extra.ref_stack_top);
     extra.ref_stack_top
 }
 
+// Releases the free slots at the top of the reference thread stack above `min_top`, so that the
+// stack does not keep growing with short term references. Returns the number of released slots.
+unsafe fn ref_stack_trim(extra: &mut ExtraData, min_top: c_int) -> usize {
+    // Sort the free slots in descending order, so the lowest slots are reused first
+    extra.ref_free.sort_unstable_by(|a, b| b.cmp(a));
+    let mut top = extra.ref_stack_top;
+    let mut n = 0;
+    while top > min_top && extra.ref_free.get(n) == Some(&top) {
+        mlua_debug_assert!(
+            ffi::lua_type(extra.ref_thread, top) == ffi::LUA_TNIL,
+            "releasing a live reference"
+        );
+        n += 1;
+        top -= 1;
+    }
+    extra.ref_free.drain(..n);
+    ffi::lua_settop(extra.ref_thread, top);
+    extra.ref_stack_top = top;
+    n
+}
+
+// Moves the values above the number of live slots to the free slots below, releasing the top of
+// the reference thread stack. Returns the number of released slots.
+unsafe fn ref_stack_compact(extra: &mut ExtraData) -> usize {
+    let live = extra.ref_stack_top - extra.ref_free.len() as c_int;
+    mlua_debug_assert!(
+        live as usize + extra.ref_free_handles.len() == extra.ref_slots.len(),
+        "reference leak detected"
+    );
+    let mut holes = extra
+        .ref_free
+        .iter()
+        .copied()
+        .filter(|&index| index <= live);
+    for index in extra.ref_slots.iter_mut().filter(|index| **index > live) {
+        let hole = mlua_expect!(holes.next(), "no free slot to relocate a reference");
+        ffi::lua_pushvalue(extra.ref_thread, *index);
+        ffi::lua_replace(extra.ref_thread, hole);
+        *index = hole;
+    }
+    let n = (extra.ref_stack_top - live) as usize;
+    extra.ref_free.clear();
+    ffi::lua_settop(extra.ref_thread, live);
+    extra.ref_stack_top = live;
+    n
+}
+
 #[cfg(test)]
 mod assertions {
     use super::*;
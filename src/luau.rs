@@ -1,20 +1,140 @@
+use std::cell::RefCell;
 use std::ffi::CStr;
 use std::os::raw::{c_float, c_int};
 use std::string::String as StdString;
 
+use rustc_hash::FxHashSet;
+
 use crate::chunk::ChunkMode;
 use crate::error::{Error, Result};
 use crate::ffi;
 use crate::lua::Lua;
 use crate::table::Table;
+use crate::types::MaybeSend;
 use crate::util::{check_stack, StackGuard};
 use crate::value::Value;
 
+/// Resolves and loads modules for the Luau `require` function.
+///
+/// Installed using [`Lua::set_require`]. Modules are required by string paths following the
+/// Luau require-by-string semantics: a path must start with `./` or `../` (relative to the
+/// requiring script), or with `@alias/` (relative to a directory the alias refers to).
+/// The `@self` alias refers to the requiring module itself, which is useful in `init` modules.
+///
+/// Requires `feature = "luau"`
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use mlua::{Error, Lua, Require, Result};
+/// struct VirtualFs(HashMap<String, String>);
+///
+/// impl Require for VirtualFs {
+///     fn load(&self, name: &str) -> Result<Vec<u8>> {
+///         match self.0.get(&format!("{name}.luau")) {
+///             Some(source) => Ok(source.as_bytes().to_vec()),
+///             None => Err(Error::RuntimeError(format!("module '{name}' not found"))),
+///         }
+///     }
+/// }
+///
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let mut files = HashMap::new();
+/// files.insert("lib/greet.luau".into(), "return require('./name') .. '!'".into());
+/// files.insert("lib/name.luau".into(), "return 'hello'".into());
+/// lua.set_require(VirtualFs(files))?;
+///
+/// let greet: String = lua.load("return require('./lib/greet')").set_name("@main").eval()?;
+/// assert_eq!(greet, "hello!");
+/// # Ok(())
+/// # }
+/// ```
+pub trait Require: MaybeSend + 'static {
+    /// Returns path of the directory the `@alias` refers to.
+    ///
+    /// The alias is passed in lowercase (aliases are case-insensitive).
+    /// The default implementation does not define any aliases.
+    fn alias(&self, alias: &str) -> Option<StdString> {
+        let _ = alias;
+        None
+    }
+
+    /// Resolves `path` required from the chunk named `parent` to a module name.
+    ///
+    /// The module name identifies the module in the cache and is used as a chunk name of the
+    /// loaded module, so requires of the module are resolved relative to it.
+    /// The default implementation joins the path with the directory of `parent` (or the alias
+    /// directory) and normalizes the result, eg. `./utils/../math` required from `lib/init`
+    /// resolves to `lib/math`.
+    fn resolve(&self, parent: &str, path: &str) -> Result<StdString> {
+        resolve_module_path(self, parent, path)
+    }
+
+    /// Loads source code (or bytecode) of the module.
+    fn load(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Returns `false` if the loaded module must not be cached.
+    ///
+    /// Modules that are not cached are loaded and executed every time they are required.
+    fn cache(&self, name: &str) -> bool {
+        let _ = name;
+        true
+    }
+}
+
 // Since Luau has some missing standard function, we re-implement them here
 
 impl Lua {
+    /// Replaces the `require` function with the one using the custom module resolver.
+    ///
+    /// See [`Require`] for details about module resolution. Loaded modules are cached in the
+    /// `_LOADED` registry table under their chunk names (`@` followed by the module name).
+    /// Cyclic dependencies between modules result in an error.
+    ///
+    /// Requires `feature = "luau"`
+    pub fn set_require(&self, require: impl Require) -> Result<()> {
+        let loading = RefCell::new(FxHashSet::default());
+        let require = self.create_function(move |lua, path: StdString| {
+            let parent = (lua.inspect_stack(1))
+                .and_then(|debug| debug.source().source.map(|s| s.to_vec()))
+                .and_then(|s| match s.split_first() {
+                    Some((b'@' | b'=', name)) => StdString::from_utf8(name.to_vec()).ok(),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            let name = require.resolve(&parent, &path)?;
+            let chunk_name = format!("@{name}");
+
+            let loaded = loaded_modules(lua)?;
+            if let Some(v) = loaded.raw_get(chunk_name.as_str())? {
+                return Ok(v);
+            }
+            if !loading.borrow_mut().insert(name.clone()) {
+                return Err(Error::RuntimeError(format!(
+                    "cyclic module dependency: '{name}' is already being loaded"
+                )));
+            }
+            let value = (require.load(&name))
+                .and_then(|source| lua.load(&source).set_name(&chunk_name).call::<_, Value>(()));
+            loading.borrow_mut().remove(&name);
+            let value = value?;
+
+            if require.cache(&name) {
+                let cached = match value.clone() {
+                    Value::Nil => Value::Boolean(true),
+                    v => v,
+                };
+                loaded.raw_set(chunk_name, cached)?;
+            }
+            Ok(value)
+        })?;
+        self.set_shared_global("require", require)
+    }
+
     pub(crate) unsafe fn prepare_luau_state(&self) -> Result<()> {
-        let globals = self.globals();
+        let globals = self.globals_storage()?;
 
         globals.raw_set(
             "collectgarbage",
@@ -33,7 +153,7 @@ impl Lua {
     }
 }
 
-unsafe extern "C" fn lua_collectgarbage(state: *mut ffi::lua_State) -> c_int {
+unsafe extern "C-unwind" fn lua_collectgarbage(state: *mut ffi::lua_State) -> c_int {
     let option = ffi::luaL_optstring(state, 1, cstr!("collect"));
     let option = CStr::from_ptr(option);
     let arg = ffi::luaL_optinteger(state, 2, 0);
@@ -70,19 +190,24 @@ unsafe extern "C" fn lua_collectgarbage(state: *mut ffi::lua_State) -> c_int {
     }
 }
 
-fn lua_require(lua: &Lua, name: Option<StdString>) -> Result<Value> {
-    let name = name.ok_or_else(|| Error::RuntimeError("invalid module name".into()))?;
-
-    // Find module in the cache
+// Returns the `_LOADED` registry table (creating it if needed)
+fn loaded_modules(lua: &Lua) -> Result<Table> {
     let state = lua.state();
-    let loaded = unsafe {
+    unsafe {
         let _sg = StackGuard::new(state);
         check_stack(state, 2)?;
         protect_lua!(state, 0, 1, fn(state) {
             ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADED"));
         })?;
-        Table(lua.pop_ref())
-    };
+        Ok(Table(lua.pop_ref()))
+    }
+}
+
+fn lua_require(lua: &Lua, name: Option<StdString>) -> Result<Value> {
+    let name = name.ok_or_else(|| Error::RuntimeError("invalid module name".into()))?;
+
+    // Find module in the cache
+    let loaded = loaded_modules(lua)?;
     if let Some(v) = loaded.raw_get(name.clone())? {
         return Ok(v);
     }
@@ -122,8 +247,53 @@ fn lua_require(lua: &Lua, name: Option<StdString>) -> Result<Value> {
     Ok(value)
 }
 
+fn resolve_module_path<R>(require: &R, parent: &str, path: &str) -> Result<StdString>
+where
+    R: Require + ?Sized,
+{
+    let parent_dir = || {
+        parent
+            .rsplit_once('/')
+            .map(|(dir, _)| dir)
+            .unwrap_or_default()
+    };
+    let (base, path) = if path.starts_with("./") || path.starts_with("../") {
+        (parent_dir().to_string(), path)
+    } else if let Some(aliased) = path.strip_prefix('@') {
+        let (alias, path) = aliased.split_once('/').unwrap_or((aliased, ""));
+        let alias = alias.to_lowercase();
+        let base = match alias.as_str() {
+            "self" => parent.to_string(),
+            _ => (require.alias(&alias))
+                .ok_or_else(|| Error::RuntimeError(format!("@{alias} is not a valid alias")))?,
+        };
+        (base, path)
+    } else {
+        return Err(Error::RuntimeError(format!(
+            "require path '{path}' must start with a valid prefix: ./, ../, or @"
+        )));
+    };
+
+    // Normalize path by resolving `.` and `..` components
+    let mut components = Vec::new();
+    for component in base.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." if matches!(components.last(), Some(&c) if c != "..") => {
+                components.pop();
+            }
+            c => components.push(c),
+        }
+    }
+    let name = components.join("/");
+    match base.starts_with('/') {
+        true => Ok(format!("/{name}")),
+        false => Ok(name),
+    }
+}
+
 // Luau vector datatype constructor
-unsafe extern "C" fn lua_vector(state: *mut ffi::lua_State) -> c_int {
+unsafe extern "C-unwind" fn lua_vector(state: *mut ffi::lua_State) -> c_int {
     let x = ffi::luaL_checknumber(state, 1) as c_float;
     let y = ffi::luaL_checknumber(state, 2) as c_float;
     let z = ffi::luaL_checknumber(state, 3) as c_float;
@@ -101,7 +101,9 @@ macro_rules! protect_lua {
     };
 
     ($state:expr, $nargs:expr, $nresults:expr, fn($state_inner:ident) $code:expr) => {{
-        unsafe extern "C" fn do_call($state_inner: *mut ffi::lua_State) -> ::std::os::raw::c_int {
+        unsafe extern "C-unwind" fn do_call(
+            $state_inner: *mut ffi::lua_State,
+        ) -> ::std::os::raw::c_int {
             $code;
             $nresults
         }
@@ -0,0 +1,183 @@
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::multi::Variadic;
+use crate::table::Table;
+use crate::types::{Integer, Number};
+use crate::value::Value;
+
+// Registry key of the module created by `Lua::math_compat`
+const MATH_COMPAT_KEY: &str = "__mlua_math_compat";
+
+impl Value {
+    /// Performs floor division (the `//` operator of Lua 5.3+) of two numbers.
+    ///
+    /// The result is an integer if both operands are integers, and a float otherwise (rounded
+    /// towards minus infinity). Integer division by zero is an error. Strings are not converted
+    /// to numbers.
+    ///
+    /// The behavior is the same on all Lua versions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Result, Value};
+    /// # fn main() -> Result<()> {
+    /// assert_eq!(Value::Integer(-7).integer_div(&Value::Integer(2))?, Value::Integer(-4));
+    /// assert_eq!(Value::Number(7.5).integer_div(&Value::Integer(2))?, Value::Number(3.0));
+    /// assert!(Value::Integer(1).integer_div(&Value::Integer(0)).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn integer_div(&self, other: &Value) -> Result<Value> {
+        let a = to_number(self, "arithmetic")?;
+        let b = to_number(other, "arithmetic")?;
+        match (a, b) {
+            (Num::Int(a), Num::Int(b)) => Ok(from_i64(int_div(a, b)?)),
+            (a, b) => Ok(Value::Number((a.to_float() / b.to_float()).floor())),
+        }
+    }
+}
+
+impl Lua {
+    /// Returns a module providing integer division and bitwise operations with the semantics of
+    /// Lua 5.4 on all Lua versions.
+    ///
+    /// The module contains the following functions:
+    /// - `idiv(a, b)`: floor division, same as `a // b`
+    /// - `band(...)`, `bor(...)`, `bxor(...)`: bitwise and, or and xor of all arguments
+    /// - `bnot(a)`: bitwise not, same as `~a`
+    /// - `lshift(a, n)`, `rshift(a, n)`: logical shifts, same as `a << n` and `a >> n`
+    ///
+    /// Bitwise operations work on 64-bit integers and raise an error for numbers without an
+    /// integer representation. Lua versions without the integer subtype store numbers as floats,
+    /// so integers larger than 2^53 lose precision there.
+    ///
+    /// The module is not installed as a global variable, it's up to the application to do so.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.globals().set("mathx", lua.math_compat()?)?;
+    ///
+    /// let (q, bits): (i64, i64) = lua.load("mathx.idiv(7, 2), mathx.band(6, 3)").eval()?;
+    /// assert_eq!((q, bits), (3, 2));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn math_compat(&self) -> Result<Table> {
+        if let Some(module) = self.named_registry_value::<Option<Table>>(MATH_COMPAT_KEY)? {
+            return Ok(module);
+        }
+
+        let module = self.create_table_with_capacity(0, 7)?;
+        let idiv = |_: &Lua, (a, b): (Value, Value)| a.integer_div(&b);
+        module.raw_set("idiv", self.create_function(idiv)?)?;
+        module.raw_set("band", self.create_bitwise_function(-1, |a, b| a & b)?)?;
+        module.raw_set("bor", self.create_bitwise_function(0, |a, b| a | b)?)?;
+        module.raw_set("bxor", self.create_bitwise_function(0, |a, b| a ^ b)?)?;
+        let bnot = |_: &Lua, a: Value| Ok(from_i64(!to_bit_integer(&a)?));
+        module.raw_set("bnot", self.create_function(bnot)?)?;
+        let lshift = |_: &Lua, (a, n): (Value, Value)| {
+            let n = to_bit_integer(&n)?;
+            Ok(from_i64(shift_left(to_bit_integer(&a)?, n)))
+        };
+        module.raw_set("lshift", self.create_function(lshift)?)?;
+        let rshift = |_: &Lua, (a, n): (Value, Value)| {
+            let n = to_bit_integer(&n)?;
+            Ok(from_i64(shift_left(to_bit_integer(&a)?, n.wrapping_neg())))
+        };
+        module.raw_set("rshift", self.create_function(rshift)?)?;
+
+        self.set_named_registry_value(MATH_COMPAT_KEY, module.clone())?;
+        Ok(module)
+    }
+
+    fn create_bitwise_function(&self, init: i64, op: fn(i64, i64) -> i64) -> Result<Function> {
+        self.create_function(move |_, args: Variadic<Value>| {
+            let mut result = init;
+            for arg in args.iter() {
+                result = op(result, to_bit_integer(arg)?);
+            }
+            Ok(from_i64(result))
+        })
+    }
+}
+
+// A Lua number with the integer subtype of Lua 5.3+
+#[derive(Clone, Copy)]
+enum Num {
+    Int(i64),
+    Float(Number),
+}
+
+impl Num {
+    fn to_float(self) -> Number {
+        match self {
+            Num::Int(i) => i as Number,
+            Num::Float(n) => n,
+        }
+    }
+}
+
+fn to_number(value: &Value, op: &str) -> Result<Num> {
+    match *value {
+        #[allow(clippy::useless_conversion)]
+        Value::Integer(i) => Ok(Num::Int(i64::from(i))),
+        Value::Number(n) => Ok(Num::Float(n)),
+        _ => Err(Error::RuntimeError(format!(
+            "attempt to perform {op} on a {} value",
+            value.type_name()
+        ))),
+    }
+}
+
+// Converts the value to an integer for a bitwise operation (floats must be integral)
+fn to_bit_integer(value: &Value) -> Result<i64> {
+    match to_number(value, "bitwise operation")? {
+        Num::Int(i) => Ok(i),
+        // `i64::MAX as f64` is 2^63, which is out of range
+        Num::Float(n) if n.floor() == n && n >= -(2f64.powi(63)) && n < 2f64.powi(63) => {
+            Ok(n as i64)
+        }
+        Num::Float(_) => Err(Error::RuntimeError(
+            "number has no integer representation".to_string(),
+        )),
+    }
+}
+
+// Converts the result to `Value::Integer` if it fits, or to `Value::Number` otherwise
+fn from_i64(i: i64) -> Value {
+    match num_traits::cast::<i64, Integer>(i) {
+        Some(i) => Value::Integer(i),
+        None => Value::Number(i as Number),
+    }
+}
+
+fn int_div(a: i64, b: i64) -> Result<i64> {
+    match b {
+        0 => Err(Error::RuntimeError("attempt to perform 'n//0'".to_string())),
+        // Avoid overflow of `i64::MIN / -1`
+        -1 => Ok(a.wrapping_neg()),
+        _ => {
+            let q = a / b;
+            // Round towards minus infinity
+            match a % b != 0 && (a ^ b) < 0 {
+                true => Ok(q - 1),
+                false => Ok(q),
+            }
+        }
+    }
+}
+
+fn shift_left(a: i64, n: i64) -> i64 {
+    const BITS: i64 = i64::BITS as i64;
+    match n {
+        n if n <= -BITS || n >= BITS => 0,
+        n if n < 0 => ((a as u64) >> (-n as u32)) as i64,
+        n => ((a as u64) << (n as u32)) as i64,
+    }
+}
@@ -0,0 +1,262 @@
+use std::os::raw::c_void;
+use std::ptr;
+use std::string::String as StdString;
+
+use crate::ffi;
+
+// Maximum number of events buffered between deliveries to the observer
+const MAX_PENDING_EVENTS: usize = 64;
+
+// Memory accounting of a Lua state using the Rust allocator
+#[derive(Default)]
+pub(crate) struct MemoryInfo {
+    pub(crate) used_memory: isize,
+    pub(crate) memory_limit: isize,
+    // Allow allocations above the limit (for operations raising unprotected errors)
+    ignore_limit: bool,
+    pub(crate) watch: Option<MemoryWatch>,
+}
+
+impl MemoryInfo {
+    // Returns memory info of the Lua state, or null if it does not use the Rust allocator
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    #[inline]
+    pub(crate) unsafe fn get(state: *mut ffi::lua_State) -> *mut MemoryInfo {
+        let mut mem_info = ptr::null_mut();
+        let allocf = ffi::lua_getallocf(state, &mut mem_info) as *const ();
+        if allocf != allocator as *const () {
+            return ptr::null_mut();
+        }
+        mem_info as *mut MemoryInfo
+    }
+
+    #[cfg(feature = "luau")]
+    #[inline]
+    pub(crate) unsafe fn get(state: *mut ffi::lua_State) -> *mut MemoryInfo {
+        crate::lua::memory_info(state)
+    }
+
+    // Calls `f` ignoring the memory limit.
+    //
+    // Lua 5.1, LuaJIT and Luau raise memory errors in some API functions which do not raise errors
+    // in newer Lua versions (eg. `lua_checkstack` or `lua_pushcfunction`). Such errors cannot be
+    // caught, so the limit is relaxed for them.
+    #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+    #[inline]
+    pub(crate) unsafe fn relax_limit_with<R>(
+        state: *mut ffi::lua_State,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        let mem_info = MemoryInfo::get(state);
+        if mem_info.is_null() || (*mem_info).memory_limit == 0 {
+            return f();
+        }
+        let ignore_limit = (*mem_info).ignore_limit;
+        (*mem_info).ignore_limit = true;
+        let r = f();
+        (*mem_info).ignore_limit = ignore_limit;
+        r
+    }
+
+    // Newer Lua versions do not raise memory errors in such API functions
+    #[cfg(not(any(feature = "lua51", feature = "luajit", feature = "luau")))]
+    #[inline(always)]
+    pub(crate) unsafe fn relax_limit_with<R>(
+        _state: *mut ffi::lua_State,
+        f: impl FnOnce() -> R,
+    ) -> R {
+        f()
+    }
+}
+
+pub(crate) unsafe extern "C-unwind" fn allocator(
+    extra_data: *mut c_void,
+    ptr: *mut c_void,
+    osize: usize,
+    nsize: usize,
+) -> *mut c_void {
+    use std::alloc::{self, Layout};
+
+    let mem_info = &mut *(extra_data as *mut MemoryInfo);
+
+    if nsize == 0 {
+        // Free memory
+        if !ptr.is_null() {
+            let layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
+            alloc::dealloc(ptr as *mut u8, layout);
+            mem_info.used_memory -= osize as isize;
+            if let Some(watch) = &mut mem_info.watch {
+                watch.on_shrink(mem_info.used_memory as usize);
+            }
+        }
+        return ptr::null_mut();
+    }
+
+    // Do not allocate more than isize::MAX
+    if nsize > isize::MAX as usize {
+        return ptr::null_mut();
+    }
+
+    // Are we fit to the memory limits?
+    let mut mem_diff = nsize as isize;
+    if !ptr.is_null() {
+        mem_diff -= osize as isize;
+    }
+    let new_used_memory = mem_info.used_memory + mem_diff;
+    // Shrinking a block must never fail (Lua relies on it)
+    let limit = mem_info.memory_limit;
+    if mem_diff > 0 && limit > 0 && new_used_memory > limit && !mem_info.ignore_limit {
+        return ptr::null_mut();
+    }
+    mem_info.used_memory += mem_diff;
+    if let Some(watch) = &mut mem_info.watch {
+        match mem_diff > 0 {
+            true => watch.on_grow(nsize, new_used_memory as usize),
+            false => watch.on_shrink(new_used_memory as usize),
+        }
+    }
+
+    if ptr.is_null() {
+        // Allocate new memory
+        let new_layout = match Layout::from_size_align(nsize, ffi::SYS_MIN_ALIGN) {
+            Ok(layout) => layout,
+            Err(_) => return ptr::null_mut(),
+        };
+        let new_ptr = alloc::alloc(new_layout) as *mut c_void;
+        if new_ptr.is_null() {
+            alloc::handle_alloc_error(new_layout);
+        }
+        return new_ptr;
+    }
+
+    // Reallocate memory
+    let old_layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
+    let new_ptr = alloc::realloc(ptr as *mut u8, old_layout, nsize) as *mut c_void;
+    if new_ptr.is_null() {
+        alloc::handle_alloc_error(old_layout);
+    }
+    new_ptr
+}
+
+/// Conditions triggering the memory observer set by [`Lua::set_memory_observer`].
+///
+/// [`Lua::set_memory_observer`]: crate::Lua::set_memory_observer
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTriggers {
+    /// Size (in bytes) of a single allocation, starting from which the allocation is reported.
+    pub large_allocation: Option<usize>,
+    /// Amounts of used memory (in bytes) reported when exceeded.
+    ///
+    /// Each threshold is reported once, and again only after memory usage drops below it.
+    pub thresholds: Vec<usize>,
+}
+
+impl MemoryTriggers {
+    /// Returns a new instance of `MemoryTriggers` with [`large_allocation`] trigger set.
+    ///
+    /// [`large_allocation`]: #structfield.large_allocation
+    pub fn large_allocation(size: usize) -> Self {
+        MemoryTriggers {
+            large_allocation: Some(size),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a new instance of `MemoryTriggers` with [`thresholds`] trigger set.
+    ///
+    /// [`thresholds`]: #structfield.thresholds
+    pub fn thresholds(thresholds: impl IntoIterator<Item = usize>) -> Self {
+        MemoryTriggers {
+            thresholds: thresholds.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Reason of a [`MemoryEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryEventKind {
+    /// A single allocation of `size` bytes, not less than [`MemoryTriggers::large_allocation`].
+    LargeAllocation { size: usize },
+    /// Memory usage exceeded the `threshold` (one of [`MemoryTriggers::thresholds`]).
+    ThresholdExceeded { threshold: usize },
+}
+
+/// An event reported to the memory observer set by [`Lua::set_memory_observer`].
+///
+/// [`Lua::set_memory_observer`]: crate::Lua::set_memory_observer
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct MemoryEvent {
+    /// Reason of the event.
+    pub kind: MemoryEventKind,
+    /// Amount of memory (in bytes) used by the Lua state right after the allocation.
+    pub used_memory: usize,
+    /// Stack traceback of the Lua code running when the event was reported.
+    pub traceback: StdString,
+}
+
+// Allocation tracking state, updated by the allocator
+pub(crate) struct MemoryWatch {
+    large_allocation: usize,
+    // Thresholds and whether they are armed (memory usage is below them)
+    thresholds: Vec<(usize, bool)>,
+    pending: Vec<(MemoryEventKind, usize)>,
+}
+
+impl MemoryWatch {
+    pub(crate) fn new(triggers: MemoryTriggers, used_memory: usize) -> Self {
+        let thresholds = (triggers.thresholds.into_iter())
+            .map(|threshold| (threshold, used_memory <= threshold))
+            .collect();
+        MemoryWatch {
+            large_allocation: triggers.large_allocation.unwrap_or(usize::MAX),
+            thresholds,
+            pending: Vec::with_capacity(MAX_PENDING_EVENTS),
+        }
+    }
+
+    // Called after a successful (re)allocation of `size` bytes which grew memory usage
+    #[inline]
+    pub(crate) fn on_grow(&mut self, size: usize, used_memory: usize) {
+        if size >= self.large_allocation {
+            self.push(MemoryEventKind::LargeAllocation { size }, used_memory);
+        }
+        for i in 0..self.thresholds.len() {
+            let (threshold, armed) = self.thresholds[i];
+            if armed && used_memory > threshold {
+                self.thresholds[i].1 = false;
+                self.push(
+                    MemoryEventKind::ThresholdExceeded { threshold },
+                    used_memory,
+                );
+            }
+        }
+    }
+
+    // Called after memory usage has dropped
+    #[inline]
+    pub(crate) fn on_shrink(&mut self, used_memory: usize) {
+        for (threshold, armed) in &mut self.thresholds {
+            if !*armed && used_memory <= *threshold {
+                *armed = true;
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub(crate) fn take_pending(&mut self) -> Vec<(MemoryEventKind, usize)> {
+        let pending = Vec::with_capacity(MAX_PENDING_EVENTS);
+        std::mem::replace(&mut self.pending, pending)
+    }
+
+    fn push(&mut self, kind: MemoryEventKind, used_memory: usize) {
+        if self.pending.len() < MAX_PENDING_EVENTS {
+            self.pending.push((kind, used_memory));
+        }
+    }
+}
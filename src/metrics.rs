@@ -0,0 +1,164 @@
+use std::string::String as StdString;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use crate::error::Result;
+use crate::lua::Lua;
+use crate::types::Callback;
+
+/// Kind of code measured by [`CallbackMetrics`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MetricsKind {
+    /// A Rust callback (function or userdata method).
+    Callback,
+    /// A loaded Lua chunk.
+    Chunk,
+}
+
+/// Call count and cumulative duration of a Rust callback or Lua chunk.
+///
+/// Returned by [`Lua::metrics`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CallbackMetrics {
+    /// Name of the callback or chunk.
+    ///
+    /// Userdata methods are named as `Type.method`, other functions are named after the
+    /// location in Rust code where they were created. Chunks use the chunk name.
+    pub name: StdString,
+    /// Kind of the measured code.
+    pub kind: MetricsKind,
+    /// Number of completed calls.
+    pub calls: u64,
+    /// Total duration of all calls, including time spent in nested calls.
+    pub total_time: Duration,
+}
+
+// Collected metrics, shared between the Lua instance and instrumented callbacks
+#[derive(Default)]
+pub(crate) struct MetricsRegistry {
+    entries: Mutex<FxHashMap<(MetricsKind, StdString), Arc<MetricsEntry>>>,
+}
+
+#[derive(Default)]
+struct MetricsEntry {
+    calls: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl MetricsRegistry {
+    fn entry(&self, kind: MetricsKind, name: &str) -> Arc<MetricsEntry> {
+        let mut entries = mlua_expect!(self.entries.lock(), "metrics registry poisoned");
+        entries.entry((kind, name.to_string())).or_default().clone()
+    }
+}
+
+impl MetricsEntry {
+    fn record(&self, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+impl Lua {
+    /// Enables or disables collection of callback and chunk metrics.
+    ///
+    /// When enabled, every Rust function (see [`Lua::create_function`]) and userdata method
+    /// records its call count and cumulative duration, as well as every executed chunk. Only
+    /// callbacks created after enabling metrics are measured, so metrics should be enabled
+    /// right after creating the Lua instance.
+    ///
+    /// Disabling metrics discards all collected data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.enable_metrics(true);
+    ///
+    /// let sleep = lua.create_function(|_, ms: u64| {
+    ///     std::thread::sleep(std::time::Duration::from_millis(ms));
+    ///     Ok(())
+    /// })?;
+    /// lua.globals().set("sleep", sleep)?;
+    /// lua.load("sleep(1); sleep(2)").set_name("main").exec()?;
+    ///
+    /// for m in lua.metrics() {
+    ///     println!("{:?} {}: {} calls, {:?}", m.kind, m.name, m.calls, m.total_time);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enable_metrics(&self, enabled: bool) {
+        let registry = enabled.then(|| self.metrics_registry().unwrap_or_default());
+        self.set_metrics_registry(registry);
+    }
+
+    /// Returns metrics collected since enabling them, sorted by total duration (descending).
+    ///
+    /// Returns an empty vector if metrics are disabled. See [`Lua::enable_metrics`].
+    pub fn metrics(&self) -> Vec<CallbackMetrics> {
+        let registry = match self.metrics_registry() {
+            Some(registry) => registry,
+            None => return Vec::new(),
+        };
+        let entries = mlua_expect!(registry.entries.lock(), "metrics registry poisoned");
+        let mut metrics = (entries.iter())
+            .map(|((kind, name), entry)| CallbackMetrics {
+                name: name.clone(),
+                kind: *kind,
+                calls: entry.calls.load(Ordering::Relaxed),
+                total_time: Duration::from_nanos(entry.nanos.load(Ordering::Relaxed)),
+            })
+            .collect::<Vec<_>>();
+        metrics.sort_by(|a, b| (b.total_time, &a.name).cmp(&(a.total_time, &b.name)));
+        metrics
+    }
+
+    /// Resets collected metrics to zero, keeping them enabled.
+    pub fn reset_metrics(&self) {
+        if let Some(registry) = self.metrics_registry() {
+            let entries = mlua_expect!(registry.entries.lock(), "metrics registry poisoned");
+            for entry in entries.values() {
+                entry.calls.store(0, Ordering::Relaxed);
+                entry.nanos.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Wraps the callback to record its metrics if they are enabled
+    pub(crate) fn instrument_callback(
+        &self,
+        name: impl FnOnce() -> StdString,
+        func: Callback<'static>,
+    ) -> Callback<'static> {
+        let entry = match self.metrics_registry() {
+            Some(registry) => registry.entry(MetricsKind::Callback, &name()),
+            None => return func,
+        };
+        Box::new(move |lua, args| {
+            let start = Instant::now();
+            let result = func(lua, args);
+            entry.record(start.elapsed());
+            result
+        })
+    }
+
+    // Runs the chunk function `f`, recording its metrics if they are enabled
+    pub(crate) fn measure_chunk<R>(&self, name: &str, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        let entry = match self.metrics_registry() {
+            Some(registry) => registry.entry(MetricsKind::Chunk, name),
+            None => return f(),
+        };
+        let start = Instant::now();
+        let result = f();
+        entry.record(start.elapsed());
+        result
+    }
+}
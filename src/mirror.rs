@@ -0,0 +1,174 @@
+use std::string::String as StdString;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::serde::LuaSerdeExt;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::userdata::{MetaMethod, UserData, UserDataMethods};
+use crate::value::Value;
+
+/// Exposes a Rust struct to Lua as a live table.
+///
+/// When passed to Lua, the mirror behaves like a table with the fields of the struct (as
+/// serialized by serde). Reading a field returns its current value, writing a field
+/// deserializes the updated struct and replaces the value, so invalid writes (eg. of a wrong
+/// type or to an unknown field) raise an error and leave the struct unchanged.
+///
+/// Nested values are read as copies: writes to them (eg. `mirror.pos.x = 1`) are not reflected
+/// in the struct, assign the whole field instead.
+///
+/// The mirror is shared, cloning it returns a new handle to the same struct. Use
+/// [`Mirror::subscribe`] to get notified about fields changed by Lua.
+///
+/// Requires `feature = "serialize"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Mirror, Result};
+/// # use serde::{Deserialize, Serialize};
+/// # fn main() -> Result<()> {
+/// #[derive(Serialize, Deserialize)]
+/// struct Settings {
+///     volume: u8,
+///     fullscreen: bool,
+/// }
+///
+/// let lua = Lua::new();
+/// let settings = Mirror::new(Settings { volume: 50, fullscreen: false });
+/// let changes = settings.subscribe();
+/// lua.globals().set("settings", settings.clone())?;
+///
+/// lua.load("settings.volume = settings.volume + 10").exec()?;
+/// assert_eq!(settings.lock().volume, 60);
+/// assert_eq!(changes.try_recv().unwrap(), "volume");
+///
+/// // Writes are validated
+/// assert!(lua.load("settings.fullscreen = 'yes'").exec().is_err());
+/// assert!(lua.load("settings.unknown = 1").exec().is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+pub struct Mirror<T> {
+    inner: Arc<MirrorInner<T>>,
+}
+
+struct MirrorInner<T> {
+    value: Mutex<T>,
+    subscribers: Mutex<Vec<Sender<StdString>>>,
+}
+
+impl<T> Mirror<T> {
+    /// Creates a new mirror of `value`.
+    pub fn new(value: T) -> Self {
+        Mirror {
+            inner: Arc::new(MirrorInner {
+                value: Mutex::new(value),
+                subscribers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Locks the mirrored value for reading or writing from Rust.
+    ///
+    /// Lua code accessing the mirror while the lock is held would block, so the guard must not
+    /// be held while calling Lua.
+    pub fn lock(&self) -> MutexGuard<T> {
+        // The value is never left in an inconsistent state, so poisoning is ignored
+        self.inner
+            .value
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns a copy of the mirrored value.
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.lock().clone()
+    }
+
+    /// Replaces the mirrored value.
+    pub fn set(&self, value: T) {
+        *self.lock() = value;
+    }
+
+    /// Returns a receiver of names of the fields changed by Lua.
+    ///
+    /// Every subscriber receives a name per write, in order of writes. Changes made from Rust
+    /// are not notified.
+    pub fn subscribe(&self) -> Receiver<StdString> {
+        let (tx, rx) = mpsc::channel();
+        let mut subscribers = self
+            .inner
+            .subscribers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        subscribers.push(tx);
+        rx
+    }
+
+    fn notify(&self, field: &str) {
+        let mut subscribers = self
+            .inner
+            .subscribers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        // Dropped receivers are unsubscribed
+        subscribers.retain(|tx| tx.send(field.to_string()).is_ok());
+    }
+}
+
+impl<T> Clone for Mirror<T> {
+    fn clone(&self) -> Self {
+        Mirror {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+// Serializes the mirrored value to a table
+fn to_table<T: Serialize>(lua: &Lua, value: &T) -> Result<Table> {
+    match lua.to_value(value)? {
+        Value::Table(table) => Ok(table),
+        value => Err(Error::RuntimeError(format!(
+            "mirrored value must be serialized to a table, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+impl<T> UserData for Mirror<T>
+where
+    T: Serialize + DeserializeOwned + MaybeSend + 'static,
+{
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::Index, |lua, this, key: Value| {
+            to_table(&lua, &*this.lock())?.raw_get::<_, Value>(key)
+        });
+
+        methods.add_meta_method(
+            MetaMethod::NewIndex,
+            |lua, this, (key, value): (StdString, Value)| {
+                {
+                    let mut guard = this.lock();
+                    let table = to_table(&lua, &*guard)?;
+                    if !table.raw_contains_key(key.as_str())? {
+                        return Err(Error::RuntimeError(format!("no field '{key}'")));
+                    }
+                    table.raw_set(key.as_str(), value)?;
+                    *guard = lua.from_value(Value::Table(table))?;
+                }
+                this.notify(&key);
+                Ok(())
+            },
+        );
+    }
+}
@@ -1,8 +1,10 @@
+use std::fmt;
 use std::iter::FromIterator;
 use std::ops::{Deref, DerefMut};
 use std::result::Result as StdResult;
+use std::sync::Arc;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::lua::Lua;
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil};
 
@@ -158,6 +160,194 @@ impl<'lua, T: FromLua> FromLuaMulti for Variadic<T> {
         MultiValue::return_to_pool(values, lua);
         res
     }
+
+    #[inline]
+    fn from_lua_multi_args(
+        mut values: MultiValue,
+        i: usize,
+        to: Option<&str>,
+        lua: &Lua,
+    ) -> Result<Self> {
+        let res = (values.drain_all().enumerate())
+            .map(|(j, e)| T::from_lua_arg(e, i + j, to, lua))
+            .collect::<Result<Vec<T>>>()
+            .map(Variadic);
+        MultiValue::return_to_pool(values, lua);
+        res
+    }
+}
+
+/// Wraps a variable number of `T`s, requiring at least `N` values.
+///
+/// Similar to [`Variadic`], but the conversion fails if less than `N` values are given. When
+/// used as the last argument of a Rust callback, the error points to the position of the first
+/// missing (or invalid) argument.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{AtLeast, Lua, Result};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let max = lua.create_function(|_, (name, vals): (String, AtLeast<1, i64>)| {
+///     Ok(format!("{name}: {}", vals.iter().max().unwrap()))
+/// })?;
+/// lua.globals().set("max", max)?;
+/// assert_eq!(lua.load("max('x', 3, 7, 5)").eval::<String>()?, "x: 7");
+///
+/// let err = lua.load("max('x')").exec().unwrap_err();
+/// assert!(err.to_string().contains("bad argument #2"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AtLeast<const N: usize, T>(Vec<T>);
+
+impl<const N: usize, T> AtLeast<N, T> {
+    /// Returns the wrapped values.
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<const N: usize, T> IntoIterator for AtLeast<N, T> {
+    type Item = T;
+    type IntoIter = <Vec<T> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<const N: usize, T> Deref for AtLeast<N, T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize, T: IntoLua> IntoLuaMulti for AtLeast<N, T> {
+    #[inline]
+    fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
+        Variadic(self.0).into_lua_multi(lua)
+    }
+}
+
+impl<const N: usize, T: FromLua> FromLuaMulti for AtLeast<N, T> {
+    #[inline]
+    fn from_lua_multi(values: MultiValue, lua: &Lua) -> Result<Self> {
+        let len = values.len();
+        if len < N {
+            MultiValue::return_to_pool(values, lua);
+            return Err(Error::FromLuaConversionError {
+                from: "nil",
+                to: std::any::type_name::<T>(),
+                message: Some(format!("expected at least {N} values, got {len}")),
+            });
+        }
+        Variadic::from_lua_multi(values, lua).map(|v| AtLeast(v.0))
+    }
+
+    #[inline]
+    fn from_lua_multi_args(
+        values: MultiValue,
+        i: usize,
+        to: Option<&str>,
+        lua: &Lua,
+    ) -> Result<Self> {
+        let len = values.len();
+        if len < N {
+            MultiValue::return_to_pool(values, lua);
+            return Err(Error::BadArgument {
+                to: to.map(|s| s.to_string()),
+                pos: i + len,
+                name: None,
+                cause: Arc::new(Error::external(format!(
+                    "value expected (at least {N} values, got {len})"
+                ))),
+            });
+        }
+        Variadic::from_lua_multi_args(values, i, to, lua).map(|v| AtLeast(v.0))
+    }
+}
+
+/// A sequence of values of different types, built without a tuple of an exact arity.
+///
+/// Each appended item is converted using its [`IntoLuaMulti`] implementation and the results are
+/// concatenated, so `Option::None` still takes one position (`nil`) and a [`Variadic`] expands
+/// to all of its values. This is useful for callbacks returning several unrelated values or a
+/// number of values that is only known at runtime.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Multi, Result, Variadic};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let stats = lua.create_function(|_, verbose: bool| {
+///     let mut ret = Multi::new().with("ok").with(None::<i32>);
+///     if verbose {
+///         ret.push(Variadic::from_iter([1, 2, 3]));
+///     }
+///     Ok(ret)
+/// })?;
+/// lua.globals().set("stats", stats)?;
+/// assert_eq!(lua.load("select('#', stats(true))").eval::<i32>()?, 5);
+/// assert_eq!(lua.load("select('#', stats(false))").eval::<i32>()?, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Multi<'a>(Vec<MultiItem<'a>>);
+
+// Deferred conversion of an item appended to `Multi`
+type MultiItem<'a> = Box<dyn FnOnce(&Lua) -> Result<MultiValue> + 'a>;
+
+impl<'a> Multi<'a> {
+    /// Creates an empty `Multi` containing no values.
+    pub fn new() -> Self {
+        Multi(Vec::new())
+    }
+
+    /// Appends value(s) to the end of the sequence and returns `self`.
+    pub fn with(mut self, value: impl IntoLuaMulti + 'a) -> Self {
+        self.push(value);
+        self
+    }
+
+    /// Appends value(s) to the end of the sequence.
+    pub fn push(&mut self, value: impl IntoLuaMulti + 'a) {
+        self.0.push(Box::new(move |lua| value.into_lua_multi(lua)));
+    }
+
+    /// Returns the number of appended items.
+    ///
+    /// This is not the number of Lua values, as a single item can expand to any number of them.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if no items were appended.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Multi<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Multi").field("len", &self.0.len()).finish()
+    }
+}
+
+impl IntoLuaMulti for Multi<'_> {
+    fn into_lua_multi(self, lua: &Lua) -> Result<MultiValue> {
+        let mut values = Vec::new();
+        for item in self.0 {
+            values.extend(item(lua)?);
+        }
+        Ok(MultiValue::from_vec(values))
+    }
 }
 
 macro_rules! impl_tuple {
@@ -0,0 +1,155 @@
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::value::{FromLuaMulti, IntoLuaMulti, Nil, Value};
+
+// Registry key of the table with the original values of overridden entries
+const ORIGINALS_KEY: &str = "__mlua_originals";
+
+impl Lua {
+    /// Replaces the global function `name` with a Rust function.
+    ///
+    /// The original value is kept and remains accessible from Rust using [`Lua::original`],
+    /// for example to call the original function from the override. Overriding the same
+    /// function again keeps the first original value. Use [`Lua::restore_original`] to undo
+    /// the override.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.override_global_fn("tostring", |lua, value: Value| {
+    ///     match value {
+    ///         Value::Boolean(b) => Ok(if b { "yes" } else { "no" }.to_string()),
+    ///         value => lua.original("tostring")?.unwrap().call(value),
+    ///     }
+    /// })?;
+    /// assert_eq!(lua.load("tostring(true) .. tostring(1)").eval::<String>()?, "yes1");
+    ///
+    /// lua.restore_original("tostring")?;
+    /// assert_eq!(lua.load("tostring(true)").eval::<String>()?, "true");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn override_global_fn<A, R, F>(&self, name: &str, func: F) -> Result<()>
+    where
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+        F: 'static + MaybeSend + Fn(&Lua, A) -> Result<R>,
+    {
+        let func = self.create_function(func)?;
+        self.override_entry(name, &self.globals_storage()?, name, func)
+    }
+
+    /// Replaces the function `name` in the global table `table` (eg. `os.exit`) with a Rust
+    /// function.
+    ///
+    /// The original value is accessible using [`Lua::original`] with the `table.name` path.
+    /// See [`Lua::override_global_fn`] for details.
+    ///
+    /// Returns an error if the global `table` is not a table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Error, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.override_table_fn("os", "exit", |_, _: Option<i32>| -> Result<()> {
+    ///     Err(Error::RuntimeError("exit is not allowed".into()))
+    /// })?;
+    /// assert!(lua.load("os.exit(1)").exec().is_err());
+    /// assert!(lua.original("os.exit")?.is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn override_table_fn<A, R, F>(&self, table: &str, name: &str, func: F) -> Result<()>
+    where
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+        F: 'static + MaybeSend + Fn(&Lua, A) -> Result<R>,
+    {
+        let target = match self.globals_storage()?.raw_get::<_, Value>(table)? {
+            Value::Table(target) => target,
+            value => {
+                return Err(Error::RuntimeError(format!(
+                    "global '{table}' is not a table (a {} value)",
+                    value.type_name()
+                )))
+            }
+        };
+        let func = self.create_function(func)?;
+        self.override_entry(&format!("{table}.{name}"), &target, name, func)
+    }
+
+    /// Returns the original function overridden using [`Lua::override_global_fn`] or
+    /// [`Lua::override_table_fn`].
+    ///
+    /// `path` is the global name (eg. `print`) or the table and function names separated by
+    /// a dot (eg. `os.exit`). Returns `None` if the entry is not overridden or its original
+    /// value is not a function.
+    pub fn original(&self, path: &str) -> Result<Option<Function>> {
+        let originals = match self.named_registry_value::<Option<Table>>(ORIGINALS_KEY)? {
+            Some(originals) => originals,
+            None => return Ok(None),
+        };
+        match originals.raw_get::<_, Value>(path)? {
+            Value::Function(func) => Ok(Some(func)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Restores the original value of an overridden entry.
+    ///
+    /// `path` has the same format as in [`Lua::original`]. Returns `false` if the entry is not
+    /// overridden.
+    pub fn restore_original(&self, path: &str) -> Result<bool> {
+        let originals = match self.named_registry_value::<Option<Table>>(ORIGINALS_KEY)? {
+            Some(originals) => originals,
+            None => return Ok(false),
+        };
+        let original = match originals.raw_get::<_, Value>(path)? {
+            Nil => return Ok(false),
+            // Entry did not exist before the override
+            Value::Boolean(false) => Nil,
+            original => original,
+        };
+        let target = match path.split_once('.') {
+            Some((table, name)) => match self.globals_storage()?.raw_get::<_, Value>(table)? {
+                Value::Table(target) => Some((target, name)),
+                _ => None,
+            },
+            None => Some((self.globals_storage()?, path)),
+        };
+        if let Some((target, name)) = target {
+            target.raw_set(name, original)?;
+        }
+        originals.raw_set(path, Nil)?;
+        Ok(true)
+    }
+
+    fn override_entry(&self, path: &str, target: &Table, name: &str, func: Function) -> Result<()> {
+        let originals = match self.named_registry_value::<Option<Table>>(ORIGINALS_KEY)? {
+            Some(originals) => originals,
+            None => {
+                let originals = self.create_table()?;
+                self.set_named_registry_value(ORIGINALS_KEY, originals.clone())?;
+                originals
+            }
+        };
+        if !originals.contains_key(path)? {
+            let original = match target.raw_get::<_, Value>(name)? {
+                Nil => Value::Boolean(false),
+                original => original,
+            };
+            originals.raw_set(path, original)?;
+        }
+        target.raw_set(name, func)
+    }
+}
@@ -0,0 +1,594 @@
+use std::io::{Read, Write};
+use std::os::raw::c_void;
+use std::ptr;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::error::{Error, Result};
+use crate::ffi;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::thread::Thread;
+use crate::types::{Integer, LightUserData, Number};
+use crate::util::{check_stack, StackGuard};
+use crate::value::{Nil, Value};
+
+#[cfg(not(feature = "luau"))]
+use {crate::chunk::ChunkMode, std::os::raw::c_int};
+
+const MAGIC: &[u8] = b"\x1bMLP";
+const VERSION: u8 = 1;
+
+// Maximum nesting level of persisted objects
+const MAX_DEPTH: usize = 200;
+
+// Value tags
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_NUMBER: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_NULL: u8 = 6;
+const TAG_REF: u8 = 7;
+const TAG_PERMANENT: u8 = 8;
+const TAG_GLOBALS: u8 = 9;
+const TAG_TABLE: u8 = 10;
+#[cfg(not(feature = "luau"))]
+const TAG_FUNCTION: u8 = 11;
+const TAG_THREAD: u8 = 12;
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+const TAG_SHARED_UPVALUE: u8 = 13;
+#[cfg(feature = "luau")]
+const TAG_VECTOR: u8 = 14;
+
+impl Lua {
+    /// Persists a table and everything reachable from it.
+    ///
+    /// Shorthand for [`Lua::persist_with`] with an empty permanents table.
+    ///
+    /// Requires `feature = "persist"`
+    #[cfg_attr(docsrs, doc(cfg(feature = "persist")))]
+    pub fn persist<W: Write + ?Sized>(&self, root: &Table, writer: &mut W) -> Result<()> {
+        self.persist_with(root, &self.create_table()?, writer)
+    }
+
+    /// Persists a table and everything reachable from it, using the `permanents` table to
+    /// refer to values that cannot be persisted.
+    ///
+    /// The object graph is written in a binary format that can be loaded back using
+    /// [`Lua::unpersist_with`]. Shared references and cycles are preserved.
+    ///
+    /// The following values are supported:
+    /// - `nil`, booleans, numbers and strings
+    /// - tables with their metatables
+    /// - Lua functions (closures) with their upvalues (not supported by Luau).
+    ///   Upvalues shared between closures stay shared in Lua 5.2+
+    /// - coroutines that have not been started yet
+    ///
+    /// Any value found in `permanents` (a table mapping names to values, usually host functions
+    /// and userdata) is written as a reference to its name. The globals table is always written
+    /// as a reference. Other values (C functions, userdata, started coroutines) cause a
+    /// [`FromLuaConversionError`].
+    ///
+    /// Requires `feature = "persist"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let permanents = lua.create_table()?;
+    /// permanents.set("greet", lua.create_function(|_, name: String| Ok(format!("Hi, {name}")))?)?;
+    /// lua.globals().set("greet", permanents.get::<_, mlua::Function>("greet")?)?;
+    ///
+    /// let save: Table = lua.load(r#"
+    ///     local player = {name = "Alice", level = 3}
+    ///     player.self = player
+    ///     return {player = player, hello = greet}
+    /// "#).eval()?;
+    ///
+    /// let mut data = Vec::new();
+    /// lua.persist_with(&save, &permanents, &mut data)?;
+    ///
+    /// let restored = lua.unpersist_with(&permanents, &mut data.as_slice())?;
+    /// let player: Table = restored.get("player")?;
+    /// assert_eq!(player.get::<_, String>("name")?, "Alice");
+    /// assert_eq!(player.get::<_, Table>("self")?, player);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`FromLuaConversionError`]: crate::Error::FromLuaConversionError
+    #[cfg_attr(docsrs, doc(cfg(feature = "persist")))]
+    pub fn persist_with<W: Write + ?Sized>(
+        &self,
+        root: &Table,
+        permanents: &Table,
+        writer: &mut W,
+    ) -> Result<()> {
+        let mut persister = Persister {
+            lua: self,
+            buf: MAGIC.to_vec(),
+            permanents: FxHashMap::default(),
+            globals: self.globals().to_pointer(),
+            objects: FxHashMap::default(),
+            threads: FxHashSet::default(),
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            upvalues: FxHashMap::default(),
+            keep: Vec::new(),
+        };
+        persister.buf.push(VERSION);
+        for pair in permanents.clone().pairs::<Value, Value>() {
+            let (name, value) = pair?;
+            let ptr = value.to_pointer();
+            if !ptr.is_null() {
+                persister.permanents.insert(ptr, name);
+            }
+        }
+        persister.write_value(&Value::Table(root.clone()), 0)?;
+        writer.write_all(&persister.buf)?;
+        Ok(())
+    }
+
+    /// Loads a table persisted by [`Lua::persist`].
+    ///
+    /// Requires `feature = "persist"`
+    #[cfg_attr(docsrs, doc(cfg(feature = "persist")))]
+    pub fn unpersist<R: Read + ?Sized>(&self, reader: &mut R) -> Result<Table> {
+        self.unpersist_with(&self.create_table()?, reader)
+    }
+
+    /// Loads a table persisted by [`Lua::persist_with`].
+    ///
+    /// Values referenced by name are looked up in the `permanents` table, which should
+    /// have the same names as the one used to persist the data.
+    ///
+    /// Be aware, Lua does not check the consistency of the code inside binary chunks,
+    /// so only data from trusted sources should be loaded.
+    ///
+    /// Requires `feature = "persist"`
+    #[cfg_attr(docsrs, doc(cfg(feature = "persist")))]
+    pub fn unpersist_with<R: Read + ?Sized>(
+        &self,
+        permanents: &Table,
+        reader: &mut R,
+    ) -> Result<Table> {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+
+        let mut unpersister = Unpersister {
+            lua: self,
+            input: &input,
+            pos: 0,
+            permanents,
+            objects: Vec::new(),
+        };
+        if unpersister.read_bytes(MAGIC.len())? != MAGIC {
+            return Err(unpersister.error("invalid header"));
+        }
+        if unpersister.read_u8()? != VERSION {
+            return Err(unpersister.error("unsupported version"));
+        }
+        let root = match unpersister.read_value(0)? {
+            Value::Table(table) => table,
+            _ => return Err(unpersister.error("root value is not a table")),
+        };
+        if unpersister.pos != input.len() {
+            return Err(unpersister.error("trailing bytes after value"));
+        }
+        Ok(root)
+    }
+}
+
+struct Persister<'a> {
+    lua: &'a Lua,
+    buf: Vec<u8>,
+    // Pointers to permanent values mapped to their names
+    permanents: FxHashMap<*const c_void, Value>,
+    globals: *const c_void,
+    // Already written objects mapped to their ids
+    objects: FxHashMap<*const c_void, u64>,
+    // Coroutines with their functions being written
+    threads: FxHashSet<*const c_void>,
+    // Upvalue ids mapped to the function id and upvalue index that first referenced them
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    upvalues: FxHashMap<*mut c_void, (u64, c_int)>,
+    // Keeps written objects alive to make sure their pointers are not reused
+    keep: Vec<Value>,
+}
+
+impl<'a> Persister<'a> {
+    fn write_value(&mut self, value: &Value, depth: usize) -> Result<()> {
+        match value {
+            Value::Nil => self.buf.push(TAG_NIL),
+            Value::Boolean(false) => self.buf.push(TAG_FALSE),
+            Value::Boolean(true) => self.buf.push(TAG_TRUE),
+            #[allow(clippy::useless_conversion)]
+            Value::Integer(i) => {
+                self.buf.push(TAG_INTEGER);
+                self.buf.extend_from_slice(&i64::from(*i).to_le_bytes());
+            }
+            Value::Number(n) => {
+                self.buf.push(TAG_NUMBER);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            #[cfg(feature = "luau")]
+            Value::Vector(x, y, z) => {
+                self.buf.push(TAG_VECTOR);
+                for v in [x, y, z] {
+                    self.buf.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Value::String(s) => {
+                self.buf.push(TAG_STRING);
+                self.write_bytes(s.as_bytes());
+            }
+            // `null` marker used by serde integration
+            Value::LightUserData(ud) if ud.0.is_null() => self.buf.push(TAG_NULL),
+            _ => self.write_object(value, depth)?,
+        }
+        Ok(())
+    }
+
+    fn write_object(&mut self, value: &Value, depth: usize) -> Result<()> {
+        let ptr = value.to_pointer();
+        if let Some(name) = self.permanents.get(&ptr).cloned() {
+            self.buf.push(TAG_PERMANENT);
+            return self.write_value(&name, depth);
+        }
+        if let Some(&id) = self.objects.get(&ptr) {
+            self.buf.push(TAG_REF);
+            self.write_u64(id);
+            return Ok(());
+        }
+        if ptr == self.globals {
+            self.buf.push(TAG_GLOBALS);
+            return Ok(());
+        }
+        if depth >= MAX_DEPTH {
+            return Err(self.error(value, "nesting is too deep"));
+        }
+
+        match value {
+            Value::Table(table) => {
+                self.buf.push(TAG_TABLE);
+                self.register(value);
+                match table.get_metatable() {
+                    Some(mt) => self.write_value(&Value::Table(mt), depth + 1)?,
+                    None => self.buf.push(TAG_NIL),
+                }
+                for pair in table.clone().pairs::<Value, Value>() {
+                    let (key, value) = pair?;
+                    self.write_value(&key, depth + 1)?;
+                    self.write_value(&value, depth + 1)?;
+                }
+                // Keys cannot be `nil`, so it terminates the table
+                self.buf.push(TAG_NIL);
+            }
+            Value::Function(func) => self.write_function(value, func, depth)?,
+            Value::Thread(thread) => {
+                let func = match unsafe { thread_function(self.lua, thread)? } {
+                    Some(func) => func,
+                    None => {
+                        return Err(self.error(value, "only not started coroutines are supported"))
+                    }
+                };
+                if !self.threads.insert(ptr) {
+                    return Err(self.error(value, "coroutine cannot reference itself"));
+                }
+                // Coroutine is registered after its function, as it's created from it
+                self.buf.push(TAG_THREAD);
+                self.write_value(&Value::Function(func), depth + 1)?;
+                self.threads.remove(&ptr);
+                self.register(value);
+            }
+            _ => return Err(self.error(value, "value is not in the permanents table")),
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "luau"))]
+    fn write_function(&mut self, value: &Value, func: &Function, depth: usize) -> Result<()> {
+        if func.info().what.as_deref() == Some(b"C") {
+            return Err(self.error(value, "C function is not in the permanents table"));
+        }
+
+        self.buf.push(TAG_FUNCTION);
+        #[allow(unused_variables)]
+        let id = self.register(value);
+        self.write_bytes(&func.dump(false));
+
+        let upvalues = unsafe { function_upvalues(self.lua, func)? };
+        self.write_u64(upvalues.len() as u64);
+        #[allow(unused_variables)]
+        for (i, (value, upvalue_id)) in upvalues.into_iter().enumerate() {
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            {
+                let n = i as c_int + 1;
+                if let Some(&(func_id, func_n)) = self.upvalues.get(&upvalue_id) {
+                    self.buf.push(TAG_SHARED_UPVALUE);
+                    self.write_u64(func_id);
+                    self.write_u64(func_n as u64);
+                    continue;
+                }
+                self.upvalues.insert(upvalue_id, (id, n));
+            }
+            self.write_value(&value, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "luau")]
+    fn write_function(&mut self, value: &Value, _func: &Function, _depth: usize) -> Result<()> {
+        Err(self.error(value, "function is not in the permanents table"))
+    }
+
+    fn register(&mut self, value: &Value) -> u64 {
+        let id = self.objects.len() as u64;
+        self.objects.insert(value.to_pointer(), id);
+        self.keep.push(value.clone());
+        id
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn error(&self, value: &Value, message: &str) -> Error {
+        Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: "persisted data",
+            message: Some(message.to_string()),
+        }
+    }
+}
+
+struct Unpersister<'a> {
+    lua: &'a Lua,
+    input: &'a [u8],
+    pos: usize,
+    permanents: &'a Table,
+    objects: Vec<Value>,
+}
+
+impl<'a> Unpersister<'a> {
+    fn read_value(&mut self, depth: usize) -> Result<Value> {
+        if depth >= MAX_DEPTH {
+            return Err(self.error("nesting is too deep"));
+        }
+        let value = match self.read_u8()? {
+            TAG_NIL => Nil,
+            TAG_FALSE => Value::Boolean(false),
+            TAG_TRUE => Value::Boolean(true),
+            TAG_INTEGER => {
+                let i = self.read_u64()? as i64;
+                match Integer::try_from(i) {
+                    Ok(i) => Value::Integer(i),
+                    Err(_) => Value::Number(i as Number),
+                }
+            }
+            TAG_NUMBER => Value::Number(f64::from_bits(self.read_u64()?)),
+            #[cfg(feature = "luau")]
+            TAG_VECTOR => {
+                let mut v = [0f32; 3];
+                for x in &mut v {
+                    let bytes = self.read_bytes(4)?;
+                    *x = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                Value::Vector(v[0], v[1], v[2])
+            }
+            TAG_STRING => {
+                let len = self.read_len()?;
+                Value::String(self.lua.create_string(self.read_bytes(len)?)?)
+            }
+            TAG_NULL => Value::LightUserData(LightUserData(ptr::null_mut())),
+            TAG_REF => {
+                let id = self.read_u64()?;
+                match self.objects.get(id as usize) {
+                    Some(value) => value.clone(),
+                    None => return Err(self.error(&format!("invalid object reference {id}"))),
+                }
+            }
+            TAG_PERMANENT => {
+                let name = self.read_value(depth + 1)?;
+                match self.permanents.raw_get::<_, Value>(name.clone())? {
+                    Nil => {
+                        let name = match name {
+                            Value::String(s) => s.to_string_lossy().into_owned(),
+                            name => format!("{name:?}"),
+                        };
+                        let msg = format!("permanent value `{name}` is not found");
+                        return Err(self.error(&msg));
+                    }
+                    value => value,
+                }
+            }
+            TAG_GLOBALS => Value::Table(self.lua.globals()),
+            TAG_TABLE => {
+                let table = self.lua.create_table()?;
+                self.objects.push(Value::Table(table.clone()));
+                match self.read_value(depth + 1)? {
+                    Nil => {}
+                    Value::Table(mt) => table.set_metatable(Some(mt)),
+                    _ => return Err(self.error("metatable is not a table")),
+                }
+                loop {
+                    let key = self.read_value(depth + 1)?;
+                    if key == Nil {
+                        break;
+                    }
+                    let value = self.read_value(depth + 1)?;
+                    table.raw_set(key, value)?;
+                }
+                Value::Table(table)
+            }
+            #[cfg(not(feature = "luau"))]
+            TAG_FUNCTION => self.read_function(depth)?,
+            TAG_THREAD => match self.read_value(depth + 1)? {
+                Value::Function(func) => {
+                    let thread = Value::Thread(self.lua.create_thread(func)?);
+                    self.objects.push(thread.clone());
+                    thread
+                }
+                _ => return Err(self.error("coroutine function is not a function")),
+            },
+            tag => return Err(self.error(&format!("unsupported tag {tag}"))),
+        };
+        Ok(value)
+    }
+
+    #[cfg(not(feature = "luau"))]
+    fn read_function(&mut self, depth: usize) -> Result<Value> {
+        let len = self.read_len()?;
+        let bytecode = self.read_bytes(len)?;
+        let func = self
+            .lua
+            .load_chunk(None, Nil, Some(ChunkMode::Binary), bytecode)?;
+        self.objects.push(Value::Function(func.clone()));
+
+        let count = self.read_u64()?;
+        for n in 1..=count {
+            let n = c_int::try_from(n).map_err(|_| self.error("too many upvalues"))?;
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            if self.input.get(self.pos) == Some(&TAG_SHARED_UPVALUE) {
+                self.pos += 1;
+                let func_id = self.read_u64()?;
+                let func_n = self.read_u64()?;
+                let other = match self.objects.get(func_id as usize) {
+                    Some(Value::Function(other)) => other.clone(),
+                    _ => return Err(self.error("invalid shared upvalue reference")),
+                };
+                let joined = c_int::try_from(func_n)
+                    .map_err(|_| self.error("too many upvalues"))
+                    .and_then(|func_n| unsafe {
+                        join_upvalue(self.lua, &func, n, &other, func_n)
+                    })?;
+                if !joined {
+                    return Err(self.error("invalid shared upvalue reference"));
+                }
+                continue;
+            }
+            let value = self.read_value(depth + 1)?;
+            if !func.set_upvalue(n as usize, value)? {
+                return Err(self.error("invalid upvalue index"));
+            }
+        }
+        Ok(Value::Function(func))
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let bytes = self.read_bytes(8)?;
+        let mut buf = [0; 8];
+        buf.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let len = self.read_u64()?;
+        usize::try_from(len).map_err(|_| self.error("length is too large"))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        match self.pos.checked_add(len) {
+            Some(end) if end <= self.input.len() => {
+                let input = self.input;
+                let bytes = &input[self.pos..end];
+                self.pos = end;
+                Ok(bytes)
+            }
+            _ => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::ToLuaConversionError {
+            from: "persisted data",
+            to: "table",
+            message: Some(format!("{message} (at byte {})", self.pos)),
+        }
+    }
+}
+
+// Returns the function of a coroutine that has not been started yet
+unsafe fn thread_function(lua: &Lua, thread: &Thread) -> Result<Option<Function>> {
+    let state = lua.state();
+    let thread_state = ffi::lua_tothread(lua.ref_thread(), thread.0.index());
+    if ffi::lua_status(thread_state) != ffi::LUA_OK
+        || ffi::lua_gettop(thread_state) != 1
+        || ffi::lua_type(thread_state, 1) != ffi::LUA_TFUNCTION
+    {
+        return Ok(None);
+    }
+
+    let _sg = StackGuard::new(state);
+    check_stack(state, 1)?;
+    check_stack(thread_state, 1)?;
+    ffi::lua_pushvalue(thread_state, 1);
+    ffi::lua_xmove(thread_state, state, 1);
+    match lua.pop_value() {
+        Value::Function(func) => Ok(Some(func)),
+        _ => Ok(None),
+    }
+}
+
+// Returns upvalues of a Lua function with their ids (null if not supported)
+#[cfg(not(feature = "luau"))]
+unsafe fn function_upvalues(lua: &Lua, func: &Function) -> Result<Vec<(Value, *mut c_void)>> {
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 2)?;
+
+    lua.push_ref(&func.0);
+    let mut upvalues = Vec::new();
+    loop {
+        let n = upvalues.len() as c_int + 1;
+        if ffi::lua_getupvalue(state, -1, n).is_null() {
+            break;
+        }
+        let value = lua.pop_value();
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        let id = ffi::lua_upvalueid(state, -1, n);
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        let id = ptr::null_mut();
+        upvalues.push((value, id));
+    }
+    Ok(upvalues)
+}
+
+// Makes the `n`-th upvalue of `func` refer to the `other_n`-th upvalue of `other`
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+unsafe fn join_upvalue(
+    lua: &Lua,
+    func: &Function,
+    n: c_int,
+    other: &Function,
+    other_n: c_int,
+) -> Result<bool> {
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 3)?;
+
+    lua.push_ref(&func.0);
+    lua.push_ref(&other.0);
+    // `lua_upvaluejoin` does not check indices
+    if ffi::lua_getupvalue(state, -2, n).is_null()
+        || ffi::lua_getupvalue(state, -2, other_n).is_null()
+    {
+        return Ok(false);
+    }
+    ffi::lua_pop(state, 2);
+    ffi::lua_upvaluejoin(state, -2, n, -1, other_n);
+    Ok(true)
+}
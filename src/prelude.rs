@@ -2,43 +2,84 @@
 
 #[doc(no_inline)]
 pub use crate::{
-    AnyUserData as LuaAnyUserData, AnyUserDataExt as LuaAnyUserDataExt, Chunk as LuaChunk,
-    Error as LuaError, ErrorContext as LuaErrorContext, ExternalError as LuaExternalError,
+    AnyUserData as LuaAnyUserData, AnyUserDataExt as LuaAnyUserDataExt, AtLeast as LuaAtLeast,
+    Backend as LuaBackend, CallbackMetrics as LuaCallbackMetrics, Capability as LuaCapability,
+    Chunk as LuaChunk, Diagnostic as LuaDiagnostic, DiagnosticKind as LuaDiagnosticKind,
+    EnumValue as LuaEnumValue, Error as LuaError, ErrorContext as LuaErrorContext,
+    ErrorPropagation as LuaErrorPropagation, ExternalError as LuaExternalError,
     ExternalResult as LuaExternalResult, FromLua, FromLuaMulti, Function as LuaFunction,
-    FunctionInfo as LuaFunctionInfo, GCMode as LuaGCMode, Integer as LuaInteger, IntoLua,
-    IntoLuaMulti, LightUserData as LuaLightUserData, Lua, LuaOptions, MetaMethod as LuaMetaMethod,
-    MultiValue as LuaMultiValue, Nil as LuaNil, Number as LuaNumber, RegistryKey as LuaRegistryKey,
-    Result as LuaResult, StdLib as LuaStdLib, String as LuaString, Table as LuaTable,
+    FunctionInfo as LuaFunctionInfo, GCMode as LuaGCMode, GlobalAccess as LuaGlobalAccess,
+    GlobalPolicy as LuaGlobalPolicy, HandleResponse as LuaHandleResponse, Integer as LuaInteger,
+    IntoLua, IntoLuaMulti, LightUserData as LuaLightUserData, Lua, LuaEnum, LuaHandle, LuaOptions,
+    MemoryEvent as LuaMemoryEvent, MemoryEventKind as LuaMemoryEventKind,
+    MemoryTriggers as LuaMemoryTriggers, MemoryVfs as LuaMemoryVfs, MetaMethod as LuaMetaMethod,
+    MetricsKind as LuaMetricsKind, Multi as LuaMulti, MultiValue as LuaMultiValue, Nil as LuaNil,
+    NonEmptyString as LuaNonEmptyString, Number as LuaNumber,
+    OwnedAnyUserData as LuaOwnedAnyUserData, OwnedFunction as LuaOwnedFunction,
+    OwnedString as LuaOwnedString, OwnedTable as LuaOwnedTable, OwnedThread as LuaOwnedThread,
+    PanicPolicy as LuaPanicPolicy, Ranged as LuaRanged, RefThreadStats as LuaRefThreadStats,
+    RegistryKey as LuaRegistryKey, ReplOutput as LuaReplOutput, ReplState as LuaReplState,
+    Result as LuaResult, Signal as LuaSignal, SourceMap as LuaSourceMap, StdLib as LuaStdLib,
+    StdLibFilter as LuaStdLibFilter, String as LuaString, StringChars as LuaStringChars,
+    StringMatches as LuaStringMatches, SyntaxErrorInfo as LuaSyntaxErrorInfo, Table as LuaTable,
     TableExt as LuaTableExt, TablePairs as LuaTablePairs, TableSequence as LuaTableSequence,
-    Thread as LuaThread, ThreadStatus as LuaThreadStatus, UserData as LuaUserData,
+    Tagged as LuaTagged, Thread as LuaThread, ThreadStatus as LuaThreadStatus,
+    TracebackFrame as LuaTracebackFrame, TypeRegistry as LuaTypeRegistry, UserData as LuaUserData,
     UserDataFields as LuaUserDataFields, UserDataMetatable as LuaUserDataMetatable,
     UserDataMethods as LuaUserDataMethods, UserDataRef as LuaUserDataRef,
     UserDataRefMut as LuaUserDataRefMut, UserDataRegistrar as LuaUserDataRegistrar,
-    Value as LuaValue,
+    UserDataTag as LuaUserDataTag, Utf8Policy as LuaUtf8Policy, Value as LuaValue, Vfs as LuaVfs,
 };
 
 #[cfg(not(feature = "luau"))]
 #[doc(no_inline)]
-pub use crate::HookTriggers as LuaHookTriggers;
+pub use crate::{
+    DebugStop as LuaDebugStop, DebugVariable as LuaDebugVariable, Debugger as LuaDebugger,
+    HookTriggers as LuaHookTriggers, Instruction as LuaInstruction, StepAction as LuaStepAction,
+    StopReason as LuaStopReason,
+};
 
-#[cfg(feature = "luau")]
+#[cfg(not(feature = "module"))]
 #[doc(no_inline)]
-pub use crate::{CoverageInfo as LuaCoverageInfo, VmState as LuaVmState};
+pub use crate::StateOwnership as LuaStateOwnership;
 
-#[cfg(feature = "async")]
+#[cfg(feature = "luajit")]
 #[doc(no_inline)]
-pub use crate::AsyncThread as LuaAsyncThread;
+pub use crate::Jit as LuaJit;
 
-#[cfg(feature = "serialize")]
+#[cfg(feature = "luau")]
 #[doc(no_inline)]
 pub use crate::{
-    DeserializeOptions as LuaDeserializeOptions, LuaSerdeExt,
-    SerializeOptions as LuaSerializeOptions,
+    CoverageInfo as LuaCoverageInfo, LuauBuffer, Require as LuaRequire, VmState as LuaVmState,
 };
 
-#[cfg(all(feature = "unstable", not(feature = "send")))]
+#[cfg(feature = "async")]
 #[doc(no_inline)]
 pub use crate::{
-    OwnedAnyUserData as LuaOwnedAnyUserData, OwnedFunction as LuaOwnedFunction,
-    OwnedTable as LuaOwnedTable,
+    AsyncThread as LuaAsyncThread, ChannelReceiver as LuaChannelReceiver,
+    ChannelSender as LuaChannelSender, PollPending as LuaPollPending,
+};
+
+#[cfg(feature = "codec")]
+#[doc(no_inline)]
+pub use crate::Encoding as LuaEncoding;
+
+#[cfg(feature = "watch")]
+#[doc(no_inline)]
+pub use crate::ScriptWatcher as LuaScriptWatcher;
+
+#[cfg(feature = "bitflags")]
+#[doc(no_inline)]
+pub use crate::Flags as LuaFlags;
+
+#[cfg(feature = "console")]
+#[doc(no_inline)]
+pub use crate::DebugConsole as LuaDebugConsole;
+
+#[cfg(feature = "serialize")]
+#[doc(no_inline)]
+pub use crate::{
+    DeserializeOptions as LuaDeserializeOptions, EnumRepr as LuaEnumRepr, LuaSerdeExt,
+    Mirror as LuaMirror, SerializeOptions as LuaSerializeOptions, StringPool as LuaStringPool,
+    UserDataSerialize as LuaUserDataSerialize,
 };
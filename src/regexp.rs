@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::os::raw::c_int;
+
+use regex::bytes::{Captures, Regex};
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::userdata::{MetaMethod, UserData, UserDataMethods};
+use crate::value::{MultiValue, Value};
+
+impl Lua {
+    /// Loads the `regex` module, exposing the [`regex`] crate to Lua.
+    ///
+    /// The module is registered in `package.loaded`, so Lua code can get it with
+    /// `require("regex")`, and is returned to the caller as well.
+    ///
+    /// The module has the following functions:
+    /// - `regex.new(pattern)`: compiles a regular expression, raising an error if it is invalid
+    /// - `regex.escape(s)`: escapes all special characters in `s`
+    ///
+    /// A compiled regular expression is a userdata with the following methods:
+    /// - `re:is_match(s)`: returns `true` if there is a match anywhere in `s`
+    /// - `re:find(s [, init])`: returns the start and end positions of the first match (starting
+    ///   at position `init`), or `nil`
+    /// - `re:match(s)`: returns the captures of the first match (or the whole match if the
+    ///   regex has no groups), or `nil`
+    /// - `re:captures(s)`: returns a table with the whole match at index `0`, the groups at
+    ///   indices `1..n` and the named groups by name, or `nil`
+    /// - `re:gmatch(s)`: returns an iterator over all matches, producing the same values as
+    ///   `re:match`
+    /// - `re:replace(s, repl [, limit])`: replaces the matches (at most `limit`) with `repl`
+    ///   and returns the new string and the number of replacements. `repl` is either a string
+    ///   (which may refer to groups as `$1` or `${name}`) or a function receiving the same values
+    ///   as `re:match`. If the function returns `false` or `nil`, the match is kept.
+    /// - `re:split(s [, limit])`: returns a table of substrings separated by the matches
+    ///
+    /// Like Lua patterns, regular expressions work on bytes and positions are 1-based. Groups
+    /// that did not participate in a match are `false`.
+    ///
+    /// Requires `feature = "regex"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.load_regex_module()?;
+    ///
+    /// let date: String = lua
+    ///     .load(r#"
+    ///         local regex = require("regex")
+    ///         local re = regex.new([[(\d{4})-(\d{2})-(\d{2})]])
+    ///         return (re:replace("2024-05-17", "$3.$2.$1"))
+    ///     "#)
+    ///     .eval()?;
+    /// assert_eq!(date, "17.05.2024");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`regex`]: https://docs.rs/regex
+    #[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
+    pub fn load_regex_module(&self) -> Result<Table> {
+        let loader = self.create_function(|lua, _: MultiValue| {
+            let module = lua.create_table_with_capacity(0, 2)?;
+            let new = |_: &Lua, pattern: String| {
+                let pattern = pattern.to_str()?;
+                Regex::new(pattern).map(LuaRegex).map_err(Error::external)
+            };
+            module.raw_set("new", lua.create_function(new)?)?;
+            let escape = |_: &Lua, s: String| Ok(regex::escape(s.to_str()?));
+            module.raw_set("escape", lua.create_function(escape)?)?;
+            Ok(module)
+        })?;
+        self.load_from_function("regex", loader)
+    }
+}
+
+// Byte ranges of the whole match (at index 0) and the groups of a match
+type Spans = Vec<Option<Range<usize>>>;
+
+struct LuaRegex(Regex);
+
+impl UserData for LuaRegex {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("is_match", |_, this, s: String| {
+            Ok(this.0.is_match(s.as_bytes()))
+        });
+
+        methods.add_method("find", |_, this, (s, init): (String, Option<Integer>)| {
+            let start = match init {
+                Some(init) if init > 1 => init as usize - 1,
+                _ => 0,
+            };
+            let hay = s.as_bytes();
+            if start > hay.len() {
+                return Ok((None, None));
+            }
+            let m = this.0.find_at(hay, start);
+            Ok((
+                m.map(|m| m.start() as Integer + 1),
+                m.map(|m| m.end() as Integer),
+            ))
+        });
+
+        methods.add_method("match", |lua, this, s: String| {
+            let hay = s.as_bytes();
+            match this.0.captures(hay) {
+                Some(caps) => match_values(&lua, hay, &spans(&caps)),
+                None => Ok(MultiValue::from_vec(vec![Value::Nil])),
+            }
+        });
+
+        methods.add_method("captures", |lua, this, s: String| {
+            let hay = s.as_bytes();
+            let caps = match this.0.captures(hay) {
+                Some(caps) => caps,
+                None => return Ok(None),
+            };
+            let table = lua.create_table_with_capacity(caps.len() as c_int, 1)?;
+            for (i, span) in spans(&caps).into_iter().enumerate() {
+                table.raw_set(i, span_value(&lua, hay, span)?)?;
+            }
+            for name in this.0.capture_names().flatten() {
+                if let Some(m) = caps.name(name) {
+                    table.raw_set(name, lua.create_string(m.as_bytes())?)?;
+                }
+            }
+            Ok(Some(table))
+        });
+
+        methods.add_method("gmatch", |_, this, s: String| {
+            let hay = s.as_bytes().to_vec();
+            let matches = this
+                .0
+                .captures_iter(&hay)
+                .map(|caps| spans(&caps))
+                .collect();
+            Ok(RegexMatches { hay, matches })
+        });
+
+        methods.add_method(
+            "replace",
+            |lua, this, (s, repl, limit): (String, Value, Option<usize>)| {
+                let hay = s.as_bytes();
+                let mut result = Vec::with_capacity(hay.len());
+                let (mut last_end, mut count) = (0, 0);
+                for caps in this.0.captures_iter(hay).take(limit.unwrap_or(usize::MAX)) {
+                    let spans = spans(&caps);
+                    let whole = spans[0].clone().unwrap_or_default();
+                    result.extend_from_slice(&hay[last_end..whole.start]);
+                    match &repl {
+                        Value::String(template) => caps.expand(template.as_bytes(), &mut result),
+                        Value::Function(func) => {
+                            replace_with(&lua, func, hay, &spans, &mut result)?
+                        }
+                        _ => {
+                            return Err(Error::RuntimeError(format!(
+                                "invalid replacement value (a {})",
+                                repl.type_name()
+                            )))
+                        }
+                    }
+                    last_end = whole.end;
+                    count += 1;
+                }
+                result.extend_from_slice(&hay[last_end..]);
+                Ok((lua.create_string(result)?, count))
+            },
+        );
+
+        methods.add_method("split", |lua, this, (s, limit): (String, Option<usize>)| {
+            let hay = s.as_bytes();
+            let pieces = this.0.splitn(hay, limit.unwrap_or(usize::MAX));
+            let table = lua.create_table()?;
+            for piece in pieces {
+                table.raw_push(lua.create_string(piece)?)?;
+            }
+            Ok(table)
+        });
+
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            Ok(format!("regex: {}", this.0.as_str()))
+        });
+    }
+}
+
+// Iterator over the matches of a regex, created by `re:gmatch`
+struct RegexMatches {
+    hay: Vec<u8>,
+    matches: VecDeque<Spans>,
+}
+
+impl UserData for RegexMatches {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        // Arguments passed by the generic `for` loop are ignored
+        methods.add_meta_method_mut(MetaMethod::Call, |lua, this, _: MultiValue| {
+            match this.matches.pop_front() {
+                Some(spans) => match_values(&lua, &this.hay, &spans),
+                None => Ok(MultiValue::from_vec(vec![Value::Nil])),
+            }
+        });
+    }
+}
+
+fn spans(caps: &Captures) -> Spans {
+    (caps.iter()).map(|m| m.map(|m| m.range())).collect()
+}
+
+fn span_value(lua: &Lua, hay: &[u8], span: Option<Range<usize>>) -> Result<Value> {
+    match span {
+        Some(span) => lua.create_string(&hay[span]).map(Value::String),
+        None => Ok(Value::Boolean(false)),
+    }
+}
+
+// Returns the groups of a match, or the whole match if the regex has no groups
+fn match_values(lua: &Lua, hay: &[u8], spans: &[Option<Range<usize>>]) -> Result<MultiValue> {
+    let groups = if spans.len() > 1 { &spans[1..] } else { spans };
+    (groups.iter())
+        .map(|span| span_value(lua, hay, span.clone()))
+        .collect()
+}
+
+fn replace_with(
+    lua: &Lua,
+    func: &Function,
+    hay: &[u8],
+    spans: &[Option<Range<usize>>],
+    result: &mut Vec<u8>,
+) -> Result<()> {
+    let whole = spans[0].clone().unwrap_or_default();
+    match func.call::<_, Value>(match_values(lua, hay, spans)?)? {
+        Value::Nil | Value::Boolean(false) => result.extend_from_slice(&hay[whole]),
+        value => {
+            let type_name = value.type_name();
+            match lua.coerce_string(value)? {
+                Some(s) => result.extend_from_slice(s.as_bytes()),
+                None => {
+                    return Err(Error::RuntimeError(format!(
+                        "invalid replacement value (a {type_name})"
+                    )))
+                }
+            }
+        }
+    }
+    Ok(())
+}
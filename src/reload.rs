@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::os::raw::c_void;
+use std::string::String as StdString;
+
+use rustc_hash::FxHashMap;
+
+use crate::chunk::AsChunk;
+use crate::error::Result;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::value::{Nil, Value};
+
+impl Lua {
+    /// Re-executes a changed chunk and patches the previously returned `module` table in place.
+    ///
+    /// The chunk must return a table. Its functions replace the module functions with the
+    /// same keys, new keys are added and nested tables are patched recursively, so the module
+    /// table (and its nested tables) keep their identity and existing references to the module
+    /// see the new code. Other values already present in the module are left untouched to
+    /// preserve its state.
+    ///
+    /// Upvalues of the new functions are migrated from the replaced functions: an upvalue keeps
+    /// the old value when the replaced function has an upvalue with the same name, unless the
+    /// old value is a function. References to the new module tables are redirected to the
+    /// patched ones. Use [`Lua::reload_chunk_with`] to customize the migration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let module: Table = lua.load(r#"
+    ///     local count = 0
+    ///     return { inc = function() count = count + 1; return count end }
+    /// "#).eval()?;
+    /// lua.globals().set("module", module.clone())?;
+    /// lua.load("module.inc()").exec()?;
+    ///
+    /// lua.reload_chunk(&module, r#"
+    ///     local count = 0
+    ///     return { inc = function() count = count + 10; return count end }
+    /// "#)?;
+    /// assert_eq!(lua.load("module.inc()").eval::<i32>()?, 11);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[track_caller]
+    pub fn reload_chunk<'a>(&self, module: &Table, chunk: impl AsChunk<'a>) -> Result<()> {
+        self.reload_chunk_with(module, chunk, |_, old, new| match old {
+            Value::Function(_) => Ok(new),
+            old => Ok(old),
+        })
+    }
+
+    /// Re-executes a changed chunk and patches the previously returned `module` table in place,
+    /// using `migrate` to compute the values of upvalues.
+    ///
+    /// The `migrate` hook is called for every upvalue of a new function that has a counterpart
+    /// with the same name in the replaced function. It receives the upvalue name, the old value
+    /// and the new value, and returns the value to set.
+    ///
+    /// Upvalue names are not available for stripped bytecode (and in Luau with debug level
+    /// lower than 2), in that case the new values are kept.
+    ///
+    /// See [`Lua::reload_chunk`] for details.
+    #[track_caller]
+    pub fn reload_chunk_with<'a, F>(
+        &self,
+        module: &Table,
+        chunk: impl AsChunk<'a>,
+        migrate: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&str, Value, Value) -> Result<Value>,
+    {
+        let new_module: Table = self.load(chunk).eval()?;
+
+        let mut reloader = Reloader {
+            tables: FxHashMap::default(),
+            functions: Vec::new(),
+            migrate,
+        };
+        reloader.patch_table(module, &new_module)?;
+        reloader.migrate_upvalues()
+    }
+}
+
+struct Reloader<F> {
+    // New tables mapped to the patched ones
+    tables: FxHashMap<*const c_void, Table>,
+    // New functions with the functions they replace
+    functions: Vec<(Option<Function>, Function)>,
+    migrate: F,
+}
+
+impl<F> Reloader<F>
+where
+    F: FnMut(&str, Value, Value) -> Result<Value>,
+{
+    fn patch_table(&mut self, old: &Table, new: &Table) -> Result<()> {
+        if self.tables.contains_key(&new.to_pointer()) {
+            return Ok(());
+        }
+        self.tables.insert(new.to_pointer(), old.clone());
+
+        for pair in new.clone().pairs::<Value, Value>() {
+            let (key, new_value) = pair?;
+            match (old.raw_get::<_, Value>(key.clone())?, new_value) {
+                (Value::Table(old_table), Value::Table(new_table)) => {
+                    self.patch_table(&old_table, &new_table)?;
+                }
+                (Value::Function(old_func), Value::Function(new_func)) => {
+                    self.functions.push((Some(old_func), new_func.clone()));
+                    old.raw_set(key, new_func)?;
+                }
+                (Nil, new_value) => {
+                    if let Value::Function(new_func) = &new_value {
+                        self.functions.push((None, new_func.clone()));
+                    }
+                    old.raw_set(key, new_value)?;
+                }
+                // Keep module state
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn migrate_upvalues(&mut self) -> Result<()> {
+        for (old_func, new_func) in std::mem::take(&mut self.functions) {
+            if new_func.info().what.as_deref() == Some(b"C") {
+                continue;
+            }
+            let old_upvalues = (old_func.iter())
+                .flat_map(|f| f.upvalues())
+                .filter(|(name, _)| !name.is_empty())
+                .collect::<HashMap<_, _>>();
+
+            for (i, (name, value)) in new_func.upvalues().into_iter().enumerate() {
+                let value = match value {
+                    Value::Table(ref t) => match self.tables.get(&t.to_pointer()) {
+                        Some(patched) => Value::Table(patched.clone()),
+                        None => value,
+                    },
+                    value => value,
+                };
+                let value = match old_upvalues.get(&name) {
+                    Some(old_value) if name != b"_ENV" => {
+                        let name = StdString::from_utf8_lossy(&name);
+                        (self.migrate)(&name, old_value.clone(), value)?
+                    }
+                    _ => value,
+                };
+                new_func.set_upvalue(i + 1, value)?;
+            }
+        }
+        Ok(())
+    }
+}
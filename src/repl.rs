@@ -0,0 +1,147 @@
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::value::MultiValue;
+
+/// State of an interactive session driven by [`Lua::repl_eval`].
+///
+/// Accumulates lines of an incomplete statement until it can be evaluated.
+#[derive(Debug, Default, Clone)]
+pub struct ReplState {
+    buffer: StdString,
+}
+
+impl ReplState {
+    /// Creates a new empty REPL state.
+    pub fn new() -> Self {
+        ReplState::default()
+    }
+
+    /// Returns `true` if the previous input was incomplete and the next line will be appended
+    /// to it.
+    ///
+    /// This can be used to select a prompt for the next line (eg. `>>` instead of `>`).
+    pub fn is_continuation(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Returns the accumulated incomplete input.
+    pub fn pending_input(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Discards the accumulated incomplete input.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+}
+
+/// Result of evaluating a line of input by [`Lua::repl_eval`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ReplOutput {
+    /// The input is incomplete, more lines are needed to evaluate it.
+    Incomplete,
+    /// The input was evaluated.
+    Complete {
+        /// Values returned by the evaluated input.
+        values: MultiValue,
+        /// Values converted to strings (using `__tostring` metamethod if available) and
+        /// separated by tabs, as printed by the standalone Lua interpreter.
+        output: StdString,
+    },
+}
+
+impl Lua {
+    /// Evaluates a line of input in REPL (read-eval-print loop) mode.
+    ///
+    /// The line is appended to the input accumulated in `state`. If the input is incomplete
+    /// (eg. an unfinished `if` statement), [`ReplOutput::Incomplete`] is returned and the input
+    /// is kept for the next call. Otherwise the input is evaluated as an expression or a
+    /// statement (see [`Chunk::eval`]) and the accumulated input is cleared.
+    ///
+    /// The first returned value is assigned to the global variable `_`, so it can be referenced
+    /// by subsequent lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, ReplOutput, ReplState, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let mut state = ReplState::new();
+    ///
+    /// assert!(matches!(lua.repl_eval("function f(x)", &mut state)?, ReplOutput::Incomplete));
+    /// lua.repl_eval("return x * 2 end", &mut state)?;
+    /// lua.repl_eval("f(21)", &mut state)?;
+    /// match lua.repl_eval("_, 'ok'", &mut state)? {
+    ///     ReplOutput::Complete { output, .. } => assert_eq!(output, "42\tok"),
+    ///     _ => unreachable!(),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Chunk::eval`]: crate::Chunk::eval
+    pub fn repl_eval(&self, line: &str, state: &mut ReplState) -> Result<ReplOutput> {
+        self.repl_eval_in(line, state, None, "=stdin")
+    }
+
+    // Evaluates a line of input using `env` as the environment (and for storing `_`)
+    pub(crate) fn repl_eval_in(
+        &self,
+        line: &str,
+        state: &mut ReplState,
+        env: Option<&Table>,
+        name: &str,
+    ) -> Result<ReplOutput> {
+        if !state.buffer.is_empty() {
+            // Separate input lines
+            state.buffer.push('\n');
+        }
+        state.buffer.push_str(line);
+
+        let mut chunk = self.load(&state.buffer).set_name(name);
+        if let Some(env) = env {
+            chunk = chunk.set_environment(env.clone());
+        }
+        let result = chunk.eval::<MultiValue>();
+        let values = match result {
+            Err(Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            }) => return Ok(ReplOutput::Incomplete),
+            result => {
+                state.buffer.clear();
+                result?
+            }
+        };
+
+        let globals = self.globals_storage()?;
+        if let Some(value) = values.get(0) {
+            env.unwrap_or(&globals).raw_set("_", value.clone())?;
+        }
+
+        let tostring: Option<Function> = globals.raw_get("tostring")?;
+        let mut output = StdString::new();
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                output.push('\t');
+            }
+            let s = match &tostring {
+                Some(tostring) => tostring.call::<_, Option<String>>(value.clone())?,
+                None => None,
+            };
+            match s {
+                Some(s) => output.push_str(&s.to_string_lossy()),
+                None => output.push_str(&format!("{value:?}")),
+            }
+        }
+
+        Ok(ReplOutput::Complete { values, output })
+    }
+}
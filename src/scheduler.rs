@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+
+// Registry key of the `tick` function of the scheduler
+const SCHEDULER_TICK_KEY: &str = "__mlua_task_scheduler_tick";
+
+// The scheduler keeps a queue of suspended threads ordered by their resume time and the order of
+// scheduling, so threads are always resumed in the same order
+const SCHEDULER_SOURCE: &str = r##"
+local coroutine, select, type, error = ...
+local now, seq = 0, 0
+local queue, first, last = {}, 1, 0
+local cancelled = setmetatable({}, { __mode = "k" })
+-- Marker yielded by `task.wait`
+local WAIT = {}
+
+local function pack(...)
+    return { n = select("#", ...), ... }
+end
+
+local function unpack(args, i)
+    if i <= args.n then
+        return args[i], unpack(args, i + 1)
+    end
+end
+
+local function schedule(time, thread, args, start)
+    if cancelled[thread] then
+        return
+    end
+    seq = seq + 1
+    local entry = { time = time, seq = seq, thread = thread, args = args, start = start }
+    local i = last
+    while i >= first and queue[i].time > time do
+        queue[i + 1] = queue[i]
+        i = i - 1
+    end
+    queue[i + 1] = entry
+    last = last + 1
+end
+
+local function resumed(thread, ok, marker, delay, ...)
+    if not ok then
+        error(marker, 0)
+    end
+    if coroutine.status(thread) == "dead" then
+        return
+    end
+    if marker == WAIT then
+        schedule(now + (delay or 0), thread, nil, now)
+    else
+        -- Yielded by `coroutine.yield`, resumed in the next tick
+        schedule(now, thread, pack())
+    end
+end
+
+local function thread_of(f)
+    if type(f) == "thread" then
+        return f
+    end
+    -- Wrapped to accept C functions (Lua 5.1) and callable values
+    return coroutine.create(function(...)
+        return f(...)
+    end)
+end
+
+local task = {}
+
+function task.spawn(f, ...)
+    local thread = thread_of(f)
+    resumed(thread, coroutine.resume(thread, ...))
+    return thread
+end
+
+function task.defer(f, ...)
+    local thread = thread_of(f)
+    schedule(now, thread, pack(...))
+    return thread
+end
+
+function task.delay(delay, f, ...)
+    local thread = thread_of(f)
+    schedule(now + delay, thread, pack(...))
+    return thread
+end
+
+function task.wait(delay)
+    return coroutine.yield(WAIT, delay)
+end
+
+function task.cancel(thread)
+    cancelled[thread] = true
+    local j = first
+    for i = first, last do
+        local entry = queue[i]
+        queue[i] = nil
+        if entry.thread ~= thread then
+            queue[j] = entry
+            j = j + 1
+        end
+    end
+    last = j - 1
+end
+
+local function tick(dt)
+    now = now + dt
+    -- Threads scheduled during this tick are resumed in the next one
+    local limit, count = seq, 0
+    while first <= last do
+        local entry = queue[first]
+        if entry.time > now or entry.seq > limit then
+            break
+        end
+        queue[first] = nil
+        first = first + 1
+        local thread = entry.thread
+        if not cancelled[thread] and coroutine.status(thread) == "suspended" then
+            count = count + 1
+            if entry.start then
+                resumed(thread, coroutine.resume(thread, now - entry.start))
+            else
+                resumed(thread, coroutine.resume(thread, unpack(entry.args, 1)))
+            end
+        end
+    end
+    return count
+end
+
+return task, tick
+"##;
+
+impl Lua {
+    /// Installs the `task` module, a scheduler of Lua threads (coroutines) driven by the host.
+    ///
+    /// Similar to the standard libraries, the module is set as the global `task` variable and
+    /// registered in `package.loaded`, so `require("task")` works as well.
+    ///
+    /// The module has the following functions:
+    /// - `task.spawn(f, ...)`: runs the function (or thread) `f` immediately until it yields
+    /// - `task.defer(f, ...)`: runs `f` in the next [`Lua::tick`]
+    /// - `task.delay(seconds, f, ...)`: runs `f` after `seconds`
+    /// - `task.wait([seconds])`: suspends the current thread for `seconds` (or until the next
+    ///   tick), returns the elapsed time
+    /// - `task.cancel(thread)`: cancels the scheduled thread
+    ///
+    /// The functions return the scheduled thread. Threads suspended by `coroutine.yield` are
+    /// resumed in the next tick.
+    ///
+    /// Time is advanced only by [`Lua::tick`], so the host can drive the scheduler from its
+    /// main loop (eg. once per frame) or from an async task. Threads due in the same tick are
+    /// resumed in order of their resume time and then in order of scheduling, which makes the
+    /// execution deterministic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.enable_task_scheduler()?;
+    /// lua.load(
+    ///     r#"
+    ///     log = {}
+    ///     task.delay(1, function() table.insert(log, "delayed") end)
+    ///     task.spawn(function()
+    ///         table.insert(log, "spawned")
+    ///         task.wait(0.5)
+    ///         table.insert(log, "resumed")
+    ///     end)
+    /// "#,
+    /// )
+    /// .exec()?;
+    ///
+    /// lua.tick(Duration::from_millis(600))?;
+    /// lua.tick(Duration::from_millis(600))?;
+    /// let log: Vec<String> = lua.globals().get("log")?;
+    /// assert_eq!(log, ["spawned", "resumed", "delayed"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enable_task_scheduler(&self) -> Result<()> {
+        let globals = self.globals_storage()?;
+        let coroutine = match globals.raw_get::<_, Option<Table>>("coroutine")? {
+            Some(coroutine) => coroutine,
+            None => {
+                let msg = "task scheduler requires the coroutine library";
+                return Err(Error::RuntimeError(msg.to_string()));
+            }
+        };
+        let (module, tick): (Table, Function) =
+            self.load(SCHEDULER_SOURCE).set_name("=task").call((
+                coroutine,
+                globals.raw_get::<_, Function>("select")?,
+                globals.raw_get::<_, Function>("type")?,
+                globals.raw_get::<_, Function>("error")?,
+            ))?;
+        self.set_named_registry_value(SCHEDULER_TICK_KEY, tick)?;
+
+        if let Some(loaded) = self.named_registry_value::<Option<Table>>("_LOADED")? {
+            loaded.raw_set("task", module.clone())?;
+        }
+        globals.raw_set("task", module)
+    }
+
+    /// Advances the time of the task scheduler by `dt` and resumes the threads that are due.
+    ///
+    /// Returns the number of resumed threads. If a thread raises an error, the error is returned
+    /// and the remaining threads are resumed by the next tick.
+    ///
+    /// The scheduler must be installed using [`Lua::enable_task_scheduler`].
+    pub fn tick(&self, dt: Duration) -> Result<usize> {
+        match self.named_registry_value::<Option<Function>>(SCHEDULER_TICK_KEY)? {
+            Some(tick) => tick.call(dt.as_secs_f64()),
+            None => Err(Error::RuntimeError(
+                "task scheduler is not enabled".to_string(),
+            )),
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::mem;
 use std::os::raw::c_int;
+use std::ptr;
+use std::rc::Rc;
+use std::string::String as StdString;
 
 #[cfg(feature = "serialize")]
 use serde::Serialize;
@@ -11,7 +15,10 @@ use crate::error::{Error, Result};
 use crate::ffi;
 use crate::function::Function;
 use crate::lua::Lua;
-use crate::types::{Callback, CallbackUpvalue, LuaRef, MaybeSend};
+use crate::table::Table;
+use crate::types::{
+    Callback, CallbackUpvalue, FieldValue, Integer, LuaRef, MaybeSend, RegistryKey,
+};
 use crate::userdata::{
     AnyUserData, MetaMethod, UserData, UserDataCell, UserDataFields, UserDataMethods,
 };
@@ -25,7 +32,7 @@ use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Val
 use crate::userdata::USER_VALUE_MAXSLOT;
 
 #[cfg(feature = "async")]
-use futures_core::future::Future;
+use {crate::userdata::UserDataRefMut, futures_core::future::Future};
 
 /// Constructed by the [`Lua::scope`] method, allows temporarily creating Lua userdata and
 /// callbacks that are not required to be Send or 'static.
@@ -35,21 +42,135 @@ use futures_core::future::Future;
 /// [`Lua::scope`]: crate::Lua.html::scope
 pub struct Scope<'scope> {
     lua: Lua,
-    destructors: RefCell<Vec<(LuaRef, DestructorCallback)>>,
+    // Shared with the guard that invalidates created values when the scope ends
+    state: Rc<ScopeState<'scope>>,
     _scope_invariant: PhantomData<Cell<&'scope ()>>,
 }
 
+struct ScopeState<'scope> {
+    destructors: RefCell<Vec<(LuaRef, DestructorCallback, Option<BorrowCheckCallback>)>>,
+    registry_ids: RefCell<Vec<c_int>>,
+    leak_audit: RefCell<Option<LeakAuditCallback<'scope>>>,
+    // Set when the `Lua::scope` or `Scope::child` call that created the scope has returned
+    ended: Cell<bool>,
+}
+
+// Invalidates values created through the scope on drop, even if the scope handle itself was
+// leaked or moved out of the `Lua::scope` (or `Scope::child`) callback.
+struct ScopeGuard<'scope>(Scope<'scope>);
+
+impl<'scope> Drop for ScopeGuard<'scope> {
+    fn drop(&mut self) {
+        // We separate the action of invalidating the userdata in Lua and actually dropping the
+        // userdata type into two phases. This is so that, in the event a userdata drop panics, we
+        // can be sure that all of the userdata in Lua is actually invalidated.
+        self.0.state.ended.set(true);
+        drop(self.0.invalidate());
+    }
+}
+
 type DestructorCallback = Box<dyn Fn(LuaRef) -> Vec<Box<dyn Any>>>;
+type BorrowCheckCallback = Box<dyn Fn(&LuaRef) -> Result<()>>;
+type LeakAuditCallback<'scope> = Box<dyn Fn(&[ScopeLeak]) + 'scope>;
+
+/// A value created through [`Scope`] that was still reachable from Lua when the scope ended.
+///
+/// Reported by the callback set using [`Scope::set_leak_audit`].
+///
+/// [`Scope::set_leak_audit`]: crate::Scope::set_leak_audit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeLeak {
+    /// Lua type name of the leaked value (`function`, `userdata` or `table`).
+    pub type_name: &'static str,
+    /// Path to the value, starting from `_G` (globals) or `registry`.
+    pub path: StdString,
+}
+
+/// A `'static` type tag that identifies a non-'static userdata type.
+///
+/// Used by [`Scope::create_nonstatic_userdata_cached`] to cache the userdata metatable. The tag
+/// defines the userdata type up to its lifetime, so a metatable cached for the tag can be safely
+/// reused with userdata borrowing data of different scopes.
+///
+/// # Examples
+///
+/// ```
+/// # use std::cell::Cell;
+/// # use mlua::{Lua, Result, UserData, UserDataMethods, UserDataTag};
+/// # fn main() -> Result<()> {
+/// struct Counter<'a>(&'a Cell<i64>);
+///
+/// impl UserData for Counter<'_> {
+///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+///         methods.add_method("inc", |_, this, ()| Ok(this.0.set(this.0.get() + 1)));
+///     }
+/// }
+///
+/// struct CounterTag;
+///
+/// impl UserDataTag for CounterTag {
+///     type UserData<'a> = Counter<'a>;
+/// }
+///
+/// let lua = Lua::new();
+/// let counter = Cell::new(0);
+/// lua.scope(|scope| {
+///     let ud = scope.create_nonstatic_userdata_cached::<CounterTag>(Counter(&counter))?;
+///     lua.globals().set("counter", ud)?;
+///     lua.load("counter:inc()").exec()
+/// })?;
+/// assert_eq!(counter.get(), 1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Scope::create_nonstatic_userdata_cached`]: crate::Scope::create_nonstatic_userdata_cached
+pub trait UserDataTag: 'static {
+    /// Userdata type borrowing data for the lifetime `'a`.
+    type UserData<'a>: UserData + 'a;
+}
 
 impl<'scope> Scope<'scope> {
-    pub(crate) fn new(lua: &Lua) -> Scope<'scope> {
-        Scope {
+    // Calls `f` with a new scope, invalidating values created through it after `f` returns
+    pub(crate) fn with<R>(lua: &Lua, f: impl FnOnce(Scope<'scope>) -> R) -> R {
+        let scope = Scope {
             lua: lua.clone(),
-            destructors: RefCell::new(Vec::new()),
+            state: Rc::new(ScopeState {
+                destructors: RefCell::new(Vec::new()),
+                registry_ids: RefCell::new(Vec::new()),
+                leak_audit: RefCell::new(None),
+                ended: Cell::new(false),
+            }),
+            _scope_invariant: PhantomData,
+        };
+        let _guard = ScopeGuard(scope.share());
+        f(scope)
+    }
+
+    fn share(&self) -> Scope<'scope> {
+        Scope {
+            lua: self.lua.clone(),
+            state: self.state.clone(),
             _scope_invariant: PhantomData,
         }
     }
 
+    // Registers destructor of a value created through the scope.
+    // If the scope has already ended, the value is invalidated immediately.
+    fn push_destructor(
+        &self,
+        r: LuaRef,
+        destructor: DestructorCallback,
+        borrow_check: Option<BorrowCheckCallback>,
+    ) -> Result<()> {
+        if self.state.ended.get() {
+            drop(destructor(r));
+            return Err(Error::RuntimeError("scope has ended".into()));
+        }
+        (self.state.destructors.borrow_mut()).push((r, destructor, borrow_check));
+        Ok(())
+    }
+
     /// Wraps a Rust function or closure, creating a callable Lua function handle to it.
     ///
     /// This is a version of [`Lua::create_function`] that creates a callback which expires on
@@ -101,6 +222,69 @@ impl<'scope> Scope<'scope> {
         })
     }
 
+    /// Creates and returns a new empty table.
+    ///
+    /// This is a version of [`Lua::create_table`] that creates a table which is emptied on scope
+    /// drop. All its entries and the metatable are removed, so that capabilities stored in the
+    /// table cannot be used by Lua code once the scope has ended.
+    ///
+    /// [`Lua::create_table`]: crate::Lua::create_table
+    pub fn create_table(&self) -> Result<Table> {
+        let t = self.lua.create_table()?;
+
+        let destructor: DestructorCallback = Box::new(|t| unsafe {
+            let state = t.lua.state();
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 4);
+
+            t.lua.push_ref(&t);
+
+            #[cfg(feature = "luau")]
+            {
+                ffi::lua_setreadonly(state, -1, 0);
+                ffi::lua_cleartable(state, -1);
+            }
+
+            #[cfg(not(feature = "luau"))]
+            {
+                // It must be safe as long as we don't use invalid keys
+                ffi::lua_pushnil(state);
+                while ffi::lua_next(state, -2) != 0 {
+                    ffi::lua_pop(state, 1); // pop value
+                    ffi::lua_pushvalue(state, -1); // copy key
+                    ffi::lua_pushnil(state);
+                    ffi::lua_rawset(state, -4);
+                }
+            }
+
+            ffi::lua_pushnil(state);
+            ffi::lua_setmetatable(state, -2);
+
+            vec![]
+        });
+        self.push_destructor(t.0.clone(), destructor, None)?;
+
+        Ok(t)
+    }
+
+    /// Places a value in the Lua registry with an auto-generated key.
+    ///
+    /// This is a version of [`Lua::create_registry_value`] where the value is removed from the
+    /// registry on scope drop. The returned [`RegistryKey`] remains valid, but refers to `nil`
+    /// after the scope has ended.
+    ///
+    /// [`Lua::create_registry_value`]: crate::Lua::create_registry_value
+    pub fn create_registry_value<T: IntoLua>(&self, t: T) -> Result<RegistryKey> {
+        if self.state.ended.get() {
+            return Err(Error::RuntimeError("scope has ended".into()));
+        }
+        let key = self.lua.create_registry_value(t)?;
+        if !key.is_nil() {
+            self.state.registry_ids.borrow_mut().push(key.registry_id);
+        }
+        Ok(key)
+    }
+
     /// Creates a Lua userdata object from a custom userdata type.
     ///
     /// This is a version of [`Lua::create_userdata`] that creates a userdata which expires on
@@ -249,9 +433,7 @@ impl<'scope> Scope<'scope> {
 
             vec![Box::new(take_userdata::<UserDataCell<T>>(state))]
         });
-        self.destructors
-            .borrow_mut()
-            .push((ud.0.clone(), destructor));
+        self.push_destructor(ud.0.clone(), destructor, Some(userdata_borrow_check::<T>()))?;
 
         Ok(())
     }
@@ -283,16 +465,20 @@ impl<'scope> Scope<'scope> {
     where
         T: UserData + 'scope,
     {
-        // 'callback outliving 'scope is a lie to make the types work out, required due to the
-        // inability to work with the more correct callback type that is universally quantified over
-        // 'lua. This is safe though, because `UserData::add_methods` does not get to pick the 'lua
-        // lifetime, so none of the static methods UserData types can add can possibly capture
-        // parameters.
-        fn wrap_method<'scope, 'lua, T: 'scope>(
-            scope: &Scope<'scope>,
-            ud_ptr: *const UserDataCell<T>,
-            method: NonStaticMethod<T>,
-        ) -> Result<Function> {
+        let lua = self.lua.clone();
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 13)?;
+
+            #[cfg(not(feature = "luau"))]
+            let ud_ptr = new_nonstatic_userdata::<T>(state)?;
+            #[cfg(feature = "luau")]
+            let ud_ptr = {
+                crate::util::push_userdata(state, UserDataCell::new(data), true)?;
+                ffi::lua_touserdata(state, -1) as *const UserDataCell<T>
+            };
+
             // On methods that actually receive the userdata, we fake a type check on the passed in
             // userdata, where we pretend there is a unique type per call to
             // `Scope::create_nonstatic_userdata`. You can grab a method from a userdata and call
@@ -300,207 +486,321 @@ impl<'scope> Scope<'scope> {
             // with a type mismatch, but here without this check would proceed as though you had
             // called the method on the original value (since we otherwise completely ignore the
             // first argument).
-            let check_ud_type = move |lua: Lua, value| -> Result<&UserDataCell<T>> {
+            let check_ud_type = move |lua: &Lua, value| -> Result<*const UserDataCell<T>> {
                 if let Some(Value::UserData(ud)) = value {
                     let state = lua.state();
-                    unsafe {
-                        let _sg = StackGuard::new(state);
-                        check_stack(state, 2)?;
-                        lua.push_userdata_ref(&ud.0)?;
-                        if get_userdata(state, -1) as *const _ == ud_ptr {
-                            return Ok(&*ud_ptr);
-                        }
+                    let _sg = StackGuard::new(state);
+                    check_stack(state, 2)?;
+                    lua.push_userdata_ref(&ud.0)?;
+                    if ptr::eq(get_userdata(state, -1), ud_ptr) {
+                        return Ok(ud_ptr);
                     }
                 };
                 Err(Error::UserDataTypeMismatch)
             };
+            push_nonstatic_metatable::<T>(&lua, check_ud_type, |f| self.create_callback(f))?;
 
-            match method {
-                NonStaticMethod::Method(method) => {
-                    let f = Box::new(move |lua: Lua, mut args: MultiValue| {
-                        let data = check_ud_type(lua.clone(), args.pop_front())?;
-                        let data = data.try_borrow()?;
-                        method(lua, &*data, args)
-                    });
-                    unsafe { scope.create_callback(f) }
-                }
-                NonStaticMethod::MethodMut(method) => {
-                    let method = RefCell::new(method);
-                    let f = Box::new(move |lua: Lua, mut args: MultiValue| {
-                        let data = check_ud_type(lua.clone(), args.pop_front())?;
-                        let mut method = method
-                            .try_borrow_mut()
-                            .map_err(|_| Error::RecursiveMutCallback)?;
-                        let mut data = data.try_borrow_mut()?;
-                        (*method)(lua, &mut *data, args)
-                    });
-                    unsafe { scope.create_callback(f) }
-                }
-                NonStaticMethod::Function(function) => unsafe { scope.create_callback(function) },
-                NonStaticMethod::FunctionMut(function) => {
-                    let function = RefCell::new(function);
-                    let f = Box::new(move |lua, args| {
-                        (*function
-                            .try_borrow_mut()
-                            .map_err(|_| Error::RecursiveMutCallback)?)(
-                            lua, args
-                        )
-                    });
-                    unsafe { scope.create_callback(f) }
-                }
-            }
+            let mt_ptr = ffi::lua_topointer(state, -1);
+            // Write userdata just before attaching metatable with `__gc` metamethod
+            #[cfg(not(feature = "luau"))]
+            std::ptr::write(ud_ptr as _, UserDataCell::new(data));
+            ffi::lua_setmetatable(state, -2);
+            let ud = AnyUserData(lua.pop_ref());
+            lua.register_raw_userdata_metatable(mt_ptr, None);
+
+            self.seal_nonstatic_userdata::<T>(&ud, true)?;
+            Ok(ud)
         }
+    }
 
-        let mut ud_fields = NonStaticUserDataFields::default();
-        let mut ud_methods = NonStaticUserDataMethods::default();
-        T::add_fields(&mut ud_fields);
-        T::add_methods(&mut ud_methods);
+    /// Creates a Lua userdata object from a custom userdata type, reusing a metatable associated
+    /// with the type tag `Tag`.
+    ///
+    /// This is a version of [`Scope::create_nonstatic_userdata`] that builds the userdata
+    /// metatable only once per `Lua` instance and `Tag`, instead of creating a new metatable for
+    /// every userdata object. Methods resolve the userdata they are called on at call time, so
+    /// the cached metatable can be reused across different scopes.
+    ///
+    /// The userdata type is defined by the tag (see [`UserDataTag`]), so the cached metatable is
+    /// only ever used with userdata of the same type.
+    ///
+    /// [`Scope::create_nonstatic_userdata`]: #method.create_nonstatic_userdata
+    pub fn create_nonstatic_userdata_cached<Tag>(
+        &self,
+        data: Tag::UserData<'scope>,
+    ) -> Result<AnyUserData>
+    where
+        Tag: UserDataTag,
+    {
+        type T<'scope, Tag> = <Tag as UserDataTag>::UserData<'scope>;
 
         let lua = self.lua.clone();
         let state = lua.state();
+        let tag_type_id = TypeId::of::<NonStaticTag<Tag>>();
         unsafe {
             let _sg = StackGuard::new(state);
             check_stack(state, 13)?;
 
-            #[cfg(not(feature = "luau"))]
-            #[allow(clippy::let_and_return)]
-            let ud_ptr = protect_lua!(state, 0, 1, |state| {
-                let ud = ffi::lua_newuserdata(state, mem::size_of::<UserDataCell<T>>());
-
-                // Set empty environment for Lua 5.1
-                #[cfg(any(feature = "lua51", feature = "luajit"))]
-                {
-                    ffi::lua_newtable(state);
-                    ffi::lua_setuservalue(state, -2);
+            let mt_id = match lua.nonstatic_userdata_metatable(tag_type_id) {
+                Some(mt_id) => mt_id,
+                None => {
+                    let check_ud_type =
+                        move |lua: &Lua, value| -> Result<*const UserDataCell<T<Tag>>> {
+                            if let Some(Value::UserData(ud)) = value {
+                                let state = lua.state();
+                                let _sg = StackGuard::new(state);
+                                check_stack(state, 2)?;
+                                if lua.push_userdata_ref(&ud.0)? == Some(tag_type_id) {
+                                    return Ok(get_userdata(state, -1));
+                                }
+                            };
+                            Err(Error::UserDataTypeMismatch)
+                        };
+                    // Methods of the cached metatable outlive the scope, but they can be called
+                    // only on alive userdata of type `T` (checked by `check_ud_type`)
+                    push_nonstatic_metatable::<T<Tag>>(&lua, check_ud_type, |f| {
+                        let f = mem::transmute::<Callback<'scope>, Callback<'static>>(f);
+                        lua.create_callback(f)
+                    })?;
+
+                    let mt_ptr = ffi::lua_topointer(state, -1);
+                    let mt_id = protect_lua!(state, 1, 0, |state| {
+                        ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX)
+                    })?;
+                    lua.register_raw_userdata_metatable(mt_ptr, Some(tag_type_id));
+                    lua.register_nonstatic_userdata_metatable(tag_type_id, mt_id);
+                    mt_id
                 }
+            };
 
-                ud as *const UserDataCell<T>
-            })?;
+            #[cfg(not(feature = "luau"))]
+            {
+                let ud_ptr = new_nonstatic_userdata::<T<Tag>>(state)?;
+                std::ptr::write(ud_ptr as _, UserDataCell::new(data));
+            }
             #[cfg(feature = "luau")]
-            let ud_ptr = {
-                crate::util::push_userdata(state, UserDataCell::new(data), true)?;
-                ffi::lua_touserdata(state, -1) as *const UserDataCell<T>
-            };
+            crate::util::push_userdata(state, UserDataCell::new(data), true)?;
 
-            // Prepare metatable, add meta methods first and then meta fields
-            let meta_methods_nrec = ud_methods.meta_methods.len() + ud_fields.meta_fields.len() + 1;
-            push_table(state, 0, meta_methods_nrec as c_int, true)?;
+            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, mt_id as Integer);
+            ffi::lua_setmetatable(state, -2);
+            let ud = AnyUserData(lua.pop_ref());
 
-            for (k, m) in ud_methods.meta_methods {
-                lua.push_value(Value::Function(wrap_method(self, ud_ptr, m)?))?;
-                rawset_field(state, -2, MetaMethod::validate(&k)?)?;
-            }
-            for (k, f) in ud_fields.meta_fields {
-                lua.push_value(f(mem::transmute(lua.clone()))?)?;
-                rawset_field(state, -2, MetaMethod::validate(&k)?)?;
+            self.seal_nonstatic_userdata::<T<Tag>>(&ud, false)?;
+            Ok(ud)
+        }
+    }
+
+    // Registers destructor for non-'static userdata.
+    // If `own_metatable` is true, the userdata metatable is deregistered on scope drop.
+    unsafe fn seal_nonstatic_userdata<T: 'scope>(
+        &self,
+        ud: &AnyUserData,
+        own_metatable: bool,
+    ) -> Result<()> {
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        let newtable = self.lua.create_table()?;
+        let destructor: DestructorCallback = Box::new(move |ud| {
+            let state = ud.lua.state();
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 2);
+
+            // Check that userdata is valid (very likely)
+            if ud.lua.push_userdata_ref(&ud).is_err() {
+                return vec![];
             }
-            let metatable_index = ffi::lua_absindex(state, -1);
-
-            let mut field_getters_index = None;
-            let field_getters_nrec = ud_fields.field_getters.len();
-            if field_getters_nrec > 0 {
-                push_table(state, 0, field_getters_nrec as c_int, true)?;
-                for (k, m) in ud_fields.field_getters {
-                    lua.push_value(Value::Function(wrap_method(self, ud_ptr, m)?))?;
-                    rawset_field(state, -2, &k)?;
-                }
-                field_getters_index = Some(ffi::lua_absindex(state, -1));
+
+            // Deregister metatable
+            if own_metatable {
+                ffi::lua_getmetatable(state, -1);
+                let mt_ptr = ffi::lua_topointer(state, -1);
+                ffi::lua_pop(state, 1);
+                ud.lua.deregister_raw_userdata_metatable(mt_ptr);
             }
 
-            let mut field_setters_index = None;
-            let field_setters_nrec = ud_fields.field_setters.len();
-            if field_setters_nrec > 0 {
-                push_table(state, 0, field_setters_nrec as c_int, true)?;
-                for (k, m) in ud_fields.field_setters {
-                    lua.push_value(Value::Function(wrap_method(self, ud_ptr, m)?))?;
-                    rawset_field(state, -2, &k)?;
-                }
-                field_setters_index = Some(ffi::lua_absindex(state, -1));
+            // Clear associated user values
+            #[cfg(feature = "lua54")]
+            for i in 1..=USER_VALUE_MAXSLOT {
+                ffi::lua_pushnil(state);
+                ffi::lua_setiuservalue(state, -2, i as c_int);
+            }
+            #[cfg(any(feature = "lua53", feature = "lua52", feature = "luau"))]
+            {
+                ffi::lua_pushnil(state);
+                ffi::lua_setuservalue(state, -2);
+            }
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            {
+                ud.lua.push_ref(&newtable.0);
+                ffi::lua_setuservalue(state, -2);
             }
 
-            let mut methods_index = None;
-            let methods_nrec = ud_methods.methods.len();
-            if methods_nrec > 0 {
-                // Create table used for methods lookup
-                push_table(state, 0, methods_nrec as c_int, true)?;
-                for (k, m) in ud_methods.methods {
-                    lua.push_value(Value::Function(wrap_method(self, ud_ptr, m)?))?;
-                    rawset_field(state, -2, &k)?;
-                }
-                methods_index = Some(ffi::lua_absindex(state, -1));
+            // A hack to drop non-static `T`
+            unsafe fn seal<T>(t: T) -> Box<dyn FnOnce() + 'static> {
+                let f: Box<dyn FnOnce()> = Box::new(move || drop(t));
+                mem::transmute(f)
             }
 
-            init_userdata_metatable::<UserDataCell<T>>(
-                state,
-                metatable_index,
-                field_getters_index,
-                field_setters_index,
-                methods_index,
-            )?;
+            let ud = take_userdata::<UserDataCell<T>>(state);
+            vec![Box::new(seal(ud))]
+        });
+        self.push_destructor(ud.0.clone(), destructor, Some(userdata_borrow_check::<T>()))?;
+        Ok(())
+    }
 
-            let count = field_getters_index.map(|_| 1).unwrap_or(0)
-                + field_setters_index.map(|_| 1).unwrap_or(0)
-                + methods_index.map(|_| 1).unwrap_or(0);
-            ffi::lua_pop(state, count);
+    /// Creates a nested scope and calls the given function with it.
+    ///
+    /// The child scope has its own list of created values, which are invalidated as soon as `f`
+    /// returns (or earlier, using [`Scope::close`]), independently of this scope.
+    /// See [`Lua::scope`] for more details.
+    ///
+    /// [`Scope::close`]: #method.close
+    /// [`Lua::scope`]: crate::Lua::scope
+    pub fn child<'inner, R>(&self, f: impl FnOnce(Scope<'inner>) -> Result<R>) -> Result<R> {
+        Scope::with(&self.lua, f)
+    }
 
-            let mt_ptr = ffi::lua_topointer(state, -1);
-            // Write userdata just before attaching metatable with `__gc` metamethod
-            #[cfg(not(feature = "luau"))]
-            std::ptr::write(ud_ptr as _, UserDataCell::new(data));
-            ffi::lua_setmetatable(state, -2);
-            let ud = AnyUserData(lua.pop_ref());
-            lua.register_raw_userdata_metatable(mt_ptr, None);
+    /// Enables audit of values created through this scope that are still reachable from Lua
+    /// when they are invalidated.
+    ///
+    /// This is a diagnostic mode for tracking down "destructed userdata" or "destructed
+    /// callback" errors. When the scope ends (or [`Scope::close`] is called), Lua globals and
+    /// the registry are traversed, including nested tables and their metatables, and the
+    /// callback is called with the list of scoped values found (if any).
+    ///
+    /// The traversal visits every reachable table, so it should not be enabled in production.
+    ///
+    /// [`Scope::close`]: #method.close
+    pub fn set_leak_audit<F>(&self, callback: F)
+    where
+        F: Fn(&[ScopeLeak]) + 'scope,
+    {
+        *self.state.leak_audit.borrow_mut() = Some(Box::new(callback));
+    }
 
-            #[cfg(any(feature = "lua51", feature = "luajit"))]
-            let newtable = lua.create_table()?;
-            let destructor: DestructorCallback = Box::new(move |ud| {
-                let state = ud.lua.state();
-                let _sg = StackGuard::new(state);
-                assert_stack(state, 2);
+    /// Ends the scope early, invalidating all values created through it without waiting for the
+    /// [`Scope::child`] call to return.
+    ///
+    /// Before invalidating anything, checks that none of the scoped userdata are currently
+    /// borrowed, and returns [`Error::UserDataBorrowMutError`] otherwise. In this case the values
+    /// are invalidated when the [`Scope::child`] call returns.
+    ///
+    /// [`Scope::child`]: #method.child
+    pub fn close(self) -> Result<()> {
+        for (r, _, check) in self.state.destructors.borrow().iter() {
+            if let Some(check) = check {
+                check(r)?;
+            }
+        }
+        self.state.ended.set(true);
+        drop(self.invalidate());
+        Ok(())
+    }
 
-                // Check that userdata is valid (very likely)
-                if ud.lua.push_userdata_ref(&ud).is_err() {
-                    return vec![];
-                }
+    // Runs all destructors and clears scoped registry values.
+    // Returns Rust values that must be dropped after all scoped Lua values are invalidated.
+    fn invalidate(&self) -> Vec<Box<dyn Any>> {
+        if let Some(callback) = &*self.state.leak_audit.borrow() {
+            let leaks = self.find_leaks();
+            if !leaks.is_empty() {
+                callback(&leaks);
+            }
+        }
 
-                // Deregister metatable
-                ffi::lua_getmetatable(state, -1);
-                let mt_ptr = ffi::lua_topointer(state, -1);
-                ffi::lua_pop(state, 1);
-                ud.lua.deregister_raw_userdata_metatable(mt_ptr);
+        // All destructors are non-panicking, so this is fine
+        let destructors = mem::take(&mut *self.state.destructors.borrow_mut());
+        let to_drop = destructors
+            .into_iter()
+            .flat_map(|(r, dest, _)| dest(r))
+            .collect::<Vec<_>>();
 
-                // Clear associated user values
-                #[cfg(feature = "lua54")]
-                for i in 1..=USER_VALUE_MAXSLOT {
-                    ffi::lua_pushnil(state);
-                    ffi::lua_setiuservalue(state, -2, i as c_int);
-                }
-                #[cfg(any(feature = "lua53", feature = "lua52", feature = "luau"))]
-                {
+        // Replace registry values with `nil` but keep the slots, they are still owned by the keys
+        let registry_ids = mem::take(&mut *self.state.registry_ids.borrow_mut());
+        if !registry_ids.is_empty() {
+            let state = self.lua.state();
+            unsafe {
+                let _sg = StackGuard::new(state);
+                assert_stack(state, 1);
+                for id in registry_ids {
                     ffi::lua_pushnil(state);
-                    ffi::lua_setuservalue(state, -2);
-                }
-                #[cfg(any(feature = "lua51", feature = "luajit"))]
-                {
-                    ud.lua.push_ref(&newtable.0);
-                    ffi::lua_setuservalue(state, -2);
+                    ffi::lua_rawseti(state, ffi::LUA_REGISTRYINDEX, id as Integer);
                 }
+            }
+        }
 
-                // A hack to drop non-static `T`
-                unsafe fn seal<T>(t: T) -> Box<dyn FnOnce() + 'static> {
-                    let f: Box<dyn FnOnce()> = Box::new(move || drop(t));
-                    mem::transmute(f)
-                }
+        to_drop
+    }
 
-                let ud = take_userdata::<UserDataCell<T>>(state);
-                vec![Box::new(seal(ud))]
-            });
-            self.destructors
-                .borrow_mut()
-                .push((ud.0.clone(), destructor));
+    // Finds scoped values that are reachable from globals or the registry
+    fn find_leaks(&self) -> Vec<ScopeLeak> {
+        let lua = &self.lua;
+        let ref_thread = lua.ref_thread();
+        let scoped = (self.state.destructors)
+            .borrow()
+            .iter()
+            .map(|(r, _, _)| unsafe {
+                let ptr = ffi::lua_topointer(ref_thread, r.index());
+                let type_name = match ffi::lua_type(ref_thread, r.index()) {
+                    ffi::LUA_TFUNCTION => "function",
+                    ffi::LUA_TTABLE => "table",
+                    _ => "userdata",
+                };
+                (ptr, type_name)
+            })
+            .collect::<HashMap<_, _>>();
+        if scoped.is_empty() {
+            return Vec::new();
+        }
 
-            Ok(ud)
+        let registry = unsafe {
+            let state = lua.state();
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 1);
+            ffi::lua_pushvalue(state, ffi::LUA_REGISTRYINDEX);
+            Table(lua.pop_ref())
+        };
+        let registry_ids = self.state.registry_ids.borrow();
+
+        let mut leaks = Vec::new();
+        let mut visited = HashSet::new();
+        // Globals go last to be visited first (registry also refers to them)
+        let mut queue = vec![
+            (registry, "registry".to_string()),
+            (
+                lua.globals_storage().unwrap_or_else(|_| lua.globals()),
+                "_G".to_string(),
+            ),
+        ];
+        while let Some((table, path)) = queue.pop() {
+            if !visited.insert(table.to_pointer()) {
+                continue;
+            }
+            let is_registry = path == "registry";
+            if let Some(mt) = table.get_metatable() {
+                queue.push((mt, format!("getmetatable({path})")));
+            }
+            for (key, value) in table.pairs::<Value, Value>().flatten() {
+                let path = match &key {
+                    // Values of scoped registry keys are cleared on scope drop
+                    Value::Integer(i) if is_registry && registry_ids.contains(&(*i as c_int)) => {
+                        continue
+                    }
+                    Value::String(s) => format!("{path}.{}", s.to_string_lossy()),
+                    Value::Integer(i) => format!("{path}[{i}]"),
+                    Value::Number(n) => format!("{path}[{n}]"),
+                    key => format!("{path}[<{}>]", key.type_name()),
+                };
+                for v in [&key, &value] {
+                    if let Some(&type_name) = scoped.get(&v.to_pointer()) {
+                        let path = path.clone();
+                        leaks.push(ScopeLeak { type_name, path });
+                    }
+                }
+                if let Value::Table(t) = value {
+                    queue.push((t, path));
+                }
+            }
         }
+        leaks
     }
 
     // Unsafe, because the callback can improperly capture any value with 'callback scope, such as
@@ -528,30 +828,177 @@ impl<'scope> Scope<'scope> {
 
             vec![Box::new(ud)]
         });
-        self.destructors
-            .borrow_mut()
-            .push((f.0.clone(), destructor));
+        self.push_destructor(f.0.clone(), destructor, None)?;
 
         Ok(f)
     }
 }
 
-impl<'scope> Drop for Scope<'scope> {
-    fn drop(&mut self) {
-        // We separate the action of invalidating the userdata in Lua and actually dropping the
-        // userdata type into two phases. This is so that, in the event a userdata drop panics, we
-        // can be sure that all of the userdata in Lua is actually invalidated.
+// Marker type to identify cached metatables of non-'static userdata
+struct NonStaticTag<Tag>(PhantomData<Tag>);
+
+// Allocates memory for non-'static userdata `T` and pushes it onto the stack.
+// The memory is not initialized.
+#[cfg(not(feature = "luau"))]
+unsafe fn new_nonstatic_userdata<T>(state: *mut ffi::lua_State) -> Result<*const UserDataCell<T>> {
+    protect_lua!(state, 0, 1, |state| {
+        // Reserve the same number of user value slots as for regular userdata
+        #[cfg(feature = "lua54")]
+        let ud = ffi::lua_newuserdatauv(
+            state,
+            mem::size_of::<UserDataCell<T>>(),
+            USER_VALUE_MAXSLOT as c_int,
+        );
+        #[cfg(not(feature = "lua54"))]
+        let ud = ffi::lua_newuserdata(state, mem::size_of::<UserDataCell<T>>());
+
+        // Set empty environment for Lua 5.1
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        {
+            ffi::lua_newtable(state);
+            ffi::lua_setuservalue(state, -2);
+        }
 
-        // All destructors are non-panicking, so this is fine
-        let to_drop = self
-            .destructors
-            .get_mut()
-            .drain(..)
-            .flat_map(|(r, dest)| dest(r))
-            .collect::<Vec<_>>();
+        ud as *const UserDataCell<T>
+    })
+}
 
-        drop(to_drop);
+// Pushes a metatable for non-'static userdata `T` onto the stack.
+// Methods receiving the userdata use `check_ud_type` to get it from the first argument,
+// callbacks are turned into functions by `create_callback`.
+// Uses 13 stack spaces, does not call checkstack.
+unsafe fn push_nonstatic_metatable<'scope, T: UserData + 'scope>(
+    lua: &Lua,
+    check_ud_type: impl Fn(&Lua, Option<Value>) -> Result<*const UserDataCell<T>> + Copy + 'scope,
+    mut create_callback: impl FnMut(Callback<'scope>) -> Result<Function>,
+) -> Result<()> {
+    // 'callback outliving 'scope is a lie to make the types work out, required due to the
+    // inability to work with the more correct callback type that is universally quantified over
+    // 'lua. This is safe though, because `UserData::add_methods` does not get to pick the 'lua
+    // lifetime, so none of the static methods UserData types can add can possibly capture
+    // parameters.
+    let mut wrap_method = |method: NonStaticMethod<T>| -> Result<Function> {
+        let f: Callback<'scope> = match method {
+            NonStaticMethod::Method(method) => Box::new(move |lua: Lua, mut args: MultiValue| {
+                let data = &*check_ud_type(&lua, args.pop_front())?;
+                let data = data.try_borrow()?;
+                method(lua, &*data, args)
+            }),
+            NonStaticMethod::MethodMut(method) => {
+                let method = RefCell::new(method);
+                Box::new(move |lua: Lua, mut args: MultiValue| {
+                    let data = &*check_ud_type(&lua, args.pop_front())?;
+                    let mut method = method
+                        .try_borrow_mut()
+                        .map_err(|_| Error::RecursiveMutCallback)?;
+                    let mut data = data.try_borrow_mut()?;
+                    (*method)(lua, &mut *data, args)
+                })
+            }
+            NonStaticMethod::Function(function) => function,
+            NonStaticMethod::FunctionMut(function) => {
+                let function = RefCell::new(function);
+                Box::new(move |lua, args| {
+                    (*function
+                        .try_borrow_mut()
+                        .map_err(|_| Error::RecursiveMutCallback)?)(lua, args)
+                })
+            }
+        };
+        create_callback(f)
+    };
+
+    let mut ud_fields = NonStaticUserDataFields::default();
+    let mut ud_methods = NonStaticUserDataMethods::default();
+    T::add_fields(&mut ud_fields);
+    T::add_methods(&mut ud_methods);
+
+    let state = lua.state();
+
+    // Prepare metatable, add meta methods first and then meta fields
+    let meta_methods_nrec = ud_methods.meta_methods.len() + ud_fields.meta_fields.len() + 1;
+    push_table(state, 0, meta_methods_nrec as c_int, true)?;
+
+    for (k, m) in ud_methods.meta_methods {
+        lua.push_value(Value::Function(wrap_method(m)?))?;
+        rawset_field(state, -2, MetaMethod::validate(&k)?)?;
+    }
+    for (k, f) in ud_fields.meta_fields {
+        lua.push_value(f(lua)?)?;
+        rawset_field(state, -2, MetaMethod::validate(&k)?)?;
+    }
+    let metatable_index = ffi::lua_absindex(state, -1);
+
+    let mut field_getters_index = None;
+    let field_getters_nrec = ud_fields.field_getters.len();
+    if field_getters_nrec > 0 {
+        push_table(state, 0, field_getters_nrec as c_int, true)?;
+        for (k, m) in ud_fields.field_getters {
+            lua.push_value(Value::Function(wrap_method(m)?))?;
+            rawset_field(state, -2, &k)?;
+        }
+        field_getters_index = Some(ffi::lua_absindex(state, -1));
     }
+
+    let mut field_setters_index = None;
+    let field_setters_nrec = ud_fields.field_setters.len();
+    if field_setters_nrec > 0 {
+        push_table(state, 0, field_setters_nrec as c_int, true)?;
+        for (k, m) in ud_fields.field_setters {
+            lua.push_value(Value::Function(wrap_method(m)?))?;
+            rawset_field(state, -2, &k)?;
+        }
+        field_setters_index = Some(ffi::lua_absindex(state, -1));
+    }
+
+    let mut methods_index = None;
+    let methods_nrec = ud_methods.methods.len() + ud_fields.fields.len();
+    if methods_nrec > 0 {
+        // Create table used for methods (and static fields) lookup
+        push_table(state, 0, methods_nrec as c_int, true)?;
+        for (k, f) in ud_fields.fields {
+            lua.push_value(f(lua)?)?;
+            rawset_field(state, -2, &k)?;
+        }
+        for (k, m) in ud_methods.methods {
+            lua.push_value(Value::Function(wrap_method(m)?))?;
+            rawset_field(state, -2, &k)?;
+        }
+        methods_index = Some(ffi::lua_absindex(state, -1));
+    }
+
+    init_userdata_metatable::<UserDataCell<T>>(
+        state,
+        metatable_index,
+        field_getters_index,
+        field_setters_index,
+        methods_index,
+    )?;
+
+    let count = field_getters_index.map(|_| 1).unwrap_or(0)
+        + field_setters_index.map(|_| 1).unwrap_or(0)
+        + methods_index.map(|_| 1).unwrap_or(0);
+    ffi::lua_pop(state, count);
+
+    Ok(())
+}
+
+// Checks that scoped userdata is not borrowed and can be safely invalidated
+fn userdata_borrow_check<T>() -> BorrowCheckCallback {
+    Box::new(|ud| unsafe {
+        let state = ud.lua.state();
+        let _sg = StackGuard::new(state);
+        assert_stack(state, 2);
+
+        if ud.lua.push_userdata_ref(ud).is_err() {
+            return Ok(());
+        }
+        let ud = get_userdata::<UserDataCell<T>>(state, -1);
+        match (*ud).is_borrowed() {
+            true => Err(Error::UserDataBorrowMutError),
+            false => Ok(()),
+        }
+    })
 }
 
 #[allow(clippy::type_complexity)]
@@ -607,14 +1054,44 @@ impl<T: UserData> UserDataMethods<T> for NonStaticUserDataMethods<T> {
         T: Clone,
         M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         // The panic should never happen as async non-static code wouldn't compile
-        // Non-static lifetime must be bounded to 'lua lifetime
         panic!("asynchronous methods are not supported for non-static userdata")
     }
 
+    #[cfg(feature = "async")]
+    fn add_async_method_mut<M, A, MR, R>(&mut self, _name: impl AsRef<str>, _method: M)
+    where
+        T: 'static,
+        M: Fn(Lua, UserDataRefMut<'static, T>, A) -> MR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLuaMulti,
+    {
+        // The future could outlive the scope, keeping the borrowed userdata alive
+        panic!("asynchronous methods are not supported for non-static userdata")
+    }
+
+    fn add_method_overload<M, A, R>(&mut self, _name: impl AsRef<str>, _method: M)
+    where
+        M: Fn(Lua, &T, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+    {
+        panic!("method overloading is not supported for non-static userdata")
+    }
+
+    fn add_function_overload<F, A, R>(&mut self, _name: impl AsRef<str>, _function: F)
+    where
+        F: Fn(Lua, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+    {
+        panic!("method overloading is not supported for non-static userdata")
+    }
+
     fn add_function<F, A, R>(&mut self, name: impl AsRef<str>, function: F)
     where
         F: Fn(Lua, A) -> Result<R> + MaybeSend + 'static,
@@ -644,11 +1121,10 @@ impl<T: UserData> UserDataMethods<T> for NonStaticUserDataMethods<T> {
     where
         F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         // The panic should never happen as async non-static code wouldn't compile
-        // Non-static lifetime must be bounded to 'lua lifetime
         panic!("asynchronous functions are not supported for non-static userdata")
     }
 
@@ -682,11 +1158,10 @@ impl<T: UserData> UserDataMethods<T> for NonStaticUserDataMethods<T> {
         T: Clone,
         M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         // The panic should never happen as async non-static code wouldn't compile
-        // Non-static lifetime must be bounded to 'lua lifetime
         panic!("asynchronous meta methods are not supported for non-static userdata")
     }
 
@@ -719,16 +1194,16 @@ impl<T: UserData> UserDataMethods<T> for NonStaticUserDataMethods<T> {
     where
         F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         // The panic should never happen as async non-static code wouldn't compile
-        // Non-static lifetime must be bounded to 'lua lifetime
         panic!("asynchronous meta functions are not supported for non-static userdata")
     }
 }
 
 struct NonStaticUserDataFields<T: UserData> {
+    fields: Vec<(String, FieldValue)>,
     field_getters: Vec<(String, NonStaticMethod<T>)>,
     field_setters: Vec<(String, NonStaticMethod<T>)>,
     #[allow(clippy::type_complexity)]
@@ -738,6 +1213,7 @@ struct NonStaticUserDataFields<T: UserData> {
 impl<T: UserData> Default for NonStaticUserDataFields<T> {
     fn default() -> NonStaticUserDataFields<T> {
         NonStaticUserDataFields {
+            fields: Vec::new(),
             field_getters: Vec::new(),
             field_setters: Vec::new(),
             meta_fields: Vec::new(),
@@ -746,6 +1222,28 @@ impl<T: UserData> Default for NonStaticUserDataFields<T> {
 }
 
 impl<T: UserData> UserDataFields<T> for NonStaticUserDataFields<T> {
+    fn add_field<V>(&mut self, name: impl AsRef<str>, value: V)
+    where
+        V: IntoLua + MaybeSend + 'static,
+    {
+        let name = name.as_ref().to_string();
+        self.fields
+            .push((name, Box::new(move |lua| value.into_lua(lua))));
+    }
+
+    fn add_field_from<R>(&mut self, name: impl AsRef<str>, accessor: fn(&T) -> &R)
+    where
+        R: IntoLua + Clone + 'static,
+    {
+        // `fn(&T) -> &R` is not 'static for non-'static `T`, so we erase the type here
+        let accessor = accessor as *const ();
+        let method = NonStaticMethod::Method(Box::new(move |lua, ud, _| {
+            let accessor = unsafe { mem::transmute::<*const (), fn(&T) -> &R>(accessor) };
+            accessor(ud).clone().into_lua_multi(&lua)
+        }));
+        self.field_getters.push((name.as_ref().into(), method));
+    }
+
     fn add_field_method_get<M, R>(&mut self, name: impl AsRef<str>, method: M)
     where
         M: Fn(Lua, &T) -> Result<R> + MaybeSend + 'static,
@@ -791,6 +1289,18 @@ impl<T: UserData> UserDataFields<T> for NonStaticUserDataFields<T> {
         self.field_setters.push((name.as_ref().into(), func));
     }
 
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    fn add_async_field_method_get<M, MR, R>(&mut self, _name: impl AsRef<str>, _method: M)
+    where
+        T: Clone,
+        M: Fn(Lua, T) -> MR + MaybeSend + 'static,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLua,
+    {
+        // The panic should never happen as async non-static code wouldn't compile
+        panic!("asynchronous field getters are not supported for non-static userdata")
+    }
+
     fn add_meta_field_with<F, R>(&mut self, name: impl AsRef<str>, f: F)
     where
         F: Fn(Lua) -> Result<R> + MaybeSend + 'static,
@@ -817,4 +1327,11 @@ impl<T: UserData> UserDataFields<T> for NonStaticUserDataFields<T> {
             }),
         ));
     }
+
+    fn on_change<F>(&mut self, _callback: F)
+    where
+        F: Fn(Lua, AnyUserData, &str, Value, Value) -> Result<()> + MaybeSend + 'static,
+    {
+        panic!("field change notifications are not supported for non-static userdata")
+    }
 }
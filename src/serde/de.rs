@@ -1,5 +1,7 @@
 use std::cell::RefCell;
 use std::convert::TryInto;
+#[cfg(not(feature = "luau"))]
+use std::iter;
 use std::os::raw::c_void;
 use std::rc::Rc;
 use std::string::String as StdString;
@@ -8,16 +10,63 @@ use rustc_hash::FxHashSet;
 use serde::de::{self, IntoDeserializer};
 
 use crate::error::{Error, Result};
+#[cfg(not(feature = "luau"))]
+use crate::function::Function;
+use crate::string::String;
 use crate::table::{Table, TablePairs, TableSequence};
 use crate::userdata::AnyUserData;
 use crate::value::Value;
 
+/// Key of the map holding a serialized function binary chunk.
+#[cfg(not(feature = "luau"))]
+pub(crate) const BYTECODE_KEY: &str = "__mlua_bytecode";
+
 /// A struct for deserializing Lua values into Rust values.
 #[derive(Debug)]
-pub struct Deserializer<'lua> {
-    value: Value<'lua>,
+pub struct Deserializer<'s> {
+    value: Value,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<&'s StringPool>,
+}
+
+/// A storage for Lua strings borrowed by values deserialized using
+/// [`LuaSerdeExt::from_value_borrowed`].
+///
+/// The pool is the borrow guard for deserialized values: strings are kept alive (pinned) until
+/// the pool is dropped, so values can reference their contents without copying, and the
+/// borrow checker ties their lifetime to the pool rather than to the [`Lua`] instance.
+///
+/// [`LuaSerdeExt::from_value_borrowed`]: crate::LuaSerdeExt::from_value_borrowed
+/// [`Lua`]: crate::Lua
+#[derive(Debug, Default)]
+pub struct StringPool {
+    strings: RefCell<Vec<String>>,
+}
+
+impl StringPool {
+    /// Creates a new empty pool.
+    pub fn new() -> Self {
+        StringPool::default()
+    }
+
+    /// Returns the number of strings held by the pool.
+    pub fn len(&self) -> usize {
+        self.strings.borrow().len()
+    }
+
+    /// Returns `true` if the pool holds no strings.
+    pub fn is_empty(&self) -> bool {
+        self.strings.borrow().is_empty()
+    }
+
+    fn pin(&self, s: String) -> &[u8] {
+        let bytes = s.as_bytes() as *const [u8];
+        self.strings.borrow_mut().push(s);
+        // SAFETY: Lua strings are never moved by the garbage collector, and the string
+        // is referenced by the pool until the pool is dropped.
+        unsafe { &*bytes }
+    }
 }
 
 /// A struct with options to change default deserializer behavior.
@@ -42,6 +91,21 @@ pub struct Options {
     ///
     /// Default: **true**
     pub deny_recursive_tables: bool,
+
+    /// If true, Lua functions are serialized as a map `{__mlua_bytecode = <bytes>}` holding
+    /// the function binary chunk, which can be loaded back by the serializer when its
+    /// [`function_bytecode`] option is enabled.
+    ///
+    /// Only Lua functions without upvalues (except `_ENV`) can be serialized,
+    /// otherwise a [`FromLuaConversionError`] is returned.
+    ///
+    /// Default: **false**
+    ///
+    /// [`function_bytecode`]: crate::SerializeOptions::function_bytecode
+    /// [`FromLuaConversionError`]: crate::Error::FromLuaConversionError
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub function_bytecode: bool,
 }
 
 impl Default for Options {
@@ -56,6 +120,8 @@ impl Options {
         Options {
             deny_unsupported_types: true,
             deny_recursive_tables: true,
+            #[cfg(not(feature = "luau"))]
+            function_bytecode: false,
         }
     }
 
@@ -76,37 +142,57 @@ impl Options {
         self.deny_recursive_tables = enabled;
         self
     }
+
+    /// Sets [`function_bytecode`] option.
+    ///
+    /// [`function_bytecode`]: #structfield.function_bytecode
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    #[must_use]
+    pub const fn function_bytecode(mut self, enabled: bool) -> Self {
+        self.function_bytecode = enabled;
+        self
+    }
 }
 
-impl<'lua> Deserializer<'lua> {
+impl<'s> Deserializer<'s> {
     /// Creates a new Lua Deserializer for the `Value`.
-    pub fn new(value: Value<'lua>) -> Self {
+    pub fn new(value: Value) -> Self {
         Self::new_with_options(value, Options::default())
     }
 
     /// Creates a new Lua Deserializer for the `Value` with custom options.
-    pub fn new_with_options(value: Value<'lua>, options: Options) -> Self {
+    pub fn new_with_options(value: Value, options: Options) -> Self {
         Deserializer {
             value,
             options,
             visited: Rc::new(RefCell::new(FxHashSet::default())),
+            strings: None,
         }
     }
 
+    // Strings are borrowed from the pool, which acts as a guard keeping them alive for `'s`
+    pub(crate) fn with_string_pool(mut self, strings: &'s StringPool) -> Self {
+        self.strings = Some(strings);
+        self
+    }
+
     fn from_parts(
-        value: Value<'lua>,
+        value: Value,
         options: Options,
         visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+        strings: Option<&'s StringPool>,
     ) -> Self {
         Deserializer {
             value,
             options,
             visited,
+            strings,
         }
     }
 }
 
-impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
+impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
     type Error = Error;
 
     #[inline]
@@ -125,16 +211,33 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
             Value::Number(n) => visitor.visit_f64(n.into()),
             #[cfg(feature = "luau")]
             Value::Vector(_, _, _) => self.deserialize_seq(visitor),
-            Value::String(s) => match s.to_str() {
-                Ok(s) => visitor.visit_str(s),
-                Err(_) => visitor.visit_bytes(s.as_bytes()),
+            Value::String(s) => match self.strings {
+                Some(strings) => {
+                    let bytes = strings.pin(s);
+                    match std::str::from_utf8(bytes) {
+                        Ok(s) => visitor.visit_borrowed_str(s),
+                        Err(_) => visitor.visit_borrowed_bytes(bytes),
+                    }
+                }
+                None => match s.to_str() {
+                    Ok(s) => visitor.visit_str(s),
+                    Err(_) => visitor.visit_bytes(s.as_bytes()),
+                },
             },
+            #[cfg(feature = "luau")]
+            Value::Buffer(buf) => visitor.visit_bytes(buf.as_slice()),
             Value::Table(ref t) if t.raw_len() > 0 || t.is_array() => self.deserialize_seq(visitor),
             Value::Table(_) => self.deserialize_map(visitor),
             Value::LightUserData(ud) if ud.0.is_null() => visitor.visit_none(),
             Value::UserData(ud) if ud.is_serializable() => {
                 serde_userdata(ud, |value| value.deserialize_any(visitor))
             }
+            #[cfg(not(feature = "luau"))]
+            Value::Function(ref f) if self.options.function_bytecode => {
+                let bytecode = function_bytecode(f)?;
+                let entries = iter::once((BYTECODE_KEY, bytecode.as_slice()));
+                visitor.visit_map(de::value::MapDeserializer::new(entries))
+            }
             Value::Function(_)
             | Value::Thread(_)
             | Value::UserData(_)
@@ -213,6 +316,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
             value,
             options: self.options,
             visited: self.visited,
+            strings: self.strings,
         })
     }
 
@@ -229,6 +333,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
                     next: 0,
                     options: self.options,
                     visited: self.visited,
+                    strings: self.strings,
                 };
                 visitor.visit_seq(&mut deserializer)
             }
@@ -240,6 +345,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
                     seq: t.raw_sequence_values(),
                     options: self.options,
                     visited: self.visited,
+                    strings: self.strings,
                 };
                 let seq = visitor.visit_seq(&mut deserializer)?;
                 if deserializer.seq.count() == 0 {
@@ -296,6 +402,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
                     value: None,
                     options: self.options,
                     visited: self.visited,
+                    strings: self.strings,
                     processed: 0,
                 };
                 let map = visitor.visit_map(&mut deserializer)?;
@@ -351,13 +458,14 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
     }
 }
 
-struct SeqDeserializer<'lua> {
-    seq: TableSequence<'lua, Value<'lua>>,
+struct SeqDeserializer<'s> {
+    seq: TableSequence<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<&'s StringPool>,
 }
 
-impl<'lua, 'de> de::SeqAccess<'de> for SeqDeserializer<'lua> {
+impl<'de> de::SeqAccess<'de> for SeqDeserializer<'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -372,7 +480,8 @@ impl<'lua, 'de> de::SeqAccess<'de> for SeqDeserializer<'lua> {
                         continue;
                     }
                     let visited = Rc::clone(&self.visited);
-                    let deserializer = Deserializer::from_parts(value, self.options, visited);
+                    let deserializer =
+                        Deserializer::from_parts(value, self.options, visited, self.strings);
                     return seed.deserialize(deserializer).map(Some);
                 }
                 None => return Ok(None),
@@ -389,15 +498,16 @@ impl<'lua, 'de> de::SeqAccess<'de> for SeqDeserializer<'lua> {
 }
 
 #[cfg(feature = "luau")]
-struct VecDeserializer {
+struct VecDeserializer<'s> {
     vec: [f32; 3],
     next: usize,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<&'s StringPool>,
 }
 
 #[cfg(feature = "luau")]
-impl<'de> de::SeqAccess<'de> for VecDeserializer {
+impl<'de> de::SeqAccess<'de> for VecDeserializer<'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -408,8 +518,12 @@ impl<'de> de::SeqAccess<'de> for VecDeserializer {
             Some(&n) => {
                 self.next += 1;
                 let visited = Rc::clone(&self.visited);
-                let deserializer =
-                    Deserializer::from_parts(Value::Number(n as _), self.options, visited);
+                let deserializer = Deserializer::from_parts(
+                    Value::Number(n as _),
+                    self.options,
+                    visited,
+                    self.strings,
+                );
                 seed.deserialize(deserializer).map(Some)
             }
             None => Ok(None),
@@ -421,15 +535,16 @@ impl<'de> de::SeqAccess<'de> for VecDeserializer {
     }
 }
 
-struct MapDeserializer<'lua> {
-    pairs: TablePairs<'lua, Value<'lua>, Value<'lua>>,
-    value: Option<Value<'lua>>,
+struct MapDeserializer<'s> {
+    pairs: TablePairs<Value, Value>,
+    value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<&'s StringPool>,
     processed: usize,
 }
 
-impl<'lua, 'de> de::MapAccess<'de> for MapDeserializer<'lua> {
+impl<'de> de::MapAccess<'de> for MapDeserializer<'de> {
     type Error = Error;
 
     fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -448,7 +563,12 @@ impl<'lua, 'de> de::MapAccess<'de> for MapDeserializer<'lua> {
                     self.processed += 1;
                     self.value = Some(value);
                     let visited = Rc::clone(&self.visited);
-                    let key_de = Deserializer::from_parts(key, self.options, visited);
+                    let key_de = MapKeyDeserializer(Deserializer::from_parts(
+                        key,
+                        self.options,
+                        visited,
+                        self.strings,
+                    ));
                     return seed.deserialize(key_de).map(Some);
                 }
                 None => return Ok(None),
@@ -463,7 +583,12 @@ impl<'lua, 'de> de::MapAccess<'de> for MapDeserializer<'lua> {
         match self.value.take() {
             Some(value) => {
                 let visited = Rc::clone(&self.visited);
-                seed.deserialize(Deserializer::from_parts(value, self.options, visited))
+                seed.deserialize(Deserializer::from_parts(
+                    value,
+                    self.options,
+                    visited,
+                    self.strings,
+                ))
             }
             None => Err(de::Error::custom("value is missing")),
         }
@@ -477,16 +602,83 @@ impl<'lua, 'de> de::MapAccess<'de> for MapDeserializer<'lua> {
     }
 }
 
-struct EnumDeserializer<'lua> {
+// Deserializer for map keys that converts integer keys to strings when a string is expected
+// (eg. object keys in JSON)
+struct MapKeyDeserializer<'s>(Deserializer<'s>);
+
+macro_rules! forward_to_deserializer {
+    ($($name:ident ( $($arg:ident: $ty:ty),* );)*) => {
+        $(
+            #[inline]
+            fn $name<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                serde::Deserializer::$name(self.0, $($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> serde::Deserializer<'de> for MapKeyDeserializer<'de> {
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.0.value {
+            Value::Integer(i) => visitor.visit_string(i.to_string()),
+            _ => serde::Deserializer::deserialize_str(self.0, visitor),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    #[inline]
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    forward_to_deserializer! {
+        deserialize_any();
+        deserialize_option();
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_newtype_struct(name: &'static str);
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes
+        byte_buf unit unit_struct ignored_any
+    }
+}
+
+struct EnumDeserializer<'s> {
     variant: StdString,
-    value: Option<Value<'lua>>,
+    value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<&'s StringPool>,
 }
 
-impl<'lua, 'de> de::EnumAccess<'de> for EnumDeserializer<'lua> {
+impl<'de> de::EnumAccess<'de> for EnumDeserializer<'de> {
     type Error = Error;
-    type Variant = VariantDeserializer<'lua>;
+    type Variant = VariantDeserializer<'de>;
 
     fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Self::Variant)>
     where
@@ -497,18 +689,20 @@ impl<'lua, 'de> de::EnumAccess<'de> for EnumDeserializer<'lua> {
             value: self.value,
             options: self.options,
             visited: self.visited,
+            strings: self.strings,
         };
         seed.deserialize(variant).map(|v| (v, variant_access))
     }
 }
 
-struct VariantDeserializer<'lua> {
-    value: Option<Value<'lua>>,
+struct VariantDeserializer<'s> {
+    value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<&'s StringPool>,
 }
 
-impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
+impl<'de> de::VariantAccess<'de> for VariantDeserializer<'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -526,9 +720,12 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
         T: de::DeserializeSeed<'de>,
     {
         match self.value {
-            Some(value) => {
-                seed.deserialize(Deserializer::from_parts(value, self.options, self.visited))
-            }
+            Some(value) => seed.deserialize(Deserializer::from_parts(
+                value,
+                self.options,
+                self.visited,
+                self.strings,
+            )),
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"newtype variant",
@@ -542,7 +739,7 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
     {
         match self.value {
             Some(value) => serde::Deserializer::deserialize_seq(
-                Deserializer::from_parts(value, self.options, self.visited),
+                Deserializer::from_parts(value, self.options, self.visited, self.strings),
                 visitor,
             ),
             None => Err(de::Error::invalid_type(
@@ -558,7 +755,7 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
     {
         match self.value {
             Some(value) => serde::Deserializer::deserialize_map(
-                Deserializer::from_parts(value, self.options, self.visited),
+                Deserializer::from_parts(value, self.options, self.visited, self.strings),
                 visitor,
             ),
             None => Err(de::Error::invalid_type(
@@ -609,6 +806,8 @@ fn check_value_if_skip(
             }
         }
         Value::UserData(ud) if ud.is_serializable() => {}
+        #[cfg(not(feature = "luau"))]
+        Value::Function(_) if options.function_bytecode => {}
         Value::Function(_)
         | Value::Thread(_)
         | Value::UserData(_)
@@ -623,6 +822,33 @@ fn check_value_if_skip(
     Ok(false) // do not skip
 }
 
+// Dumps a Lua function without upvalues (except `_ENV`) to a binary chunk
+#[cfg(not(feature = "luau"))]
+fn function_bytecode(f: &Function) -> Result<Vec<u8>> {
+    let error = |message: &str| Error::FromLuaConversionError {
+        from: "function",
+        to: "bytecode",
+        message: Some(message.to_string()),
+    };
+
+    if f.info().what.as_deref() == Some(b"C") {
+        return Err(error("C functions cannot be serialized"));
+    }
+    let upvalues = (f.upvalues().into_iter())
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    let upvalues = match upvalues.split_first() {
+        // `_ENV` is set to the globals table when the chunk is loaded
+        Some((env, rest)) if env == b"_ENV" => rest,
+        _ => &upvalues[..],
+    };
+    if !upvalues.is_empty() {
+        return Err(error("functions with upvalues cannot be serialized"));
+    }
+    Ok(f.dump(false))
+}
+
 fn serde_userdata<V>(
     ud: AnyUserData,
     f: impl FnOnce(serde_value::Value) -> std::result::Result<V, serde_value::DeserializerError>,
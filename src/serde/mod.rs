@@ -3,9 +3,9 @@
 use std::os::raw::c_void;
 use std::ptr;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::ffi;
 use crate::lua::Lua;
 use crate::private::Sealed;
@@ -16,7 +16,7 @@ use crate::value::Value;
 
 /// Trait for serializing/deserializing Lua values using Serde.
 #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
-pub trait LuaSerdeExt<'lua>: Sealed {
+pub trait LuaSerdeExt: Sealed {
     /// A special value (lightuserdata) to encode/decode optional (none) values.
     ///
     /// Requires `feature = "serialize"`
@@ -38,7 +38,7 @@ pub trait LuaSerdeExt<'lua>: Sealed {
     ///     Ok(())
     /// }
     /// ```
-    fn null(&'lua self) -> Value<'lua>;
+    fn null(&self) -> Value;
 
     /// A metatable attachable to a Lua table to systematically encode it as Array (instead of Map).
     /// As result, encoded Array will contain only sequence part of the table, with the same length
@@ -69,7 +69,30 @@ pub trait LuaSerdeExt<'lua>: Sealed {
     ///     Ok(())
     /// }
     /// ```
-    fn array_metatable(&'lua self) -> Table<'lua>;
+    fn array_metatable(&self) -> Table;
+
+    /// Creates a new empty Lua table with the [`array_metatable`] attached.
+    ///
+    /// Unlike a plain empty table (encoded as Map), it is encoded as an empty Array.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`array_metatable`]: #tymethod.array_metatable
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt};
+    /// use serde_json::Value as JsonValue;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let j: JsonValue = lua.from_value(mlua::Value::Table(lua.empty_array()?))?;
+    ///     assert_eq!(j.to_string(), "[]");
+    ///     Ok(())
+    /// }
+    /// ```
+    fn empty_array(&self) -> Result<Table>;
 
     /// Converts `T` into a [`Value`] instance.
     ///
@@ -102,7 +125,7 @@ pub trait LuaSerdeExt<'lua>: Sealed {
     ///     "#).exec()
     /// }
     /// ```
-    fn to_value<T: Serialize + ?Sized>(&'lua self, t: &T) -> Result<Value<'lua>>;
+    fn to_value<T: Serialize + ?Sized>(&self, t: &T) -> Result<Value>;
 
     /// Converts `T` into a [`Value`] instance with options.
     ///
@@ -127,10 +150,74 @@ pub trait LuaSerdeExt<'lua>: Sealed {
     ///     "#).exec()
     /// }
     /// ```
-    fn to_value_with<T>(&'lua self, t: &T, options: ser::Options) -> Result<Value<'lua>>
+    fn to_value_with<T>(&self, t: &T, options: ser::Options) -> Result<Value>
     where
         T: Serialize + ?Sized;
 
+    /// Converts `T` into a Lua [`Table`].
+    ///
+    /// Similar to [`to_value`], but returns an error if `T` is not serialized as a table
+    /// (eg. a struct, map or sequence). Fields are written directly to the resulting table as
+    /// they are serialized.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`Table`]: crate::Table
+    /// [`to_value`]: #tymethod.to_value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Config {
+    ///     width: u32,
+    ///     height: u32,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let config = lua.to_table(&Config { width: 800, height: 600 })?;
+    ///     assert_eq!(config.get::<_, u32>("width")?, 800);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn to_table<T: Serialize + ?Sized>(&self, t: &T) -> Result<Table>;
+
+    /// Deserializes a Lua [`Table`] into any serde deserializable object.
+    ///
+    /// The table is read directly while deserializing, without converting it to intermediate
+    /// values first.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`Table`]: crate::Table
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt, Table};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     width: u32,
+    ///     height: u32,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let table: Table = lua.load("{width = 800, height = 600}").eval()?;
+    ///     let config: Config = lua.from_table(&table)?;
+    ///     assert_eq!((config.width, config.height), (800, 600));
+    ///     Ok(())
+    /// }
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    fn from_table<T: DeserializeOwned>(&self, table: &Table) -> Result<T>;
+
     /// Deserializes a [`Value`] into any serde deserializable object.
     ///
     /// Requires `feature = "serialize"`
@@ -160,7 +247,46 @@ pub trait LuaSerdeExt<'lua>: Sealed {
     /// }
     /// ```
     #[allow(clippy::wrong_self_convention)]
-    fn from_value<T: Deserialize<'lua>>(&'lua self, value: Value<'lua>) -> Result<T>;
+    fn from_value<T: DeserializeOwned>(&self, value: Value) -> Result<T>;
+
+    /// Deserializes a [`Value`] into a serde deserializable object that borrows strings.
+    ///
+    /// Unlike [`from_value`], string fields can be deserialized into `&str` and `&[u8]` (or
+    /// `Cow` with `#[serde(borrow)]`) without copying. The Lua strings referenced by the result
+    /// are kept alive by `strings` pool.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`from_value`]: #tymethod.from_value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt, StringPool};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Page<'a> {
+    ///     title: &'a str,
+    ///     body: &'a str,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let strings = StringPool::new();
+    ///     let val = lua.load(r#"{title = "Hello", body = string.rep("a", 1000)}"#).eval()?;
+    ///     let page: Page = lua.from_value_borrowed(val, &strings)?;
+    ///     assert_eq!(page.title, "Hello");
+    ///     assert_eq!(page.body.len(), 1000);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    fn from_value_borrowed<'de, T: Deserialize<'de>>(
+        &self,
+        value: Value,
+        strings: &'de de::StringPool,
+    ) -> Result<T>;
 
     /// Deserializes a [`Value`] into any serde deserializable object with options.
     ///
@@ -192,49 +318,80 @@ pub trait LuaSerdeExt<'lua>: Sealed {
     /// }
     /// ```
     #[allow(clippy::wrong_self_convention)]
-    fn from_value_with<T: Deserialize<'lua>>(
-        &'lua self,
-        value: Value<'lua>,
-        options: de::Options,
-    ) -> Result<T>;
+    fn from_value_with<T: DeserializeOwned>(&self, value: Value, options: de::Options)
+        -> Result<T>;
 }
 
-impl<'lua> LuaSerdeExt<'lua> for Lua {
-    fn null(&'lua self) -> Value<'lua> {
+impl LuaSerdeExt for Lua {
+    fn null(&self) -> Value {
         Value::LightUserData(LightUserData(ptr::null_mut()))
     }
 
-    fn array_metatable(&'lua self) -> Table<'lua> {
+    fn array_metatable(&self) -> Table {
         unsafe {
             push_array_metatable(self.ref_thread());
             Table(self.pop_ref_thread())
         }
     }
 
-    fn to_value<T>(&'lua self, t: &T) -> Result<Value<'lua>>
+    fn empty_array(&self) -> Result<Table> {
+        let table = self.create_table()?;
+        table.set_metatable(Some(self.array_metatable()));
+        Ok(table)
+    }
+
+    fn to_value<T>(&self, t: &T) -> Result<Value>
     where
         T: Serialize + ?Sized,
     {
         t.serialize(ser::Serializer::new(self))
     }
 
-    fn to_value_with<T>(&'lua self, t: &T, options: ser::Options) -> Result<Value<'lua>>
+    fn to_value_with<T>(&self, t: &T, options: ser::Options) -> Result<Value>
     where
         T: Serialize + ?Sized,
     {
         t.serialize(ser::Serializer::new_with_options(self, options))
     }
 
-    fn from_value<T>(&'lua self, value: Value<'lua>) -> Result<T>
+    fn to_table<T>(&self, t: &T) -> Result<Table>
     where
-        T: Deserialize<'lua>,
+        T: Serialize + ?Sized,
+    {
+        match self.to_value(t)? {
+            Value::Table(table) => Ok(table),
+            value => Err(Error::ToLuaConversionError {
+                from: value.type_name(),
+                to: "table",
+                message: Some("value is not serialized as a table".to_string()),
+            }),
+        }
+    }
+
+    fn from_table<T>(&self, table: &Table) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        T::deserialize(de::Deserializer::new(Value::Table(table.clone())))
+    }
+
+    fn from_value<T>(&self, value: Value) -> Result<T>
+    where
+        T: DeserializeOwned,
     {
         T::deserialize(de::Deserializer::new(value))
     }
 
-    fn from_value_with<T>(&'lua self, value: Value<'lua>, options: de::Options) -> Result<T>
+    fn from_value_borrowed<'de, T>(&self, value: Value, strings: &'de de::StringPool) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        T::deserialize(de::Deserializer::new(value).with_string_pool(strings))
+    }
+
+    fn from_value_with<T>(&self, value: Value, options: de::Options) -> Result<T>
     where
-        T: Deserialize<'lua>,
+        T: DeserializeOwned,
     {
         T::deserialize(de::Deserializer::new_with_options(value, options))
     }
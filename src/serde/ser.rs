@@ -11,11 +11,13 @@ use crate::table::Table;
 use crate::types::Integer;
 use crate::util::{check_stack, StackGuard};
 use crate::value::{IntoLua, Value};
+#[cfg(not(feature = "luau"))]
+use {super::de::BYTECODE_KEY, crate::chunk::ChunkMode};
 
 /// A struct for serializing Rust values into Lua values.
 #[derive(Debug)]
-pub struct Serializer<'lua> {
-    lua: &'lua Lua,
+pub struct Serializer<'a> {
+    lua: &'a Lua,
     options: Options,
 }
 
@@ -48,6 +50,61 @@ pub struct Options {
     /// [`null`]: crate::LuaSerdeExt::null
     /// [`Nil`]: crate::Value::Nil
     pub serialize_unit_to_null: bool,
+
+    /// If true, map keys that are strings containing a decimal integer (eg. `"42"`) are
+    /// converted to Lua integers.
+    ///
+    /// Formats like JSON store integer keys as strings, this option restores them
+    /// when converting such data back to Lua.
+    ///
+    /// Default: **false**
+    pub detect_integer_keys: bool,
+
+    /// Representation of Rust enums (see [`EnumRepr`]).
+    ///
+    /// Default: **[`EnumRepr::ExternallyTagged`]**
+    pub enum_repr: EnumRepr,
+
+    /// If true, maps in the form `{__mlua_bytecode = <bytes>}` (produced by the deserializer
+    /// with the [`function_bytecode`] option enabled) are loaded back as Lua functions.
+    ///
+    /// Be aware, Lua does not check the consistency of the code inside binary chunks,
+    /// so this option must be enabled only for trusted input.
+    ///
+    /// Default: **false**
+    ///
+    /// [`function_bytecode`]: crate::DeserializeOptions::function_bytecode
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub function_bytecode: bool,
+}
+
+/// Representation of Rust enum variants in Lua.
+///
+/// Only affects serialization, the deserializer accepts externally tagged enums.
+/// Untagged and internally tagged enums can be deserialized using the corresponding
+/// `#[serde(...)]` attributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnumRepr {
+    /// Unit variants are serialized to strings, other variants to a table with a single
+    /// key (the variant name) mapped to the variant content, eg. `{Move = {x = 1, y = 2}}`.
+    ExternallyTagged,
+    /// The variant name is stored in the `tag` field of the variant content,
+    /// eg. `{type = "Move", x = 1, y = 2}`.
+    ///
+    /// Newtype variants must contain a value serialized to a table.
+    InternallyTagged {
+        /// Name of the field holding the variant name.
+        tag: &'static str,
+    },
+    /// Variants are serialized to their content without the variant name.
+    ///
+    /// Unit variants are serialized as unit type (see [`Options::serialize_unit_to_null`]).
+    Untagged,
+    /// Unit variants are serialized to strings, other variants to their content without the
+    /// variant name.
+    StringForUnit,
 }
 
 impl Default for Options {
@@ -63,6 +120,10 @@ impl Options {
             set_array_metatable: true,
             serialize_none_to_null: true,
             serialize_unit_to_null: true,
+            detect_integer_keys: false,
+            enum_repr: EnumRepr::ExternallyTagged,
+            #[cfg(not(feature = "luau"))]
+            function_bytecode: false,
         }
     }
 
@@ -92,16 +153,45 @@ impl Options {
         self.serialize_unit_to_null = enabled;
         self
     }
+
+    /// Sets [`detect_integer_keys`] option.
+    ///
+    /// [`detect_integer_keys`]: #structfield.detect_integer_keys
+    #[must_use]
+    pub const fn detect_integer_keys(mut self, enabled: bool) -> Self {
+        self.detect_integer_keys = enabled;
+        self
+    }
+
+    /// Sets [`enum_repr`] option.
+    ///
+    /// [`enum_repr`]: #structfield.enum_repr
+    #[must_use]
+    pub const fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.enum_repr = repr;
+        self
+    }
+
+    /// Sets [`function_bytecode`] option.
+    ///
+    /// [`function_bytecode`]: #structfield.function_bytecode
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    #[must_use]
+    pub const fn function_bytecode(mut self, enabled: bool) -> Self {
+        self.function_bytecode = enabled;
+        self
+    }
 }
 
-impl<'lua> Serializer<'lua> {
+impl<'a> Serializer<'a> {
     /// Creates a new Lua Serializer with default options.
-    pub fn new(lua: &'lua Lua) -> Self {
+    pub fn new(lua: &'a Lua) -> Self {
         Self::new_with_options(lua, Options::default())
     }
 
     /// Creates a new Lua Serializer with custom options.
-    pub fn new_with_options(lua: &'lua Lua, options: Options) -> Self {
+    pub fn new_with_options(lua: &'a Lua, options: Options) -> Self {
         Serializer { lua, options }
     }
 }
@@ -109,28 +199,28 @@ impl<'lua> Serializer<'lua> {
 macro_rules! lua_serialize_number {
     ($name:ident, $t:ty) => {
         #[inline]
-        fn $name(self, value: $t) -> Result<Value<'lua>> {
+        fn $name(self, value: $t) -> Result<Value> {
             value.into_lua(self.lua)
         }
     };
 }
 
-impl<'lua> ser::Serializer for Serializer<'lua> {
-    type Ok = Value<'lua>;
+impl<'a> ser::Serializer for Serializer<'a> {
+    type Ok = Value;
     type Error = Error;
 
     // Associated types for keeping track of additional state while serializing
     // compound data structures like sequences and maps.
-    type SerializeSeq = SerializeVec<'lua>;
-    type SerializeTuple = SerializeVec<'lua>;
-    type SerializeTupleStruct = SerializeVec<'lua>;
-    type SerializeTupleVariant = SerializeTupleVariant<'lua>;
-    type SerializeMap = SerializeMap<'lua>;
-    type SerializeStruct = SerializeMap<'lua>;
-    type SerializeStructVariant = SerializeStructVariant<'lua>;
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeStructVariant;
 
     #[inline]
-    fn serialize_bool(self, value: bool) -> Result<Value<'lua>> {
+    fn serialize_bool(self, value: bool) -> Result<Value> {
         Ok(Value::Boolean(value))
     }
 
@@ -149,22 +239,22 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
     lua_serialize_number!(serialize_f64, f64);
 
     #[inline]
-    fn serialize_char(self, value: char) -> Result<Value<'lua>> {
+    fn serialize_char(self, value: char) -> Result<Value> {
         self.serialize_str(&value.to_string())
     }
 
     #[inline]
-    fn serialize_str(self, value: &str) -> Result<Value<'lua>> {
+    fn serialize_str(self, value: &str) -> Result<Value> {
         self.lua.create_string(value).map(Value::String)
     }
 
     #[inline]
-    fn serialize_bytes(self, value: &[u8]) -> Result<Value<'lua>> {
+    fn serialize_bytes(self, value: &[u8]) -> Result<Value> {
         self.lua.create_string(value).map(Value::String)
     }
 
     #[inline]
-    fn serialize_none(self) -> Result<Value<'lua>> {
+    fn serialize_none(self) -> Result<Value> {
         if self.options.serialize_none_to_null {
             Ok(self.lua.null())
         } else {
@@ -173,7 +263,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
     }
 
     #[inline]
-    fn serialize_some<T>(self, value: &T) -> Result<Value<'lua>>
+    fn serialize_some<T>(self, value: &T) -> Result<Value>
     where
         T: Serialize + ?Sized,
     {
@@ -181,7 +271,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
     }
 
     #[inline]
-    fn serialize_unit(self) -> Result<Value<'lua>> {
+    fn serialize_unit(self) -> Result<Value> {
         if self.options.serialize_unit_to_null {
             Ok(self.lua.null())
         } else {
@@ -190,7 +280,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
     }
 
     #[inline]
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value<'lua>> {
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
         if self.options.serialize_unit_to_null {
             Ok(self.lua.null())
         } else {
@@ -204,12 +294,20 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
         _name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-    ) -> Result<Value<'lua>> {
-        self.serialize_str(variant)
+    ) -> Result<Value> {
+        match self.options.enum_repr {
+            EnumRepr::ExternallyTagged | EnumRepr::StringForUnit => self.serialize_str(variant),
+            EnumRepr::InternallyTagged { tag } => {
+                let table = self.lua.create_table()?;
+                table.raw_set(tag, variant)?;
+                Ok(Value::Table(table))
+            }
+            EnumRepr::Untagged => self.serialize_unit(),
+        }
     }
 
     #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value<'lua>>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value>
     where
         T: Serialize + ?Sized,
     {
@@ -219,19 +317,33 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
     #[inline]
     fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
         value: &T,
-    ) -> Result<Value<'lua>>
+    ) -> Result<Value>
     where
         T: Serialize + ?Sized,
     {
-        let table = self.lua.create_table()?;
-        let variant = self.lua.create_string(variant)?;
         let value = self.lua.to_value_with(value, self.options)?;
-        table.raw_set(variant, value)?;
-        Ok(Value::Table(table))
+        match self.options.enum_repr {
+            EnumRepr::ExternallyTagged => {
+                let table = self.lua.create_table()?;
+                table.raw_set(variant, value)?;
+                Ok(Value::Table(table))
+            }
+            EnumRepr::InternallyTagged { tag } => match value {
+                Value::Table(table) => {
+                    table.raw_set(tag, variant)?;
+                    Ok(Value::Table(table))
+                }
+                value => Err(Error::SerializeError(format!(
+                    "cannot serialize internally tagged variant `{name}::{variant}` containing {}",
+                    value.type_name()
+                ))),
+            },
+            EnumRepr::Untagged | EnumRepr::StringForUnit => Ok(value),
+        }
     }
 
     #[inline]
@@ -306,20 +418,20 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
 }
 
 #[doc(hidden)]
-pub struct SerializeVec<'lua> {
-    table: Table<'lua>,
+pub struct SerializeVec {
+    table: Table,
     options: Options,
 }
 
-impl<'lua> ser::SerializeSeq for SerializeVec<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_element<T>(&mut self, value: &T) -> Result<()>
     where
         T: Serialize + ?Sized,
     {
-        let lua = self.table.0.lua;
+        let lua = &self.table.0.lua;
         let state = lua.state();
         let value = lua.to_value_with(value, self.options)?;
         unsafe {
@@ -342,13 +454,13 @@ impl<'lua> ser::SerializeSeq for SerializeVec<'lua> {
         }
     }
 
-    fn end(self) -> Result<Value<'lua>> {
+    fn end(self) -> Result<Value> {
         Ok(Value::Table(self.table))
     }
 }
 
-impl<'lua> ser::SerializeTuple for SerializeVec<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_element<T>(&mut self, value: &T) -> Result<()>
@@ -358,13 +470,13 @@ impl<'lua> ser::SerializeTuple for SerializeVec<'lua> {
         ser::SerializeSeq::serialize_element(self, value)
     }
 
-    fn end(self) -> Result<Value<'lua>> {
+    fn end(self) -> Result<Value> {
         ser::SerializeSeq::end(self)
     }
 }
 
-impl<'lua> ser::SerializeTupleStruct for SerializeVec<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, value: &T) -> Result<()>
@@ -374,57 +486,80 @@ impl<'lua> ser::SerializeTupleStruct for SerializeVec<'lua> {
         ser::SerializeSeq::serialize_element(self, value)
     }
 
-    fn end(self) -> Result<Value<'lua>> {
+    fn end(self) -> Result<Value> {
         ser::SerializeSeq::end(self)
     }
 }
 
 #[doc(hidden)]
-pub struct SerializeTupleVariant<'lua> {
-    name: String<'lua>,
-    table: Table<'lua>,
+pub struct SerializeTupleVariant {
+    name: String,
+    table: Table,
     options: Options,
 }
 
-impl<'lua> ser::SerializeTupleVariant for SerializeTupleVariant<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: Serialize + ?Sized,
     {
-        let lua = self.table.0.lua;
+        let lua = &self.table.0.lua;
         let idx = self.table.raw_len() + 1;
         self.table
             .raw_insert(idx, lua.to_value_with(value, self.options)?)
     }
 
-    fn end(self) -> Result<Value<'lua>> {
-        let lua = self.table.0.lua;
-        let table = lua.create_table()?;
-        table.raw_set(self.name, self.table)?;
-        Ok(Value::Table(table))
+    fn end(self) -> Result<Value> {
+        let lua = &self.table.0.lua;
+        match self.options.enum_repr {
+            EnumRepr::ExternallyTagged => {
+                let table = lua.create_table()?;
+                table.raw_set(self.name, self.table)?;
+                Ok(Value::Table(table))
+            }
+            EnumRepr::InternallyTagged { tag } => {
+                self.table.raw_set(tag, self.name)?;
+                Ok(Value::Table(self.table))
+            }
+            EnumRepr::Untagged | EnumRepr::StringForUnit => {
+                if self.options.set_array_metatable {
+                    self.table.set_metatable(Some(lua.array_metatable()));
+                }
+                Ok(Value::Table(self.table))
+            }
+        }
     }
 }
 
 #[doc(hidden)]
-pub struct SerializeMap<'lua> {
-    table: Table<'lua>,
-    key: Option<Value<'lua>>,
+pub struct SerializeMap {
+    table: Table,
+    key: Option<Value>,
     options: Options,
 }
 
-impl<'lua> ser::SerializeMap for SerializeMap<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
         T: Serialize + ?Sized,
     {
-        let lua = self.table.0.lua;
-        self.key = Some(lua.to_value_with(key, self.options)?);
+        let lua = &self.table.0.lua;
+        let key = lua.to_value_with(key, self.options)?;
+        self.key = Some(match key {
+            Value::String(ref s) if self.options.detect_integer_keys => {
+                match s.to_str().ok().and_then(parse_integer_key) {
+                    Some(i) => Value::Integer(i),
+                    None => key,
+                }
+            }
+            key => key,
+        });
         Ok(())
     }
 
@@ -432,7 +567,7 @@ impl<'lua> ser::SerializeMap for SerializeMap<'lua> {
     where
         T: Serialize + ?Sized,
     {
-        let lua = self.table.0.lua;
+        let lua = &self.table.0.lua;
         let key = mlua_expect!(
             self.key.take(),
             "serialize_value called before serialize_key"
@@ -441,13 +576,21 @@ impl<'lua> ser::SerializeMap for SerializeMap<'lua> {
         self.table.raw_set(key, value)
     }
 
-    fn end(self) -> Result<Value<'lua>> {
+    fn end(self) -> Result<Value> {
+        #[cfg(not(feature = "luau"))]
+        if self.options.function_bytecode {
+            if let Some(bytecode) = bytecode_chunk(&self.table)? {
+                let lua = &self.table.0.lua;
+                let func = lua.load_chunk(None, Value::Nil, Some(ChunkMode::Binary), &bytecode)?;
+                return Ok(Value::Function(func));
+            }
+        }
         Ok(Value::Table(self.table))
     }
 }
 
-impl<'lua> ser::SerializeStruct for SerializeMap<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
@@ -458,36 +601,69 @@ impl<'lua> ser::SerializeStruct for SerializeMap<'lua> {
         ser::SerializeMap::serialize_value(self, value)
     }
 
-    fn end(self) -> Result<Value<'lua>> {
+    fn end(self) -> Result<Value> {
         ser::SerializeMap::end(self)
     }
 }
 
 #[doc(hidden)]
-pub struct SerializeStructVariant<'lua> {
-    name: String<'lua>,
-    table: Table<'lua>,
+pub struct SerializeStructVariant {
+    name: String,
+    table: Table,
     options: Options,
 }
 
-impl<'lua> ser::SerializeStructVariant for SerializeStructVariant<'lua> {
-    type Ok = Value<'lua>;
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: Serialize + ?Sized,
     {
-        let lua = self.table.0.lua;
+        let lua = &self.table.0.lua;
         self.table
             .raw_set(key, lua.to_value_with(value, self.options)?)?;
         Ok(())
     }
 
-    fn end(self) -> Result<Value<'lua>> {
-        let lua = self.table.0.lua;
-        let table = lua.create_table()?;
-        table.raw_set(self.name, self.table)?;
-        Ok(Value::Table(table))
+    fn end(self) -> Result<Value> {
+        let lua = &self.table.0.lua;
+        match self.options.enum_repr {
+            EnumRepr::ExternallyTagged => {
+                let table = lua.create_table()?;
+                table.raw_set(self.name, self.table)?;
+                Ok(Value::Table(table))
+            }
+            EnumRepr::InternallyTagged { tag } => {
+                self.table.raw_set(tag, self.name)?;
+                Ok(Value::Table(self.table))
+            }
+            EnumRepr::Untagged | EnumRepr::StringForUnit => Ok(Value::Table(self.table)),
+        }
+    }
+}
+
+// Returns the binary chunk if the table has the only `__mlua_bytecode` string field
+#[cfg(not(feature = "luau"))]
+fn bytecode_chunk(table: &Table) -> Result<Option<Vec<u8>>> {
+    let mut pairs = table.clone().pairs::<Value, Value>();
+    match (pairs.next().transpose()?, pairs.next()) {
+        (Some((Value::String(key), Value::String(bytecode))), None)
+            if key.as_bytes() == BYTECODE_KEY.as_bytes() =>
+        {
+            Ok(Some(bytecode.as_bytes().to_vec()))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Parses a string in canonical decimal integer form (the one produced by formatting an integer)
+fn parse_integer_key(s: &str) -> Option<Integer> {
+    let i = s.parse::<Integer>().ok()?;
+    if i.to_string() == s {
+        Some(i)
+    } else {
+        None
     }
 }
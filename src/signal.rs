@@ -0,0 +1,288 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+#[cfg(feature = "async")]
+use {
+    futures_util::future,
+    std::cell::RefCell,
+    std::task::{Context, Poll, Waker},
+};
+
+use crate::error::Result;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::userdata::{AnyUserData, UserData, UserDataFields, UserDataMethods};
+use crate::value::{IntoLua, IntoLuaMulti, MultiValue, Value};
+
+/// A signal (event) to which Lua functions can be connected, created by [`Lua::create_signal`].
+///
+/// Signals are fired from Rust with typed arguments `A`, which are passed to every connected
+/// function. In Lua, signals provide the following methods:
+/// - `signal:Connect(f)`: connects the function `f`, returns a connection
+/// - `signal:Once(f)`: connects the function `f`, which is disconnected after the first call
+/// - `signal:Wait()`: waits (asynchronously) until the signal is fired, returns its arguments.
+///   Requires `feature = "async"`.
+/// - `signal:Fire(...)`: fires the signal
+///
+/// Connections have the `Connected` field and the `Disconnect()` method.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let clicked = lua.create_signal::<(i32, i32)>()?;
+/// lua.globals().set("clicked", clicked.clone())?;
+/// lua.load(
+///     r#"
+///     clicks = 0
+///     clicked:Connect(function(x, y) clicks = clicks + 1 end)
+///     clicked:Once(function(x, y) first = x .. "," .. y end)
+/// "#,
+/// )
+/// .exec()?;
+///
+/// clicked.fire((10, 20))?;
+/// clicked.fire((30, 40))?;
+/// assert_eq!(lua.globals().get::<_, i32>("clicks")?, 2);
+/// assert_eq!(lua.globals().get::<_, String>("first")?, "10,20");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Signal<A> {
+    // Connections are stored in the user value of the userdata
+    ud: AnyUserData,
+    _args: PhantomData<fn(A)>,
+}
+
+#[derive(Default)]
+struct SignalState {
+    #[cfg(feature = "async")]
+    waiters: RefCell<Vec<SignalWaiter>>,
+    #[cfg(feature = "async")]
+    next_waiter_id: Cell<u64>,
+}
+
+// Task waiting for the signal to be fired. Arguments are packed into a table (with the `n` field)
+// to keep the signal state `Send`.
+#[cfg(feature = "async")]
+struct SignalWaiter {
+    id: u64,
+    args: Option<Table>,
+    waker: Option<Waker>,
+}
+
+// Registration of a `Wait` call, removed from the signal when the call completes or is cancelled
+#[cfg(feature = "async")]
+struct WaiterGuard {
+    signal: AnyUserData,
+    id: u64,
+}
+
+// Connection of a function to a signal. The function and the signal are stored in the user values
+// of the userdata.
+struct Connection {
+    once: bool,
+    connected: Cell<bool>,
+}
+
+impl Lua {
+    /// Creates a new [`Signal`] fired with arguments of type `A`.
+    pub fn create_signal<A: IntoLuaMulti>(&self) -> Result<Signal<A>> {
+        let ud = self.create_userdata(SignalState::default())?;
+        ud.set_user_value(self.create_table()?)?;
+        Ok(Signal {
+            ud,
+            _args: PhantomData,
+        })
+    }
+}
+
+impl<A: IntoLuaMulti> Signal<A> {
+    /// Fires the signal, calling every connected function with `args`.
+    ///
+    /// Functions are called in order of connection. Functions connected while firing are not
+    /// called until the next time. If a function raises an error, the remaining functions are
+    /// not called and the error is returned.
+    pub fn fire(&self, args: A) -> Result<()> {
+        let lua = &self.ud.0.lua;
+        fire(&self.ud, args.into_lua_multi(lua)?)
+    }
+
+    /// Returns the number of connected functions.
+    pub fn connection_count(&self) -> Result<usize> {
+        Ok(self.ud.get_user_value::<Table>()?.raw_len() as usize)
+    }
+
+    /// Disconnects all connected functions.
+    pub fn disconnect_all(&self) -> Result<()> {
+        let connections = self.ud.get_user_value::<Table>()?;
+        for conn in connections.raw_sequence_values::<AnyUserData>() {
+            conn?.borrow::<Connection>()?.connected.set(false);
+        }
+        self.ud.set_user_value(self.ud.0.lua.create_table()?)
+    }
+}
+
+impl<A> Clone for Signal<A> {
+    fn clone(&self) -> Self {
+        Signal {
+            ud: self.ud.clone(),
+            _args: PhantomData,
+        }
+    }
+}
+
+impl<A> IntoLua for Signal<A> {
+    #[inline]
+    fn into_lua(self, _: &Lua) -> Result<Value> {
+        Ok(Value::UserData(self.ud))
+    }
+}
+
+fn connect(lua: &Lua, signal: AnyUserData, func: Function, once: bool) -> Result<AnyUserData> {
+    let conn = lua.create_userdata(Connection {
+        once,
+        connected: Cell::new(true),
+    })?;
+    conn.set_nth_user_value(1, func)?;
+    conn.set_nth_user_value(2, signal.clone())?;
+    signal.get_user_value::<Table>()?.raw_push(conn.clone())?;
+    Ok(conn)
+}
+
+fn disconnect(conn: &AnyUserData) -> Result<()> {
+    if !conn.borrow::<Connection>()?.connected.replace(false) {
+        return Ok(());
+    }
+    let signal: AnyUserData = conn.get_nth_user_value(2)?;
+    let connections: Table = signal.get_user_value()?;
+    for (i, other) in connections
+        .clone()
+        .raw_sequence_values::<AnyUserData>()
+        .enumerate()
+    {
+        if other? == *conn {
+            return connections.raw_remove(i as Integer + 1);
+        }
+    }
+    Ok(())
+}
+
+fn fire(signal: &AnyUserData, args: MultiValue) -> Result<()> {
+    #[cfg(feature = "async")]
+    {
+        let state = signal.borrow::<SignalState>()?;
+        let mut waiters = state.waiters.borrow_mut();
+        let mut wakers = Vec::new();
+        if waiters.iter().any(|waiter| waiter.args.is_none()) {
+            let packed = signal.0.lua.create_sequence_from(args.iter().cloned())?;
+            packed.raw_set("n", args.len())?;
+            for waiter in waiters.iter_mut().filter(|waiter| waiter.args.is_none()) {
+                waiter.args = Some(packed.clone());
+                wakers.extend(waiter.waker.take());
+            }
+        }
+        drop(waiters);
+        drop(state);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    // Take a snapshot of the connections, so that functions connected while firing are skipped
+    let connections: Vec<AnyUserData> = (signal.get_user_value::<Table>()?)
+        .raw_sequence_values()
+        .collect::<Result<_>>()?;
+    for conn in connections {
+        let once = match conn.borrow::<Connection>()? {
+            // Disconnected by a previous function
+            this if !this.connected.get() => continue,
+            this => this.once,
+        };
+        if once {
+            disconnect(&conn)?;
+        }
+        let func: Function = conn.get_nth_user_value(1)?;
+        func.call::<_, ()>(args.clone())?;
+    }
+    Ok(())
+}
+
+impl UserData for SignalState {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("Connect", |lua, (signal, func): (AnyUserData, Function)| {
+            connect(&lua, signal, func, false)
+        });
+        methods.add_function("Once", |lua, (signal, func): (AnyUserData, Function)| {
+            connect(&lua, signal, func, true)
+        });
+        methods.add_function("Fire", |_, (signal, args): (AnyUserData, MultiValue)| {
+            fire(&signal, args)
+        });
+        #[cfg(feature = "async")]
+        methods.add_async_function("Wait", |_, signal: AnyUserData| async move {
+            let waiter = WaiterGuard::new(signal)?;
+            let args = future::poll_fn(|cx| waiter.poll(cx)).await?;
+            let n: usize = args.raw_get("n")?;
+            (1..=n)
+                .map(|i| args.raw_get(i))
+                .collect::<Result<MultiValue>>()
+        });
+    }
+}
+
+#[cfg(feature = "async")]
+impl WaiterGuard {
+    fn new(signal: AnyUserData) -> Result<Self> {
+        let state = signal.borrow::<SignalState>()?;
+        let id = state.next_waiter_id.get();
+        state.next_waiter_id.set(id + 1);
+        state.waiters.borrow_mut().push(SignalWaiter {
+            id,
+            args: None,
+            waker: None,
+        });
+        drop(state);
+        Ok(WaiterGuard { signal, id })
+    }
+
+    fn poll(&self, cx: &mut Context) -> Poll<Result<Table>> {
+        let state = match self.signal.borrow::<SignalState>() {
+            Ok(state) => state,
+            Err(err) => return Poll::Ready(Err(err)),
+        };
+        let mut waiters = state.waiters.borrow_mut();
+        let waiter = waiters.iter_mut().find(|waiter| waiter.id == self.id);
+        let waiter = waiter.expect("signal waiter is registered until dropped");
+        match waiter.args.clone() {
+            Some(args) => Poll::Ready(Ok(args)),
+            None => {
+                waiter.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for WaiterGuard {
+    fn drop(&mut self) {
+        if let Ok(state) = self.signal.borrow::<SignalState>() {
+            if let Ok(mut waiters) = state.waiters.try_borrow_mut() {
+                waiters.retain(|waiter| waiter.id != self.id);
+            }
+        }
+    }
+}
+
+impl UserData for Connection {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("Connected", |_, this| Ok(this.connected.get()));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("Disconnect", |_, conn: AnyUserData| disconnect(&conn));
+    }
+}
@@ -1,6 +1,10 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign};
+use std::string::String as StdString;
 use std::u32;
 
+use crate::ffi;
+
 /// Flags describing the set of lua standard libraries to load.
 #[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct StdLib(u32);
@@ -48,6 +52,12 @@ impl StdLib {
     #[cfg(any(feature = "luajit", doc))]
     #[cfg_attr(docsrs, doc(cfg(feature = "luajit")))]
     pub const JIT: StdLib = StdLib(1 << 9);
+    /// [`buffer`](https://luau-lang.org/library#buffer-library) library
+    ///
+    /// Requires `feature = "luau"`
+    #[cfg(any(feature = "luau", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub const BUFFER: StdLib = StdLib(1 << 10);
 
     /// (**unsafe**) [`ffi`](http://luajit.org/ext_ffi.html) library
     ///
@@ -71,6 +81,65 @@ impl StdLib {
     pub fn contains(self, lib: Self) -> bool {
         (self & lib).0 != 0
     }
+
+    // Returns global names of the libraries in this set
+    pub(crate) fn names(self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        #[cfg(any(
+            feature = "lua54",
+            feature = "lua53",
+            feature = "lua52",
+            feature = "luau"
+        ))]
+        if self.contains(StdLib::COROUTINE) {
+            names.push(ffi::LUA_COLIBNAME);
+        }
+        if self.contains(StdLib::TABLE) {
+            names.push(ffi::LUA_TABLIBNAME);
+        }
+        #[cfg(not(feature = "luau"))]
+        if self.contains(StdLib::IO) {
+            names.push(ffi::LUA_IOLIBNAME);
+        }
+        if self.contains(StdLib::OS) {
+            names.push(ffi::LUA_OSLIBNAME);
+        }
+        if self.contains(StdLib::STRING) {
+            names.push(ffi::LUA_STRLIBNAME);
+        }
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "luau"))]
+        if self.contains(StdLib::UTF8) {
+            names.push(ffi::LUA_UTF8LIBNAME);
+        }
+        #[cfg(any(feature = "lua52", feature = "luajit", feature = "luau"))]
+        if self.contains(StdLib::BIT) {
+            names.push(ffi::LUA_BITLIBNAME);
+        }
+        #[cfg(feature = "luau")]
+        if self.contains(StdLib::BUFFER) {
+            names.push(ffi::LUA_BUFFERLIBNAME);
+        }
+        if self.contains(StdLib::MATH) {
+            names.push(ffi::LUA_MATHLIBNAME);
+        }
+        if self.contains(StdLib::DEBUG) {
+            names.push(ffi::LUA_DBLIBNAME);
+        }
+        #[cfg(not(feature = "luau"))]
+        if self.contains(StdLib::PACKAGE) {
+            names.push(ffi::LUA_LOADLIBNAME);
+        }
+        #[cfg(feature = "luajit")]
+        {
+            if self.contains(StdLib::JIT) {
+                names.push(ffi::LUA_JITLIBNAME);
+            }
+            if self.contains(StdLib::FFI) {
+                names.push(ffi::LUA_FFILIBNAME);
+            }
+        }
+        names
+    }
 }
 
 impl BitAnd for StdLib {
@@ -111,3 +180,57 @@ impl BitXorAssign for StdLib {
         *self = StdLib(self.0 ^ rhs.0)
     }
 }
+
+/// Fine-grained filter of the standard library functions.
+///
+/// Applied to a Lua state by [`Lua::filter_std_lib`] to remove individual functions (for example
+/// `string.dump` or `os.execute`) from the loaded libraries.
+///
+/// Entries are written as `"library.function"` paths, or as a plain global name (eg. `"dofile"`).
+///
+/// [`Lua::filter_std_lib`]: crate::Lua::filter_std_lib
+#[derive(Clone, Debug, Default)]
+pub struct StdLibFilter {
+    pub(crate) allowed: BTreeMap<StdString, BTreeSet<StdString>>,
+    pub(crate) denied: BTreeSet<(StdString, Option<StdString>)>,
+    // Paths that cannot be allowed, reported by `Lua::filter_std_lib`
+    pub(crate) invalid: BTreeSet<StdString>,
+}
+
+impl StdLibFilter {
+    /// Creates a new filter which does not remove anything.
+    pub fn new() -> Self {
+        StdLibFilter::default()
+    }
+
+    /// Allows a library function.
+    ///
+    /// Once at least one function of a library is allowed, all other functions of this library
+    /// are removed.
+    ///
+    /// Only library functions can be allowed, a plain global name (eg. `"print"`) makes
+    /// [`Lua::filter_std_lib`] return an error.
+    ///
+    /// [`Lua::filter_std_lib`]: crate::Lua::filter_std_lib
+    pub fn allow(mut self, path: &str) -> Self {
+        match path.split_once('.') {
+            Some((lib, name)) if !lib.is_empty() && !name.is_empty() => {
+                (self.allowed.entry(lib.to_string()).or_default()).insert(name.to_string());
+            }
+            _ => {
+                self.invalid.insert(path.to_string());
+            }
+        }
+        self
+    }
+
+    /// Removes a library function or a global (when `path` does not contain a dot).
+    pub fn deny(mut self, path: &str) -> Self {
+        let entry = match path.split_once('.') {
+            Some((lib, name)) => (lib.to_string(), Some(name.to_string())),
+            None => (path.to_string(), None),
+        };
+        self.denied.insert(entry);
+        self
+    }
+}
@@ -1,9 +1,14 @@
 use std::borrow::{Borrow, Cow};
 use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Bound, Deref, Range, RangeBounds};
 use std::os::raw::c_void;
+use std::str::Chars;
 use std::string::String as StdString;
 use std::{fmt, slice, str};
 
+use bstr::{ByteSlice, Utf8Chunks};
+
 #[cfg(feature = "serialize")]
 use {
     serde::ser::{Serialize, Serializer},
@@ -12,7 +17,11 @@ use {
 
 use crate::error::{Error, Result};
 use crate::ffi;
-use crate::types::LuaRef;
+use crate::function::Function;
+use crate::table::Table;
+use crate::types::{Integer, LuaRef};
+use crate::util::{assert_stack, StackGuard};
+use crate::value::{FromLuaMulti, MultiValue, Value};
 
 /// Handle to an internal Lua string.
 ///
@@ -20,6 +29,37 @@ use crate::types::LuaRef;
 #[derive(Clone)]
 pub struct String(pub(crate) LuaRef);
 
+/// Owned handle to an internal Lua string.
+///
+/// See [`OwnedTable`] for the semantics of owned handles.
+///
+/// [`OwnedTable`]: crate::OwnedTable
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OwnedString(pub(crate) String);
+
+impl OwnedString {
+    /// Returns a handle to the underlying Lua string.
+    #[inline]
+    pub fn to_ref(&self) -> String {
+        self.0.clone()
+    }
+
+    /// Converts this owned handle into a regular handle to the Lua string.
+    #[inline]
+    pub fn into_ref(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for OwnedString {
+    type Target = String;
+
+    #[inline]
+    fn deref(&self) -> &String {
+        &self.0
+    }
+}
+
 impl String {
     /// Get a `&str` slice if the Lua string is valid UTF-8.
     ///
@@ -71,6 +111,47 @@ impl String {
         StdString::from_utf8_lossy(self.as_bytes())
     }
 
+    /// Returns an iterator over the chars of this string, decoded as UTF-8.
+    ///
+    /// Invalid byte sequences are handled according to the `policy`. With
+    /// [`Utf8Policy::Strict`], the iterator yields an error at the first invalid sequence and
+    /// stops.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Utf8Policy};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let s = lua.create_string(b"a\xFFb")?;
+    /// let chars = s.chars(Utf8Policy::Replace).collect::<Result<Vec<_>>>()?;
+    /// assert_eq!(chars, vec!['a', '\u{FFFD}', 'b']);
+    /// assert!(s.chars(Utf8Policy::Strict).any(|c| c.is_err()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chars(&self, policy: Utf8Policy) -> StringChars<'_> {
+        StringChars {
+            chunks: Some(ByteSlice::utf8_chunks(self.as_bytes())),
+            chars: "".chars(),
+            invalid: None,
+            offset: 0,
+            policy,
+        }
+    }
+
+    /// Returns the number of chars in this string, decoded as UTF-8.
+    ///
+    /// Invalid byte sequences are handled according to the `policy` (see [`String::chars`]).
+    pub fn char_len(&self, policy: Utf8Policy) -> Result<usize> {
+        let mut len = 0;
+        for c in self.chars(policy) {
+            c?;
+            len += 1;
+        }
+        Ok(len)
+    }
+
     /// Get the bytes that make up this string.
     ///
     /// The returned slice will not contain the terminating nul byte, but will contain any nul
@@ -99,14 +180,14 @@ impl String {
         let ref_thread = self.0.lua.ref_thread();
         unsafe {
             mlua_debug_assert!(
-                ffi::lua_type(ref_thread, self.0.index) == ffi::LUA_TSTRING,
+                ffi::lua_type(ref_thread, self.0.index()) == ffi::LUA_TSTRING,
                 "string ref is not string type"
             );
 
             let mut size = 0;
             // This will not trigger a 'm' error, because the reference is guaranteed to be of
             // string type
-            let data = ffi::lua_tolstring(ref_thread, self.0.index, &mut size);
+            let data = ffi::lua_tolstring(ref_thread, self.0.index(), &mut size);
 
             slice::from_raw_parts(data as *const u8, size + 1)
         }
@@ -120,7 +201,133 @@ impl String {
     #[inline]
     pub fn to_pointer(&self) -> *const c_void {
         let ref_thread = self.0.lua.ref_thread();
-        unsafe { ffi::lua_topointer(ref_thread, self.0.index) }
+        unsafe { ffi::lua_topointer(ref_thread, self.0.index()) }
+    }
+
+    /// Returns a substring of this string.
+    ///
+    /// The range is in bytes, starting from 0. Similar to `string.sub`, out of range bounds are
+    /// clamped to the string length instead of panicking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let s = lua.create_string("hello world")?;
+    /// assert_eq!(s.sub(6..)?, "world");
+    /// assert_eq!(s.sub(..=4)?, "hello");
+    /// assert_eq!(s.sub(8..100)?, "rld");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sub(&self, range: impl RangeBounds<usize>) -> Result<String> {
+        let bytes = self.as_bytes();
+        let end = match range.end_bound() {
+            Bound::Included(&i) => i.saturating_add(1),
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => bytes.len(),
+        };
+        let end = end.min(bytes.len());
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        self.0.lua.create_string(&bytes[start.min(end)..end])
+    }
+
+    /// Looks for the first match of a Lua `pattern` in this string.
+    ///
+    /// Returns the byte range of the match, or `None` if there is no match. The search is done
+    /// by the `string.find` function of the Lua string library (which must be loaded) in
+    /// protected mode, so any error in the pattern is returned as [`Error::RuntimeError`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let s = lua.create_string("key = value")?;
+    /// assert_eq!(s.find("%s*=%s*")?, Some(3..6));
+    /// assert_eq!(s.find("%d+")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn find(&self, pattern: impl AsRef<[u8]>) -> Result<Option<Range<usize>>> {
+        let find = self.string_lib_function("find")?;
+        let pattern = self.0.lua.create_string(pattern)?;
+        let (start, end) =
+            find.call::<_, (Option<Integer>, Option<Integer>)>((self.clone(), pattern))?;
+        match (start, end) {
+            (Some(start), Some(end)) => Ok(Some(start as usize - 1..end as usize)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns an iterator over all matches of a Lua `pattern` in this string.
+    ///
+    /// Each item contains the captures of the match (or the whole match if the pattern has no
+    /// captures), converted to `R`. The iterator is backed by the `string.gmatch` function of the
+    /// Lua string library, which must be loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let s = lua.create_string("a=1, b=2")?;
+    /// let pairs = s
+    ///     .gmatch::<(String, i32)>("(%w+)=(%w+)")?
+    ///     .collect::<Result<Vec<_>>>()?;
+    /// assert_eq!(pairs, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gmatch<R: FromLuaMulti>(&self, pattern: impl AsRef<[u8]>) -> Result<StringMatches<R>> {
+        let gmatch = self.string_lib_function("gmatch")?;
+        let pattern = self.0.lua.create_string(pattern)?;
+        Ok(StringMatches {
+            iter: Some(gmatch.call((self.clone(), pattern))?),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Converts this handle into an owned version.
+    ///
+    /// See [`OwnedString`] for details.
+    #[inline]
+    pub fn into_owned(self) -> OwnedString {
+        OwnedString(self)
+    }
+
+    // Returns a function of the string library (the `__index` table of the string metatable)
+    fn string_lib_function(&self, name: &str) -> Result<Function> {
+        let lua = &self.0.lua;
+        let metatable = unsafe {
+            let state = lua.state();
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 2);
+
+            lua.push_ref(&self.0);
+            match ffi::lua_getmetatable(state, -1) {
+                0 => None,
+                _ => Some(Table(lua.pop_ref())),
+            }
+        };
+        if let Some(metatable) = metatable {
+            if let Value::Table(lib) = metatable.raw_get("__index")? {
+                if let Value::Function(func) = lib.raw_get(name)? {
+                    return Ok(func);
+                }
+            }
+        }
+        Err(Error::RuntimeError(format!(
+            "string library function '{name}' is not available"
+        )))
     }
 }
 
@@ -190,6 +397,99 @@ impl Hash for String {
     }
 }
 
+/// Handling of invalid UTF-8 sequences when decoding a Lua string.
+///
+/// Used by [`String::chars`] and [`String::char_len`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Return an error.
+    Strict,
+    /// Replace each invalid sequence with [`U+FFFD REPLACEMENT CHARACTER`][U+FFFD], similar to
+    /// [`String::to_string_lossy`].
+    ///
+    /// [U+FFFD]: std::char::REPLACEMENT_CHARACTER
+    Replace,
+    /// Skip invalid sequences.
+    Skip,
+}
+
+/// An iterator over the chars of a Lua string.
+///
+/// This struct is created by the [`String::chars`] method.
+///
+/// [`String::chars`]: crate::String::chars
+pub struct StringChars<'a> {
+    chunks: Option<Utf8Chunks<'a>>,
+    chars: Chars<'a>,
+    // Byte offset of the invalid sequence ending the current chunk
+    invalid: Option<usize>,
+    // Byte offset of the next chunk
+    offset: usize,
+    policy: Utf8Policy,
+}
+
+impl<'a> Iterator for StringChars<'a> {
+    type Item = Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(c) = self.chars.next() {
+                return Some(Ok(c));
+            }
+            if let Some(invalid) = self.invalid.take() {
+                match self.policy {
+                    Utf8Policy::Strict => {
+                        self.chunks = None;
+                        return Some(Err(Error::FromLuaConversionError {
+                            from: "string",
+                            to: "char",
+                            message: Some(format!("invalid UTF-8 sequence at byte {invalid}")),
+                        }));
+                    }
+                    Utf8Policy::Replace => return Some(Ok(char::REPLACEMENT_CHARACTER)),
+                    Utf8Policy::Skip => {}
+                }
+            }
+            let chunk = self.chunks.as_mut()?.next()?;
+            self.chars = chunk.valid().chars();
+            if !chunk.invalid().is_empty() {
+                self.invalid = Some(self.offset + chunk.valid().len());
+            }
+            self.offset += chunk.valid().len() + chunk.invalid().len();
+        }
+    }
+}
+
+/// An iterator over the matches of a Lua pattern in a string.
+///
+/// This struct is created by the [`String::gmatch`] method.
+///
+/// [`String::gmatch`]: crate::String::gmatch
+pub struct StringMatches<R> {
+    iter: Option<Function>,
+    _phantom: PhantomData<R>,
+}
+
+impl<R: FromLuaMulti> Iterator for StringMatches<R> {
+    type Item = Result<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let iter = self.iter.as_ref()?;
+        let captures = match iter.call::<_, MultiValue>(()) {
+            Ok(captures) => captures,
+            Err(err) => {
+                self.iter = None;
+                return Some(Err(err));
+            }
+        };
+        if matches!(captures.iter().next(), None | Some(Value::Nil)) {
+            self.iter = None;
+            return None;
+        }
+        Some(R::from_lua_multi(captures, &iter.0.lua))
+    }
+}
+
 #[cfg(feature = "serialize")]
 impl Serialize for String {
     fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
@@ -208,4 +508,5 @@ mod assertions {
     use super::*;
 
     static_assertions::assert_not_impl_any!(String: Send);
+    static_assertions::assert_impl_all!(OwnedString: Send, Sync);
 }
@@ -1,4 +1,6 @@
+use std::cmp::Ordering;
 use std::marker::PhantomData;
+use std::ops::Deref;
 use std::os::raw::c_void;
 
 #[cfg(feature = "serialize")]
@@ -23,17 +25,41 @@ use {futures_core::future::LocalBoxFuture, futures_util::future};
 #[derive(Clone, Debug)]
 pub struct Table(pub(crate) LuaRef);
 
-/// Owned handle to an internal Lua table.
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-#[derive(Clone, Debug)]
-pub struct OwnedTable(pub(crate) crate::types::LuaOwnedRef);
+/// Owned handle to an internal Lua table, suitable for long-lived application state.
+///
+/// The handle keeps the Lua state alive: the state is closed only after the [`Lua`] instance
+/// and all handles referencing it are dropped, so an owned handle never outlives its state.
+/// Dropping the handle releases the table to the Lua garbage collector.
+///
+/// Owned handles are `Send` and `Sync` as the [`Lua`] instance itself. The Lua state is not
+/// synchronized, so it must not be used from multiple threads at the same time.
+///
+/// The handle dereferences to [`Table`], so all table methods are available on it.
+///
+/// [`Lua`]: crate::Lua
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedTable(pub(crate) Table);
 
-#[cfg(feature = "unstable")]
 impl OwnedTable {
-    /// Get borrowed handle to the underlying Lua table.
-    pub const fn as_ref(&self) -> Table {
-        Table(self.0.as_ref())
+    /// Returns a handle to the underlying Lua table.
+    #[inline]
+    pub fn to_ref(&self) -> Table {
+        self.0.clone()
+    }
+
+    /// Converts this owned handle into a regular handle to the Lua table.
+    #[inline]
+    pub fn into_ref(self) -> Table {
+        self.0
+    }
+}
+
+impl Deref for OwnedTable {
+    type Target = Table;
+
+    #[inline]
+    fn deref(&self) -> &Table {
+        &self.0
     }
 }
 
@@ -141,11 +167,145 @@ impl Table {
         V::from_lua(value, &lua)
     }
 
+    /// Asynchronously sets a key-value pair in the table.
+    ///
+    /// This is an async version of [`set`] which can invoke an async `__newindex` metamethod
+    /// (eg. created with [`Lua::create_async_function`]), driving it to completion.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`set`]: #method.set
+    /// [`Lua::create_async_function`]: crate::Lua::create_async_function
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn set_async<K, V>(&self, key: K, value: V) -> LocalBoxFuture<'static, Result<()>>
+    where
+        K: IntoLua,
+        V: IntoLua,
+    {
+        // Fast track
+        if !self.has_metatable() {
+            return Box::pin(future::ready(self.raw_set(key, value)));
+        }
+
+        let set = (self.0.lua)
+            .load("local t, k, v = ... t[k] = v")
+            .try_cache()
+            .set_name("_mlua_table_set")
+            .into_function();
+        match set {
+            Ok(set) => set.call_async((self.clone(), key, value)),
+            Err(e) => Box::pin(future::err(e)),
+        }
+    }
+
+    /// Asynchronously gets the value associated to `key` from the table.
+    ///
+    /// This is an async version of [`get`] which can invoke an async `__index` metamethod
+    /// (eg. created with [`Lua::create_async_function`]), driving it to completion.
+    /// Regular [`get`] fails in that case as the metamethod cannot yield outside of a coroutine.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let proxy = lua.create_table()?;
+    /// let metatable = lua.create_table()?;
+    /// metatable.set("__index", lua.create_async_function(|_, (_, key): (Table, String)| async move {
+    ///     // Fetch the value from a database...
+    ///     Ok(format!("value of {key}"))
+    /// })?)?;
+    /// proxy.set_metatable(Some(metatable));
+    ///
+    /// assert_eq!(proxy.get_async::<_, String>("name").await?, "value of name");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`get`]: #method.get
+    /// [`Lua::create_async_function`]: crate::Lua::create_async_function
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn get_async<K, V>(&self, key: K) -> LocalBoxFuture<'static, Result<V>>
+    where
+        K: IntoLua,
+        V: FromLua + 'static,
+    {
+        // Fast track
+        if !self.has_metatable() {
+            return Box::pin(future::ready(self.raw_get(key)));
+        }
+
+        let get = (self.0.lua)
+            .load("local t, k = ... return t[k]")
+            .try_cache()
+            .set_name("_mlua_table_get")
+            .into_function();
+        match get {
+            Ok(get) => get.call_async((self.clone(), key)),
+            Err(e) => Box::pin(future::err(e)),
+        }
+    }
+
     /// Checks whether the table contains a non-nil value for `key`.
     pub fn contains_key<K: IntoLua>(&self, key: K) -> Result<bool> {
         Ok(self.get::<_, Value>(key)? != Value::Nil)
     }
 
+    /// Checks whether the table contains a non-nil value for `key`, without invoking metamethods.
+    pub fn raw_contains_key<K: IntoLua>(&self, key: K) -> Result<bool> {
+        Ok(self.raw_get::<_, Value>(key)? != Value::Nil)
+    }
+
+    /// Gets the value at the given path of keys in nested tables, without invoking metamethods.
+    ///
+    /// Each key is looked up using [`raw_get`] in the table returned by the previous key.
+    /// Returns `nil` (converted to `V`) if any of the intermediate values is `nil`, and an error
+    /// if it's not a table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let config: Table = lua.load("{ window = { size = { width = 800 } } }").eval()?;
+    /// let width: i32 = config.raw_get_path(["window", "size", "width"])?;
+    /// assert_eq!(width, 800);
+    /// let height: Option<i32> = config.raw_get_path(["window", "size", "height"])?;
+    /// assert_eq!(height, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`raw_get`]: #method.raw_get
+    pub fn raw_get_path<K, V>(&self, path: impl IntoIterator<Item = K>) -> Result<V>
+    where
+        K: IntoLua,
+        V: FromLua,
+    {
+        let lua = &self.0.lua;
+        let mut value = Value::Table(self.clone());
+        for key in path {
+            value = match value {
+                Value::Table(table) => table.raw_get(key)?,
+                Value::Nil => break,
+                value => {
+                    return Err(Error::RuntimeError(format!(
+                        "attempt to index a {} value",
+                        value.type_name()
+                    )))
+                }
+            };
+        }
+        V::from_lua(value, lua)
+    }
+
     /// Appends a value to the back of the table.
     pub fn push<V: IntoLua>(&self, value: V) -> Result<()> {
         // Fast track
@@ -415,6 +575,87 @@ impl Table {
         }
     }
 
+    /// Removes the element at position `idx` from the sequence part of the table and returns it,
+    /// without invoking metamethods.
+    ///
+    /// The removed element is replaced by the last element of the sequence. This does not
+    /// preserve ordering, but is O(1) unlike [`raw_remove`].
+    ///
+    /// [`raw_remove`]: #method.raw_remove
+    pub fn raw_swap_remove<V: FromLua>(&self, idx: Integer) -> Result<V> {
+        #[cfg(feature = "luau")]
+        self.check_readonly_write()?;
+
+        let lua = self.0.lua.clone();
+        let state = lua.state();
+
+        let size = self.raw_len();
+        if idx < 1 || idx > size {
+            return Err(Error::RuntimeError("index out of bounds".to_string()));
+        }
+
+        let value = unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 4)?;
+
+            lua.push_ref(&self.0);
+            ffi::lua_rawgeti(state, -1, idx);
+            protect_lua!(state, 2, 1, |state| {
+                if idx < size {
+                    // table[idx] = table[size]
+                    ffi::lua_rawgeti(state, -2, size);
+                    ffi::lua_rawseti(state, -3, idx);
+                }
+                ffi::lua_pushnil(state);
+                ffi::lua_rawseti(state, -3, size);
+            })?;
+            lua.pop_value()
+        };
+        V::from_lua(value, &lua)
+    }
+
+    /// Sorts the sequence part of the table using a Rust comparator, without invoking
+    /// metamethods.
+    ///
+    /// The sort is stable. Unlike the Lua `table.sort` function, an inconsistent comparator
+    /// does not cause errors like "invalid order function for sorting" and the first error
+    /// returned by the comparator aborts sorting, leaving the table unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let table = lua.create_sequence_from(["banana", "Apple", "cherry"])?;
+    /// table.sort_by(|a, b| match (a, b) {
+    ///     (Value::String(a), Value::String(b)) => {
+    ///         let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+    ///         Ok(a.to_lowercase().cmp(&b.to_lowercase()))
+    ///     }
+    ///     _ => Err(mlua::Error::RuntimeError("expected strings".to_string())),
+    /// })?;
+    /// assert_eq!(table.raw_get::<_, String>(1)?, "Apple");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort_by<F>(&self, mut compare: F) -> Result<()>
+    where
+        F: FnMut(&Value, &Value) -> Result<Ordering>,
+    {
+        #[cfg(feature = "luau")]
+        self.check_readonly_write()?;
+
+        let values = (1..=self.raw_len())
+            .map(|i| self.raw_get(i))
+            .collect::<Result<Vec<Value>>>()?;
+        let values = merge_sort(values, &mut compare)?;
+        for (i, value) in values.into_iter().enumerate() {
+            self.raw_set(i as Integer + 1, value)?;
+        }
+        Ok(())
+    }
+
     /// Clears the table, removing all keys and values from array and hash parts,
     /// without invoking metamethods.
     ///
@@ -426,7 +667,7 @@ impl Table {
         let lua = self.0.lua.clone();
         unsafe {
             #[cfg(feature = "luau")]
-            ffi::lua_cleartable(lua.ref_thread(), self.0.index);
+            ffi::lua_cleartable(lua.ref_thread(), self.0.index());
 
             #[cfg(not(feature = "luau"))]
             {
@@ -481,7 +722,38 @@ impl Table {
     /// Returns the result of the Lua `#` operator, without invoking the `__len` metamethod.
     pub fn raw_len(&self) -> Integer {
         let ref_thread = self.0.lua.ref_thread();
-        unsafe { ffi::lua_rawlen(ref_thread, self.0.index) as Integer }
+        unsafe { ffi::lua_rawlen(ref_thread, self.0.index()) as Integer }
+    }
+
+    /// Returns the raw length of the table as `usize`, without invoking the `__len` metamethod.
+    ///
+    /// This is the length of the array part (the border found by the Lua `#` operator) and is
+    /// intended for preallocating buffers before iterating over the table sequence.
+    #[inline]
+    pub fn len_hint(&self) -> usize {
+        self.raw_len() as usize
+    }
+
+    /// Returns `true` if the table has no entries in either array or hash part.
+    ///
+    /// Unlike checking `len()? == 0`, this method never invokes metamethods and does not
+    /// ignore non-sequence keys.
+    pub fn is_empty(&self) -> bool {
+        // Fast track
+        if self.raw_len() > 0 {
+            return false;
+        }
+
+        let lua = &self.0.lua;
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 4);
+
+            lua.push_ref(&self.0);
+            ffi::lua_pushnil(state);
+            ffi::lua_next(state, -2) == 0
+        }
     }
 
     /// Returns a reference to the metatable of this table, or `None` if no metatable is set.
@@ -536,7 +808,7 @@ impl Table {
     pub fn has_metatable(&self) -> bool {
         let ref_thread = self.0.lua.ref_thread();
         unsafe {
-            if ffi::lua_getmetatable(ref_thread, self.0.index) != 0 {
+            if ffi::lua_getmetatable(ref_thread, self.0.index()) != 0 {
                 ffi::lua_pop(ref_thread, 1);
                 return true;
             }
@@ -552,10 +824,10 @@ impl Table {
     pub fn set_readonly(&self, enabled: bool) {
         let ref_thread = self.0.lua.ref_thread();
         unsafe {
-            ffi::lua_setreadonly(ref_thread, self.0.index, enabled as _);
+            ffi::lua_setreadonly(ref_thread, self.0.index(), enabled as _);
             if !enabled {
                 // Reset "safeenv" flag
-                ffi::lua_setsafeenv(ref_thread, self.0.index, 0);
+                ffi::lua_setsafeenv(ref_thread, self.0.index(), 0);
             }
         }
     }
@@ -567,7 +839,7 @@ impl Table {
     #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
     pub fn is_readonly(&self) -> bool {
         let ref_thread = self.0.lua.ref_thread();
-        unsafe { ffi::lua_getreadonly(ref_thread, self.0.index) != 0 }
+        unsafe { ffi::lua_getreadonly(ref_thread, self.0.index()) != 0 }
     }
 
     /// Converts the table to a generic C pointer.
@@ -579,15 +851,15 @@ impl Table {
     #[inline]
     pub fn to_pointer(&self) -> *const c_void {
         let ref_thread = self.0.lua.ref_thread();
-        unsafe { ffi::lua_topointer(ref_thread, self.0.index) }
+        unsafe { ffi::lua_topointer(ref_thread, self.0.index()) }
     }
 
-    /// Convert this handle to owned version.
-    #[cfg(feature = "unstable")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
+    /// Converts this handle into an owned version.
+    ///
+    /// See [`OwnedTable`] for details.
     #[inline]
     pub fn into_owned(self) -> OwnedTable {
-        OwnedTable(self.0.into_owned())
+        OwnedTable(self)
     }
 
     /// Consume this table and return an iterator over the pairs of the table.
@@ -631,6 +903,22 @@ impl Table {
         }
     }
 
+    /// Consume this table and return an iterator over the pairs of the table, without invoking
+    /// metamethods.
+    ///
+    /// Unlike [`pairs`], which mirrors the Lua `pairs` function and may honor metamethods in
+    /// future, this method guarantees that no script-controlled code (such as `__pairs` or
+    /// `__index` metamethods) is run during the iteration.
+    ///
+    /// [`pairs`]: #method.pairs
+    pub fn raw_pairs<K: FromLua, V: FromLua>(self) -> TablePairs<K, V> {
+        TablePairs {
+            table: self.0,
+            key: Some(Nil),
+            _phantom: PhantomData,
+        }
+    }
+
     /// Consume this table and return an iterator over all values in the sequence part of the table.
     ///
     /// The iterator will yield all values `t[1]`, `t[2]`, and so on, until a `nil` value is
@@ -770,7 +1058,6 @@ pub trait TableExt: Sealed {
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     fn call_async<'fut, A, R>(&self, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut;
 
@@ -810,7 +1097,6 @@ pub trait TableExt: Sealed {
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     fn call_async_method<'fut, K, A, R>(&self, key: K, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         K: IntoLua,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut;
@@ -829,7 +1115,6 @@ pub trait TableExt: Sealed {
         args: A,
     ) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         K: IntoLua,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut;
@@ -848,7 +1133,6 @@ impl TableExt for Table {
     #[cfg(feature = "async")]
     fn call_async<'fut, A, R>(&self, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
@@ -879,13 +1163,12 @@ impl TableExt for Table {
     #[cfg(feature = "async")]
     fn call_async_method<'fut, K, A, R>(&self, key: K, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         K: IntoLua,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
         let lua = self.0.lua.clone();
-        let mut args = match args.into_lua_multi(lua) {
+        let mut args = match args.into_lua_multi(&lua) {
             Ok(args) => args,
             Err(e) => return Box::pin(future::err(e)),
         };
@@ -896,7 +1179,6 @@ impl TableExt for Table {
     #[cfg(feature = "async")]
     fn call_async_function<'fut, K, A, R>(&self, key: K, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         K: IntoLua,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
@@ -1025,6 +1307,31 @@ pub struct TableSequence<V> {
     _phantom: PhantomData<V>,
 }
 
+// Stable merge sort which never panics on an inconsistent comparator
+fn merge_sort<F>(mut values: Vec<Value>, compare: &mut F) -> Result<Vec<Value>>
+where
+    F: FnMut(&Value, &Value) -> Result<Ordering>,
+{
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+    let right = values.split_off(values.len() / 2);
+    let left = merge_sort(values, compare)?;
+    let right = merge_sort(right, compare)?;
+
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        match compare(b, a)? {
+            Ordering::Less => result.extend(right.next()),
+            _ => result.extend(left.next()),
+        }
+    }
+    result.extend(left);
+    result.extend(right);
+    Ok(result)
+}
+
 impl<V> Iterator for TableSequence<V>
 where
     V: FromLua,
@@ -1072,6 +1379,5 @@ mod assertions {
 
     static_assertions::assert_not_impl_any!(Table: Send);
 
-    #[cfg(feature = "unstable")]
-    static_assertions::assert_not_impl_any!(OwnedTable: Send);
+    static_assertions::assert_impl_all!(OwnedTable: Send, Sync);
 }
@@ -0,0 +1,167 @@
+use std::fmt::Write as _;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::value::Value;
+
+// Registry key of the table caching compiled templates by their source
+const TEMPLATE_CACHE_KEY: &str = "__mlua_template_cache";
+
+impl Lua {
+    /// Renders the template string `template`, replacing every `${expr}` with the value of the
+    /// Lua expression `expr` evaluated in the environment `ctx`.
+    ///
+    /// Values are converted to strings using the `tostring` function. Use `$${` to insert
+    /// a literal `${`.
+    ///
+    /// Like [`Lua::eval`], templates are compiled to Lua functions only once and cached by their
+    /// source. Expressions can read only the `ctx` table; set its metatable (eg. with `__index`
+    /// pointing to globals) to give access to other variables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let ctx = lua.create_table()?;
+    /// ctx.set("user", lua.create_table_from([("name", "Alice")])?)?;
+    /// ctx.set("count", 3)?;
+    ///
+    /// let text = lua.render("Hello ${user.name}, you have ${count + 1} messages", &ctx)?;
+    /// assert_eq!(text, "Hello Alice, you have 4 messages");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn render(&self, template: &str, ctx: &Table) -> Result<StdString> {
+        self.render_with(template, ctx, |s| s.to_string())
+    }
+
+    /// Renders the template string like [`Lua::render`], passing every interpolated value
+    /// (converted to a string) through `escape`.
+    ///
+    /// This can be used to escape values for the output format, eg. HTML or shell arguments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let ctx = lua.create_table_from([("title", "<b>news</b>")])?;
+    /// let html = lua.render_with("<h1>${title}</h1>", &ctx, |s| s.replace('<', "&lt;"))?;
+    /// assert_eq!(html, "<h1>&lt;b>news&lt;/b></h1>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn render_with<F>(&self, template: &str, ctx: &Table, mut escape: F) -> Result<StdString>
+    where
+        F: FnMut(&str) -> StdString,
+    {
+        let func = self.cached_chunk(TEMPLATE_CACHE_KEY, template, || {
+            self.compile_with_env("=template", &compile_template(template)?)
+        })?;
+        let (parts, n): (Table, usize) = self.call_with_env(&func, ctx.clone(), ())?;
+
+        let tostring: Option<Function> = self.globals_storage()?.raw_get("tostring")?;
+        let mut output = StdString::new();
+        for i in 1..=n {
+            let value = parts.raw_get::<_, Value>(i)?;
+            // Literal parts have odd indices
+            if i % 2 == 1 {
+                if let Value::String(s) = value {
+                    output.push_str(&s.to_string_lossy());
+                }
+                continue;
+            }
+            let s = match (value, &tostring) {
+                (Value::String(s), _) => s,
+                (value, Some(tostring)) => tostring.call(value)?,
+                (value, None) => self.create_string(format!("{value:?}"))?,
+            };
+            output.push_str(&escape(&s.to_string_lossy()));
+        }
+        Ok(output)
+    }
+
+    /// Removes all compiled templates cached by [`Lua::render`].
+    pub fn clear_template_cache(&self) -> Result<()> {
+        self.unset_named_registry_value(TEMPLATE_CACHE_KEY)
+    }
+}
+
+// Compiles the template to a Lua chunk returning a table of literal parts (at odd indices)
+// interleaved with values of the expressions, and the number of parts
+fn compile_template(template: &str) -> Result<StdString> {
+    let mut body = StdString::from("return {");
+    let mut literal = StdString::new();
+    let mut parts = 0;
+    let mut rest = template;
+    while let Some(pos) = rest.find("${") {
+        if rest[..pos].ends_with('$') {
+            // Escaped `$${`
+            literal.push_str(&rest[..pos - 1]);
+            literal.push_str("${");
+            rest = &rest[pos + 2..];
+            continue;
+        }
+        literal.push_str(&rest[..pos]);
+        let expr_start = pos + 2;
+        let expr_len = expression_len(&rest[expr_start..]).ok_or_else(|| {
+            let offset = template.len() - rest.len() + pos;
+            Error::RuntimeError(format!("unterminated '${{' in template at offset {offset}"))
+        })?;
+        let expr = &rest[expr_start..expr_start + expr_len];
+        let _ = write!(body, "{}, ({expr}), ", quote(&literal));
+        literal.clear();
+        parts += 2;
+        rest = &rest[expr_start + expr_len + 1..];
+    }
+    literal.push_str(rest);
+    let _ = write!(body, "{}}}, {}", quote(&literal), parts + 1);
+    Ok(body)
+}
+
+// Returns the length of the expression up to the closing brace, skipping nested braces and
+// braces in strings
+fn expression_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') if depth == 0 => return Some(i),
+            (None, '}') => depth -= 1,
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+// Quotes a string as a Lua string literal
+fn quote(s: &str) -> StdString {
+    let mut quoted = StdString::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_ascii_control() => {
+                let _ = write!(quoted, "\\{:03}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
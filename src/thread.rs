@@ -1,4 +1,5 @@
 use std::cmp;
+use std::ops::Deref;
 use std::os::raw::c_int;
 
 use crate::error::{Error, Result};
@@ -11,13 +12,16 @@ use crate::value::{FromLuaMulti, IntoLuaMulti};
     feature = "lua54",
     all(feature = "luajit", feature = "vendored"),
     feature = "luau",
+    feature = "async",
 ))]
 use crate::function::Function;
 
 #[cfg(feature = "async")]
 use {
     crate::{
-        lua::{Lua, ASYNC_POLL_PENDING},
+        lua::Lua,
+        table::Table,
+        userdata::{UserData, UserDataMethods},
         value::{MultiValue, Value},
     },
     futures_core::{future::Future, stream::Stream},
@@ -26,9 +30,14 @@ use {
         pin::Pin,
         ptr::NonNull,
         task::{Context, Poll, Waker},
+        time::{Duration, Instant},
     },
 };
 
+// Number of VM instructions after which a running async thread with a time budget is suspended
+#[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+const PREEMPT_INSTRUCTIONS: c_int = 1000;
+
 /// Status of a Lua thread (or coroutine).
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ThreadStatus {
@@ -48,6 +57,37 @@ pub enum ThreadStatus {
 #[derive(Clone, Debug)]
 pub struct Thread(pub(crate) LuaRef);
 
+/// Owned handle to an internal Lua thread.
+///
+/// See [`OwnedTable`] for the semantics of owned handles.
+///
+/// [`OwnedTable`]: crate::OwnedTable
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedThread(pub(crate) Thread);
+
+impl OwnedThread {
+    /// Returns a handle to the underlying Lua thread.
+    #[inline]
+    pub fn to_ref(&self) -> Thread {
+        self.0.clone()
+    }
+
+    /// Converts this owned handle into a regular handle to the Lua thread.
+    #[inline]
+    pub fn into_ref(self) -> Thread {
+        self.0
+    }
+}
+
+impl Deref for OwnedThread {
+    type Target = Thread;
+
+    #[inline]
+    fn deref(&self) -> &Thread {
+        &self.0
+    }
+}
+
 /// Thread (coroutine) representation as an async [`Future`] or [`Stream`].
 ///
 /// Requires `feature = "async"`
@@ -61,6 +101,70 @@ pub struct AsyncThread<R> {
     args0: Option<Result<MultiValue>>,
     ret: PhantomData<R>,
     recycle: bool,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+/// Pending result of an async function called from Lua.
+///
+/// When a function created with [`Lua::create_async_function`] cannot complete immediately,
+/// the coroutine running it yields a `PollPending` userdata. [`AsyncThread`] resumes the coroutine
+/// once the future is woken up, but the result can also be driven manually (eg. from synchronous
+/// Lua code) using the following methods:
+///
+/// - `pending:is_pending()` returns `true` until the future has completed;
+/// - `pending:poll()` polls the future once and returns `true` followed by the results if it has
+///   completed, or `false` otherwise.
+///
+/// Outside of an executor futures are polled using a no-op waker, so they must make progress
+/// without being woken up. Once the future has completed, resuming the coroutine returns
+/// the results.
+///
+/// Yielding a `PollPending` from a coroutine driven by [`AsyncThread`] suspends the whole
+/// thread until the executor polls it again.
+///
+/// Requires `feature = "async"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # use std::task::Poll;
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let ready = lua.create_async_function(|_, ()| async {
+///     // Not ready on the first poll
+///     let mut polled = false;
+///     futures_util::future::poll_fn(|_| match polled {
+///         true => Poll::Ready(()),
+///         false => {
+///             polled = true;
+///             Poll::Pending
+///         }
+///     })
+///     .await;
+///     Ok("done")
+/// })?;
+/// lua.globals().set("ready", ready)?;
+///
+/// lua.load(r#"
+///     local co = coroutine.create(function() return ready() end)
+///     local _, pending = coroutine.resume(co)
+///     assert(pending:is_pending())
+///     while not pending:poll() do end
+///     assert(not pending:is_pending())
+///     assert(select(2, coroutine.resume(co)) == "done")
+/// "#).exec()
+/// # }
+/// ```
+///
+/// [`Lua::create_async_function`]: crate::Lua::create_async_function
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct PollPending {
+    poll: Function,
+    // Results are packed into a table (with the `n` field) to keep this type `Send`
+    results: Option<Result<Table>>,
 }
 
 impl Thread {
@@ -119,7 +223,7 @@ impl Thread {
             let _sg = StackGuard::new(state);
             check_stack(state, cmp::max(nargs + 1, 3))?;
 
-            let thread_state = ffi::lua_tothread(lua.ref_thread(), self.0.index);
+            let thread_state = ffi::lua_tothread(lua.ref_thread(), self.0.index());
 
             let status = ffi::lua_status(thread_state);
             if status != ffi::LUA_YIELD && ffi::lua_gettop(thread_state) == 0 {
@@ -160,7 +264,7 @@ impl Thread {
     pub fn status(&self) -> ThreadStatus {
         let lua = self.0.lua.clone();
         unsafe {
-            let thread_state = ffi::lua_tothread(lua.ref_thread(), self.0.index);
+            let thread_state = ffi::lua_tothread(lua.ref_thread(), self.0.index());
 
             let status = ffi::lua_status(thread_state);
             if status != ffi::LUA_OK && status != ffi::LUA_YIELD {
@@ -173,6 +277,14 @@ impl Thread {
         }
     }
 
+    /// Converts this handle into an owned version.
+    ///
+    /// See [`OwnedThread`] for details.
+    #[inline]
+    pub fn into_owned(self) -> OwnedThread {
+        OwnedThread(self)
+    }
+
     /// Resets a thread
     ///
     /// In [Lua 5.4]: cleans its call stack and closes all pending to-be-closed variables.
@@ -280,12 +392,15 @@ impl Thread {
         A: IntoLuaMulti,
         R: FromLuaMulti,
     {
-        let args = args.into_lua_multi(self.0.lua);
+        let args = args.into_lua_multi(&self.0.lua);
+        let timeout = self.0.lua.async_timeout();
         AsyncThread {
             thread: self,
             args0: Some(args),
             ret: PhantomData,
             recycle: false,
+            timeout,
+            deadline: None,
         }
     }
 
@@ -324,10 +439,10 @@ impl Thread {
     #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
     #[doc(hidden)]
     pub fn sandbox(&self) -> Result<()> {
-        let lua = self.0.lua;
+        let lua = &self.0.lua;
         let state = lua.state();
         unsafe {
-            let thread = ffi::lua_tothread(lua.ref_thread(), self.0.index);
+            let thread = ffi::lua_tothread(lua.ref_thread(), self.0.index());
             check_stack(thread, 1)?;
             check_stack(state, 3)?;
             // Inherit `LUA_GLOBALSINDEX` from the caller
@@ -350,6 +465,42 @@ impl<R> AsyncThread<R> {
     pub(crate) fn set_recyclable(&mut self, recyclable: bool) {
         self.recycle = recyclable;
     }
+
+    // Resumes the thread, returns `None` if it was suspended to check the time budget
+    fn resume(&mut self) -> Result<Option<MultiValue>> {
+        let lua = self.thread.0.lua.clone();
+
+        if let Some(timeout) = self.timeout {
+            let deadline = *self
+                .deadline
+                .get_or_insert_with(|| Instant::now() + timeout);
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+        }
+
+        #[cfg(any(feature = "lua54", feature = "lua53"))]
+        let preempt =
+            (self.timeout.is_some()).then(|| unsafe { PreemptGuard::new(&lua, &self.thread) });
+
+        let ret = if let Some(args) = self.args0.take() {
+            self.thread.resume(args?)?
+        } else {
+            self.thread.resume(())?
+        };
+
+        // Amortize the collection work across polls
+        if let Some(kbytes) = lua.gc_budget() {
+            lua.gc_step_kbytes(kbytes)?;
+        }
+
+        #[cfg(any(feature = "lua54", feature = "lua53"))]
+        if preempt.is_some() && unsafe { lua.take_thread_preempted() } {
+            return Ok(None);
+        }
+
+        Ok(Some(ret))
+    }
 }
 
 #[cfg(feature = "async")]
@@ -362,12 +513,13 @@ impl<R> Drop for AsyncThread<R> {
     fn drop(&mut self) {
         if self.recycle {
             unsafe {
-                let lua = self.thread.0.lua;
+                let lua = self.thread.0.lua.clone();
                 // For Lua 5.4 this also closes all pending to-be-closed variables
                 if !lua.recycle_thread(&mut self.thread) {
                     #[cfg(feature = "lua54")]
                     if self.thread.status() == ThreadStatus::Error {
-                        let thread_state = ffi::lua_tothread(lua.ref_thread(), self.thread.0.index);
+                        let thread_state =
+                            ffi::lua_tothread(lua.ref_thread(), self.thread.0.index());
                         ffi::lua_resetthread(thread_state);
                     }
                 }
@@ -384,29 +536,34 @@ where
     type Item = Result<R>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let lua = self.thread.0.lua;
+        let lua = self.thread.0.lua.clone();
 
         match self.thread.status() {
             ThreadStatus::Resumable => {}
             _ => return Poll::Ready(None),
         };
 
-        let _wg = WakerGuard::new(lua, cx.waker());
+        let _wg = WakerGuard::new(&lua, cx.waker());
 
         // This is safe as we are not moving the whole struct
         let this = unsafe { self.get_unchecked_mut() };
-        let ret: MultiValue = if let Some(args) = this.args0.take() {
-            this.thread.resume(args?)?
-        } else {
-            this.thread.resume(())?
+        let ret = match this.resume()? {
+            Some(ret) => ret,
+            None => {
+                // Suspended to check the time budget
+                lua.call_yield_async_hook()?;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
         };
 
         if is_poll_pending(&ret) {
+            lua.call_yield_async_hook()?;
             return Poll::Pending;
         }
 
         cx.waker().wake_by_ref();
-        Poll::Ready(Some(R::from_lua_multi(ret, lua)))
+        Poll::Ready(Some(R::from_lua_multi(ret, &lua)))
     }
 }
 
@@ -418,34 +575,103 @@ where
     type Output = Result<R>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let lua = self.thread.0.lua;
+        let lua = self.thread.0.lua.clone();
 
         match self.thread.status() {
             ThreadStatus::Resumable => {}
             _ => return Poll::Ready(Err(Error::CoroutineInactive)),
         };
 
-        let _wg = WakerGuard::new(lua, cx.waker());
+        let _wg = WakerGuard::new(&lua, cx.waker());
 
         // This is safe as we are not moving the whole struct
         let this = unsafe { self.get_unchecked_mut() };
-        let ret: MultiValue = if let Some(args) = this.args0.take() {
-            this.thread.resume(args?)?
-        } else {
-            this.thread.resume(())?
+        let ret = match this.resume()? {
+            Some(ret) => ret,
+            None => {
+                // Suspended to check the time budget
+                lua.call_yield_async_hook()?;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
         };
 
         if is_poll_pending(&ret) {
+            lua.call_yield_async_hook()?;
             return Poll::Pending;
         }
 
         if let ThreadStatus::Resumable = this.thread.status() {
             // Ignore value returned via yield()
+            lua.call_yield_async_hook()?;
             cx.waker().wake_by_ref();
             return Poll::Pending;
         }
 
-        Poll::Ready(R::from_lua_multi(ret, lua))
+        Poll::Ready(R::from_lua_multi(ret, &lua))
+    }
+}
+
+#[cfg(feature = "async")]
+impl PollPending {
+    pub(crate) fn new(poll: Function) -> Self {
+        PollPending {
+            poll,
+            results: None,
+        }
+    }
+
+    /// Returns `true` if the future has not completed yet.
+    pub fn is_pending(&self) -> bool {
+        self.results.is_none()
+    }
+
+    /// Polls the future once.
+    ///
+    /// Returns the results if the future has completed, or `None` otherwise.
+    /// Once completed, the same results are returned on subsequent calls.
+    pub fn poll(&mut self) -> Result<Option<MultiValue>> {
+        if self.results.is_none() {
+            match self.poll.call::<_, MultiValue>(()) {
+                Ok(mut ret) => {
+                    if let Some(Value::Boolean(true)) = ret.pop_front() {
+                        let n = ret.len();
+                        let packed = self.poll.0.lua.create_sequence_from(ret)?;
+                        packed.raw_set("n", n)?;
+                        self.results = Some(Ok(packed));
+                    }
+                }
+                // Keep the error, the future must not be polled again
+                Err(err) => self.results = Some(Err(err)),
+            }
+        }
+        match self.results {
+            Some(Ok(ref packed)) => {
+                let n: usize = packed.raw_get("n")?;
+                let results = (1..=n)
+                    .map(|i| packed.raw_get(i))
+                    .collect::<Result<Vec<Value>>>()?;
+                Ok(Some(MultiValue::from_vec(results)))
+            }
+            Some(Err(ref err)) => Err(err.clone()),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl UserData for PollPending {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("is_pending", |_, this, ()| Ok(this.is_pending()));
+        methods.add_method_mut("poll", |_, this, ()| {
+            Ok(match this.poll()? {
+                Some(mut results) => {
+                    results.push_front(Value::Boolean(true));
+                    results
+                }
+                None => MultiValue::from_vec(vec![Value::Boolean(false)]),
+            })
+        });
     }
 }
 
@@ -453,24 +679,22 @@ where
 #[inline(always)]
 fn is_poll_pending(val: &MultiValue) -> bool {
     match val.iter().enumerate().last() {
-        Some((0, Value::LightUserData(ud))) => {
-            std::ptr::eq(ud.0 as *const u8, &ASYNC_POLL_PENDING as *const u8)
-        }
+        Some((0, Value::UserData(ud))) => ud.is::<PollPending>(),
         _ => false,
     }
 }
 
 #[cfg(feature = "async")]
-struct WakerGuard<'a> {
+struct WakerGuard<'lua, 'a> {
     lua: &'lua Lua,
     prev: NonNull<Waker>,
     _phantom: PhantomData<&'a ()>,
 }
 
 #[cfg(feature = "async")]
-impl<'a> WakerGuard<'a> {
+impl<'lua, 'a> WakerGuard<'lua, 'a> {
     #[inline]
-    pub fn new(lua: &'lua Lua, waker: &'a Waker) -> Result<WakerGuard<'a>> {
+    pub fn new(lua: &'lua Lua, waker: &'a Waker) -> Result<WakerGuard<'lua, 'a>> {
         unsafe {
             let prev = lua.set_waker(NonNull::from(waker));
             Ok(WakerGuard {
@@ -483,7 +707,7 @@ impl<'a> WakerGuard<'a> {
 }
 
 #[cfg(feature = "async")]
-impl<'a> Drop for WakerGuard<'a> {
+impl<'lua, 'a> Drop for WakerGuard<'lua, 'a> {
     fn drop(&mut self) {
         unsafe {
             self.lua.set_waker(self.prev);
@@ -491,9 +715,64 @@ impl<'a> Drop for WakerGuard<'a> {
     }
 }
 
+#[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+struct PreemptGuard<'a> {
+    lua: &'a Lua,
+    state: *mut ffi::lua_State,
+    prev_thread: *mut ffi::lua_State,
+    prev_hook: (Option<ffi::lua_Hook>, c_int, c_int),
+}
+
+#[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+impl<'a> PreemptGuard<'a> {
+    unsafe fn new(lua: &'a Lua, thread: &Thread) -> Self {
+        let state = ffi::lua_tothread(lua.ref_thread(), thread.0.index());
+        let prev_hook = (
+            ffi::lua_gethook(state),
+            ffi::lua_gethookmask(state),
+            ffi::lua_gethookcount(state),
+        );
+        ffi::lua_sethook(
+            state,
+            Some(preempt_hook),
+            ffi::LUA_MASKCOUNT,
+            PREEMPT_INSTRUCTIONS,
+        );
+        let prev_thread = lua.set_preempted_thread(state);
+        PreemptGuard {
+            lua,
+            state,
+            prev_thread,
+            prev_hook,
+        }
+    }
+}
+
+#[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+impl<'a> Drop for PreemptGuard<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            self.lua.set_preempted_thread(self.prev_thread);
+            let (hook, mask, count) = self.prev_hook;
+            ffi::lua_sethook(self.state, hook, mask, count);
+        }
+    }
+}
+
+#[cfg(all(feature = "async", any(feature = "lua54", feature = "lua53")))]
+unsafe extern "C-unwind" fn preempt_hook(state: *mut ffi::lua_State, _ar: *mut ffi::lua_Debug) {
+    // Coroutines created by the thread inherit the hook, they are not suspended
+    if let Some(lua) = Lua::try_from_ptr(state) {
+        if lua.preempt_thread(state) {
+            ffi::lua_yield(state, 0);
+        }
+    }
+}
+
 #[cfg(test)]
 mod assertions {
     use super::*;
 
     static_assertions::assert_not_impl_any!(Thread: Send);
+    static_assertions::assert_impl_all!(OwnedThread: Send, Sync);
 }
@@ -0,0 +1,131 @@
+use std::any::{type_name, TypeId};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::lua::{Lua, LuaOptions};
+use crate::stdlib::StdLib;
+use crate::userdata::UserData;
+use crate::userdata_impl::UserDataRegistrar;
+
+type Registration = Arc<dyn Fn(&Lua) -> Result<()> + Send + Sync>;
+
+/// A shareable description of userdata types to register in Lua states.
+///
+/// Every Lua state registers userdata types lazily, on creation of the first userdata of each
+/// type. Servers running many states can describe their types once in a `TypeRegistry` and apply
+/// it to each new state (see [`Lua::new_with_registry`]), so all metatables are built in one pass
+/// when the state is created instead of on the hot path.
+///
+/// The registry is cheap to clone and can be shared between threads.
+///
+/// # Examples
+///
+/// ```
+/// # use std::net::Ipv4Addr;
+/// # use mlua::{Lua, Result, TypeRegistry, UserData, UserDataMethods};
+/// # fn main() -> Result<()> {
+/// struct Counter(u32);
+///
+/// impl UserData for Counter {
+///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+///         methods.add_method_mut("increment", |_, this, ()| Ok(this.0 += 1));
+///     }
+/// }
+///
+/// let mut registry = TypeRegistry::new();
+/// registry.register::<Counter>();
+/// registry.register_type::<Ipv4Addr>(|reg| {
+///     reg.add_method("is_loopback", |_, this, ()| Ok(this.is_loopback()));
+/// });
+///
+/// for _ in 0..4 {
+///     let lua = Lua::new_with_registry(&registry)?;
+///     lua.globals().set("counter", Counter(0))?;
+///     lua.globals().set("addr", lua.create_any_userdata(Ipv4Addr::LOCALHOST)?)?;
+///     lua.load("counter:increment(); assert(addr:is_loopback())").exec()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+    types: Vec<(TypeId, &'static str, Registration)>,
+}
+
+impl TypeRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        TypeRegistry::default()
+    }
+
+    /// Adds a type implementing [`UserData`] to the registry.
+    pub fn register<T: UserData + 'static>(&mut self) -> &mut Self {
+        self.add::<T>(Arc::new(|lua| unsafe {
+            lua.userdata_metatable_id::<T>()?;
+            Ok(())
+        }))
+    }
+
+    /// Adds a custom type to the registry, registering its methods and fields using `f`.
+    ///
+    /// When applied, the type is registered using [`Lua::register_userdata_type`], so
+    /// userdata of the type can be created with [`Lua::create_any_userdata`].
+    pub fn register_type<T: 'static>(
+        &mut self,
+        f: impl Fn(&mut UserDataRegistrar<T>) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.add::<T>(Arc::new(move |lua| lua.register_userdata_type::<T>(&f)))
+    }
+
+    /// Returns the number of types in the registry.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Returns `true` if the registry has no types.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Registers all types of the registry in the Lua instance.
+    ///
+    /// Types registered with [`TypeRegistry::register_type`] replace any previous registration of
+    /// the same type in the Lua instance.
+    pub fn apply(&self, lua: &Lua) -> Result<()> {
+        for (_, _, register) in &self.types {
+            register(lua)?;
+        }
+        Ok(())
+    }
+
+    fn add<T: 'static>(&mut self, registration: Registration) -> &mut Self {
+        let type_id = TypeId::of::<T>();
+        match self.types.iter_mut().find(|(id, ..)| *id == type_id) {
+            Some((_, _, r)) => *r = registration,
+            None => self.types.push((type_id, type_name::<T>(), registration)),
+        }
+        self
+    }
+}
+
+impl fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = self.types.iter().map(|(_, name, _)| name);
+        f.debug_struct("TypeRegistry")
+            .field("types", &names.collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Lua {
+    /// Creates a new Lua state, loads the **safe** subset of the standard libraries and registers
+    /// all types of the `registry`.
+    ///
+    /// See [`Lua::new`] and [`TypeRegistry`] for details.
+    pub fn new_with_registry(registry: &TypeRegistry) -> Result<Lua> {
+        let lua = Lua::new_with(StdLib::ALL_SAFE, LuaOptions::default())?;
+        registry.apply(&lua)?;
+        Ok(lua)
+    }
+}
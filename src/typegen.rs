@@ -0,0 +1,339 @@
+use std::any::type_name;
+use std::fmt::Write;
+use std::os::raw::c_void;
+use std::string::String as StdString;
+
+use rustc_hash::FxHashSet;
+
+use crate::error::Result;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::userdata::{AnyUserData, MetaMethod};
+use crate::userdata_impl::UserDataRegistrar;
+use crate::util::{check_stack, StackGuard};
+use crate::value::{IntoLua, Value};
+
+// Maximum nesting level of generated table types
+const MAX_DEPTH: usize = 16;
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "export", "false", "for",
+    "function", "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true",
+    "type", "typeof", "until", "while",
+];
+
+// Metamethods that Luau allows in class declarations
+const OPERATORS: &[MetaMethod] = &[
+    MetaMethod::Add,
+    MetaMethod::Sub,
+    MetaMethod::Mul,
+    MetaMethod::Div,
+    MetaMethod::Mod,
+    MetaMethod::Pow,
+    MetaMethod::Unm,
+    MetaMethod::Concat,
+    MetaMethod::Len,
+    MetaMethod::Eq,
+    MetaMethod::Lt,
+    MetaMethod::Le,
+    MetaMethod::Call,
+    MetaMethod::ToString,
+];
+
+// Shape of a registered userdata type, collected during registration
+#[derive(Clone, Debug)]
+pub(crate) struct UserDataTypeInfo {
+    pub(crate) name: StdString,
+    fields: Vec<(StdString, StdString)>,
+    methods: Vec<StdString>,
+    meta_methods: Vec<StdString>,
+}
+
+impl UserDataTypeInfo {
+    pub(crate) fn new<T: 'static>(registry: &UserDataRegistrar<T>) -> Option<Self> {
+        // Skip internal types
+        if type_name::<T>().starts_with("mlua::") {
+            return None;
+        }
+
+        let mut info = UserDataTypeInfo {
+            name: class_name(type_name::<T>()),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            meta_methods: Vec::new(),
+        };
+
+        let field_names = (registry.field_getters.iter().map(|(k, _)| k))
+            .chain(registry.field_accessors.iter().map(|(k, _)| k))
+            .chain(registry.field_setters.iter().map(|(k, _)| k));
+        #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+        let field_names = field_names.chain(registry.async_field_getters.iter().map(|(k, _)| k));
+        for name in field_names {
+            info.set_field_type(name, "any");
+        }
+
+        let method_names = registry.methods.iter().map(|(k, _)| k);
+        #[cfg(feature = "async")]
+        let method_names = method_names.chain(registry.async_methods.iter().map(|(k, _)| k));
+        for name in method_names {
+            if !info.methods.contains(name) && !info.fields.iter().any(|(k, _)| k == name) {
+                info.methods.push(name.clone());
+            }
+        }
+
+        let meta_method_names = registry.meta_methods.iter().map(|(k, _)| k);
+        #[cfg(feature = "async")]
+        let meta_method_names =
+            meta_method_names.chain(registry.async_meta_methods.iter().map(|(k, _)| k));
+        for name in meta_method_names {
+            if OPERATORS.iter().any(|m| m.name() == name) && !info.meta_methods.contains(name) {
+                info.meta_methods.push(name.clone());
+            }
+        }
+
+        Some(info)
+    }
+
+    // Sets type of a field, static fields are typed using their values
+    pub(crate) fn set_field_type(&mut self, name: &str, ty: impl Into<StdString>) {
+        match self.fields.iter_mut().find(|(k, _)| k == name) {
+            Some((_, t)) => *t = ty.into(),
+            None => self.fields.push((name.to_string(), ty.into())),
+        }
+    }
+
+    // Sets class name from the `__type` or `__name` meta field
+    pub(crate) fn set_name(&mut self, value: &Value) {
+        if let Value::String(s) = value {
+            match s.to_str() {
+                Ok(name) if is_identifier(name) => self.name = name.to_string(),
+                _ => {}
+            }
+        }
+    }
+
+    fn render(&self, out: &mut StdString) {
+        let _ = writeln!(out, "declare class {}", self.name);
+        for (name, ty) in &self.fields {
+            let _ = writeln!(out, "    {}: {ty}", property_name(name));
+        }
+        for name in self.methods.iter().chain(&self.meta_methods) {
+            if is_identifier(name) {
+                let _ = writeln!(out, "    function {name}(self, ...: any): ...any");
+            }
+        }
+        out.push_str("end\n");
+    }
+}
+
+impl Lua {
+    /// Generates Luau type declarations of all userdata types registered in this Lua instance.
+    ///
+    /// Every type is declared as a class with its fields, methods and operator metamethods.
+    /// The class is named after the `__type` (or `__name`) meta field if it's set, or after the
+    /// Rust type otherwise. Argument and return types are not known at runtime, so methods
+    /// accept and return `any` values. Static fields are typed using their values.
+    ///
+    /// The output is suitable for a `.d.luau` definitions file consumed by `luau-analyze`
+    /// (`--definitions` option) and Luau language servers.
+    ///
+    /// Requires `feature = "typegen"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, UserData, UserDataFields, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// struct Counter(u32);
+    ///
+    /// impl UserData for Counter {
+    ///     fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+    ///         fields.add_field_method_get("value", |_, this| Ok(this.0));
+    ///     }
+    ///
+    ///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    ///         methods.add_method_mut("increment", |_, this, ()| Ok(this.0 += 1));
+    ///     }
+    /// }
+    ///
+    /// let lua = Lua::new();
+    /// lua.create_userdata(Counter(0))?;
+    ///
+    /// let declarations = lua.userdata_type_declarations();
+    /// assert!(declarations.contains("declare class Counter\n"));
+    /// assert!(declarations.contains("    value: any\n"));
+    /// assert!(declarations.contains("    function increment(self, ...: any): ...any\n"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "typegen")))]
+    pub fn userdata_type_declarations(&self) -> StdString {
+        let mut out = StdString::new();
+        for (_, info) in self.userdata_type_infos() {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            info.render(&mut out);
+        }
+        out
+    }
+
+    /// Generates a Luau declaration of a global variable `name` holding `value`.
+    ///
+    /// Tables (usually modules) are walked recursively and declared as table types, functions
+    /// are declared as accepting and returning `any` values. Userdata of registered types refer
+    /// to classes generated by [`Lua::userdata_type_declarations`].
+    ///
+    /// Requires `feature = "typegen"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let module = lua.create_table()?;
+    /// module.set("version", "1.0")?;
+    /// module.set("add", lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?)?;
+    ///
+    /// let declaration = lua.global_type_declaration("mymod", module)?;
+    /// assert_eq!(
+    ///     declaration,
+    ///     "declare mymod: {\n    add: (...any) -> ...any,\n    version: string,\n}\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "typegen")))]
+    pub fn global_type_declaration(&self, name: &str, value: impl IntoLua) -> Result<StdString> {
+        Ok(match value.into_lua(self)? {
+            Value::Function(_) if is_identifier(name) => {
+                format!("declare function {name}(...: any): ...any\n")
+            }
+            value => {
+                let mut visited = FxHashSet::default();
+                format!(
+                    "declare {name}: {}\n",
+                    value_type(self, &value, &mut visited, 0)?
+                )
+            }
+        })
+    }
+
+    // Returns class name of a registered userdata type
+    unsafe fn userdata_class_name(&self, ud: &AnyUserData) -> Option<StdString> {
+        let state = self.state();
+        let _sg = StackGuard::new(state);
+        check_stack(state, 2).ok()?;
+        let type_id = self.push_userdata_ref(&ud.0).ok()??;
+        (self.userdata_type_infos().into_iter())
+            .find(|(id, _)| *id == type_id)
+            .map(|(_, info)| info.name)
+    }
+}
+
+// Returns Luau type of the value
+pub(crate) fn value_type(
+    lua: &Lua,
+    value: &Value,
+    visited: &mut FxHashSet<*const c_void>,
+    depth: usize,
+) -> Result<StdString> {
+    Ok(match value {
+        Value::Nil => "nil".to_string(),
+        Value::Boolean(_) => "boolean".to_string(),
+        Value::Integer(_) | Value::Number(_) => "number".to_string(),
+        #[cfg(feature = "luau")]
+        Value::Vector(..) => "vector".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Function(_) => "(...any) -> ...any".to_string(),
+        Value::Thread(_) => "thread".to_string(),
+        Value::Table(t) => table_type(lua, t, visited, depth)?,
+        Value::UserData(ud) => {
+            unsafe { lua.userdata_class_name(ud) }.unwrap_or_else(|| "any".to_string())
+        }
+        Value::LightUserData(_) | Value::Error(_) => "any".to_string(),
+    })
+}
+
+fn table_type(
+    lua: &Lua,
+    table: &Table,
+    visited: &mut FxHashSet<*const c_void>,
+    depth: usize,
+) -> Result<StdString> {
+    let ptr = table.to_pointer();
+    if depth >= MAX_DEPTH || !visited.insert(ptr) {
+        return Ok("any".to_string());
+    }
+
+    let mut props = Vec::new();
+    let (mut key_type, mut elem_type) = (None, None);
+    for pair in table.clone().pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let ty = value_type(lua, &value, visited, depth + 1)?;
+        match key {
+            Value::String(s) => match s.to_str() {
+                Ok(s) => props.push((s.to_string(), ty)),
+                Err(_) => continue,
+            },
+            key => {
+                let kty = value_type(lua, &key, visited, depth + 1)?;
+                key_type = Some(unify(key_type, kty));
+                elem_type = Some(unify(elem_type, ty));
+            }
+        }
+    }
+    visited.remove(&ptr);
+
+    let indexer = key_type.zip(elem_type);
+    match indexer {
+        None if props.is_empty() => return Ok("{}".to_string()),
+        Some((k, v)) if props.is_empty() && k == "number" => return Ok(format!("{{{v}}}")),
+        _ => {}
+    }
+
+    props.sort();
+    let indent = "    ".repeat(depth + 1);
+    let mut out = "{\n".to_string();
+    if let Some((k, v)) = indexer {
+        let _ = writeln!(out, "{indent}[{k}]: {v},");
+    }
+    for (name, ty) in props {
+        let _ = writeln!(out, "{indent}{}: {ty},", property_name(&name));
+    }
+    let _ = write!(out, "{}}}", "    ".repeat(depth));
+    Ok(out)
+}
+
+fn unify(current: Option<StdString>, ty: StdString) -> StdString {
+    match current {
+        Some(current) if current != ty => "any".to_string(),
+        _ => ty,
+    }
+}
+
+fn property_name(name: &str) -> StdString {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        format!("[{name:?}]")
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&s)
+}
+
+// Converts Rust type name (eg. `my_crate::module::Type<u32>`) to a class name (`Type`)
+fn class_name(type_name: &str) -> StdString {
+    let name = type_name.split('<').next().unwrap_or(type_name);
+    let name = name.rsplit("::").next().unwrap_or(name);
+    match is_identifier(name) {
+        true => name.to_string(),
+        false => "UserData".to_string(),
+    }
+}
@@ -5,7 +5,6 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::{fmt, mem, ptr};
 
-#[cfg(feature = "lua54")]
 use std::ffi::CStr;
 
 #[cfg(feature = "async")]
@@ -13,11 +12,15 @@ use futures_core::future::LocalBoxFuture;
 
 use crate::error::Result;
 use crate::ffi;
+use crate::globals::{GlobalAccess, GlobalPolicy};
 #[cfg(not(feature = "luau"))]
 use crate::hook::Debug;
 use crate::lua::{ExtraData, Lua};
+use crate::memory::MemoryEvent;
+#[cfg(feature = "serialize")]
+use crate::userdata::AnyUserData;
 use crate::util::{assert_stack, StackGuard};
-use crate::value::MultiValue;
+use crate::value::{MultiValue, Value};
 
 /// Type of Lua integer numbers.
 pub type Integer = ffi::lua_Integer;
@@ -28,8 +31,71 @@ pub type Number = ffi::lua_Number;
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct LightUserData(pub *mut c_void);
 
+/// A light userdata pointer tagged with the Rust type it points to.
+///
+/// Converting it to a Lua value tags the pointer in the Lua instance (see
+/// [`Lua::tag_light_userdata`]) or fails if the pointer is tagged with another type. Converting
+/// a Lua value back checks that the light userdata was tagged with `T`, instead of blindly
+/// trusting the pointer (eg. passed by C code).
+///
+/// Tags are not removed automatically, the pointer must be untagged using
+/// [`Lua::untag_light_userdata`] when the value is freed.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{LightUserData, Lua, Result, Tagged};
+/// # fn main() -> Result<()> {
+/// struct Connection(u32);
+/// struct Window;
+///
+/// let lua = Lua::new();
+/// let conn = Box::into_raw(Box::new(Connection(1)));
+/// let value = lua.pack(Tagged(conn))?;
+/// assert_eq!(lua.unpack::<Tagged<Connection>>(value.clone())?, Tagged(conn));
+/// assert!(lua.unpack::<Tagged<Window>>(value).is_err());
+///
+/// // Untagged pointers are rejected too
+/// let value = lua.pack(LightUserData(1 as *mut _))?;
+/// assert!(lua.unpack::<Tagged<Connection>>(value).is_err());
+///
+/// lua.untag_light_userdata(conn);
+/// let conn = unsafe { Box::from_raw(conn) };
+/// assert_eq!(conn.0, 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Tagged<T>(pub *mut T);
+
+impl<T> Clone for Tagged<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Tagged<T> {}
+
+impl<T> PartialEq for Tagged<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for Tagged<T> {}
+
+impl<T> fmt::Debug for Tagged<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Tagged").field(&self.0).finish()
+    }
+}
+
 pub(crate) type Callback<'a> = Box<dyn Fn(Lua, MultiValue) -> Result<MultiValue> + 'a>;
 
+pub(crate) type FieldValue = Box<dyn FnOnce(&Lua) -> Result<Value> + 'static>;
+
+// A Lua C function implementing a field getter and a (function) pointer passed to it as upvalue
+pub(crate) type FieldAccessor = (ffi::lua_CFunction, *const c_void);
+
 pub(crate) struct Upvalue<T> {
     pub(crate) data: T,
     pub(crate) extra: Arc<UnsafeCell<ExtraData>>,
@@ -39,13 +105,13 @@ pub(crate) type CallbackUpvalue = Upvalue<Callback<'static>>;
 
 #[cfg(feature = "async")]
 pub(crate) type AsyncCallback<'a> =
-    Box<dyn Fn(Lua, MultiValue) -> LocalBoxFuture<Result<MultiValue>> + 'a>;
+    Box<dyn Fn(Lua, MultiValue) -> LocalBoxFuture<'static, Result<MultiValue>> + 'a>;
 
 #[cfg(feature = "async")]
-pub(crate) type AsyncCallbackUpvalue = Upvalue<AsyncCallback<'static, 'static>>;
+pub(crate) type AsyncCallbackUpvalue = Upvalue<AsyncCallback<'static>>;
 
 #[cfg(feature = "async")]
-pub(crate) type AsyncPollUpvalue = Upvalue<LocalBoxFuture<'static, Result<MultiValue<'static>>>>;
+pub(crate) type AsyncPollUpvalue = Upvalue<LocalBoxFuture<'static, Result<MultiValue>>>;
 
 /// Type to set next Luau VM action after executing interrupt function.
 #[cfg(any(feature = "luau", doc))]
@@ -67,12 +133,40 @@ pub(crate) type InterruptCallback = Arc<dyn Fn() -> Result<VmState> + Send>;
 #[cfg(all(feature = "luau", not(feature = "send")))]
 pub(crate) type InterruptCallback = Arc<dyn Fn() -> Result<VmState>>;
 
-#[cfg(all(feature = "send", feature = "lua54"))]
+#[cfg(feature = "send")]
+pub(crate) type GlobalsInterceptor =
+    Arc<dyn Fn(&Lua, GlobalAccess, &str) -> Result<GlobalPolicy> + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type GlobalsInterceptor = Arc<dyn Fn(&Lua, GlobalAccess, &str) -> Result<GlobalPolicy>>;
+
+#[cfg(feature = "serialize")]
+pub(crate) type UserDataSerializer = fn(&AnyUserData) -> Result<serde_value::Value>;
+
+#[cfg(feature = "send")]
 pub(crate) type WarnCallback = Box<dyn Fn(&Lua, &CStr, bool) -> Result<()> + Send>;
 
-#[cfg(all(not(feature = "send"), feature = "lua54"))]
+#[cfg(not(feature = "send"))]
 pub(crate) type WarnCallback = Box<dyn Fn(&Lua, &CStr, bool) -> Result<()>>;
 
+#[cfg(feature = "send")]
+pub(crate) type MemoryObserverCallback = Arc<dyn Fn(&Lua, &MemoryEvent) -> Result<()> + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type MemoryObserverCallback = Arc<dyn Fn(&Lua, &MemoryEvent) -> Result<()>>;
+
+#[cfg(all(feature = "async", feature = "send"))]
+pub(crate) type YieldAsyncHook = Arc<dyn Fn(&Lua) -> Result<()> + Send>;
+
+#[cfg(all(feature = "async", not(feature = "send")))]
+pub(crate) type YieldAsyncHook = Arc<dyn Fn(&Lua) -> Result<()>>;
+
+#[cfg(all(feature = "send", not(feature = "module")))]
+pub(crate) type CloseCallback = Box<dyn FnOnce(*mut ffi::lua_State) + Send>;
+
+#[cfg(all(not(feature = "send"), not(feature = "module")))]
+pub(crate) type CloseCallback = Box<dyn FnOnce(*mut ffi::lua_State)>;
+
 #[cfg(feature = "send")]
 pub trait MaybeSend: Send {}
 #[cfg(feature = "send")]
@@ -176,35 +270,35 @@ impl RegistryKey {
     }
 }
 
+// Handle of a value stored in the auxiliary reference thread.
+//
+// The stack slot of the value can be changed by `Lua::compact_refs`, so it must be looked up
+// using `LuaRef::index` every time the value is accessed.
 pub(crate) struct LuaRef {
     pub(crate) lua: Lua,
-    pub(crate) index: c_int,
+    pub(crate) handle: c_int,
     pub(crate) drop: bool,
 }
 
 impl LuaRef {
-    pub(crate) const fn new(lua: Lua, index: c_int) -> Self {
+    pub(crate) const fn new(lua: Lua, handle: c_int) -> Self {
         LuaRef {
             lua,
-            index,
+            handle,
             drop: true,
         }
     }
 
-
-    #[cfg(feature = "unstable")]
+    // Returns the stack index of the value in the reference thread
     #[inline]
-    pub(crate) fn into_owned(self) -> LuaOwnedRef {
-        assert!(self.drop, "Cannot turn non-drop reference into owned");
-        let owned_ref = LuaOwnedRef::new(self.lua.clone(), self.index);
-        mem::forget(self);
-        owned_ref
+    pub(crate) fn index(&self) -> c_int {
+        self.lua.ref_index(self.handle)
     }
 }
 
 impl fmt::Debug for LuaRef {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Ref({})", self.index)
+        write!(f, "Ref({})", self.index())
     }
 }
 
@@ -217,7 +311,7 @@ impl Clone for LuaRef {
 impl Drop for LuaRef {
     fn drop(&mut self) {
         if self.drop {
-            self.lua.drop_ref_index(self.index);
+            self.lua.drop_ref(self.handle);
         }
     }
 }
@@ -236,68 +330,10 @@ impl PartialEq for LuaRef {
     }
 }
 
-#[cfg(feature = "unstable")]
-pub(crate) struct LuaOwnedRef {
-    pub(crate) lua: Lua,
-    pub(crate) index: c_int,
-    _non_send: std::marker::PhantomData<*const ()>,
-}
-
-#[cfg(feature = "unstable")]
-impl fmt::Debug for LuaOwnedRef {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "OwnedRef({})", self.index)
-    }
-}
-
-#[cfg(feature = "unstable")]
-impl Clone for LuaOwnedRef {
-    fn clone(&self) -> Self {
-        self.as_ref().clone().into_owned()
-    }
-}
-
-#[cfg(feature = "unstable")]
-impl Drop for LuaOwnedRef {
-    fn drop(&mut self) {
-        self.lua.drop_ref_index(self.index);
-    }
-}
-
-#[cfg(feature = "unstable")]
-impl LuaOwnedRef {
-    pub(crate) const fn new(lua: Lua, index: c_int) -> Self {
-        #[cfg(feature = "send")]
-        {
-            let _lua = lua;
-            let _index = index;
-            panic!("mlua must be compiled without \"send\" feature to use Owned types");
-        }
-
-        #[cfg(not(feature = "send"))]
-        LuaOwnedRef {
-            lua,
-            index,
-            _non_send: std::marker::PhantomData,
-        }
-    }
-
-    pub(crate) const fn as_ref(&self) -> LuaRef {
-        LuaRef {
-            lua: &self.lua,
-            index: self.index,
-            drop: false,
-        }
-    }
-}
-
 #[cfg(test)]
 mod assertions {
     use super::*;
 
     static_assertions::assert_impl_all!(RegistryKey: Send, Sync);
     static_assertions::assert_not_impl_any!(LuaRef: Send);
-
-    #[cfg(feature = "unstable")]
-    static_assertions::assert_not_impl_any!(LuaOwnedRef: Send);
 }
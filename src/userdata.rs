@@ -3,8 +3,8 @@ use std::cell::{Ref, RefCell, RefMut};
 use std::fmt;
 use std::hash::Hash;
 use std::mem;
-use std::ops::{Deref, DerefMut};
-use std::os::raw::{c_char, c_int};
+use std::ops::{Add, Deref, DerefMut, Div, Mul, Sub};
+use std::os::raw::{c_char, c_int, c_void};
 use std::string::String as StdString;
 
 #[cfg(feature = "async")]
@@ -21,7 +21,7 @@ use crate::ffi;
 use crate::function::Function;
 use crate::lua::Lua;
 use crate::table::{Table, TablePairs};
-use crate::types::{Callback, LuaRef, MaybeSend};
+use crate::types::{Callback, FieldAccessor, FieldValue, LightUserData, LuaRef, MaybeSend};
 use crate::util::{check_stack, get_userdata, take_userdata, StackGuard};
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Value};
 
@@ -31,6 +31,9 @@ use crate::types::AsyncCallback;
 #[cfg(feature = "lua54")]
 pub(crate) const USER_VALUE_MAXSLOT: usize = 8;
 
+// Registry key of the table mapping raw pointers to userdata
+const RAW_POINTERS_KEY: &str = "__mlua_userdata_pointers";
+
 /// Kinds of metamethods that can be overridden.
 ///
 /// Currently, this mechanism does not allow overriding the `__gc` metamethod, since there is
@@ -90,6 +93,9 @@ pub enum MetaMethod {
     /// The `<=` operator.
     Le,
     /// Index access `obj[key]`.
+    ///
+    /// When a userdata type has regular methods or fields, a function set as `__index` is used as
+    /// a fall-back: it's called only if the key is not found among them.
     Index,
     /// Index write access `obj[key] = value`.
     NewIndex,
@@ -243,6 +249,11 @@ pub trait UserDataMethods<T> {
     ///
     /// If `add_meta_method` is used to set the `__index` metamethod, the `__index` metamethod will
     /// be used as a fall-back if no regular method is found.
+    ///
+    /// In Luau `userdata:method()` calls are dispatched directly using the `__namecall`
+    /// metamethod, skipping `__index` lookup, unless the type has async methods or a custom
+    /// `__index` metamethod. Other Lua versions have no such fast path and always look up
+    /// methods using `__index`.
     fn add_method<M, A, R>(&mut self, name: impl AsRef<str>, method: M)
     where
         M: Fn(Lua, &T, A) -> Result<R> + MaybeSend + 'static,
@@ -275,7 +286,32 @@ pub trait UserDataMethods<T> {
         T: Clone,
         M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLuaMulti;
+
+    /// Add an async method which accepts a mutably borrowed `T` as the first parameter and returns
+    /// Future.
+    ///
+    /// The method receives a [`UserDataRefMut`] that can be moved into the returned future, so
+    /// the userdata stays mutably borrowed until the future completes (or is dropped). Calling
+    /// another method that borrows it in the meantime fails with [`UserDataBorrowMutError`]
+    /// (or [`UserDataBorrowError`]).
+    ///
+    /// Refer to [`add_method`] for more information about the implementation.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`add_method`]: #method.add_method
+    /// [`UserDataBorrowMutError`]: crate::Error::UserDataBorrowMutError
+    /// [`UserDataBorrowError`]: crate::Error::UserDataBorrowError
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    fn add_async_method_mut<M, A, MR, R>(&mut self, name: impl AsRef<str>, method: M)
+    where
+        T: 'static,
+        M: Fn(Lua, UserDataRefMut<'static, T>, A) -> MR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti;
 
     /// Add a regular method as a function which accepts generic arguments, the first argument will
@@ -305,6 +341,65 @@ pub trait UserDataMethods<T> {
         A: FromLuaMulti,
         R: IntoLuaMulti;
 
+    /// Add an overload of a regular method which accepts a `&T` as the first parameter.
+    ///
+    /// Several overloads (added using this method or [`add_function_overload`]) can be
+    /// registered under the same name. When the method is called, the overloads are tried in
+    /// order of registration, and the first one whose parameters can be converted from the
+    /// arguments is called. If none matches, an error listing the signatures of the overloads is
+    /// raised.
+    ///
+    /// Keep in mind that Lua conversions are lenient (eg. numbers can be converted to strings,
+    /// and extra arguments are ignored), so more specific overloads and overloads with more
+    /// parameters should be registered first.
+    ///
+    /// Not supported for non-static userdata created by [`Scope`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, UserData, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// struct Canvas;
+    ///
+    /// impl UserData for Canvas {
+    ///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    ///         methods.add_method_overload("draw", |_, _, (x, y): (f64, f64)| {
+    ///             Ok(format!("point {x} {y}"))
+    ///         });
+    ///         methods.add_method_overload("draw", |_, _, text: String| {
+    ///             Ok(format!("text {text}"))
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// let lua = Lua::new();
+    /// lua.globals().set("canvas", Canvas)?;
+    /// assert_eq!(lua.load("canvas:draw(1, 2)").eval::<String>()?, "point 1 2");
+    /// assert_eq!(lua.load("canvas:draw('hi')").eval::<String>()?, "text hi");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`add_function_overload`]: #method.add_function_overload
+    /// [`Scope`]: crate::Scope
+    fn add_method_overload<M, A, R>(&mut self, name: impl AsRef<str>, method: M)
+    where
+        M: Fn(Lua, &T, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti;
+
+    /// Add an overload of a regular method as a function which accepts generic arguments.
+    ///
+    /// Refer to [`add_method_overload`] for more information about overloading.
+    ///
+    /// [`add_method_overload`]: #method.add_method_overload
+    fn add_function_overload<F, A, R>(&mut self, name: impl AsRef<str>, function: F)
+    where
+        F: Fn(Lua, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti;
+
     /// Add a regular method as an async function which accepts generic arguments
     /// and returns Future.
     ///
@@ -319,7 +414,7 @@ pub trait UserDataMethods<T> {
     where
         F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti;
 
     /// Add a metamethod which accepts a `&T` as the first parameter.
@@ -365,7 +460,7 @@ pub trait UserDataMethods<T> {
         T: Clone,
         M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti;
 
     /// Add a metamethod which accepts generic arguments.
@@ -403,9 +498,116 @@ pub trait UserDataMethods<T> {
     where
         F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti;
 
+    /// Adds `__add`, `__sub`, `__mul` and `__div` metamethods implemented using the
+    /// [`Add`], [`Sub`], [`Mul`] and [`Div`] traits of `T`.
+    ///
+    /// The left operand must be a userdata of type `T` and the right operand is converted to `Rhs`.
+    /// The left operand is cloned before applying the operator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::ops::{Add, Div, Mul, Sub};
+    /// # use mlua::{Lua, Result, UserData, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// #[derive(Clone, Copy, PartialEq, PartialOrd)]
+    /// struct Meters(f64);
+    ///
+    /// impl Add<f64> for Meters {
+    ///     type Output = Meters;
+    ///     fn add(self, rhs: f64) -> Meters { Meters(self.0 + rhs) }
+    /// }
+    /// # impl Sub<f64> for Meters {
+    /// #     type Output = Meters;
+    /// #     fn sub(self, rhs: f64) -> Meters { Meters(self.0 - rhs) }
+    /// # }
+    /// # impl Mul<f64> for Meters {
+    /// #     type Output = Meters;
+    /// #     fn mul(self, rhs: f64) -> Meters { Meters(self.0 * rhs) }
+    /// # }
+    /// # impl Div<f64> for Meters {
+    /// #     type Output = Meters;
+    /// #     fn div(self, rhs: f64) -> Meters { Meters(self.0 / rhs) }
+    /// # }
+    /// // Sub, Mul and Div are implemented in the same way
+    ///
+    /// impl UserData for Meters {
+    ///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    ///         methods.add_std_ops::<f64, Meters>();
+    ///         methods.add_std_cmp_ops();
+    ///     }
+    /// }
+    ///
+    /// lua.globals().set("m", Meters(10.0))?;
+    /// lua.load("assert((m + 5) > m and (m * 2) / 2 == m)").exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn add_std_ops<Rhs, Out>(&mut self)
+    where
+        T: Add<Rhs, Output = Out>
+            + Sub<Rhs, Output = Out>
+            + Mul<Rhs, Output = Out>
+            + Div<Rhs, Output = Out>
+            + Clone
+            + 'static,
+        Rhs: FromLua,
+        Out: IntoLua,
+    {
+        self.add_meta_function(MetaMethod::Add, |_, (lhs, rhs): (UserDataRef<T>, Rhs)| {
+            Ok(T::clone(&lhs) + rhs)
+        });
+        self.add_meta_function(MetaMethod::Sub, |_, (lhs, rhs): (UserDataRef<T>, Rhs)| {
+            Ok(T::clone(&lhs) - rhs)
+        });
+        self.add_meta_function(MetaMethod::Mul, |_, (lhs, rhs): (UserDataRef<T>, Rhs)| {
+            Ok(T::clone(&lhs) * rhs)
+        });
+        self.add_meta_function(MetaMethod::Div, |_, (lhs, rhs): (UserDataRef<T>, Rhs)| {
+            Ok(T::clone(&lhs) / rhs)
+        });
+    }
+
+    /// Adds `__eq`, `__lt` and `__le` metamethods implemented using the [`PartialEq`] and
+    /// [`PartialOrd`] traits of `T`.
+    ///
+    /// Both operands must be userdata of type `T`.
+    fn add_std_cmp_ops(&mut self)
+    where
+        T: PartialOrd + 'static,
+    {
+        self.add_meta_function(
+            MetaMethod::Eq,
+            |_, (lhs, rhs): (UserDataRef<T>, UserDataRef<T>)| Ok(*lhs == *rhs),
+        );
+        self.add_meta_function(
+            MetaMethod::Lt,
+            |_, (lhs, rhs): (UserDataRef<T>, UserDataRef<T>)| Ok(*lhs < *rhs),
+        );
+        self.add_meta_function(
+            MetaMethod::Le,
+            |_, (lhs, rhs): (UserDataRef<T>, UserDataRef<T>)| Ok(*lhs <= *rhs),
+        );
+    }
+
+    /// Sets whether the method `name` is dispatched using the Luau `__namecall` fast path.
+    ///
+    /// By default regular methods are dispatched, unless they are shadowed by field getters or
+    /// the type has async methods or a custom `__index` metamethod (see [`add_method`]).
+    /// Dispatched methods are looked up using interned method names and called without going
+    /// through `__index`, so they cannot yield. Async methods are never dispatched.
+    ///
+    /// Requires `feature = "luau"`
+    ///
+    /// [`add_method`]: #method.add_method
+    #[cfg(any(feature = "luau", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    fn set_namecall(&mut self, _name: impl AsRef<str>, _enabled: bool) {}
+
     //
     // Below are internal methods used in generated code
     //
@@ -429,6 +631,53 @@ pub trait UserDataMethods<T> {
 ///
 /// [`UserData`]: crate::UserData
 pub trait UserDataFields<T> {
+    /// Add a static field to the `UserData`.
+    ///
+    /// Static fields are implemented by updating the `__index` metamethod and returning the
+    /// accessed field. The value is converted to Lua once, when the type metatable is created,
+    /// and is shared between all instances of the type.
+    ///
+    /// Static fields have lower priority than field getters, but higher than regular methods.
+    fn add_field<V>(&mut self, name: impl AsRef<str>, value: V)
+    where
+        V: IntoLua + MaybeSend + 'static;
+
+    /// Add a regular field getter which returns a clone of the value referenced by `accessor`.
+    ///
+    /// This is a cheaper alternative to [`add_field_method_get`] for plain struct fields.
+    /// The `accessor` is a function pointer, so no boxed closure is allocated per getter: it is
+    /// stored directly in the Lua C function which implements the getter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, UserData, UserDataFields};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// struct Point {
+    ///     x: f64,
+    ///     y: f64,
+    /// }
+    ///
+    /// impl UserData for Point {
+    ///     fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+    ///         fields.add_field("dimensions", 2);
+    ///         fields.add_field_from("x", |this| &this.x);
+    ///         fields.add_field_from("y", |this| &this.y);
+    ///     }
+    /// }
+    ///
+    /// lua.globals().set("p", Point { x: 1.0, y: 2.0 })?;
+    /// lua.load("assert(p.x + p.y == 3 and p.dimensions == 2)").exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`add_field_method_get`]: #method.add_field_method_get
+    fn add_field_from<R>(&mut self, name: impl AsRef<str>, accessor: fn(&T) -> &R)
+    where
+        R: IntoLua + Clone + 'static;
+
     /// Add a regular field getter as a method which accepts a `&T` as the parameter.
     ///
     /// Regular field getters are implemented by overriding the `__index` metamethod and returning the
@@ -477,6 +726,27 @@ pub trait UserDataFields<T> {
         F: FnMut(Lua, AnyUserData, A) -> Result<()> + MaybeSend + 'static,
         A: FromLua;
 
+    /// Add an async field getter as a method which accepts a `T` as the parameter and returns
+    /// Future. The passed `T` is cloned from the original value.
+    ///
+    /// The field can only be accessed from a coroutine driven by an async executor (eg. inside
+    /// an async function called with [`Function::call_async`]): accessing the field yields
+    /// to the executor until the future is ready.
+    ///
+    /// Refer to [`add_field_method_get`] for more information about the implementation.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`add_field_method_get`]: #method.add_field_method_get
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    fn add_async_field_method_get<M, MR, R>(&mut self, name: impl AsRef<str>, method: M)
+    where
+        T: Clone,
+        M: Fn(Lua, T) -> MR + MaybeSend + 'static,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLua;
+
     /// Add a metamethod value computed from `f`.
     ///
     /// This will initialize the metamethod value from `f` on `UserData` creation.
@@ -490,6 +760,57 @@ pub trait UserDataFields<T> {
         F: Fn(Lua) -> Result<R> + MaybeSend + 'static,
         R: IntoLua;
 
+    /// Add a callback notified whenever Lua sets a regular field of the userdata.
+    ///
+    /// The callback is called after the field setter with the userdata, the field name, the old
+    /// value and the new (assigned) value. The old value is read using the field getter, or is
+    /// `Nil` if the field has no getter. Errors returned by the callback are propagated to Lua.
+    ///
+    /// This applies to all field setters of the type, regardless of the order of registration.
+    /// Several callbacks can be added, they are called in order of registration.
+    ///
+    /// Not supported for non-static userdata created by [`Scope`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::Mutex;
+    /// # use mlua::{FromLua, Lua, Result, UserData, UserDataFields};
+    /// # fn main() -> Result<()> {
+    /// struct Light {
+    ///     intensity: f64,
+    /// }
+    ///
+    /// static CHANGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    ///
+    /// impl UserData for Light {
+    ///     fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+    ///         fields.add_field_method_get("intensity", |_, this| Ok(this.intensity));
+    ///         fields.add_field_method_set("intensity", |_, this, val| {
+    ///             this.intensity = val;
+    ///             Ok(())
+    ///         });
+    ///         fields.on_change(|lua, _, name, old, new| {
+    ///             let (old, new) = (f64::from_lua(old, &lua)?, f64::from_lua(new, &lua)?);
+    ///             CHANGES.lock().unwrap().push(format!("{name}: {old} -> {new}"));
+    ///             Ok(())
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// let lua = Lua::new();
+    /// lua.globals().set("light", Light { intensity: 0.5 })?;
+    /// lua.load("light.intensity = 1").exec()?;
+    /// assert_eq!(*CHANGES.lock().unwrap(), ["intensity: 0.5 -> 1"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Scope`]: crate::Scope
+    fn on_change<F>(&mut self, callback: F)
+    where
+        F: Fn(Lua, AnyUserData, &str, Value, Value) -> Result<()> + MaybeSend + 'static;
+
     //
     // Below are internal methods used in generated code
     //
@@ -499,6 +820,16 @@ pub trait UserDataFields<T> {
 
     #[doc(hidden)]
     fn add_field_setter(&mut self, _name: String, _callback: Callback<'static>) {}
+
+    #[doc(hidden)]
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    fn add_async_field_getter(&mut self, _name: String, _callback: AsyncCallback<'static>) {}
+
+    #[doc(hidden)]
+    fn add_field_value(&mut self, _name: String, _value: FieldValue) {}
+
+    #[doc(hidden)]
+    fn add_field_accessor(&mut self, _name: String, _accessor: FieldAccessor) {}
 }
 
 /// Trait for custom userdata types.
@@ -627,6 +958,20 @@ impl<T> UserDataCell<T> {
             })
     }
 
+    // Returns a pointer to the wrapped value, which is not moved while the userdata is alive.
+    // The value itself is not accessed, so the userdata can be borrowed.
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> Result<*mut T> {
+        let variant = unsafe { &mut *self.0.as_ptr() };
+        variant.try_deref_mut().map(|data| data as *mut T)
+    }
+
+    // Returns true if the wrapped value is currently borrowed.
+    #[inline]
+    pub(crate) fn is_borrowed(&self) -> bool {
+        self.0.try_borrow_mut().is_err()
+    }
+
     // Consumes this `UserDataCell`, returning the wrapped value.
     #[inline]
     fn into_inner(self) -> Result<T> {
@@ -708,6 +1053,45 @@ impl<T> Deref for UserDataVariant<T> {
     }
 }
 
+/// Trait for serializing userdata types that do not implement [`serde::Serialize`].
+///
+/// The implementation is used after registering the type using
+/// [`Lua::register_userdata_serializer`]. The default implementation serializes the userdata as
+/// an opaque placeholder: a map with a single `__userdata` key holding the type name.
+///
+/// Requires `feature = "serialize"`
+///
+/// [`serde::Serialize`]: https://docs.serde.rs/serde/ser/trait.Serialize.html
+#[cfg(feature = "serialize")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+pub trait UserDataSerialize: 'static {
+    /// Serializes this userdata using the given serializer.
+    fn serialize_userdata<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("__userdata", type_name::<Self>())?;
+        map.end()
+    }
+}
+
+// Implements `serde::Serialize` for `UserDataSerialize` types
+#[cfg(feature = "serialize")]
+pub(crate) struct UserDataSerializeWrapper<'a, T>(pub(crate) &'a T);
+
+#[cfg(feature = "serialize")]
+impl<'a, T: UserDataSerialize> Serialize for UserDataSerializeWrapper<'a, T> {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize_userdata(serializer)
+    }
+}
+
 #[cfg(feature = "serialize")]
 struct UserDataSerializeError;
 
@@ -740,15 +1124,34 @@ impl Serialize for UserDataSerializeError {
 #[derive(Clone, Debug)]
 pub struct AnyUserData(pub(crate) LuaRef);
 
-#[cfg(feature = "unstable")]
-#[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
-#[derive(Clone, Debug)]
-pub struct OwnedAnyUserData(pub(crate) crate::types::LuaOwnedRef);
+/// Owned handle to an internal Lua userdata.
+///
+/// See [`OwnedTable`] for the semantics of owned handles.
+///
+/// [`OwnedTable`]: crate::OwnedTable
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedAnyUserData(pub(crate) AnyUserData);
 
-#[cfg(feature = "unstable")]
 impl OwnedAnyUserData {
-    pub const fn as_ref(&self) -> AnyUserData {
-        AnyUserData(self.0.as_ref())
+    /// Returns a handle to the underlying Lua userdata.
+    #[inline]
+    pub fn to_ref(&self) -> AnyUserData {
+        self.0.clone()
+    }
+
+    /// Converts this owned handle into a regular handle to the Lua userdata.
+    #[inline]
+    pub fn into_ref(self) -> AnyUserData {
+        self.0
+    }
+}
+
+impl Deref for OwnedAnyUserData {
+    type Target = AnyUserData;
+
+    #[inline]
+    fn deref(&self) -> &AnyUserData {
+        &self.0
     }
 }
 
@@ -788,6 +1191,7 @@ impl AnyUserData {
     /// Sets the special "destructed" metatable that prevents any further operations with this userdata.
     ///
     /// Keeps associated user values unchanged (they will be collected by Lua's GC).
+    /// Pending async method calls of the userdata are aborted.
     pub fn take<T: 'static>(&self) -> Result<T> {
         let lua = self.0.lua.clone();
         let state = lua.state();
@@ -800,6 +1204,8 @@ impl AnyUserData {
                 Some(type_id) if type_id == TypeId::of::<T>() => {
                     // Try to borrow userdata exclusively
                     let _ = (*get_userdata::<UserDataCell<T>>(state, -1)).try_borrow_mut()?;
+                    #[cfg(feature = "async")]
+                    lua.abort_userdata_tasks(ffi::lua_touserdata(state, -1));
                     take_userdata::<UserDataCell<T>>(state).into_inner()
                 }
                 _ => Err(Error::UserDataTypeMismatch),
@@ -807,6 +1213,74 @@ impl AnyUserData {
         }
     }
 
+    /// Returns a raw pointer to the value of this userdata if it is of type `T`.
+    ///
+    /// The value is pinned: it is never moved while the userdata is alive, so the pointer can be
+    /// stored by C libraries in their own structures while Lua owns the object. The pointer
+    /// remains valid until the userdata is garbage collected. Taking the value using
+    /// [`AnyUserData::take`] invalidates the pointer as well, and [`AnyUserData::from_raw_ptr`]
+    /// returns `None` for it afterwards.
+    ///
+    /// Accessing the value through the pointer bypasses borrow checking of the userdata, so the
+    /// caller must ensure that it is not accessed while borrowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `UserDataBorrowMutError` if the userdata cannot be borrowed mutably (eg. was
+    /// created from a shared reference).
+    /// Returns a `UserDataTypeMismatch` if the userdata is not of type `T`.
+    pub fn as_raw_ptr<T: 'static>(&self) -> Result<*mut T> {
+        let ptr = self.inspect(|cell: &UserDataCell<T>| cell.as_ptr())?;
+        let lua = &self.0.lua;
+        let pointers = match lua.named_registry_value::<Option<Table>>(RAW_POINTERS_KEY)? {
+            Some(pointers) => pointers,
+            None => {
+                // Pointers must not keep the userdata alive
+                let pointers = lua.create_table()?;
+                pointers.set_metatable(Some(lua.create_table_from([("__mode", "v")])?));
+                lua.set_named_registry_value(RAW_POINTERS_KEY, pointers.clone())?;
+                pointers
+            }
+        };
+        pointers.raw_set(LightUserData(ptr as *mut c_void), self.clone())?;
+        Ok(ptr)
+    }
+
+    /// Recovers the userdata from a pointer returned by [`AnyUserData::as_raw_ptr`].
+    ///
+    /// Returns `None` if the pointer does not belong to a live userdata of type `T` of this Lua
+    /// instance.
+    pub fn from_raw_ptr<T: 'static>(lua: &Lua, ptr: *mut T) -> Result<Option<AnyUserData>> {
+        let pointers = match lua.named_registry_value::<Option<Table>>(RAW_POINTERS_KEY)? {
+            Some(pointers) => pointers,
+            None => return Ok(None),
+        };
+        let key = LightUserData(ptr as *mut c_void);
+        match pointers.raw_get::<_, Option<AnyUserData>>(key)? {
+            // Userdata can be destructed
+            Some(ud) if ud.inspect(|_: &UserDataCell<T>| Ok(())).is_ok() => Ok(Some(ud)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Aborts pending async method calls of this userdata.
+    ///
+    /// Futures of the aborted calls are dropped and the calls raise [`Error::Aborted`] in Lua.
+    /// Returns the number of aborted calls.
+    ///
+    /// Async method calls are also aborted when the userdata is destroyed (garbage collected
+    /// or taken using [`AnyUserData::take`]), so that their futures do not keep resources
+    /// of the userdata. On Luau, only calls of taken userdata are aborted automatically.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn abort_pending(&self) -> usize {
+        let lua = &self.0.lua;
+        let ud_ptr = unsafe { ffi::lua_topointer(lua.ref_thread(), self.0.index()) };
+        lua.abort_userdata_tasks(ud_ptr)
+    }
+
     /// Sets an associated value to this `AnyUserData`.
     ///
     /// The value may be any Lua value whatsoever, and can be retrieved with [`get_user_value`].
@@ -1030,11 +1504,12 @@ impl AnyUserData {
         }
     }
 
-    #[cfg(feature = "unstable")]
-    #[cfg_attr(docsrs, doc(cfg(feature = "unstable")))]
+    /// Converts this handle into an owned version.
+    ///
+    /// See [`OwnedAnyUserData`] for details.
     #[inline]
     pub fn into_owned(self) -> OwnedAnyUserData {
-        OwnedAnyUserData(self.0.into_owned())
+        OwnedAnyUserData(self)
     }
 
     pub(crate) fn equals<T: AsRef<Self>>(&self, other: T) -> Result<bool> {
@@ -1068,12 +1543,12 @@ impl AnyUserData {
             check_stack(state, 2)?;
 
             // Userdata can be unregistered or destructed
-            lua.push_userdata_ref(&self.0)?;
+            let type_id = lua.push_userdata_ref(&self.0)?;
 
             let ud = &*get_userdata::<UserDataCell<()>>(state, -1);
             match &*ud.0.try_borrow().map_err(|_| Error::UserDataBorrowError)? {
                 UserDataVariant::Serializable(_) => Result::Ok(true),
-                _ => Result::Ok(false),
+                _ => Result::Ok(type_id.and_then(|id| lua.userdata_serializer(id)).is_some()),
             }
         };
         is_serializable().unwrap_or(false)
@@ -1203,18 +1678,27 @@ impl Serialize for AnyUserData {
     {
         let lua = self.0.lua.clone();
         let state = lua.state();
-        let data = unsafe {
+        let (data, type_id) = unsafe {
             let _sg = StackGuard::new(state);
             check_stack(state, 3).map_err(ser::Error::custom)?;
 
-            lua.push_userdata_ref(&self.0).map_err(ser::Error::custom)?;
+            let type_id = lua.push_userdata_ref(&self.0).map_err(ser::Error::custom)?;
             let ud = &*get_userdata::<UserDataCell<()>>(state, -1);
-            ud.0.try_borrow()
-                .map_err(|_| ser::Error::custom(Error::UserDataBorrowError))?
+            let data =
+                ud.0.try_borrow()
+                    .map_err(|_| ser::Error::custom(Error::UserDataBorrowError))?;
+            (data, type_id)
         };
-        match &*data {
-            UserDataVariant::Serializable(ser) => ser.serialize(serializer),
-            _ => UserDataSerializeError.serialize(serializer),
+        if let UserDataVariant::Serializable(ser) = &*data {
+            return ser.serialize(serializer);
+        }
+        drop(data);
+
+        match type_id.and_then(|id| lua.userdata_serializer(id)) {
+            Some(serialize) => serialize(self)
+                .map_err(ser::Error::custom)?
+                .serialize(serializer),
+            None => UserDataSerializeError.serialize(serializer),
         }
     }
 }
@@ -1286,6 +1770,5 @@ mod assertions {
 
     static_assertions::assert_not_impl_any!(AnyUserData: Send);
 
-    #[cfg(feature = "unstable")]
-    static_assertions::assert_not_impl_any!(OwnedAnyUserData: Send);
+    static_assertions::assert_impl_all!(OwnedAnyUserData: Send, Sync);
 }
@@ -29,7 +29,6 @@ pub trait AnyUserDataExt: Sealed {
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     fn call_async<'fut, A, R>(&self, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut;
 
@@ -54,7 +53,6 @@ pub trait AnyUserDataExt: Sealed {
         args: A,
     ) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut;
 
@@ -84,7 +82,6 @@ pub trait AnyUserDataExt: Sealed {
         args: A,
     ) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut;
 }
@@ -129,7 +126,6 @@ impl AnyUserDataExt for AnyUserData {
     #[cfg(feature = "async")]
     fn call_async<'fut, A, R>(&self, args: A) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
@@ -161,7 +157,6 @@ impl AnyUserDataExt for AnyUserData {
         args: A,
     ) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
@@ -189,7 +184,6 @@ impl AnyUserDataExt for AnyUserData {
         args: A,
     ) -> LocalBoxFuture<'fut, Result<R>>
     where
-        'lua: 'fut,
         A: IntoLuaMulti,
         R: FromLuaMulti + 'fut,
     {
@@ -1,32 +1,48 @@
 use std::any::{self, TypeId};
 use std::cell::{Ref, RefCell, RefMut};
 use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
 use std::string::String as StdString;
 use std::sync::{Arc, Mutex, RwLock};
 
 use crate::error::{Error, Result};
 use crate::ffi;
-use crate::lua::Lua;
-use crate::types::{Callback, MaybeSend};
+use crate::lua::{userdata_field_accessor, Lua};
+use crate::types::{Callback, FieldAccessor, FieldValue, MaybeSend};
 use crate::userdata::{
     AnyUserData, MetaMethod, UserData, UserDataCell, UserDataFields, UserDataMethods,
 };
 use crate::util::{check_stack, get_userdata, StackGuard};
-use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Value};
+use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Value};
 
-#[cfg(not(feature = "send"))]
 use std::rc::Rc;
 
 #[cfg(feature = "async")]
 use {
     crate::types::AsyncCallback,
-    futures_util::future::{self, TryFutureExt},
+    crate::userdata::UserDataRefMut,
+    futures_util::future::{self, LocalBoxFuture},
+    std::cell::Cell,
     std::future::Future,
+    std::pin::Pin,
+    std::ptr,
+    std::task::{Context, Poll, Waker},
 };
 
+/// Handle to registry for userdata methods and metamethods.
+///
+/// It implements [`UserDataFields`] and [`UserDataMethods`] traits and is passed to the closure
+/// in [`Lua::register_userdata_type`] to describe an external type `T`.
+///
+/// [`Lua::register_userdata_type`]: crate::Lua::register_userdata_type
 pub struct UserDataRegistrar<T: 'static> {
     // Fields
+    pub(crate) fields: Vec<(String, FieldValue)>,
+    pub(crate) field_accessors: Vec<(String, FieldAccessor)>,
     pub(crate) field_getters: Vec<(String, Callback<'static>)>,
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    pub(crate) async_field_getters: Vec<(String, AsyncCallback<'static>)>,
     pub(crate) field_setters: Vec<(String, Callback<'static>)>,
     #[allow(clippy::type_complexity)]
     pub(crate) meta_fields: Vec<(String, Box<dyn Fn(&Lua) -> Result<Value> + 'static>)>,
@@ -38,14 +54,38 @@ pub struct UserDataRegistrar<T: 'static> {
     pub(crate) meta_methods: Vec<(String, Callback<'static>)>,
     #[cfg(feature = "async")]
     pub(crate) async_meta_methods: Vec<(String, AsyncCallback<'static>)>,
+    #[cfg(feature = "luau")]
+    pub(crate) namecall: Vec<(String, bool)>,
+    // Overloads of methods, dispatched by a callback added to `methods`
+    overloads: Vec<(String, Overloads)>,
+    // Callbacks notified when a field is set, applied to setters on registration
+    change_hooks: Vec<FieldChangeHook>,
 
     _type: PhantomData<T>,
 }
 
+// A method overload with its signature and a check whether arguments match its parameters
+struct Overload {
+    signature: StdString,
+    is_method: bool,
+    #[allow(clippy::type_complexity)]
+    matches: Box<dyn Fn(&Lua, MultiValue) -> bool>,
+    callback: Callback<'static>,
+}
+
+// Overloads are shared with the dispatcher, so that they can be added after it's created
+type Overloads = Rc<RefCell<Vec<Overload>>>;
+
+type FieldChangeHook = Box<dyn Fn(Lua, AnyUserData, &str, Value, Value) -> Result<()>>;
+
 impl<T: 'static> UserDataRegistrar<T> {
     pub(crate) const fn new() -> Self {
         UserDataRegistrar {
+            fields: Vec::new(),
+            field_accessors: Vec::new(),
             field_getters: Vec::new(),
+            #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+            async_field_getters: Vec::new(),
             field_setters: Vec::new(),
             meta_fields: Vec::new(),
             methods: Vec::new(),
@@ -54,6 +94,10 @@ impl<T: 'static> UserDataRegistrar<T> {
             meta_methods: Vec::new(),
             #[cfg(feature = "async")]
             async_meta_methods: Vec::new(),
+            #[cfg(feature = "luau")]
+            namecall: Vec::new(),
+            overloads: Vec::new(),
+            change_hooks: Vec::new(),
             _type: PhantomData,
         }
     }
@@ -225,9 +269,9 @@ impl<T: 'static> UserDataRegistrar<T> {
     fn box_async_method<M, A, MR, R>(name: &str, method: M) -> AsyncCallback<'static>
     where
         T: Clone,
-        M: Fn(&Lua, T, A) -> MR + MaybeSend + 'static,
+        M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         let name = get_function_name::<T>(name);
@@ -242,16 +286,17 @@ impl<T: 'static> UserDataRegistrar<T> {
 
         Box::new(move |lua, mut args| {
             let front = args.pop_front();
+            let ud_ptr = front.as_ref().map_or(ptr::null(), Value::to_pointer);
             let call = |ud| {
                 // Self was at index 1, so we pass 2 here
-                let args = A::from_lua_multi_args(args, 2, Some(&name), lua)?;
-                Ok(method(lua, ud, args))
+                let args = A::from_lua_multi_args(args, 2, Some(&name), &lua)?;
+                Ok(method(lua.clone(), ud, args))
             };
 
             let fut_res = || {
                 if let Some(front) = front {
                     let state = lua.state();
-                    let userdata = AnyUserData::from_lua(front, lua)?;
+                    let userdata = AnyUserData::from_lua(front, &lua)?;
                     unsafe {
                         let _sg = StackGuard::new(state);
                         check_stack(state, 2)?;
@@ -304,7 +349,47 @@ impl<T: 'static> UserDataRegistrar<T> {
             };
             match fut_res() {
                 Ok(fut) => {
-                    Box::pin(fut.and_then(move |ret| future::ready(ret.into_lua_multi(lua))))
+                    let lua2 = lua.clone();
+                    let fut = async move { fut.await?.into_lua_multi(&lua2) };
+                    lua.track_userdata_task(ud_ptr, Box::pin(fut))
+                }
+                Err(e) => Box::pin(future::err(e)),
+            }
+        })
+    }
+
+    #[cfg(feature = "async")]
+    fn box_async_method_mut<M, A, MR, R>(name: &str, method: M) -> AsyncCallback<'static>
+    where
+        T: 'static,
+        M: Fn(Lua, UserDataRefMut<'static, T>, A) -> MR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLuaMulti,
+    {
+        let name = get_function_name::<T>(name);
+        Box::new(move |lua, mut args| {
+            let fut_res = || {
+                let front = match args.pop_front() {
+                    Some(front) => front,
+                    None => {
+                        let err = Error::from_lua_conversion("missing argument", "userdata", None);
+                        return Err(Error::bad_self_argument(&name, err));
+                    }
+                };
+                let ud_ptr = front.to_pointer();
+                // The future owns the borrow, which is released once the future is dropped
+                let ud = UserDataRefMut::<T>::from_value(front)
+                    .map_err(|err| Error::bad_self_argument(&name, err))?;
+                // Self was at index 1, so we pass 2 here
+                let args = A::from_lua_multi_args(args, 2, Some(&name), &lua)?;
+                Ok((method(lua.clone(), ud, args), ud_ptr))
+            };
+            match fut_res() {
+                Ok((fut, ud_ptr)) => {
+                    let lua2 = lua.clone();
+                    let fut = async move { fut.await?.into_lua_multi(&lua2) };
+                    lua.track_userdata_task(ud_ptr, Box::pin(fut))
                 }
                 Err(e) => Box::pin(future::err(e)),
             }
@@ -350,31 +435,163 @@ impl<T: 'static> UserDataRegistrar<T> {
     #[cfg(feature = "async")]
     fn box_async_function<F, A, FR, R>(name: &str, function: F) -> AsyncCallback<'static>
     where
-        F: Fn(&Lua, A) -> FR + MaybeSend + 'static,
+        F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         let name = get_function_name::<T>(name);
         Box::new(move |lua, args| {
-            let args = match A::from_lua_multi_args(args, 1, Some(&name), lua) {
+            let args = match A::from_lua_multi_args(args, 1, Some(&name), &lua) {
                 Ok(args) => args,
                 Err(e) => return Box::pin(future::err(e)),
             };
-            Box::pin(
-                function(lua, args).and_then(move |ret| future::ready(ret.into_lua_multi(lua))),
-            )
+            let fut = function(lua.clone(), args);
+            Box::pin(async move { fut.await?.into_lua_multi(&lua) })
+        })
+    }
+
+    fn add_overload(&mut self, name: &str, overload: Overload) {
+        if let Some((_, overloads)) = self.overloads.iter().find(|(n, _)| n == name) {
+            overloads.borrow_mut().push(overload);
+            return;
+        }
+        let overloads = Rc::new(RefCell::new(vec![overload]));
+        let dispatcher = Self::box_overloads(name, overloads.clone());
+        self.overloads.push((name.into(), overloads));
+        self.methods.push((name.into(), dispatcher));
+    }
+
+    fn box_overloads(name: &str, overloads: Overloads) -> Callback<'static> {
+        let name = get_function_name::<T>(name);
+        Box::new(move |lua, args| {
+            let overloads = overloads.borrow();
+            for overload in overloads.iter() {
+                if (overload.matches)(&lua, args.clone()) {
+                    return (overload.callback)(lua, args);
+                }
+            }
+
+            let skip = overloads.first().map_or(0, |o| o.is_method as usize);
+            let types = args.iter().skip(skip).map(|v| v.type_name());
+            let mut message = format!(
+                "no matching overload for '{name}' with arguments ({}), candidates are:",
+                types.collect::<Vec<_>>().join(", ")
+            );
+            for overload in overloads.iter() {
+                message.push_str(&format!("\n  {name}{}", overload.signature));
+            }
+            Err(Error::RuntimeError(message))
         })
     }
+
+    // Wraps field setters to call the change hooks after setting a field
+    pub(crate) fn apply_change_hooks(&mut self) {
+        if self.change_hooks.is_empty() {
+            return;
+        }
+        let hooks: Rc<[FieldChangeHook]> = mem::take(&mut self.change_hooks).into();
+        for (name, setter) in mem::take(&mut self.field_setters) {
+            let readable = self.field_getters.iter().any(|(k, _)| *k == name)
+                || self.field_accessors.iter().any(|(k, _)| *k == name);
+            let (hooks, field) = (hooks.clone(), name.clone());
+            let setter: Callback<'static> = Box::new(move |lua, args| {
+                let userdata = match args.get(0) {
+                    Some(Value::UserData(ud)) => ud.clone(),
+                    _ => return setter(lua, args),
+                };
+                let new = args.get(1).cloned().unwrap_or(Value::Nil);
+                let old = match readable {
+                    true => get_field(&lua, &userdata, &field)?,
+                    false => Value::Nil,
+                };
+                let result = setter(lua.clone(), args)?;
+                for hook in hooks.iter() {
+                    let (old, new) = (old.clone(), new.clone());
+                    hook(lua.clone(), userdata.clone(), &field, old, new)?;
+                }
+                Ok(result)
+            });
+            self.field_setters.push((name, setter));
+        }
+    }
+}
+
+// Reads the field of the userdata using the `__index` metamethod
+fn get_field(lua: &Lua, userdata: &AnyUserData, name: &str) -> Result<Value> {
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        check_stack(state, 4)?;
+
+        lua.push_ref(&userdata.0);
+        lua.push_value(Value::String(lua.create_string(name)?))?;
+        protect_lua!(state, 2, 1, fn(state) ffi::lua_gettable(state, -2))?;
+        Ok(lua.pop_value())
+    }
 }
 
 // Returns function name for the type `T`, without the module path
-fn get_function_name<T: 'static>(name: &str) -> StdString {
+pub(crate) fn get_function_name<T: 'static>(name: &str) -> StdString {
     let type_name = any::type_name::<T>().rsplitn(2, "::").next().unwrap();
     format!("{type_name}.{name}",)
 }
 
+// Returns the signature of parameters `A` without module paths and lifetimes,
+// eg. `(i64, String)`
+fn signature<A>() -> StdString {
+    let mut signature = StdString::new();
+    // Start of the current path in `signature`
+    let mut path_start = 0;
+    let mut chars = any::type_name::<A>().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            signature.truncate(path_start);
+            continue;
+        }
+        if c == '\'' {
+            // Skip lifetimes, eg. `'_, `
+            while chars
+                .next_if(|&c| c.is_alphanumeric() || c == '_')
+                .is_some()
+            {}
+            if chars.next_if_eq(&',').is_some() {
+                chars.next_if_eq(&' ');
+            }
+            continue;
+        }
+        signature.push(c);
+        if !(c.is_alphanumeric() || c == '_') {
+            path_start = signature.len();
+        }
+    }
+    if !signature.starts_with('(') {
+        signature = format!("({signature})");
+    }
+    signature
+}
+
 impl<T: 'static> UserDataFields<T> for UserDataRegistrar<T> {
+    fn add_field<V>(&mut self, name: impl AsRef<str>, value: V)
+    where
+        V: IntoLua + MaybeSend + 'static,
+    {
+        let name = name.as_ref().to_string();
+        self.fields
+            .push((name, Box::new(move |lua| value.into_lua(lua))));
+    }
+
+    fn add_field_from<R>(&mut self, name: impl AsRef<str>, accessor: fn(&T) -> &R)
+    where
+        R: IntoLua + Clone + 'static,
+    {
+        let name = name.as_ref().to_string();
+        let func = userdata_field_accessor::<T, R> as ffi::lua_CFunction;
+        let ptr = accessor as *const () as *const c_void;
+        self.field_accessors.push((name, (func, ptr)));
+    }
+
     fn add_field_method_get<M, R>(&mut self, name: impl AsRef<str>, method: M)
     where
         M: Fn(Lua, &T) -> Result<R> + MaybeSend + 'static,
@@ -415,6 +632,19 @@ impl<T: 'static> UserDataFields<T> for UserDataRegistrar<T> {
         self.field_setters.push((name.into(), func));
     }
 
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    fn add_async_field_method_get<M, MR, R>(&mut self, name: impl AsRef<str>, method: M)
+    where
+        T: Clone,
+        M: Fn(Lua, T) -> MR + MaybeSend + 'static,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLua,
+    {
+        let name = name.as_ref();
+        let method = Self::box_async_method(name, move |lua, data, ()| method(lua.clone(), data));
+        self.async_field_getters.push((name.into(), method));
+    }
+
     fn add_meta_field_with<F, R>(&mut self, name: impl AsRef<str>, f: F)
     where
         F: Fn(Lua) -> Result<R> + MaybeSend + 'static,
@@ -442,6 +672,13 @@ impl<T: 'static> UserDataFields<T> for UserDataRegistrar<T> {
         ));
     }
 
+    fn on_change<F>(&mut self, callback: F)
+    where
+        F: Fn(Lua, AnyUserData, &str, Value, Value) -> Result<()> + MaybeSend + 'static,
+    {
+        self.change_hooks.push(Box::new(callback));
+    }
+
     // Below are internal methods
 
     fn add_field_getter(&mut self, name: String, callback: Callback<'static>) {
@@ -451,6 +688,19 @@ impl<T: 'static> UserDataFields<T> for UserDataRegistrar<T> {
     fn add_field_setter(&mut self, name: String, callback: Callback<'static>) {
         self.field_setters.push((name, callback));
     }
+
+    #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+    fn add_async_field_getter(&mut self, name: String, callback: AsyncCallback<'static>) {
+        self.async_field_getters.push((name, callback));
+    }
+
+    fn add_field_value(&mut self, name: String, value: FieldValue) {
+        self.fields.push((name, value));
+    }
+
+    fn add_field_accessor(&mut self, name: String, accessor: FieldAccessor) {
+        self.field_accessors.push((name, accessor));
+    }
 }
 
 impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
@@ -480,9 +730,9 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
     fn add_async_method<M, A, MR, R>(&mut self, name: impl AsRef<str>, method: M)
     where
         T: Clone,
-        M: Fn(&Lua, T, A) -> MR + MaybeSend + 'static,
+        M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         let name = name.as_ref();
@@ -490,6 +740,20 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
             .push((name.into(), Self::box_async_method(name, method)));
     }
 
+    #[cfg(feature = "async")]
+    fn add_async_method_mut<M, A, MR, R>(&mut self, name: impl AsRef<str>, method: M)
+    where
+        T: 'static,
+        M: Fn(Lua, UserDataRefMut<'static, T>, A) -> MR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        MR: Future<Output = Result<R>> + 'static,
+        R: IntoLuaMulti,
+    {
+        let name = name.as_ref();
+        self.async_methods
+            .push((name.into(), Self::box_async_method_mut(name, method)));
+    }
+
     fn add_function<F, A, R>(&mut self, name: impl AsRef<str>, function: F)
     where
         F: Fn(Lua, A) -> Result<R> + MaybeSend + 'static,
@@ -512,12 +776,47 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
             .push((name.into(), Self::box_function_mut(name, function)));
     }
 
+    fn add_method_overload<M, A, R>(&mut self, name: impl AsRef<str>, method: M)
+    where
+        M: Fn(Lua, &T, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+    {
+        let name = name.as_ref();
+        let overload = Overload {
+            signature: signature::<A>(),
+            is_method: true,
+            matches: Box::new(|lua, mut args| {
+                args.pop_front();
+                A::from_lua_multi(args, lua).is_ok()
+            }),
+            callback: Self::box_method(name, method),
+        };
+        self.add_overload(name, overload);
+    }
+
+    fn add_function_overload<F, A, R>(&mut self, name: impl AsRef<str>, function: F)
+    where
+        F: Fn(Lua, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+    {
+        let name = name.as_ref();
+        let overload = Overload {
+            signature: signature::<A>(),
+            is_method: false,
+            matches: Box::new(|lua, args| A::from_lua_multi(args, lua).is_ok()),
+            callback: Self::box_function(name, function),
+        };
+        self.add_overload(name, overload);
+    }
+
     #[cfg(feature = "async")]
     fn add_async_function<F, A, FR, R>(&mut self, name: impl AsRef<str>, function: F)
     where
-        F: Fn(&Lua, A) -> FR + MaybeSend + 'static,
+        F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         let name = name.as_ref();
@@ -551,9 +850,9 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
     fn add_async_meta_method<M, A, MR, R>(&mut self, name: impl AsRef<str>, method: M)
     where
         T: Clone,
-        M: Fn(&Lua, T, A) -> MR + MaybeSend + 'static,
+        M: Fn(Lua, T, A) -> MR + MaybeSend + 'static,
         A: FromLuaMulti,
-        MR: Future<Output = Result<R>> + 'lua,
+        MR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         let name = name.as_ref();
@@ -586,9 +885,9 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
     #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
     fn add_async_meta_function<F, A, FR, R>(&mut self, name: impl AsRef<str>, function: F)
     where
-        F: Fn(&Lua, A) -> FR + MaybeSend + 'static,
+        F: Fn(Lua, A) -> FR + MaybeSend + 'static,
         A: FromLuaMulti,
-        FR: Future<Output = Result<R>> + 'lua,
+        FR: Future<Output = Result<R>> + 'static,
         R: IntoLuaMulti,
     {
         let name = name.as_ref();
@@ -596,6 +895,11 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
             .push((name.into(), Self::box_async_function(name, function)));
     }
 
+    #[cfg(feature = "luau")]
+    fn set_namecall(&mut self, name: impl AsRef<str>, enabled: bool) {
+        self.namecall.push((name.as_ref().into(), enabled));
+    }
+
     // Below are internal methods used in generated code
 
     fn add_callback(&mut self, name: String, callback: Callback<'static>) {
@@ -617,6 +921,103 @@ impl<T: 'static> UserDataMethods<T> for UserDataRegistrar<T> {
     }
 }
 
+// Borrows the userdata (on top of the stack) as `&T` and passes it to `f`.
+// Supports the same set of wrapped types as `box_method`.
+pub(crate) unsafe fn with_userdata_ref<T: 'static, R>(
+    state: *mut ffi::lua_State,
+    type_id: Option<TypeId>,
+    f: impl FnOnce(&T) -> R,
+) -> Result<R> {
+    match type_id {
+        Some(id) if id == TypeId::of::<T>() => Ok(f(&*get_userdata_ref::<T>(state)?)),
+        #[cfg(not(feature = "send"))]
+        Some(id) if id == TypeId::of::<Rc<RefCell<T>>>() => {
+            let ud = get_userdata_ref::<Rc<RefCell<T>>>(state)?;
+            let ud = ud.try_borrow().map_err(|_| Error::UserDataBorrowError)?;
+            Ok(f(&ud))
+        }
+        Some(id) if id == TypeId::of::<Arc<Mutex<T>>>() => {
+            let ud = get_userdata_ref::<Arc<Mutex<T>>>(state)?;
+            let ud = ud.try_lock().map_err(|_| Error::UserDataBorrowError)?;
+            Ok(f(&ud))
+        }
+        #[cfg(feature = "parking_lot")]
+        Some(id) if id == TypeId::of::<Arc<parking_lot::Mutex<T>>>() => {
+            let ud = get_userdata_ref::<Arc<parking_lot::Mutex<T>>>(state)?;
+            let ud = ud.try_lock().ok_or(Error::UserDataBorrowError)?;
+            Ok(f(&ud))
+        }
+        Some(id) if id == TypeId::of::<Arc<RwLock<T>>>() => {
+            let ud = get_userdata_ref::<Arc<RwLock<T>>>(state)?;
+            let ud = ud.try_read().map_err(|_| Error::UserDataBorrowError)?;
+            Ok(f(&ud))
+        }
+        #[cfg(feature = "parking_lot")]
+        Some(id) if id == TypeId::of::<Arc<parking_lot::RwLock<T>>>() => {
+            let ud = get_userdata_ref::<Arc<parking_lot::RwLock<T>>>(state)?;
+            let ud = ud.try_read().ok_or(Error::UserDataBorrowError)?;
+            Ok(f(&ud))
+        }
+        _ => Err(Error::UserDataTypeMismatch),
+    }
+}
+
+// State of a pending async method call, shared with the userdata tasks registry
+#[cfg(feature = "async")]
+#[derive(Default)]
+pub(crate) struct PendingTask {
+    aborted: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+#[cfg(feature = "async")]
+impl PendingTask {
+    pub(crate) fn abort(&self) {
+        self.aborted.set(true);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// Future of an async method call, which is dropped and resolves to `Error::Aborted` once
+// the call is aborted
+#[cfg(feature = "async")]
+pub(crate) struct TrackedFuture<'a> {
+    fut: Option<LocalBoxFuture<'a, Result<MultiValue>>>,
+    task: Rc<PendingTask>,
+}
+
+#[cfg(feature = "async")]
+impl<'a> TrackedFuture<'a> {
+    pub(crate) fn new(fut: LocalBoxFuture<'a, Result<MultiValue>>, task: Rc<PendingTask>) -> Self {
+        let fut = Some(fut);
+        TrackedFuture { fut, task }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Future for TrackedFuture<'_> {
+    type Output = Result<MultiValue>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if self.task.aborted.get() {
+            self.fut = None;
+        }
+        let fut = match self.fut.as_mut() {
+            Some(fut) => fut,
+            None => return Poll::Ready(Err(Error::Aborted)),
+        };
+        match fut.as_mut().poll(cx) {
+            Poll::Pending => {
+                *self.task.waker.borrow_mut() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            ready => ready,
+        }
+    }
+}
+
 #[inline]
 unsafe fn get_userdata_ref<'a, T>(state: *mut ffi::lua_State) -> Result<Ref<'a, T>> {
     (*get_userdata::<UserDataCell<T>>(state, -1)).try_borrow()
@@ -633,9 +1034,19 @@ macro_rules! lua_userdata_impl {
             fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
                 let mut orig_fields = UserDataRegistrar::new();
                 T::add_fields(&mut orig_fields);
+                for (name, value) in orig_fields.fields {
+                    fields.add_field_value(name, value);
+                }
+                for (name, accessor) in orig_fields.field_accessors {
+                    fields.add_field_accessor(name, accessor);
+                }
                 for (name, callback) in orig_fields.field_getters {
                     fields.add_field_getter(name, callback);
                 }
+                #[cfg(all(feature = "async", not(any(feature = "lua51", feature = "luau"))))]
+                for (name, callback) in orig_fields.async_field_getters {
+                    fields.add_async_field_getter(name, callback);
+                }
                 for (name, callback) in orig_fields.field_setters {
                     fields.add_field_setter(name, callback);
                 }
@@ -658,6 +1069,10 @@ macro_rules! lua_userdata_impl {
                 for (meta, callback) in orig_methods.async_meta_methods {
                     methods.add_async_meta_callback(meta, callback);
                 }
+                #[cfg(feature = "luau")]
+                for (name, enabled) in orig_methods.namecall {
+                    methods.set_namecall(name, enabled);
+                }
             }
         }
     };
@@ -0,0 +1,295 @@
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::multi::Variadic;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::{MultiValue, Value};
+
+// Registry key of the module created by `Lua::utf8_compat`
+const UTF8_COMPAT_KEY: &str = "__mlua_utf8_compat";
+
+// Maximum code point of Unicode and of the extended (lax) encoding
+const MAX_UNICODE: u32 = 0x10FFFF;
+const MAX_UTF: u32 = 0x7FFFFFFF;
+
+// Pattern matching exactly one UTF-8 byte sequence
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+const CHAR_PATTERN: &[u8] = b"[%z\x01-\x7F\xC2-\xFD][\x80-\xBF]*";
+#[cfg(not(any(feature = "lua51", feature = "luajit")))]
+const CHAR_PATTERN: &[u8] = b"[\x00-\x7F\xC2-\xFD][\x80-\xBF]*";
+
+impl Lua {
+    /// Returns a module with the API of the Lua 5.4 `utf8` library on all Lua versions.
+    ///
+    /// The module is implemented in Rust and provides `char`, `charpattern`, `codes`,
+    /// `codepoint`, `len` and `offset` with the semantics of Lua 5.4 (including the `lax`
+    /// arguments), so scripts can rely on the same text handling on Lua 5.1, 5.2 and LuaJIT,
+    /// which do not have the `utf8` library.
+    ///
+    /// The module is not installed as a global variable, it's up to the application to do so.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.globals().set("utf8", lua.utf8_compat()?)?;
+    ///
+    /// let (len, s): (usize, String) = lua
+    ///     .load(r#"utf8.len("привет"), utf8.char(72, 0x20AC)"#)
+    ///     .eval()?;
+    /// assert_eq!((len, s.as_str()), (6, "H€"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn utf8_compat(&self) -> Result<Table> {
+        if let Some(module) = self.named_registry_value::<Option<Table>>(UTF8_COMPAT_KEY)? {
+            return Ok(module);
+        }
+
+        let module = self.create_table_with_capacity(0, 6)?;
+        module.raw_set("charpattern", self.create_string(CHAR_PATTERN)?)?;
+        module.raw_set("char", self.create_function(utf8_char)?)?;
+        module.raw_set("codes", self.create_function(utf8_codes)?)?;
+        module.raw_set("codepoint", self.create_function(utf8_codepoint)?)?;
+        module.raw_set("len", self.create_function(utf8_len)?)?;
+        module.raw_set("offset", self.create_function(utf8_offset)?)?;
+
+        self.set_named_registry_value(UTF8_COMPAT_KEY, module.clone())?;
+        Ok(module)
+    }
+}
+
+fn utf8_char(lua: &Lua, codes: Variadic<Integer>) -> Result<String> {
+    let mut bytes = Vec::with_capacity(codes.len());
+    for (i, &code) in codes.iter().enumerate() {
+        #[allow(clippy::useless_conversion)]
+        let code = i64::from(code);
+        if !(0..=MAX_UTF as i64).contains(&code) {
+            return Err(arg_error(i + 1, "char", "value out of range"));
+        }
+        encode(code as u32, &mut bytes);
+    }
+    lua.create_string(bytes)
+}
+
+fn utf8_codes(lua: &Lua, (s, lax): (String, Option<bool>)) -> Result<MultiValue> {
+    if s.as_bytes().first().copied().is_some_and(is_cont) {
+        return Err(arg_error(1, "codes", "invalid UTF-8 code"));
+    }
+    let iter = match lax.unwrap_or_default() {
+        true => lua.create_function(|_, (s, n): (String, Integer)| codes_next(&s, n, false))?,
+        false => lua.create_function(|_, (s, n): (String, Integer)| codes_next(&s, n, true))?,
+    };
+    Ok(MultiValue::from_vec(vec![
+        Value::Function(iter),
+        Value::String(s),
+        Value::Integer(0),
+    ]))
+}
+
+// Iteration function returned by `utf8.codes`
+fn codes_next(s: &String, n: Integer, strict: bool) -> Result<(Option<Integer>, Option<Integer>)> {
+    let bytes = s.as_bytes();
+    #[allow(clippy::useless_conversion)]
+    let mut n = match usize::try_from(i64::from(n)) {
+        Ok(n) => n,
+        Err(_) => return Ok((None, None)),
+    };
+    while n < bytes.len() && is_cont(bytes[n]) {
+        n += 1;
+    }
+    if n >= bytes.len() {
+        return Ok((None, None));
+    }
+    match decode(&bytes[n..], strict) {
+        Some((code, len)) if !bytes.get(n + len).copied().is_some_and(is_cont) => {
+            Ok((Some(n as Integer + 1), Some(code as Integer)))
+        }
+        _ => Err(Error::RuntimeError("invalid UTF-8 code".to_string())),
+    }
+}
+
+fn utf8_codepoint(
+    _: &Lua,
+    (s, i, j, lax): (String, Option<Integer>, Option<Integer>, Option<bool>),
+) -> Result<Variadic<Integer>> {
+    let bytes = s.as_bytes();
+    let i = position(i.unwrap_or(1), bytes.len());
+    let j = j.map_or(i, |j| position(j, bytes.len()));
+    if i < 1 {
+        return Err(arg_error(2, "codepoint", "out of bounds"));
+    }
+    if j > bytes.len() as i64 {
+        return Err(arg_error(3, "codepoint", "out of bounds"));
+    }
+
+    let mut codes = Variadic::new();
+    let mut pos = i as usize - 1;
+    while pos < j as usize {
+        match decode(&bytes[pos..], !lax.unwrap_or_default()) {
+            Some((code, len)) => {
+                codes.push(code as Integer);
+                pos += len;
+            }
+            None => return Err(Error::RuntimeError("invalid UTF-8 code".to_string())),
+        }
+    }
+    Ok(codes)
+}
+
+fn utf8_len(
+    _: &Lua,
+    (s, i, j, lax): (String, Option<Integer>, Option<Integer>, Option<bool>),
+) -> Result<MultiValue> {
+    let bytes = s.as_bytes();
+    let i = position(i.unwrap_or(1), bytes.len());
+    let j = position(j.unwrap_or(-1), bytes.len());
+    if i < 1 || i - 1 > bytes.len() as i64 {
+        return Err(arg_error(2, "len", "initial position out of bounds"));
+    }
+    if j > bytes.len() as i64 {
+        return Err(arg_error(3, "len", "final position out of bounds"));
+    }
+
+    let (mut pos, mut n) = (i - 1, 0);
+    while pos < j {
+        match decode(&bytes[pos as usize..], !lax.unwrap_or_default()) {
+            Some((_, len)) => pos += len as i64,
+            None => {
+                let pos = Value::Integer(pos as Integer + 1);
+                return Ok(MultiValue::from_vec(vec![Value::Nil, pos]));
+            }
+        }
+        n += 1;
+    }
+    Ok(MultiValue::from_vec(vec![Value::Integer(n)]))
+}
+
+fn utf8_offset(_: &Lua, (s, n, i): (String, Integer, Option<Integer>)) -> Result<Option<Integer>> {
+    let bytes = s.as_bytes();
+    let len = bytes.len() as i64;
+    #[allow(clippy::useless_conversion)]
+    let mut n = i64::from(n);
+    let default_i = if n >= 0 { 1 } else { len + 1 };
+    #[allow(clippy::useless_conversion)]
+    let i = position(i.map_or(default_i, i64::from), bytes.len());
+    if i < 1 || i - 1 > len {
+        return Err(arg_error(3, "offset", "position out of bounds"));
+    }
+
+    // The position after the last byte is never a continuation byte
+    let cont_at = |pos: i64| bytes.get(pos as usize).copied().is_some_and(is_cont);
+    let mut pos = i - 1;
+    if n == 0 {
+        // Find the beginning of the current byte sequence
+        while pos > 0 && cont_at(pos) {
+            pos -= 1;
+        }
+        return Ok(Some(pos as Integer + 1));
+    }
+    if cont_at(pos) {
+        return Err(Error::RuntimeError(
+            "initial position is a continuation byte".to_string(),
+        ));
+    }
+    if n < 0 {
+        while n < 0 && pos > 0 {
+            pos -= 1;
+            while pos > 0 && cont_at(pos) {
+                pos -= 1;
+            }
+            n += 1;
+        }
+    } else {
+        n -= 1;
+        while n > 0 && pos < len {
+            pos += 1;
+            while cont_at(pos) {
+                pos += 1;
+            }
+            n -= 1;
+        }
+    }
+    match n {
+        0 => Ok(Some(pos as Integer + 1)),
+        _ => Ok(None),
+    }
+}
+
+// Translates a relative string position (negative means back from the end)
+fn position(pos: impl Into<i64>, len: usize) -> i64 {
+    let pos = pos.into();
+    match pos {
+        pos if pos >= 0 => pos,
+        pos if pos.unsigned_abs() > len as u64 => 0,
+        pos => len as i64 + pos + 1,
+    }
+}
+
+#[inline]
+fn is_cont(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+// Decodes one UTF-8 sequence (up to 6 bytes long), returning the code point and its length.
+//
+// In strict mode, code points above `MAX_UNICODE` and surrogates are rejected.
+fn decode(bytes: &[u8], strict: bool) -> Option<(u32, usize)> {
+    const LIMITS: [u64; 6] = [!0, 0x80, 0x800, 0x10000, 0x200000, 0x4000000];
+
+    let mut c = *bytes.first()? as u64;
+    if c < 0x80 {
+        return Some((c as u32, 1));
+    }
+    let (mut res, mut count) = (0u64, 0);
+    while c & 0x40 != 0 {
+        count += 1;
+        let cc = *bytes.get(count)? as u64;
+        if cc & 0xC0 != 0x80 {
+            return None;
+        }
+        res = (res << 6) | (cc & 0x3F);
+        c <<= 1;
+    }
+    if count > 5 {
+        return None;
+    }
+    res |= (c & 0x7F) << (count * 5);
+    if res > MAX_UTF as u64 || res < LIMITS[count] {
+        return None;
+    }
+    if strict && (res > MAX_UNICODE as u64 || (0xD800..=0xDFFF).contains(&res)) {
+        return None;
+    }
+    Some((res as u32, count + 1))
+}
+
+// Encodes a code point (up to `MAX_UTF`) using the extended UTF-8 encoding
+fn encode(mut code: u32, bytes: &mut Vec<u8>) {
+    if code < 0x80 {
+        bytes.push(code as u8);
+        return;
+    }
+    let mut buf = [0u8; 6];
+    let mut n = 0;
+    // Maximum value that fits in the first byte
+    let mut mfb = 0x3F;
+    loop {
+        buf[5 - n] = 0x80 | (code & 0x3F) as u8;
+        n += 1;
+        code >>= 6;
+        mfb >>= 1;
+        if code <= mfb {
+            break;
+        }
+    }
+    buf[5 - n] = ((!mfb << 1) | code) as u8;
+    bytes.extend_from_slice(&buf[5 - n..]);
+}
+
+fn arg_error(pos: usize, func: &str, msg: &str) -> Error {
+    Error::RuntimeError(format!("bad argument #{pos} to '{func}' ({msg})"))
+}
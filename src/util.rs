@@ -5,13 +5,16 @@ use std::mem::MaybeUninit;
 use std::os::raw::{c_char, c_int, c_void};
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::sync::Arc;
+#[cfg(feature = "luau")]
+use std::sync::Mutex;
 use std::{mem, ptr, slice};
 
 use once_cell::sync::Lazy;
 use rustc_hash::FxHashMap;
 
-use crate::error::{Error, Result};
+use crate::error::{self, Error, Result, TracebackFrame};
 use crate::ffi;
+use crate::memory::MemoryInfo;
 
 static METATABLE_CACHE: Lazy<FxHashMap<TypeId, u8>> = Lazy::new(|| {
     let mut map = FxHashMap::with_capacity_and_hasher(32, Default::default());
@@ -37,7 +40,7 @@ pub unsafe fn assert_stack(state: *mut ffi::lua_State, amount: c_int) {
 // Checks that Lua has enough free stack space and returns `Error::StackError` on failure.
 #[inline]
 pub unsafe fn check_stack(state: *mut ffi::lua_State, amount: c_int) -> Result<()> {
-    if ffi::lua_checkstack(state, amount) == 0 {
+    if MemoryInfo::relax_limit_with(state, || ffi::lua_checkstack(state, amount)) == 0 {
         Err(Error::StackError)
     } else {
         Ok(())
@@ -85,12 +88,14 @@ impl Drop for StackGuard {
 pub unsafe fn protect_lua_call(
     state: *mut ffi::lua_State,
     nargs: c_int,
-    f: unsafe extern "C" fn(*mut ffi::lua_State) -> c_int,
+    f: unsafe extern "C-unwind" fn(*mut ffi::lua_State) -> c_int,
 ) -> Result<()> {
     let stack_start = ffi::lua_gettop(state) - nargs;
 
-    ffi::lua_pushcfunction(state, error_traceback);
-    ffi::lua_pushcfunction(state, f);
+    MemoryInfo::relax_limit_with(state, || {
+        ffi::lua_pushcfunction(state, error_traceback);
+        ffi::lua_pushcfunction(state, f);
+    });
     if nargs > 0 {
         ffi::lua_rotate(state, stack_start + 1, 2);
     }
@@ -128,7 +133,7 @@ where
         nresults: c_int,
     }
 
-    unsafe extern "C" fn do_call<F, R>(state: *mut ffi::lua_State) -> c_int
+    unsafe extern "C-unwind" fn do_call<F, R>(state: *mut ffi::lua_State) -> c_int
     where
         F: Fn(*mut ffi::lua_State) -> R,
         R: Copy,
@@ -147,8 +152,10 @@ where
 
     let stack_start = ffi::lua_gettop(state) - nargs;
 
-    ffi::lua_pushcfunction(state, error_traceback);
-    ffi::lua_pushcfunction(state, do_call::<F, R>);
+    MemoryInfo::relax_limit_with(state, || {
+        ffi::lua_pushcfunction(state, error_traceback);
+        ffi::lua_pushcfunction(state, do_call::<F, R>);
+    });
     if nargs > 0 {
         ffi::lua_rotate(state, stack_start + 1, 2);
     }
@@ -199,18 +206,11 @@ pub unsafe fn pop_error(state: *mut ffi::lua_State, err_code: c_int) -> Error {
         _ => {
             let err_string = to_string(state, -1);
             ffi::lua_pop(state, 1);
+            let err_string = crate::lua::apply_source_maps(state, err_string);
 
             match err_code {
                 ffi::LUA_ERRRUN => Error::RuntimeError(err_string),
-                ffi::LUA_ERRSYNTAX => {
-                    Error::SyntaxError {
-                        // This seems terrible, but as far as I can tell, this is exactly what the
-                        // stock Lua REPL does.
-                        incomplete_input: err_string.ends_with("<eof>")
-                            || err_string.ends_with("'<eof>'"),
-                        message: err_string,
-                    }
-                }
+                ffi::LUA_ERRSYNTAX => Error::syntax_error(err_string),
                 ffi::LUA_ERRERR => {
                     // This error is raised when the error handler raises an error too many times
                     // recursively, and continuing to trigger the error handler would cause a stack
@@ -286,7 +286,7 @@ pub unsafe fn push_userdata<T>(state: *mut ffi::lua_State, t: T, protect: bool)
 #[cfg(feature = "luau")]
 #[inline]
 pub unsafe fn push_userdata<T>(state: *mut ffi::lua_State, t: T, protect: bool) -> Result<()> {
-    unsafe extern "C" fn destructor<T>(ud: *mut c_void) {
+    unsafe extern "C-unwind" fn destructor<T>(ud: *mut c_void) {
         ptr::drop_in_place(ud as *mut T);
     }
 
@@ -391,11 +391,11 @@ pub unsafe fn get_gc_userdata<T: Any>(
     ud
 }
 
-unsafe extern "C" fn lua_error_impl(state: *mut ffi::lua_State) -> c_int {
+unsafe extern "C-unwind" fn lua_error_impl(state: *mut ffi::lua_State) -> c_int {
     ffi::lua_error(state);
 }
 
-unsafe extern "C" fn lua_isfunction_impl(state: *mut ffi::lua_State) -> c_int {
+unsafe extern "C-unwind" fn lua_isfunction_impl(state: *mut ffi::lua_State) -> c_int {
     let t = ffi::lua_type(state, -1);
     ffi::lua_pop(state, 1);
     ffi::lua_pushboolean(state, (t == ffi::LUA_TFUNCTION) as c_int);
@@ -584,10 +584,66 @@ pub unsafe fn init_userdata_metatable<T>(
     Ok(())
 }
 
+// Atoms of userdata method names, used by `__namecall` to find methods without string lookup
+#[cfg(feature = "luau")]
+static METHOD_ATOMS: Lazy<Mutex<FxHashMap<Vec<u8>, i16>>> =
+    Lazy::new(|| Mutex::new(FxHashMap::default()));
+
+// Returns the atom of a method name, assigning a new one if needed
+#[cfg(feature = "luau")]
+pub(crate) fn method_atom(name: &[u8]) -> Option<i16> {
+    let mut atoms = METHOD_ATOMS.lock().ok()?;
+    if let Some(&atom) = atoms.get(name) {
+        return Some(atom);
+    }
+    let atom = i16::try_from(atoms.len()).ok()?;
+    atoms.insert(name.to_vec(), atom);
+    Some(atom)
+}
+
+// Luau `useratom` callback, assigns atoms to new strings of registered method names
+#[cfg(feature = "luau")]
+pub unsafe extern "C-unwind" fn method_useratom(s: *const c_char, l: usize) -> i16 {
+    let name = slice::from_raw_parts(s as *const u8, l);
+    match METHOD_ATOMS.lock() {
+        Ok(atoms) => atoms.get(name).copied().unwrap_or(-1),
+        Err(_) => -1,
+    }
+}
+
+// Dispatches `userdata:method()` calls using the table of methods in the first upvalue,
+// other names are resolved using `__index`.
+#[cfg(feature = "luau")]
+pub unsafe extern "C-unwind" fn userdata_namecall(state: *mut ffi::lua_State) -> c_int {
+    let mut atom = -1;
+    let name = ffi::lua_namecallatom(state, &mut atom);
+    if name.is_null() {
+        ffi::luaL_error(state, cstr!("attempt to call a userdata value"));
+    }
+    let nargs = ffi::lua_gettop(state);
+    ffi::luaL_checkstack(state, 2, ptr::null());
+    // Strings created before the method registration have no atoms
+    let methods = ffi::lua_upvalueindex(1);
+    if atom < 0 || ffi::lua_rawgeti(state, methods, atom as ffi::lua_Integer + 1) == ffi::LUA_TNIL {
+        if atom >= 0 {
+            ffi::lua_pop(state, 1);
+        }
+        if ffi::lua_rawgetfield(state, methods, name) == ffi::LUA_TNIL {
+            ffi::lua_pop(state, 1);
+            ffi::lua_getfield(state, 1, name);
+        }
+    }
+    ffi::lua_insert(state, 1);
+    ffi::lua_call(state, nargs, ffi::LUA_MULTRET);
+    ffi::lua_gettop(state)
+}
+
 #[cfg(not(feature = "luau"))]
-pub unsafe extern "C" fn userdata_destructor<T>(state: *mut ffi::lua_State) -> c_int {
+pub unsafe extern "C-unwind" fn userdata_destructor<T>(state: *mut ffi::lua_State) -> c_int {
     // It's probably NOT a good idea to catch Rust panics in finalizer
     // Lua 5.4 ignores it, other versions generates `LUA_ERRGCMM` without calling message handler
+    #[cfg(feature = "async")]
+    crate::lua::abort_userdata_tasks(state, ffi::lua_touserdata(state, -1));
     take_userdata::<T>(state);
     0
 }
@@ -637,6 +693,7 @@ where
                 ffi::luaL_traceback(state, state, ptr::null(), 0);
                 let traceback = to_string(state, -1);
                 ffi::lua_pop(state, 1);
+                error::store_traceback_frames(&traceback, traceback_frames(state, 0));
                 traceback
             } else {
                 "<not enough stack space for traceback>".to_string()
@@ -661,7 +718,7 @@ where
     }
 }
 
-pub unsafe extern "C" fn error_traceback(state: *mut ffi::lua_State) -> c_int {
+pub unsafe extern "C-unwind" fn error_traceback(state: *mut ffi::lua_State) -> c_int {
     if ffi::lua_checkstack(state, 2) == 0 {
         // If we don't have enough stack space to even check the error type, do
         // nothing so we don't risk shadowing a rust panic.
@@ -673,6 +730,9 @@ pub unsafe extern "C" fn error_traceback(state: *mut ffi::lua_State) -> c_int {
         if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
             ffi::luaL_traceback(state, state, s, 0);
             ffi::lua_remove(state, -2);
+            // Skip the message handler itself
+            let frames = traceback_frames(state, 1);
+            error::store_traceback_frames(&to_string(state, -1), frames);
         }
     }
 
@@ -689,12 +749,55 @@ pub unsafe fn error_traceback_thread(state: *mut ffi::lua_State, thread: *mut ff
         if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
             ffi::luaL_traceback(state, thread, s, 0);
             ffi::lua_remove(state, -2);
+            let frames = traceback_frames(thread, 0);
+            error::store_traceback_frames(&to_string(state, -1), frames);
+        }
+    }
+}
+
+// Maximum number of frames gathered by `traceback_frames`
+const TRACEBACK_MAX_FRAMES: usize = 64;
+
+// Gathers traceback frames of the `thread` call stack, starting from the given `level`.
+// Does not use the Lua stack.
+pub(crate) unsafe fn traceback_frames(
+    thread: *mut ffi::lua_State,
+    level: c_int,
+) -> Vec<TracebackFrame> {
+    let lossy = |s: *const c_char| ptr_to_cstr_bytes(s).map(String::from_utf8_lossy);
+    let mut frames = Vec::new();
+    for level in (level..).take(TRACEBACK_MAX_FRAMES) {
+        let mut ar: ffi::lua_Debug = mem::zeroed();
+        #[cfg(not(feature = "luau"))]
+        {
+            if ffi::lua_getstack(thread, level, &mut ar) == 0 {
+                break;
+            }
+            mlua_assert!(
+                ffi::lua_getinfo(thread, cstr!("Sln"), &mut ar) != 0,
+                "lua_getinfo failed with `Sln`"
+            );
         }
+        #[cfg(feature = "luau")]
+        if ffi::lua_getinfo(thread, level, cstr!("sln"), &mut ar) == 0 {
+            break;
+        }
+        #[cfg(not(feature = "luau"))]
+        let short_src = ar.short_src.as_ptr();
+        #[cfg(feature = "luau")]
+        let short_src = ar.short_src;
+        frames.push(TracebackFrame {
+            source: lossy(short_src).unwrap_or_default().into_owned(),
+            line: (ar.currentline > 0).then_some(ar.currentline as usize),
+            name: lossy(ar.name).map(|name| name.into_owned()),
+            is_c: ptr_to_cstr_bytes(ar.what) == Some(b"C"),
+        });
     }
+    frames
 }
 
 // A variant of `pcall` that does not allow Lua to catch Rust panics from `callback_error`.
-pub unsafe extern "C" fn safe_pcall(state: *mut ffi::lua_State) -> c_int {
+pub unsafe extern "C-unwind" fn safe_pcall(state: *mut ffi::lua_State) -> c_int {
     ffi::luaL_checkstack(state, 2, ptr::null());
 
     let top = ffi::lua_gettop(state);
@@ -720,8 +823,8 @@ pub unsafe extern "C" fn safe_pcall(state: *mut ffi::lua_State) -> c_int {
 }
 
 // A variant of `xpcall` that does not allow Lua to catch Rust panics from `callback_error`.
-pub unsafe extern "C" fn safe_xpcall(state: *mut ffi::lua_State) -> c_int {
-    unsafe extern "C" fn xpcall_msgh(state: *mut ffi::lua_State) -> c_int {
+pub unsafe extern "C-unwind" fn safe_xpcall(state: *mut ffi::lua_State) -> c_int {
+    unsafe extern "C-unwind" fn xpcall_msgh(state: *mut ffi::lua_State) -> c_int {
         ffi::luaL_checkstack(state, 2, ptr::null());
 
         if let Some(WrappedFailure::Panic(_)) =
@@ -835,7 +938,7 @@ pub unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
 
     // Create error and panic metatables
 
-    unsafe extern "C" fn error_tostring(state: *mut ffi::lua_State) -> c_int {
+    unsafe extern "C-unwind" fn error_tostring(state: *mut ffi::lua_State) -> c_int {
         callback_error(state, |_| {
             check_stack(state, 3)?;
 
@@ -893,7 +996,7 @@ pub unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
 
     // Create destructed userdata metatable
 
-    unsafe extern "C" fn destructed_error(state: *mut ffi::lua_State) -> c_int {
+    unsafe extern "C-unwind" fn destructed_error(state: *mut ffi::lua_State) -> c_int {
         callback_error(state, |_| Err(Error::CallbackDestructed))
     }
 
@@ -976,7 +1079,7 @@ impl WrappedFailure {
         let size = mem::size_of::<WrappedFailure>();
         #[cfg(feature = "luau")]
         let ud = {
-            unsafe extern "C" fn destructor(p: *mut c_void) {
+            unsafe extern "C-unwind" fn destructor(p: *mut c_void) {
                 ptr::drop_in_place(p as *mut WrappedFailure);
             }
             ffi::lua_newuserdatadtor(state, size, destructor) as *mut Self
@@ -1025,6 +1128,8 @@ pub(crate) unsafe fn to_string(state: *mut ffi::lua_State, index: c_int) -> Stri
         ffi::LUA_TFUNCTION => format!("<function {:?}>", ffi::lua_topointer(state, index)),
         ffi::LUA_TUSERDATA => format!("<userdata {:?}>", ffi::lua_topointer(state, index)),
         ffi::LUA_TTHREAD => format!("<thread {:?}>", ffi::lua_topointer(state, index)),
+        #[cfg(feature = "luau")]
+        ffi::LUA_TBUFFER => format!("<buffer {:?}>", ffi::lua_topointer(state, index)),
         _ => "<unknown>".to_string(),
     }
 }
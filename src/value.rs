@@ -11,6 +11,8 @@ use {
     std::result::Result as StdResult,
 };
 
+#[cfg(feature = "luau")]
+use crate::buffer::LuauBuffer;
 use crate::error::{Error, Result};
 use crate::ffi;
 use crate::function::Function;
@@ -55,6 +57,10 @@ pub enum Value {
     /// Reference to a userdata object that holds a custom type which implements `UserData`.
     /// Special builtin userdata types will be represented as other `Value` variants.
     UserData(AnyUserData),
+    /// Reference to a Luau buffer.
+    #[cfg(feature = "luau")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    Buffer(LuauBuffer),
     /// `Error` is a special builtin userdata type. When received from Lua it is implicitly cloned.
     Error(Error),
 }
@@ -76,6 +82,8 @@ impl Value {
             Value::Function(_) => "function",
             Value::Thread(_) => "thread",
             Value::UserData(_) => "userdata",
+            #[cfg(feature = "luau")]
+            Value::Buffer(_) => "buffer",
             Value::Error(_) => "error",
         }
     }
@@ -112,10 +120,12 @@ impl Value {
                 Value::LightUserData(ud) => ud.0,
                 Value::Table(t) => t.to_pointer(),
                 Value::String(s) => s.to_pointer(),
+                #[cfg(feature = "luau")]
+                Value::Buffer(buf) => buf.to_pointer(),
                 Value::Function(Function(r))
                 | Value::Thread(Thread(r))
                 | Value::UserData(AnyUserData(r)) => {
-                    ffi::lua_topointer(r.lua.ref_thread(), r.index)
+                    ffi::lua_topointer(r.lua.ref_thread(), r.index())
                 }
                 _ => ptr::null(),
             }
@@ -140,6 +150,8 @@ impl PartialEq for Value {
             (Value::Function(a), Value::Function(b)) => a == b,
             (Value::Thread(a), Value::Thread(b)) => a == b,
             (Value::UserData(a), Value::UserData(b)) => a == b,
+            #[cfg(feature = "luau")]
+            (Value::Buffer(a), Value::Buffer(b)) => a == b,
             _ => false,
         }
     }
@@ -170,6 +182,8 @@ impl Serialize for Value {
             Value::String(s) => s.serialize(serializer),
             Value::Table(t) => t.serialize(serializer),
             Value::UserData(ud) => ud.serialize(serializer),
+            #[cfg(feature = "luau")]
+            Value::Buffer(buf) => buf.serialize(serializer),
             Value::LightUserData(ud) if ud.0.is_null() => serializer.serialize_none(),
             Value::Error(_) | Value::LightUserData(_) | Value::Function(_) | Value::Thread(_) => {
                 let msg = format!("cannot serialize <{}>", self.type_name());
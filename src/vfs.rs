@@ -0,0 +1,639 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataMethods};
+use crate::value::{MultiValue, Value};
+
+/// A virtual filesystem backing the `io` library and the module resolver.
+///
+/// Installed using [`Lua::set_vfs`]. Files are addressed by `/`-separated paths and are read
+/// and written as a whole: a file opened by Lua code is loaded into memory and its contents
+/// are written back when the file is flushed or closed.
+///
+/// # Examples
+///
+/// ```
+/// # use std::io;
+/// # use mlua::{Lua, Result, Vfs};
+/// // Read-only filesystem with assets embedded into the binary
+/// struct Assets;
+///
+/// impl Vfs for Assets {
+///     fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+///         match path {
+///             "greet.lua" => Ok(b"return function(name) return 'Hello, ' .. name end".to_vec()),
+///             _ => Err(io::ErrorKind::NotFound.into()),
+///         }
+///     }
+/// }
+///
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// lua.set_vfs(Assets)?;
+/// lua.load(r#"package.path = "?.lua""#).exec()?;
+///
+/// let s: String = lua.load(r#"require("greet")("world")"#).eval()?;
+/// assert_eq!(s, "Hello, world");
+/// # Ok(())
+/// # }
+/// ```
+pub trait Vfs: Send + Sync + 'static {
+    /// Reads the whole contents of the file at `path`.
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Writes `data` to the file at `path`, creating the file or replacing its contents.
+    ///
+    /// The default implementation returns a `PermissionDenied` error (read-only filesystem).
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        let _ = (path, data);
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "read-only filesystem",
+        ))
+    }
+
+    /// Returns the names of the entries of the directory at `path`.
+    ///
+    /// The default implementation returns an `Unsupported` error.
+    fn list(&self, path: &str) -> io::Result<Vec<StdString>> {
+        let _ = path;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "listing directories is not supported",
+        ))
+    }
+}
+
+/// An in-memory [`Vfs`].
+///
+/// The filesystem is cheap to clone, all clones share the same files. This allows the
+/// application to inspect files written by Lua code.
+///
+/// Directories are implicit: a directory exists if there is a file under it.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryVfs {
+    files: Arc<Mutex<BTreeMap<StdString, Vec<u8>>>>,
+}
+
+impl MemoryVfs {
+    /// Creates an empty filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates or replaces the file at `path`.
+    pub fn insert(&self, path: impl AsRef<str>, data: impl Into<Vec<u8>>) {
+        let path = normalize_path(path.as_ref());
+        self.files().insert(path, data.into());
+    }
+
+    /// Returns contents of the file at `path`.
+    pub fn get(&self, path: impl AsRef<str>) -> Option<Vec<u8>> {
+        self.files().get(&normalize_path(path.as_ref())).cloned()
+    }
+
+    /// Removes the file at `path`, returning its contents.
+    pub fn remove(&self, path: impl AsRef<str>) -> Option<Vec<u8>> {
+        self.files().remove(&normalize_path(path.as_ref()))
+    }
+
+    fn files(&self) -> MutexGuard<'_, BTreeMap<StdString, Vec<u8>>> {
+        mlua_expect!(self.files.lock(), "memory filesystem poisoned")
+    }
+}
+
+impl Vfs for MemoryVfs {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.get(path).ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        self.insert(path, data);
+        Ok(())
+    }
+
+    fn list(&self, path: &str) -> io::Result<Vec<StdString>> {
+        let dir = normalize_path(path);
+        let prefix = match dir.as_str() {
+            "" => StdString::new(),
+            dir => format!("{dir}/"),
+        };
+        let files = self.files();
+        let entries = (files.keys())
+            .filter_map(|path| path.strip_prefix(&prefix))
+            .map(|rest| rest.split('/').next().unwrap_or(rest).to_string())
+            .collect::<BTreeSet<_>>();
+        if entries.is_empty() && !prefix.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+// Removes `.` components and redundant separators
+fn normalize_path(path: &str) -> StdString {
+    let components = (path.split('/'))
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>();
+    match path.starts_with('/') {
+        true => format!("/{}", components.join("/")),
+        false => components.join("/"),
+    }
+}
+
+impl Lua {
+    /// Installs a virtual filesystem backing the `io` library and the module resolver.
+    ///
+    /// The global `io` table is replaced with a sandboxed library working with files of the
+    /// `vfs`. It provides `io.open`, `io.lines`, `io.type` and a non-standard `io.list(path)`
+    /// function that returns a table with the names of the directory entries. Files support
+    /// the `read`, `write`, `lines`, `seek`, `flush`, `setvbuf` and `close` methods. Standard
+    /// streams and processes (`io.stdout`, `io.popen`, etc) are not available.
+    ///
+    /// The Lua searcher of `require` is replaced with one that looks for modules in the `vfs`
+    /// using templates of `package.path`, and C modules are disabled. In Luau, modules are
+    /// required using `Lua::set_require` with a resolver that loads `<name>.luau`,
+    /// `<name>.lua` or `<name>/init.luau` files.
+    ///
+    /// See [`Vfs`] for an example.
+    pub fn set_vfs(&self, vfs: impl Vfs) -> Result<()> {
+        let vfs: Arc<dyn Vfs> = Arc::new(vfs);
+
+        let io = self.create_table_with_capacity(0, 4)?;
+        let fs = vfs.clone();
+        let open =
+            self.create_function(move |lua, (path, mode): (StdString, Option<String>)| {
+                let mode = match mode {
+                    Some(mode) => OpenMode::parse(mode.as_bytes()).ok_or_else(|| {
+                        Error::RuntimeError("bad argument #2 to 'open' (invalid mode)".into())
+                    })?,
+                    None => OpenMode::READ,
+                };
+                match FileState::open(fs.clone(), path, mode) {
+                    Ok(file) => Ok((Some(lua.create_userdata(file)?), None)),
+                    Err(err) => Ok((None, Some(err))),
+                }
+            })?;
+        io.raw_set("open", open)?;
+
+        let fs = vfs.clone();
+        let lines = self.create_function(move |_, (path, formats): (StdString, MultiValue)| {
+            let formats = ReadFormat::parse_all(formats)?;
+            let file =
+                FileState::open(fs.clone(), path, OpenMode::READ).map_err(Error::RuntimeError)?;
+            Ok(FileLines {
+                file,
+                formats,
+                close: true,
+            })
+        })?;
+        io.raw_set("lines", lines)?;
+
+        let fs = vfs.clone();
+        let list = self.create_function(move |lua, path: StdString| match fs.list(&path) {
+            Ok(names) => Ok((Some(lua.create_sequence_from(names)?), None)),
+            Err(err) => Ok((None, Some(format!("{path}: {err}")))),
+        })?;
+        io.raw_set("list", list)?;
+
+        let io_type = self.create_function(|_, value: Value| match value {
+            Value::UserData(ud) => match ud.borrow::<LuaFile>() {
+                Ok(file) if file.state().closed => Ok(Some("closed file")),
+                Ok(_) => Ok(Some("file")),
+                Err(_) => Ok(None),
+            },
+            _ => Ok(None),
+        })?;
+        io.raw_set("type", io_type)?;
+
+        if let Some(loaded) = self.named_registry_value::<Option<Table>>("_LOADED")? {
+            loaded.raw_set("io", io.clone())?;
+        }
+        self.globals_storage()?.raw_set("io", io)?;
+
+        self.set_vfs_searcher(vfs)
+    }
+
+    #[cfg(not(feature = "luau"))]
+    fn set_vfs_searcher(&self, vfs: Arc<dyn Vfs>) -> Result<()> {
+        let package = match self.globals_storage()?.raw_get::<_, Value>("package")? {
+            Value::Table(package) => package,
+            _ => return Ok(()),
+        };
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        let searchers: Table = package.raw_get("searchers")?;
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        let searchers: Table = package.raw_get("loaders")?;
+
+        let searcher = self.create_function(move |lua, name: StdString| {
+            let package: Table = lua.globals_storage()?.raw_get("package")?;
+            let templates: StdString = package.raw_get("path")?;
+            let name = name.replace('.', "/");
+            let mut message = StdString::new();
+            for template in templates.split(';').filter(|t| !t.is_empty()) {
+                let path = template.replace('?', &name);
+                match vfs.read(&path) {
+                    Ok(source) => {
+                        let loader = lua
+                            .load(&source)
+                            .set_name(format!("@{path}"))
+                            .into_function()?;
+                        return Ok((Value::Function(loader), Some(path)));
+                    }
+                    Err(_) => message.push_str(&format!("\n\tno file '{path}'")),
+                }
+            }
+            // Lua 5.4 adds the separator itself
+            #[cfg(feature = "lua54")]
+            let message = message.split_off(2);
+            Ok((Value::String(lua.create_string(message)?), None))
+        })?;
+        let c_loader =
+            self.create_function(|_, ()| Ok("\n\tcan't load C modules with a virtual filesystem"))?;
+
+        // The second searcher looks for a Lua file, the third and fourth for a C library
+        searchers.raw_set(2, searcher)?;
+        searchers.raw_set(3, c_loader)?;
+        // Safe mode has already removed the fourth searcher
+        if searchers.raw_len() >= 4 {
+            searchers.raw_remove(4)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "luau")]
+    fn set_vfs_searcher(&self, vfs: Arc<dyn Vfs>) -> Result<()> {
+        self.set_require(VfsRequire(vfs))
+    }
+}
+
+#[cfg(feature = "luau")]
+struct VfsRequire(Arc<dyn Vfs>);
+
+#[cfg(feature = "luau")]
+impl crate::luau::Require for VfsRequire {
+    fn load(&self, name: &str) -> Result<Vec<u8>> {
+        for path in ["{}.luau", "{}.lua", "{}/init.luau", "{}/init.lua"] {
+            if let Ok(source) = self.0.read(&path.replace("{}", name)) {
+                return Ok(source);
+            }
+        }
+        Err(Error::RuntimeError(format!("module '{name}' not found")))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct OpenMode {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+}
+
+impl OpenMode {
+    const READ: OpenMode = OpenMode {
+        read: true,
+        write: false,
+        append: false,
+        truncate: false,
+    };
+
+    // Parses modes accepted by the standard `io.open`
+    fn parse(mode: &[u8]) -> Option<Self> {
+        let (mode, plus) = match mode.strip_suffix(b"b").unwrap_or(mode) {
+            [mode] => (*mode, false),
+            [mode, b'+'] => (*mode, true),
+            _ => return None,
+        };
+        let (read, write, append, truncate) = match mode {
+            b'r' => (true, plus, false, false),
+            b'w' => (plus, true, false, true),
+            b'a' => (plus, true, true, false),
+            _ => return None,
+        };
+        Some(OpenMode {
+            read,
+            write,
+            append,
+            truncate,
+        })
+    }
+}
+
+// State of an open file, shared between the file and its `lines` iterators
+struct FileState {
+    vfs: Arc<dyn Vfs>,
+    path: StdString,
+    data: Vec<u8>,
+    pos: usize,
+    mode: OpenMode,
+    dirty: bool,
+    closed: bool,
+}
+
+#[derive(Clone)]
+struct LuaFile(Arc<Mutex<FileState>>);
+
+impl FileState {
+    fn open(vfs: Arc<dyn Vfs>, path: StdString, mode: OpenMode) -> StdResult<LuaFile, StdString> {
+        let (data, created) = match vfs.read(&path) {
+            _ if mode.truncate => (Vec::new(), true),
+            Ok(data) => (data, false),
+            Err(err) if err.kind() == io::ErrorKind::NotFound && mode.append => (Vec::new(), true),
+            Err(err) => return Err(format!("{path}: {err}")),
+        };
+        // Create (or truncate) the file right away to report errors early
+        if created {
+            (vfs.write(&path, &data)).map_err(|err| format!("{path}: {err}"))?;
+        }
+        let pos = if mode.append { data.len() } else { 0 };
+        Ok(LuaFile(Arc::new(Mutex::new(FileState {
+            vfs,
+            path,
+            data,
+            pos,
+            mode,
+            dirty: false,
+            closed: false,
+        }))))
+    }
+
+    fn check_open(&self) -> Result<()> {
+        match self.closed {
+            true => Err(Error::RuntimeError("attempt to use a closed file".into())),
+            false => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> StdResult<(), StdString> {
+        if self.dirty {
+            (self.vfs.write(&self.path, &self.data))
+                .map_err(|err| format!("{}: {err}", self.path))?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    fn remaining(&self) -> &[u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+
+    fn read(&mut self, lua: &Lua, format: &ReadFormat) -> Result<Value> {
+        let rest = self.remaining();
+        let (value, len) = match format {
+            ReadFormat::All => (Some(rest), rest.len()),
+            ReadFormat::Line { keep_newline } => match rest.iter().position(|&b| b == b'\n') {
+                Some(i) if *keep_newline => (Some(&rest[..=i]), i + 1),
+                Some(i) => (Some(&rest[..i]), i + 1),
+                None if rest.is_empty() => (None, 0),
+                None => (Some(rest), rest.len()),
+            },
+            ReadFormat::Count(0) => (
+                if rest.is_empty() {
+                    None
+                } else {
+                    Some(&rest[..0])
+                },
+                0,
+            ),
+            ReadFormat::Count(n) => match rest.is_empty() {
+                true => (None, 0),
+                false => (Some(&rest[..(*n).min(rest.len())]), (*n).min(rest.len())),
+            },
+            ReadFormat::Number => {
+                let start = rest.iter().take_while(|b| b.is_ascii_whitespace()).count();
+                let len = (rest[start..].iter())
+                    .take_while(|&&b| b.is_ascii_hexdigit() || b"+-.xXpP".contains(&b))
+                    .count();
+                self.pos += start + len;
+                let number = lua.string_to_number(&self.data[self.pos - len..self.pos])?;
+                return Ok(number.unwrap_or(Value::Nil));
+            }
+        };
+        let value = match value {
+            Some(value) => Value::String(lua.create_string(value)?),
+            None => Value::Nil,
+        };
+        self.pos += len;
+        Ok(value)
+    }
+
+    fn read_all(&mut self, lua: &Lua, formats: &[ReadFormat]) -> Result<MultiValue> {
+        self.check_open()?;
+        if !self.mode.read {
+            return Ok(MultiValue::from_vec(vec![
+                Value::Nil,
+                Value::String(lua.create_string("file is not readable")?),
+            ]));
+        }
+        let mut values = Vec::with_capacity(formats.len());
+        for format in formats {
+            let value = self.read(lua, format)?;
+            let failed = value == Value::Nil;
+            values.push(value);
+            if failed {
+                break;
+            }
+        }
+        Ok(MultiValue::from_vec(values))
+    }
+}
+
+impl Drop for FileState {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = self.flush();
+        }
+    }
+}
+
+impl LuaFile {
+    fn state(&self) -> MutexGuard<'_, FileState> {
+        mlua_expect!(self.0.lock(), "file state poisoned")
+    }
+}
+
+enum ReadFormat {
+    All,
+    Line { keep_newline: bool },
+    Count(usize),
+    Number,
+}
+
+impl ReadFormat {
+    fn parse_all(formats: MultiValue) -> Result<Vec<ReadFormat>> {
+        if formats.is_empty() {
+            return Ok(vec![ReadFormat::Line {
+                keep_newline: false,
+            }]);
+        }
+        (formats.into_iter())
+            .map(|format| match format {
+                Value::Integer(n) => Ok(ReadFormat::Count(n.max(0) as usize)),
+                Value::Number(n) => Ok(ReadFormat::Count(n.max(0.0) as usize)),
+                Value::String(s) => match s.as_bytes().strip_prefix(b"*").unwrap_or(s.as_bytes()) {
+                    [b'a', ..] => Ok(ReadFormat::All),
+                    [b'l', ..] => Ok(ReadFormat::Line {
+                        keep_newline: false,
+                    }),
+                    [b'L', ..] => Ok(ReadFormat::Line { keep_newline: true }),
+                    [b'n', ..] => Ok(ReadFormat::Number),
+                    _ => Err(Error::RuntimeError(
+                        "bad argument to 'read' (invalid format)".into(),
+                    )),
+                },
+                _ => Err(Error::RuntimeError(
+                    "bad argument to 'read' (invalid format)".into(),
+                )),
+            })
+            .collect()
+    }
+}
+
+impl UserData for LuaFile {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("read", |lua, this, formats: MultiValue| {
+            let formats = ReadFormat::parse_all(formats)?;
+            this.state().read_all(&lua, &formats)
+        });
+
+        methods.add_function("write", |lua, (ud, args): (AnyUserData, MultiValue)| {
+            {
+                let this = ud.borrow::<LuaFile>()?;
+                let mut file = this.state();
+                file.check_open()?;
+                if !file.mode.write {
+                    return Ok((None, Some("file is not writable")));
+                }
+                for (i, arg) in args.into_iter().enumerate() {
+                    let type_name = arg.type_name();
+                    let s = match arg {
+                        Value::String(_) | Value::Integer(_) | Value::Number(_) => {
+                            lua.coerce_string(arg)?
+                        }
+                        _ => None,
+                    };
+                    let s = s.ok_or_else(|| {
+                        Error::RuntimeError(format!(
+                            "bad argument #{} to 'write' (string expected, got {type_name})",
+                            i + 1
+                        ))
+                    })?;
+                    if file.mode.append {
+                        file.pos = file.data.len();
+                    }
+                    let (pos, bytes) = (file.pos, s.as_bytes());
+                    if pos > file.data.len() {
+                        file.data.resize(pos, 0);
+                    }
+                    let end = (pos + bytes.len()).min(file.data.len());
+                    file.data.splice(pos..end, bytes.iter().copied());
+                    file.pos += bytes.len();
+                    file.dirty = true;
+                }
+            }
+            Ok((Some(ud), None))
+        });
+
+        methods.add_method("lines", |_, this, formats: MultiValue| {
+            this.state().check_open()?;
+            let formats = ReadFormat::parse_all(formats)?;
+            Ok(FileLines {
+                file: this.clone(),
+                formats,
+                close: false,
+            })
+        });
+
+        methods.add_method(
+            "seek",
+            |_, this, (whence, offset): (Option<String>, Option<Integer>)| {
+                let mut file = this.state();
+                file.check_open()?;
+                let base = match whence.as_ref().map(|s| s.as_bytes()) {
+                    None | Some(b"cur") => file.pos as i64,
+                    Some(b"set") => 0,
+                    Some(b"end") => file.data.len() as i64,
+                    Some(_) => {
+                        return Err(Error::RuntimeError(
+                            "bad argument #1 to 'seek' (invalid option)".into(),
+                        ))
+                    }
+                };
+                #[allow(clippy::useless_conversion)]
+                let pos = base + i64::from(offset.unwrap_or(0));
+                if pos < 0 {
+                    return Ok((None, Some("Invalid argument")));
+                }
+                file.pos = pos as usize;
+                Ok((Some(pos as Integer), None))
+            },
+        );
+
+        methods.add_method("flush", |_, this, ()| {
+            let mut file = this.state();
+            file.check_open()?;
+            match file.flush() {
+                Ok(()) => Ok((true, None)),
+                Err(err) => Ok((false, Some(err))),
+            }
+        });
+
+        methods.add_method("setvbuf", |_, this, _: MultiValue| {
+            this.state().check_open()?;
+            Ok(true)
+        });
+
+        methods.add_method("close", |_, this, ()| {
+            let mut file = this.state();
+            file.check_open()?;
+            file.closed = true;
+            match file.flush() {
+                Ok(()) => Ok((true, None)),
+                Err(err) => Ok((false, Some(err))),
+            }
+        });
+
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| {
+            let file = this.state();
+            match file.closed {
+                true => Ok("file (closed)".to_string()),
+                false => Ok(format!("file ({})", file.path)),
+            }
+        });
+    }
+}
+
+// Iterator over the contents of a file, created by `io.lines` and `file:lines`
+struct FileLines {
+    file: LuaFile,
+    formats: Vec<ReadFormat>,
+    // Close the file when the end of file is reached
+    close: bool,
+}
+
+impl UserData for FileLines {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        // Arguments passed by the generic `for` loop are ignored
+        methods.add_meta_method(MetaMethod::Call, |lua, this, _: MultiValue| {
+            let mut file = this.file.state();
+            if this.close && file.closed {
+                return Ok(MultiValue::from_vec(vec![Value::Nil]));
+            }
+            let values = file.read_all(&lua, &this.formats)?;
+            if this.close && values.iter().next().is_none_or(|v| *v == Value::Nil) {
+                file.closed = true;
+                file.flush().map_err(Error::RuntimeError)?;
+            }
+            Ok(values)
+        });
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::table::Table;
+
+/// Watches Lua scripts on disk and hot-reloads them when they change.
+///
+/// Every script must return a module table. Scripts are loaded when the watcher is created,
+/// and reloaded in place using [`Lua::reload_chunk`] when their files change, so references
+/// to the module tables stay valid.
+///
+/// File system events are collected in background, changed scripts are reloaded on the thread
+/// calling [`ScriptWatcher::poll`] (eg. once per frame in a game loop).
+///
+/// Requires `feature = "watch"`
+///
+/// # Examples
+///
+/// ```no_run
+/// # use mlua::{Lua, Result, ScriptWatcher};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let mut watcher = ScriptWatcher::new(&lua, ["scripts/game.lua"])?
+///     .on_reload(|path, _| println!("reloaded {}", path.display()))
+///     .on_error(|path, err| eprintln!("cannot reload {}: {err}", path.display()));
+/// lua.globals().set("game", watcher.module("scripts/game.lua"))?;
+///
+/// loop {
+///     watcher.poll();
+///     lua.load("game.update()").exec()?;
+/// #   break;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "watch")))]
+pub struct ScriptWatcher {
+    lua: Lua,
+    // Canonical script paths with their module tables
+    scripts: Vec<(PathBuf, Table)>,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+    on_reload: Option<ReloadCallback>,
+    on_error: Option<ErrorCallback>,
+}
+
+type ReloadCallback = Box<dyn FnMut(&Path, &Table)>;
+type ErrorCallback = Box<dyn FnMut(&Path, &Error)>;
+
+impl ScriptWatcher {
+    /// Loads the scripts at `paths` and starts watching them.
+    ///
+    /// Returns an error if a script cannot be loaded or does not return a table.
+    pub fn new<P: AsRef<Path>>(lua: &Lua, paths: impl IntoIterator<Item = P>) -> Result<Self> {
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(Error::external)?;
+
+        let mut scripts = Vec::new();
+        let mut dirs = HashSet::new();
+        for path in paths {
+            let path = path.as_ref().canonicalize()?;
+            let module: Table = lua.load(path.as_path()).eval()?;
+            // Watch the parent directory to keep watching files replaced by editors on save
+            if let Some(dir) = path.parent() {
+                if dirs.insert(dir.to_path_buf()) {
+                    (watcher.watch(dir, RecursiveMode::NonRecursive)).map_err(Error::external)?;
+                }
+            }
+            scripts.push((path, module));
+        }
+
+        Ok(ScriptWatcher {
+            lua: lua.clone(),
+            scripts,
+            events,
+            _watcher: watcher,
+            on_reload: None,
+            on_error: None,
+        })
+    }
+
+    /// Sets a callback invoked with the script path and its module table after a successful
+    /// reload.
+    #[must_use]
+    pub fn on_reload<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Path, &Table) + 'static,
+    {
+        self.on_reload = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets a callback invoked with the script path and the error when a script fails
+    /// to reload.
+    ///
+    /// The module table is left untouched in that case.
+    #[must_use]
+    pub fn on_error<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Path, &Error) + 'static,
+    {
+        self.on_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the module table of the watched script at `path`.
+    pub fn module(&self, path: impl AsRef<Path>) -> Option<Table> {
+        let path = path.as_ref().canonicalize().ok()?;
+        (self.scripts.iter())
+            .find(|(p, _)| *p == path)
+            .map(|(_, module)| module.clone())
+    }
+
+    /// Reloads scripts changed since the last call and returns the number of successfully
+    /// reloaded scripts.
+    ///
+    /// Does not block.
+    pub fn poll(&mut self) -> usize {
+        let mut changed = Vec::new();
+        for event in self.events.try_iter().flatten() {
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        let mut reloaded = 0;
+        for (path, module) in &self.scripts {
+            if !changed.contains(path) {
+                continue;
+            }
+            match self.lua.reload_chunk(module, path.as_path()) {
+                Ok(()) => {
+                    reloaded += 1;
+                    if let Some(on_reload) = self.on_reload.as_mut() {
+                        on_reload(path, module);
+                    }
+                }
+                Err(err) => {
+                    if let Some(on_error) = self.on_error.as_mut() {
+                        on_error(path, &err);
+                    }
+                }
+            }
+        }
+        reloaded
+    }
+}
+
+impl fmt::Debug for ScriptWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScriptWatcher")
+            .field("scripts", &self.scripts)
+            .finish()
+    }
+}
@@ -9,7 +9,7 @@ use futures_util::stream::TryStreamExt;
 
 use mlua::{
     AnyUserDataExt, Error, Function, Lua, LuaOptions, Result, StdLib, Table, TableExt, UserData,
-    UserDataMethods,
+    UserDataFields, UserDataMethods, Value,
 };
 
 #[tokio::test]
@@ -81,6 +81,95 @@ async fn test_async_call() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "send"))]
+#[tokio::test]
+async fn test_local_async_function() -> Result<()> {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let lua = Lua::new();
+    let counter = Rc::new(RefCell::new(0));
+
+    let counter2 = counter.clone();
+    let incr = lua.create_local_async_function(move |lua, n: i64| {
+        let counter = counter2.clone();
+        async move {
+            Delay::new(Duration::from_millis(10)).await;
+            *counter.borrow_mut() += n;
+            // The future owns the Lua handle
+            lua.globals().set("counter", *counter.borrow())?;
+            Ok(*counter.borrow())
+        }
+    })?;
+    lua.globals().set("incr", incr)?;
+
+    let res: i64 = lua.load("incr(2) return incr(3)").eval_async().await?;
+    assert_eq!(res, 5);
+    assert_eq!(*counter.borrow(), 5);
+    assert_eq!(lua.globals().get::<_, i64>("counter")?, 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_async_poll_pending() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(|_lua, n: u64| async move {
+        Delay::new(Duration::from_millis(n)).await;
+        Ok(("done", n))
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    lua.load(
+        r#"
+        local co = coroutine.create(function() return sleep(10) end)
+        local ok, pending = coroutine.resume(co)
+        assert(ok and pending:is_pending())
+        local polls = 0
+        repeat polls = polls + 1 until pending:poll()
+        assert(not pending:is_pending() and polls > 1)
+
+        local ready, res, n = pending:poll()
+        assert(ready and res == "done" and n == 10)
+
+        local ok, res, n = coroutine.resume(co)
+        assert(ok and res == "done" and n == 10)
+    "#,
+    )
+    .exec()
+}
+
+#[test]
+fn test_load_async_reader() -> Result<()> {
+    // Reader that returns a few bytes at a time, each after being pending once
+    struct SlowReader(&'static [u8], bool);
+
+    impl futures_util::io::AsyncRead for SlowReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.1 = !self.1;
+            if self.1 {
+                cx.waker().wake_by_ref();
+                return std::task::Poll::Pending;
+            }
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            std::task::Poll::Ready(Ok(n))
+        }
+    }
+
+    let lua = Lua::new();
+    let reader = SlowReader(b"local x = 40\nreturn x + 2", false);
+    assert_eq!(lua.load_async_reader(reader).eval::<i64>()?, 42);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_bind_call() -> Result<()> {
     let lua = Lua::new();
@@ -271,6 +360,135 @@ async fn test_async_thread() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_thread_from_closure() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = |_, ms: u64| async move {
+        Delay::new(Duration::from_millis(ms)).await;
+        Ok(ms * 2)
+    };
+
+    // Driven by Lua
+    lua.globals()
+        .set("co", lua.create_thread_from_closure(sleep)?)?;
+    let (n, polls): (u64, u32) = lua
+        .load(
+            r#"
+            local polls, ok, n = 1, coroutine.resume(co, 10)
+            while coroutine.status(co) ~= "dead" do
+                ok, n = coroutine.resume(co)
+                assert(ok)
+                polls = polls + 1
+            end
+            return n, polls
+        "#,
+        )
+        .eval()?;
+    assert_eq!(n, 20);
+    assert!(polls > 1);
+
+    // Driven by executor
+    let thread = lua.create_thread_from_closure(sleep)?;
+    assert_eq!(thread.into_async::<_, u64>(5).await?, 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_channel() -> Result<()> {
+    let lua = Lua::new();
+    lua.load_channel_module()?;
+
+    // Lua producer, Rust consumer
+    let (tx, rx) = lua.create_channel(Some(2));
+    let producer = lua
+        .load("local tx = ... for i = 1, 5 do tx:send(i) end tx:close()")
+        .call_async::<_, ()>(tx);
+    let consumer = async {
+        let mut values = Vec::new();
+        while let Some(value) = rx.recv().await {
+            values.push(lua.unpack::<i64>(value)?);
+            assert!(rx.len() <= 2);
+        }
+        Ok::<_, Error>(values)
+    };
+    let (result, values) = futures_util::future::join(producer, consumer).await;
+    result?;
+    assert_eq!(values?, [1, 2, 3, 4, 5]);
+
+    // Rust producer, Lua consumer
+    let (tx, rx) = lua.create_channel(None);
+    let consumer: Function = lua
+        .load(
+            r#"
+            function(rx)
+                local sum = 0
+                while true do
+                    local value = rx:recv()
+                    if value == nil then return sum end
+                    sum = sum + value
+                end
+            end
+        "#,
+        )
+        .eval()?;
+    let consumer = consumer.call_async::<_, i64>(rx);
+    let producer = async {
+        for i in 1..=4 {
+            Delay::new(Duration::from_millis(5)).await;
+            tx.send(Value::Integer(i)).await?;
+        }
+        drop(tx);
+        Ok::<_, Error>(())
+    };
+    let (sum, result) = futures_util::future::join(consumer, producer).await;
+    result?;
+    assert_eq!(sum?, 10);
+
+    // Channel between Lua coroutines
+    let value: String = lua
+        .load(
+            r#"
+            local channel = require("channel")
+            local tx, rx = channel.bounded(1)
+            assert(tx:try_send("first"))
+            assert(not tx:try_send("second"))
+            assert(rx:try_recv() == "first" and rx:try_recv() == nil)
+            local co = coroutine.wrap(function() return rx:recv() end)
+            tx:send("hello")
+            tx:close()
+            assert(tx:is_closed() and not pcall(tx.send, tx, "closed"))
+            return co()
+        "#,
+        )
+        .eval_async()
+        .await?;
+    assert_eq!(value, "hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_signal_wait() -> Result<()> {
+    let lua = Lua::new();
+    let signal = lua.create_signal::<(i64, i64)>()?;
+
+    let waiter: Function = lua
+        .load("function(signal) local a, b = signal:Wait() return a + b end")
+        .eval()?;
+    let wait = waiter.call_async::<_, i64>(signal.clone());
+    let fire = async {
+        Delay::new(Duration::from_millis(10)).await;
+        signal.fire((2, 3))
+    };
+    let (sum, fired) = futures_util::future::join(wait, fire).await;
+    fired?;
+    assert_eq!(sum?, 5);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_table() -> Result<()> {
     let options = LuaOptions::new().thread_pool_size(4);
@@ -320,6 +538,51 @@ async fn test_async_table() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(any(feature = "lua51", feature = "luau")))]
+#[tokio::test]
+async fn test_async_table_metamethods() -> Result<()> {
+    let lua = Lua::new();
+
+    let storage = lua.create_table()?;
+    let proxy = lua.create_table()?;
+    let mt = lua.create_table()?;
+    let storage2 = storage.clone();
+    mt.set(
+        "__index",
+        lua.create_async_function(move |_, (_, key): (Table, String)| {
+            let storage = storage2.clone();
+            async move {
+                Delay::new(Duration::from_millis(10)).await;
+                storage.get::<_, Option<i64>>(key)
+            }
+        })?,
+    )?;
+    let storage2 = storage.clone();
+    mt.set(
+        "__newindex",
+        lua.create_async_function(move |_, (_, key, value): (Table, String, i64)| {
+            let storage = storage2.clone();
+            async move {
+                Delay::new(Duration::from_millis(10)).await;
+                storage.set(key, value * 2)
+            }
+        })?,
+    )?;
+    proxy.set_metatable(Some(mt));
+
+    proxy.set_async("a", 21).await?;
+    assert_eq!(storage.get::<_, i64>("a")?, 42);
+    assert_eq!(proxy.get_async::<_, i64>("a").await?, 42);
+    assert_eq!(proxy.get_async::<_, Option<i64>>("b").await?, None);
+    assert!(proxy.get::<_, i64>("a").is_err());
+
+    // Tables without metatable
+    storage.set_async("c", 1).await?;
+    assert_eq!(storage.get_async::<_, i64>("c").await?, 1);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_thread_pool() -> Result<()> {
     let options = LuaOptions::new().thread_pool_size(4);
@@ -444,6 +707,266 @@ async fn test_async_userdata() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_userdata_mut() -> Result<()> {
+    struct Counter(u64);
+
+    impl UserData for Counter {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_async_method_mut("add", |_, mut this, n: u64| async move {
+                Delay::new(Duration::from_millis(10)).await;
+                this.0 += n;
+                Ok(this.0)
+            });
+
+            methods.add_method("get", |_, this, ()| Ok(this.0));
+        }
+    }
+
+    let lua = Lua::new();
+    let counter = lua.create_userdata(Counter(1))?;
+    lua.globals().set("counter", counter.clone())?;
+
+    let n: u64 = lua.load("counter:add(2)").eval_async().await?;
+    assert_eq!(n, 3);
+    assert_eq!(counter.borrow::<Counter>()?.0, 3);
+
+    // The userdata is borrowed until the future completes
+    let add = counter.call_async_method::<_, u64>("add", 10);
+    let get = lua.load("counter:get()").eval_async::<u64>();
+    let (add, get) = futures_util::future::join(add, get).await;
+    assert_eq!(add?, 13);
+    match get {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { cause, .. } => {
+                assert!(matches!(*cause.as_ref(), Error::UserDataBorrowError))
+            }
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+    assert_eq!(counter.call_method::<_, u64>("get", ())?, 13);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_userdata_abort() -> Result<()> {
+    #[derive(Clone)]
+    struct Socket(Arc<AtomicU64>);
+
+    // Counts dropped futures
+    struct DropGuard(Arc<AtomicU64>);
+
+    impl Drop for DropGuard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    impl UserData for Socket {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_async_method("recv", |_, this, ()| async move {
+                let _guard = DropGuard(this.0.clone());
+                Delay::new(Duration::from_secs(10)).await;
+                Ok("data")
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    let dropped = Arc::new(AtomicU64::new(0));
+    let socket = lua.create_userdata(Socket(dropped.clone()))?;
+    assert_eq!(socket.abort_pending(), 0);
+
+    let recv = socket.call_async_method::<_, String>("recv", ());
+    let abort = async {
+        Delay::new(Duration::from_millis(10)).await;
+        socket.abort_pending()
+    };
+    let (recv, aborted) = futures_util::future::join(recv, abort).await;
+    assert_eq!(aborted, 1);
+    let err = recv.unwrap_err();
+    assert!(err.chain().any(|e| matches!(e, Error::Aborted)), "{err:?}");
+    assert_eq!(dropped.load(Ordering::Relaxed), 1);
+
+    // Taking the userdata aborts its pending calls
+    let recv = socket.call_async_method::<_, String>("recv", ());
+    let take = async {
+        Delay::new(Duration::from_millis(10)).await;
+        socket.take::<Socket>()
+    };
+    let (recv, _) = futures_util::future::join(recv, take).await;
+    assert!(recv.is_err());
+    assert_eq!(dropped.load(Ordering::Relaxed), 2);
+
+    Ok(())
+}
+
+#[cfg(not(any(feature = "lua51", feature = "luau")))]
+#[tokio::test]
+async fn test_async_userdata_fields() -> Result<()> {
+    #[derive(Clone)]
+    struct Remote(u64);
+
+    impl UserData for Remote {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_async_field_method_get("value", |_, this| async move {
+                Delay::new(Duration::from_millis(10)).await;
+                Ok(this.0)
+            });
+            fields.add_field_method_get("id", |_, this| Ok(this.0));
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("remote", Remote(42))?;
+
+    let value: u64 = lua.load("remote.value + remote.id").eval_async().await?;
+    assert_eq!(value, 84);
+
+    // Async fields cannot be accessed outside of async context
+    match lua.load("return remote.value").eval::<u64>() {
+        Err(Error::RuntimeError(_)) => {}
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_gc_budget() -> Result<()> {
+    // Returns memory used after making garbage between awaits with the collector stopped
+    async fn make_garbage(budget: Option<i32>) -> Result<usize> {
+        let lua = Lua::new();
+        lua.gc_stop();
+        lua.gc_budget_per_tick(budget);
+        let sleep = lua.create_async_function(|_, n: u64| async move {
+            Delay::new(Duration::from_millis(n)).await;
+            Ok(())
+        })?;
+        lua.globals().set("sleep", sleep)?;
+        let f: Function = lua
+            .load(
+                r#"
+                function()
+                    for i = 1, 20 do
+                        local garbage = {}
+                        for j = 1, 1000 do garbage[j] = {} end
+                        sleep(1)
+                    end
+                end
+            "#,
+            )
+            .eval()?;
+        f.call_async::<_, ()>(()).await?;
+        Ok(lua.used_memory())
+    }
+
+    // Garbage is collected between awaits
+    assert!(make_garbage(Some(1024)).await? < make_garbage(None).await?);
+
+    let lua = Lua::new();
+    lua.load("garbage = {} for j = 1, 10000 do garbage[j] = {} end garbage = nil")
+        .exec()?;
+    let used_memory = lua.used_memory();
+    lua.gc_collect_async().await?;
+    lua.gc_collect_async().await?;
+    assert!(lua.used_memory() < used_memory);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_yield_hook() -> Result<()> {
+    let lua = Lua::new();
+    let yields = Arc::new(AtomicU64::new(0));
+    let yields2 = yields.clone();
+    lua.set_yield_async_hook(move |_| {
+        yields2.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    });
+
+    let sleep = lua.create_async_function(|_, n: u64| async move {
+        Delay::new(Duration::from_millis(n)).await;
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    lua.load("sleep(10); sleep(10)").exec_async().await?;
+    assert!(yields.load(Ordering::Relaxed) >= 2);
+
+    // Errors of the hook are returned by the future
+    lua.set_yield_async_hook(|_| Err(Error::RuntimeError("stop".into())));
+    match lua.load("sleep(10)").exec_async().await {
+        Err(Error::RuntimeError(msg)) => assert_eq!(msg, "stop"),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // No hook is called after removing it
+    lua.remove_yield_async_hook();
+    let before = yields.load(Ordering::Relaxed);
+    lua.load("sleep(10)").exec_async().await?;
+    assert_eq!(yields.load(Ordering::Relaxed), before);
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53"))]
+#[tokio::test]
+async fn test_async_timeout() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_async_timeout(Duration::from_millis(50));
+
+    match lua.load("while true do end").exec_async().await {
+        Err(Error::Timeout) => {}
+        r => panic!("expected Timeout, got {r:?}"),
+    }
+
+    // Lua code is resumed after suspension
+    let n: i64 = (lua.load("local i = 0 while i < 1e4 do i = i + 1 end return i"))
+        .eval_async()
+        .await?;
+    assert_eq!(n, 10000);
+
+    // Coroutines inside the async thread are not suspended
+    let sum: i64 = lua
+        .load(
+            r#"
+            local co = coroutine.wrap(function()
+                local sum = 0
+                for i = 1, 1e5 do sum = sum + i end
+                coroutine.yield(sum)
+            end)
+            return co()
+        "#,
+        )
+        .eval_async()
+        .await?;
+    assert_eq!(sum, 5000050000);
+
+    // Busy Lua code is suspended to let other tasks of the executor run
+    lua.set_async_timeout(Duration::from_secs(60));
+    let other_ran = std::cell::Cell::new(false);
+    let busy = async {
+        let res = lua
+            .load("local i = 0 while i < 1e7 do i = i + 1 end")
+            .exec_async()
+            .await;
+        res.map(|_| other_ran.get())
+    };
+    let other = async { other_ran.set(true) };
+    let (busy, _) = futures_util::future::join(busy, other).await;
+    assert!(busy?);
+
+    lua.remove_async_timeout();
+    lua.load("local i = 0 while i < 1e6 do i = i + 1 end")
+        .exec_async()
+        .await?;
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_thread_error() -> Result<()> {
     struct MyUserData;
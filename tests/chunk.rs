@@ -1,7 +1,7 @@
 use std::fs;
 use std::io;
 
-use mlua::{Lua, Result};
+use mlua::{DiagnosticKind, Error, Function, Lua, Result, Table, Value};
 
 #[test]
 fn test_chunk_path() -> Result<()> {
@@ -51,3 +51,262 @@ fn test_chunk_macro() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_chunk_source_map() -> Result<()> {
+    let lua = Lua::new();
+
+    let source_map = |line| match line {
+        1 => None,
+        line => Some(("original.fnl".to_string(), line + 100)),
+    };
+
+    // Runtime error with traceback
+    let err = lua
+        .load("local x = 1\nlocal function f() error('boom') end\nf()")
+        .set_name("=generated")
+        .set_source_map(source_map)
+        .exec()
+        .unwrap_err();
+    let frames = err.traceback().unwrap();
+    assert!(frames
+        .iter()
+        .any(|f| f.source == "original.fnl" && f.line == Some(103)));
+    match err {
+        Error::RuntimeError(msg) => {
+            assert!(msg.starts_with("original.fnl:102: boom"));
+            // Luau does not name the main chunk
+            assert!(msg.contains("original.fnl:103: in "));
+            assert!(!msg.contains("generated:"));
+        }
+        err => panic!("expected RuntimeError, got {err:?}"),
+    }
+
+    // Functions defined in the chunk use the source map too
+    lua.load("\nfunction g(x) return x + nil end")
+        .set_name("@script.lua")
+        .set_source_map(|line| Some(("script.tmpl".to_string(), line * 2)))
+        .exec()?;
+    let err = lua.load("g(1)").exec().unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("script.tmpl:4: attempt to perform arithmetic"));
+
+    // Syntax errors
+    let err = lua
+        .load("local x = 1\nlocal y = = 2")
+        .set_name("=generated")
+        .set_source_map(source_map)
+        .exec()
+        .unwrap_err();
+    match err {
+        Error::SyntaxError { message, .. } => assert!(message.starts_with("original.fnl:102:")),
+        err => panic!("expected SyntaxError, got {err:?}"),
+    }
+
+    // Source map of a chunk does not apply to chunks with a longer name ending with its name
+    lua.load("\nerror('boom')")
+        .set_name("=a.lua")
+        .set_source_map(|line| Some(("a.tmpl".to_string(), line)))
+        .into_function()?;
+    let err = lua.load("\nerror('boom')").set_name("=data.lua").exec();
+    let err = err.unwrap_err().to_string();
+    assert!(err.starts_with("runtime error: data.lua:2: boom"));
+
+    // Removed source maps are not applied
+    assert!(lua.remove_source_map("=a.lua"));
+    assert!(!lua.remove_source_map("=a.lua"));
+    let err = lua.load("\nerror('boom')").set_name("=a.lua").exec();
+    let err = err.unwrap_err().to_string();
+    assert!(err.starts_with("runtime error: a.lua:2: boom"));
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_reader() -> Result<()> {
+    let lua = Lua::new();
+
+    // Source larger than a single read block
+    let source = format!("local s = '{}'\nreturn #s", "x".repeat(20000));
+    let len: usize = lua.load_reader(io::Cursor::new(source)).eval()?;
+    assert_eq!(len, 20000);
+
+    // Read errors are propagated
+    struct FailingReader;
+    impl io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("read failed"))
+        }
+    }
+    match lua.load_reader(FailingReader).exec() {
+        Err(Error::ExternalError(err)) => assert_eq!(err.to_string(), "read failed"),
+        r => panic!("expected ExternalError, got {r:?}"),
+    }
+
+    #[cfg(not(feature = "luau"))]
+    {
+        let bytecode = lua.load("return 42").into_function()?.dump(false);
+        let f = lua.load_reader(io::Cursor::new(bytecode.clone()));
+        assert_eq!(f.eval::<i32>()?, 42);
+
+        let f = lua.load_reader(io::Cursor::new(bytecode));
+        match f.set_mode(mlua::ChunkMode::Text).exec() {
+            Err(Error::SyntaxError { .. }) => {}
+            r => panic!("expected SyntaxError, got {r:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_name_from_caller() -> Result<()> {
+    let lua = Lua::new();
+
+    #[track_caller]
+    fn run(lua: &Lua, code: &str) -> Result<()> {
+        lua.load(code).exec()
+    }
+
+    // Default name is the location of `Lua::load` caller
+    let line = line!() + 1;
+    let err = run(&lua, "error('boom')").unwrap_err().to_string();
+    assert!(
+        err.contains(&format!("[string \"{}:{line}:", file!())),
+        "{err}"
+    );
+
+    let line = line!() + 3;
+    let err = lua
+        .load("local x = 1\nerror('boom')")
+        .set_name_from_caller()
+        .exec()
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.starts_with(&format!("runtime error: {}:{line}:", file!())),
+        "{err}"
+    );
+    assert!(err.contains(":2: boom"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_reload_chunk() -> Result<()> {
+    let lua = Lua::new();
+    // Upvalue names are required to migrate upvalues
+    #[cfg(feature = "luau")]
+    lua.set_compiler(mlua::Compiler::new().set_debug_level(2));
+
+    let source = r#"
+        local M = {}
+        local calls = 0
+        local greeting = "hello"
+        M.config = {verbose = false}
+        function M.greet(name)
+            calls = calls + 1
+            return greeting .. ", " .. name
+        end
+        function M.calls() return calls end
+        function M.self() return M end
+        return M
+    "#;
+    let module: Table = lua.load(source).eval()?;
+    let config: Table = module.get("config")?;
+    lua.globals().set("module", module.clone())?;
+    let greet_ref: Function = lua
+        .load("local greet = module.greet; return greet")
+        .eval()?;
+    lua.load("module.greet('a'); module.config.verbose = true")
+        .exec()?;
+
+    lua.reload_chunk(&module, source.replace("\", \"", "\"!, \"").as_str())?;
+
+    // Module and nested tables keep identity and state
+    assert_eq!(module.get::<_, Table>("config")?, config);
+    assert!(config.get::<_, bool>("verbose")?);
+    assert_eq!(
+        module.get::<_, Function>("self")?.call::<_, Table>(())?,
+        module
+    );
+    assert_eq!(lua.load("module.greet('b')").eval::<String>()?, "hello!, b");
+    assert_eq!(module.get::<_, Function>("calls")?.call::<_, i64>(())?, 2);
+    // Old references still run old code
+    assert_eq!(greet_ref.call::<_, String>("c")?, "hello, c");
+
+    // Custom migration
+    lua.reload_chunk_with(&module, source, |name, old, new| match name {
+        "greeting" => Ok(new),
+        "calls" => Ok(Value::Integer(100)),
+        _ => Ok(old),
+    })?;
+    assert_eq!(module.get::<_, Function>("calls")?.call::<_, i64>(())?, 100);
+
+    // Errors leave the module untouched
+    assert!(lua.reload_chunk(&module, "return 1").is_err());
+    assert!(module.contains_key("greet")?);
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_analyze() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("config", lua.create_table()?)?;
+
+    let diagnostics = lua
+        .load(
+            r#"
+            local utils = require("utils")
+            local unused, _ignored = 1, 2
+            local function helper(a, b)
+                return a
+            end
+            function counter:increment(step)
+                self.value = self.value + 1
+            end
+            for i, v in ipairs(config) do
+                print(v)
+            end
+            repeat
+                local done = true
+            until done
+            total = 0
+            total = total + helper(1) + missing
+            return utils, undefined_call()
+        "#,
+        )
+        .analyze()?;
+
+    let found = diagnostics
+        .iter()
+        .map(|diag| (diag.kind, diag.name.as_str(), diag.line))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        [
+            (DiagnosticKind::UnusedVariable, "unused", 3),
+            (DiagnosticKind::UndefinedGlobal, "counter", 7),
+            (DiagnosticKind::UnusedVariable, "i", 10),
+            (DiagnosticKind::UndefinedGlobal, "missing", 17),
+            (DiagnosticKind::UndefinedGlobal, "undefined_call", 18),
+        ]
+    );
+
+    // Custom environment
+    let env = lua.create_table()?;
+    env.set("print", lua.globals().get::<_, Function>("print")?)?;
+    let diagnostics = lua.load("print(x)").set_environment(env).analyze()?;
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].to_string(), "line 1: undefined global 'x'");
+
+    // Syntax errors
+    match lua.load("local x = ").analyze() {
+        Err(Error::SyntaxError { .. }) => {}
+        r => panic!("expected SyntaxError, got {r:?}"),
+    }
+
+    Ok(())
+}
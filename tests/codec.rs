@@ -0,0 +1,111 @@
+#![cfg(feature = "codec")]
+
+use mlua::{Encoding, Error, Lua, Result, Table, Value};
+
+#[test]
+fn test_codec_round_trip() -> Result<()> {
+    let lua = Lua::new();
+
+    let value: Value = lua
+        .load(
+            r#"
+            {
+                int = 1, neg = -200, big = 0x7fffffff, float = 1.5,
+                bool = true, str = "hello", bin = "\255\254",
+                arr = {1, "two", {3}}, map = {[10] = "ten", [-1] = false},
+                empty = {},
+            }
+        "#,
+        )
+        .eval()?;
+    lua.globals().set("orig", value.clone())?;
+
+    for encoding in [Encoding::MessagePack, Encoding::Cbor] {
+        let bytes = lua.encode(&value, encoding)?;
+        lua.globals()
+            .set("decoded", lua.decode(&bytes, encoding)?)?;
+        lua.load(
+            r#"
+            local function equal(a, b)
+                if type(a) ~= "table" or type(b) ~= "table" then
+                    return a == b
+                end
+                for k, v in pairs(a) do
+                    if not equal(v, b[k]) then return false end
+                end
+                for k in pairs(b) do
+                    if a[k] == nil then return false end
+                end
+                return true
+            end
+            assert(equal(orig, decoded))
+        "#,
+        )
+        .exec()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_codec_format() -> Result<()> {
+    let lua = Lua::new();
+
+    let value: Value = lua.load(r#"{1, -1, "a", true}"#).eval()?;
+    assert_eq!(
+        lua.encode(&value, Encoding::MessagePack)?,
+        [0x94, 0x01, 0xff, 0xa1, b'a', 0xc3]
+    );
+    assert_eq!(
+        lua.encode(&value, Encoding::Cbor)?,
+        [0x84, 0x01, 0x20, 0x61, b'a', 0xf5]
+    );
+
+    let value = lua.decode(&[0x81, 0xa1, b'k', 0xcd, 0x01, 0x00], Encoding::MessagePack)?;
+    let table: Table = lua.unpack(value)?;
+    assert_eq!(table.get::<_, i64>("k")?, 256);
+
+    // CBOR half-precision float and tagged value
+    let value = lua.decode(&[0xf9, 0x3e, 0x00], Encoding::Cbor)?;
+    assert_eq!(value, Value::Number(1.5));
+    let value = lua.decode(&[0xc1, 0x1a, 0x00, 0x01, 0x00, 0x00], Encoding::Cbor)?;
+    assert_eq!(value, Value::Integer(65536));
+
+    Ok(())
+}
+
+#[test]
+fn test_codec_errors() -> Result<()> {
+    let lua = Lua::new();
+
+    // Unsupported values
+    let func = Value::Function(lua.create_function(|_, ()| Ok(()))?);
+    match lua.encode(&func, Encoding::MessagePack) {
+        Err(Error::FromLuaConversionError {
+            from: "function", ..
+        }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    // Recursive tables
+    let value: Value = lua.load("local t = {}; t.t = t; return t").eval()?;
+    assert!(lua.encode(&value, Encoding::Cbor).is_err());
+
+    // Truncated and trailing input
+    for bytes in [&[0x92, 0x01][..], &[0xa5, b'a'], &[0x01, 0x02]] {
+        match lua.decode(bytes, Encoding::MessagePack) {
+            Err(Error::ToLuaConversionError { .. }) => {}
+            r => panic!("expected ToLuaConversionError, got {r:?}"),
+        }
+    }
+
+    // Huge declared length must not allocate
+    assert!(lua
+        .decode(
+            &[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            Encoding::Cbor
+        )
+        .is_err());
+
+    Ok(())
+}
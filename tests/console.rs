@@ -0,0 +1,125 @@
+#![cfg(feature = "console")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use mlua::{DebugConsole, Lua, Result};
+
+// Reads the output until the next prompt
+fn read_reply(reader: &mut impl BufRead) -> String {
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    while !(reply.ends_with(b"> ") || reply.ends_with(b"token: ")) {
+        if reader.read(&mut byte).unwrap() == 0 {
+            break;
+        }
+        reply.push(byte[0]);
+    }
+    String::from_utf8(reply).unwrap()
+}
+
+fn send(stream: &mut impl Write, reader: &mut impl BufRead, line: &str) -> String {
+    writeln!(stream, "{line}").unwrap();
+    read_reply(reader)
+}
+
+fn run_client<T>(console: &mut DebugConsole, client: JoinHandle<T>) -> T {
+    while !client.is_finished() {
+        console.poll();
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.join().unwrap()
+}
+
+#[test]
+fn test_debug_console() -> Result<()> {
+    let lua = Lua::new();
+    let env = lua.create_table()?;
+    env.set("x", 42)?;
+    let meta = lua.create_table()?;
+    meta.set("__index", lua.globals())?;
+    env.set_metatable(Some(meta));
+
+    let mut console = lua
+        .serve_debug_console("127.0.0.1:0")?
+        .with_environment(env.clone())
+        .with_auth(|token| token == "secret");
+    let addr = console.local_addr().unwrap();
+
+    // Wrong token
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let prompt = read_reply(&mut reader);
+        let reply = send(&mut stream, &mut reader, "wrong");
+        (prompt, reply)
+    });
+    let (prompt, reply) = run_client(&mut console, client);
+    assert_eq!(prompt, "token: ");
+    assert_eq!(reply, "error: unauthorized\n");
+
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        read_reply(&mut reader);
+        let lines = [
+            "secret",
+            "x",
+            "function double(a)",
+            "return a * 2 end",
+            "double(x)",
+            "error('boom')",
+            "_",
+        ];
+        (lines.iter())
+            .map(|line| send(&mut stream, &mut reader, line))
+            .collect::<Vec<_>>()
+    });
+    let replies = run_client(&mut console, client);
+    assert_eq!(replies[..5], ["> ", "42\n> ", ">> ", "> ", "84\n> "]);
+    assert!(replies[5].starts_with("error: ") && replies[5].contains("boom"));
+    assert_eq!(replies[6], "84\n> ");
+    assert!(env.contains_key("double")?);
+    assert!(!lua.globals().contains_key("double")?);
+
+    // Disconnected sessions are removed
+    for _ in 0..1000 {
+        console.poll();
+        if console.clients() == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(console.clients(), 0);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_debug_console_unix() -> Result<()> {
+    use std::os::unix::net::UnixStream;
+
+    let lua = Lua::new();
+    let path = std::env::temp_dir().join(format!("mlua-console-{}.sock", std::process::id()));
+    let mut console = lua.serve_debug_console(&format!("unix:{}", path.display()))?;
+    assert_eq!(console.local_addr(), None);
+
+    let path2 = path.clone();
+    let client = thread::spawn(move || {
+        let mut stream = UnixStream::connect(path2).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let prompt = read_reply(&mut reader);
+        (prompt, send(&mut stream, &mut reader, "1 + 1"))
+    });
+    let (prompt, reply) = run_client(&mut console, client);
+    assert_eq!(prompt, "> ");
+    assert_eq!(reply, "2\n> ");
+
+    drop(console);
+    assert!(!path.exists());
+
+    Ok(())
+}
@@ -3,7 +3,10 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::{CStr, CString};
 
 use maplit::{btreemap, btreeset, hashmap, hashset};
-use mlua::{Error, Lua, Result};
+use mlua::{
+    EnumValue, Error, Lua, LuaEnum, NonEmptyString, OwnedAnyUserData, OwnedFunction, OwnedString,
+    OwnedTable, OwnedThread, Ranged, Result, ThreadStatus, UserData, UserDataMethods, Value,
+};
 
 #[test]
 fn test_conv_vec() -> Result<()> {
@@ -56,6 +59,28 @@ fn test_conv_btreemap() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_conv_indexmap() -> Result<()> {
+    use indexmap::{indexmap, IndexMap};
+
+    let lua = Lua::new();
+
+    let map =
+        indexmap! {"hello".to_string() => "world".to_string(), "a".to_string() => "b".to_string()};
+    lua.globals().set("map", map.clone())?;
+    let map2: IndexMap<String, String> = lua.globals().get("map")?;
+    assert_eq!(map, map2);
+
+    // Named return values
+    let f = lua.create_function(|_, ()| Ok(indexmap! {"x" => 1, "y" => 2}))?;
+    lua.globals().set("f", f)?;
+    lua.load("local p = f(); assert(p.x == 1 and p.y == 2)")
+        .exec()?;
+
+    Ok(())
+}
+
 #[test]
 fn test_conv_btreeset() -> Result<()> {
     let lua = Lua::new();
@@ -138,3 +163,221 @@ fn test_conv_array() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_conv_owned_handles() -> Result<()> {
+    struct MyUserData(i32);
+    impl UserData for MyUserData {}
+
+    let lua = Lua::new();
+    let globals = lua.globals();
+    lua.load(
+        r#"
+        t = {1, 2, 3}
+        f = function(x) return x * 2 end
+        s = "hello"
+        co = coroutine.create(function() end)
+    "#,
+    )
+    .exec()?;
+    globals.set("ud", MyUserData(123))?;
+
+    let t: OwnedTable = globals.get("t")?;
+    let f: OwnedFunction = globals.get("f")?;
+    let s: OwnedString = globals.get("s")?;
+    let co: OwnedThread = globals.get("co")?;
+    let ud: OwnedAnyUserData = globals.get("ud")?;
+    assert!(globals.get::<_, OwnedTable>("f").is_err());
+
+    // Round trip
+    globals.set("t2", t.clone())?;
+    globals.set("f2", f.clone())?;
+    globals.set("s2", s.clone())?;
+    globals.set("co2", co.clone())?;
+    globals.set("ud2", ud.clone())?;
+    assert!(lua
+        .load("t == t2 and f == f2 and s == s2 and co == co2 and ud == ud2")
+        .eval()?);
+
+    // Owned handles keep the Lua state alive
+    drop(globals);
+    drop(lua);
+    assert_eq!(t.len()?, 3);
+    assert_eq!(f.call::<_, i32>(21)?, 42);
+    assert_eq!(s.to_str()?, "hello");
+    assert_eq!(co.to_ref().status(), ThreadStatus::Resumable);
+    assert_eq!(ud.borrow::<MyUserData>()?.0, 123);
+    assert_eq!(t.to_ref(), t.clone().into_ref());
+
+    Ok(())
+}
+
+#[test]
+fn test_conv_arg_wrappers() -> Result<()> {
+    let lua = Lua::new();
+
+    let f = lua.create_function(|_, (n, x): (Ranged<i64, 1, 10>, Ranged<f64, -1, 1>)| {
+        Ok(n.into_inner() as f64 * *x)
+    })?;
+    assert_eq!(f.call::<_, f64>((10, -0.5))?, -5.0);
+    assert_eq!(f.call::<_, f64>(("2", 1))?, 2.0);
+    assert!(lua
+        .unpack::<Ranged<u8, 0, 300>>(Value::Integer(255))
+        .is_ok());
+    assert!(lua
+        .unpack::<Ranged<u8, 0, 300>>(Value::Integer(256))
+        .is_err());
+    assert!(lua
+        .unpack::<Ranged<f32, 0, 1>>(Value::Number(f64::NAN))
+        .is_err());
+    assert_eq!(lua.pack(Ranged::<i32, 0, 1>(1))?, Value::Integer(1));
+
+    struct Greeter;
+    impl UserData for Greeter {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("greet", |_, _, name: NonEmptyString| {
+                Ok(format!("Hello, {}!", &*name))
+            });
+        }
+    }
+    lua.globals().set("greeter", Greeter)?;
+    let s: String = lua.load("greeter:greet('Lua')").eval()?;
+    assert_eq!(s, "Hello, Lua!");
+    assert_eq!(lua.load("greeter:greet(1)").eval::<String>()?, "Hello, 1!");
+    assert!(lua.unpack::<NonEmptyString>(Value::Nil).is_err());
+
+    match f.call::<_, f64>((11, 0)) {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { pos: 1, cause, .. } => {
+                let msg = "error converting Lua integer to i64 (value 11 out of range 1..=10)";
+                assert_eq!(cause.to_string(), msg);
+            }
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+    let err = f.call::<_, f64>((1, 1.5)).unwrap_err();
+    assert!(err.to_string().contains("bad argument #2"), "{err}");
+
+    let err = lua.load("greeter:greet('')").exec().unwrap_err();
+    let msg = "bad argument #2 to `Greeter.greet`: error converting Lua string to NonEmptyString";
+    assert!(err.to_string().contains(msg), "{err}");
+
+    let err = Error::bad_argument_type(3, "table", &Value::Boolean(true));
+    assert_eq!(
+        err.to_string(),
+        "bad argument #3: table expected, got boolean"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_conv_enum() -> Result<()> {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Level {
+        Low = 1,
+        High = 10,
+    }
+
+    impl LuaEnum for Level {
+        const VARIANTS: &'static [(&'static str, Self)] =
+            &[("Low", Level::Low), ("High", Level::High)];
+
+        fn value(self) -> mlua::Integer {
+            self as mlua::Integer
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("Level", lua.create_enum::<Level>()?)?;
+
+    let f = lua.create_function(|_, level: EnumValue<Level>| Ok(EnumValue(*level)))?;
+    lua.globals().set("f", f)?;
+    lua.load(
+        r#"
+        assert(Level.Low == 1 and Level.High == 10)
+        assert(Level[1] == "Low" and Level[10] == "High")
+        assert(f(Level.High) == 10 and f("Low") == 1 and f(10.0) == 10)
+    "#,
+    )
+    .exec()?;
+
+    #[cfg(any(
+        feature = "lua54",
+        feature = "lua53",
+        feature = "lua52",
+        feature = "luau"
+    ))]
+    {
+        let n = lua
+            .load("local n = 0 for _ in pairs(Level) do n = n + 1 end return n")
+            .eval::<i32>()?;
+        assert_eq!(n, 4);
+    }
+
+    let level: EnumValue<Level> = lua.unpack(Value::Integer(10))?;
+    assert_eq!(level.into_inner(), Level::High);
+    match lua.unpack::<EnumValue<Level>>(lua.pack("Medium")?) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "invalid variant 'Medium'")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    assert!(lua.unpack::<EnumValue<Level>>(Value::Integer(2)).is_err());
+    assert!(lua
+        .unpack::<EnumValue<Level>>(Value::Boolean(true))
+        .is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "bitflags")]
+#[test]
+fn test_conv_bitflags() -> Result<()> {
+    use mlua::Flags;
+
+    bitflags::bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Mode: u32 {
+            const READ = 0b001;
+            const WRITE = 0b010;
+            const EXEC = 0b100;
+            const RW = Self::READ.bits() | Self::WRITE.bits();
+        }
+    }
+
+    let lua = Lua::new();
+    let modes = lua.create_flags::<Mode>()?;
+    assert_eq!(modes.get::<_, u32>("RW")?, 3);
+    assert_eq!(modes.raw_len(), 0);
+    lua.globals().set("Mode", modes)?;
+
+    let f = lua.create_function(|_, mode: Flags<Mode>| Ok(Flags(*mode | Mode::EXEC)))?;
+    lua.globals().set("f", f)?;
+    lua.load(
+        r#"
+        assert(f(Mode.READ) == 5)
+        assert(f("READ|WRITE") == 7 and f(" RW ") == 7 and f("") == 4)
+        assert(f({"READ", Mode.WRITE, "EXEC"}) == 7)
+        assert(f({}) == 4)
+    "#,
+    )
+    .exec()?;
+
+    let mode: Flags<Mode> = lua.unpack(Value::Integer(6))?;
+    assert_eq!(mode.into_inner(), Mode::WRITE | Mode::EXEC);
+    match lua.unpack::<Flags<Mode>>(lua.pack("READ|DELETE")?) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "unknown flag 'DELETE'")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    assert!(lua.unpack::<Flags<Mode>>(Value::Integer(8)).is_err());
+    assert!(lua
+        .unpack::<Flags<Mode>>(lua.pack(vec!["READ", "X"])?)
+        .is_err());
+    assert!(lua.unpack::<Flags<Mode>>(Value::Boolean(true)).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+#![cfg(not(feature = "luau"))]
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use mlua::{DebugStop, Debugger, Lua, Result, StepAction, StopReason};
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(10));
+
+fn wait(debugger: &Debugger) -> DebugStop {
+    debugger.wait(TIMEOUT).expect("execution is not stopped")
+}
+
+fn local(debugger: &Debugger, level: usize, name: &str) -> Option<String> {
+    let locals = debugger.locals(level).unwrap();
+    locals.into_iter().find(|v| v.name == name).map(|v| v.value)
+}
+
+#[test]
+fn test_debugger_stepping() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = lua.attach_debugger()?;
+    debugger.set_breakpoint("dbg", 7);
+    debugger.set_breakpoint("dbg", 100);
+    assert_eq!(debugger.breakpoints_in("dbg"), [7, 100]);
+    assert!(debugger.remove_breakpoint("dbg", 100));
+    assert!(!debugger.remove_breakpoint("dbg", 100));
+    assert!(debugger.resume(StepAction::Continue).is_err());
+
+    let thread = thread::spawn(move || {
+        lua.load(
+            r#"
+            local function add(a, b)
+                local sum = a + b
+                return sum
+            end
+            local x = 10
+            local y = add(x, 5)
+            local z = add(y, 1)
+            return z
+        "#,
+        )
+        .set_name("@dbg")
+        .eval::<i64>()
+    });
+
+    let stop = wait(&debugger);
+    assert_eq!(stop.reason, StopReason::Breakpoint);
+    assert_eq!((stop.chunk.as_str(), stop.line), ("dbg", 7));
+    assert!(debugger.is_stopped());
+    assert_eq!(local(&debugger, 0, "x").as_deref(), Some("10"));
+    assert_eq!(local(&debugger, 0, "y"), None);
+    let add = debugger.locals(0)?.into_iter().find(|v| v.name == "add");
+    assert_eq!(add.map(|v| v.type_name), Some("function"));
+
+    // Step over the call
+    debugger.resume(StepAction::StepOver)?;
+    let stop = wait(&debugger);
+    assert_eq!((stop.reason, stop.line), (StopReason::Step, 8));
+    assert_eq!(local(&debugger, 0, "y").as_deref(), Some("15"));
+
+    // Step into the call
+    debugger.resume(StepAction::StepInto)?;
+    let stop = wait(&debugger);
+    assert_eq!((stop.reason, stop.line), (StopReason::Step, 3));
+    assert_eq!(local(&debugger, 0, "a").as_deref(), Some("15"));
+    assert_eq!(local(&debugger, 0, "b").as_deref(), Some("1"));
+    assert_eq!(local(&debugger, 1, "y").as_deref(), Some("15"));
+    let stack = debugger.stack()?;
+    assert_eq!(stack[0].name.as_deref(), Some("add"));
+    assert_eq!(stack[0].line, Some(3));
+    assert_eq!(stack[1].line, Some(8));
+
+    // Step out of the function
+    debugger.resume(StepAction::StepOut)?;
+    let stop = wait(&debugger);
+    assert_eq!(stop.reason, StopReason::Step);
+    assert!(stop.line >= 8);
+    assert_eq!(debugger.stack()?[0].name, None);
+
+    debugger.resume(StepAction::Continue)?;
+    assert_eq!(thread.join().unwrap()?, 16);
+    assert!(debugger.wait(Some(Duration::ZERO)).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger_pause() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = lua.attach_debugger()?;
+
+    let (started, running) = (
+        Arc::new(AtomicBool::new(false)),
+        Arc::new(AtomicBool::new(true)),
+    );
+    let (started2, running2) = (started.clone(), running.clone());
+    let is_running = lua.create_function(move |_, ()| {
+        started2.store(true, Ordering::Relaxed);
+        Ok(running2.load(Ordering::Relaxed))
+    })?;
+    lua.globals().set("is_running", is_running)?;
+
+    let thread = thread::spawn(move || {
+        lua.load("local n = 0\nwhile is_running() do\n    n = n + 1\nend\nreturn n")
+            .set_name("=loop")
+            .eval::<i64>()
+    });
+
+    while !started.load(Ordering::Relaxed) {
+        thread::yield_now();
+    }
+    debugger.pause();
+    let stop = wait(&debugger);
+    assert_eq!(stop.reason, StopReason::Pause);
+    assert_eq!(stop.chunk, "loop");
+    assert!(local(&debugger, 0, "n").is_some());
+
+    running.store(false, Ordering::Relaxed);
+    debugger.resume(StepAction::Continue)?;
+    assert!(thread.join().unwrap()? >= 0);
+
+    Ok(())
+}
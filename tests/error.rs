@@ -1,3 +1,5 @@
+use std::fmt;
+
 use mlua::{Error, ErrorContext, Lua, Result};
 
 #[test]
@@ -31,3 +33,140 @@ fn test_error_context() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_error_traceback() -> Result<()> {
+    let lua = Lua::new();
+
+    // Runtime error
+    let err = lua
+        .load(
+            r#"
+            local function inner()
+                error("boom")
+            end
+            inner()
+        "#,
+        )
+        .set_name("=chunk")
+        .exec()
+        .unwrap_err();
+    let frames = err.traceback().expect("runtime error must have traceback");
+    assert!(frames
+        .iter()
+        .any(|f| f.is_c && f.name.as_deref() == Some("error")));
+    let inner = frames
+        .iter()
+        .find(|f| f.name.as_deref() == Some("inner"))
+        .expect("frame of `inner` function");
+    assert_eq!(
+        (inner.source.as_str(), inner.line, inner.is_c),
+        ("chunk", Some(3), false)
+    );
+    assert!(frames
+        .iter()
+        .any(|f| f.source == "chunk" && f.line == Some(5)));
+
+    // Chunk names that don't survive a round trip through the traceback string
+    let err = lua
+        .load("local x = nil; return x.field")
+        .set_name("=weird: name\nwith newline")
+        .exec()
+        .unwrap_err();
+    let frames = err.traceback().expect("runtime error must have traceback");
+    assert_eq!(frames[0].source, "weird: name\nwith newline");
+    assert_eq!(frames[0].line, Some(1));
+
+    // Callback error
+    let func = lua.create_function(|_, ()| Err::<(), _>(Error::RuntimeError("fail".into())))?;
+    lua.globals().set("rust_func", func)?;
+    let err = lua
+        .load("rust_func()")
+        .set_name("=caller")
+        .exec()
+        .unwrap_err();
+    let frames = err.traceback().expect("callback error must have traceback");
+    assert!(frames[0].is_c);
+    assert!(frames
+        .iter()
+        .any(|f| f.source == "caller" && f.line == Some(1)));
+
+    // Context is transparent
+    let err = err.context("some context");
+    assert_eq!(err.traceback(), Some(frames));
+
+    assert_eq!(Error::RuntimeError("no traceback".into()).traceback(), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_error_downcast() -> Result<()> {
+    #[derive(Debug, PartialEq)]
+    struct MyError(i32);
+
+    impl fmt::Display for MyError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "my error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for MyError {}
+
+    let lua = Lua::new();
+
+    let func = lua.create_function(|_, ()| {
+        Err::<(), _>(Error::external(MyError(42))).context("inside callback")
+    })?;
+    lua.globals().set("func", func)?;
+
+    // Rust -> Lua -> Rust -> Lua -> Rust
+    let outer = lua.create_function(|lua, ()| lua.load("func()").exec().context("calling func"))?;
+    let err = outer.call::<_, ()>(()).unwrap_err();
+    assert!(matches!(err, Error::CallbackError { .. }));
+    assert_eq!(err.downcast_ref::<MyError>(), Some(&MyError(42)));
+    assert!(err.downcast_ref::<std::io::Error>().is_none());
+
+    let err = match err.downcast::<std::io::Error>() {
+        Ok(_) => panic!("unexpected io::Error"),
+        Err(err) => err,
+    };
+    assert_eq!(*err.downcast::<MyError>().unwrap(), MyError(42));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_context_chain() -> Result<()> {
+    let lua = Lua::new();
+
+    let func = lua.create_function(|lua, path: String| {
+        lua.load("error('invalid syntax')")
+            .exec()
+            .with_context(|_| format!("failed to parse `{path}`"))
+    })?;
+    lua.globals().set("parse", func)?;
+
+    let err = lua
+        .load("parse('config.lua')")
+        .exec()
+        .context("while loading config")
+        .unwrap_err();
+
+    let chain = err.chain().collect::<Vec<_>>();
+    assert_eq!(chain.len(), 4);
+    assert!(
+        matches!(chain[0], Error::WithContext { context, .. } if context == "while loading config")
+    );
+    assert!(matches!(chain[1], Error::CallbackError { .. }));
+    assert!(
+        matches!(chain[2], Error::WithContext { context, .. } if context == "failed to parse `config.lua`")
+    );
+    assert!(matches!(chain[3], Error::RuntimeError(msg) if msg.contains("invalid syntax")));
+
+    let msg = err.to_string();
+    assert!(msg.starts_with("while loading config\n"));
+    assert!(msg.contains("failed to parse `config.lua`"));
+
+    Ok(())
+}
@@ -86,7 +86,7 @@ fn test_rust_function() -> Result<()> {
 fn test_c_function() -> Result<()> {
     let lua = Lua::new();
 
-    unsafe extern "C" fn c_function(state: *mut mlua::lua_State) -> std::os::raw::c_int {
+    unsafe extern "C-unwind" fn c_function(state: *mut mlua::lua_State) -> std::os::raw::c_int {
         let lua = Lua::init_from_ptr(state);
         lua.globals().set("c_function", true).unwrap();
         0
@@ -114,6 +114,49 @@ fn test_dump() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_function_disassemble() -> Result<()> {
+    let lua = Lua::new();
+
+    let func = lua
+        .load(
+            r#"
+            return function(t)
+                return t.x + 1
+            end
+        "#,
+        )
+        .eval::<Function>()?;
+    let instructions = func.disassemble();
+    assert!(!instructions.is_empty());
+    assert_eq!(instructions[0].pc, 1);
+    assert_eq!(instructions[0].line, Some(3));
+
+    let opcodes = instructions
+        .iter()
+        .map(|ins| ins.opcode)
+        .collect::<Vec<_>>();
+    #[cfg(feature = "lua54")]
+    assert_eq!(&opcodes[..3], ["GETFIELD", "ADDI", "MMBINI"]);
+    #[cfg(any(feature = "lua53", feature = "lua52", feature = "lua51"))]
+    assert_eq!(&opcodes[..2], ["GETTABLE", "ADD"]);
+    #[cfg(feature = "luajit")]
+    assert_eq!(&opcodes[..2], ["TGETS", "ADDVN"]);
+    assert!(opcodes.iter().any(|op| op.starts_with("RET")));
+
+    let text = instructions[0].to_string();
+    assert!(text.starts_with("1"));
+    assert!(text.contains("[3]"));
+    assert!(text.contains(instructions[0].opcode));
+
+    // C functions have no bytecode
+    let print = lua.globals().get::<_, Function>("print")?;
+    assert!(print.disassemble().is_empty());
+
+    Ok(())
+}
+
 #[test]
 fn test_function_info() -> Result<()> {
     let lua = Lua::new();
@@ -0,0 +1,64 @@
+use std::thread;
+
+use mlua::{Error, Lua, LuaHandle, Result};
+
+#[test]
+fn test_handle() -> Result<()> {
+    let handle = LuaHandle::with(|| {
+        let lua = Lua::new();
+        lua.globals().set(
+            "rust_add",
+            lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?,
+        )?;
+        Ok(lua)
+    })?;
+
+    handle.set_global("counter", 0).wait()?;
+    handle
+        .load::<()>("function incr(n) counter = counter + n; return counter end")
+        .wait()?;
+
+    let threads = (0..4)
+        .map(|_| {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    handle.call::<_, i64>("incr", 1).wait().unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(handle.get_global::<i64>("counter").wait()?, 40);
+
+    // Responses are futures
+    let sum = futures::executor::block_on(handle.call::<_, i64>("rust_add", (2, 3)))?;
+    assert_eq!(sum, 5);
+    let n = futures::executor::block_on(handle.exec(|lua| lua.load("counter").eval::<i64>()))?;
+    assert_eq!(n, 40);
+
+    // Errors are returned
+    match handle.load::<()>("error('boom')").wait() {
+        Err(Error::RuntimeError(msg)) => assert!(msg.contains("boom")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Panics are returned too and don't stop the thread
+    match handle.exec::<(), _>(|_| panic!("test panic")).wait() {
+        Err(Error::CallbackPanic { .. }) => {}
+        r => panic!("expected CallbackPanic, got {r:?}"),
+    }
+    assert_eq!(handle.get_global::<i64>("counter").wait()?, 40);
+
+    Ok(())
+}
+
+#[test]
+fn test_handle_init_error() {
+    match LuaHandle::with(|| Err(Error::RuntimeError("init failed".to_string()))) {
+        Err(Error::RuntimeError(msg)) => assert_eq!(msg, "init failed"),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+}
@@ -6,7 +6,7 @@ use std::str;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::{Arc, Mutex};
 
-use mlua::{DebugEvent, Error, HookTriggers, Lua, Result, Value};
+use mlua::{Capability, DebugEvent, Error, HookTriggers, Lua, Result, Value};
 
 #[test]
 fn test_hook_triggers_bitor() {
@@ -27,6 +27,7 @@ fn test_line_counts() -> Result<()> {
     let hook_output = output.clone();
 
     let lua = Lua::new();
+    assert!(lua.supports(Capability::Hooks));
     lua.set_hook(HookTriggers::every_line(), move |_lua, debug| {
         assert_eq!(debug.event(), DebugEvent::Line);
         hook_output.lock().unwrap().push(debug.curr_line());
@@ -0,0 +1,40 @@
+#![cfg(feature = "json")]
+
+use mlua::{Lua, LuaSerdeExt, Result, Value};
+
+#[test]
+fn test_json_module() -> Result<()> {
+    let lua = Lua::new();
+    lua.enable_json_module()?;
+
+    lua.load(
+        r#"
+        assert(require("json") == json)
+
+        assert(json.encode(1) == "1")
+        assert(json.encode(1.5) == "1.5")
+        assert(json.encode("a\"b") == [["a\"b"]])
+        assert(json.encode({1, 2, 3}) == "[1,2,3]")
+        assert(json.encode({a = json.null}) == [[{"a":null}]])
+        assert(json.encode(json.decode("[]")) == "[]")
+        local pretty = json.encode({x = {true, false}}, true)
+        assert(pretty == '{\n  "x": [\n    true,\n    false\n  ]\n}')
+
+        local t = json.decode([[{"a": [1, 2.5, "s", null], "b": {"c": false}}]])
+        assert(t.a[1] == 1 and t.a[2] == 2.5 and t.a[3] == "s" and t.a[4] == json.null)
+        assert(t.b.c == false)
+        assert(getmetatable(t.a) ~= nil)
+        assert(json.encode(json.decode('{"k":[{},[]]}')) == '{"k":[{},[]]}')
+    "#,
+    )
+    .exec()?;
+
+    let null: Value = lua.load("json.null").eval()?;
+    assert_eq!(null, lua.null());
+
+    let err = lua.load("json.decode('{')").exec().unwrap_err();
+    assert!(err.to_string().contains("EOF"), "{err}");
+    assert!(lua.load("json.encode(print)").exec().is_err());
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "log")]
+
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use mlua::{Lua, Result};
+
+struct TestLogger;
+
+static RECORDS: Mutex<Vec<(Level, String, String, Option<String>, Option<u32>)>> =
+    Mutex::new(Vec::new());
+
+impl Log for TestLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        RECORDS.lock().unwrap().push((
+            record.level(),
+            record.target().to_string(),
+            record.args().to_string(),
+            record.file().map(|s| s.to_string()),
+            record.line(),
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn test_attach_logger() -> Result<()> {
+    log::set_logger(&TestLogger).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let lua = Lua::new();
+    lua.attach_logger("lua_test")?;
+
+    lua.load(
+        r#"
+        print("hello", 1, true)
+        log.error("oops")
+        warn("part1", "part2")
+        "#,
+    )
+    .set_name("=logtest")
+    .exec()?;
+
+    let records = RECORDS.lock().unwrap();
+    let file = Some("logtest".to_string());
+    assert_eq!(
+        *records,
+        vec![
+            (
+                Level::Info,
+                "lua_test".into(),
+                "hello\t1\ttrue".into(),
+                file.clone(),
+                Some(2)
+            ),
+            (
+                Level::Error,
+                "lua_test".into(),
+                "oops".into(),
+                file.clone(),
+                Some(3)
+            ),
+            (
+                Level::Warn,
+                "lua_test".into(),
+                "part1part2".into(),
+                file,
+                Some(4)
+            ),
+        ]
+    );
+
+    Ok(())
+}
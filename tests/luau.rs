@@ -1,5 +1,6 @@
 #![cfg(feature = "luau")]
 
+use std::collections::HashMap;
 use std::env;
 use std::fmt::Debug;
 use std::fs;
@@ -7,7 +8,10 @@ use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use mlua::{Compiler, CoverageInfo, Error, Lua, Result, Table, ThreadStatus, Value, VmState};
+use mlua::{
+    Compiler, CoverageInfo, Error, Lua, LuauBuffer, Require, Result, Table, ThreadStatus, Value,
+    VmState,
+};
 
 #[test]
 fn test_require() -> Result<()> {
@@ -40,6 +44,67 @@ fn test_require() -> Result<()> {
     .exec()
 }
 
+#[test]
+fn test_require_custom() -> Result<()> {
+    struct VirtualFs(HashMap<&'static str, &'static str>);
+
+    impl Require for VirtualFs {
+        fn alias(&self, alias: &str) -> Option<String> {
+            (alias == "std").then(|| "libs/std".into())
+        }
+
+        fn load(&self, name: &str) -> Result<Vec<u8>> {
+            match self.0.get(name) {
+                Some(source) => Ok(source.as_bytes().to_vec()),
+                None => Err(Error::RuntimeError(format!("module '{name}' not found"))),
+            }
+        }
+
+        fn cache(&self, name: &str) -> bool {
+            name != "app/uncached"
+        }
+    }
+
+    let lua = Lua::new();
+    let files = [
+        (
+            "app/main",
+            "return require('./util') .. require('@STD/x') .. require('@self/inner')",
+        ),
+        ("app/util", "loads = (loads or 0) + 1; return 'u'"),
+        ("app/main/inner", "return require('../util')"),
+        ("app/uncached", "uncached = (uncached or 0) + 1"),
+        ("libs/std/x", "return require('../std/./y')"),
+        ("libs/std/y", "return 'y'"),
+        ("cycle/a", "return require('./b')"),
+        ("cycle/b", "return require('./a')"),
+    ];
+    lua.set_require(VirtualFs(files.into_iter().collect()))?;
+
+    let s: String = lua.load("return require('./app/main')").eval()?;
+    assert_eq!(s, "uyu");
+    assert_eq!(lua.globals().get::<_, i32>("loads")?, 1);
+
+    lua.load("require('./app/uncached'); require('./app/uncached')")
+        .exec()?;
+    assert_eq!(lua.globals().get::<_, i32>("uncached")?, 2);
+
+    match lua.load("require('./cycle/a')").exec() {
+        Err(err) => assert!(err.to_string().contains("cyclic module dependency")),
+        Ok(_) => panic!("expected error"),
+    }
+    match lua.load("require('module')").exec() {
+        Err(err) => assert!(err.to_string().contains("must start with a valid prefix")),
+        Ok(_) => panic!("expected error"),
+    }
+    match lua.load("require('@unknown/module')").exec() {
+        Err(err) => assert!(err.to_string().contains("@unknown is not a valid alias")),
+        Ok(_) => panic!("expected error"),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_vectors() -> Result<()> {
     let lua = Lua::new();
@@ -73,6 +138,57 @@ fn test_vectors() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_buffer() -> Result<()> {
+    let lua = Lua::new();
+
+    let buf: LuauBuffer = lua
+        .load("local b = buffer.create(16); buffer.writeu16(b, 0, 0x1234); return b")
+        .eval()?;
+    assert_eq!(buf.len(), 16);
+    assert_eq!(buf.read_u16(0)?, 0x1234);
+    assert_eq!(&buf.as_slice()[..4], &[0x34, 0x12, 0, 0]);
+
+    // Writes from Rust are visible to Lua without copying
+    let mut buf2 = buf.clone();
+    buf2.write_i32(2, -2)?;
+    buf2.write_f32(8, 0.5)?;
+    lua.globals().set("buf", buf.clone())?;
+    lua.load(
+        r#"
+        assert(buffer.readi32(buf, 2) == -2)
+        assert(buffer.readf32(buf, 8) == 0.5)
+        buffer.writeu8(buf, 15, 255)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(buf.read_u8(15)?, 255);
+    assert_eq!(buf2.as_slice().as_ptr(), buf.as_slice().as_ptr());
+    assert_eq!(lua.load("buf").eval::<LuauBuffer>()?, buf);
+
+    // Out of bounds access
+    assert!(buf.read_f64(9).is_err());
+    assert!(buf2.write_bytes(usize::MAX, &[1, 2]).is_err());
+    assert_eq!(buf.read_bytes::<2>(2)?, [0xfe, 0xff]);
+
+    let buf = lua.create_buffer(b"hello")?;
+    assert_eq!(
+        lua.load("return buffer.tostring(...)")
+            .call::<_, String>(buf.clone())?,
+        "hello"
+    );
+    assert_eq!(buf.to_vec(), b"hello");
+    assert!(lua.create_buffer([])?.is_empty());
+
+    match lua.load("1").eval::<LuauBuffer>() {
+        Err(Error::FromLuaConversionError { to: "buffer", .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    assert_eq!(Value::Buffer(buf).type_name(), "buffer");
+
+    Ok(())
+}
+
 #[test]
 fn test_readonly_table() -> Result<()> {
     let lua = Lua::new();
@@ -166,6 +282,30 @@ fn test_sandbox_threads() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_sandbox_environment() -> Result<()> {
+    let lua = Lua::new();
+    lua.sandbox(true)?;
+
+    let env1 = lua.create_sandbox_environment()?;
+    let env2 = lua.create_sandbox_environment()?;
+    lua.load("var = 1").set_environment(env1.clone()).exec()?;
+    lua.load("var = 2").set_environment(env2.clone()).exec()?;
+    assert_eq!(env1.get::<_, i32>("var")?, 1);
+    assert_eq!(env2.get::<_, i32>("var")?, 2);
+    assert_eq!(lua.globals().get::<_, Option<i32>>("var")?, None);
+
+    // Shared globals are visible in every environment
+    lua.set_shared_global("shared", "value")?;
+    let f = lua.load("return shared");
+    assert_eq!(f.set_environment(env1).eval::<String>()?, "value");
+    assert_eq!(lua.load("return shared").eval::<String>()?, "value");
+    assert!(lua.load("shared = 1").exec().is_ok());
+    assert_eq!(lua.globals().get::<_, i32>("shared")?, 1);
+
+    Ok(())
+}
+
 #[test]
 fn test_interrupts() -> Result<()> {
     let lua = Lua::new();
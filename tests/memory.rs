@@ -1,14 +1,11 @@
 use std::sync::Arc;
 
-use mlua::{GCMode, Lua, Result, UserData};
+use mlua::{Capability, Error, GCMode, Lua, Result, UserData};
 
-#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
-use mlua::Error;
-
-#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
 #[test]
 fn test_memory_limit() -> Result<()> {
     let lua = Lua::new();
+    assert!(lua.supports(Capability::MemoryLimit));
 
     let initial_memory = lua.used_memory();
     assert!(
@@ -78,8 +75,6 @@ fn test_gc_control() -> Result<()> {
 #[cfg(any(feature = "lua53", feature = "lua52"))]
 #[test]
 fn test_gc_error() {
-    use mlua::Error;
-
     let lua = Lua::new();
     match lua
         .load(
@@ -102,3 +97,94 @@ fn test_gc_error() {
         Ok(()) => panic!("__gc error did not result in error"),
     }
 }
+
+#[test]
+fn test_memory_observer() -> Result<()> {
+    use std::sync::Mutex;
+
+    use mlua::{MemoryEventKind, MemoryTriggers};
+
+    let lua = Lua::new();
+    lua.globals()
+        .set("check", lua.create_function(|_, ()| Ok(()))?)?;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events2 = events.clone();
+    let threshold = lua.used_memory() + 4 * 1024 * 1024;
+    let triggers = MemoryTriggers {
+        large_allocation: Some(1024 * 1024),
+        thresholds: vec![threshold],
+    };
+    lua.set_memory_observer(triggers, move |_, event| {
+        if let MemoryEventKind::LargeAllocation { size } = event.kind {
+            if size >= 16 * 1024 * 1024 {
+                return Err(Error::RuntimeError("allocation is too large".to_string()));
+            }
+        }
+        events2.lock().unwrap().push(event.clone());
+        Ok(())
+    })?;
+
+    // Small allocations are not reported
+    lua.load("local t = {1, 2, 3}; check()").exec()?;
+    assert!(events.lock().unwrap().is_empty());
+
+    lua.load(
+        r#"
+        local function build()
+            local s = string.rep("x", 2 * 1024 * 1024)
+            check()
+            return s
+        end
+        big = build()
+    "#,
+    )
+    .set_name("big")
+    .exec()?;
+    {
+        let events = events.lock().unwrap();
+        let is_large =
+            |kind| matches!(kind, MemoryEventKind::LargeAllocation { size } if size >= 2 << 20);
+        let event = (events.iter())
+            .find(|ev| is_large(ev.kind))
+            .expect("large allocation was not reported");
+        assert!(event.traceback.contains("build"), "{}", event.traceback);
+    }
+    events.lock().unwrap().clear();
+
+    // Exceed the threshold
+    lua.load(
+        r#"
+        parts = {}
+        for i = 1, 3 do parts[i] = string.rep(tostring(i), 1024 * 1024) end
+        check()
+    "#,
+    )
+    .exec()?;
+    {
+        let events = events.lock().unwrap();
+        let exceeded = events
+            .iter()
+            .filter(|ev| ev.kind == MemoryEventKind::ThresholdExceeded { threshold })
+            .collect::<Vec<_>>();
+        assert!(!exceeded.is_empty());
+        assert!(exceeded.iter().all(|ev| ev.used_memory > threshold));
+    }
+
+    // Observer errors interrupt the running code
+    match lua
+        .load("local s = string.rep('x', 32 * 1024 * 1024); check()")
+        .exec()
+    {
+        Err(err) => assert!(err.to_string().contains("allocation is too large")),
+        Ok(()) => panic!("expected error"),
+    }
+
+    lua.remove_memory_observer();
+    events.lock().unwrap().clear();
+    lua.load("local s = string.rep('x', 2 * 1024 * 1024); check()")
+        .exec()?;
+    assert!(events.lock().unwrap().is_empty());
+
+    Ok(())
+}
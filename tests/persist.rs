@@ -0,0 +1,153 @@
+#![cfg(feature = "persist")]
+
+use mlua::{Error, Function, Lua, Result, Table};
+
+#[test]
+fn test_persist_tables() -> Result<()> {
+    let lua = Lua::new();
+
+    let root: Table = lua
+        .load(
+            r#"
+            local shared = {x = 1, y = 2.5}
+            local root = {
+                a = shared, b = shared, str = "hello\0world", flag = true,
+                list = {1, 2, 3}, [10] = "ten",
+            }
+            root.self = root
+            setmetatable(shared, {__index = {z = "z"}})
+            return root
+        "#,
+        )
+        .eval()?;
+
+    let mut data = Vec::new();
+    lua.persist(&root, &mut data)?;
+    let restored = lua.unpersist(&mut data.as_slice())?;
+    lua.globals().set("restored", restored)?;
+    lua.load(
+        r#"
+        local r = restored
+        assert(r.self == r)
+        assert(r.a == r.b and r.a.x == 1 and r.a.y == 2.5)
+        assert(math.type == nil or math.type(r.a.x) == "integer")
+        assert(r.a.z == "z")
+        assert(r.str == "hello\0world" and r.flag == true)
+        assert(#r.list == 3 and r.list[3] == 3 and r[10] == "ten")
+    "#,
+    )
+    .exec()
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_persist_closures() -> Result<()> {
+    let lua = Lua::new();
+
+    let root: Table = lua
+        .load(
+            r#"
+            local count = 10
+            local function inc() count = count + 1; return count end
+            local function get() return count end
+            local function fact(n) if n <= 1 then return 1 end return n * fact(n - 1) end
+            return {
+                inc = inc, get = get, fact = fact,
+                co = coroutine.create(function(a) local b = coroutine.yield(a + 1); return b * 2 end),
+            }
+        "#,
+        )
+        .eval()?;
+
+    let mut data = Vec::new();
+    lua.persist(&root, &mut data)?;
+    let restored = lua.unpersist(&mut data.as_slice())?;
+    lua.globals().set("restored", restored)?;
+    lua.load(
+        r#"
+        local r = restored
+        assert(r.inc() == 11)
+        assert(r.fact(5) == 120)
+        local _, v = coroutine.resume(r.co, 1)
+        assert(v == 2)
+        local _, v = coroutine.resume(r.co, 5)
+        assert(v == 10)
+    "#,
+    )
+    .exec()?;
+
+    // Upvalues are shared between closures since Lua 5.2
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    lua.load("assert(restored.get() == 11)").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_permanents() -> Result<()> {
+    let lua = Lua::new();
+
+    let permanents = lua.create_table()?;
+    let double = lua.create_function(|_, x: i64| Ok(x * 2))?;
+    permanents.set("double", double.clone())?;
+
+    let root = lua.create_table()?;
+    root.set("double", double)?;
+    root.set("globals", lua.globals())?;
+
+    // Host functions must be in the permanents table
+    match lua.persist(&root, &mut Vec::new()) {
+        Err(Error::FromLuaConversionError {
+            from: "function", ..
+        }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    let mut data = Vec::new();
+    lua.persist_with(&root, &permanents, &mut data)?;
+    let restored = lua.unpersist_with(&permanents, &mut data.as_slice())?;
+    assert_eq!(
+        restored.get::<_, Function>("double")?.call::<_, i64>(21)?,
+        42
+    );
+    assert_eq!(restored.get::<_, Table>("globals")?, lua.globals());
+
+    // Missing permanent
+    match lua.unpersist(&mut data.as_slice()) {
+        Err(Error::ToLuaConversionError { .. }) => {}
+        r => panic!("expected ToLuaConversionError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_errors() -> Result<()> {
+    let lua = Lua::new();
+
+    // Started coroutines are not supported
+    let thread = lua.create_thread(lua.load("coroutine.yield()").into_function()?)?;
+    thread.resume::<_, ()>(())?;
+    let root = lua.create_table()?;
+    root.set("co", thread)?;
+    match lua.persist(&root, &mut Vec::new()) {
+        Err(Error::FromLuaConversionError { from: "thread", .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    // Malformed input
+    let mut data = Vec::new();
+    lua.persist(&lua.create_table()?, &mut data)?;
+    for bytes in [
+        &b"garbage"[..],
+        &data[..data.len() - 1],
+        &[&data[..], &[0]].concat(),
+    ] {
+        match lua.unpersist(&mut &bytes[..]) {
+            Err(Error::ToLuaConversionError { .. }) => {}
+            r => panic!("expected ToLuaConversionError, got {r:?}"),
+        }
+    }
+
+    Ok(())
+}
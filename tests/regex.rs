@@ -0,0 +1,86 @@
+#![cfg(feature = "regex")]
+
+use mlua::{Lua, Result};
+
+#[test]
+fn test_regex_module() -> Result<()> {
+    let lua = Lua::new();
+    let module = lua.load_regex_module()?;
+    assert_eq!(lua.load_regex_module()?, module);
+
+    lua.load(
+        r##"
+        local regex = require("regex")
+        assert(regex == ...)
+        local re = regex.new([[(?P<key>\w+)\s*=\s*(?P<value>\d+)?]])
+        assert(tostring(re) == [[regex: (?P<key>\w+)\s*=\s*(?P<value>\d+)?]])
+
+        assert(re:is_match("a = 1"))
+        assert(not re:is_match("= 1"))
+
+        -- find
+        local s, e = re:find("  abc = 12;")
+        assert(s == 3 and e == 10)
+        assert(re:find("abc = 12", 2) == 2)
+        assert(re:find("abc = 12", 100) == nil)
+        assert(re:find("???") == nil)
+
+        -- match
+        local k, v = re:match("x=5")
+        assert(k == "x" and v == "5")
+        k, v = re:match("x=")
+        assert(k == "x" and v == false)
+        assert(regex.new("%d+"):match("x") == nil)
+        assert(regex.new([[\d+]]):match("ab12cd") == "12")
+
+        -- captures
+        local caps = re:captures("foo = 42")
+        assert(caps[0] == "foo = 42" and caps[1] == "foo" and caps[2] == "42")
+        assert(caps.key == "foo" and caps.value == "42")
+        assert(re:captures("") == nil)
+
+        -- gmatch
+        local items = {}
+        for k, v in re:gmatch("a=1, b=2, c=") do
+            table.insert(items, k .. ":" .. tostring(v))
+        end
+        assert(table.concat(items, " ") == "a:1 b:2 c:false")
+        local words = {}
+        for w in regex.new([[\w+]]):gmatch("one two  three") do
+            table.insert(words, w)
+        end
+        assert(table.concat(words, ",") == "one,two,three")
+
+        -- replace
+        local digits = regex.new([[\d]])
+        local r, n = digits:replace("a1b2c3", "#")
+        assert(r == "a#b#c#" and n == 3)
+        r, n = digits:replace("a1b2c3", "#", 2)
+        assert(r == "a#b#c3" and n == 2)
+        r = re:replace("x=1 y=2", "${value}=$key")
+        assert(r == "1=x 2=y")
+        r = digits:replace("a1b2", function(d) return tonumber(d) * 2 end)
+        assert(r == "a2b4")
+        r = digits:replace("a1b2", function(d) if d == "1" then return "one" end end)
+        assert(r == "aoneb2")
+
+        -- split
+        local parts = regex.new([[\s*,\s*]]):split("a , b,c")
+        assert(#parts == 3 and parts[1] == "a" and parts[2] == "b" and parts[3] == "c")
+        parts = regex.new(","):split("a,b,c", 2)
+        assert(#parts == 2 and parts[2] == "b,c")
+
+        assert(regex.escape("a.b*c") == [[a\.b\*c]])
+    "##,
+    )
+    .call(module)?;
+
+    let res = lua.load(r#"require("regex").new("(")"#).exec();
+    assert!(res.is_err());
+    let res = lua
+        .load(r#"require("regex").new("a"):replace("a", {})"#)
+        .exec();
+    assert!(res.is_err());
+
+    Ok(())
+}
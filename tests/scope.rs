@@ -1,10 +1,10 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use std::sync::Arc;
 
 use mlua::{
-    AnyUserData, Error, Function, Lua, MetaMethod, Result, String, UserData, UserDataFields,
-    UserDataMethods,
+    AnyUserData, Error, Function, Lua, MetaMethod, Result, ScopeLeak, String, UserData,
+    UserDataFields, UserDataMethods, UserDataTag, Value,
 };
 
 #[test]
@@ -357,6 +357,213 @@ fn test_scope_nonstatic_userdata_drop() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_scope_nonstatic_userdata_cached() -> Result<()> {
+    struct MyUserData<'a>(&'a Cell<i64>);
+
+    impl<'a> UserData for MyUserData<'a> {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("value", |_, data| Ok(data.0.get()));
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("inc", |_, data, ()| {
+                data.0.set(data.0.get() + 1);
+                Ok(())
+            });
+        }
+    }
+
+    struct MyTag;
+
+    impl UserDataTag for MyTag {
+        type UserData<'a> = MyUserData<'a>;
+    }
+
+    let lua = Lua::new();
+
+    let i = Cell::new(1);
+    let j = Cell::new(10);
+    lua.scope(|scope| {
+        let ud = scope.create_nonstatic_userdata_cached::<MyTag>(MyUserData(&i))?;
+        lua.globals().set("ud1", ud)?;
+        lua.load("ud1:inc(); mt1 = getmetatable(ud1)").exec()
+    })?;
+    assert_eq!(i.get(), 2);
+    assert!(lua.load("ud1:inc()").exec().is_err());
+
+    lua.scope(|scope| {
+        let ud = scope.create_nonstatic_userdata_cached::<MyTag>(MyUserData(&j))?;
+        lua.globals().set("ud2", ud)?;
+        lua.load(
+            r#"
+            ud2:inc()
+            assert(ud2.value == 11)
+            assert(getmetatable(ud2) == mt1)
+        "#,
+        )
+        .exec()?;
+
+        // Calling a method on a userdata of different type must fail
+        let other = scope.create_nonstatic_userdata(MyUserData(&i))?;
+        lua.globals().set("other", other)?;
+        assert!(lua.load("ud2.inc(other)").exec().is_err());
+        Ok(())
+    })?;
+    assert_eq!(j.get(), 11);
+
+    Ok(())
+}
+
+#[test]
+fn test_scope_nonstatic_userdata_user_values() -> Result<()> {
+    let lua = Lua::new();
+
+    struct MyUserData<'a>(&'a i64);
+
+    impl<'a> UserData for MyUserData<'a> {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("get", |_, data, ()| Ok(*data.0));
+        }
+    }
+
+    let i = 1;
+    lua.scope(|scope| {
+        let ud = scope.create_nonstatic_userdata(MyUserData(&i))?;
+        for n in 1..=10 {
+            ud.set_nth_user_value(n, n * 10)?;
+        }
+        ud.set_named_user_value("name", "ud")?;
+        for n in 1..=10 {
+            assert_eq!(ud.get_nth_user_value::<usize>(n)?, n * 10);
+        }
+        assert_eq!(ud.get_named_user_value::<String>("name")?, "ud");
+        lua.globals().set("ud", ud)?;
+        assert_eq!(lua.load("ud:get()").eval::<i64>()?, 1);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_scope_table_and_registry_value() -> Result<()> {
+    let lua = Lua::new();
+
+    let key = lua.scope(|scope| {
+        let caps = scope.create_table()?;
+        caps.set("secret", 42)?;
+        caps.set(1, "one")?;
+        caps.set_metatable(Some(lua.create_table()?));
+        lua.globals().set("caps", caps)?;
+        lua.load("assert(caps.secret == 42 and caps[1] == \"one\")")
+            .exec()?;
+
+        let key = scope.create_registry_value("temporary")?;
+        assert_eq!(lua.registry_value::<String>(&key)?, "temporary");
+        Ok(key)
+    })?;
+
+    lua.load(
+        r#"
+        assert(next(caps) == nil)
+        assert(getmetatable(caps) == nil)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(lua.registry_value::<Value>(&key)?, Value::Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_scope_child_and_close() -> Result<()> {
+    struct MyUserData(i64);
+    impl UserData for MyUserData {}
+
+    let lua = Lua::new();
+
+    let rc = Rc::new(Cell::new(0));
+    lua.scope(|scope| {
+        let r = rc.clone();
+        scope.child(|inner| {
+            let f = inner.create_function(move |_, ()| Ok(r.get()))?;
+            lua.globals().set("inner_f", f)?;
+            Ok(())
+        })?;
+        // The child scope has ended
+        assert_eq!(Rc::strong_count(&rc), 1);
+        assert!(lua.load("inner_f()").exec().is_err());
+
+        // Closing a child scope invalidates its values before the `child` call returns
+        scope.child(|inner| {
+            let ud = inner.create_userdata(MyUserData(1))?;
+            inner.close()?;
+            assert!(ud.borrow::<MyUserData>().is_err());
+            Ok(())
+        })?;
+
+        // Borrowed userdata are invalidated when the `child` call returns
+        let ud = scope.child(|inner| {
+            let ud = inner.create_userdata(MyUserData(2))?;
+            let borrow = ud.borrow::<MyUserData>()?;
+            match inner.close() {
+                Err(Error::UserDataBorrowMutError) => {}
+                r => panic!("expected UserDataBorrowMutError, got {:?}", r),
+            }
+            assert_eq!(borrow.0, 2);
+            drop(borrow);
+            Ok(ud)
+        })?;
+        assert!(ud.borrow::<MyUserData>().is_err());
+
+        // Scopes moved out of the `child` call cannot create new values
+        let inner = scope.child(Ok)?;
+        assert!(inner.create_function(|_, ()| Ok(())).is_err());
+        assert!(inner.create_table().is_err());
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn test_scope_leak_audit() -> Result<()> {
+    struct MyUserData;
+    impl UserData for MyUserData {}
+
+    let lua = Lua::new();
+
+    let leaks = RefCell::new(Vec::new());
+    lua.scope(|scope| {
+        scope.set_leak_audit(|found| leaks.borrow_mut().extend_from_slice(found));
+
+        let f = scope.create_function(|_, ()| Ok(()))?;
+        let ud = scope.create_userdata(MyUserData)?;
+        let _unused = scope.create_function(|_, ()| Ok(()))?;
+        lua.globals().set("f", f)?;
+        lua.load("config = { handlers = {} }").exec()?;
+        lua.load("config.handlers.ud = ...").call::<_, ()>(ud)?;
+        Ok(())
+    })?;
+
+    let mut leaks = leaks.into_inner();
+    leaks.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(
+        leaks,
+        vec![
+            ScopeLeak {
+                type_name: "userdata",
+                path: "_G.config.handlers.ud".to_string(),
+            },
+            ScopeLeak {
+                type_name: "function",
+                path: "_G.f".to_string(),
+            },
+        ]
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_scope_userdata_ref() -> Result<()> {
     let lua = Lua::new();
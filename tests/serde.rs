@@ -4,8 +4,8 @@ use std::collections::HashMap;
 use std::error::Error as StdError;
 
 use mlua::{
-    DeserializeOptions, Error, Lua, LuaSerdeExt, Result as LuaResult, SerializeOptions, UserData,
-    Value,
+    DeserializeOptions, EnumRepr, Error, Lua, LuaSerdeExt, Mirror, Result as LuaResult,
+    SerializeOptions, StringPool, UserData, UserDataSerialize, Value,
 };
 use serde::{Deserialize, Serialize};
 
@@ -71,6 +71,46 @@ fn test_serialize() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_serialize_userdata_hook() -> Result<(), Box<dyn StdError>> {
+    struct Point(i32, i32);
+    impl UserData for Point {}
+    impl UserDataSerialize for Point {
+        fn serialize_userdata<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            (self.0, self.1).serialize(serializer)
+        }
+    }
+
+    struct Opaque;
+    impl UserData for Opaque {}
+    impl UserDataSerialize for Opaque {}
+
+    let lua = Lua::new();
+    let value = Value::Table(lua.create_table_from([
+        ("point", lua.create_userdata(Point(1, 2))?),
+        ("opaque", lua.create_userdata(Opaque)?),
+    ])?);
+
+    // Not registered yet
+    assert!(serde_json::to_value(&value).is_err());
+
+    lua.register_userdata_serializer::<Point>();
+    lua.register_userdata_serializer::<Opaque>();
+    let json = serde_json::to_value(&value)?;
+    assert_eq!(json["point"], serde_json::json!([1, 2]));
+    let type_name = json["opaque"]["__userdata"].as_str().unwrap();
+    assert!(type_name.ends_with("Opaque"));
+
+    // Registered userdata is serializable by the Lua deserializer too
+    let json: serde_json::Value = lua.from_value(value)?;
+    assert_eq!(json["point"], serde_json::json!([1, 2]));
+
+    Ok(())
+}
+
 #[test]
 fn test_serialize_in_scope() -> LuaResult<()> {
     #[derive(Serialize, Clone)]
@@ -305,6 +345,166 @@ fn test_to_value_with_options() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_to_value_enum_repr() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+    let globals = lua.globals();
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(Circle),
+        Point(i32, i32),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[derive(Serialize)]
+    struct Circle {
+        r: u32,
+    }
+
+    let shapes = vec![
+        Shape::Empty,
+        Shape::Circle(Circle { r: 1 }),
+        Shape::Point(1, 2),
+        Shape::Rect { w: 3, h: 4 },
+    ];
+
+    let opts = SerializeOptions::new();
+    globals.set("null", lua.null())?;
+    let reprs = [
+        ("ext", EnumRepr::ExternallyTagged),
+        ("int", EnumRepr::InternallyTagged { tag: "type" }),
+        ("untagged", EnumRepr::Untagged),
+        ("str", EnumRepr::StringForUnit),
+    ];
+    for (name, repr) in reprs {
+        globals.set(name, lua.to_value_with(&shapes, opts.enum_repr(repr))?)?;
+    }
+    lua.load(
+        r#"
+        assert(ext[1] == "Empty")
+        assert(ext[2].Circle.r == 1)
+        assert(ext[3].Point[1] == 1 and ext[3].Point[2] == 2)
+        assert(ext[4].Rect.w == 3 and ext[4].Rect.h == 4)
+
+        assert(int[1].type == "Empty")
+        assert(int[2].type == "Circle" and int[2].r == 1)
+        assert(int[3].type == "Point" and int[3][1] == 1 and int[3][2] == 2)
+        assert(int[4].type == "Rect" and int[4].w == 3)
+
+        assert(untagged[1] == null)
+        assert(untagged[2].r == 1)
+        assert(untagged[3][2] == 2)
+        assert(untagged[4].h == 4 and untagged[4].type == nil)
+
+        assert(str[1] == "Empty")
+        assert(str[2].r == 1)
+    "#,
+    )
+    .exec()?;
+
+    // Internally tagged newtype variants must contain a table
+    #[derive(Serialize)]
+    enum Scalar {
+        Number(i32),
+    }
+    let opts = SerializeOptions::new().enum_repr(EnumRepr::InternallyTagged { tag: "type" });
+    match lua.to_value_with(&Scalar::Number(1), opts) {
+        Err(Error::SerializeError(_)) => {}
+        r => panic!("expected SerializeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_serde_round_trip_markers() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+    let globals = lua.globals();
+    globals.set("null", lua.null())?;
+    let empty_array = lua.empty_array()?;
+    assert_eq!(empty_array.get_metatable(), Some(lua.array_metatable()));
+    assert_eq!(serde_json::to_string(&empty_array)?, "[]");
+    globals.set("empty_array", empty_array)?;
+
+    let value = lua
+        .load(
+            r#"{arr = empty_array, map = {}, none = null, sparse = {[5] = "five", [-1] = "neg"}}"#,
+        )
+        .eval()?;
+    let json: serde_json::Value = lua.from_value(value)?;
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "arr": [],
+            "map": {},
+            "none": null,
+            "sparse": {"5": "five", "-1": "neg"},
+        })
+    );
+
+    let options = SerializeOptions::new().detect_integer_keys(true);
+    globals.set("data", lua.to_value_with(&json, options)?)?;
+    lua.load(
+        r#"
+        assert(type(data.arr) == "table" and next(data.arr) == nil)
+        assert(getmetatable(data.arr) ~= nil)
+        assert(type(data.map) == "table" and getmetatable(data.map) == nil)
+        assert(data.none == null)
+        assert(data.sparse[5] == "five" and data.sparse[-1] == "neg")
+        assert(data.sparse["5"] == nil)
+    "#,
+    )
+    .exec()?;
+
+    // Without the option keys are kept as strings, non-canonical forms are never converted
+    let json = serde_json::json!({"1": 1, "01": 2, "+3": 3});
+    globals.set("data", lua.to_value(&json)?)?;
+    globals.set("data2", lua.to_value_with(&json, options)?)?;
+    lua.load(
+        r#"
+        assert(data["1"] == 1 and data[1] == nil)
+        assert(data2[1] == 1 and data2["01"] == 2 and data2["+3"] == 3)
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_to_from_table() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Test {
+        name: String,
+        values: Vec<u32>,
+    }
+
+    let test = Test {
+        name: "test".into(),
+        values: vec![1, 2, 3],
+    };
+    let table = lua.to_table(&test)?;
+    assert_eq!(table.get::<_, String>("name")?, "test");
+    assert_eq!(table.get::<_, mlua::Table>("values")?.raw_len(), 3);
+
+    table.set("name", "changed")?;
+    let got: Test = lua.from_table(&table)?;
+    assert_eq!(got.name, "changed");
+    assert_eq!(got.values, vec![1, 2, 3]);
+
+    // Non-table values are rejected
+    match lua.to_table(&123) {
+        Err(Error::ToLuaConversionError { to: "table", .. }) => {}
+        r => panic!("expected ToLuaConversionError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_from_value_nested_tables() -> Result<(), Box<dyn StdError>> {
     let lua = Lua::new();
@@ -517,6 +717,41 @@ fn test_from_value_with_options() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_from_value_borrowed() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Test<'a> {
+        name: &'a str,
+        data: &'a [u8],
+        tags: Vec<&'a str>,
+        #[serde(borrow)]
+        note: std::borrow::Cow<'a, str>,
+    }
+
+    let strings = StringPool::new();
+    let value = lua
+        .load(r#"{name = "test", data = "\255\0", tags = {"a", "b"}, note = "c"}"#)
+        .eval()?;
+    let got: Test = lua.from_value_borrowed(value, &strings)?;
+    assert_eq!(got.name, "test");
+    assert_eq!(got.data, b"\xff\x00");
+    assert_eq!(got.tags, vec!["a", "b"]);
+    assert!(matches!(got.note, std::borrow::Cow::Borrowed("c")));
+    assert!(strings.len() >= 5);
+
+    // Strings are pinned even after Lua values are collected
+    lua.gc_collect()?;
+    assert_eq!(got.name, "test");
+
+    // The pool holds owned string handles, so borrowed values outlive the `Lua` handle
+    drop(lua);
+    assert_eq!(got.tags, vec!["a", "b"]);
+
+    Ok(())
+}
+
 #[test]
 fn test_from_value_userdata() -> Result<(), Box<dyn StdError>> {
     let lua = Lua::new();
@@ -575,3 +810,115 @@ fn test_from_value_userdata() -> Result<(), Box<dyn StdError>> {
 
     Ok(())
 }
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_function_bytecode() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    let de_options = DeserializeOptions::new().function_bytecode(true);
+    let ser_options = SerializeOptions::new().function_bytecode(true);
+
+    let value: Value = lua
+        .load(
+            r#"
+            {
+                score = 10,
+                on_load = function(score) return string.format("score: %d", score) end,
+            }
+        "#,
+        )
+        .eval()?;
+
+    // Functions are not serialized by default
+    assert!(lua.from_value::<serde_value::Value>(value.clone()).is_err());
+
+    let saved = lua.from_value_with::<serde_value::Value>(value, de_options)?;
+    let restored = lua.to_value_with(&saved, ser_options)?;
+    lua.globals().set("restored", restored)?;
+    lua.load(r#"assert(restored.on_load(restored.score) == "score: 10")"#)
+        .exec()?;
+
+    // Without the option the bytecode stays a table
+    let restored = lua.to_value(&saved)?;
+    lua.globals().set("restored", restored)?;
+    lua.load(r#"assert(type(restored.on_load.__mlua_bytecode) == "string")"#)
+        .exec()?;
+
+    // C functions and functions with upvalues are rejected
+    let print = lua.globals().get::<_, Value>("print")?;
+    let closure = lua
+        .load("local n = 0; return function() n = n + 1 end")
+        .eval::<Value>()?;
+    for value in [print, closure] {
+        match lua.from_value_with::<serde_value::Value>(value, de_options) {
+            Err(Error::FromLuaConversionError {
+                from: "function", ..
+            }) => {}
+            r => panic!("expected FromLuaConversionError, got {r:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_mirror() -> Result<(), Box<dyn StdError>> {
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Player {
+        name: String,
+        health: u32,
+        pos: (f64, f64),
+        tags: Vec<String>,
+    }
+
+    let lua = Lua::new();
+    let player = Mirror::new(Player {
+        name: "alice".into(),
+        health: 100,
+        pos: (0.0, 0.0),
+        tags: vec![],
+    });
+    let changes = player.subscribe();
+    lua.globals().set("player", player.clone())?;
+
+    // Reads return current values
+    player.lock().health = 90;
+    assert_eq!(lua.load("player.health").eval::<u32>()?, 90);
+    assert_eq!(lua.load("player.name").eval::<String>()?, "alice");
+
+    lua.load(
+        r#"
+        player.health = player.health - 15
+        player.pos = {1.5, 2}
+        player.tags = {"admin"}
+        player.pos[1] = 100 -- nested values are copies
+    "#,
+    )
+    .exec()?;
+    let expected = Player {
+        name: "alice".into(),
+        health: 75,
+        pos: (1.5, 2.0),
+        tags: vec!["admin".into()],
+    };
+    assert_eq!(player.get(), expected);
+    assert_eq!(
+        changes.try_iter().collect::<Vec<_>>(),
+        ["health", "pos", "tags"]
+    );
+
+    // Invalid writes are rejected
+    for chunk in ["player.health = -1", "player.name = {}", "player.level = 1"] {
+        assert!(lua.load(chunk).exec().is_err(), "{chunk}");
+    }
+    assert_eq!(player.get(), expected);
+    assert!(changes.try_recv().is_err());
+
+    // Dropped subscribers are removed
+    drop(changes);
+    lua.load("player.health = 1").exec()?;
+    assert_eq!(player.lock().health, 1);
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::collections::HashSet;
 
-use mlua::{Lua, Result, String};
+use mlua::{Lua, LuaOptions, Result, StdLib, String, Utf8Policy};
 
 #[test]
 fn test_string_compare() {
@@ -54,7 +54,7 @@ fn test_string_views() -> Result<()> {
 
     assert_eq!(empty.to_str()?, "");
     assert_eq!(empty.as_bytes_with_nul(), &[0]);
-    assert_eq!(empty.as_bytes(), &[]);
+    assert_eq!(empty.as_bytes(), &[] as &[u8]);
 
     Ok(())
 }
@@ -63,7 +63,7 @@ fn test_string_views() -> Result<()> {
 fn test_raw_string() -> Result<()> {
     let lua = Lua::new();
 
-    let rs = lua.create_string(&[0, 1, 2, 3, 0, 1, 2, 3])?;
+    let rs = lua.create_string([0, 1, 2, 3, 0, 1, 2, 3])?;
     assert_eq!(rs.as_bytes(), &[0, 1, 2, 3, 0, 1, 2, 3]);
 
     Ok(())
@@ -98,3 +98,148 @@ fn test_string_debug() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_string_patterns() -> Result<()> {
+    let lua = Lua::new();
+
+    let s = lua.create_string("hello world")?;
+    assert_eq!(s.sub(..)?, "hello world");
+    assert_eq!(s.sub(6..)?, "world");
+    assert_eq!(s.sub(2..=3)?, "ll");
+    assert_eq!(s.sub(20..)?, "");
+    let (start, end) = (5, 2);
+    assert_eq!(s.sub(start..end)?, "");
+
+    assert_eq!(s.find("o")?, Some(4..5));
+    assert_eq!(s.find("w%a+")?, Some(6..11));
+    assert_eq!(s.find("")?, Some(0..0));
+    assert_eq!(s.find("xyz")?, None);
+
+    let words = s.gmatch::<String>("%a+")?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(words, vec!["hello", "world"]);
+    let positions = (s.gmatch::<i64>("()o")?).collect::<Result<Vec<_>>>()?;
+    assert_eq!(positions, vec![5, 8]);
+    assert_eq!(s.gmatch::<String>("%d")?.count(), 0);
+
+    assert!(s.find("%").is_err());
+    let no_strlib = Lua::new_with(StdLib::NONE, LuaOptions::default())?;
+    let s = no_strlib.create_string("abc")?;
+    assert!(s.find("b").is_err());
+    assert_eq!(s.sub(1..)?, "bc");
+
+    Ok(())
+}
+
+#[test]
+fn test_string_chars() -> Result<()> {
+    let lua = Lua::new();
+
+    let s = lua.create_string("añ€😀")?;
+    let chars = s.chars(Utf8Policy::Strict).collect::<Result<Vec<_>>>()?;
+    assert_eq!(chars, vec!['a', 'ñ', '€', '😀']);
+    assert_eq!(s.char_len(Utf8Policy::Strict)?, 4);
+
+    let s = lua.create_string(b"a\xF0\x9F\xFFb\xC3")?;
+    let chars = s.chars(Utf8Policy::Replace).collect::<Result<Vec<_>>>()?;
+    assert_eq!(chars, vec!['a', '\u{FFFD}', '\u{FFFD}', 'b', '\u{FFFD}']);
+    assert_eq!(
+        chars.into_iter().collect::<std::string::String>(),
+        s.to_string_lossy()
+    );
+    let chars = s.chars(Utf8Policy::Skip).collect::<Result<Vec<_>>>()?;
+    assert_eq!(chars, vec!['a', 'b']);
+    assert_eq!(s.char_len(Utf8Policy::Skip)?, 2);
+
+    let mut chars = s.chars(Utf8Policy::Strict);
+    assert_eq!(chars.next().unwrap()?, 'a');
+    let err = chars.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("at byte 1"), "{err}");
+    assert!(chars.next().is_none());
+    assert!(s.char_len(Utf8Policy::Strict).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_utf8_compat() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("utf8x", lua.utf8_compat()?)?;
+    assert_eq!(lua.utf8_compat()?, lua.globals().get("utf8x")?);
+
+    lua.load(
+        r##"
+        assert(utf8x.char(72, 0xF1, 0x20AC, 0x1F600) == "Hñ€😀")
+        assert(utf8x.char() == "")
+        assert(utf8x.char(0x7FFFFFFF) == "\253\191\191\191\191\191")
+
+        local s = "añ€😀"
+        assert(utf8x.len(s) == 4)
+        assert(utf8x.len(s, 2) == 3)
+        assert(utf8x.len(s, -4) == 1)
+        assert(utf8x.len("") == 0)
+        local n, pos = utf8x.len("ab\255c")
+        assert(n == nil and pos == 3)
+
+        local cps = {utf8x.codepoint(s, 1, -1)}
+        assert(#cps == 4 and cps[2] == 0xF1 and cps[4] == 0x1F600)
+        assert(utf8x.codepoint(s) == 97)
+        assert(select("#", utf8x.codepoint(s, 3, 2)) == 0)
+
+        assert(utf8x.offset(s, 3) == 4)
+        assert(utf8x.offset(s, -1) == 7)
+        assert(utf8x.offset(s, 0, 3) == 2)
+        assert(utf8x.offset(s, 10) == nil)
+
+        local out = {}
+        for p, c in utf8x.codes(s) do
+            table.insert(out, p .. ":" .. c)
+        end
+        assert(table.concat(out, " ") == "1:97 2:241 4:8364 7:128512")
+
+        local chars = {}
+        for c in s:gmatch(utf8x.charpattern) do
+            table.insert(chars, c)
+        end
+        assert(#chars == 4 and chars[3] == "€")
+
+        -- Lax mode accepts surrogates
+        local surrogate = "\237\160\128"
+        assert(utf8x.len(surrogate) == nil)
+        assert(utf8x.len(surrogate, 1, -1, true) == 1)
+        assert(utf8x.codepoint(surrogate, 1, 1, true) == 0xD800)
+    "##,
+    )
+    .exec()?;
+
+    // Results are the same as of the standard library
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    for expr in [
+        "len('añ€😀', 3)",
+        "len('\\xC3\\xA9\\x80')",
+        "codepoint('añ€😀', 2, 7)",
+        "offset('añ€😀', -2, 7)",
+        "offset('añ€😀', 2, 2)",
+        "char(0x10FFFF, 0x7FF)",
+    ] {
+        let expected: Vec<mlua::Value> = lua.load(format!("{{utf8.{expr}}}")).eval()?;
+        let actual: Vec<mlua::Value> = lua.load(format!("{{utf8x.{expr}}}")).eval()?;
+        assert_eq!(actual, expected, "{expr}");
+    }
+
+    let err = lua.load("utf8x.codepoint('\\255')").exec().unwrap_err();
+    assert!(err.to_string().contains("invalid UTF-8 code"), "{err}");
+    let err = lua.load("utf8x.len('abc', 5)").exec().unwrap_err();
+    assert!(
+        err.to_string().contains("initial position out of bounds"),
+        "{err}"
+    );
+    let err = lua
+        .load("for _ in utf8x.codes('a\\255') do end")
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid UTF-8 code"), "{err}");
+    assert!(lua.load("utf8x.char(-1)").exec().is_err());
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use std::sync::{Arc, Mutex};
+
 use mlua::{Error, Lua, Nil, Result, Table, TableExt, Value};
 
 #[test]
@@ -68,7 +70,7 @@ fn test_table() -> Result<()> {
     );
     assert_eq!(
         table2.sequence_values().collect::<Result<Vec<i64>>>()?,
-        vec![]
+        Vec::<i64>::new()
     );
 
     // sequence_values should only iterate until the first border
@@ -152,6 +154,64 @@ fn test_table_push_pop() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_table_sequence_ops() -> Result<()> {
+    let lua = Lua::new();
+
+    let table = lua.create_sequence_from(vec![1, 2, 3, 4])?;
+    table.raw_insert(2, 10)?;
+    table.raw_remove(4)?;
+    assert_eq!(table.raw_swap_remove::<i64>(1)?, 1);
+    assert_eq!(
+        table
+            .clone()
+            .raw_sequence_values::<i64>()
+            .collect::<Result<Vec<_>>>()?,
+        vec![4, 10, 2]
+    );
+    assert_eq!(table.raw_swap_remove::<i64>(3)?, 2);
+    assert_eq!(table.raw_len(), 2);
+    assert!(table.raw_swap_remove::<i64>(3).is_err());
+    assert!(table.raw_swap_remove::<i64>(0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_table_sort_by() -> Result<()> {
+    let lua = Lua::new();
+
+    let table = lua.create_sequence_from(vec![3, 1, 2, 5, 4])?;
+    table.sort_by(|a, b| match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(b.cmp(a)),
+        _ => unreachable!(),
+    })?;
+    assert_eq!(
+        table
+            .clone()
+            .raw_sequence_values::<i64>()
+            .collect::<Result<Vec<_>>>()?,
+        vec![5, 4, 3, 2, 1]
+    );
+
+    // Inconsistent comparator does not fail
+    table.sort_by(|_, _| Ok(std::cmp::Ordering::Less))?;
+    assert_eq!(table.raw_len(), 5);
+
+    // Error aborts sorting and leaves the table unchanged
+    let table = lua.create_sequence_from(vec![3, 1, 2])?;
+    let result = table.sort_by(|_, _| Err(Error::RuntimeError("cmp error".to_string())));
+    assert!(matches!(result, Err(Error::RuntimeError(msg)) if msg == "cmp error"));
+    assert_eq!(
+        table
+            .raw_sequence_values::<i64>()
+            .collect::<Result<Vec<_>>>()?,
+        vec![3, 1, 2]
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_table_clear() -> Result<()> {
     let lua = Lua::new();
@@ -232,6 +292,35 @@ fn test_table_sequence_from() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_table_is_empty() -> Result<()> {
+    let lua = Lua::new();
+
+    let t = lua.create_table()?;
+    assert!(t.is_empty());
+    assert_eq!(t.len_hint(), 0);
+
+    t.set("a", 1)?;
+    assert!(!t.is_empty());
+    assert_eq!(t.len_hint(), 0);
+    t.set("a", Nil)?;
+    assert!(t.is_empty());
+
+    let seq = lua.create_sequence_from([1, 2, 3])?;
+    assert!(!seq.is_empty());
+    assert_eq!(seq.len_hint(), 3);
+
+    // Metamethods are not invoked
+    let t = lua
+        .load("setmetatable({}, {__len = function() error('len') end, __pairs = error})")
+        .eval::<Table>()?;
+    assert!(t.is_empty());
+    assert_eq!(t.len_hint(), 0);
+    assert!(t.len().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_table_scope() -> Result<()> {
     let lua = Lua::new();
@@ -282,6 +371,45 @@ fn test_metatable() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_table_raw_access() -> Result<()> {
+    let lua = Lua::new();
+
+    let table: Table = lua
+        .load(
+            r#"
+        called = false
+        local inner = setmetatable({}, {
+            __index = function() called = true; return 1 end,
+        })
+        return setmetatable({a = 1, b = 2, inner = inner, nested = {x = {y = 5}}}, {
+            __index = function() called = true; return 1 end,
+            __pairs = function() called = true; return next, {}, nil end,
+        })
+    "#,
+        )
+        .eval()?;
+
+    let mut keys = table
+        .clone()
+        .raw_pairs::<String, Value>()
+        .map(|pair| pair.map(|(k, _)| k))
+        .collect::<Result<Vec<_>>>()?;
+    keys.sort();
+    assert_eq!(keys, vec!["a", "b", "inner", "nested"]);
+
+    assert!(table.raw_contains_key("a")?);
+    assert!(!table.raw_contains_key("missing")?);
+    assert_eq!(table.raw_get_path::<_, i32>(["nested", "x", "y"])?, 5);
+    assert_eq!(table.raw_get_path::<_, Value>(["inner", "missing"])?, Nil);
+    assert_eq!(table.raw_get_path::<_, Value>(["missing", "x"])?, Nil);
+    assert!(table.raw_get_path::<_, Value>(["a", "x"]).is_err());
+
+    assert!(!lua.globals().get::<_, bool>("called")?);
+
+    Ok(())
+}
+
 #[test]
 fn test_table_eq() -> Result<()> {
     let lua = Lua::new();
@@ -392,3 +520,57 @@ fn test_table_call() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_table_alias_deprecate() -> Result<()> {
+    let lua = Lua::new();
+
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let warnings2 = warnings.clone();
+    lua.set_warning_function(move |_, msg, _| {
+        warnings2
+            .lock()
+            .unwrap()
+            .push(msg.to_string_lossy().into_owned());
+        Ok(())
+    });
+
+    let api = lua.create_table()?;
+    api.set(
+        "add",
+        lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?,
+    )?;
+    api.set(
+        "old_sub",
+        lua.create_function(|_, (a, b): (i64, i64)| Ok(a - b))?,
+    )?;
+    api.alias("sum", "add")?;
+    api.deprecate("old_sub", "1.2", "")?;
+    lua.globals().set("api", api.clone())?;
+
+    lua.load(
+        r#"
+        assert(api.sum(1, 2) == 3 and api.sum(2, 3) == 5)
+        assert(api.old_sub(5, 3) == 2 and api.old_sub(1, 1) == 0)
+        api.add = function(a, b) return a * b end
+        assert(api.sum(2, 3) == 6)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        [
+            "'sum' is deprecated, use 'add' instead",
+            "'old_sub' is deprecated since 1.2",
+        ]
+    );
+
+    match api.deprecate("missing", "1.0", "") {
+        Err(Error::RuntimeError(msg)) => {
+            assert_eq!(msg, "'missing' is not a function (a nil value)")
+        }
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
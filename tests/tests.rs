@@ -1,14 +1,18 @@
 use std::collections::HashMap;
 use std::iter::FromIterator;
+use std::os::raw::c_int;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::string::String as StdString;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{error, f32, f64, fmt};
 
 use mlua::{
-    ChunkMode, Error, ExternalError, Function, Lua, LuaOptions, Nil, Result, StdLib, String, Table,
-    UserData, Value, Variadic,
+    AtLeast, Backend, Capability, ChunkMode, Error, ErrorContext, ErrorPropagation, ExternalError,
+    FromLuaMulti, Function, GlobalAccess, GlobalPolicy, Lua, LuaOptions, MetricsKind, Multi, Nil,
+    PanicPolicy, ReplOutput, ReplState, Result, StateOwnership, StdLib, StdLibFilter, String,
+    Table, UserData, Value, Variadic,
 };
 
 #[cfg(not(feature = "luau"))]
@@ -65,6 +69,203 @@ fn test_safety() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_std_lib_unload_and_filter() -> Result<()> {
+    let lua = Lua::new();
+
+    lua.unload_std_lib(StdLib::OS | StdLib::MATH)?;
+    assert_eq!(lua.load("return os").eval::<Value>()?, Nil);
+    assert_eq!(lua.load("return math").eval::<Value>()?, Nil);
+
+    lua.load_from_std_lib(StdLib::OS)?;
+    lua.filter_std_lib(
+        &StdLibFilter::new()
+            .allow("os.time")
+            .deny("string.rep")
+            .deny("print"),
+    )?;
+    let (time, clock, rep, upper, print) = lua
+        .load("return os.time, os.clock, string.rep, string.upper, print")
+        .eval::<(Value, Value, Value, Value, Value)>()?;
+    assert!(matches!(time, Value::Function(_)));
+    assert_eq!(clock, Nil);
+    assert_eq!(rep, Nil);
+    assert!(matches!(upper, Value::Function(_)));
+    assert_eq!(print, Nil);
+
+    // Only library functions can be allowed
+    let err = lua
+        .filter_std_lib(&StdLibFilter::new().allow("print").deny("os.time"))
+        .unwrap_err();
+    assert!(err.to_string().contains("cannot allow 'print'"));
+    assert!(lua
+        .load("return os.time")
+        .eval::<Option<Function>>()?
+        .is_some());
+
+    // Reloading the library restores removed functions
+    lua.unload_std_lib(StdLib::OS)?;
+    lua.load_from_std_lib(StdLib::OS)?;
+    assert!(lua
+        .load("return os.clock")
+        .eval::<Option<Function>>()?
+        .is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic_options() -> Result<()> {
+    let new_lua = || Lua::new_with(StdLib::ALL_SAFE, LuaOptions::new().deterministic(42));
+    let script = "local t = {} for i = 1, 10 do t[i] = math.random(1, 100) end return t";
+
+    let seq1 = new_lua()?.load(script).eval::<Vec<i64>>()?;
+    let seq2 = new_lua()?.load(script).eval::<Vec<i64>>()?;
+    assert_eq!(seq1, seq2);
+    assert!(seq1.iter().all(|&x| (1..=100).contains(&x)));
+
+    let lua = new_lua()?;
+    let x = lua
+        .load("math.randomseed(7) return math.random()")
+        .eval::<f64>()?;
+    let y = lua
+        .load("math.randomseed(7) return math.random()")
+        .eval::<f64>()?;
+    assert_eq!(x, y);
+    assert!((0.0..1.0).contains(&x));
+    assert!(lua.load("math.random(2, 1)").exec().is_err());
+
+    let lua = Lua::new_with(StdLib::ALL_SAFE, LuaOptions::new().stable_pairs(true))?;
+    let keys = lua
+        .load(
+            r#"
+            local t = {c = 1, [2] = 1, a = 1, [true] = 1, b = 1, [1.5] = 1}
+            local keys = {}
+            for k in pairs(t) do keys[#keys + 1] = tostring(k) end
+            return table.concat(keys, ",")
+        "#,
+        )
+        .eval::<StdString>()?;
+    assert_eq!(keys, "true,1.5,2,a,b,c");
+
+    // Keys inserted after the first iteration are traversed in the insertion order
+    let (keys, mt) = lua
+        .load(
+            r#"
+            local t, a, b = {}, {}, function() end
+            local names = {[a] = "a", [b] = "b"}
+            for _ in pairs(t) do end
+            t.z = 1; t[b] = 2; t[1] = 3; t[a] = 4; t.y = 5
+            t.z = nil; t.z = 6
+            local keys = {}
+            for k in pairs(t) do keys[#keys + 1] = names[k] or tostring(k) end
+            return table.concat(keys, ","), getmetatable(t)
+        "#,
+        )
+        .eval::<(StdString, Value)>()?;
+    assert_eq!(keys, "b,1,a,y,z");
+    assert_eq!(mt, Value::Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_override_functions() -> Result<()> {
+    let lua = Lua::new();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let print: Function = lua.globals().get("print")?;
+
+    let output2 = output.clone();
+    lua.override_global_fn("print", move |_, s: StdString| {
+        output2.lock().unwrap().push(s);
+        Ok(())
+    })?;
+    lua.override_global_fn("type", |lua, value: Value| match value {
+        Value::UserData(_) => Ok("object".to_string()),
+        value => lua.original("type")?.unwrap().call(value),
+    })?;
+    lua.override_table_fn("os", "time", |_, ()| Ok("time"))?;
+    lua.override_table_fn("string", "missing", |_, ()| Ok(1))?;
+
+    lua.load(r#"print("hello")"#).exec()?;
+    assert_eq!(*output.lock().unwrap(), vec!["hello".to_string()]);
+    lua.globals().set("ud", lua.create_any_userdata(0)?)?;
+    assert_eq!(lua.load("type(ud)").eval::<StdString>()?, "object");
+    assert_eq!(lua.load("type(1)").eval::<StdString>()?, "number");
+    assert_eq!(lua.load("os.time()").eval::<StdString>()?, "time");
+    assert_eq!(lua.load("string.missing()").eval::<i64>()?, 1);
+
+    // The first original value is kept
+    lua.override_global_fn("print", |_, ()| Ok(()))?;
+    assert_eq!(lua.original("print")?, Some(print));
+    let time = lua.original("os.time")?.unwrap();
+    assert!(lua.original("string.missing")?.is_none());
+    assert!(lua.original("pairs")?.is_none());
+
+    // Restore originals
+    assert!(lua.restore_original("type")?);
+    assert_eq!(lua.load("type(ud)").eval::<StdString>()?, "userdata");
+    assert!(lua.restore_original("string.missing")?);
+    assert_eq!(lua.load("string.missing").eval::<Value>()?, Value::Nil);
+    assert!(!lua.restore_original("type")?);
+    assert!(!lua.restore_original("pairs")?);
+    assert!(lua.original("type")?.is_none());
+    assert!(lua.restore_original("os.time")?);
+    assert_eq!(lua.load("os.time").eval::<Function>()?, time);
+
+    match lua.override_table_fn("print", "x", |_, ()| Ok(())) {
+        Err(Error::RuntimeError(msg)) => {
+            assert_eq!(msg, "global 'print' is not a table (a function value)")
+        }
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_time_source() -> Result<()> {
+    let lua = Lua::new();
+
+    let now = Arc::new(AtomicU64::new(1_000_000));
+    let now2 = now.clone();
+    lua.set_time_source(move || UNIX_EPOCH + Duration::from_secs(now2.load(Ordering::Relaxed)))?;
+    lua.set_clock_source(|| Duration::from_millis(250))?;
+
+    assert_eq!(lua.load("os.time()").eval::<i64>()?, 1_000_000);
+    now.fetch_add(86400, Ordering::Relaxed);
+    assert_eq!(lua.load("os.time()").eval::<i64>()?, 1_086_400);
+    assert_eq!(
+        lua.load(r#"os.date("!%Y-%m-%d %H:%M:%S")"#)
+            .eval::<StdString>()?,
+        "1970-01-13 13:46:40"
+    );
+    assert_eq!(lua.load(r#"os.date("!*t").day"#).eval::<i64>()?, 13);
+    assert_eq!(lua.load("os.clock()").eval::<f64>()?, 0.25);
+
+    // Explicit arguments are handled by the original functions
+    assert_eq!(
+        lua.load(r#"os.date("!%Y-%m-%d", 0)"#).eval::<StdString>()?,
+        "1970-01-01"
+    );
+    let time = lua
+        .load("os.time({year = 2000, month = 1, day = 1, hour = 12})")
+        .eval::<i64>()?;
+    assert!((946_684_800 - 86400..946_684_800 + 86400).contains(&time));
+
+    // Setting the source again replaces it
+    lua.set_time_source(|| UNIX_EPOCH - Duration::from_millis(1500))?;
+    assert_eq!(lua.load("os.time()").eval::<i64>()?, -2);
+
+    // Does nothing without the `os` library
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::default())?;
+    lua.set_time_source(SystemTime::now)?;
+    lua.set_clock_source(|| Duration::ZERO)?;
+    assert_eq!(lua.globals().get::<_, Value>("os")?, Value::Nil);
+
+    Ok(())
+}
+
 #[test]
 fn test_load() -> Result<()> {
     let lua = Lua::new();
@@ -365,6 +566,310 @@ fn test_error() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_syntax_error_location() -> Result<()> {
+    let lua = Lua::new();
+
+    let err = (lua.load("local x = 1\nlocal y = 2 +)").set_name("=test"))
+        .exec()
+        .unwrap_err();
+    let info = err.syntax_error_info().expect("expected SyntaxError");
+    assert_eq!(info.chunk_name.as_deref(), Some("test"));
+    assert_eq!(info.line, Some(2));
+    assert_eq!(info.near.as_deref(), Some(")"));
+    assert!(!info.incomplete_input);
+
+    // Context is transparent
+    let err = err.context("while loading");
+    assert_eq!(err.syntax_error_info(), Some(info));
+
+    #[cfg(not(feature = "luau"))]
+    {
+        let err = lua.load("x = 1\nif x then").exec().unwrap_err();
+        let info = err.syntax_error_info().expect("expected SyntaxError");
+        assert!(info.chunk_name.unwrap().starts_with("[string \""));
+        assert_eq!(info.line, Some(2));
+        assert_eq!(info.near.as_deref(), Some("<eof>"));
+        assert!(info.incomplete_input);
+    }
+
+    assert_eq!(Error::RuntimeError("x".into()).syntax_error_info(), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_repl_eval() -> Result<()> {
+    let lua = Lua::new();
+    let mut state = ReplState::new();
+
+    assert!(matches!(
+        lua.repl_eval("t = setmetatable({}, {", &mut state)?,
+        ReplOutput::Incomplete
+    ));
+    assert!(state.is_continuation());
+    assert!(matches!(
+        lua.repl_eval("__tostring = function() return 'T' end", &mut state)?,
+        ReplOutput::Incomplete
+    ));
+    assert!(matches!(
+        lua.repl_eval("})", &mut state)?,
+        ReplOutput::Complete { .. }
+    ));
+    match lua.repl_eval("return t, 1 + 1", &mut state)? {
+        ReplOutput::Complete { values, output } => {
+            assert_eq!(values.len(), 2);
+            assert_eq!(output, "T\t2");
+        }
+        r => panic!("expected complete output, got {r:?}"),
+    }
+    assert!(!state.is_continuation());
+
+    // `_` is bound to the last value
+    match lua.repl_eval("getmetatable(_) ~= nil", &mut state)? {
+        ReplOutput::Complete { output, .. } => assert_eq!(output, "true"),
+        r => panic!("expected complete output, got {r:?}"),
+    }
+
+    // Errors reset the input
+    assert!(lua.repl_eval("error('boom')", &mut state).is_err());
+    assert!(lua.repl_eval("x = = 1", &mut state).is_err());
+    assert!(!state.is_continuation());
+
+    Ok(())
+}
+
+#[test]
+fn test_globals_interceptor() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("existing", 1)?;
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let log2 = log.clone();
+    lua.set_globals_interceptor(move |_, access, name| {
+        log2.lock().unwrap().push((access, name.to_string()));
+        match (access, name) {
+            (GlobalAccess::Read, "secret") => Ok(GlobalPolicy::Deny),
+            (GlobalAccess::Write, "readonly") => Ok(GlobalPolicy::Deny),
+            (GlobalAccess::Read, "lazy") => Ok(GlobalPolicy::Replace(Value::Integer(42))),
+            _ => Ok(GlobalPolicy::Allow),
+        }
+    })?;
+
+    lua.load("x = existing + lazy").exec()?;
+    assert_eq!(lua.globals().raw_get::<_, Value>("x")?, Nil);
+    assert_eq!(lua.globals().get::<_, i64>("x")?, 43);
+    assert!(lua.load("return secret").exec().is_err());
+    assert!(lua.load("readonly = 1").exec().is_err());
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            (GlobalAccess::Read, "existing".to_string()),
+            (GlobalAccess::Read, "lazy".to_string()),
+            (GlobalAccess::Write, "x".to_string()),
+            (GlobalAccess::Read, "x".to_string()),
+            (GlobalAccess::Read, "secret".to_string()),
+            (GlobalAccess::Write, "readonly".to_string()),
+        ]
+    );
+
+    // Standard library is still accessible
+    assert_eq!(
+        lua.load("return string.rep('a', 3)").eval::<StdString>()?,
+        "aaa"
+    );
+
+    lua.remove_globals_interceptor();
+    assert_eq!(lua.load("return secret").eval::<Value>()?, Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_globals_interceptor_with_features() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_globals_interceptor(|_, _, _| Ok(GlobalPolicy::Allow))?;
+
+    // Features managing global variables see them behind the interceptor
+    lua.set_time_source(|| UNIX_EPOCH + Duration::from_secs(100))?;
+    let filter = StdLibFilter::new().deny("string.rep").allow("os.time");
+    lua.filter_std_lib(&filter)?;
+    lua.override_global_fn("tostring", |_, _: Value| Ok("overridden"))?;
+    let (rep, clock, time, s): (bool, bool, i64, StdString) = lua
+        .load("return string.rep ~= nil, os.clock ~= nil, os.time(), tostring(1)")
+        .eval()?;
+    assert_eq!(
+        (rep, clock, time, s.as_str()),
+        (false, false, 100, "overridden")
+    );
+    assert!(lua
+        .filter_std_lib(&StdLibFilter::new().allow("print"))
+        .is_err());
+    assert!(lua.original("tostring")?.is_some());
+
+    lua.unload_std_lib(StdLib::MATH)?;
+    assert_eq!(lua.load("return math").eval::<Value>()?, Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_global_path() -> Result<()> {
+    let lua = Lua::new();
+
+    lua.set_global_path("app.config.width", 800)?;
+    lua.set_global_path("app.config.height", 600)?;
+    lua.set_global_path("version", "1.0")?;
+    assert_eq!(
+        lua.load("return app.config.width * app.config.height .. version")
+            .eval::<StdString>()?,
+        "4800001.0"
+    );
+    assert_eq!(lua.get_global_path::<i64>("app.config.width")?, 800);
+    assert_eq!(lua.get_global_path::<Option<i64>>("app.menu.size")?, None);
+    assert_eq!(lua.get_global_path::<StdString>("version")?, "1.0");
+
+    // Replacing an intermediate table invalidates the cache
+    lua.set_global_path("app.config", lua.create_table_from([("width", 1024)])?)?;
+    assert_eq!(lua.get_global_path::<i64>("app.config.width")?, 1024);
+    assert_eq!(
+        lua.get_global_path::<Option<i64>>("app.config.height")?,
+        None
+    );
+
+    // Tables replaced by scripts are picked up
+    lua.load("app.config = { width = 640 }").exec()?;
+    assert_eq!(lua.get_global_path::<i64>("app.config.width")?, 640);
+    lua.load("app = { config = { width = 320 } }").exec()?;
+    assert_eq!(lua.get_global_path::<i64>("app.config.width")?, 320);
+    lua.set_global_path("app.config.height", 240)?;
+    assert_eq!(lua.load("return app.config.height").eval::<i64>()?, 240);
+
+    // Errors
+    let err = lua.set_global_path("version.major", 1).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("global 'version' is not a table (a string value)"));
+    assert!(lua.get_global_path::<Value>("version.major.minor").is_err());
+    assert!(lua.get_global_path::<Value>("app..config").is_err());
+    assert!(lua.set_global_path("", 1).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_expression() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("x", 100)?;
+
+    let env = lua.create_table()?;
+    for (x, y) in [(1, 2), (3, 4)] {
+        env.set("x", x)?;
+        env.set("y", y)?;
+        assert_eq!(lua.eval::<i64>("x + y", Some(&env))?, x + y);
+    }
+    assert_eq!(lua.eval::<i64>("x", None)?, 100);
+    assert_eq!(lua.eval::<(i64, i64)>("y, x", Some(&env))?, (4, 3));
+    assert_eq!(lua.eval::<Option<i64>>("z", Some(&env))?, None);
+
+    // Expressions are compiled once
+    assert!(lua.eval::<bool>("1 + 1 == 2", None)?);
+    lua.clear_expression_cache()?;
+    assert!(lua.eval::<bool>("1 + 1 == 2", None)?);
+    for i in 0..1000 {
+        assert_eq!(lua.eval::<i64>(&format!("x + {i}"), None)?, 100 + i);
+    }
+    let cache: Table = lua.named_registry_value("__mlua_expression_cache")?;
+    assert!(cache.pairs::<Value, Value>().count() <= 256);
+
+    // Nested evaluation of the same expression does not change the outer environment
+    let inner = lua.create_table()?;
+    inner.set("x", 10)?;
+    inner.set("f", lua.create_function(|_, ()| Ok(0))?)?;
+    let outer = lua.create_table()?;
+    outer.set("x", 1)?;
+    outer.set(
+        "f",
+        lua.create_function(move |lua, ()| lua.eval::<i64>("f() + x", Some(&inner)))?,
+    )?;
+    assert_eq!(lua.eval::<i64>("f() + x", Some(&outer))?, 11);
+
+    assert!(matches!(
+        lua.eval::<()>("local x = 1", None),
+        Err(Error::SyntaxError { .. })
+    ));
+    assert!(matches!(
+        lua.eval::<()>("x + nil", None),
+        Err(Error::RuntimeError(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_render_template() -> Result<()> {
+    let lua = Lua::new();
+    let ctx = lua.create_table()?;
+    ctx.set("user", lua.create_table_from([("name", "Alice")])?)?;
+    ctx.set("items", vec![1, 2, 3])?;
+    ctx.set("price", 1.5)?;
+
+    assert_eq!(lua.render("Hi ${user.name}!", &ctx)?, "Hi Alice!");
+    assert_eq!(lua.render("${#items} items", &ctx)?, "3 items");
+    assert_eq!(lua.render("${price}${missing}", &ctx)?, "1.5nil");
+    assert_eq!(lua.render("${ ({a = '}'}).a }", &ctx)?, "}");
+    assert_eq!(
+        lua.render("$${user.name} \"\\\n", &ctx)?,
+        "${user.name} \"\\\n"
+    );
+    assert_eq!(lua.render("no interpolation", &ctx)?, "no interpolation");
+
+    // Context values are read on every render
+    ctx.set("user", lua.create_table_from([("name", "<Bob>")])?)?;
+    let escape = |s: &str| s.replace('<', "&lt;").replace('>', "&gt;");
+    assert_eq!(
+        lua.render_with("<p>${user.name}</p>", &ctx, escape)?,
+        "<p>&lt;Bob&gt;</p>"
+    );
+    lua.clear_template_cache()?;
+
+    assert!(lua.render("${user.name", &ctx).is_err());
+    assert!(matches!(
+        lua.render("${)}", &ctx),
+        Err(Error::SyntaxError { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_panic_policy() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_panic_policy(PanicPolicy::ConvertToError);
+
+    let panicking = lua.create_function(|_, ()| -> Result<()> { panic!("rust panic") })?;
+    lua.globals().set("panicking", panicking)?;
+
+    // Panic can be caught by Lua code as a regular error
+    let msg = lua
+        .load("local ok, err = pcall(panicking); assert(not ok); return tostring(err)")
+        .eval::<StdString>()?;
+    assert!(msg.contains("panic in callback: rust panic"));
+
+    // Original payload is available from the returned error
+    let err = lua.load("panicking()").exec().unwrap_err();
+    let payload = err.take_panic_payload().expect("panic payload");
+    assert_eq!(*payload.downcast::<&str>().unwrap(), "rust panic");
+    assert!(err.take_panic_payload().is_none());
+
+    // Default policy resumes the panic
+    lua.set_panic_policy(PanicPolicy::Propagate);
+    let result = catch_unwind(AssertUnwindSafe(|| lua.load("panicking()").exec()));
+    assert!(result.is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_panic() -> Result<()> {
     fn make_lua(options: LuaOptions) -> Result<Lua> {
@@ -592,6 +1097,37 @@ fn test_num_conversion() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_number_string_conversion() -> Result<()> {
+    let lua = Lua::new();
+
+    // Same as `tostring` in Lua
+    let tostring: Function = lua.globals().get("tostring")?;
+    for n in [0.0, 1.0, -1.5, 0.1, 1e100, 1.0 / 3.0, f64::INFINITY] {
+        assert_eq!(lua.number_to_string(n)?, tostring.call::<_, StdString>(n)?);
+    }
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    assert_eq!(lua.number_to_string(1.0)?, "1.0");
+    #[cfg(not(any(feature = "lua54", feature = "lua53")))]
+    assert_eq!(lua.number_to_string(1.0)?, "1");
+
+    // Same as `tonumber` in Lua
+    let tonumber: Function = lua.globals().get("tonumber")?;
+    for s in [
+        "10", " 10 ", "0x10", "1.5", "1e2", "0x1p4", "abc", "", "1 2", "10\0",
+    ] {
+        let expected = tonumber.call::<_, Value>(lua.create_string(s)?)?;
+        let expected = Some(expected).filter(|v| *v != Nil);
+        assert_eq!(lua.string_to_number(s)?, expected, "{s:?}");
+    }
+    assert_eq!(lua.string_to_number("0x10")?, Some(Value::Integer(16)));
+    assert_eq!(lua.string_to_number("1.5")?, Some(Value::Number(1.5)));
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    assert_eq!(lua.string_to_number("1e2")?, Some(Value::Number(100.0)));
+
+    Ok(())
+}
+
 #[test]
 fn test_pcall_xpcall() -> Result<()> {
     let lua = Lua::new();
@@ -1004,6 +1540,119 @@ fn test_ref_stack_exhaustion() {
     }
 }
 
+#[test]
+fn test_with_arena() -> Result<()> {
+    // The `Arc` is only held to count live userdata
+    struct MyUserdata(#[allow(dead_code)] Arc<()>);
+
+    impl UserData for MyUserdata {}
+
+    let lua = Lua::new();
+    let mut kept = Vec::new();
+    for i in 0..10 {
+        let table = lua.with_arena(|lua| {
+            let tables = (0..1000)
+                .map(|_| lua.create_table())
+                .collect::<Result<Vec<_>>>()?;
+            // Drop the handles in the middle of the arena
+            drop(tables);
+            let table = lua.create_table()?;
+            table.set("i", i)?;
+            Ok(table)
+        })?;
+        kept.push(table);
+    }
+    // Handles returned from the arena stay valid
+    for (i, table) in kept.iter().enumerate() {
+        assert_eq!(table.get::<_, usize>("i")?, i);
+    }
+
+    // Registry values of dropped keys are removed
+    let rc = Arc::new(());
+    lua.with_arena(|lua| {
+        let key = lua.create_registry_value(MyUserdata(rc.clone()))?;
+        assert_eq!(Arc::strong_count(&rc), 2);
+        drop(key);
+        Ok(())
+    })?;
+    lua.gc_collect()?;
+    assert_eq!(Arc::strong_count(&rc), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_ref_thread_stats() -> Result<()> {
+    let lua = Lua::new();
+    let stats = lua.ref_thread_stats();
+    assert!(stats.used <= stats.capacity);
+
+    let mut tables = (0..100)
+        .map(|_| lua.create_table())
+        .collect::<Result<Vec<_>>>()?;
+    let last = tables.pop().unwrap();
+    drop(tables);
+    let stats2 = lua.ref_thread_stats();
+    assert_eq!(stats2.live, stats.live + 1);
+    assert!(stats2.holes >= 99);
+    assert_eq!(stats2.live + stats2.holes, stats2.used);
+    assert!(stats2.high_water_mark >= stats2.used);
+
+    // The live handle at the top is relocated to the lowest hole
+    last.set("key", "value")?;
+    assert!(lua.compact_refs() >= 99);
+    let stats3 = lua.ref_thread_stats();
+    assert_eq!(stats3.live, stats2.live);
+    assert_eq!(stats3.holes, 0);
+    assert!(stats3.used < stats2.used);
+    assert!(stats3.capacity < stats2.capacity);
+    assert_eq!(stats3.high_water_mark, stats2.high_water_mark);
+    assert_eq!(last.get::<_, String>("key")?, "value");
+    let last2 = last.clone();
+    drop(last);
+    assert_eq!(last2.get::<_, String>("key")?, "value");
+    drop(last2);
+    assert_eq!(lua.ref_thread_stats().live, stats.live);
+
+    // The stack grows again after compaction
+    let tables = (0..100)
+        .map(|_| lua.create_table())
+        .collect::<Result<Vec<_>>>()?;
+    assert!(lua.ref_thread_stats().capacity >= lua.ref_thread_stats().used);
+    drop(tables);
+    lua.compact_refs();
+    assert_eq!(lua.ref_thread_stats().used, stats.live);
+
+    // Compaction inside a callback keeps the handles used by the call
+    let f = lua.create_function(|lua, fail: bool| {
+        let _tables = (0..10)
+            .map(|_| lua.create_table())
+            .collect::<Result<Vec<_>>>()?;
+        lua.compact_refs();
+        match fail {
+            true => Err(Error::RuntimeError("compacted".into())),
+            false => Ok(()),
+        }
+    })?;
+    for i in 0..6 {
+        assert_eq!(f.call::<_, ()>(i % 2 == 0).is_err(), i % 2 == 0);
+    }
+    drop(f);
+
+    // Handles are not leaked by calls and iteration
+    let f: Function = lua.load("function(t) return t, #t end").eval()?;
+    let t = lua.create_sequence_from([1, 2, 3])?;
+    let live = lua.ref_thread_stats().live;
+    for _ in 0..10 {
+        let (t2, n): (Table, usize) = f.call(t.clone())?;
+        assert_eq!(n, 3);
+        assert_eq!(t2.pairs::<Value, Value>().count(), 3);
+    }
+    assert_eq!(lua.ref_thread_stats().live, live);
+
+    Ok(())
+}
+
 #[test]
 fn test_large_args() -> Result<()> {
     let lua = Lua::new();
@@ -1055,6 +1704,68 @@ fn test_large_args_ref() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_variadic_arity() -> Result<()> {
+    let lua = Lua::new();
+
+    let f = lua.create_function(|_, (sep, vals): (StdString, AtLeast<2, i64>)| {
+        let vals = vals.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        Ok(vals.join(&sep))
+    })?;
+    assert_eq!(f.call::<_, StdString>(("-", 1, 2))?, "1-2");
+    assert_eq!(f.call::<_, StdString>(("-", 1, 2, 3, 4))?, "1-2-3-4");
+    assert_eq!(
+        AtLeast::<1, i64>::from_lua_multi(lua.pack_multi((1, 2))?, &lua)?.len(),
+        2
+    );
+    assert!(AtLeast::<3, i64>::from_lua_multi(lua.pack_multi((1, 2))?, &lua).is_err());
+
+    match f.call::<_, StdString>(("-", 1)) {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { pos, cause, .. } => {
+                assert_eq!(*pos, 3);
+                assert!(cause.to_string().contains("at least 2 values, got 1"));
+            }
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    let sum = lua.create_function(|_, vals: Variadic<i64>| Ok(vals.iter().sum::<i64>()))?;
+    assert_eq!(sum.call::<_, i64>((1, 2, 3))?, 6);
+    match sum.call::<_, i64>((1, 2, "x")) {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { pos, .. } => assert_eq!(*pos, 3),
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_multi_returns() -> Result<()> {
+    let lua = Lua::new();
+
+    let f = lua.create_function(|_, n: usize| {
+        let mut ret = Multi::new().with(n).with(None::<bool>);
+        for i in 0..n {
+            ret.push(Variadic::from_iter(0..i));
+        }
+        ret.push(());
+        Ok(ret.with("end"))
+    })?;
+    assert_eq!(f.call::<_, (usize, Value, StdString)>(0)?.2, "end");
+    let values = f.call::<_, Variadic<Value>>(3)?;
+    assert_eq!(values.len(), 6);
+    assert_eq!(values[1], Value::Nil);
+    assert_eq!(values[4], Value::Integer(1));
+    assert_eq!(Multi::new().with(1).with(Variadic::<i32>::new()).len(), 2);
+
+    Ok(())
+}
+
 #[test]
 fn test_chunk_env() -> Result<()> {
     let lua = Lua::new();
@@ -1245,7 +1956,6 @@ fn test_multi_states() -> Result<()> {
 }
 
 #[test]
-#[cfg(feature = "lua54")]
 fn test_warnings() -> Result<()> {
     let lua = Lua::new();
     lua.set_app_data::<Vec<(StdString, bool)>>(Vec::new());
@@ -1308,6 +2018,247 @@ fn test_luajit_cdata() {
         .eval();
 }
 
+#[test]
+#[cfg(feature = "luajit")]
+fn test_luajit_control() -> Result<()> {
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::default())?;
+    let jit = lua.jit();
+
+    jit.off()?;
+    assert!(!jit.status()?.0);
+    jit.on()?;
+    let (enabled, flags) = jit.status()?;
+    assert!(enabled);
+    assert!(flags.iter().any(|f| f == "fold"));
+    jit.flush()?;
+
+    jit.set_opt(&["-fold", "hotloop=10"])?;
+    assert!(!jit.status()?.1.iter().any(|f| f == "fold"));
+    assert!(jit.set_opt(&["unknown"]).is_err());
+
+    let f: Function = lua.load("return function() return 1 end").eval()?;
+    jit.off_function(&f, true)?;
+    jit.flush_function(&f, false)?;
+    jit.on_function(&f, false)?;
+    let c_func = lua.create_function(|_, ()| Ok(()))?;
+    assert!(jit.off_function(&c_func, false).is_err());
+
+    // The `jit` module is not exposed to scripts
+    assert_eq!(lua.globals().get::<_, Value>("jit")?, Value::Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_raw_state() -> Result<()> {
+    extern "C-unwind" {
+        fn lua_pushboolean(state: *mut mlua::lua_State, b: c_int);
+        fn lua_gettop(state: *mut mlua::lua_State) -> c_int;
+        fn lua_error(state: *mut mlua::lua_State) -> c_int;
+    }
+
+    let lua = Lua::new();
+    let t = lua.create_table()?;
+
+    // Arguments are left on the stack and returned too
+    let (t2, n, n2, b): (Table, c_int, c_int, bool) = unsafe {
+        lua.with_raw_state((t.clone(), 123), |state| {
+            let n: c_int = lua.stack_value(state, -1).unwrap();
+            lua.push_stack_value(state, n + lua_gettop(state)).unwrap();
+            lua_pushboolean(state, 1);
+        })?
+    };
+    assert_eq!(t2, t);
+    assert_eq!((n, n2), (123, 125));
+    assert!(b);
+
+    // Only the requested number of results is returned (-1 is `LUA_MULTRET`)
+    let results = unsafe {
+        lua.exec_raw_multi((1, 2), |state| {
+            lua_pushboolean(state, 0);
+            2
+        })?
+    };
+    assert_eq!(lua.unpack_multi::<(i32, bool)>(results)?, (2, false));
+    let results = unsafe { lua.exec_raw_multi((1, 2), |_| -1)? };
+    assert_eq!(results.len(), 2);
+    let res = unsafe { lua.exec_raw_multi((), |_| 1) };
+    assert!(matches!(res, Err(Error::RuntimeError(_))));
+
+    // Lua errors are returned
+    let res = unsafe {
+        lua.with_raw_state::<()>("error message", |state| {
+            lua_error(state);
+        })
+    };
+    match res {
+        Err(Error::RuntimeError(msg)) => assert!(msg.contains("error message")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Rust panics are propagated (LuaJIT cannot catch them in raw C API code)
+    #[cfg(not(feature = "luajit"))]
+    {
+        let res = catch_unwind(AssertUnwindSafe(|| unsafe {
+            lua.with_raw_state::<()>((), |_| panic!("test panic"))
+        }));
+        assert!(res.is_err());
+    }
+    assert_eq!(lua.load("1 + 1").eval::<i32>()?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_ffi_util() -> Result<()> {
+    use mlua::ffi_util::{self, StackGuard};
+
+    extern "C-unwind" {
+        fn lua_gettop(state: *mut mlua::lua_State) -> c_int;
+        fn lua_pushinteger(state: *mut mlua::lua_State, n: mlua::Integer);
+    }
+
+    unsafe extern "C-unwind" fn add_one(state: *mut mlua::lua_State) -> c_int {
+        ffi_util::callback_error(state, |nargs| {
+            if nargs != 1 {
+                return Err(Error::RuntimeError("expected 1 argument".into()));
+            }
+            lua_pushinteger(state, 1);
+            Ok(1)
+        })
+    }
+
+    let lua = Lua::new();
+    let add_one = unsafe { lua.create_c_function(add_one)? };
+    assert_eq!(add_one.call::<_, i64>(0)?, 1);
+    match add_one.call::<_, ()>(()) {
+        Err(Error::CallbackError { cause, .. }) => {
+            assert!(cause.to_string().contains("expected 1 argument"))
+        }
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    unsafe {
+        lua.with_raw_state::<()>((), |state| {
+            let top = lua_gettop(state);
+            {
+                let _sg = StackGuard::new(state);
+                ffi_util::check_stack(state, 2).unwrap();
+                lua_pushinteger(state, 1);
+                lua_pushinteger(state, 2);
+                let n = ffi_util::protect_lua_closure(state, 2, 0, |state| lua_gettop(state));
+                assert_eq!(n.unwrap(), 2);
+            }
+            assert_eq!(lua_gettop(state), top);
+        })?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_state_ownership() -> Result<()> {
+    let closed = Arc::new(AtomicU32::new(0));
+
+    let lua = Lua::new();
+    lua.globals().set("value", 123)?;
+    let f = lua.create_function(|_, ()| Ok(()))?;
+    lua.globals().set("rust_func", f)?;
+    let closed2 = closed.clone();
+    lua.set_close_callback(move |_| {
+        closed2.fetch_add(1, Ordering::Relaxed);
+    });
+    let state = lua.detach();
+    assert_eq!(closed.load(Ordering::Relaxed), 1);
+
+    // The state is still alive and can be attached again
+    let lua = unsafe { Lua::from_existing_state(state, StateOwnership::Borrowed) };
+    assert_eq!(lua.globals().get::<_, i32>("value")?, 123);
+    match lua.load("rust_func()").exec() {
+        Err(Error::CallbackError { cause, .. }) => match cause.as_ref() {
+            Error::CallbackDestructed => {}
+            err => panic!("expected CallbackDestructed, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+    lua.globals().set("value", 456)?;
+    drop(lua);
+
+    // Take the ownership back, the state is closed on drop
+    let lua = unsafe { Lua::from_existing_state(state, StateOwnership::Owned) };
+    assert_eq!(lua.globals().get::<_, i32>("value")?, 456);
+    let closed2 = closed.clone();
+    lua.set_close_callback(move |_| {
+        closed2.fetch_add(1, Ordering::Relaxed);
+    });
+    drop(lua);
+    assert_eq!(closed.load(Ordering::Relaxed), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_metrics() -> Result<()> {
+    struct MyUserData;
+
+    impl UserData for MyUserData {
+        fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("method", |_, _, ()| Ok(()));
+        }
+    }
+
+    let lua = Lua::new();
+    assert!(lua.metrics().is_empty());
+
+    // Callbacks created before enabling metrics are not measured
+    let f0 = lua.create_function(|_, ()| Ok(()))?;
+    lua.enable_metrics(true);
+    let f = lua.create_function(|_, ()| {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        Ok(())
+    })?;
+    lua.globals().set("f0", f0)?;
+    lua.globals().set("f", f)?;
+    lua.globals().set("ud", MyUserData)?;
+
+    lua.load("f0(); f(); ud:method(); ud:method()")
+        .set_name("main")
+        .exec()?;
+    lua.load("f()").set_name("main").exec()?;
+    assert_eq!(lua.load("1 + 1").set_name("expr").eval::<i32>()?, 2);
+
+    let metrics = lua.metrics();
+    assert_eq!(metrics.len(), 4);
+    let find = |kind, name: &str| {
+        metrics
+            .iter()
+            .find(|m| m.kind == kind && m.name.contains(name))
+    };
+
+    let main = find(MetricsKind::Chunk, "main").unwrap();
+    assert_eq!(main.calls, 2);
+    assert!(main.total_time >= std::time::Duration::from_millis(10));
+    assert_eq!(find(MetricsKind::Chunk, "expr").unwrap().calls, 1);
+    let f_metrics = find(MetricsKind::Callback, "tests.rs").unwrap();
+    assert_eq!(f_metrics.calls, 2);
+    assert!(f_metrics.total_time >= std::time::Duration::from_millis(10));
+    assert_eq!(
+        find(MetricsKind::Callback, "MyUserData.method")
+            .unwrap()
+            .calls,
+        2
+    );
+    // Sorted by total time
+    assert_eq!(metrics[0].name, "main");
+
+    lua.reset_metrics();
+    assert!(lua.metrics().iter().all(|m| m.calls == 0));
+    lua.enable_metrics(false);
+    assert!(lua.metrics().is_empty());
+
+    Ok(())
+}
+
 #[test]
 #[cfg(feature = "send")]
 fn test_send() {
@@ -1318,3 +2269,125 @@ fn test_send() {
     .join()
     .unwrap();
 }
+
+#[test]
+fn test_error_propagation() -> Result<()> {
+    #[cfg(any(feature = "luajit", feature = "cxx-exceptions"))]
+    assert_eq!(Lua::error_propagation(), ErrorPropagation::Unwind);
+    #[cfg(not(any(feature = "luajit", feature = "cxx-exceptions")))]
+    assert_eq!(Lua::error_propagation(), ErrorPropagation::LongJmp);
+
+    let lua = Lua::new();
+    let fail = lua.create_function(|_, ()| Err::<(), _>("rust error".into_lua_err()))?;
+    let panic = lua.create_function(|_, ()| -> Result<()> { panic!("rust panic") })?;
+    lua.globals().set("fail", fail)?;
+    lua.globals().set("panic", panic)?;
+
+    // Rust error -> Lua error caught by `pcall` -> raised again to Rust
+    let res = lua
+        .load(
+            r#"
+            local ok, err = pcall(fail)
+            assert(not ok)
+            error(err)
+        "#,
+        )
+        .exec();
+    match res {
+        Err(Error::CallbackError { cause, .. }) => assert_eq!(cause.to_string(), "rust error"),
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    // Rust panic is carried through `pcall` and nested Rust -> Lua calls
+    const RETHROW: &str = "local _, err = pcall(...); error(err)";
+    let nested = lua.create_function(|lua, ()| {
+        let panic = lua.globals().get::<_, Function>("panic")?;
+        lua.load(RETHROW).call::<_, ()>(panic)
+    })?;
+    let res = catch_unwind(AssertUnwindSafe(|| lua.load(RETHROW).call::<_, ()>(nested)));
+    match res {
+        Err(p) => assert_eq!(*p.downcast::<&str>().unwrap(), "rust panic"),
+        Ok(r) => panic!("expected panic, got {r:?}"),
+    }
+
+    // The state is still usable
+    assert_eq!(lua.load("select('#', pcall(fail))").eval::<i32>()?, 2);
+
+    // Errors thrown by LuaJIT unwind Rust frames of C functions, running their destructors
+    #[cfg(feature = "luajit")]
+    {
+        use std::cell::Cell;
+
+        extern "C-unwind" {
+            fn lua_pushstring(state: *mut mlua::lua_State, s: *const std::os::raw::c_char);
+            fn lua_error(state: *mut mlua::lua_State) -> c_int;
+        }
+
+        thread_local! {
+            static UNWOUND: Cell<bool> = const { Cell::new(false) };
+        }
+
+        struct Guard;
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                UNWOUND.with(|u| u.set(true));
+            }
+        }
+
+        unsafe extern "C-unwind" fn throw(state: *mut mlua::lua_State) -> c_int {
+            let _guard = Guard;
+            lua_pushstring(state, c"thrown".as_ptr());
+            lua_error(state)
+        }
+
+        let throw = unsafe { lua.create_c_function(throw)? };
+        let (ok, msg): (bool, StdString) = lua.load("return pcall(...)").call(throw)?;
+        assert!(!ok);
+        assert_eq!(msg, "thrown");
+        assert!(UNWOUND.with(|u| u.get()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_backend_capabilities() -> Result<()> {
+    let lua = Lua::new();
+
+    let backend = Lua::backend();
+    #[cfg(feature = "lua54")]
+    assert_eq!(backend, Backend::Lua54);
+    #[cfg(feature = "lua53")]
+    assert_eq!(backend, Backend::Lua53);
+    #[cfg(feature = "lua52")]
+    assert_eq!(backend, Backend::Lua52);
+    #[cfg(feature = "lua51")]
+    assert_eq!(backend, Backend::Lua51);
+    #[cfg(feature = "luajit")]
+    assert_eq!(backend, Backend::LuaJIT);
+    #[cfg(feature = "luau")]
+    assert_eq!(backend, Backend::Luau);
+
+    // Capabilities match what Lua code can actually do
+    if lua.supports(Capability::BitwiseOperators) {
+        assert_eq!(lua.load("return 6 & 3").eval::<i64>()?, 2);
+    } else {
+        assert!(lua.load("return 6 & 3").exec().is_err());
+    }
+    if lua.supports(Capability::Integers) {
+        assert_eq!(lua.load("return math.type(1)").eval::<String>()?, "integer");
+    }
+    let tbc = "local x <close> = nil";
+    assert_eq!(
+        lua.supports(Capability::ToBeClosed),
+        lua.load(tbc).exec().is_ok()
+    );
+    // Lua 5.1 returns an error from `pcall` instead of yielding
+    let yield_in_pcall = lua
+        .load("return coroutine.wrap(function() return pcall(coroutine.yield, 1) end)")
+        .eval::<Function>()?;
+    let yielded = yield_in_pcall.call::<_, Value>(())? != Value::Boolean(false);
+    assert_eq!(lua.supports(Capability::YieldAcrossC), yielded);
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::panic::catch_unwind;
+use std::time::Duration;
 
 use mlua::{Error, Function, Lua, Result, Thread, ThreadStatus};
 
@@ -194,3 +195,55 @@ fn test_coroutine_panic() {
         Err(p) => assert!(*p.downcast::<&str>().unwrap() == "test_panic"),
     }
 }
+
+#[test]
+fn test_task_scheduler() -> Result<()> {
+    let lua = Lua::new();
+    assert!(lua.tick(Duration::ZERO).is_err());
+    lua.enable_task_scheduler()?;
+
+    lua.load(
+        r#"
+        log = {}
+        function push(...) log[#log + 1] = table.concat({...}, " ") end
+        task.delay(1, push, "delay", 1)
+        task.defer(push, "defer")
+        task.spawn(function(name)
+            push("spawn", name)
+            push("waited", task.wait(0.5))
+            coroutine.yield()
+            push("yielded")
+        end, "a")
+        local cancelled = task.delay(0.2, push, "cancelled")
+        task.cancel(cancelled)
+        task.spawn(function()
+            while true do
+                task.wait()
+                push("loop")
+            end
+        end)
+    "#,
+    )
+    .exec()?;
+
+    let log = || lua.globals().get::<_, Vec<String>>("log");
+    assert_eq!(log()?, ["spawn a"]);
+    // `defer` and the loop
+    assert_eq!(lua.tick(Duration::ZERO)?, 2);
+    assert_eq!(log()?, ["spawn a", "defer", "loop"]);
+    assert_eq!(lua.tick(Duration::from_millis(750))?, 2);
+    // Threads are resumed in order of resume time
+    assert_eq!(log()?[3..], ["loop", "waited 0.75"]);
+    assert_eq!(lua.tick(Duration::from_millis(250))?, 3);
+    assert_eq!(log()?[5..], ["loop", "yielded", "delay 1"]);
+
+    // Errors are propagated, other threads are resumed by the next tick
+    lua.load("task.defer(error, 'boom') task.defer(push, 'after')")
+        .exec()?;
+    let err = lua.tick(Duration::ZERO).unwrap_err();
+    assert!(err.to_string().contains("boom"), "{err}");
+    lua.tick(Duration::ZERO)?;
+    assert!(log()?.contains(&"after".to_string()));
+
+    Ok(())
+}
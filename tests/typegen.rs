@@ -0,0 +1,104 @@
+#![cfg(feature = "typegen")]
+
+use mlua::{AnyUserData, Lua, MetaMethod, Result, UserData, UserDataFields, UserDataMethods};
+
+struct Vec2(f64, f64);
+
+impl UserData for Vec2 {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("x", |_, this| Ok(this.0));
+        fields.add_field_method_set("x", |_, this, x| Ok(this.0 = x));
+        fields.add_field_method_get("y", |_, this| Ok(this.1));
+        fields.add_field("dimensions", 2);
+        fields.add_meta_field_with("__type", |_| Ok("Vector2"));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("length", |_, this, ()| Ok(this.0.hypot(this.1)));
+        methods.add_meta_method(MetaMethod::Add, |_, this, other: AnyUserData| {
+            let other = other.borrow::<Self>()?;
+            Ok(Vec2(this.0 + other.0, this.1 + other.1))
+        });
+        methods.add_meta_method(MetaMethod::Index, |_, _, ()| Ok(()));
+    }
+}
+
+struct Empty;
+
+impl UserData for Empty {}
+
+#[test]
+fn test_userdata_type_declarations() -> Result<()> {
+    let lua = Lua::new();
+    assert_eq!(lua.userdata_type_declarations(), "");
+
+    lua.create_userdata(Vec2(1., 2.))?;
+    lua.create_userdata(Empty)?;
+
+    assert_eq!(
+        lua.userdata_type_declarations(),
+        concat!(
+            "declare class Vector2\n",
+            "    x: any\n",
+            "    y: any\n",
+            "    dimensions: number\n",
+            "    function length(self, ...: any): ...any\n",
+            "    function __add(self, ...: any): ...any\n",
+            "end\n",
+            "\n",
+            "declare class Empty\n",
+            "end\n",
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_global_type_declaration() -> Result<()> {
+    let lua = Lua::new();
+
+    let module = lua
+        .load(
+            r#"
+            local m = {
+                name = "mod", enabled = true, list = {1, 2, 3}, mixed = {1, "a"},
+                ["not an identifier"] = 0, nested = { f = function() end },
+            }
+            m.self = m
+            return m
+        "#,
+        )
+        .eval::<mlua::Table>()?;
+    module.set("point", Vec2(0., 0.))?;
+
+    assert_eq!(
+        lua.global_type_declaration("mymod", module)?,
+        concat!(
+            "declare mymod: {\n",
+            "    enabled: boolean,\n",
+            "    list: {number},\n",
+            "    mixed: {any},\n",
+            "    name: string,\n",
+            "    nested: {\n",
+            "        f: (...any) -> ...any,\n",
+            "    },\n",
+            "    [\"not an identifier\"]: number,\n",
+            "    point: Vector2,\n",
+            "    self: any,\n",
+            "}\n",
+        )
+    );
+
+    let f = lua.create_function(|_, ()| Ok(()))?;
+    assert_eq!(
+        lua.global_type_declaration("hello", f)?,
+        "declare function hello(...: any): ...any\n"
+    );
+    assert_eq!(
+        lua.global_type_declaration("answer", 42)?,
+        "declare answer: number\n"
+    );
+
+    Ok(())
+}
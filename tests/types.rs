@@ -1,6 +1,6 @@
 use std::os::raw::c_void;
 
-use mlua::{Function, LightUserData, Lua, Result};
+use mlua::{Error, Function, LightUserData, Lua, Result, Tagged};
 
 #[test]
 fn test_lightuserdata() -> Result<()> {
@@ -24,3 +24,52 @@ fn test_lightuserdata() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_tagged_lightuserdata() -> Result<()> {
+    struct Foo;
+    struct Bar;
+
+    let lua = Lua::new();
+    let id = lua.load("function(a) return a end").eval::<Function>()?;
+
+    let foo = Tagged(16 as *mut Foo);
+    assert_eq!(id.call::<_, Tagged<Foo>>(foo)?, foo);
+    assert_eq!(id.call::<_, LightUserData>(foo)?.0, foo.0 as *mut c_void);
+    match id.call::<_, Tagged<Bar>>(foo) {
+        Err(Error::FromLuaConversionError { .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    // Tagged pointers are not retagged implicitly
+    match id.call::<_, ()>(Tagged(foo.0 as *mut Bar)) {
+        Err(Error::ToLuaConversionError { .. }) => {}
+        r => panic!("expected ToLuaConversionError, got {r:?}"),
+    }
+    assert!(!lua.untag_light_userdata(foo.0 as *mut Bar));
+    assert!(lua.untag_light_userdata(foo.0));
+    assert!(id
+        .call::<_, Tagged<Foo>>(LightUserData(foo.0 as *mut c_void))
+        .is_err());
+
+    // Pointers created outside of Rust
+    let ptr = 32 as *mut c_void;
+    assert!(id.call::<_, Tagged<Bar>>(LightUserData(ptr)).is_err());
+    lua.tag_light_userdata(ptr as *mut Bar)?;
+    assert_eq!(
+        id.call::<_, Tagged<Bar>>(LightUserData(ptr))?,
+        Tagged(ptr as *mut Bar)
+    );
+    // Reused memory is tagged explicitly
+    lua.tag_light_userdata(ptr as *mut Foo)?;
+    assert!(id.call::<_, Tagged<Bar>>(LightUserData(ptr)).is_err());
+    assert!(lua.untag_light_userdata(ptr as *mut Foo));
+    assert!(!lua.untag_light_userdata(ptr as *mut Foo));
+    assert!(id.call::<_, Tagged<Foo>>(LightUserData(ptr)).is_err());
+
+    // Number of tags is limited
+    let res = (1..=usize::MAX).try_for_each(|i| lua.tag_light_userdata((i * 8) as *mut Foo));
+    assert!(matches!(res, Err(Error::RuntimeError(_))));
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use std::ptr;
 use std::string::String as StdString;
 use std::sync::Arc;
 #[cfg(not(feature = "parking_lot"))]
@@ -9,12 +10,14 @@ use parking_lot::{Mutex, RwLock};
 #[cfg(not(feature = "send"))]
 use std::{cell::RefCell, rc::Rc};
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 #[cfg(feature = "lua54")]
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::atomic::AtomicI64;
 
 use mlua::{
     AnyUserData, AnyUserDataExt, Error, ExternalError, Function, Lua, MetaMethod, Nil, Result,
-    String, UserData, UserDataFields, UserDataMethods, UserDataRef, Value,
+    String, TypeRegistry, UserData, UserDataFields, UserDataMethods, UserDataRef, Value,
 };
 
 #[test]
@@ -209,6 +212,64 @@ fn test_metamethods() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_std_ops() -> Result<()> {
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    struct Vec2(f64, f64);
+
+    impl std::ops::Add<f64> for Vec2 {
+        type Output = Vec2;
+        fn add(self, rhs: f64) -> Vec2 {
+            Vec2(self.0 + rhs, self.1 + rhs)
+        }
+    }
+
+    impl std::ops::Sub<f64> for Vec2 {
+        type Output = Vec2;
+        fn sub(self, rhs: f64) -> Vec2 {
+            Vec2(self.0 - rhs, self.1 - rhs)
+        }
+    }
+
+    impl std::ops::Mul<f64> for Vec2 {
+        type Output = Vec2;
+        fn mul(self, rhs: f64) -> Vec2 {
+            Vec2(self.0 * rhs, self.1 * rhs)
+        }
+    }
+
+    impl std::ops::Div<f64> for Vec2 {
+        type Output = Vec2;
+        fn div(self, rhs: f64) -> Vec2 {
+            Vec2(self.0 / rhs, self.1 / rhs)
+        }
+    }
+
+    impl UserData for Vec2 {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_std_ops::<f64, Vec2>();
+            methods.add_std_cmp_ops();
+        }
+    }
+
+    let lua = Lua::new();
+    let globals = lua.globals();
+    globals.set("v", Vec2(1.0, 2.0))?;
+    globals.set("v2", Vec2(1.0, 2.0))?;
+
+    let r = lua
+        .load("(v + 1) * 4 / 2 - 3")
+        .eval::<UserDataRef<Vec2>>()?;
+    assert_eq!(*r, Vec2(1.0, 3.0));
+    assert!(lua
+        .load("v == v2 and v <= v2 and v < v + 1")
+        .eval::<bool>()?);
+    assert!(lua.load("v > v + 1").eval::<bool>().map(|r| !r)?);
+    assert!(lua.load("return 1 + v").exec().is_err());
+
+    Ok(())
+}
+
 #[test]
 #[cfg(feature = "lua54")]
 fn test_metamethod_close() -> Result<()> {
@@ -373,6 +434,52 @@ fn test_userdata_take() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_userdata_raw_ptr() -> Result<()> {
+    struct MyUserData(i64);
+
+    impl UserData for MyUserData {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("get", |_, this, ()| Ok(this.0));
+        }
+    }
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(MyUserData(1))?;
+    let ptr = ud.as_raw_ptr::<MyUserData>()?;
+    assert_eq!(ud.as_raw_ptr::<MyUserData>()?, ptr);
+    assert!(matches!(
+        ud.as_raw_ptr::<i64>(),
+        Err(Error::UserDataTypeMismatch)
+    ));
+
+    // The pointer stays valid when the userdata is moved around by Lua
+    lua.globals().set("ud", ud)?;
+    lua.load("local t = {ud}; ud = nil; ud = t[1]").exec()?;
+    lua.gc_collect()?;
+    unsafe { (*ptr).0 = 2 };
+    assert_eq!(lua.load("ud:get()").eval::<i64>()?, 2);
+
+    // Recover the userdata from the pointer
+    let ud = AnyUserData::from_raw_ptr(&lua, ptr)?.unwrap();
+    assert_eq!(ud, lua.globals().get::<_, AnyUserData>("ud")?);
+    assert!(AnyUserData::from_raw_ptr(&lua, ptr::dangling_mut::<MyUserData>())?.is_none());
+
+    // The pointer can be obtained while the userdata is borrowed
+    {
+        let _borrow = ud.borrow::<MyUserData>()?;
+        assert_eq!(ud.as_raw_ptr::<MyUserData>()?, ptr);
+    }
+
+    // Taken userdata is not recovered
+    assert!(AnyUserData::from_raw_ptr(&lua, ptr)?.is_some());
+    ud.take::<MyUserData>()?;
+    assert!(AnyUserData::from_raw_ptr(&lua, ptr)?.is_none());
+    assert!(ud.as_raw_ptr::<MyUserData>().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_userdata_destroy() -> Result<()> {
     struct MyUserdata(Arc<()>);
@@ -535,6 +642,175 @@ fn test_fields() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_static_fields_and_accessors() -> Result<()> {
+    struct MyUserData {
+        name: StdString,
+        val: i64,
+    }
+
+    impl UserData for MyUserData {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field("version", 3);
+            fields.add_field("val", "shadowed");
+            fields.add_field_from("name", |this| &this.name);
+            fields.add_field_from("val", |this| &this.val);
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method_mut("inc", |_, this, ()| {
+                this.val += 1;
+                Ok(())
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    let globals = lua.globals();
+    let ud = MyUserData {
+        name: "foo".into(),
+        val: 1,
+    };
+    globals.set("ud", ud)?;
+    #[cfg(not(feature = "send"))]
+    globals.set(
+        "rc_ud",
+        Rc::new(RefCell::new(MyUserData {
+            name: "bar".into(),
+            val: 10,
+        })),
+    )?;
+    lua.load(
+        r#"
+        assert(ud.version == 3)
+        assert(ud.name == "foo")
+        assert(ud.val == 1)
+        ud:inc()
+        assert(ud.val == 2)
+        if rc_ud ~= nil then
+            assert(rc_ud.name == "bar" and rc_ud.version == 3)
+        end
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_index_fallback() -> Result<()> {
+    struct MyUserData(i64);
+
+    impl UserData for MyUserData {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("val", |_, this| Ok(this.0));
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("get", |_, this, ()| Ok(this.0));
+            methods.add_meta_method(MetaMethod::Index, |_, this, key: StdString| {
+                // Called only for keys that are not registered methods or fields
+                match key.strip_prefix("times_") {
+                    Some(n) => Ok(n.parse::<i64>().ok().map(|n| this.0 * n)),
+                    None => Ok(None),
+                }
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("ud", MyUserData(5))?;
+    lua.load(
+        r#"
+        assert(ud.val == 5)
+        assert(ud:get() == 5)
+        assert(ud.times_3 == 15)
+        assert(ud.unknown == nil)
+        assert(ud.get ~= nil and ud.val == 5)
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_method_dispatch() -> Result<()> {
+    struct MyUserData(i64);
+
+    impl UserData for MyUserData {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field("kind", "counter");
+            fields.add_field_method_get("val", |_, this| Ok(this.0));
+            // Field getters have priority over methods
+            fields.add_field_method_get("shadowed", |lua, _| {
+                lua.create_function(|_, ()| Ok("field"))
+            });
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method_mut("add", |_, this, n: i64| {
+                this.0 += n;
+                Ok(this.0)
+            });
+            methods.add_method("shadowed", |_, _, ()| Ok("method"));
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("ud", MyUserData(1))?;
+    lua.load(
+        r#"
+        for i = 1, 10 do
+            assert(ud:add(i) == ud.val)
+        end
+        assert(ud.val == 56)
+        assert(ud:shadowed() == "field")
+        assert(ud.kind == "counter")
+        assert(not pcall(function() return ud:kind() end))
+        local ok, err = pcall(function() return ud:unknown() end)
+        assert(not ok and tostring(err):find("unknown"))
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[cfg(feature = "luau")]
+#[test]
+fn test_method_dispatch_namecall() -> Result<()> {
+    struct MyUserData(i64);
+
+    impl UserData for MyUserData {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("get", |lua, _| lua.create_function(|_, ()| Ok("field")));
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("get", |_, this, ()| Ok(this.0));
+            methods.add_method("val", |_, this, ()| Ok(this.0));
+            methods.add_meta_method(MetaMethod::Index, |_, _, key: StdString| Ok(key));
+            // Dispatch methods despite custom `__index` and field getters
+            methods.set_namecall("get", true);
+            methods.set_namecall("val", true);
+            methods.set_namecall("val", false);
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("ud", MyUserData(7))?;
+    lua.load(
+        r#"
+        assert(ud:get() == 7)
+        assert(ud.get() == "field")
+        assert(ud:val() == 7)
+        assert(ud.other == "other")
+    "#,
+    )
+    .exec()
+}
+
 #[test]
 fn test_metatable() -> Result<()> {
     #[derive(Copy, Clone)]
@@ -583,7 +859,11 @@ fn test_metatable() -> Result<()> {
         .map(|kv: Result<(_, Value)>| Ok(kv?.0))
         .collect::<Result<Vec<_>>>()?;
     methods.sort();
+    #[cfg(not(feature = "luau"))]
     assert_eq!(methods, vec!["__index", "__type_name"]);
+    // Luau dispatches method calls using `__namecall`
+    #[cfg(feature = "luau")]
+    assert_eq!(methods, vec!["__index", "__namecall", "__type_name"]);
 
     #[derive(Copy, Clone)]
     struct MyUserData2(i64);
@@ -745,6 +1025,81 @@ fn test_any_userdata() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_register_userdata_type() -> Result<()> {
+    let lua = Lua::new();
+
+    // Creating userdata of an unregistered type does not register it
+    lua.create_any_userdata(std::net::Ipv4Addr::UNSPECIFIED)?;
+    assert!(!lua.is_userdata_type_registered::<std::net::Ipv4Addr>());
+
+    lua.register_userdata_type::<std::net::Ipv4Addr>(|reg| {
+        reg.add_method("is_loopback", |_, this, ()| Ok(this.is_loopback()));
+    })?;
+    let ud1 = lua.create_any_userdata(std::net::Ipv4Addr::LOCALHOST)?;
+    assert!(lua.is_userdata_type_registered::<std::net::Ipv4Addr>());
+    assert!(!lua.is_userdata_type_registered::<std::net::Ipv6Addr>());
+
+    // Re-register the type, `ud1` keeps the original metatable
+    lua.register_userdata_type::<std::net::Ipv4Addr>(|reg| {
+        reg.add_field_method_get("first_octet", |_, this| Ok(this.octets()[0]));
+    })?;
+    let ud2 = lua.create_any_userdata(std::net::Ipv4Addr::new(10, 0, 0, 1))?;
+    assert!(lua.is_userdata_type_registered::<std::net::Ipv4Addr>());
+
+    lua.globals().set("ud1", ud1)?;
+    lua.globals().set("ud2", ud2.clone())?;
+    lua.load(
+        r#"
+        assert(ud1:is_loopback())
+        assert(ud2.first_octet == 10)
+        assert(not pcall(function() return ud2:is_loopback() end))
+    "#,
+    )
+    .exec()?;
+    assert_eq!(ud2.borrow::<std::net::Ipv4Addr>()?.octets()[3], 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_any_userdata_with() -> Result<()> {
+    let lua = Lua::new();
+
+    fn wrap(lua: &Lua, v: Vec<i32>) -> Result<AnyUserData> {
+        lua.create_any_userdata_with(v, |reg| {
+            reg.add_method("len", |_, this, ()| Ok(this.len()));
+            reg.add_method_mut("push", |_, this, v: i32| {
+                this.push(v);
+                Ok(())
+            });
+        })
+    }
+
+    let ud1 = wrap(&lua, vec![1, 2])?;
+    let ud2 = wrap(&lua, vec![])?;
+    assert!(!lua.is_userdata_type_registered::<Vec<i32>>());
+
+    lua.globals().set("ud1", ud1.clone())?;
+    lua.globals().set("ud2", ud2)?;
+    lua.load(
+        r#"
+        assert(ud1:len() == 2)
+        ud2:push(3)
+        assert(ud2:len() == 1)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(*ud1.borrow::<Vec<i32>>()?, vec![1, 2]);
+
+    // Metatable for `Vec<i32>` itself is not affected
+    let ud3 = lua.create_any_userdata(vec![1i32])?;
+    lua.globals().set("ud3", ud3)?;
+    assert!(lua.load("ud3:len()").exec().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_userdata_ext() -> Result<()> {
     let lua = Lua::new();
@@ -820,3 +1175,189 @@ fn test_userdata_method_errors() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_type_registry() -> Result<()> {
+    static REGISTRATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    struct MyUserData(i64);
+
+    impl UserData for MyUserData {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            REGISTRATIONS.fetch_add(1, Ordering::Relaxed);
+            methods.add_method("get", |_, this, ()| Ok(this.0));
+        }
+    }
+
+    let mut registry = TypeRegistry::new();
+    registry.register::<MyUserData>();
+    registry.register_type::<Vec<i32>>(|reg| {
+        reg.add_method("len", |_, this, ()| Ok(this.len()));
+    });
+    assert_eq!(registry.len(), 2);
+
+    for i in 1..=3 {
+        // Types are registered when the state is created
+        let lua = Lua::new_with_registry(&registry)?;
+        assert_eq!(REGISTRATIONS.load(Ordering::Relaxed), i);
+
+        lua.globals().set("ud", MyUserData(123))?;
+        lua.globals()
+            .set("v", lua.create_any_userdata(vec![1i32, 2])?)?;
+        lua.load("assert(ud:get() == 123 and v:len() == 2)")
+            .exec()?;
+        assert_eq!(REGISTRATIONS.load(Ordering::Relaxed), i);
+    }
+
+    // Registry can be applied to an existing state too
+    let lua = Lua::new();
+    registry.apply(&lua)?;
+    lua.globals()
+        .set("v", lua.create_any_userdata(vec![1i32])?)?;
+    lua.load("assert(v:len() == 1)").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_method_overloads() -> Result<()> {
+    struct Vec2(f64, f64);
+
+    impl UserData for Vec2 {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method_overload("scale", |_, this, (x, y): (f64, f64)| {
+                Ok(Vec2(this.0 * x, this.1 * y))
+            });
+            methods
+                .add_method_overload("scale", |_, this, k: f64| Ok(Vec2(this.0 * k, this.1 * k)));
+            methods.add_method_overload("scale", |_, this, v: UserDataRef<Vec2>| {
+                Ok(Vec2(this.0 * v.0, this.1 * v.1))
+            });
+            methods.add_method("x", |_, this, ()| Ok(this.0));
+            // Extra arguments are ignored, so overloads with more parameters go first
+            methods.add_function_overload("new", |_, (x, y): (f64, f64)| Ok(Vec2(x, y)));
+            methods.add_function_overload("new", |_, ()| Ok(Vec2(0.0, 0.0)));
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("v", Vec2(1.0, 2.0))?;
+    lua.load(
+        r#"
+        assert(v:scale(2, 3):x() == 2)
+        assert(v:scale(4):x() == 4)
+        assert(v:scale(v:scale(5)):x() == 5)
+        assert(v.new():x() == 0)
+        assert(v.new(7, 8):x() == 7)
+    "#,
+    )
+    .exec()?;
+
+    let err = lua.load("v:scale(true)").exec().unwrap_err().to_string();
+    assert!(
+        err.contains("no matching overload for 'Vec2.scale' with arguments (boolean)"),
+        "{err}"
+    );
+    assert!(
+        err.contains("Vec2.scale(f64, f64)\n  Vec2.scale(f64)\n"),
+        "{err}"
+    );
+    assert!(err.contains("Vec2.scale(UserDataRef<Vec2>)"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_field_change_hooks() -> Result<()> {
+    static CHANGES: std::sync::Mutex<Vec<StdString>> = std::sync::Mutex::new(Vec::new());
+
+    struct Node {
+        name: StdString,
+        visible: bool,
+    }
+
+    impl UserData for Node {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            // Hooks apply to setters added later too
+            fields.on_change(|_, ud, name, old, new| {
+                let node = ud.borrow::<Node>()?;
+                let change = format!("{}.{name}: {old:?} -> {new:?}", node.name);
+                CHANGES.lock().unwrap().push(change);
+                Ok(())
+            });
+            fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+            fields.add_field_method_set("name", |_, this, name| {
+                this.name = name;
+                Ok(())
+            });
+            // Write-only field
+            fields.add_field_method_set("visible", |_, this, visible| {
+                this.visible = visible;
+                Ok(())
+            });
+            fields.on_change(|_, _, name, _, new| match (name, new) {
+                ("visible", Value::Nil) => Err("visible cannot be nil".into_lua_err()),
+                _ => Ok(()),
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    let node = lua.create_userdata(Node {
+        name: "a".into(),
+        visible: false,
+    })?;
+    lua.globals().set("node", node.clone())?;
+    lua.load("node.name = 'b'; node.visible = true").exec()?;
+    assert!(node.borrow::<Node>()?.visible);
+    assert_eq!(
+        *CHANGES.lock().unwrap(),
+        [
+            r#"b.name: String("a") -> String("b")"#,
+            "b.visible: Nil -> Boolean(true)"
+        ]
+    );
+
+    let err = lua.load("node.visible = nil").exec().unwrap_err();
+    assert!(err.to_string().contains("visible cannot be nil"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_signal() -> Result<()> {
+    let lua = Lua::new();
+    let signal = lua.create_signal::<(i32, StdString)>()?;
+    lua.globals().set("signal", signal.clone())?;
+
+    lua.load(
+        r#"
+        log = {}
+        conn = signal:Connect(function(n, s)
+            table.insert(log, "connect " .. n .. " " .. s)
+        end)
+        signal:Once(function(n)
+            table.insert(log, "once " .. n)
+            -- Functions connected while firing are called the next time
+            signal:Connect(function(n) table.insert(log, "late " .. n) end)
+        end)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(signal.connection_count()?, 2);
+
+    signal.fire((1, "a".into()))?;
+    assert_eq!(signal.connection_count()?, 2);
+    lua.load("assert(conn.Connected); conn:Disconnect(); assert(not conn.Connected)")
+        .exec()?;
+    lua.load("signal:Fire(2, 'b')").exec()?;
+    let log: Vec<StdString> = lua.globals().get("log")?;
+    assert_eq!(log, ["connect 1 a", "once 1", "late 2"]);
+
+    signal.disconnect_all()?;
+    assert_eq!(signal.connection_count()?, 0);
+    signal.fire((3, "c".into()))?;
+    assert_eq!(lua.globals().get::<_, Vec<StdString>>("log")?.len(), 3);
+
+    Ok(())
+}
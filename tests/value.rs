@@ -81,3 +81,69 @@ fn test_multi_value() {
     multi_value.clear();
     assert!(multi_value.is_empty());
 }
+
+#[test]
+fn test_value_integer_div() -> Result<()> {
+    let div = |a: Value, b: Value| a.integer_div(&b);
+    assert_eq!(
+        div(Value::Integer(7), Value::Integer(2))?,
+        Value::Integer(3)
+    );
+    assert_eq!(
+        div(Value::Integer(-7), Value::Integer(2))?,
+        Value::Integer(-4)
+    );
+    assert_eq!(
+        div(Value::Integer(7), Value::Integer(-2))?,
+        Value::Integer(-4)
+    );
+    assert_eq!(
+        div(Value::Number(7.5), Value::Integer(2))?,
+        Value::Number(3.0)
+    );
+    assert_eq!(
+        div(Value::Number(-1.0), Value::Number(0.0))?,
+        Value::Number(f64::NEG_INFINITY)
+    );
+    assert!(div(Value::Integer(1), Value::Integer(0)).is_err());
+    assert!(div(Value::Boolean(true), Value::Integer(1)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_math_compat() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("mathx", lua.math_compat()?)?;
+
+    // Same results on all Lua versions
+    let check = |expr: &str, expected: i64| -> Result<()> {
+        let value = lua.load(expr).eval::<i64>()?;
+        assert_eq!(value, expected, "{expr}");
+        Ok(())
+    };
+    check("mathx.idiv(7, 2)", 3)?;
+    check("mathx.idiv(-7, 2)", -4)?;
+    check("mathx.band(0xF0, 0x3C)", 0x30)?;
+    check("mathx.band(0xFF, 0x3C, 0x0F)", 0x0C)?;
+    check("mathx.bor(0xF0, 0x0F)", 0xFF)?;
+    check("mathx.bxor(0xFF, 0x0F)", 0xF0)?;
+    check("mathx.bnot(0)", -1)?;
+    check("mathx.lshift(1, 10)", 1024)?;
+    check("mathx.lshift(1, 64)", 0)?;
+    check("mathx.rshift(1024, 3)", 128)?;
+    check("mathx.rshift(-1, 60)", 15)?;
+    check("mathx.lshift(1024, -3)", 128)?;
+    assert_eq!(lua.load("mathx.idiv(7.5, 2)").eval::<f64>()?, 3.0);
+
+    // The module is created once
+    let module = lua.math_compat()?;
+    assert_eq!(module, lua.globals().get("mathx")?);
+
+    let err = lua.load("mathx.band(1.5, 1)").exec().unwrap_err();
+    assert!(err.to_string().contains("no integer representation"));
+    let err = lua.load("mathx.idiv(1, 0)").exec().unwrap_err();
+    assert!(err.to_string().contains("'n//0'"));
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::io;
+
+use mlua::{Lua, MemoryVfs, Result, Vfs};
+
+#[test]
+fn test_vfs_io() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryVfs::new();
+    vfs.insert("data/numbers.txt", "10 20.5\n0x10\n");
+    vfs.insert("data/lines.txt", "one\ntwo\n\nthree");
+    lua.set_vfs(vfs.clone())?;
+
+    lua.load(
+        r#"
+        local f = assert(io.open("data/numbers.txt"))
+        assert(io.type(f) == "file")
+        local a, b, c = f:read("n", "*n", "n")
+        assert(a == 10 and b == 20.5 and c == 16)
+        assert(f:read("n") == nil)
+        assert(f:seek("set", 3) == 3)
+        assert(f:read(4) == "20.5")
+        assert(f:seek() == 7)
+        assert(f:seek("end") == 13)
+        assert(f:read("a") == "")
+        assert(f:read("l") == nil)
+        assert(f:read(0) == nil)
+        local ok, err = f:write("x")
+        assert(ok == nil and err == "file is not writable")
+        assert(f:close())
+        assert(io.type(f) == "closed file")
+        assert(tostring(f) == "file (closed)")
+        assert(io.type(42) == nil)
+
+        local lines = {}
+        for line in io.lines("data/lines.txt") do
+            table.insert(lines, line)
+        end
+        assert(table.concat(lines, ",") == "one,two,,three")
+        lines = {}
+        for line in io.lines("data/lines.txt", "L") do
+            table.insert(lines, line)
+        end
+        assert(table.concat(lines) == "one\ntwo\n\nthree")
+
+        f = assert(io.open("data/lines.txt", "rb"))
+        assert(f:read() == "one" and f:read("L") == "two\n")
+        for line in f:lines() do
+            assert(line == "" or line == "three")
+        end
+        f:close()
+
+        -- Writing
+        f = assert(io.open("out/log.txt", "w"))
+        assert(f:write("a", 1, " ", 2.5) == f)
+        f:write("\n")
+        assert(f:close())
+        f = assert(io.open("out/log.txt", "a+"))
+        f:write("end")
+        f:seek("set")
+        assert(f:read("a") == "a1 2.5\nend")
+        f:close()
+        f = assert(io.open("out/log.txt", "r+"))
+        f:write("b")
+        f:flush()
+        f:close()
+
+        local names = io.list("data")
+        assert(#names == 2 and names[1] == "lines.txt" and names[2] == "numbers.txt")
+        names = io.list(".")
+        assert(#names == 2 and names[1] == "data" and names[2] == "out")
+
+        -- Errors
+        local f, err = io.open("missing.txt")
+        assert(f == nil and err:find("missing.txt"))
+        assert(io.list("missing") == nil)
+        assert(io.stdout == nil and io.popen == nil)
+    "#,
+    )
+    .exec()?;
+
+    assert_eq!(vfs.get("out/log.txt").unwrap(), b"b1 2.5\nend");
+
+    let err = lua.load("io.lines('missing.txt')").exec().unwrap_err();
+    assert!(err.to_string().contains("missing.txt"), "{err}");
+    let err = lua
+        .load("io.open('data/lines.txt', 'rw')")
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid mode"), "{err}");
+    #[cfg(not(feature = "luau"))]
+    assert_eq!(
+        lua.load("require('io')").eval::<mlua::Table>()?,
+        lua.globals().get("io")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_vfs_require() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryVfs::new();
+    vfs.insert("lib/greet.lua", "return require('lib.name') .. '!'");
+    vfs.insert("lib/name/init.lua", "return 'hello'");
+    lua.set_vfs(vfs)?;
+
+    #[cfg(not(feature = "luau"))]
+    {
+        lua.load(r#"package.path = "?.lua;?/init.lua""#).exec()?;
+        let greet: String = lua.load("require('lib.greet')").eval()?;
+        assert_eq!(greet, "hello!");
+
+        let err = lua.load("require('missing')").exec().unwrap_err();
+        assert!(
+            err.to_string().contains("no file 'missing/init.lua'"),
+            "{err}"
+        );
+    }
+
+    #[cfg(feature = "luau")]
+    {
+        let name: String = lua.load("require('./lib/name')").set_name("@main").eval()?;
+        assert_eq!(name, "hello");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_vfs_read_only() -> Result<()> {
+    struct Assets;
+
+    impl Vfs for Assets {
+        fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+            match path {
+                "config.txt" => Ok(b"key=value".to_vec()),
+                _ => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    let lua = Lua::new();
+    lua.set_vfs(Assets)?;
+    lua.load(
+        r#"
+        local f = assert(io.open("config.txt"))
+        assert(f:read("a") == "key=value")
+        f:close()
+
+        local f, err = io.open("config.txt", "w")
+        assert(f == nil and err == "config.txt: read-only filesystem")
+        local names, err = io.list(".")
+        assert(names == nil and err:find("not supported"))
+    "#,
+    )
+    .exec()
+}
@@ -0,0 +1,91 @@
+#![cfg(all(feature = "async", target_family = "wasm"))]
+
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use futures::executor::block_on;
+
+use mlua::{Error, Lua, Result};
+
+// Future that is pending once before completing, like a browser event loop tick
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[test]
+fn test_wasm_async_function() -> Result<()> {
+    let lua = Lua::new();
+
+    let tick = lua.create_async_function(|_, n: i64| async move {
+        YieldOnce(false).await;
+        Ok(n + 1)
+    })?;
+    lua.globals().set("tick", tick)?;
+
+    let res: i64 = block_on(lua.load("return tick(tick(1))").eval_async())?;
+    assert_eq!(res, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_wasm_async_errors() -> Result<()> {
+    let lua = Lua::new();
+
+    let fail = lua.create_async_function(|_, ()| async move {
+        YieldOnce(false).await;
+        Err::<(), _>(Error::RuntimeError("failed".into()))
+    })?;
+    lua.globals().set("fail", fail)?;
+
+    // Errors must cross the yield point without relying on unwinding
+    let res: (bool, String) = block_on(
+        lua.load("local ok, err = pcall(fail); return ok, tostring(err)")
+            .eval_async(),
+    )?;
+    assert!(!res.0);
+    assert!(res.1.contains("failed"));
+
+    Ok(())
+}
+
+#[test]
+fn test_wasm_yield_async_hook() -> Result<()> {
+    let lua = Lua::new();
+
+    let tick = lua.create_async_function(|_, ()| async move {
+        YieldOnce(false).await;
+        Ok(())
+    })?;
+    lua.globals().set("tick", tick)?;
+
+    let yields = Rc::new(Cell::new(0));
+    let yields2 = yields.clone();
+    lua.set_yield_async_hook(move |_| {
+        yields2.set(yields2.get() + 1);
+        Ok(())
+    });
+
+    block_on(lua.load("tick(); tick(); tick()").exec_async())?;
+    assert_eq!(yields.get(), 3);
+
+    lua.remove_yield_async_hook();
+    block_on(lua.load("tick()").exec_async())?;
+    assert_eq!(yields.get(), 3);
+
+    Ok(())
+}
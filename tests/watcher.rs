@@ -0,0 +1,53 @@
+#![cfg(feature = "watch")]
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mlua::{Lua, Result, ScriptWatcher, Table};
+
+fn poll_until(watcher: &mut ScriptWatcher, mut done: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !done() && start.elapsed() < Duration::from_secs(5) {
+        watcher.poll();
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn test_script_watcher() -> Result<()> {
+    let lua = Lua::new();
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("module.lua");
+    fs::write(&path, "return { value = function() return 1 end }")?;
+
+    let reloads = Rc::new(RefCell::new(0));
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let (reloads2, errors2) = (reloads.clone(), errors.clone());
+    let mut watcher = ScriptWatcher::new(&lua, [&path])?
+        .on_reload(move |_, _| *reloads2.borrow_mut() += 1)
+        .on_error(move |_, err| errors2.borrow_mut().push(err.to_string()));
+
+    let module: Table = watcher.module(&path).unwrap();
+    lua.globals().set("module", module)?;
+    assert_eq!(lua.load("module.value()").eval::<i32>()?, 1);
+
+    fs::write(&path, "return { value = function() return 2 end }")?;
+    poll_until(&mut watcher, || *reloads.borrow() > 0);
+    assert_eq!(lua.load("module.value()").eval::<i32>()?, 2);
+
+    // Syntax errors are reported and the module keeps working
+    fs::write(&path, "return {")?;
+    let has_syntax_error = || errors.borrow().iter().any(|e| e.contains("syntax error"));
+    poll_until(&mut watcher, has_syntax_error);
+    assert!(has_syntax_error());
+    assert_eq!(lua.load("module.value()").eval::<i32>()?, 2);
+
+    // Missing scripts fail to load
+    assert!(ScriptWatcher::new(&lua, [temp_dir.path().join("missing.lua")]).is_err());
+
+    Ok(())
+}